@@ -1,345 +1,177 @@
-#import bevy_sprite::mesh2d_bindings
-
-@group(1) @binding(0)
-var t_in: texture_2d<f32>;
-@group(1) @binding(1)
-var s_in: sampler;
-
-// --- Particle type IDs ---
-const AIR: f32 = 0.0;
-const BEDROCK: f32 = 0.1;
-const SAND: f32 = 0.5;
-const WATER: f32 = 1.0;
-
-// CORRECTED: Renamed function from `get` to `get_cell`
-fn get_cell(uv: vec2<f32>) -> vec4<f32> {
-    return textureSample(t_in, s_in, uv);
-}
-
-@fragment
-fn fragment(
-    @builtin(position) frag_coord: vec4<f32>,
-    @location(0) uv: vec2<f32>
-) -> @location(0) vec4<f32> {
-    let tex_size = vec2<f32>(textureDimensions(t_in));
-    let one_px = 1.0 / tex_size;
-
-    let center = get_cell(uv);
-    let down = get_cell(uv + vec2(0.0, -one_px.y));
-    let up = get_cell(uv + vec2(0.0, one_px.y));
-    let left = get_cell(uv + vec2(-one_px.x, 0.0));
-    let right = get_cell(uv + vec2(one_px.x, 0.0));
-    let down_left = get_cell(uv + vec2(-one_px.x, -one_px.y));
-    let down_right = get_cell(uv + vec2(one_px.x, -one_px.y));
-
-    let my_id = center.r;
-    var new_state = center;
-
-    // --- Simulation Rules ---
-    if (my_id == AIR) {
-        if (up.r == SAND) { new_state = up; }
-        else if (up.r == WATER) { new_state = up; }
-        else if (left.r == WATER) { new_state = left; }
-        else if (right.r == WATER) { new_state = right; }
-    }
-
-    if (my_id == SAND) {
-        if (down.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (down.r == WATER) { new_state = down; }
-        else if (down_left.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (down_right.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-    }
-
-    if (my_id == WATER) {
-        if (down.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (down_left.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (down_right.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (left.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
-        else if (right.r == AIR) { new_state = vec4(AIR, 0.0, 0.0, 1.0); }
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+// The simulation runs on the CPU; this pass only turns the state of each cell into a color: its
+// material's from the palette, weathered, stained and glowing with its charge, or its scaled
+// temperature; or, for empty cells, the wall behind them if there is one.
+
+// The state of every cell, as integers: its particle id in the red channel, its scaled
+// temperature in the green one while temperatures are drawn and its charge otherwise, how
+// weathered it is in the blue one and how stained in the alpha one. It is only ever loaded texel
+// by texel, never sampled.
+@group(2) @binding(0)
+var t_in: texture_2d<u32>;
+// 0 draws materials, 1 draws temperatures.
+@group(2) @binding(2)
+var<uniform> view_mode: u32;
+// Each cell's neighbor mask in the red channel and whether it is autotiled in the green one, and
+// the atlas of 16x16 tiles the mask picks from; see autotile.rs.
+@group(2) @binding(3)
+var t_tiles: texture_2d<f32>;
+@group(2) @binding(4)
+var s_tiles: sampler;
+@group(2) @binding(5)
+var t_atlas: texture_2d<f32>;
+@group(2) @binding(6)
+var s_atlas: sampler;
+// 1 while solid materials are drawn autotiled.
+@group(2) @binding(7)
+var<uniform> autotile: u32;
+// 0 draws cells as sharp squares, 1 smooths their edges with xBR.
+@group(2) @binding(8)
+var<uniform> upscaler: u32;
+// How bright each cell is drawn, with its particle's shade and its material's texture worked in.
+@group(2) @binding(9)
+var t_shade: texture_2d<f32>;
+@group(2) @binding(10)
+var s_shade: sampler;
+// Every material's color, fresh in the top row and fully weathered in the bottom one, a column per
+// particle id; see `palette_texture` in main.rs.
+@group(2) @binding(11)
+var t_palette: texture_2d<f32>;
+// The wall behind every cell: its particle id, with `WALL_MORTAR` set where the mortar between its
+// bricks runs, or 0 for none; see backdrop.rs. Loaded texel by texel like the state.
+@group(2) @binding(12)
+var t_backdrop: texture_2d<u32>;
+
+const VIEW_THERMAL: u32 = 1u;
+const UPSCALE_XBR: u32 = 1u;
+// How soft the diagonals xBR draws are, in cells.
+const XBR_SOFTNESS: f32 = 0.1;
+
+// What soot and sediment darken stained cells towards, and how far at most.
+const STAIN_COLOR: vec3<f32> = vec3(0.06, 0.05, 0.04);
+const STAIN_OPACITY: f32 = 0.85;
+// What fully charged cells glow with, on top of their color; keep in sync with cpu_display.rs.
+const CHARGE_GLOW: vec3<f32> = vec3(0.25, 0.4, 0.8);
+const ATLAS_COLUMNS: f32 = 16.0;
+// How walls are drawn; keep in sync with backdrop.rs.
+const WALL_MORTAR: u32 = 128u;
+const WALL_DIM: f32 = 0.45;
+const MORTAR_DIM: f32 = 0.7;
+const AIR: u32 = 0u;
+// The shade that leaves a cell's color as it is; keep in sync with `NEUTRAL_SHADE`.
+const NEUTRAL_SHADE: f32 = 128.0 / 255.0;
+
+// What a material looks like fresh, faded towards what it looks like fully weathered.
+fn material_color(id: u32, weathered: f32) -> vec3<f32> {
+    if (id >= textureDimensions(t_palette).x) {
+        return vec3(0.0, 0.0, 0.0);
     }
-
-    // --- Coloring ---
-    if (new_state.r == SAND) {
-        return vec4(0.8, 0.7, 0.1, 1.0);
-    } else if (new_state.r == WATER) {
-        return vec4(0.1, 0.2, 0.9, 1.0);
-    } else if (new_state.r == BEDROCK) {
-        return vec4(0.3, 0.3, 0.3, 1.0);
-    } else {
-        return vec4(0.0, 0.0, 0.0, 1.0);
-    }
-}```
-
----
-
-### Corrected Rust File: `src/main.rs`
-
-```rust
-// --- IMPORTS ---
-use bevy::prelude::*;
-use bevy::image::ImageSampler;
-use bevy::render::camera::{RenderTarget, ImageRenderTarget, ViewportConversionError};
-// CORRECTED: Import TextureDescriptor and TextureUsages
-use bevy::render::render_resource::{
-    AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
-    TextureUsages,
-};
-use bevy::render::mesh::Mesh2d;
-use bevy::sprite::{Material2d, Material2dPlugin, MeshMaterial2d};
-use bevy::window::PrimaryWindow;
-
-// --- CONSTANTS ---
-const SIMULATION_WIDTH: u32 = 256;
-const SIMULATION_HEIGHT: u32 = 256;
-const BRUSH_SIZE: i32 = 5;
-
-// --- PARTICLE DEFINITION ---
-#[derive(Clone, Copy, PartialEq, Default, Debug)]
-enum Particle {
-    #[default]
-    Air,
-    Bedrock,
-    Sand,
-    Water,
+    let fresh = textureLoad(t_palette, vec2(id, 0u), 0).rgb;
+    return mix(fresh, textureLoad(t_palette, vec2(id, 1u), 0).rgb, weathered);
 }
 
-impl Particle {
-    fn get_color_id(&self) -> f32 {
-        match self {
-            Particle::Air => 0.0,
-            Particle::Bedrock => 0.1,
-            Particle::Sand => 0.5,
-            Particle::Water => 1.0,
-        }
-    }
-}
-
-// --- MAIN APP ---
-fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "Bevy Falling Sand (0.16 Final)".into(),
-                    resolution: (
-                        SIMULATION_WIDTH as f32 * 4.0,
-                        SIMULATION_HEIGHT as f32 * 4.0,
-                    )
-                        .into(),
-                    ..default()
-                }),
-                ..default()
-            }),
-            Material2dPlugin::<SimulationMaterial>::default(),
-        ))
-        .init_resource::<SelectedParticle>()
-        .add_systems(Startup, setup)
-        .add_systems(
-            Update,
-            (
-                paint_on_texture,
-                switch_particle_type,
-                ping_pong.after(paint_on_texture),
-            ),
-        )
-        .run();
+// The state of the cell at `texel`, counted in rows from the top; cells off the edge of the world
+// repeat the edge.
+fn cell_state(texel: vec2<i32>) -> vec4<u32> {
+    let size = vec2<i32>(textureDimensions(t_in));
+    return textureLoad(t_in, clamp(texel, vec2(0), size - 1), 0);
 }
 
-// --- COMPONENTS AND RESOURCES ---
-
-#[derive(Resource)]
-struct PingPong {
-    read: Handle<Image>,
-    write: Handle<Image>,
+// The stained, weathered color of the cell at `texel`, or of the wall behind it if it is empty.
+fn cell_color(texel: vec2<i32>) -> vec3<f32> {
+    let state = cell_state(texel);
+    let size = vec2<i32>(textureDimensions(t_backdrop));
+    let wall = textureLoad(t_backdrop, clamp(texel, vec2(0), size - 1), 0).r;
+    if (state.r == AIR && wall != 0u) {
+        let dim = select(WALL_DIM, WALL_DIM * MORTAR_DIM, (wall & WALL_MORTAR) != 0u);
+        return material_color(wall & ~WALL_MORTAR, 0.0) * dim;
+    }
+    let weathered = f32(state.b) / 255.0;
+    let stained = f32(state.a) / 255.0 * STAIN_OPACITY;
+    return mix(material_color(state.r, weathered), STAIN_COLOR, stained);
 }
 
-#[derive(Resource, Default)]
-struct SelectedParticle(Particle);
-
-#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
-struct SimulationMaterial {
-    #[texture(0)]
-    #[sampler(1)]
-    source_image: Handle<Image>,
+// How different two colors look, as xBR measures it: weighted towards brightness in YUV.
+fn color_distance(a: vec3<f32>, b: vec3<f32>) -> f32 {
+    let d = a - b;
+    let yuv = vec3(
+        dot(d, vec3(0.299, 0.587, 0.114)),
+        dot(d, vec3(-0.169, -0.331, 0.5)),
+        dot(d, vec3(0.5, -0.419, -0.081)),
+    );
+    return dot(abs(yuv), vec3(48.0, 7.0, 6.0));
 }
 
-impl Material2d for SimulationMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/falling_sand.wgsl".into()
+// xBR level 1 for the corner of the cell at `texel` nearest the fragment at `inside` (0..1
+// across the cell). `s` points at that corner. When the 5x5 neighborhood says an edge runs
+// diagonally past the corner, the part of the corner beyond the diagonal takes the color from the
+// other side of the edge.
+fn xbr(texel: vec2<i32>, inside: vec2<f32>, e: vec3<f32>) -> vec3<f32> {
+    let s = select(vec2(-1), vec2(1), inside >= vec2(0.5));
+    let b = cell_color(texel + vec2(0, -s.y));
+    let c = cell_color(texel + vec2(s.x, -s.y));
+    let d = cell_color(texel + vec2(-s.x, 0));
+    let f = cell_color(texel + vec2(s.x, 0));
+    let g = cell_color(texel + vec2(-s.x, s.y));
+    let h = cell_color(texel + vec2(0, s.y));
+    let i = cell_color(texel + s);
+    let f4 = cell_color(texel + vec2(2 * s.x, 0));
+    let i4 = cell_color(texel + vec2(2 * s.x, s.y));
+    let h5 = cell_color(texel + vec2(0, 2 * s.y));
+    let i5 = cell_color(texel + vec2(s.x, 2 * s.y));
+
+    // The edge runs across the corner (from f to h) when that is more of a boundary than the
+    // one through it (from e to i).
+    let across = color_distance(e, c) + color_distance(e, g) + color_distance(i, h5)
+        + color_distance(i, f4) + 4.0 * color_distance(h, f);
+    let through = color_distance(h, d) + color_distance(h, i5) + color_distance(f, i4)
+        + color_distance(f, b) + 4.0 * color_distance(e, i);
+    if (across >= through || all(e == f) || all(e == h)) {
+        return e;
     }
+    let other = select(h, f, color_distance(e, f) <= color_distance(e, h));
+    // 0 at the middle of the cell, 1 at the corner's edges; the diagonal runs where they sum to 1.
+    let corner = (inside - 0.5) * vec2<f32>(s) * 2.0;
+    let beyond = smoothstep(1.0 - XBR_SOFTNESS, 1.0 + XBR_SOFTNESS, corner.x + corner.y);
+    return mix(e, other, beyond);
 }
 
-// --- SYSTEMS ---
-
-fn setup(
-    mut commands: Commands,
-    mut images: ResMut<Assets<Image>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
-) {
-    let size = Extent3d {
-        width: SIMULATION_WIDTH,
-        height: SIMULATION_HEIGHT,
-        ..default()
-    };
-    let mut image_data = vec![0; (SIMULATION_WIDTH * SIMULATION_HEIGHT * 4) as usize];
-
-    // Create a bedrock floor
-    for x in 0..SIMULATION_WIDTH {
-        for y in 0..5 {
-            let i = ((y * SIMULATION_WIDTH + x) * 4) as usize;
-            image_data[i] = (Particle::Bedrock.get_color_id() * 255.0) as u8;
-        }
-    }
-
-    // --- THIS IS THE CORRECTED PART ---
-    // Manually construct the Image so we can set the correct TextureUsages.
-    let texture_descriptor = TextureDescriptor {
-        label: None,
-        size,
-        dimension: TextureDimension::D2,
-        format: TextureFormat::Rgba8Unorm,
-        mip_level_count: 1,
-        sample_count: 1,
-        usage: TextureUsages::TEXTURE_BINDING    // We need to read from it in a shader.
-            | TextureUsages::COPY_DST            // We need to copy CPU data to it.
-            | TextureUsages::RENDER_ATTACHMENT,  // We need to render to it.
-    };
-
-    let mut image_a = Image {
-        data: Some(image_data.clone()),
-        texture_descriptor: texture_descriptor.clone(),
-        sampler: ImageSampler::nearest(),
-        ..default()
-    };
-    image_a.resize(size);
-
-    let mut image_b = Image {
-        data: Some(image_data),
-        texture_descriptor,
-        sampler: ImageSampler::nearest(),
-        ..default()
-    };
-    image_b.resize(size);
-    // --- END OF CORRECTION ---
-
-    let h_image_a = images.add(image_a);
-    let h_image_b = images.add(image_b);
-
-    // This camera renders the simulation shader TO a texture.
-    commands.spawn((
-        Camera2d,
-        Camera {
-            target: RenderTarget::Image(h_image_b.clone().into()),
-            order: -1,
-            ..default()
-        },
-    ));
-
-    // This camera renders the final result TO the screen.
-    commands.spawn(Camera2d::default());
-
-    let material = sim_materials.add(SimulationMaterial {
-        source_image: h_image_a.clone(),
-    });
-
-    let quad_handle = meshes.add(Rectangle::new(size.width as f32, size.height as f32));
-
-    // Spawn a tuple of components for the simulation quad.
-    commands.spawn((
-        Mesh2d(quad_handle.into()),
-        MeshMaterial2d(material),
-        Transform::default(),
-        Visibility::default(),
-    ));
-
-    // The sprite that displays the final texture on screen.
-    commands.spawn((
-        Sprite {
-            image: h_image_b.clone(),
-            custom_size: Some(Vec2::new(
-                SIMULATION_WIDTH as f32 * 4.0,
-                SIMULATION_HEIGHT as f32 * 4.0,
-            )),
-            ..default()
-        },
-        Transform::default(),
-        Visibility::default(),
-    ));
-
-    commands.insert_resource(PingPong {
-        read: h_image_a,
-        write: h_image_b,
-    });
+// Polynomial fit of matplotlib's inferno colormap; keep the coefficients in sync with thermal.rs.
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3(0.000219, 0.001651, -0.0194809);
+    let c1 = vec3(0.106513, 0.563956, 3.93271);
+    let c2 = vec3(11.6025, -3.97285, -15.9424);
+    let c3 = vec3(-41.704, 17.4364, 44.3541);
+    let c4 = vec3(77.1629, -33.4024, -81.8073);
+    let c5 = vec3(-71.3194, 32.6261, 73.2095);
+    let c6 = vec3(25.1311, -12.2427, -23.0703);
+    let srgb = clamp(c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6))))), vec3(0.0), vec3(1.0));
+    return pow(srgb, vec3(2.2));
 }
 
-fn ping_pong(
-    mut ping_pong: ResMut<PingPong>,
-    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
-    mut sprite_query: Query<&mut Sprite>,
-    mut camera_query: Query<&mut Camera>,
-) {
-    let temp = ping_pong.read.clone();
-    ping_pong.read = ping_pong.write.clone();
-    ping_pong.write = temp;
-
-    for (_, material) in sim_materials.iter_mut() {
-        material.source_image = ping_pong.read.clone();
-    }
-
-    for mut cam in camera_query.iter_mut() {
-        if cam.order == -1 {
-            cam.target = RenderTarget::Image(ping_pong.write.clone().into());
-        }
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let position = in.uv * vec2<f32>(textureDimensions(t_in));
+    let texel = vec2<i32>(floor(position));
+    if (view_mode == VIEW_THERMAL) {
+        return vec4(inferno(f32(cell_state(texel).g) / 255.0), 1.0);
     }
 
-    // Filter for the specific sprite we want to update.
-    for mut sprite in sprite_query.iter_mut().filter(|s| s.custom_size.is_some()) {
-        sprite.image = ping_pong.write.clone();
+    var color = cell_color(texel);
+    // Where in its cell this fragment is.
+    let inside = fract(position);
+    if (upscaler == UPSCALE_XBR) {
+        color = xbr(texel, inside, color);
     }
+    color *= textureSample(t_shade, s_shade, in.uv).r / NEUTRAL_SHADE;
+
+    // ..which also picks the texel of the cell's tile.
+    let tile = textureSample(t_tiles, s_tiles, in.uv);
+    let mask = u32(round(tile.r * 255.0));
+    let atlas_uv = (vec2(f32(mask % 16u), f32(mask / 16u)) + inside) / ATLAS_COLUMNS;
+    let brightness = textureSample(t_atlas, s_atlas, atlas_uv).r * 2.0;
+    let tiled = autotile != 0u && tile.g > 0.5;
+    let glow = CHARGE_GLOW * f32(cell_state(texel).g) / 255.0;
+    return vec4(select(color, color * brightness, tiled) + glow, 1.0);
 }
-
-fn switch_particle_type(
-    keys: Res<ButtonInput<KeyCode>>,
-    mut selected: ResMut<SelectedParticle>,
-) {
-    if keys.just_pressed(KeyCode::Digit1) {
-        selected.0 = Particle::Sand;
-        info!("Switched to Sand");
-    }
-    if keys.just_pressed(KeyCode::Digit2) {
-        selected.0 = Particle::Water;
-        info!("Switched to Water");
-    }
-    if keys.just_pressed(KeyCode::Digit3) {
-        selected.0 = Particle::Bedrock;
-        info!("Switched to Bedrock");
-    }
-}
-
-fn paint_on_texture(
-    buttons: Res<ButtonInput<MouseButton>>,
-    q_window: Query<&Window, With<PrimaryWindow>>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
-    mut images: ResMut<Assets<Image>>,
-    ping_pong: Res<PingPong>,
-    selected_particle: Res<SelectedParticle>,
-) {
-    if !buttons.pressed(MouseButton::Left) {
-        return;
-    }
-    
-    let Ok(window) = q_window.single() else { return };
-    let Some((camera, camera_transform)) = q_camera.iter().find(|(c, _)| c.order == 0) else {
-        return;
-    };
-
-    if let Some(world_pos) = window
-        .cursor_position()
-        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
-        .map(|ray| ray.origin.truncate())
-    {
-        let texture_pos = (world_pos
-            + Vec2::new(
-                SIMULATION_WIDTH as f32 / 2.0,
-                SIM
\ No newline at end of file
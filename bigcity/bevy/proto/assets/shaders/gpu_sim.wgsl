@@ -0,0 +1,164 @@
+// Steps the world on the GPU; see gpu_sim.rs. Each pass moves cells within 2x2 blocks that don't
+// overlap, a block to a thread, so no two threads touch the same cell. From one pass to the next
+// the blocks shift a cell along x, y or both, so what is at a block's edge in one pass is inside
+// a block in another. Only movement is stepped: powders and liquids sink through what is lighter
+// and slide off what they can't, liquids spread sideways and gases rise through air.
+
+// Rows run top-down, as in the state texture: a cell's texel is the same here, with the same
+// channels (particle id, charge or temperature, weathering, stain), all carried along as the
+// particle moves, and so is the id the CPU knows the cell by.
+@group(0) @binding(0)
+var t_in: texture_2d<u32>;
+@group(0) @binding(1)
+var t_out: texture_storage_2d<rgba8uint, write>;
+
+struct Params {
+    size: vec2<u32>,
+    // Where this pass's blocks start, 0 or 1 cells along each axis.
+    offset: vec2<u32>,
+    // The pass, counted since the world started, and the world's own seed.
+    seed: vec2<u32>,
+    // How many regions there are along a row, and unused.
+    regions: vec2<u32>,
+}
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+// Every material's class (`kind`) and density, by particle id.
+struct Material {
+    kind: u32,
+    density: f32,
+}
+@group(0) @binding(3)
+var<storage, read> materials: array<Material>;
+
+@group(0) @binding(4)
+var ids_in: texture_2d<u32>;
+@group(0) @binding(5)
+var ids_out: texture_storage_2d<r32uint, write>;
+// A flag for every REGION by REGION square of cells, row by row, set when a cell in it changes;
+// the CPU reads them back and then only the regions flagged.
+@group(0) @binding(6)
+var<storage, read_write> dirty: array<atomic<u32>>;
+
+// Keep in sync with `material_table` in gpu_sim.rs.
+const GAS: u32 = 0u;
+const SOLID: u32 = 1u;
+const POWDER: u32 = 2u;
+const LIQUID: u32 = 3u;
+const AIR: u32 = 0u;
+// Beyond the world's edges is bedrock, which holds everything in and is never written.
+const EDGE: vec4<u32> = vec4(1u, 0u, 0u, 0u);
+// Keep in sync with `REGION` in gpu_sim.rs.
+const REGION: u32 = 32u;
+
+fn material(cell: vec4<u32>) -> Material {
+    return materials[min(cell.r, arrayLength(&materials) - 1u)];
+}
+
+fn pcg(n: u32) -> u32 {
+    let state = n * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Random bits for the block at `block` in this pass.
+fn dice(block: vec2<u32>) -> u32 {
+    return pcg(block.x ^ pcg(block.y ^ pcg(params.seed.x ^ pcg(params.seed.y))));
+}
+
+// Whether `upper` and the cell below it, `lower`, trade places: a powder or liquid sinking into a
+// lighter liquid or gas, or a gas other than air rising into air.
+fn falls(upper: vec4<u32>, lower: vec4<u32>) -> bool {
+    let a = material(upper);
+    let b = material(lower);
+    if (upper.r == AIR && lower.r != AIR && b.kind == GAS) {
+        return true;
+    }
+    let sinks = a.kind == POWDER || a.kind == LIQUID;
+    let gives = b.kind == GAS || b.kind == LIQUID;
+    return sinks && gives && a.density > b.density && upper.r != lower.r;
+}
+
+// Whether `cell` flows sideways into `beside`: a liquid into a gas, or a gas other than air into
+// air.
+fn flows(cell: vec4<u32>, beside: vec4<u32>) -> bool {
+    let a = material(cell);
+    let b = material(beside);
+    let pours = a.kind == LIQUID && b.kind == GAS;
+    let drifts = a.kind == GAS && cell.r != AIR && beside.r == AIR;
+    return pours || drifts;
+}
+
+fn trade(
+    cells: ptr<function, array<vec4<u32>, 4>>,
+    ids: ptr<function, array<u32, 4>>,
+    a: u32,
+    b: u32,
+) {
+    let held = (*cells)[a];
+    (*cells)[a] = (*cells)[b];
+    (*cells)[b] = held;
+    let id = (*ids)[a];
+    (*ids)[a] = (*ids)[b];
+    (*ids)[b] = id;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn step_world(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(params.size);
+    let origin = vec2<i32>(id.xy) * 2 - vec2<i32>(params.offset);
+    if (any(origin >= size)) {
+        return;
+    }
+    // Top left, top right, bottom left, bottom right.
+    var at = array(origin, origin + vec2(1, 0), origin + vec2(0, 1), origin + vec2(1, 1));
+    var cells: array<vec4<u32>, 4>;
+    var ids: array<u32, 4>;
+    for (var i = 0u; i < 4u; i++) {
+        let inside = all(at[i] >= vec2(0)) && all(at[i] < size);
+        cells[i] = select(EDGE, textureLoad(t_in, at[i], 0), inside);
+        ids[i] = select(0u, textureLoad(ids_in, at[i], 0).r, inside);
+    }
+    let before = ids;
+    let roll = dice(id.xy);
+
+    // Straight down (or up, for gases), in each column.
+    var fell = false;
+    for (var column = 0u; column < 2u; column++) {
+        if (falls(cells[column], cells[column + 2u])) {
+            trade(&cells, &ids, column, column + 2u);
+            fell = true;
+        }
+    }
+    // Down a diagonal where that is blocked, trying either side first.
+    if (!fell) {
+        let first = roll & 1u;
+        let second = 1u - first;
+        if (falls(cells[first], cells[3u - first])) {
+            trade(&cells, &ids, first, 3u - first);
+        } else if (falls(cells[second], cells[3u - second])) {
+            trade(&cells, &ids, second, 3u - second);
+        }
+    }
+    // Sideways along each row, half the time, for liquids to level out and gases to drift.
+    for (var row = 0u; row < 4u; row += 2u) {
+        if (((roll >> (1u + row)) & 1u) == 0u) {
+            continue;
+        }
+        if (flows(cells[row], cells[row + 1u]) || flows(cells[row + 1u], cells[row])) {
+            trade(&cells, &ids, row, row + 1u);
+        }
+    }
+
+    for (var i = 0u; i < 4u; i++) {
+        if (all(at[i] >= vec2(0)) && all(at[i] < size)) {
+            textureStore(t_out, at[i], cells[i]);
+            textureStore(ids_out, at[i], vec4(ids[i], 0u, 0u, 0u));
+            if (ids[i] != before[i]) {
+                let region = vec2<u32>(at[i]) / REGION;
+                atomicStore(&dirty[region.y * params.regions.x + region.x], 1u);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+// --- IMPORTS ---
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+
+use falling_sand::Particle;
+use falling_sand::test_utils::{Stepper, WorldBuilder};
+
+// --- CONSTANTS ---
+// Every world is stepped this many ticks before it is timed, so it is measured in motion.
+const WARMUP_TICKS: u64 = 20;
+const SIZE: u32 = 256;
+
+// --- BENCHMARKS ---
+
+// Times one tick of `sim::step` on a few worlds of the game's default size under the bundled
+// rules, the way the headless runner steps them. Every tick starts from a copy of the warmed-up
+// world, so the sand is still falling however many ticks criterion takes.
+fn sim_step(c: &mut Criterion) {
+    let worlds: [(&str, fn() -> Stepper); 4] = [
+        ("empty", || WorldBuilder::new(SIZE, SIZE).stepper()),
+        ("falling sand", || {
+            let half = SIZE as i32 / 2;
+            WorldBuilder::new(SIZE, SIZE)
+                .boxed()
+                .fill(Particle::Sand, (1, half), (SIZE as i32 - 1, SIZE as i32))
+                .stepper()
+        }),
+        ("water pool", || {
+            let third = SIZE as i32 / 3;
+            WorldBuilder::new(SIZE, SIZE)
+                .boxed()
+                .fill(Particle::Water, (1, 1), (SIZE as i32 - 1, third))
+                .fill(Particle::Sand, (third, third), (2 * third, 2 * third))
+                .stepper()
+        }),
+        ("lava and water", || {
+            let quarter = SIZE as i32 / 4;
+            WorldBuilder::new(SIZE, SIZE)
+                .boxed()
+                .fill(Particle::Lava, (1, 1), (SIZE as i32 - 1, quarter))
+                .fill(Particle::Water, (1, 2 * quarter), (SIZE as i32 - 1, 3 * quarter))
+                .stepper()
+        }),
+    ];
+    let mut group = c.benchmark_group(format!("sim_step_{}x{}", SIZE, SIZE));
+    for (name, world) in worlds {
+        let mut world = world();
+        world.run(WARMUP_TICKS);
+        let warm = world.into_grid();
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || Stepper::new(warm.clone()),
+                |world| world.step(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, sim_step);
+criterion_main!(benches);
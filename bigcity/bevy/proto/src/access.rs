@@ -0,0 +1,271 @@
+// --- IMPORTS ---
+use std::collections::HashSet;
+
+use bevy::ecs::component::Tick;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::coords::CellPos;
+use crate::regions::{ConnectedRegion, Connectivity, RegionIndex};
+use crate::sim::SimulationGrid;
+use crate::{MaterialClass, Particle};
+
+// --- TYPES ---
+
+// Which cells stop a ray.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RayFilter {
+    // Anything but air.
+    Occupied,
+    // Anything beams can't pass through.
+    Opaque,
+    Solid,
+    Liquid,
+    Class(MaterialClass),
+    Material(Particle),
+}
+
+impl RayFilter {
+    pub fn matches(self, particle: Particle) -> bool {
+        match self {
+            RayFilter::Occupied => particle != Particle::Air,
+            RayFilter::Opaque => !particle.is_transparent(),
+            RayFilter::Solid => particle.class() == MaterialClass::Solid,
+            RayFilter::Liquid => particle.class() == MaterialClass::Liquid,
+            RayFilter::Class(class) => particle.class() == class,
+            RayFilter::Material(material) => particle == material,
+        }
+    }
+}
+
+// The cells of one material connected side by side to a cell, as far as a search went, and
+// whether they are closed off: all found, and none of them on the world's edge, like the air of a
+// cave or the water in a cup.
+#[derive(Clone, Debug)]
+pub struct Pocket {
+    pub particle: Particle,
+    pub cells: Vec<CellPos>,
+    pub enclosed: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    pub cell: CellPos,
+    pub particle: Particle,
+    // Where the ray entered the cell, in cells, and how far it travelled to get there.
+    pub point: Vec2,
+    pub distance: f32,
+    // The side of the cell the ray came through; zero when the ray starts inside the hit cell.
+    pub normal: IVec2,
+}
+
+// --- SYSTEM PARAM ---
+
+// Read-only queries about the world for gameplay, UI and tools. Expensive lookups are backed by
+// indexes that are built on first use and only rebuilt after the grid has changed.
+#[derive(SystemParam)]
+pub struct SimulationAccess<'w, 's> {
+    grid: Res<'w, SimulationGrid>,
+    regions: Local<'s, RegionCache>,
+}
+
+#[derive(Default)]
+struct RegionCache {
+    by_material: Option<(Tick, RegionIndex)>,
+    by_class: Option<(Tick, RegionIndex)>,
+}
+
+impl SimulationAccess<'_, '_> {
+    pub fn particle(&self, cell: CellPos) -> Option<Particle> {
+        self.grid.get(cell.x, cell.y)
+    }
+
+    pub fn temperature(&self, cell: CellPos) -> Option<f32> {
+        self.grid.temperature(cell.x, cell.y)
+    }
+
+    // The eight cells around `cell` that are in the world, with what they hold.
+    pub fn neighbors(&self, cell: CellPos) -> impl Iterator<Item = (CellPos, Particle)> + '_ {
+        (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+            .filter(|&offset| offset != IVec2::ZERO)
+            .filter_map(move |offset| {
+                let neighbor = cell + offset;
+                Some((neighbor, self.particle(neighbor)?))
+            })
+    }
+
+    // The pocket of `cell`'s material around it, searching at most `limit` cells. Unlike
+    // `connected_region`, this only looks near the cell, so it stays cheap on a busy world; the
+    // search stops as soon as it reaches the world's edge or runs past `limit`.
+    pub fn pocket(&self, cell: CellPos, limit: usize) -> Option<Pocket> {
+        let particle = self.particle(cell)?;
+        let (width, height) = (self.grid.width() as i32, self.grid.height() as i32);
+        let mut seen = HashSet::from([cell]);
+        let mut open = vec![cell];
+        let mut cells = Vec::new();
+        while let Some(next) = open.pop() {
+            let on_edge = next.x == 0 || next.y == 0 || next.x == width - 1 || next.y == height - 1;
+            if on_edge || cells.len() == limit {
+                cells.push(next);
+                cells.extend(open);
+                return Some(Pocket {
+                    particle,
+                    cells,
+                    enclosed: false,
+                });
+            }
+            cells.push(next);
+            for step in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                let side = next + step;
+                if self.particle(side) == Some(particle) && seen.insert(side) {
+                    open.push(side);
+                }
+            }
+        }
+        Some(Pocket {
+            particle,
+            cells,
+            enclosed: true,
+        })
+    }
+
+    // The cells connected to `cell` through the same material.
+    pub fn connected_region(&mut self, cell: CellPos) -> Option<ConnectedRegion> {
+        self.connected_region_by(cell, Connectivity::Material)
+    }
+
+    pub fn connected_region_by(
+        &mut self,
+        CellPos(IVec2 { x, y }): CellPos,
+        connectivity: Connectivity,
+    ) -> Option<ConnectedRegion> {
+        let particle = self.grid.get(x, y)?;
+        let index = self.regions.index(&self.grid, connectivity);
+        let root = index.root(x, y);
+        let (_, min, max) = index.bounds(x, y);
+
+        let mut cells = Vec::new();
+        for cy in min.y..=max.y {
+            for cx in min.x..=max.x {
+                if index.root(cx, cy) == root {
+                    cells.push(CellPos::new(cx, cy));
+                }
+            }
+        }
+        Some(ConnectedRegion {
+            particle,
+            cells,
+            min: CellPos(min),
+            max: CellPos(max),
+        })
+    }
+
+    // Returns the first cell along the ray that matches `filter`. Positions are in cells, with
+    // cell (x, y) covering [x, x + 1) x [y, y + 1). Gives up after `max_distance` cells or when
+    // the ray leaves the grid.
+    pub fn raycast(
+        &self,
+        origin: Vec2,
+        dir: Vec2,
+        filter: RayFilter,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        RayWalk::new(&self.grid, origin, dir, max_distance).find(|hit| filter.matches(hit.particle))
+    }
+}
+
+impl RegionCache {
+    fn index(&mut self, grid: &Res<SimulationGrid>, connectivity: Connectivity) -> &mut RegionIndex {
+        let slot = match connectivity {
+            Connectivity::Material => &mut self.by_material,
+            Connectivity::Class => &mut self.by_class,
+        };
+        let changed = grid.last_changed();
+        if slot.as_ref().is_none_or(|(built, _)| *built != changed) {
+            *slot = Some((changed, RegionIndex::build(grid, connectivity)));
+        }
+        &mut slot.as_mut().unwrap().1
+    }
+}
+
+// --- RAY TRAVERSAL ---
+
+// Every cell a ray passes through, in order, found with a DDA traversal. Usable directly on a
+// grid for systems that need to edit the cells they walk through, like beams.
+pub struct RayWalk<'a> {
+    grid: &'a SimulationGrid,
+    origin: Vec2,
+    dir: Vec2,
+    max_distance: f32,
+    cell: IVec2,
+    step: IVec2,
+    // Distance along the ray to cross one whole cell on each axis.
+    delta: Vec2,
+    // Distance along the ray to the next cell boundary on each axis.
+    next: Vec2,
+    distance: f32,
+    normal: IVec2,
+}
+
+impl<'a> RayWalk<'a> {
+    pub fn new(grid: &'a SimulationGrid, origin: Vec2, dir: Vec2, max_distance: f32) -> Self {
+        // A zero direction walks nowhere; `next` returns None straight away.
+        let dir = dir.try_normalize().unwrap_or(Vec2::ZERO);
+        let max_distance = if dir == Vec2::ZERO { -1.0 } else { max_distance };
+        let cell = origin.floor().as_ivec2();
+        let step = IVec2::new(dir.x.signum() as i32, dir.y.signum() as i32);
+        let delta = Vec2::new(
+            if dir.x == 0.0 { f32::INFINITY } else { 1.0 / dir.x.abs() },
+            if dir.y == 0.0 { f32::INFINITY } else { 1.0 / dir.y.abs() },
+        );
+        let boundary = |o: f32, c: i32, s: i32| if s > 0 { c as f32 + 1.0 - o } else { o - c as f32 };
+        let next = Vec2::new(
+            boundary(origin.x, cell.x, step.x) * delta.x,
+            boundary(origin.y, cell.y, step.y) * delta.y,
+        );
+        Self {
+            grid,
+            origin,
+            dir,
+            max_distance,
+            cell,
+            step,
+            delta,
+            next,
+            distance: 0.0,
+            normal: IVec2::ZERO,
+        }
+    }
+}
+
+impl Iterator for RayWalk<'_> {
+    type Item = RayHit;
+
+    fn next(&mut self) -> Option<RayHit> {
+        if self.distance > self.max_distance {
+            return None;
+        }
+        let particle = self.grid.get(self.cell.x, self.cell.y)?;
+        let hit = RayHit {
+            cell: CellPos(self.cell),
+            particle,
+            point: self.origin + self.dir * self.distance,
+            distance: self.distance,
+            normal: self.normal,
+        };
+
+        if self.next.x < self.next.y {
+            self.distance = self.next.x;
+            self.next.x += self.delta.x;
+            self.cell.x += self.step.x;
+            self.normal = IVec2::new(-self.step.x, 0);
+        } else {
+            self.distance = self.next.y;
+            self.next.y += self.delta.y;
+            self.cell.y += self.step.y;
+            self.normal = IVec2::new(0, -self.step.y);
+        }
+        Some(hit)
+    }
+}
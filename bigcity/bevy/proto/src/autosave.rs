@@ -0,0 +1,250 @@
+// --- IMPORTS ---
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::tasks::futures::check_ready;
+use bevy::tasks::{IoTaskPool, Task};
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::levels::PaintRules;
+use crate::loading::StartupLoading;
+use crate::persist::{load_user_ron, save_user_ron, user_data_dir};
+use crate::saves::{SaveError, date_time, remove_if_present};
+use crate::sim::{SimParams, SimulationGrid, SimulationSet, ViewOnly};
+use crate::snapshot::WorldSnapshot;
+use crate::world_file::WorldSerializer;
+use crate::worldgen::WorldGeneration;
+
+// --- CONSTANTS ---
+const SETTINGS_FILE: &str = "autosave.ron";
+const AUTOSAVES_FOLDER: &str = "autosaves";
+// How often autosaves can be made, in minutes (0 turns them off), and how many may be kept.
+pub const AUTOSAVE_INTERVALS: RangeInclusive<u32> = 0..=60;
+pub const AUTOSAVE_SLOTS: RangeInclusive<usize> = 1..=10;
+// Each slot is the world as a picture with its sidecar (see `world_file`), and its metadata.
+const CELLS_EXTENSION: &str = "cells.png";
+const SIDECAR_EXTENSION: &str = "cells.ron";
+const META_EXTENSION: &str = "meta.ron";
+
+// --- PLUGIN ---
+
+// Autosaves: every few minutes the world and the simulation parameters are kept in one of a few
+// slots in `autosaves` in the user data directory, taking turns so the oldest is written over.
+// The saved worlds browser sets how often (every 5 minutes at first, or never) and how many are
+// kept. The grid is copied on the frame an autosave is due and written out on the IO task pool, so
+// encoding it never holds up a frame; one due while the last is still being written waits for it.
+// When the game starts with autosaves there, after a crash or otherwise, it offers to put the
+// newest back once the starting world is in.
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_user_ron::<AutosaveSettings>(SETTINGS_FILE).unwrap_or_default())
+            .init_resource::<Autosaves>()
+            .add_systems(Startup, find_autosaves)
+            .add_systems(
+                Update,
+                (start_autosave, finish_autosave)
+                    .chain()
+                    .after(SimulationSet)
+                    .run_if(not(resource_exists::<ViewOnly>)),
+            )
+            .add_systems(
+                EguiContextPass,
+                draw_restore_prompt.run_if(
+                    not(resource_exists::<WorldGeneration>)
+                        .and(not(resource_exists::<StartupLoading>)),
+                ),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// What a slot keeps besides the world.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct AutosaveMeta {
+    // When it was made, in seconds since the Unix epoch.
+    saved_at: u64,
+    // Counts up with every autosave, so the newest has the highest.
+    sequence: u64,
+    params: SimParams,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct AutosaveSettings {
+    // Minutes between autosaves; 0 makes none.
+    pub interval_minutes: u32,
+    // How many autosaves are kept before the oldest is written over.
+    pub slots: usize,
+}
+
+impl AutosaveSettings {
+    pub fn save(&self) {
+        save_user_ron(SETTINGS_FILE, self);
+    }
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 5,
+            slots: 3,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Autosaves {
+    // Seconds since the last autosave was made, or since the game started.
+    elapsed: f32,
+    // The next autosave's sequence number.
+    sequence: u64,
+    // The autosave being written, and its slot.
+    writing: Option<(usize, Task<Result<(), SaveError>>)>,
+    // The newest autosave there was at startup and its slot, until it is put back or dismissed.
+    offered: Option<(usize, AutosaveMeta)>,
+    status: String,
+}
+
+// --- SYSTEMS ---
+
+// Finds the newest autosave to offer, and carries on counting from it.
+fn find_autosaves(mut autosaves: ResMut<Autosaves>) {
+    let Some(dir) = autosaves_dir() else { return };
+    let newest = (0..*AUTOSAVE_SLOTS.end())
+        .filter_map(|slot| Some((slot, read_meta(&dir, slot).ok()?)))
+        .max_by_key(|(_, meta)| meta.sequence);
+    if let Some((slot, meta)) = newest {
+        autosaves.sequence = meta.sequence + 1;
+        autosaves.offered = Some((slot, meta));
+    }
+}
+
+// Copies the world when an autosave is due and hands it to the IO task pool to write.
+fn start_autosave(
+    time: Res<Time>,
+    settings: Res<AutosaveSettings>,
+    grid: Res<SimulationGrid>,
+    params: Res<SimParams>,
+    mut autosaves: ResMut<Autosaves>,
+) {
+    autosaves.elapsed += time.delta_secs();
+    let due = autosaves.elapsed >= settings.interval_minutes as f32 * 60.0;
+    if settings.interval_minutes == 0 || !due || autosaves.writing.is_some() {
+        return;
+    }
+    let Some(dir) = autosaves_dir() else { return };
+    let slot = (autosaves.sequence % settings.slots.max(1) as u64) as usize;
+    let meta = AutosaveMeta {
+        saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        sequence: autosaves.sequence,
+        params: params.clone(),
+    };
+    let grid = grid.clone();
+    let task = IoTaskPool::get().spawn(async move { write_slot(&dir, slot, &grid, &meta) });
+    autosaves.writing = Some((slot, task));
+    autosaves.sequence += 1;
+    autosaves.elapsed = 0.0;
+}
+
+fn finish_autosave(mut autosaves: ResMut<Autosaves>) {
+    let Some((slot, task)) = &mut autosaves.writing else { return };
+    let Some(result) = check_ready(task) else { return };
+    match result {
+        Ok(()) => info!("Autosaved into slot {}", *slot + 1),
+        Err(err) => warn!("Could not autosave into slot {}: {}", *slot + 1, err),
+    }
+    autosaves.writing = None;
+}
+
+fn draw_restore_prompt(
+    mut contexts: EguiContexts,
+    rules: Res<PaintRules>,
+    mut autosaves: ResMut<Autosaves>,
+    mut grid: ResMut<SimulationGrid>,
+    mut params: ResMut<SimParams>,
+) {
+    let Some((slot, meta)) = autosaves.offered.clone() else { return };
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let (mut restore, mut dismiss) = (false, false);
+    egui::Window::new("Restore autosave")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!("The world was autosaved {}.", date_time(meta.saved_at)));
+            ui.label("If the game closed unexpectedly, it can carry on from there.");
+            ui.horizontal(|ui| {
+                let button = egui::Button::new("Restore");
+                restore = ui.add_enabled(!rules.protect_world, button).clicked();
+                dismiss = ui.button("Start fresh").clicked();
+            });
+            if !autosaves.status.is_empty() {
+                ui.label(&autosaves.status);
+            }
+        });
+    if restore {
+        match read_slot(slot) {
+            Ok((world, meta)) => {
+                world.apply_to(&mut grid);
+                *params = meta.params;
+                info!("Restored the autosave in slot {}", slot + 1);
+                autosaves.offered = None;
+            }
+            Err(err) => autosaves.status = format!("Could not restore it: {}", err),
+        }
+    }
+    if dismiss {
+        autosaves.offered = None;
+    }
+}
+
+// --- HELPERS ---
+
+fn autosaves_dir() -> Option<PathBuf> {
+    user_data_dir().map(|dir| dir.join(AUTOSAVES_FOLDER))
+}
+
+fn slot_path(dir: &Path, slot: usize, extension: &str) -> PathBuf {
+    dir.join(format!("slot_{}.{}", slot + 1, extension))
+}
+
+fn read_meta(dir: &Path, slot: usize) -> Result<AutosaveMeta, SaveError> {
+    let text = std::fs::read_to_string(slot_path(dir, slot, META_EXTENSION))?;
+    Ok(ron::from_str(&text)?)
+}
+
+// The metadata goes first and comes back last, so a slot whose world was only partly written,
+// because the game closed meanwhile, isn't offered.
+fn write_slot(
+    dir: &Path,
+    slot: usize,
+    grid: &SimulationGrid,
+    meta: &AutosaveMeta,
+) -> Result<(), SaveError> {
+    std::fs::create_dir_all(dir)?;
+    let meta_path = slot_path(dir, slot, META_EXTENSION);
+    remove_if_present(&meta_path)?;
+    let (cells, sidecar) =
+        (slot_path(dir, slot, CELLS_EXTENSION), slot_path(dir, slot, SIDECAR_EXTENSION));
+    WorldSerializer::save(grid, &cells, &sidecar)?;
+    std::fs::write(meta_path, ron::ser::to_string_pretty(meta, default())?)?;
+    Ok(())
+}
+
+fn read_slot(slot: usize) -> Result<(WorldSnapshot, AutosaveMeta), SaveError> {
+    let dir = autosaves_dir().ok_or(SaveError::NoDataDir)?;
+    let world = WorldSerializer::load(
+        &slot_path(&dir, slot, CELLS_EXTENSION),
+        &slot_path(&dir, slot, SIDECAR_EXTENSION),
+    )?;
+    Ok((world.snapshot, read_meta(&dir, slot)?))
+}
@@ -0,0 +1,264 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::packed::PackedCells;
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{LowMemory, SimulationGrid, SimulationSet};
+use crate::{MaterialClass, SimulationDisplay, SimulationMaterial};
+
+// --- CONSTANTS ---
+// Changes are looked for in square regions of this many cells; only regions that changed have
+// their tiles picked again.
+const REGION_SIZE: usize = 16;
+// The atlas holds one tile for each of the 256 neighbor masks, 16 to a row, each this many texels
+// wide.
+const TILE_SIZE: u32 = 8;
+const ATLAS_COLUMNS: u32 = 16;
+// How far into a cell an exposed edge is shaded, as a fraction of the cell.
+const BEVEL_WIDTH: f32 = 0.3;
+
+// Neighbor mask bits: which of the eight neighbors are the same material.
+const NORTH: u8 = 1 << 0;
+const EAST: u8 = 1 << 1;
+const SOUTH: u8 = 1 << 2;
+const WEST: u8 = 1 << 3;
+const NORTH_EAST: u8 = 1 << 4;
+const SOUTH_EAST: u8 = 1 << 5;
+const SOUTH_WEST: u8 = 1 << 6;
+const NORTH_WEST: u8 = 1 << 7;
+const NEIGHBORS: [(i32, i32, u8); 8] = [
+    (0, 1, NORTH),
+    (1, 0, EAST),
+    (0, -1, SOUTH),
+    (-1, 0, WEST),
+    (1, 1, NORTH_EAST),
+    (1, -1, SOUTH_EAST),
+    (-1, -1, SOUTH_WEST),
+    (-1, 1, NORTH_WEST),
+];
+
+// --- PLUGIN ---
+
+// Autotiled terrain (Y): every cell of a solid material picks a tile by which of its eight
+// neighbors are the same material, so blocks of bedrock, glass or ice get lit top edges, shaded
+// undersides and inner corners instead of flat squares. Tiles are only picked again in regions of
+// the world that changed.
+pub struct AutotilePlugin;
+
+impl Plugin for AutotilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Autotiles>()
+            .add_systems(Startup, attach_tile_textures.after(crate::setup))
+            .add_systems(
+                Update,
+                (toggle_autotiles, update_autotiles.after(SimulationSet)).chain(),
+            );
+    }
+}
+
+// --- RESOURCES ---
+
+// The picked tiles, and the world they were picked for.
+#[derive(Resource, Default)]
+struct Autotiles {
+    enabled: bool,
+    tiles: Handle<Image>,
+    previous: Option<PackedCells>,
+}
+
+// --- SYSTEMS ---
+
+// Gives the world's material the tile textures: one texel per cell holding its neighbor mask, and
+// the atlas the mask picks a tile from.
+fn attach_tile_textures(
+    display: Res<SimulationDisplay>,
+    mut autotiles: ResMut<Autotiles>,
+    mut images: ResMut<Assets<Image>>,
+    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
+) {
+    let Some(material) = sim_materials.get_mut(&display.material) else { return };
+    let Some(state) = images.get(&display.state_image) else { return };
+    let size = state.texture_descriptor.size;
+    let mut tiles = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0, 0],
+        TextureFormat::Rg8Unorm,
+        RenderAssetUsages::default(),
+    );
+    tiles.sampler = ImageSampler::nearest();
+    let mut atlas = Image::new(
+        Extent3d {
+            width: TILE_SIZE * ATLAS_COLUMNS,
+            height: TILE_SIZE * ATLAS_COLUMNS,
+            ..default()
+        },
+        TextureDimension::D2,
+        tile_atlas(),
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::default(),
+    );
+    atlas.sampler = ImageSampler::nearest();
+
+    autotiles.tiles = images.add(tiles);
+    material.tile_image = autotiles.tiles.clone();
+    material.tile_atlas = images.add(atlas);
+}
+
+fn toggle_autotiles(
+    keys: Res<ButtonInput<KeyCode>>,
+    display: Res<SimulationDisplay>,
+    mut autotiles: ResMut<Autotiles>,
+    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
+) {
+    // Shift+Y is cell shading and Ctrl+Y redo.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift || ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyY) {
+        return;
+    }
+    autotiles.enabled = !autotiles.enabled;
+    // Tiles aren't kept up to date while they're off; turning them on picks them all again.
+    autotiles.previous = None;
+    if let Some(material) = sim_materials.get_mut(&display.material) {
+        material.autotile = autotiles.enabled as u32;
+    }
+    info!("Autotiled terrain {}", if autotiles.enabled { "on" } else { "off" });
+}
+
+// Picks tiles again for every region with a changed cell, and for the ring of cells around it
+// whose neighbors changed with it.
+fn update_autotiles(
+    grid: Res<SimulationGrid>,
+    low_memory: Option<Res<LowMemory>>,
+    mut autotiles: ResMut<Autotiles>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !autotiles.enabled || (!grid.is_changed() && autotiles.previous.is_some()) {
+        return;
+    }
+    let (width, height) = (grid.width() as usize, grid.height() as usize);
+    let autotiles = &mut *autotiles;
+    let everything =
+        autotiles.previous.as_ref().is_none_or(|previous| previous.cell_count() != width * height);
+    // A resized world needs a tile texture of its new size.
+    if everything && let Some(image) = images.get_mut(&autotiles.tiles) {
+        let size = Extent3d {
+            width: width as u32,
+            height: height as u32,
+            ..default()
+        };
+        if image.texture_descriptor.size != size {
+            image.resize(size);
+        }
+    }
+    let previous = match &mut autotiles.previous {
+        Some(previous) if !everything => previous,
+        previous => previous.insert(PackedCells::new(grid.cells(), low_memory.is_some())),
+    };
+
+    let mut dirty = Vec::new();
+    for region_y in (0..height).step_by(REGION_SIZE) {
+        for region_x in (0..width).step_by(REGION_SIZE) {
+            let x_end = (region_x + REGION_SIZE).min(width);
+            let y_end = (region_y + REGION_SIZE).min(height);
+            let mut changed = everything;
+            for y in region_y..y_end {
+                let start = y * width + region_x;
+                let cells = &grid.cells()[start..start + x_end - region_x];
+                if !previous.matches(start, cells) {
+                    previous.copy_from(start, cells);
+                    changed = true;
+                }
+            }
+            if changed {
+                dirty.push((region_x, region_y, x_end, y_end));
+            }
+        }
+    }
+    if dirty.is_empty() {
+        return;
+    }
+
+    let Some(data) = images.get_mut(&autotiles.tiles).and_then(|image| image.data.as_mut()) else {
+        return;
+    };
+    for (x_start, y_start, x_end, y_end) in dirty {
+        for y in y_start.saturating_sub(1)..(y_end + 1).min(height) {
+            for x in x_start.saturating_sub(1)..(x_end + 1).min(width) {
+                // Texture rows run top-down, grid rows bottom-up.
+                let i = ((height - 1 - y) * width + x) * 2;
+                data[i..i + 2].copy_from_slice(&tile_at(&grid, x as i32, y as i32));
+            }
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// The tile texel for the cell at (x, y): its neighbor mask, and whether it is tiled at all. The
+// world's edges count as more of the same material, so terrain runs cleanly off screen.
+fn tile_at(grid: &SimulationGrid, x: i32, y: i32) -> [u8; 2] {
+    let Some(particle) = grid.get(x, y).filter(|p| p.class() == MaterialClass::Solid) else {
+        return [0, 0];
+    };
+    let mut mask = 0;
+    for (dx, dy, bit) in NEIGHBORS {
+        if grid.get(x + dx, y + dy).is_none_or(|neighbor| neighbor == particle) {
+            mask |= bit;
+        }
+    }
+    [mask, 255]
+}
+
+// How bright each texel of every tile is, from 0 to twice the material's own color, tile rows top
+// first. Light comes from the top left: exposed top and left edges are lit and exposed bottom and
+// right edges shaded, fading towards the cell's middle, and a missing diagonal neighbor between
+// two present ones makes an inner corner.
+fn tile_atlas() -> Vec<u8> {
+    let side = (TILE_SIZE * ATLAS_COLUMNS) as usize;
+    let mut data = vec![0; side * side];
+    for mask in 0..=u8::MAX {
+        let (tile_x, tile_y) = (mask as u32 % ATLAS_COLUMNS, mask as u32 / ATLAS_COLUMNS);
+        for py in 0..TILE_SIZE {
+            for px in 0..TILE_SIZE {
+                let u = (px as f32 + 0.5) / TILE_SIZE as f32;
+                let v = 1.0 - (py as f32 + 0.5) / TILE_SIZE as f32;
+                let brightness = tile_brightness(mask, u, v);
+                let x = (tile_x * TILE_SIZE + px) as usize;
+                let y = (tile_y * TILE_SIZE + py) as usize;
+                data[y * side + x] = (brightness / 2.0 * 255.0).round() as u8;
+            }
+        }
+    }
+    data
+}
+
+// The brightness at (u, v) of the tile for `mask`, with v = 1 at the top of the cell.
+fn tile_brightness(mask: u8, u: f32, v: f32) -> f32 {
+    let open = |bit: u8| mask & bit == 0;
+    let inner = |diagonal: u8, a: u8, b: u8| open(diagonal) && !open(a) && !open(b);
+    // Distance to each exposed edge or inner corner, and the brightness right at it.
+    let edges = [
+        (open(NORTH), 1.0 - v, 1.35),
+        (open(WEST), u, 1.15),
+        (open(EAST), 1.0 - u, 0.8),
+        (open(SOUTH), v, 0.6),
+        (inner(NORTH_WEST, NORTH, WEST), u.hypot(1.0 - v), 1.25),
+        (inner(NORTH_EAST, NORTH, EAST), (1.0 - u).hypot(1.0 - v), 1.1),
+        (inner(SOUTH_WEST, SOUTH, WEST), u.hypot(v), 0.9),
+        (inner(SOUTH_EAST, SOUTH, EAST), (1.0 - u).hypot(v), 0.7),
+    ];
+    let nearest = edges
+        .iter()
+        .filter(|(exposed, _, _)| *exposed)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    match nearest {
+        Some(&(_, distance, edge)) if distance < BEVEL_WIDTH => {
+            1.0 + (edge - 1.0) * (1.0 - distance / BEVEL_WIDTH)
+        }
+        _ => 1.0,
+    }
+}
@@ -0,0 +1,97 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::frame_order::SwapSet;
+use crate::packed::PackedCells;
+use crate::sim::SimulationGrid;
+use crate::{Particle, SimulationDisplay};
+
+// --- CONSTANTS ---
+// Set on a wall's texel where the mortar between its bricks runs; the rest of the byte is the
+// wall's particle id.
+pub const WALL_MORTAR: u8 = 0x80;
+// How much darker walls are drawn than their material, and the mortar than the bricks; keep in
+// sync with the shader.
+pub const WALL_DIM: f32 = 0.45;
+pub const MORTAR_DIM: f32 = 0.7;
+// Bricks are this many cells across and high, every other row of them shifted by half a brick.
+const BRICK_SIZE: (usize, usize) = (4, 2);
+
+// --- PLUGIN ---
+
+// Background walls: a second layer of the world behind the particles, painted with the brush set
+// to the background layer in the tool palette, or with Alt held, in the selected material (air
+// takes walls away). Walls are decoration, drawn as darkened brickwork in their material's color
+// wherever the cell in front of them is empty; particles fall, flow and burn in front of them as
+// if they weren't there, and blasts don't break them. They are saved and loaded with the world,
+// and the tool palette can hide them.
+pub struct BackdropPlugin;
+
+impl Plugin for BackdropPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BackdropView { shown: true })
+            .add_systems(Update, upload_backdrop.in_set(SwapSet));
+    }
+}
+
+// --- RESOURCES ---
+
+// Whether the walls behind the particles are drawn.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct BackdropView {
+    pub shown: bool,
+}
+
+// --- SYSTEMS ---
+
+// Copies the walls into their texture when they changed, or clears it while they are hidden. The
+// grid changes every tick but its walls hardly ever do, so they are compared with the ones last
+// uploaded first, and the texture is only touched when they differ.
+fn upload_backdrop(
+    grid: Res<SimulationGrid>,
+    view: Res<BackdropView>,
+    display: Res<SimulationDisplay>,
+    mut images: ResMut<Assets<Image>>,
+    mut uploaded: Local<Option<PackedCells>>,
+    mut texels: Local<Vec<u8>>,
+) {
+    let walls = grid.backdrop_layer();
+    if !view.is_changed() && (!grid.is_changed() || uploaded.as_ref() == Some(walls)) {
+        return;
+    }
+    *uploaded = Some(walls.clone());
+    let (width, height) = (grid.width() as usize, grid.height() as usize);
+    texels.clear();
+    texels.resize(width * height, 0);
+    if view.shown {
+        for (y, row) in grid.backdrops().chunks(width).enumerate() {
+            // Texture rows run top-down, grid rows bottom-up.
+            let texture_row = &mut texels[(height - 1 - y) * width..(height - y) * width];
+            for (x, (&wall, texel)) in row.iter().zip(texture_row).enumerate() {
+                *texel = wall_texel(wall, x, y);
+            }
+        }
+    }
+    let current = images.get(&display.backdrop_image).and_then(|image| image.data.as_ref());
+    if current.is_some_and(|data| *data == *texels) {
+        return;
+    }
+    if let Some(image) = images.get_mut(&display.backdrop_image) {
+        image.data = Some(texels.clone());
+    }
+}
+
+// --- HELPERS ---
+
+// The backdrop texel of a wall of `wall` at grid cell (x, y): its id, and whether mortar runs
+// there. Air is no wall at all.
+pub fn wall_texel(wall: Particle, x: usize, y: usize) -> u8 {
+    if wall == Particle::Air {
+        return 0;
+    }
+    let (across, high) = BRICK_SIZE;
+    let shift = (y / high % 2) * across / 2;
+    let mortar = y.is_multiple_of(high) || (x + shift).is_multiple_of(across);
+    wall.id() | if mortar { WALL_MORTAR } else { 0 }
+}
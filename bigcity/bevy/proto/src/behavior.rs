@@ -0,0 +1,178 @@
+// --- IMPORTS ---
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::access::RayWalk;
+use crate::coords::CellPos;
+use crate::sim::{Reaction, SimulationGrid, compound, roll};
+
+// --- CONSTANTS ---
+// Radiation travels this many cells from a radioactive cell before it is spent.
+const RADIATION_REACH: f32 = 8.0;
+
+// --- TYPES ---
+
+// Rules of a material's own, written in Rust, that run on every cell of that material once per
+// chemistry step, after the built-in rules. Implement it and register it for a material with
+// `MaterialBehaviors::register`, and the CPU rules call it like their own. Radioactive decay is
+// written this way, and Lua behavior scripts from mods are registered as one.
+pub trait MaterialBehavior: Send + Sync + 'static {
+    fn update(&self, ctx: &mut CellCtx);
+}
+
+// What a behavior sees of the world while it updates one cell: the cell itself, the grid to read
+// the neighbourhood from, and the dice of this tick. The changes it may make go through here.
+pub struct CellCtx<'a> {
+    grid: &'a mut SimulationGrid,
+    cell: CellPos,
+    tick: u64,
+    ticks: u32,
+}
+
+impl CellCtx<'_> {
+    pub fn cell(&self) -> CellPos {
+        self.cell
+    }
+
+    pub fn particle(&self) -> Particle {
+        self.grid.get(self.cell.x, self.cell.y).unwrap_or_default()
+    }
+
+    pub fn grid(&self) -> &SimulationGrid {
+        self.grid
+    }
+
+    // How many ticks' worth of change this update applies at once; see `TickSchedule`.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    // Whether something that happens with `per_tick` chance each tick happens in this update.
+    pub fn chance(&self, per_tick: f32) -> bool {
+        roll(self.cell.x, self.cell.y, self.tick) < compound(per_tick, self.ticks)
+    }
+
+    // A number in [0, 1) for this cell and tick, independent of `chance`.
+    pub fn random(&self) -> f32 {
+        roll(self.cell.x, self.cell.y, !self.tick)
+    }
+
+    // The `nth` of as many numbers in [0, 1) as an update needs for this cell and tick, each
+    // independent of the others and of `chance` and `random`.
+    #[cfg(feature = "scripting")]
+    pub fn dice(&self, nth: u32) -> f32 {
+        let salt = (nth as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        roll(self.cell.x, self.cell.y, self.tick ^ salt)
+    }
+
+    pub fn add_heat(&mut self, cell: CellPos, degrees: f32) {
+        self.grid.add_heat(cell.x, cell.y, degrees);
+    }
+
+    // Turns the cell into `particle`, which starts out new but keeps its temperature and whether a
+    // player placed it.
+    pub fn turn_into(&mut self, particle: Particle) {
+        self.set(self.cell, particle);
+    }
+
+    // Turns `cell`, this one or another, into `particle` the way `turn_into` does. Cells outside
+    // the world stay as they are.
+    pub fn set(&mut self, cell: CellPos, particle: Particle) {
+        if self.grid.in_bounds(cell.x, cell.y) {
+            self.grid.transmute(cell.x, cell.y, particle);
+            self.grid.note_reaction(cell.0, Reaction::Behavior);
+        }
+    }
+
+    // Trades places with `cell`, and follows the particle there: the rest of the update is about
+    // the cell it moved to. A move up or to the right can bring it to a cell still to be updated
+    // this step, which then updates it again.
+    #[cfg(feature = "scripting")]
+    pub fn swap(&mut self, cell: CellPos) {
+        if cell != self.cell && self.grid.in_bounds(cell.x, cell.y) {
+            self.grid.swap_cells(self.cell, cell);
+            self.cell = cell;
+        }
+    }
+}
+
+// Radioactive materials send radiation along one random direction, heating every cell it passes
+// until something shields it, and may decay into their product.
+struct Radioactive;
+
+impl MaterialBehavior for Radioactive {
+    fn update(&self, ctx: &mut CellCtx) {
+        let Some(decay) = ctx.particle().decay() else { return };
+        let direction = Vec2::from_angle(ctx.random() * std::f32::consts::TAU);
+        let center = ctx.cell().center();
+        let reached: Vec<CellPos> = RayWalk::new(ctx.grid(), center, direction, RADIATION_REACH)
+            .take_while(|hit| !hit.particle.shields_radiation())
+            .map(|hit| hit.cell)
+            .collect();
+        for cell in reached {
+            ctx.add_heat(cell, decay.heat * ctx.ticks() as f32);
+        }
+
+        if ctx.chance(decay.chance_per_tick()) {
+            ctx.turn_into(decay.product);
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// The behavior registered for each material, if any. The default holds the built-in ones; one
+// registered for a material replaces what was there.
+#[derive(Resource, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(opaque, Resource))]
+pub struct MaterialBehaviors {
+    by_material: [Option<Arc<dyn MaterialBehavior>>; Particle::ALL.len()],
+}
+
+impl Default for MaterialBehaviors {
+    fn default() -> Self {
+        let mut behaviors = Self {
+            by_material: std::array::from_fn(|_| None),
+        };
+        for particle in Particle::ALL.into_iter().filter(|p| p.decay().is_some()) {
+            behaviors.register(particle, Radioactive);
+        }
+        behaviors
+    }
+}
+
+impl MaterialBehaviors {
+    pub fn has_behavior(&self, particle: Particle) -> bool {
+        self.by_material[particle as usize].is_some()
+    }
+
+    pub fn register(&mut self, particle: Particle, behavior: impl MaterialBehavior) {
+        self.by_material[particle as usize] = Some(Arc::new(behavior));
+    }
+
+    // Puts back the built-in behavior of `particle`, or none if it has no built-in one.
+    #[cfg(feature = "scripting")]
+    pub fn restore(&mut self, particle: Particle) {
+        self.by_material[particle as usize] = Self::default().by_material[particle as usize].take();
+    }
+
+    // Updates every cell whose material has a behavior, in row order, with `ticks` ticks' worth.
+    pub fn run(&self, grid: &mut SimulationGrid, tick: u64, ticks: u32) {
+        if self.by_material.iter().all(Option::is_none) {
+            return;
+        }
+        let width = grid.width() as usize;
+        for i in 0..grid.cells().len() {
+            let Some(behavior) = &self.by_material[grid.cells()[i] as usize] else { continue };
+            let cell = CellPos::new((i % width) as i32, (i / width) as i32);
+            behavior.update(&mut CellCtx {
+                grid,
+                cell,
+                tick,
+                ticks,
+            });
+        }
+    }
+}
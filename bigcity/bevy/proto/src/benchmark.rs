@@ -0,0 +1,258 @@
+// --- IMPORTS ---
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::renderer::RenderAdapterInfo;
+use bevy::window::PrimaryWindow;
+use serde::Serialize;
+use web_time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::degradation::DegradationPolicy;
+use crate::persist::user_data_dir;
+use crate::quality::Quality;
+use crate::saves::SaveError;
+use crate::sim::{SimulationControl, SimulationGrid, SimulationSet, SimulationStats, TickSchedule};
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+const BENCHMARK_FOLDER: &str = "benchmarks";
+// How long each scenario runs before it is measured, so the world has started moving, and how
+// long it is measured for, in real seconds.
+const WARMUP_SECS: f32 = 2.0;
+const MEASURE_SECS: f32 = 5.0;
+
+// --- PLUGIN ---
+
+// `--benchmark` runs the benchmark scenarios in the game's own window instead of playing: each one
+// fills the world, runs it for a few seconds at the highest quality with nothing shed, and
+// measures how many ticks and frames it managed and how long a tick took on the CPU. Then it
+// writes a results file with the game's version, the operating system, the CPU, the GPU and its
+// driver, the window and world sizes and every scenario's numbers to `benchmarks` in the user
+// data directory, and quits. Nothing is sent anywhere; the file is for attaching to a performance
+// issue by hand, so machines can be compared. Without the flag this plugin does nothing.
+pub struct BenchmarkPlugin;
+
+impl Plugin for BenchmarkPlugin {
+    fn build(&self, app: &mut App) {
+        if std::env::args().any(|arg| arg == "--benchmark") {
+            app.init_resource::<BenchmarkRun>()
+                .add_systems(Update, run_benchmark.after(SimulationSet));
+        }
+    }
+}
+
+// --- TYPES ---
+
+// The worlds the benchmark times, from the busiest kinds of work to none at all.
+#[derive(Clone, Copy, Debug)]
+enum Scenario {
+    // The first-launch quality benchmark's world: a band of sand and water squares.
+    SandAndWater,
+    // A pool of oil burning from the top.
+    OilFire,
+    // Lava under a sheet of ice: mostly heat and melting.
+    LavaAndIce,
+    // A cloud of steam, all gas.
+    Steam,
+    // Nothing but air, for the cost of the frame itself.
+    Empty,
+}
+
+impl Scenario {
+    const ALL: [Scenario; 5] = [
+        Scenario::SandAndWater,
+        Scenario::OilFire,
+        Scenario::LavaAndIce,
+        Scenario::Steam,
+        Scenario::Empty,
+    ];
+
+    // What the cell at (x, y) starts as in a world `height` cells tall.
+    fn particle(self, x: i32, y: i32, height: i32) -> Particle {
+        match self {
+            Scenario::SandAndWater if (height / 4..height * 3 / 4).contains(&y) => {
+                if (x / 8 + y / 8) % 2 == 0 { Particle::Sand } else { Particle::Water }
+            }
+            Scenario::OilFire if y < height / 3 => Particle::Oil,
+            Scenario::OilFire if y < height / 3 + 2 => Particle::Fire,
+            Scenario::LavaAndIce if y < height / 4 => Particle::Lava,
+            Scenario::LavaAndIce if y < height / 2 => Particle::Ice,
+            Scenario::Steam if (height / 4..height * 3 / 4).contains(&y) => Particle::Steam,
+            _ => Particle::Air,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct BenchmarkReport {
+    version: &'static str,
+    // Debug builds run far slower, so results from one mean little.
+    optimized: bool,
+    os: &'static str,
+    arch: &'static str,
+    cpu: Option<String>,
+    threads: usize,
+    gpu: Option<GpuInfo>,
+    window: (u32, u32),
+    world: (u32, u32),
+    scenarios: Vec<ScenarioResult>,
+}
+
+#[derive(Serialize, Debug)]
+struct GpuInfo {
+    name: String,
+    kind: String,
+    backend: String,
+    driver: String,
+}
+
+#[derive(Serialize, Debug)]
+struct ScenarioResult {
+    name: String,
+    // How many ticks and frames ran per real second.
+    ticks_per_second: f32,
+    frames_per_second: f32,
+    // How long a tick took on the CPU, and how many ticks a second that would allow if nothing
+    // else took any time.
+    ms_per_tick: f32,
+    max_ticks_per_second: f32,
+}
+
+// --- RESOURCES ---
+
+// How far the benchmark has got.
+#[derive(Resource, Default)]
+struct BenchmarkRun {
+    scenario: usize,
+    // When the current scenario started, if it has.
+    started: Option<Instant>,
+    ticks: u64,
+    frames: u32,
+    step_time: Duration,
+    results: Vec<ScenarioResult>,
+}
+
+// --- SYSTEM PARAM ---
+
+// The simulation the scenarios run in, and what it runs at.
+#[derive(SystemParam)]
+struct BenchedWorld<'w> {
+    grid: ResMut<'w, SimulationGrid>,
+    stats: Res<'w, SimulationStats>,
+    schedule: ResMut<'w, TickSchedule>,
+    control: ResMut<'w, SimulationControl>,
+    policy: ResMut<'w, DegradationPolicy>,
+}
+
+// --- SYSTEMS ---
+
+fn run_benchmark(
+    mut run: ResMut<BenchmarkRun>,
+    mut world: BenchedWorld,
+    layout: Res<WorldLayout>,
+    adapter: Option<Res<RenderAdapterInfo>>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(&scenario) = Scenario::ALL.get(run.scenario) else { return };
+    let Some(started) = run.started else {
+        start_scenario(scenario, &mut world);
+        info!("Benchmark: running {:?}", scenario);
+        run.started = Some(Instant::now());
+        return;
+    };
+
+    let elapsed = started.elapsed().as_secs_f32();
+    if elapsed < WARMUP_SECS {
+        return;
+    }
+    run.ticks += world.stats.ticks_last_frame as u64;
+    run.frames += 1;
+    run.step_time += world.stats.step_time;
+    if elapsed < WARMUP_SECS + MEASURE_SECS {
+        return;
+    }
+
+    let secs = elapsed - WARMUP_SECS;
+    let ms_per_tick = run.step_time.as_secs_f32() * 1000.0 / run.ticks.max(1) as f32;
+    let result = ScenarioResult {
+        name: format!("{:?}", scenario),
+        ticks_per_second: run.ticks as f32 / secs,
+        frames_per_second: run.frames as f32 / secs,
+        ms_per_tick,
+        max_ticks_per_second: 1000.0 / ms_per_tick.max(f32::EPSILON),
+    };
+    info!("Benchmark: {:?} took {:.3} ms per tick", scenario, ms_per_tick);
+    run.results.push(result);
+    run.scenario += 1;
+    run.started = None;
+    (run.ticks, run.frames, run.step_time) = (0, 0, Duration::ZERO);
+    if run.scenario < Scenario::ALL.len() {
+        return;
+    }
+
+    world.grid.clear();
+    let window = q_window.single().map_or((0, 0), |window| {
+        (window.resolution.physical_width(), window.resolution.physical_height())
+    });
+    let report = BenchmarkReport {
+        version: env!("CARGO_PKG_VERSION"),
+        optimized: !cfg!(debug_assertions),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        cpu: cpu_name(),
+        threads: std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+        gpu: adapter.map(|adapter| GpuInfo {
+            name: adapter.name.clone(),
+            kind: format!("{:?}", adapter.device_type),
+            backend: format!("{:?}", adapter.backend),
+            driver: format!("{} {}", adapter.driver, adapter.driver_info).trim().to_string(),
+        }),
+        window,
+        world: (layout.width, layout.height),
+        scenarios: std::mem::take(&mut run.results),
+    };
+    match write_report(&report) {
+        Ok(path) => println!("Benchmark finished; results are in {:?}", path),
+        Err(err) => eprintln!("Could not write the benchmark results: {}", err),
+    }
+    exit.write(AppExit::Success);
+}
+
+// --- HELPERS ---
+
+// Fills the world for `scenario` and lets it run flat out at the highest quality, with nothing
+// shed, so every machine runs the same work.
+fn start_scenario(scenario: Scenario, world: &mut BenchedWorld) {
+    let (width, height) = (world.grid.width() as i32, world.grid.height() as i32);
+    world.grid.clear();
+    for y in 0..height {
+        for x in 0..width {
+            let particle = scenario.particle(x, y, height);
+            if particle != Particle::Air {
+                world.grid.set(x, y, particle);
+            }
+        }
+    }
+    *world.schedule = Quality::Ultra.schedule();
+    *world.control = SimulationControl::default();
+    world.policy.enabled = false;
+}
+
+// The CPU's model name, where the system says.
+fn cpu_name() -> Option<String> {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+    let line = cpuinfo.lines().find(|line| line.starts_with("model name"))?;
+    Some(line.split_once(':')?.1.trim().to_string())
+}
+
+fn write_report(report: &BenchmarkReport) -> Result<PathBuf, SaveError> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let dir = user_data_dir().ok_or(SaveError::NoDataDir)?.join(BENCHMARK_FOLDER);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("benchmark_{}.ron", stamp));
+    std::fs::write(&path, ron::ser::to_string_pretty(report, default())?)?;
+    Ok(path)
+}
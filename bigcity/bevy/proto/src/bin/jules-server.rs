@@ -0,0 +1,7 @@
+// --- MAIN APP ---
+
+// The dedicated server: one world kept running for players who join it from the game with
+// `--join <host>:<port>`, with no window; see server.rs for its flags and console.
+fn main() {
+    std::process::exit(falling_sand::run_server());
+}
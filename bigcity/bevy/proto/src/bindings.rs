@@ -0,0 +1,236 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy::reflect::{DynamicEnum, DynamicVariant, Enum, TypeInfo, Typed, VariantInfo};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Particle;
+use crate::persist::{load_user_ron, save_user_ron, user_data_dir};
+
+// --- CONSTANTS ---
+const BINDINGS_FILE: &str = "bindings.ron";
+
+// --- PLUGIN ---
+
+// Input bindings: which keys pick materials (and with Shift, their second materials), pause,
+// step, change the speed, resize and reshape the brush, switch the stabilizer and show the tool
+// palette, and which gamepad buttons and stick move the cursor, paint, erase, pick materials,
+// resize the brush and open the handheld layout's radial menus. They are read from `bindings.ron`
+// in the user data directory at startup, which is written with the usual bindings the first time
+// so there is something to edit; whatever it leaves out keeps its usual binding. Keys and buttons
+// go by Bevy's names for them, like `KeyJ`, `Digit1` or `LeftTrigger2`.
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        let written = user_data_dir().is_some_and(|dir| dir.join(BINDINGS_FILE).exists());
+        let bindings = load_user_ron::<InputBindings>(BINDINGS_FILE).unwrap_or_default();
+        if !written {
+            save_user_ron(BINDINGS_FILE, &bindings);
+        }
+        app.insert_resource(bindings);
+    }
+}
+
+// --- TYPES ---
+
+// A key or a gamepad button, saved by its name.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Binding<T>(pub T);
+
+impl<T: Enum> Serialize for Binding<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.variant_name())
+    }
+}
+
+impl<'de, T: FromReflect + Typed> Deserialize<'de> for Binding<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        // Reflection panics on names the enum doesn't have, so they are looked up first.
+        let known = matches!(T::type_info(), TypeInfo::Enum(info)
+            if matches!(info.variant(&name), Some(VariantInfo::Unit(_))));
+        let named = DynamicEnum::new(name.clone(), DynamicVariant::Unit);
+        (known.then(|| T::from_reflect(&named)).flatten())
+            .map(Binding)
+            .ok_or_else(|| D::Error::custom(format!("no key or button is called `{}`", name)))
+    }
+}
+
+// A key that picks `particle` for the mouse player, and `shifted` with Shift held. With Shift held
+// a key without a second material picks nothing, leaving Shift+- and Shift+= to the speed.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub struct MaterialKey {
+    pub key: Binding<KeyCode>,
+    pub particle: Particle,
+    #[serde(default)]
+    pub shifted: Option<Particle>,
+}
+
+impl MaterialKey {
+    fn new(key: KeyCode, particle: Particle, shifted: Option<Particle>) -> Self {
+        Self {
+            key: Binding(key),
+            particle,
+            shifted,
+        }
+    }
+}
+
+// Which of a gamepad's sticks.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum Stick {
+    Left,
+    Right,
+}
+
+impl Stick {
+    pub fn read(self, gamepad: &Gamepad) -> Vec2 {
+        match self {
+            Stick::Left => gamepad.left_stick(),
+            Stick::Right => gamepad.right_stick(),
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Stick::Left => Stick::Right,
+            Stick::Right => Stick::Left,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct GamepadBindings {
+    // The stick that moves the cursor; the other one picks slices of the radial menus.
+    pub cursor: Stick,
+    pub paint: Vec<Binding<GamepadButton>>,
+    // Held, paints with the eraser whatever material is picked.
+    pub erase: Vec<Binding<GamepadButton>>,
+    pub materials: Vec<(Binding<GamepadButton>, Particle)>,
+    pub grow_brush: Binding<GamepadButton>,
+    pub shrink_brush: Binding<GamepadButton>,
+    // Held, open the handheld layout's radial menus (see handheld.rs).
+    pub materials_menu: Binding<GamepadButton>,
+    pub actions_menu: Binding<GamepadButton>,
+}
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self {
+            cursor: Stick::Right,
+            paint: vec![Binding(GamepadButton::RightTrigger2), Binding(GamepadButton::South)],
+            erase: vec![Binding(GamepadButton::LeftTrigger2)],
+            materials: vec![
+                (Binding(GamepadButton::West), Particle::Sand),
+                (Binding(GamepadButton::North), Particle::Water),
+                (Binding(GamepadButton::East), Particle::Bedrock),
+                (Binding(GamepadButton::DPadDown), Particle::Air),
+            ],
+            grow_brush: Binding(GamepadButton::DPadRight),
+            shrink_brush: Binding(GamepadButton::DPadLeft),
+            materials_menu: Binding(GamepadButton::LeftTrigger),
+            actions_menu: Binding(GamepadButton::Start),
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct InputBindings {
+    pub materials: Vec<MaterialKey>,
+    pub pause: Binding<KeyCode>,
+    // Runs a single tick while paused.
+    pub step: Binding<KeyCode>,
+    // Shift+= and Shift+- change the speed too, as long as no material takes them.
+    pub faster: Binding<KeyCode>,
+    pub slower: Binding<KeyCode>,
+    pub grow_brush: Binding<KeyCode>,
+    pub shrink_brush: Binding<KeyCode>,
+    // With Shift held, steps through the spray densities instead.
+    pub brush_shape: Binding<KeyCode>,
+    pub stabilizer: Binding<KeyCode>,
+    pub palette: Binding<KeyCode>,
+    pub gamepad: GamepadBindings,
+}
+
+impl InputBindings {
+    // The material the mouse player's keys pick this frame, if any.
+    pub fn picked_material(&self, keys: &ButtonInput<KeyCode>) -> Option<Particle> {
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let binding = self.materials.iter().find(|binding| keys.just_pressed(binding.key.0))?;
+        if shift { binding.shifted } else { Some(binding.particle) }
+    }
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let materials = vec![
+            MaterialKey::new(KeyCode::Digit1, Particle::Sand, Some(Particle::Battery)),
+            MaterialKey::new(KeyCode::Digit2, Particle::Water, Some(Particle::Goo)),
+            MaterialKey::new(KeyCode::Digit3, Particle::Bedrock, Some(Particle::Rope)),
+            MaterialKey::new(KeyCode::Digit4, Particle::Laser, Some(Particle::Lava)),
+            MaterialKey::new(KeyCode::Digit5, Particle::Mirror, Some(Particle::Steam)),
+            MaterialKey::new(KeyCode::Digit6, Particle::Glass, Some(Particle::Fire)),
+            MaterialKey::new(KeyCode::Digit7, Particle::Turbine, Some(Particle::Smoke)),
+            MaterialKey::new(KeyCode::Digit8, Particle::Snow, Some(Particle::Oil)),
+            MaterialKey::new(KeyCode::Digit9, Particle::Ice, Some(Particle::Tnt)),
+            MaterialKey::new(KeyCode::Minus, Particle::Dust, None),
+            MaterialKey::new(KeyCode::Equal, Particle::Salt, None),
+            MaterialKey::new(KeyCode::Backquote, Particle::Crystal, None),
+            MaterialKey::new(KeyCode::KeyM, Particle::Magnet, Some(Particle::IronPowder)),
+            MaterialKey::new(KeyCode::KeyU, Particle::Uranium, Some(Particle::Radium)),
+            MaterialKey::new(KeyCode::KeyP, Particle::Lead, Some(Particle::Gunpowder)),
+            MaterialKey::new(KeyCode::Digit0, Particle::Air, None),
+        ];
+        Self {
+            materials,
+            pause: Binding(KeyCode::Space),
+            step: Binding(KeyCode::Period),
+            faster: Binding(KeyCode::NumpadAdd),
+            slower: Binding(KeyCode::NumpadSubtract),
+            grow_brush: Binding(KeyCode::BracketRight),
+            shrink_brush: Binding(KeyCode::BracketLeft),
+            brush_shape: Binding(KeyCode::KeyJ),
+            stabilizer: Binding(KeyCode::KeyK),
+            palette: Binding(KeyCode::KeyS),
+            gamepad: GamepadBindings::default(),
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// Whether any of `buttons` is held down on `gamepad`.
+pub fn any_held(gamepad: &Gamepad, buttons: &[Binding<GamepadButton>]) -> bool {
+    buttons.iter().any(|button| gamepad.pressed(button.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_are_read_back_as_written() {
+        let bindings = InputBindings::default();
+        let text = ron::ser::to_string_pretty(&bindings, default()).unwrap();
+        assert!(text.contains("\"KeyJ\"") && text.contains("\"LeftTrigger2\""));
+        assert_eq!(ron::from_str::<InputBindings>(&text).unwrap(), bindings);
+    }
+
+    #[test]
+    fn unknown_keys_and_left_out_bindings() {
+        assert!(ron::from_str::<InputBindings>("(pause: \"Spacebar\")").is_err());
+        let bindings: InputBindings = ron::from_str("(pause: \"KeyO\")").unwrap();
+        assert_eq!(bindings.pause, Binding(KeyCode::KeyO));
+        assert_eq!(bindings.step, InputBindings::default().step);
+    }
+}
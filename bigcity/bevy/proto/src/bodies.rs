@@ -0,0 +1,318 @@
+// --- IMPORTS ---
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::SimulationGrid;
+use crate::{MaterialClass, Particle};
+
+// --- TYPES ---
+
+// A rigid body as the world keeps it (physics.rs moves it): its center and half its extent in
+// cells, its velocity in cells per second, what its cells are, a solid, so the simulation leaves
+// them where they are put, and its density relative to water's, so below 1 it floats. Points are
+// saved as `(x, y)` pairs, like cells.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub struct Body {
+    pub id: u32,
+    pub position: (f32, f32),
+    pub half_size: (f32, f32),
+    #[serde(default)]
+    pub velocity: (f32, f32),
+    pub material: Particle,
+    pub density: f32,
+}
+
+// The world's rigid bodies and which cell belongs to which, as a sparse map from cell index (in
+// grid order) to body id, like `CellTags`. A body is a rectangle of cells stamped into the grid
+// where it is and lifted out again when it moves. Cells keep their body as they move, and lose it
+// when painted over or burnt away; since the map is kept with the world, saving, loading,
+// resizing and undoing carry it along, and a body's cells can always be told from the world's.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CellBodies {
+    pub bodies: Vec<Body>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    cells: BTreeMap<u32, u32>,
+}
+
+impl CellBodies {
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Body> {
+        self.bodies.iter().find(|body| body.id == id)
+    }
+
+    // Adds `body` under a new id, which it returns.
+    fn add(&mut self, body: Body) -> u32 {
+        let id = self.bodies.iter().map(|body| body.id + 1).max().unwrap_or(0);
+        self.bodies.push(Body { id, ..body });
+        id
+    }
+
+    // The body the cell at `index` belongs to, in grid order.
+    pub fn owner(&self, index: usize) -> Option<u32> {
+        self.cells.get(&(index as u32)).copied()
+    }
+
+    // Gives the cell at `index` to `body`, or with `None` to none. Bodies that don't exist are
+    // ignored.
+    pub fn set(&mut self, index: usize, body: Option<u32>) {
+        match body {
+            Some(body) if self.get(body).is_some() => {
+                self.cells.insert(index as u32, body);
+            }
+            Some(_) => {}
+            None => {
+                self.cells.remove(&(index as u32));
+            }
+        }
+    }
+
+    // Every cell that belongs to a body, and the body's id, in grid order.
+    pub fn marks(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.cells.iter().map(|(&index, &body)| (index as usize, body))
+    }
+
+    pub fn cells_of(&self, body: u32) -> impl Iterator<Item = usize> + '_ {
+        self.marks().filter(move |&(_, of)| of == body).map(|(index, _)| index)
+    }
+
+    // Exchanges the bodies of two cells, as the grid swaps them.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if self.cells.is_empty() {
+            return;
+        }
+        let (body_a, body_b) = (self.owner(a), self.owner(b));
+        if body_a != body_b {
+            self.set(a, body_b);
+            self.set(b, body_a);
+        }
+    }
+
+    // Removes every body.
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+        self.cells.clear();
+    }
+}
+
+// What Ctrl+B and physics.rs ask of the world's bodies, by way of `WorldCommand::Bodies`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum BodyOperation {
+    // Adds a body, at rest, and stamps it in. Its id is the next free one.
+    Add {
+        position: (f32, f32),
+        half_size: (f32, f32),
+        material: Particle,
+        density: f32,
+    },
+    // Moves the body to `position`, lifting its cells and stamping them in again there.
+    Move {
+        id: u32,
+        position: (f32, f32),
+        velocity: (f32, f32),
+    },
+    // Lifts the body's cells and forgets it.
+    Remove(u32),
+}
+
+// --- HELPERS ---
+
+// Does `operation` to the world's bodies and their cells. A level's world is part of the puzzle,
+// so with `protect_world` no bodies are added to it.
+pub fn edit_bodies(grid: &mut SimulationGrid, operation: &BodyOperation, protect_world: bool) {
+    match *operation {
+        BodyOperation::Add {
+            position,
+            half_size,
+            material,
+            density,
+        } => {
+            if protect_world {
+                info!("No blocks can be dropped into a level");
+                return;
+            }
+            let body = Body {
+                id: 0,
+                position,
+                half_size,
+                velocity: (0.0, 0.0),
+                material,
+                density,
+            };
+            let id = grid.bodies_mut().add(body);
+            stamp(grid, id);
+        }
+        BodyOperation::Move {
+            id,
+            position,
+            velocity,
+        } => {
+            let Some(body) = grid.bodies_mut().bodies.iter_mut().find(|body| body.id == id) else {
+                return;
+            };
+            let was = footprint(body.position.into(), body.half_size.into());
+            (body.position, body.velocity) = (position, velocity);
+            // Most ticks a body moves by less than a cell, which leaves its cells as they are.
+            if footprint(position.into(), body.half_size.into()) != was {
+                lift(grid, id);
+                stamp(grid, id);
+            }
+        }
+        BodyOperation::Remove(id) => {
+            lift(grid, id);
+            grid.bodies_mut().bodies.retain(|body| body.id != id);
+        }
+    }
+}
+
+// The cells a body at `position` covers: those whose centers are inside it, from the first
+// up to the second, exclusive.
+pub fn footprint(position: Vec2, half_size: Vec2) -> (IVec2, IVec2) {
+    ((position - half_size).round().as_ivec2(), (position + half_size).round().as_ivec2())
+}
+
+// Solids and powders hold bodies up and stop them; liquids and gases give way.
+pub fn is_obstacle(particle: Particle) -> bool {
+    matches!(particle.class(), MaterialClass::Solid | MaterialClass::Powder)
+}
+
+// How much of a body at `position` is under liquid, from 0 to 1, and how dense that liquid is: the
+// rows with liquid just beside them count as under.
+#[cfg(feature = "physics")]
+pub fn submersion(grid: &SimulationGrid, position: Vec2, half_size: Vec2) -> (f32, f32) {
+    let (min, max) = footprint(position, half_size);
+    let (mut wet, mut density) = (0, 0.0);
+    for y in min.y..max.y {
+        let beside = [grid.get(min.x - 1, y), grid.get(max.x, y)];
+        let liquid = beside
+            .into_iter()
+            .flatten()
+            .filter(|particle| particle.class() == MaterialClass::Liquid)
+            .map(|particle| particle.density())
+            .reduce(f32::max);
+        if let Some(liquid) = liquid {
+            wet += 1;
+            density += liquid;
+        }
+    }
+    let rows = (max.y - min.y).max(1);
+    let density = if wet > 0 { density / wet as f32 } else { 0.0 };
+    (wet as f32 / rows as f32, density)
+}
+
+// Puts the body's cells back to air, but only those still its own material: ones the world has
+// since changed (burnt or melted) are left as they are, and no longer its own.
+fn lift(grid: &mut SimulationGrid, id: u32) {
+    let Some(material) = grid.bodies().get(id).map(|body| body.material) else { return };
+    let width = grid.width() as usize;
+    let cells: Vec<usize> = grid.bodies().cells_of(id).collect();
+    for index in cells {
+        let (x, y) = ((index % width) as i32, (index / width) as i32);
+        if grid.get(x, y) == Some(material) {
+            grid.set(x, y, Particle::Air);
+        } else {
+            grid.set_body(x, y, None);
+        }
+    }
+}
+
+// Fills the cells the body covers with its material. Liquids and gases there are pushed up their
+// column to the first air above the body, short of any obstacle; where there is none, and where
+// an obstacle is in the way, the body leaves a cell out.
+fn stamp(grid: &mut SimulationGrid, id: u32) {
+    let Some(&body) = grid.bodies().get(id) else { return };
+    let (min, max) = footprint(body.position.into(), body.half_size.into());
+    let height = grid.height() as i32;
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            let Some(particle) = grid.get(x, y) else { continue };
+            if is_obstacle(particle) {
+                continue;
+            }
+            if particle != Particle::Air {
+                let above = (max.y..height)
+                    .map(|to| (to, grid.get(x, to)))
+                    .take_while(|&(_, cell)| !cell.is_some_and(is_obstacle))
+                    .find(|&(_, cell)| cell == Some(Particle::Air));
+                let (Some((to, _)), Some(cell)) = (above, grid.cell(x, y)) else { continue };
+                grid.set_cell(x, to, cell);
+            }
+            grid.set(x, y, body.material);
+            grid.set_body(x, y, Some(id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coords::CellPos;
+    use crate::locks::PaintLocks;
+    use crate::snapshot::WorldSnapshot;
+    use crate::test_utils::{WorldBuilder, count};
+    use crate::undo::PaintHistory;
+
+    fn drop_block(grid: &mut SimulationGrid, position: (f32, f32)) {
+        let add = BodyOperation::Add {
+            position,
+            half_size: (2.0, 2.0),
+            material: Particle::Glass,
+            density: 0.5,
+        };
+        edit_bodies(grid, &add, false);
+    }
+
+    #[test]
+    fn bodies_push_liquids_up_and_take_their_cells_along() {
+        let mut grid = WorldBuilder::new(16, 16).fill(Particle::Water, (0, 0), (16, 4)).build();
+        let water = count(&grid, Particle::Water);
+        drop_block(&mut grid, (8.0, 4.0));
+        // The lower half of the block went into the water, and that water went on top of it.
+        assert_eq!(count(&grid, Particle::Glass), 16);
+        assert_eq!(count(&grid, Particle::Water), water);
+        assert_eq!(grid.get(7, 6), Some(Particle::Water));
+
+        let up = BodyOperation::Move {
+            id: 0,
+            position: (8.0, 10.0),
+            velocity: (0.0, 1.0),
+        };
+        edit_bodies(&mut grid, &up, false);
+        assert_eq!(count(&grid, Particle::Glass), 16);
+        assert_eq!(grid.body(7, 11), Some(0));
+        assert_eq!(grid.get(7, 3), Some(Particle::Air));
+        edit_bodies(&mut grid, &BodyOperation::Remove(0), false);
+        assert_eq!(count(&grid, Particle::Glass), 0);
+    }
+
+    #[test]
+    fn no_cells_are_left_behind_by_saves_or_undoing() {
+        let mut grid = WorldBuilder::new(16, 16).build();
+        drop_block(&mut grid, (8.0, 8.0));
+        let saved = WorldSnapshot::from_grid(&grid);
+
+        // Painted over and undone after the body has gone, its cell goes back to air.
+        let (locks, mut history) = (PaintLocks::default(), PaintHistory::default());
+        let cell = CellPos::new(7, 7);
+        let before = grid.cell(cell.x, cell.y);
+        grid.place(cell.x, cell.y, Particle::Sand, 0);
+        history.stroke(0).note(&grid, cell, before);
+        history.finish(0);
+        edit_bodies(&mut grid, &BodyOperation::Remove(0), false);
+        history.undo(&mut grid, &locks);
+        assert_eq!(count(&grid, Particle::Glass), 0);
+
+        // Loaded again, the body is back with its cells, and takes them when it goes.
+        saved.apply_to(&mut grid);
+        assert_eq!(grid.bodies().bodies.len(), 1);
+        assert_eq!(count(&grid, Particle::Glass), 16);
+        edit_bodies(&mut grid, &BodyOperation::Remove(0), false);
+        assert_eq!(count(&grid, Particle::Glass), 0);
+    }
+}
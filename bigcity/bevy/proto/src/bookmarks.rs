@@ -0,0 +1,187 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+use crate::coords::WorldPos;
+use crate::resolution::WorldShifted;
+use crate::{ScreenCamera, WorldLayout};
+
+// --- CONSTANTS ---
+pub const BOOKMARK_SLOTS: usize = 9;
+const SLOT_KEYS: [KeyCode; BOOKMARK_SLOTS] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+// --- PLUGIN ---
+
+// Camera bookmarks: Ctrl+1..9 keeps where the camera looks and how far it is zoomed in under that
+// number, Alt+1..9 jumps back there, and Ctrl+0 opens the list to name, visit or clear them.
+// Bookmarks belong to the world: saving it keeps them with the save, and loading a save brings
+// its bookmarks back.
+pub struct BookmarksPlugin;
+
+impl Plugin for BookmarksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bookmarks>()
+            .init_resource::<BookmarkPanel>()
+            .add_systems(Update, (use_bookmarks, follow_world_shifts))
+            .add_systems(EguiContextPass, draw_bookmarks);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Bookmark {
+    pub name: String,
+    // Where the camera looks, in cells, and its orthographic scale (1 shows the whole world).
+    pub center: (f32, f32),
+    pub zoom: f32,
+}
+
+impl Bookmark {
+    // Moves where the camera looks by `offset` cells, for a world whose cells moved.
+    pub fn shift(&mut self, offset: IVec2) {
+        self.center = (Vec2::from(self.center) + offset.as_vec2()).into();
+    }
+}
+
+// --- RESOURCES ---
+
+// The current world's bookmarks, by slot.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct Bookmarks {
+    pub slots: [Option<Bookmark>; BOOKMARK_SLOTS],
+}
+
+#[derive(Resource, Default)]
+struct BookmarkPanel {
+    open: bool,
+}
+
+// --- SYSTEMS ---
+
+fn use_bookmarks(
+    keys: Res<ButtonInput<KeyCode>>,
+    layout: Res<WorldLayout>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut panel: ResMut<BookmarkPanel>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if ctrl && keys.just_pressed(KeyCode::Digit0) {
+        panel.open = !panel.open;
+        return;
+    }
+    let Some(slot) = SLOT_KEYS.iter().position(|&key| keys.just_pressed(key)) else { return };
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+
+    if ctrl {
+        let center = layout.world_to_cell(WorldPos(transform.translation.truncate()));
+        let name = match &bookmarks.slots[slot] {
+            Some(bookmark) => bookmark.name.clone(),
+            None => format!("Bookmark {}", slot + 1),
+        };
+        info!("Bookmarked {:?} as \"{}\"", center.floor(), name);
+        bookmarks.slots[slot] = Some(Bookmark {
+            name,
+            center: center.into(),
+            zoom: ortho.scale,
+        });
+    } else if alt {
+        let Some(bookmark) = &bookmarks.slots[slot] else {
+            info!("Bookmark {} is empty (Ctrl+{} sets it)", slot + 1, slot + 1);
+            return;
+        };
+        jump(&layout, bookmark, &mut transform, ortho);
+    }
+}
+
+fn draw_bookmarks(
+    mut contexts: EguiContexts,
+    layout: Res<WorldLayout>,
+    mut panel: ResMut<BookmarkPanel>,
+    mut bookmarks: ResMut<Bookmarks>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let mut open = true;
+    let mut visit = None;
+    let mut clear = None;
+    egui::Window::new("Bookmarks").open(&mut open).resizable(false).show(ctx, |ui| {
+        egui::Grid::new("bookmarks").num_columns(3).show(ui, |ui| {
+            for (slot, bookmark) in bookmarks.slots.iter_mut().enumerate() {
+                ui.label(format!("{}", slot + 1));
+                match bookmark {
+                    Some(bookmark) => {
+                        ui.text_edit_singleline(&mut bookmark.name);
+                        ui.horizontal(|ui| {
+                            if ui.button("Go").clicked() {
+                                visit = Some(slot);
+                            }
+                            if ui.button("Clear").clicked() {
+                                clear = Some(slot);
+                            }
+                        });
+                    }
+                    None => {
+                        ui.weak(format!("Ctrl+{} to set", slot + 1));
+                        ui.label("");
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    });
+    panel.open &= open;
+
+    if let Some(slot) = clear {
+        bookmarks.slots[slot] = None;
+    }
+    if let Some(bookmark) = visit.and_then(|slot| bookmarks.slots[slot].as_ref())
+        && let Ok((mut transform, mut projection)) = q_camera.single_mut()
+        && let Projection::Orthographic(ortho) = &mut *projection
+    {
+        jump(&layout, bookmark, &mut transform, ortho);
+    }
+}
+
+// Keeps bookmarks on the same cells when the world grows under them.
+fn follow_world_shifts(mut shifts: EventReader<WorldShifted>, mut bookmarks: ResMut<Bookmarks>) {
+    for shift in shifts.read() {
+        for bookmark in bookmarks.slots.iter_mut().flatten() {
+            bookmark.shift(shift.offset);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn jump(
+    layout: &WorldLayout,
+    bookmark: &Bookmark,
+    transform: &mut Transform,
+    ortho: &mut OrthographicProjection,
+) {
+    let center = layout.cell_to_world(Vec2::from(bookmark.center));
+    transform.translation = center.extend(transform.translation.z);
+    ortho.scale = bookmark.zoom;
+    info!("Jumped to \"{}\"", bookmark.name);
+}
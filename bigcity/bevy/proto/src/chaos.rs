@@ -0,0 +1,512 @@
+// --- IMPORTS ---
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::persist::{load_user_ron, save_user_ron};
+use crate::meteors::spawn_meteor;
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimulationGrid, SimulationSet, ViewOnly, roll};
+use crate::{MaterialClass, Particle, WorldLayout, WorldView};
+
+// --- CONSTANTS ---
+const SETTINGS_FILE: &str = "chaos.ron";
+// Meteors come in from above the world, at up to this many cells per second sideways and this
+// many down, with these payloads to pick from.
+const METEOR_SPREAD: f32 = 80.0;
+const METEOR_SPEED: f32 = 160.0;
+const METEOR_ENTRY_HEIGHT: f32 = 8.0;
+const PAYLOADS: [Particle; 6] = [
+    Particle::IronPowder,
+    Particle::Magnet,
+    Particle::Ice,
+    Particle::Crystal,
+    Particle::Uranium,
+    Particle::Lead,
+];
+// Earthquakes shake loose what lies within this many cells of their center. A solid cell with
+// fewer than this many solid neighbours holding it, out of eight, counts as weakly supported, and
+// crumbles with this chance.
+const QUAKE_RADIUS: f32 = 40.0;
+const QUAKE_SUPPORT: usize = 4;
+const QUAKE_CHANCE: f32 = 0.6;
+const NEIGHBOURS: [(i32, i32); 8] =
+    [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+// Each quake runs this many fracture lines through its circle, each turning by up to this many
+// radians per cell as it goes.
+const FRACTURE_LINES: std::ops::Range<usize> = 2..6;
+const FRACTURE_JITTER: f32 = 0.5;
+// The earthquake tool (X) shakes this far around the cursor.
+const TOOL_QUAKE_RADIUS: f32 = 24.0;
+// Acid rain lasts this many seconds, with this many drops a second, each of which may eat the
+// surface cell it falls on.
+const RAIN_SECS: f32 = 8.0;
+const RAIN_DROPS_PER_SEC: f32 = 120.0;
+const ACID_BITE: f32 = 0.35;
+// How often each kind of event is allowed to come up, on average, in seconds.
+const INTERVALS: std::ops::RangeInclusive<f32> = 5.0..=600.0;
+
+// --- PLUGIN ---
+
+// Chaos mode (C): random disasters that strike the world now and then, each switched on and
+// timed on its own. Meteors fall in at an angle and leave a payload in their crater, earthquakes
+// run cracks through the solids around a point and collapse what they cut loose, and acid rain
+// eats into whatever lies on top. Every disaster goes through an event, so anything can set one
+// off; the window also has a button to trigger each one at once, and X sets off an earthquake at
+// the cursor. Random disasters stay quiet during levels, and spectators get none at all.
+pub struct ChaosPlugin;
+
+impl Plugin for ChaosPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Chaos {
+            settings: load_user_ron(SETTINGS_FILE).unwrap_or_default(),
+            ..default()
+        })
+        .add_event::<Disaster>()
+        .add_event::<Earthquake>()
+        .add_systems(
+            Update,
+            (
+                toggle_chaos,
+                schedule_disasters,
+                unleash_disasters,
+                quake_at_cursor,
+                shake,
+                rain_acid,
+            )
+                .chain()
+                .before(SimulationSet)
+                .run_if(not(resource_exists::<ViewOnly>)),
+        )
+        .add_systems(EguiContextPass, draw_chaos);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum DisasterKind {
+    Meteor,
+    Earthquake,
+    AcidRain,
+}
+
+impl DisasterKind {
+    const ALL: [DisasterKind; 3] =
+        [DisasterKind::Meteor, DisasterKind::Earthquake, DisasterKind::AcidRain];
+
+    fn label(self) -> &'static str {
+        match self {
+            DisasterKind::Meteor => "Meteors",
+            DisasterKind::Earthquake => "Earthquakes",
+            DisasterKind::AcidRain => "Acid rain",
+        }
+    }
+}
+
+// Whether one kind of disaster strikes on its own, and how often on average.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+struct DisasterRule {
+    enabled: bool,
+    every_secs: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+struct ChaosSettings {
+    meteor: DisasterRule,
+    earthquake: DisasterRule,
+    acid_rain: DisasterRule,
+    // What meteors leave in their craters.
+    meteor_payload: Particle,
+}
+
+impl ChaosSettings {
+    fn rule_mut(&mut self, kind: DisasterKind) -> &mut DisasterRule {
+        match kind {
+            DisasterKind::Meteor => &mut self.meteor,
+            DisasterKind::Earthquake => &mut self.earthquake,
+            DisasterKind::AcidRain => &mut self.acid_rain,
+        }
+    }
+}
+
+impl Default for ChaosSettings {
+    fn default() -> Self {
+        let rule = |every_secs| DisasterRule {
+            enabled: false,
+            every_secs,
+        };
+        Self {
+            meteor: rule(30.0),
+            earthquake: rule(90.0),
+            acid_rain: rule(120.0),
+            meteor_payload: Particle::IronPowder,
+        }
+    }
+}
+
+// --- EVENTS ---
+
+// A disaster of `kind` striking now, somewhere in the world.
+#[derive(Event, Debug, Clone, Copy)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Disaster(pub DisasterKind);
+
+// Shakes the world around `center` (in cells): within `radius`, solids that barely hold on to
+// anything crumble into rubble and fall.
+#[derive(Event, Debug, Clone, Copy)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Earthquake {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct Chaos {
+    open: bool,
+    settings: ChaosSettings,
+    // Seconds of acid rain still to fall.
+    rain_left: f32,
+    // Counts random draws, so that every one differs.
+    draws: u64,
+}
+
+impl Chaos {
+    // A number in 0..1, different on every call.
+    fn draw(&mut self) -> f32 {
+        self.draws += 1;
+        roll(self.draws as i32, (self.draws >> 32) as i32, self.draws)
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_chaos(keys: Res<ButtonInput<KeyCode>>, mut chaos: ResMut<Chaos>) {
+    // Shift+C is the collision view, and Ctrl+C copies the selection.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::KeyC) && !shift && !ctrl_held(&keys) {
+        chaos.open = !chaos.open;
+    }
+}
+
+// Each enabled kind strikes with the chance that keeps it to its interval on average.
+fn schedule_disasters(
+    time: Res<Time>,
+    rules: Res<PaintRules>,
+    mut chaos: ResMut<Chaos>,
+    mut disasters: EventWriter<Disaster>,
+) {
+    if rules.protect_world {
+        return;
+    }
+    let chaos = chaos.bypass_change_detection();
+    for kind in DisasterKind::ALL {
+        let rule = *chaos.settings.rule_mut(kind);
+        if rule.enabled && chaos.draw() < time.delta_secs() / rule.every_secs.max(1.0) {
+            disasters.write(Disaster(kind));
+        }
+    }
+}
+
+fn unleash_disasters(
+    mut commands: Commands,
+    rules: Res<PaintRules>,
+    layout: Res<WorldLayout>,
+    grid: Res<SimulationGrid>,
+    mut disasters: EventReader<Disaster>,
+    mut chaos: ResMut<Chaos>,
+    mut earthquakes: EventWriter<Earthquake>,
+) {
+    let (width, height) = (grid.width() as f32, grid.height() as f32);
+    for &Disaster(kind) in disasters.read() {
+        if rules.protect_world {
+            continue;
+        }
+        info!("Disaster: {:?}", kind);
+        match kind {
+            DisasterKind::Meteor => {
+                let x = chaos.draw() * width;
+                let sideways = (chaos.draw() * 2.0 - 1.0) * METEOR_SPREAD;
+                let position = Vec2::new(x, height + METEOR_ENTRY_HEIGHT);
+                let velocity = Vec2::new(sideways, -METEOR_SPEED);
+                let payload = chaos.settings.meteor_payload;
+                spawn_meteor(&mut commands, &layout, position, velocity, payload);
+            }
+            DisasterKind::Earthquake => {
+                let center = Vec2::new(chaos.draw() * width, chaos.draw() * height);
+                earthquakes.write(Earthquake {
+                    center,
+                    radius: QUAKE_RADIUS,
+                });
+            }
+            DisasterKind::AcidRain => chaos.rain_left = RAIN_SECS,
+        }
+    }
+}
+
+fn quake_at_cursor(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    mut earthquakes: EventWriter<Earthquake>,
+) {
+    if !keys.just_pressed(KeyCode::KeyX) {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    earthquakes.write(Earthquake {
+        center: cell.center(),
+        radius: TOOL_QUAKE_RADIUS,
+    });
+}
+
+// Cracks run through the solids in reach first, then whatever barely holds on crumbles, and then
+// every piece the cracks cut loose from anything holding it up collapses into rubble.
+fn shake(
+    rules: Res<PaintRules>,
+    mut earthquakes: EventReader<Earthquake>,
+    mut chaos: ResMut<Chaos>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    for earthquake in earthquakes.read() {
+        let cracked = crack(&mut grid, &rules, earthquake, &mut chaos);
+        let crumbled = crumble_thin(&mut grid, &rules, earthquake, &mut chaos);
+        let collapsed = collapse_loose(&mut grid, &rules, earthquake);
+        info!(
+            "Earthquake at {:?}: {} cells cracked, {} crumbled and {} collapsed",
+            earthquake.center.floor(),
+            cracked,
+            crumbled,
+            collapsed
+        );
+    }
+}
+
+// Drops fall as water from the top row and eat the topmost cell of their column, unless it is
+// bedrock or a liquid.
+fn rain_acid(
+    time: Res<Time>,
+    rules: Res<PaintRules>,
+    mut chaos: ResMut<Chaos>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    if chaos.rain_left <= 0.0 {
+        return;
+    }
+    let dt = time.delta_secs().min(chaos.rain_left);
+    chaos.rain_left -= dt;
+    let (width, height) = (grid.width() as i32, grid.height() as i32);
+    let drops = (RAIN_DROPS_PER_SEC * dt + chaos.draw()) as usize;
+    for _ in 0..drops {
+        let x = ((chaos.draw() * width as f32) as i32).min(width - 1);
+        grid.set(x, height - 1, Particle::Water);
+        let Some(y) = (0..height - 1).rev().find(|&y| grid.get(x, y) != Some(Particle::Air))
+        else {
+            continue;
+        };
+        let Some(surface) = grid.get(x, y) else { continue };
+        let soluble = surface != Particle::Bedrock && surface.class() != MaterialClass::Liquid;
+        let protected = rules.protect_world && !grid.is_placed(x, y);
+        if soluble && !protected && chaos.draw() < ACID_BITE {
+            grid.set(x, y, Particle::Air);
+        }
+    }
+}
+
+fn draw_chaos(
+    mut contexts: EguiContexts,
+    mut chaos: ResMut<Chaos>,
+    mut disasters: EventWriter<Disaster>,
+) {
+    if !chaos.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let mut open = true;
+    let mut settings = chaos.settings.clone();
+    egui::Window::new("Chaos").open(&mut open).resizable(false).show(ctx, |ui| {
+        egui::Grid::new("chaos_rules").num_columns(3).show(ui, |ui| {
+            for kind in DisasterKind::ALL {
+                let rule = settings.rule_mut(kind);
+                ui.checkbox(&mut rule.enabled, kind.label());
+                let every = egui::Slider::new(&mut rule.every_secs, INTERVALS)
+                    .logarithmic(true)
+                    .prefix("about every ")
+                    .suffix(" s");
+                ui.add_enabled(rule.enabled, every);
+                if ui.button("Now").clicked() {
+                    disasters.write(Disaster(kind));
+                }
+                ui.end_row();
+            }
+        });
+        egui::ComboBox::from_label("Meteor payload")
+            .selected_text(format!("{:?}", settings.meteor_payload))
+            .show_ui(ui, |ui| {
+                for payload in PAYLOADS {
+                    let label = format!("{:?}", payload);
+                    ui.selectable_value(&mut settings.meteor_payload, payload, label);
+                }
+            });
+    });
+    chaos.open &= open;
+    if settings != chaos.settings {
+        chaos.settings = settings;
+        save_user_ron(SETTINGS_FILE, &chaos.settings);
+    }
+}
+
+// --- HELPERS ---
+
+// What the cell at (x, y) crumbles into, if a quake can break it at all.
+fn breakable(grid: &SimulationGrid, rules: &PaintRules, x: i32, y: i32) -> Option<Particle> {
+    // Like blasts, quakes in a level leave the level's own cells alone.
+    if rules.protect_world && !grid.is_placed(x, y) {
+        return None;
+    }
+    grid.get(x, y).and_then(rubble)
+}
+
+// The cells within the quake's reach, as inclusive corners.
+fn reach(earthquake: &Earthquake) -> (IVec2, IVec2) {
+    let min = (earthquake.center - Vec2::splat(earthquake.radius)).floor().as_ivec2();
+    let max = (earthquake.center + Vec2::splat(earthquake.radius)).ceil().as_ivec2();
+    (min, max)
+}
+
+fn in_reach(earthquake: &Earthquake, x: i32, y: i32) -> bool {
+    let cell = CellPos::new(x, y).center();
+    cell.distance(earthquake.center) <= earthquake.radius
+}
+
+// Runs a few jagged fracture lines through the quake's circle, turning every breakable cell on
+// them into rubble. Returns how many cells cracked.
+fn crack(
+    grid: &mut SimulationGrid,
+    rules: &PaintRules,
+    earthquake: &Earthquake,
+    chaos: &mut Chaos,
+) -> usize {
+    let mut cracked = 0;
+    let lines = FRACTURE_LINES.start + (chaos.draw() * FRACTURE_LINES.len() as f32) as usize;
+    for _ in 0..lines {
+        // Each line starts somewhere in the inner half of the circle and wanders off both ways.
+        let start = earthquake.center
+            + Vec2::from_angle(chaos.draw() * TAU) * chaos.draw() * earthquake.radius * 0.5;
+        let heading = chaos.draw() * TAU;
+        for direction in [heading, heading + PI] {
+            let (mut position, mut angle) = (start, direction);
+            while position.distance(earthquake.center) <= earthquake.radius {
+                let cell = position.floor().as_ivec2();
+                if let Some(rubble) = breakable(grid, rules, cell.x, cell.y) {
+                    grid.set(cell.x, cell.y, rubble);
+                    cracked += 1;
+                }
+                angle += (chaos.draw() * 2.0 - 1.0) * FRACTURE_JITTER;
+                position += Vec2::from_angle(angle);
+            }
+        }
+    }
+    cracked
+}
+
+// Crumbles solids that barely hold on to anything, such as thin bridges and the cracks' edges.
+// Decided on the world as it was, so crumbling doesn't spread through a whole block.
+fn crumble_thin(
+    grid: &mut SimulationGrid,
+    rules: &PaintRules,
+    earthquake: &Earthquake,
+    chaos: &mut Chaos,
+) -> usize {
+    let (min, max) = reach(earthquake);
+    let mut crumbled = Vec::new();
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let Some(rubble) = breakable(grid, rules, x, y) else { continue };
+            if !in_reach(earthquake, x, y) {
+                continue;
+            }
+            let support = NEIGHBOURS
+                .iter()
+                .filter(|(dx, dy)| {
+                    grid.get(x + dx, y + dy).is_some_and(|p| p.class() == MaterialClass::Solid)
+                })
+                .count();
+            if support < QUAKE_SUPPORT && chaos.draw() < QUAKE_CHANCE {
+                crumbled.push((x, y, rubble));
+            }
+        }
+    }
+    for &(x, y, rubble) in &crumbled {
+        grid.set(x, y, rubble);
+    }
+    crumbled.len()
+}
+
+// Collapses every piece of breakable solid around the quake that nothing holds up any more: one
+// that touches neither an unbreakable solid (bedrock, machines, a level's own cells) nor the
+// world's edges. Pieces reaching out of the quake's square are taken to be held beyond it.
+fn collapse_loose(grid: &mut SimulationGrid, rules: &PaintRules, earthquake: &Earthquake) -> usize {
+    let (min, max) = reach(earthquake);
+    let side = (max - min + IVec2::ONE).as_uvec2();
+    let mut seen = vec![false; (side.x * side.y) as usize];
+    let index = |x: i32, y: i32| ((y - min.y) as u32 * side.x + (x - min.x) as u32) as usize;
+    let mut collapsed = 0;
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            if seen[index(x, y)] || breakable(grid, rules, x, y).is_none() {
+                continue;
+            }
+            // Flood the piece, noting whether anything holds it.
+            let (mut piece, mut open, mut held) = (Vec::new(), vec![IVec2::new(x, y)], false);
+            seen[index(x, y)] = true;
+            while let Some(cell) = open.pop() {
+                piece.push(cell);
+                for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                    let (nx, ny) = (cell.x + dx, cell.y + dy);
+                    let solid = grid.get(nx, ny).is_some_and(|p| p.class() == MaterialClass::Solid);
+                    if !grid.in_bounds(nx, ny) {
+                        held = true;
+                    } else if nx < min.x || ny < min.y || nx > max.x || ny > max.y {
+                        held |= solid;
+                    } else if seen[index(nx, ny)] {
+                        continue;
+                    } else if breakable(grid, rules, nx, ny).is_some() {
+                        seen[index(nx, ny)] = true;
+                        open.push(IVec2::new(nx, ny));
+                    } else {
+                        held |= solid;
+                    }
+                }
+            }
+            if !held {
+                for cell in &piece {
+                    if let Some(rubble) = breakable(grid, rules, cell.x, cell.y) {
+                        grid.set(cell.x, cell.y, rubble);
+                    }
+                }
+                collapsed += piece.len();
+            }
+        }
+    }
+    collapsed
+}
+
+// What a solid crumbles into when shaken loose. Bedrock holds, and so do the machines.
+fn rubble(particle: Particle) -> Option<Particle> {
+    match particle {
+        Particle::Glass => Some(Particle::Sand),
+        Particle::Ice => Some(Particle::Snow),
+        Particle::Crystal => Some(Particle::Salt),
+        Particle::Magnet => Some(Particle::IronPowder),
+        _ => None,
+    }
+}
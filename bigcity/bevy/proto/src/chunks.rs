@@ -0,0 +1,156 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::coords::{CellPos, ChunkPos};
+use crate::pan_zoom::ctrl_held;
+use crate::sim::SimulationGrid;
+use crate::WorldView;
+
+// --- CONSTANTS ---
+// A chunk that nothing in or next to it changed in for this many ticks has settled.
+pub const SETTLE_TICKS: u8 = 30;
+const AWAKE_COLOR: Color = Color::srgba(0.2, 1.0, 0.4, 0.6);
+
+// --- PLUGIN ---
+
+// Chunk view (Shift+R), for seeing where the world is still busy: every chunk movement is
+// simulating is outlined, and a legend counts them. The others have settled and are skipped until
+// something changes near them; see `ChunkActivity`.
+pub struct ChunkViewPlugin;
+
+impl Plugin for ChunkViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkView>()
+            .add_systems(Update, (toggle_chunk_view, draw_awake_chunks).chain())
+            .add_systems(EguiContextPass, draw_chunk_legend);
+    }
+}
+
+// --- TYPES ---
+
+// Which chunks of the world the simulation still has to look at. A chunk is awake while anything
+// in it or in a chunk next to it changed within the last SETTLE_TICKS ticks, however it changed: a
+// particle moving or reacting, heat spreading, a brush, a blast, a loaded world. The grid touches
+// the chunk of every cell it writes as it writes it, so finding them costs nothing. Movement, heat
+// and reactions skip the chunks that are asleep; see `step`.
+#[derive(Clone)]
+pub struct ChunkActivity {
+    across: IVec2,
+    // Ticks since anything changed in or next to each chunk, up to SETTLE_TICKS, row by row.
+    quiet: Vec<u8>,
+    // Whether anything in each chunk changed since the last tick settled.
+    touched: Vec<bool>,
+}
+
+impl ChunkActivity {
+    // Every chunk of a new world starts out awake.
+    pub fn new(width: u32, height: u32) -> Self {
+        let across = ChunkPos::across(width, height);
+        let chunks = (across.x * across.y) as usize;
+        Self {
+            across,
+            quiet: vec![0; chunks],
+            touched: vec![false; chunks],
+        }
+    }
+
+    // Notes that the cell at `cell` changed, which wakes its chunk and the ones around it when the
+    // tick settles.
+    pub fn touch(&mut self, cell: CellPos) {
+        let i = self.index(ChunkPos::containing(cell));
+        self.touched[i] = true;
+    }
+
+    // Wakes every chunk at once, for changes that reach the whole world, like a restored one.
+    pub fn wake_all(&mut self) {
+        self.quiet.fill(0);
+    }
+
+    // Ends a tick: the chunks touched since the last one and the ones around them wake, and the
+    // rest grow one tick quieter.
+    pub fn settle(&mut self) {
+        for quiet in &mut self.quiet {
+            *quiet = quiet.saturating_add(1).min(SETTLE_TICKS);
+        }
+        for y in 0..self.across.y {
+            for x in 0..self.across.x {
+                let chunk = self.index(ChunkPos(IVec2::new(x, y)));
+                if !std::mem::take(&mut self.touched[chunk]) {
+                    continue;
+                }
+                for ny in (y - 1).max(0)..=(y + 1).min(self.across.y - 1) {
+                    for nx in (x - 1).max(0)..=(x + 1).min(self.across.x - 1) {
+                        let neighbour = self.index(ChunkPos(IVec2::new(nx, ny)));
+                        self.quiet[neighbour] = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn is_awake(&self, chunk: ChunkPos) -> bool {
+        self.quiet[self.index(chunk)] < SETTLE_TICKS
+    }
+
+    pub fn awake(&self) -> impl Iterator<Item = ChunkPos> + '_ {
+        let across = self.across;
+        (0..across.y)
+            .flat_map(move |y| (0..across.x).map(move |x| ChunkPos(IVec2::new(x, y))))
+            .filter(|&chunk| self.is_awake(chunk))
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.quiet.len()
+    }
+
+    fn index(&self, chunk: ChunkPos) -> usize {
+        (chunk.0.y * self.across.x + chunk.0.x) as usize
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct ChunkView {
+    enabled: bool,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_chunk_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<ChunkView>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift && !ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyR) {
+        view.enabled = !view.enabled;
+        info!("Chunk view {}", if view.enabled { "on" } else { "off" });
+    }
+}
+
+fn draw_awake_chunks(
+    view: Res<ChunkView>,
+    grid: Res<SimulationGrid>,
+    world: WorldView,
+    mut gizmos: Gizmos,
+) {
+    if !view.enabled {
+        return;
+    }
+    for chunk in grid.activity().awake() {
+        let origin = chunk.origin();
+        let size = chunk.size_within(grid.width(), grid.height()).as_ivec2();
+        let rect = world.cells_to_world(origin, origin + size - IVec2::ONE);
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), AWAKE_COLOR);
+    }
+}
+
+fn draw_chunk_legend(mut contexts: EguiContexts, view: Res<ChunkView>, grid: Res<SimulationGrid>) {
+    if !view.enabled {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let activity = grid.activity();
+    egui::Window::new("Chunks").resizable(false).show(ctx, |ui| {
+        ui.label(format!("Awake: {} of {}", activity.awake().count(), activity.chunk_count()));
+        ui.weak(format!("Chunks settle after {} quiet ticks", SETTLE_TICKS));
+    });
+}
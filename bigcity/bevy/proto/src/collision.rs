@@ -0,0 +1,372 @@
+// --- IMPORTS ---
+use std::collections::{HashMap, HashSet};
+
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+
+use crate::coords::{CellPos, ChunkPos};
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{MaterialClass, WorldLayout};
+
+// --- CONSTANTS ---
+const SHAPE_COLOR: Color = Color::srgb(1.0, 0.4, 0.8);
+// How far, in cells, the collision view's simplified outlines may stray from the traced ones.
+const VIEW_TOLERANCE: f32 = 0.75;
+
+// --- PLUGIN ---
+
+// Collision geometry for games that give the world entity physics of their own: an entity with a
+// `CollisionArea` gets a `CollisionShape` with the outlines of the solid cells inside the area, and
+// a `Mesh2d` of the same outlines as lines, traced with marching squares and simplified with
+// Douglas-Peucker. Both follow the world as it changes, but only the chunks whose solid cells
+// changed are traced again, so outlines come and go a chunk at a time. The collision view (Shift+C)
+// traces the whole world and draws every area's outlines.
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionView>().add_systems(
+            Update,
+            (toggle_collision_view, update_collision_shapes, draw_collision_shapes)
+                .chain()
+                .after(SimulationSet),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// An outline in world units, with the solid on its left. A closed one wraps around; an open one
+// ends at the border of a chunk, where the outline of the next chunk carries on.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Polyline {
+    pub points: Vec<Vec2>,
+    pub closed: bool,
+}
+
+// What was traced of one chunk: its solid cells inside the area, and their outlines in cells.
+#[derive(Default)]
+struct TracedChunk {
+    solid: Vec<bool>,
+    outlines: Vec<Polyline>,
+}
+
+// --- COMPONENTS ---
+
+// Asks for collision geometry of the solid cells in the inclusive rectangle `min..=max`.
+// `tolerance` is how far, in cells, the simplified outlines may stray from the cells' edges.
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+#[require(CollisionShape, TracedArea, Transform, Visibility)]
+pub struct CollisionArea {
+    pub min: CellPos,
+    pub max: CellPos,
+    pub tolerance: f32,
+}
+
+#[derive(Component, Default, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+pub struct CollisionShape {
+    pub polylines: Vec<Polyline>,
+}
+
+// The traced chunks of an area, and the area they were traced for.
+#[derive(Component, Default)]
+struct TracedArea {
+    area: Option<CollisionArea>,
+    chunks: HashMap<ChunkPos, TracedChunk>,
+}
+
+// --- RESOURCES ---
+
+// The area the collision view traces, while it is on.
+#[derive(Resource, Default)]
+struct CollisionView {
+    area: Option<Entity>,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_collision_view(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Res<SimulationGrid>,
+    mut view: ResMut<CollisionView>,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !shift || ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+    match view.area.take() {
+        Some(area) => {
+            commands.entity(area).despawn();
+            info!("Collision view off");
+        }
+        None => {
+            let area = CollisionArea {
+                min: CellPos::new(0, 0),
+                max: CellPos::new(grid.width() as i32 - 1, grid.height() as i32 - 1),
+                tolerance: VIEW_TOLERANCE,
+            };
+            view.area = Some(commands.spawn((Name::new("collision_view"), area)).id());
+            info!("Collision view on");
+        }
+    }
+}
+
+// Traces the chunks of every area whose solid cells changed, and the chunks left of and below
+// them, whose outlines reach into them, then republishes the area's shape and mesh.
+fn update_collision_shapes(
+    mut commands: Commands,
+    grid: Res<SimulationGrid>,
+    layout: Res<WorldLayout>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut q_areas: Query<(
+        Entity,
+        &CollisionArea,
+        &mut CollisionShape,
+        &mut TracedArea,
+        Option<&Mesh2d>,
+    )>,
+) {
+    for (entity, area, mut shape, mut traced, mesh) in &mut q_areas {
+        if traced.area != Some(*area) {
+            *traced = TracedArea {
+                area: Some(*area),
+                ..default()
+            };
+        }
+        let Some((min, max)) = clip_to_world(&grid, area) else { continue };
+
+        let chunks = chunks_within(min, max);
+        let mut dirty = HashSet::new();
+        for &chunk in &chunks {
+            let solid = solid_cells(&grid, chunk, min, max);
+            let cached = traced.chunks.entry(chunk).or_default();
+            if cached.solid != solid || cached.solid.is_empty() {
+                cached.solid = solid;
+                for (dx, dy) in [(0, 0), (-1, 0), (0, -1), (-1, -1)] {
+                    dirty.insert(ChunkPos(chunk.0 + IVec2::new(dx, dy)));
+                }
+            }
+        }
+        traced.chunks.retain(|chunk, _| chunks.contains(chunk));
+        if dirty.is_empty() {
+            continue;
+        }
+        for chunk in dirty.into_iter().filter(|chunk| chunks.contains(chunk)) {
+            let outlines = trace_chunk(&grid, chunk, min, max)
+                .into_iter()
+                .map(|outline| simplify(outline, area.tolerance))
+                .collect();
+            if let Some(cached) = traced.chunks.get_mut(&chunk) {
+                cached.outlines = outlines;
+            }
+        }
+
+        shape.polylines = traced
+            .chunks
+            .values()
+            .flat_map(|chunk| &chunk.outlines)
+            .map(|outline| Polyline {
+                points: outline.points.iter().map(|&p| layout.cell_to_world(p).0).collect(),
+                closed: outline.closed,
+            })
+            .collect();
+        let lines = line_mesh(&shape.polylines);
+        match mesh.and_then(|mesh| meshes.get_mut(&mesh.0)) {
+            Some(existing) => *existing = lines,
+            None => {
+                commands.entity(entity).insert(Mesh2d(meshes.add(lines)));
+            }
+        }
+    }
+}
+
+fn draw_collision_shapes(
+    view: Res<CollisionView>,
+    q_shapes: Query<&CollisionShape>,
+    mut gizmos: Gizmos,
+) {
+    if view.area.is_none() {
+        return;
+    }
+    for shape in &q_shapes {
+        for polyline in &shape.polylines {
+            let closing = polyline.points.first().filter(|_| polyline.closed);
+            gizmos.linestrip_2d(polyline.points.iter().chain(closing).copied(), SHAPE_COLOR);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// The part of `area` inside the world, if any.
+fn clip_to_world(grid: &SimulationGrid, area: &CollisionArea) -> Option<(IVec2, IVec2)> {
+    let top_right = IVec2::new(grid.width() as i32 - 1, grid.height() as i32 - 1);
+    let (min, max) = (area.min.max(IVec2::ZERO), area.max.min(top_right));
+    (min.x <= max.x && min.y <= max.y).then_some((min, max))
+}
+
+fn chunks_within(min: IVec2, max: IVec2) -> HashSet<ChunkPos> {
+    let (first, last) = (ChunkPos::containing(CellPos(min)), ChunkPos::containing(CellPos(max)));
+    (first.0.y..=last.0.y)
+        .flat_map(|y| (first.0.x..=last.0.x).map(move |x| ChunkPos(IVec2::new(x, y))))
+        .collect()
+}
+
+// Which of the chunk's cells inside `min..=max` are solid, row by row.
+fn solid_cells(grid: &SimulationGrid, chunk: ChunkPos, min: IVec2, max: IVec2) -> Vec<bool> {
+    let (from, to) = chunk_span(chunk, min, max);
+    (from.y..=to.y)
+        .flat_map(|y| (from.x..=to.x).map(move |x| (x, y)))
+        .map(|(x, y)| is_solid(grid, x, y, min, max))
+        .collect()
+}
+
+// The cells of `chunk` inside `min..=max`.
+fn chunk_span(chunk: ChunkPos, min: IVec2, max: IVec2) -> (IVec2, IVec2) {
+    let origin = chunk.origin().0;
+    let end = ChunkPos(chunk.0 + IVec2::ONE).origin().0 - IVec2::ONE;
+    (origin.max(min), end.min(max))
+}
+
+fn is_solid(grid: &SimulationGrid, x: i32, y: i32, min: IVec2, max: IVec2) -> bool {
+    let inside = x >= min.x && y >= min.y && x <= max.x && y <= max.y;
+    inside && grid.get(x, y).is_some_and(|p| p.class() == MaterialClass::Solid)
+}
+
+// The squares between cell centers that `chunk` owns: those whose bottom-left corner is one of its
+// cells, plus the row and column just outside the area on its bottom and left, so outlines close
+// around solids at the edge of the area.
+fn trace_chunk(grid: &SimulationGrid, chunk: ChunkPos, min: IVec2, max: IVec2) -> Vec<Polyline> {
+    let (mut from, to) = chunk_span(chunk, min, max);
+    if from.x == min.x {
+        from.x -= 1;
+    }
+    if from.y == min.y {
+        from.y -= 1;
+    }
+    trace(from, to, |x, y| is_solid(grid, x, y, min, max))
+}
+
+// Marching squares over the squares between cell centers whose bottom-left corner is a cell in
+// `from..=to`, around the cells `filled` picks. The outlines come out in cells.
+pub(crate) fn trace(from: IVec2, to: IVec2, filled: impl Fn(i32, i32) -> bool) -> Vec<Polyline> {
+    // Segments run between the midpoints of the squares' sides, kept in half cells so they match
+    // exactly where squares meet. Each midpoint starts one segment and ends another at most.
+    let mut next = HashMap::new();
+    for y in from.y..=to.y {
+        for x in from.x..=to.x {
+            let solid = |dx, dy| filled(x + dx, y + dy) as usize;
+            let case = solid(0, 0) | solid(1, 0) << 1 | solid(1, 1) << 2 | solid(0, 1) << 3;
+            let center = IVec2::new(2 * x + 2, 2 * y + 2);
+            let (bottom, top) = (center - IVec2::Y, center + IVec2::Y);
+            let (left, right) = (center - IVec2::X, center + IVec2::X);
+            let segments: &[(IVec2, IVec2)] = match case {
+                1 => &[(bottom, left)],
+                2 => &[(right, bottom)],
+                3 => &[(right, left)],
+                4 => &[(top, right)],
+                5 => &[(bottom, left), (top, right)],
+                6 => &[(top, bottom)],
+                7 => &[(top, left)],
+                8 => &[(left, top)],
+                9 => &[(bottom, top)],
+                10 => &[(right, bottom), (left, top)],
+                11 => &[(right, top)],
+                12 => &[(left, right)],
+                13 => &[(bottom, right)],
+                14 => &[(left, bottom)],
+                _ => &[],
+            };
+            next.extend(segments.iter().copied());
+        }
+    }
+
+    // Chains that start where no segment ends are open; everything left over is a loop.
+    let ends: HashSet<IVec2> = next.values().copied().collect();
+    let mut starts: Vec<IVec2> =
+        next.keys().filter(|start| !ends.contains(start)).copied().collect();
+    let mut outlines = Vec::new();
+    loop {
+        let (start, closed) = match starts.pop() {
+            Some(start) => (start, false),
+            None => match next.keys().next() {
+                Some(&start) => (start, true),
+                None => break,
+            },
+        };
+        let mut points = vec![start];
+        let mut at = start;
+        while let Some(to) = next.remove(&at) {
+            points.push(to);
+            at = to;
+        }
+        outlines.push(Polyline {
+            points: points.into_iter().map(|half| half.as_vec2() / 2.0).collect(),
+            closed,
+        });
+    }
+    outlines
+}
+
+// Douglas-Peucker: keeps the points the outline can't do without to stay within `tolerance` of
+// where it was. A loop keeps its first point, and its last, which is the same one, is dropped.
+pub(crate) fn simplify(outline: Polyline, tolerance: f32) -> Polyline {
+    let points = outline.points;
+    if points.len() < 3 {
+        return Polyline { points, ..outline };
+    }
+    let last = points.len() - 1;
+    let mut keep = vec![false; points.len()];
+    (keep[0], keep[last]) = (true, true);
+    let mut spans = vec![(0, last)];
+    while let Some((a, b)) = spans.pop() {
+        let farthest = (a + 1..b)
+            .map(|i| (i, distance_to_segment(points[i], points[a], points[b])))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, distance)) = farthest
+            && distance > tolerance
+        {
+            keep[i] = true;
+            spans.extend([(a, i), (i, b)]);
+        }
+    }
+    let mut kept: Vec<Vec2> =
+        points.iter().zip(&keep).filter(|(_, kept)| **kept).map(|(point, _)| *point).collect();
+    if outline.closed {
+        kept.pop();
+    }
+    Polyline {
+        points: kept,
+        closed: outline.closed,
+    }
+}
+
+fn distance_to_segment(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let along = b - a;
+    let t = if along.length_squared() > 0.0 {
+        ((point - a).dot(along) / along.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    point.distance(a + along * t)
+}
+
+// The outlines as a list of line segments, one pair of vertices per segment.
+fn line_mesh(polylines: &[Polyline]) -> Mesh {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    for polyline in polylines {
+        let closing = polyline.points.first().filter(|_| polyline.closed);
+        let points: Vec<Vec2> = polyline.points.iter().chain(closing).copied().collect();
+        for pair in points.windows(2) {
+            positions.extend([pair[0].extend(0.0).to_array(), pair[1].extend(0.0).to_array()]);
+        }
+    }
+    Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
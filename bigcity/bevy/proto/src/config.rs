@@ -0,0 +1,155 @@
+// --- IMPORTS ---
+#[cfg(feature = "reflect")]
+use bevy::prelude::{Reflect, ReflectDefault};
+use thiserror::Error;
+
+use crate::cpu_display::DisplayBackend;
+use crate::display::{DisplaySettings, SCALES, Upscaler, WORLD_SIZES};
+use crate::sim::SimulationBackend;
+
+// --- TYPES ---
+
+// How an app embedding the sandbox sets it up, in place of the saved display settings:
+//
+//     SimulationConfig::new()
+//         .size(512, 512)
+//         .scale(3.0)
+//         .display_backend(DisplayBackend::Cpu)
+//         .seed(42)
+//
+// Whatever isn't set comes from `display.ron` as in the game, and `--world` and `--seed` on the
+// command line still win. `FallingSandPlugin::new` checks the combination before anything is
+// built, so a world too big or an upscaler the display backend can't draw is an error at startup
+// rather than a world quietly clamped or drawn differently.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+pub struct SimulationConfig {
+    size: Option<(u32, u32)>,
+    scale: Option<f32>,
+    display_backend: Option<DisplayBackend>,
+    simulation_backend: Option<SimulationBackend>,
+    upscaler: Option<Upscaler>,
+    pub(crate) seed: Option<u64>,
+}
+
+impl SimulationConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The world's size in cells.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    // Window pixels per cell, for a window sized to the world.
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    // Where the world's colors are worked out; see cpu_display.rs.
+    pub fn display_backend(mut self, backend: DisplayBackend) -> Self {
+        self.display_backend = Some(backend);
+        self
+    }
+
+    // Where the world steps; see gpu_sim.rs.
+    pub fn simulation_backend(mut self, backend: SimulationBackend) -> Self {
+        self.simulation_backend = Some(backend);
+        self
+    }
+
+    // How cells are blown up to screen pixels.
+    pub fn upscaler(mut self, upscaler: Upscaler) -> Self {
+        self.upscaler = Some(upscaler);
+        self
+    }
+
+    // The seed the world's dice are rolled with; see replay.rs.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Whether the sandbox can run as set up, and why not.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some((width, height)) = self.size
+            && !(WORLD_SIZES.contains(&width) && WORLD_SIZES.contains(&height))
+        {
+            return Err(ConfigError::WorldSize { width, height });
+        }
+        if let Some(scale) = self.scale.filter(|scale| !SCALES.contains(scale)) {
+            return Err(ConfigError::Scale(scale));
+        }
+        let cpu_display = self.display_backend == Some(DisplayBackend::Cpu);
+        if self.upscaler == Some(Upscaler::Xbr) && cpu_display {
+            return Err(ConfigError::UpscalerNeedsGpu);
+        }
+        Ok(())
+    }
+
+    // The saved display settings with whatever is set here in their place.
+    pub(crate) fn display_settings(&self) -> DisplaySettings {
+        let mut settings = DisplaySettings::load();
+        if let Some(size) = self.size {
+            settings.world = size;
+        }
+        if let Some(scale) = self.scale {
+            settings.scale = scale;
+        }
+        if let Some(backend) = self.display_backend {
+            settings.backend = backend;
+        }
+        if let Some(backend) = self.simulation_backend {
+            settings.simulation = backend;
+        }
+        if let Some(upscaler) = self.upscaler {
+            settings.upscaler = upscaler;
+        }
+        settings
+    }
+}
+
+// Why a `SimulationConfig` can't run.
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigError {
+    #[error(
+        "a world is {} to {} cells along either side, not {}x{}",
+        WORLD_SIZES.start(),
+        WORLD_SIZES.end(),
+        .width,
+        .height
+    )]
+    WorldSize { width: u32, height: u32 },
+    #[error(
+        "the scale is {} to {} window pixels per cell, not {}",
+        SCALES.start(),
+        SCALES.end(),
+        .0
+    )]
+    Scale(f32),
+    #[error("xBR upscaling is drawn by the world's shader, which the CPU display doesn't use")]
+    UpscalerNeedsGpu,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_what_cannot_run() {
+        let config = SimulationConfig::new().size(512, 512).scale(3.0).seed(42);
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(
+            config.size(4096, 512).validate(),
+            Err(ConfigError::WorldSize { width: 4096, height: 512 })
+        );
+        assert_eq!(config.scale(0.0).validate(), Err(ConfigError::Scale(0.0)));
+        assert!(config.scale(f32::NAN).validate().is_err());
+        let cpu = config.display_backend(DisplayBackend::Cpu);
+        assert_eq!(cpu.validate(), Ok(()));
+        assert_eq!(cpu.upscaler(Upscaler::Xbr).validate(), Err(ConfigError::UpscalerNeedsGpu));
+    }
+}
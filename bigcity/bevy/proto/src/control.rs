@@ -0,0 +1,106 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::bindings::InputBindings;
+use crate::sim::SimulationControl;
+
+// --- CONSTANTS ---
+// The speeds + and - step through, as multiples of the tick rate.
+pub const SPEEDS: [f32; 7] = [0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+// --- PLUGIN ---
+
+// Simulation controls for looking at particles tick by tick: Space pauses and resumes, `.` runs a
+// single tick while paused, and + and - (on the numpad, or Shift with = and -) make the world run
+// faster or slower than the tick rate. Painting and every tool keep working while paused, so a
+// scene can be set up before it runs. Speeds past what the quality level's ticks per frame allow
+// run as fast as they allow. The pause, step and numpad keys can be rebound (see bindings.rs).
+pub struct ControlPlugin;
+
+impl Plugin for ControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_control_label)
+            .add_systems(Update, (control_simulation, update_control_label).chain());
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct ControlLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_control_label(mut commands: Commands) {
+    commands.spawn((
+        ControlLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn control_simulation(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if keys.just_pressed(bindings.pause.0) {
+        control.paused = !control.paused;
+        control.pending_steps = 0;
+        info!("Simulation {}", if control.paused { "paused" } else { "resumed" });
+    }
+    if keys.just_pressed(bindings.step.0) && control.paused {
+        control.pending_steps += 1;
+    }
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let faster = keys.just_pressed(bindings.faster.0) || shift && keys.just_pressed(KeyCode::Equal);
+    let slower =
+        keys.just_pressed(bindings.slower.0) || shift && keys.just_pressed(KeyCode::Minus);
+    let current = SPEEDS.iter().position(|&speed| speed >= control.speed).unwrap_or(3);
+    let next = if faster {
+        (current + 1).min(SPEEDS.len() - 1)
+    } else if slower {
+        current.saturating_sub(1)
+    } else {
+        return;
+    };
+    if SPEEDS[next] != control.speed {
+        control.speed = SPEEDS[next];
+        info!("Simulation speed x{}", control.speed);
+    }
+}
+
+fn update_control_label(
+    control: Res<SimulationControl>,
+    mut q_label: Query<&mut Text, With<ControlLabel>>,
+) {
+    if !control.is_changed() {
+        return;
+    }
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let speed = (control.speed != 1.0).then(|| format!("x{}", control.speed));
+    let text = match (control.paused, speed) {
+        (true, speed) => {
+            let speed = speed.map_or_else(String::new, |speed| format!(" ({})", speed));
+            format!("Paused{}: Space resumes, . runs one tick", speed)
+        }
+        (false, Some(speed)) => format!("Speed {}", speed),
+        (false, None) => String::new(),
+    };
+    if label.0 != text {
+        label.0 = text;
+    }
+}
@@ -0,0 +1,169 @@
+// --- IMPORTS ---
+use std::ops::{Add, Deref, Sub};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// --- CONSTANTS ---
+// Chunks are square blocks of this many cells a side, the unit world generation and spectating
+// work in.
+const CHUNK_SIZE: i32 = 32;
+
+// --- TYPES ---
+
+// The three spaces positions live in, kept apart by type so one can't be passed for another:
+// cells of the grid, chunks of cells, and the screen camera's world space. Window pixels stay
+// plain `Vec2`s, as Bevy hands them out; `WorldLayout` converts between the spaces. Points in
+// between cells, like a beam's path or a grenade in flight, are `Vec2`s in cell units.
+
+// A cell of the grid, counted from the bottom-left corner. Saved as an `(x, y)` pair, like the
+// cells of every file format here.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+#[serde(from = "(i32, i32)", into = "(i32, i32)")]
+pub struct CellPos(pub IVec2);
+
+// A chunk of CHUNK_SIZE by CHUNK_SIZE cells, counted like cells from the bottom-left corner.
+// Chunks along the right and top edges may hang over the world's edge.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct ChunkPos(pub IVec2);
+
+// A point in the screen camera's world space, with the middle of the world at the origin.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct WorldPos(pub Vec2);
+
+impl CellPos {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self(IVec2::new(x, y))
+    }
+
+    // The cell a point in cell units falls in.
+    pub fn containing(point: Vec2) -> Self {
+        Self(point.floor().as_ivec2())
+    }
+
+    // The middle of the cell, in cell units.
+    pub fn center(self) -> Vec2 {
+        self.0.as_vec2() + Vec2::splat(0.5)
+    }
+
+    // Every cell on the straight line from this one to `end`, both included, without gaps or
+    // doubled-up corners (Bresenham's line).
+    pub fn line_to(self, end: CellPos) -> Vec<CellPos> {
+        let (delta, step) = ((end.0 - self.0).abs(), (end.0 - self.0).signum());
+        let mut cells = Vec::with_capacity(delta.max_element() as usize + 1);
+        let (mut cell, mut error) = (self.0, delta.x - delta.y);
+        loop {
+            cells.push(CellPos(cell));
+            if cell == end.0 {
+                return cells;
+            }
+            let doubled = 2 * error;
+            if doubled > -delta.y {
+                error -= delta.y;
+                cell.x += step.x;
+            }
+            if doubled < delta.x {
+                error += delta.x;
+                cell.y += step.y;
+            }
+        }
+    }
+}
+
+impl ChunkPos {
+    // The chunk `cell` lies in.
+    pub fn containing(cell: CellPos) -> Self {
+        Self(cell.0.div_euclid(IVec2::splat(CHUNK_SIZE)))
+    }
+
+    // How many chunks a world `width` by `height` cells has along each side.
+    pub fn across(width: u32, height: u32) -> IVec2 {
+        IVec2::new(chunks_across(width), chunks_across(height))
+    }
+
+    // Every chunk of a world `width` by `height` cells, row by row from the bottom.
+    pub fn covering(width: u32, height: u32) -> impl Iterator<Item = Self> {
+        let across = Self::across(width, height);
+        (0..across.y).flat_map(move |y| (0..across.x).map(move |x| Self(IVec2::new(x, y))))
+    }
+
+    // The chunk's bottom-left cell.
+    pub fn origin(self) -> CellPos {
+        CellPos(self.0 * CHUNK_SIZE)
+    }
+
+    // How many cells of the chunk are inside a world `width` by `height` cells.
+    pub fn size_within(self, width: u32, height: u32) -> UVec2 {
+        let origin = self.origin().0;
+        let end = (origin + IVec2::splat(CHUNK_SIZE)).min(IVec2::new(width as i32, height as i32));
+        (end - origin).max(IVec2::ZERO).as_uvec2()
+    }
+}
+
+impl From<(i32, i32)> for CellPos {
+    fn from((x, y): (i32, i32)) -> Self {
+        Self::new(x, y)
+    }
+}
+
+impl From<CellPos> for (i32, i32) {
+    fn from(cell: CellPos) -> Self {
+        (cell.x, cell.y)
+    }
+}
+
+// Cells move by offsets, and two cells are an offset apart.
+impl Add<IVec2> for CellPos {
+    type Output = CellPos;
+
+    fn add(self, offset: IVec2) -> CellPos {
+        CellPos(self.0 + offset)
+    }
+}
+
+impl Sub<IVec2> for CellPos {
+    type Output = CellPos;
+
+    fn sub(self, offset: IVec2) -> CellPos {
+        CellPos(self.0 - offset)
+    }
+}
+
+impl Sub for CellPos {
+    type Output = IVec2;
+
+    fn sub(self, other: CellPos) -> IVec2 {
+        self.0 - other.0
+    }
+}
+
+impl WorldPos {
+    pub fn extend(self, z: f32) -> Vec3 {
+        self.0.extend(z)
+    }
+}
+
+// Both newtypes read like the vectors they wrap, so `cell.x` and `world.distance(..)` work as
+// before; only building one takes saying which space it is in.
+impl Deref for CellPos {
+    type Target = IVec2;
+
+    fn deref(&self) -> &IVec2 {
+        &self.0
+    }
+}
+
+impl Deref for WorldPos {
+    type Target = Vec2;
+
+    fn deref(&self) -> &Vec2 {
+        &self.0
+    }
+}
+
+// --- HELPERS ---
+
+fn chunks_across(cells: u32) -> i32 {
+    cells.div_ceil(CHUNK_SIZE as u32) as i32
+}
@@ -0,0 +1,385 @@
+// --- IMPORTS ---
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    CachedPipelineState, Extent3d, PipelineCache, PipelineCacheError, PipelineDescriptor,
+    TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderAdapter, RenderDevice};
+use bevy::render::{Render, RenderApp, RenderSet};
+use bevy::sprite::MeshMaterial2d;
+use serde::{Deserialize, Serialize};
+
+use crate::backdrop::{MORTAR_DIM, WALL_DIM, WALL_MORTAR};
+use crate::display::DisplaySettings;
+use crate::frame_order::DisplaySet;
+use crate::shading::NEUTRAL_SHADE;
+use crate::thermal::ThermalView;
+use crate::{Particle, SimulationDisplay, SimulationMaterial, WORLD_UNITS_PER_CELL, WorldLayout};
+
+// --- CONSTANTS ---
+// The shader the world is normally drawn with; a pipeline built from it that fails means the GPU
+// display pass doesn't work here.
+pub const WORLD_SHADER: &str = "shaders/falling_sand.wgsl";
+// Every texture the world's material samples, and how they are used.
+const DISPLAY_FORMATS: [TextureFormat; 5] = [
+    TextureFormat::Rgba8Uint,
+    TextureFormat::Rgba8Unorm,
+    TextureFormat::Rg8Unorm,
+    TextureFormat::R8Unorm,
+    TextureFormat::R8Uint,
+];
+const DISPLAY_USAGES: TextureUsages = TextureUsages::TEXTURE_BINDING.union(TextureUsages::COPY_DST);
+// ..and how many textures and uniforms it binds at once.
+const DISPLAY_TEXTURES: u32 = 6;
+const DISPLAY_UNIFORMS: u32 = 4;
+const STAIN_COLOR: Color = Color::linear_rgb(0.06, 0.05, 0.04);
+const STAIN_OPACITY: f32 = 0.85;
+// What fully charged cells glow with; keep in sync with the shader's `CHARGE_GLOW`.
+const CHARGE_GLOW: LinearRgba = LinearRgba::rgb(0.25, 0.4, 0.8);
+const NOTICE_COLOR: Color = Color::srgb(1.0, 0.6, 0.3);
+
+// --- PLUGIN ---
+
+// The world is normally drawn by a shader that decodes the state texture. The display settings
+// can switch to the CPU display backend at any time instead, which decodes the state texture into
+// colors on the CPU and uploads those as a plain sprite every time the world changed, leaving the
+// GPU nothing but a textured quad to draw. Where the shader pass can't run at all, because the GPU
+// or WebGL lacks one of its texture formats or binding slots, or its pipeline fails to build, the
+// world would stay black, so the CPU display takes over for good, with a notice saying why.
+// Autotiling and upscaling are shader effects and are off on the CPU display; everything else
+// looks the same. This is only about drawing; where the world steps is gpu_sim.rs's setting.
+pub struct CpuDisplayPlugin;
+
+impl Plugin for CpuDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        let failure = PipelineFailure::default();
+        app.insert_resource(failure.clone())
+            .init_resource::<DisplayBackend>()
+            .add_systems(Startup, check_display_support.after(crate::setup))
+            .add_systems(
+                Update,
+                (
+                    fall_back_on_pipeline_failure,
+                    pick_backend,
+                    switch_backend.run_if(resource_changed::<DisplayBackend>),
+                    resize_cpu_sprite.run_if(
+                        resource_exists::<CpuDisplay>.and(resource_changed::<WorldLayout>),
+                    ),
+                    draw_on_cpu.in_set(DisplaySet).run_if(resource_exists::<CpuDisplay>),
+                )
+                    .chain(),
+            );
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(failure)
+                .add_systems(Render, watch_world_pipeline.in_set(RenderSet::Cleanup));
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// Where the world's state texture is turned into colors: in the world's shader, or on the CPU.
+// Only the drawing moves; where the world steps is `SimulationBackend`'s to say. The display
+// settings pick one; it stays on the CPU where the GPU can't run the shader.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+pub enum DisplayBackend {
+    #[default]
+    Gpu,
+    Cpu,
+}
+
+impl DisplayBackend {
+    pub const ALL: [DisplayBackend; 2] = [DisplayBackend::Gpu, DisplayBackend::Cpu];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DisplayBackend::Gpu => "GPU (shader)",
+            DisplayBackend::Cpu => "CPU",
+        }
+    }
+}
+
+// Present once the GPU turned out not to be able to draw the world.
+#[derive(Resource)]
+struct GpuUnavailable;
+
+// Set from the render world once the world's pipeline failed to build.
+#[derive(Resource, Clone, Default)]
+struct PipelineFailure(Arc<AtomicBool>);
+
+// The sprite the world is drawn into while on the CPU backend.
+#[derive(Resource)]
+struct CpuDisplay {
+    sprite: Entity,
+    image: Handle<Image>,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct FallbackNotice;
+
+// --- SYSTEMS ---
+
+// Falls back straight away when the GPU can't sample the state texture the way the shader does.
+fn check_display_support(
+    mut commands: Commands,
+    adapter: Option<Res<RenderAdapter>>,
+    device: Option<Res<RenderDevice>>,
+) {
+    let (Some(adapter), Some(device)) = (adapter, device) else { return };
+    let limits = device.limits();
+    let unsupported = DISPLAY_FORMATS.iter().find(|&&format| {
+        !adapter.get_texture_format_features(format).allowed_usages.contains(DISPLAY_USAGES)
+    });
+    let reason = if let Some(format) = unsupported {
+        format!("{:?} textures aren't supported", format)
+    } else if limits.max_sampled_textures_per_shader_stage < DISPLAY_TEXTURES {
+        "not enough texture slots".to_string()
+    } else if limits.max_uniform_buffers_per_shader_stage < DISPLAY_UNIFORMS {
+        "not enough uniform slots".to_string()
+    } else {
+        return;
+    };
+    fall_back(&mut commands, &reason);
+}
+
+fn fall_back_on_pipeline_failure(
+    mut commands: Commands,
+    failure: Res<PipelineFailure>,
+    unavailable: Option<Res<GpuUnavailable>>,
+) {
+    if unavailable.is_none() && failure.0.load(Ordering::Relaxed) {
+        fall_back(&mut commands, "the world's shader failed to build");
+    }
+}
+
+// The settings' backend, or the CPU one wherever the GPU can't draw the world.
+fn pick_backend(
+    settings: Res<DisplaySettings>,
+    unavailable: Option<Res<GpuUnavailable>>,
+    mut backend: ResMut<DisplayBackend>,
+) {
+    let wanted = if unavailable.is_some() { DisplayBackend::Cpu } else { settings.backend };
+    if *backend != wanted {
+        info!("Drawing the world with the {} backend", wanted.label());
+        *backend = wanted;
+    }
+}
+
+// Sets up the sprite the CPU backend draws into, or takes it down again and shows the shader's
+// quad.
+fn switch_backend(
+    mut commands: Commands,
+    backend: Res<DisplayBackend>,
+    cpu_display: Option<Res<CpuDisplay>>,
+    display: Res<SimulationDisplay>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_quad: Query<&mut Visibility, With<MeshMaterial2d<SimulationMaterial>>>,
+) {
+    match (*backend, cpu_display) {
+        (DisplayBackend::Cpu, None) => {
+            let Some(cpu_display) = spawn_cpu_sprite(&mut commands, &display, &mut images) else {
+                return;
+            };
+            commands.insert_resource(cpu_display);
+        }
+        (DisplayBackend::Gpu, Some(cpu_display)) => {
+            commands.entity(cpu_display.sprite).despawn();
+            images.remove(&cpu_display.image);
+            commands.remove_resource::<CpuDisplay>();
+            for mut visibility in &mut q_quad {
+                visibility.set_if_neq(Visibility::Inherited);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Makes the sprite over at the world's new size when it is resized.
+fn resize_cpu_sprite(
+    mut commands: Commands,
+    mut cpu_display: ResMut<CpuDisplay>,
+    display: Res<SimulationDisplay>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    commands.entity(cpu_display.sprite).despawn();
+    images.remove(&cpu_display.image);
+    if let Some(resized) = spawn_cpu_sprite(&mut commands, &display, &mut images) {
+        *cpu_display = resized;
+    }
+}
+
+// Decodes the state texture the way the shader would: materials, weathered, stained and shaded, or
+// the scaled temperatures while the thermal view is on.
+fn draw_on_cpu(
+    cpu_display: Res<CpuDisplay>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    display: Res<SimulationDisplay>,
+    thermal: Res<ThermalView>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_quad: Query<&mut Visibility, With<MeshMaterial2d<SimulationMaterial>>>,
+) {
+    // The shader's quad stays hidden; it would draw black over the sprite.
+    for mut visibility in &mut q_quad {
+        visibility.set_if_neq(Visibility::Hidden);
+    }
+    // Only when the state texture was refreshed from the grid.
+    let refreshed = image_events
+        .read()
+        .any(|event| {
+            event.is_modified(&display.state_image)
+                || event.is_modified(&display.shade_image)
+                || event.is_modified(&display.backdrop_image)
+        });
+    if !refreshed && !cpu_display.is_added() {
+        return;
+    }
+    let Some(state) = images.get(&display.state_image) else { return };
+    let Some(data) = state.data.as_ref() else { return };
+    let Some(shades) = images.get(&display.shade_image).and_then(|image| image.data.as_ref())
+    else {
+        return;
+    };
+    let Some(walls) = images.get(&display.backdrop_image).and_then(|image| image.data.as_ref())
+    else {
+        return;
+    };
+    let colors: Vec<u8> = data
+        .as_chunks::<4>()
+        .0
+        .iter()
+        .zip(shades)
+        .zip(walls)
+        .flat_map(|((&texel, &shade), &wall)| texel_color(texel, shade, wall, thermal.enabled))
+        .collect();
+    if let Some(image) = images.get_mut(&cpu_display.image) {
+        image.data = Some(colors);
+    }
+}
+
+// Render world: flags the world's pipeline once building it failed for good. Missing shaders are
+// retried by the pipeline cache, so those don't count.
+fn watch_world_pipeline(cache: Res<PipelineCache>, failure: Res<PipelineFailure>) {
+    if failure.0.load(Ordering::Relaxed) {
+        return;
+    }
+    let failed = cache.pipelines().any(|pipeline| {
+        let PipelineDescriptor::RenderPipelineDescriptor(descriptor) = &pipeline.descriptor else {
+            return false;
+        };
+        let world_shader = descriptor.fragment.as_ref().is_some_and(|fragment| {
+            fragment.shader.path().is_some_and(|path| path.path().ends_with(WORLD_SHADER))
+        });
+        let failed = matches!(
+            &pipeline.state,
+            CachedPipelineState::Err(err) if !matches!(
+                err,
+                PipelineCacheError::ShaderNotLoaded(_)
+                    | PipelineCacheError::ShaderImportNotYetAvailable
+            )
+        );
+        world_shader && failed
+    });
+    if failed {
+        failure.0.store(true, Ordering::Relaxed);
+    }
+}
+
+// --- HELPERS ---
+
+// The sRGB color the shader gives a texel of the state texture with this shade and this texel of
+// the backdrop behind it, or its temperature's while the thermal view is on.
+pub fn texel_color(
+    [id, heat, weathered, stain]: [u8; 4],
+    shade: u8,
+    wall: u8,
+    thermal: bool,
+) -> [u8; 4] {
+    // Outside the thermal view, the green channel is the cell's charge instead.
+    let charge = heat;
+    let color = if thermal {
+        crate::thermal::inferno(heat as f32 / 255.0)
+    } else {
+        // An empty cell shows the wall behind it, if any.
+        let (id, dim) = match (id, wall & !WALL_MORTAR) {
+            (0, 0) | (1.., _) => (id, 1.0),
+            (_, wall_id) if wall & WALL_MORTAR != 0 => (wall_id, WALL_DIM * MORTAR_DIM),
+            (_, wall_id) => (wall_id, WALL_DIM),
+        };
+        let particle = Particle::ALL.get(id as usize).copied().unwrap_or_default();
+        let fresh = particle.color();
+        let color = match particle.weathering() {
+            Some((aged, _)) => fresh.mix(&aged, weathered as f32 / 255.0),
+            None => fresh,
+        };
+        let stained = color.mix(&STAIN_COLOR, stain as f32 / 255.0 * STAIN_OPACITY);
+        let linear = stained.to_linear() * dim;
+        let brightness = shade as f32 / NEUTRAL_SHADE as f32;
+        let glow = CHARGE_GLOW * (charge as f32 / 255.0);
+        Color::from(LinearRgba { alpha: linear.alpha, ..linear * brightness + glow })
+    };
+    color.to_srgba().to_u8_array()
+}
+
+// Gives up on the GPU display for good, saying why.
+pub fn fall_back(commands: &mut Commands, reason: &str) {
+    warn!("Drawing the world on the CPU: {}", reason);
+    commands.spawn((
+        FallbackNotice,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::new(format!(
+            "GPU display unavailable ({}): drawing on the CPU, without autotiling or upscaling",
+            reason
+        )),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(NOTICE_COLOR),
+    ));
+    commands.insert_resource(GpuUnavailable);
+}
+
+// A sprite the size of the world, for the CPU backend to draw into.
+fn spawn_cpu_sprite(
+    commands: &mut Commands,
+    display: &SimulationDisplay,
+    images: &mut Assets<Image>,
+) -> Option<CpuDisplay> {
+    let size = images.get(&display.state_image)?.texture_descriptor.size;
+    let mut image = Image::new_fill(
+        Extent3d {
+            depth_or_array_layers: 1,
+            ..size
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let image = images.add(image);
+    let sprite = commands
+        .spawn(Sprite {
+            image: image.clone(),
+            custom_size: Some(UVec2::new(size.width, size.height).as_vec2() * WORLD_UNITS_PER_CELL),
+            ..default()
+        })
+        .id();
+    Some(CpuDisplay { sprite, image })
+}
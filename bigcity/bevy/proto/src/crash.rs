@@ -0,0 +1,197 @@
+// --- IMPORTS ---
+use std::collections::VecDeque;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
+
+use bevy::input::ButtonState;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::MouseButtonInput;
+use bevy::prelude::*;
+use serde::Serialize;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::WorldView;
+use crate::persist::user_data_dir;
+use crate::saves::SaveError;
+use crate::sim::{SimParams, SimulationGrid, SimulationSet, SimulationStats};
+use crate::world_file::WorldSerializer;
+use crate::worldgen::StartingTerrain;
+
+// --- CONSTANTS ---
+const CRASH_FOLDER: &str = "crashes";
+// How often, in real seconds, the world is copied aside for a crash report.
+const SNAPSHOT_SECS: f32 = 1.0;
+// How many of the latest key presses and clicks a crash report keeps.
+const RECENT_INPUTS: usize = 256;
+
+// --- PLUGIN ---
+
+// Crash reports, so a panic in a new rule comes with a world it can be reproduced in: the world is
+// copied aside every second, with the simulation parameters and the worldgen seed, and the latest
+// key presses and clicks are kept along with the tick they came in. When anything panics, a hook
+// writes all of that to a folder of its own in `crashes` in the user data directory, as a saved
+// world, `params.ron` and `report.ron` with the panic's message and where it happened, before the
+// game goes down as it would have anyway. The world in the report can be up to a second older
+// than the crash; the inputs since it was copied are in the report too.
+pub struct CrashHandlerPlugin;
+
+impl Plugin for CrashHandlerPlugin {
+    fn build(&self, app: &mut App) {
+        let state = Arc::new(Mutex::new(CrashState::default()));
+        install_hook(state.clone());
+        app.insert_resource(CrashRecorder {
+            state,
+            timer: Timer::from_seconds(SNAPSHOT_SECS, TimerMode::Repeating),
+        })
+        .add_systems(Update, (record_inputs, copy_world.after(SimulationSet)));
+    }
+}
+
+// --- TYPES ---
+
+// What the panic hook has to go on. It lives outside the ECS world, which a panicking system may
+// have left in any state.
+#[derive(Default)]
+struct CrashState {
+    grid: Option<SimulationGrid>,
+    params: Option<SimParams>,
+    seed: Option<u64>,
+    // The tick the world was copied at, and the latest tick seen.
+    copied_tick: u64,
+    tick: u64,
+    inputs: VecDeque<RecordedInput>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct RecordedInput {
+    tick: u64,
+    input: String,
+    pressed: bool,
+    // The cell under the cursor, for clicks.
+    cell: Option<(i32, i32)>,
+}
+
+#[derive(Serialize, Debug)]
+struct CrashReport {
+    message: String,
+    location: Option<String>,
+    tick: u64,
+    // The tick the saved world is from.
+    world_tick: u64,
+    seed: Option<u64>,
+    inputs: Vec<RecordedInput>,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct CrashRecorder {
+    state: Arc<Mutex<CrashState>>,
+    timer: Timer,
+}
+
+// --- SYSTEMS ---
+
+fn record_inputs(
+    recorder: Res<CrashRecorder>,
+    stats: Res<SimulationStats>,
+    view: WorldView,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+) {
+    let keys = keyboard.read().filter(|event| !event.repeat).map(|event| RecordedInput {
+        tick: stats.tick,
+        input: format!("{:?}", event.key_code),
+        pressed: event.state == ButtonState::Pressed,
+        cell: None,
+    });
+    let cell = view.cursor_cell().map(|cell| (cell.x, cell.y));
+    let clicks = buttons.read().map(|event| RecordedInput {
+        tick: stats.tick,
+        input: format!("{:?}", event.button),
+        pressed: event.state == ButtonState::Pressed,
+        cell,
+    });
+    let inputs: Vec<_> = keys.chain(clicks).collect();
+
+    let Ok(mut state) = recorder.state.lock() else { return };
+    state.tick = stats.tick;
+    for input in inputs {
+        if state.inputs.len() == RECENT_INPUTS {
+            state.inputs.pop_front();
+        }
+        state.inputs.push_back(input);
+    }
+}
+
+fn copy_world(
+    time: Res<Time<Real>>,
+    grid: Res<SimulationGrid>,
+    params: Res<SimParams>,
+    stats: Res<SimulationStats>,
+    terrain: Option<Res<StartingTerrain>>,
+    mut recorder: ResMut<CrashRecorder>,
+) {
+    recorder.timer.tick(time.delta());
+    let Ok(mut state) = recorder.state.lock() else { return };
+    if state.grid.is_some() && !recorder.timer.just_finished() {
+        return;
+    }
+    state.grid = Some(grid.clone());
+    state.params = Some(params.clone());
+    state.seed = terrain.and_then(|terrain| terrain.seed());
+    state.copied_tick = stats.tick;
+}
+
+// --- HELPERS ---
+
+fn install_hook(state: Arc<Mutex<CrashState>>) {
+    let previous = std::panic::take_hook();
+    let reported = AtomicBool::new(false);
+    std::panic::set_hook(Box::new(move |info| {
+        // Only the first panic gets a report; the ones after it tend to follow from it.
+        if !reported.swap(true, Ordering::SeqCst) {
+            match write_report(&state, info) {
+                Ok(dir) => error!("Crash report written to {:?}", dir),
+                Err(err) => error!("Could not write a crash report: {}", err),
+            }
+        }
+        previous(info);
+    }));
+}
+
+fn write_report(state: &Mutex<CrashState>, info: &PanicHookInfo) -> Result<PathBuf, SaveError> {
+    // A panic while the state was being updated leaves it poisoned but still worth writing.
+    let state = match state.try_lock() {
+        Ok(state) => state,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            return Err(std::io::Error::other("the world was being copied").into());
+        }
+    };
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let dir = user_data_dir()
+        .ok_or(SaveError::NoDataDir)?
+        .join(CRASH_FOLDER)
+        .join(format!("crash_{}", stamp));
+    std::fs::create_dir_all(&dir)?;
+
+    let report = CrashReport {
+        message: info.payload_as_str().unwrap_or("(no message)").to_string(),
+        location: info.location().map(ToString::to_string),
+        tick: state.tick,
+        world_tick: state.copied_tick,
+        seed: state.seed,
+        inputs: state.inputs.iter().cloned().collect(),
+    };
+    std::fs::write(dir.join("report.ron"), ron::ser::to_string_pretty(&report, default())?)?;
+    if let Some(params) = &state.params {
+        std::fs::write(dir.join("params.ron"), ron::ser::to_string_pretty(params, default())?)?;
+    }
+    if let Some(grid) = &state.grid {
+        WorldSerializer::save(grid, &dir.join("world.cells.png"), &dir.join("world.cells.ron"))?;
+    }
+    Ok(dir)
+}
@@ -0,0 +1,350 @@
+// --- IMPORTS ---
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::inventory::Inventory;
+use crate::levels::{ActiveLevel, Level, LevelCompleted, LevelShape, WinCondition};
+use crate::pan_zoom::ctrl_held;
+use crate::persist::{load_user_ron, save_user_ron, user_data_dir};
+use crate::sim::roll;
+
+// --- CONSTANTS ---
+const RESULTS_FILE: &str = "daily.ron";
+const EXPORT_FILE: &str = "daily_results.csv";
+// Daily levels are laid out for a world this size, like the bundled ones.
+const DAILY_SIZE: (i32, i32) = (256, 144);
+// The bedrock floor comes in steps this wide, and its top row is at most this high.
+const STEP_WIDTH: i32 = 16;
+const FLOOR_TOP: i32 = 14;
+// Mixed into the day's number so the daily worlds don't share their randomness with anything else.
+const DAILY_SALT: u64 = 0xDA11_C4A1;
+const SECS_PER_DAY: u64 = 86_400;
+
+// --- PLUGIN ---
+
+// The daily challenge (Ctrl+D, or from the level select screen): a level made up from the date,
+// the same for everyone on the same UTC day. Its world, what has to be done in it and how much of
+// each material there is to do it with all follow from the day's seed, and it plays like any other
+// level. Every day's best result, the fewest ticks and with them the fewest cells used, is kept in
+// `daily.ron` in the user data directory, and Ctrl+Shift+D exports them all to
+// `daily_results.csv` beside it.
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<StartDailyChallenge>()
+            .init_resource::<DailyChallenge>()
+            .insert_resource(load_user_ron::<DailyResults>(RESULTS_FILE).unwrap_or_default())
+            .add_systems(
+                Update,
+                (daily_keys, start_daily_challenge, record_daily_result, export_daily_results)
+                    .chain(),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// What a day asks for. The day's seed picks one and lays it out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Challenge {
+    // Pour water into a basin over a ledge.
+    Basin,
+    // Fill a bin held up on a pillar with sand.
+    Silo,
+    // Keep a fire burning for a while.
+    Bonfire,
+}
+
+impl Challenge {
+    const ALL: [Challenge; 3] = [Challenge::Basin, Challenge::Silo, Challenge::Bonfire];
+}
+
+// The best a day was done in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+struct DailyResult {
+    ticks: u64,
+    cells_used: u32,
+}
+
+// --- EVENTS ---
+
+// Starts today's challenge, as Ctrl+D does.
+#[derive(Event, Debug, Clone, Copy)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct StartDailyChallenge;
+
+// --- RESOURCES ---
+
+// The day whose challenge was last started, with its level.
+#[derive(Resource, Default)]
+struct DailyChallenge {
+    running: Option<(i64, Handle<Level>)>,
+}
+
+// Every day's best result, by date.
+#[derive(Resource, Serialize, Deserialize, Default)]
+struct DailyResults {
+    days: BTreeMap<String, DailyResult>,
+}
+
+// --- SYSTEMS ---
+
+fn daily_keys(keys: Res<ButtonInput<KeyCode>>, mut starts: EventWriter<StartDailyChallenge>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if ctrl_held(&keys) && !shift && keys.just_pressed(KeyCode::KeyD) {
+        starts.write(StartDailyChallenge);
+    }
+}
+
+fn start_daily_challenge(
+    mut starts: EventReader<StartDailyChallenge>,
+    mut daily: ResMut<DailyChallenge>,
+    mut levels: ResMut<Assets<Level>>,
+    mut active: ResMut<ActiveLevel>,
+) {
+    if starts.read().count() == 0 {
+        return;
+    }
+    let day = today();
+    // The same day's level is the same asset, however often it is restarted.
+    let handle = match &daily.running {
+        Some((running, handle)) if *running == day => handle.clone(),
+        _ => levels.add(daily_level(day)),
+    };
+    info!("Daily challenge for {}", date_text(day));
+    active.start(handle.clone());
+    daily.running = Some((day, handle));
+}
+
+fn record_daily_result(
+    mut completions: EventReader<LevelCompleted>,
+    daily: Res<DailyChallenge>,
+    levels: Res<Assets<Level>>,
+    inventory: Res<Inventory>,
+    mut results: ResMut<DailyResults>,
+) {
+    for completion in completions.read() {
+        let Some((day, handle)) = &daily.running else { continue };
+        let Some(level) = levels.get(handle).filter(|level| level.id == completion.id) else {
+            continue;
+        };
+        let cells_used = (level.materials.iter())
+            .filter_map(|&(particle, budget)| {
+                Some(budget?.saturating_sub(inventory.remaining(particle)?))
+            })
+            .sum();
+        let result = DailyResult {
+            ticks: completion.ticks,
+            cells_used,
+        };
+        let best = results.days.entry(date_text(*day)).or_insert(result);
+        if (result.ticks, result.cells_used) < (best.ticks, best.cells_used) {
+            *best = result;
+        }
+        info!("Daily challenge done in {} ticks with {} cells", result.ticks, result.cells_used);
+        save_user_ron(RESULTS_FILE, &*results);
+    }
+}
+
+fn export_daily_results(keys: Res<ButtonInput<KeyCode>>, results: Res<DailyResults>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !(ctrl_held(&keys) && shift && keys.just_pressed(KeyCode::KeyD)) {
+        return;
+    }
+    let Some(dir) = user_data_dir() else {
+        warn!("Can't export the daily results: there is no user data directory");
+        return;
+    };
+    let mut csv = "date,ticks,cells_used\n".to_string();
+    for (date, result) in &results.days {
+        let _ = writeln!(csv, "{},{},{}", date, result.ticks, result.cells_used);
+    }
+    let path = dir.join(EXPORT_FILE);
+    match std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, csv)) {
+        Ok(()) => info!("Exported {} daily results to {:?}", results.days.len(), path),
+        Err(err) => warn!("Couldn't export the daily results: {}", err),
+    }
+}
+
+// --- HELPERS ---
+
+// Today's number, in whole UTC days since 1970-01-01.
+pub fn today() -> i64 {
+    let now = web_time::SystemTime::now().duration_since(web_time::UNIX_EPOCH);
+    now.map_or(0, |since| (since.as_secs() / SECS_PER_DAY) as i64)
+}
+
+// The id the challenge of `day` plays under, e.g. "daily-2026-10-14".
+pub fn daily_id(day: i64) -> String {
+    format!("daily-{}", date_text(day))
+}
+
+fn date_text(day: i64) -> String {
+    let (year, month, date) = civil_date(day);
+    format!("{:04}-{:02}-{:02}", year, month, date)
+}
+
+// The calendar date `day` days after 1970-01-01, as (year, month, day of the month). This is
+// Howard Hinnant's `civil_from_days`, counting in 400-year eras of 146097 days from March.
+fn civil_date(day: i64) -> (i64, u32, u32) {
+    let z = day + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let date = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, date)
+}
+
+// The challenge of `day`: its world, its materials with their budgets and what wins it. Nothing
+// but the day goes into it.
+fn daily_level(day: i64) -> Level {
+    let seed = (day as u64).wrapping_add(DAILY_SALT);
+    // A number in `low..high` for the `salt`th choice made about the day.
+    let pick = |salt: i32, low: i32, high: i32| {
+        low + (roll(salt, 0, seed) * (high - low) as f32) as i32
+    };
+    let challenge = Challenge::ALL[pick(0, 0, Challenge::ALL.len() as i32) as usize];
+    let (width, height) = DAILY_SIZE;
+
+    // A floor of bedrock steps.
+    let mut shapes: Vec<LevelShape> = (0..width / STEP_WIDTH)
+        .map(|step| LevelShape {
+            particle: Particle::Bedrock,
+            min: (step * STEP_WIDTH, 0),
+            max: (step * STEP_WIDTH + STEP_WIDTH - 1, pick(100 + step, 4, FLOOR_TOP)),
+        })
+        .collect();
+    let mut bedrock = |min: (i32, i32), max: (i32, i32)| {
+        shapes.push(LevelShape {
+            particle: Particle::Bedrock,
+            min,
+            max,
+        });
+    };
+    let floor = FLOOR_TOP + 1;
+    let (description, materials, win) = match challenge {
+        Challenge::Basin => {
+            let across = pick(1, 40, 80);
+            let left = pick(2, 128, width - across - 8);
+            let deep = pick(3, 24, 56);
+            bedrock((left, 0), (left + across - 1, floor - 1));
+            bedrock((left, floor), (left + 3, floor + deep));
+            bedrock((left + across - 4, floor), (left + across - 1, floor + deep));
+            let ledge = pick(4, 70, height - 30);
+            bedrock((pick(5, 16, 48), ledge), (left - pick(6, 8, 32), ledge + 3));
+            let (min, max) = ((left + 4, floor), (left + across - 5, floor + deep));
+            let volume = ((max.0 - min.0 + 1) * (max.1 - min.1 + 1)) as u32;
+            let win = WinCondition::InRegion {
+                particle: Particle::Water,
+                min,
+                max,
+                at_least: volume * 3 / 5,
+                at_most: None,
+            };
+            let materials = vec![
+                (Particle::Water, Some(round_budget(volume * 3 / 2))),
+                (Particle::Glass, Some(round_budget(pick(7, 100, 400) as u32))),
+            ];
+            ("Pour water over the ledge into the basin.", materials, win)
+        }
+        Challenge::Silo => {
+            let across = pick(1, 30, 50);
+            let left = pick(2, 64, width - across - 16);
+            let bottom = pick(3, 40, 80);
+            let tall = pick(4, 16, 32);
+            let middle = left + across / 2;
+            bedrock((middle - 3, 0), (middle + 3, bottom - 1));
+            bedrock((left, bottom), (left + across - 1, bottom + 2));
+            bedrock((left, bottom + 3), (left + 2, bottom + tall));
+            bedrock((left + across - 3, bottom + 3), (left + across - 1, bottom + tall));
+            let (min, max) = ((left + 3, bottom + 3), (left + across - 4, bottom + tall));
+            let volume = ((max.0 - min.0 + 1) * (max.1 - min.1 + 1)) as u32;
+            let win = WinCondition::InRegion {
+                particle: Particle::Sand,
+                min,
+                max,
+                at_least: volume / 2,
+                at_most: None,
+            };
+            let materials = vec![
+                (Particle::Sand, Some(round_budget(volume * 2))),
+                (Particle::Bedrock, Some(round_budget(pick(5, 100, 300) as u32))),
+            ];
+            ("Fill the bin on the pillar with sand.", materials, win)
+        }
+        Challenge::Bonfire => {
+            let across = pick(1, 40, 90);
+            let left = pick(2, 32, width - across - 32);
+            bedrock((left, 0), (left + across - 1, floor - 1));
+            bedrock((left, floor), (left + 3, floor + 12));
+            bedrock((left + across - 4, floor), (left + across - 1, floor + 12));
+            let win = WinCondition::KeepAlive {
+                particle: Particle::Fire,
+                at_least: pick(3, 100, 300) as u32,
+                ticks: pick(4, 300, 900) as u32,
+            };
+            let materials = vec![
+                (Particle::Oil, Some(round_budget(pick(5, 800, 2000) as u32))),
+                (Particle::Dust, Some(round_budget(pick(6, 300, 1200) as u32))),
+                (Particle::Fire, Some(round_budget(pick(7, 50, 200) as u32))),
+            ];
+            ("Keep a fire going in the pit.", materials, win)
+        }
+    };
+    Level {
+        id: daily_id(day),
+        name: format!("Daily challenge, {}", date_text(day)),
+        description: description.to_string(),
+        snapshot: None,
+        shapes,
+        zones: Vec::new(),
+        loops: Vec::new(),
+        timeline: None,
+        materials,
+        win: vec![win],
+    }
+}
+
+// Budgets are whole fifties, never none.
+fn round_budget(cells: u32) -> u32 {
+    (cells / 50).max(1) * 50
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_are_calendar_dates() {
+        assert_eq!(date_text(0), "1970-01-01");
+        assert_eq!(date_text(11_016), "2000-02-29");
+        assert_eq!(date_text(20_740), "2026-10-14");
+        assert_eq!(date_text(-1), "1969-12-31");
+    }
+
+    #[test]
+    fn a_day_always_gets_the_same_challenge() {
+        let (first, again) = (daily_level(20_740), daily_level(20_740));
+        assert_eq!(first.id, again.id);
+        assert_eq!(first.description, again.description);
+        assert_eq!(first.materials, again.materials);
+        let corners = |level: &Level| -> Vec<_> {
+            level.shapes.iter().map(|shape| (shape.min, shape.max)).collect()
+        };
+        assert_eq!(corners(&first), corners(&again));
+        // And the shapes stay in the world they are laid out for.
+        for (min, max) in corners(&first) {
+            assert!(min.0 >= 0 && min.1 >= 0 && max.0 < DAILY_SIZE.0 && max.1 < DAILY_SIZE.1);
+        }
+    }
+}
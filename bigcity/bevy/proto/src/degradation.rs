@@ -0,0 +1,206 @@
+// --- IMPORTS ---
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::sim::{SimulationSet, Subsystem, TickSchedule};
+
+// --- CONSTANTS ---
+// How long frames stay over the target before the next thing is shed, and how long under
+// RECOVER_SHARE of it before the last thing shed comes back, in seconds.
+const SHED_AFTER_SECS: f32 = 1.0;
+const RECOVER_AFTER_SECS: f32 = 5.0;
+const RECOVER_SHARE: f32 = 0.85;
+// How often a shed display refreshes the world's texture at most, in hertz.
+const SHED_UPLOAD_RATE: f32 = 30.0;
+
+// --- PLUGIN ---
+
+// Keeps frames inside a time budget under load by giving things up in the policy's order, how the
+// world looks before how it runs: by default decorative effects go first, then the display rate
+// (the world's texture refreshes at most 30 times a second), then the simulation's cadence (heat,
+// chemistry and aging run half as often, in coarser steps), and the simulation's resolution last
+// (at most one tick a frame, so the world slows down). The next step is shed once frames have been
+// over target for a second, and the last one comes back once they have been well under it for five.
+// A label at the bottom of the window says what is shed.
+pub struct DegradationPlugin;
+
+impl Plugin for DegradationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DegradationPolicy>()
+            .init_resource::<Degradation>()
+            .add_systems(Startup, spawn_degradation_label)
+            .add_systems(
+                Update,
+                (adjust_degradation, update_degradation_label).chain().after(SimulationSet),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// What can be given up under load.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum Shed {
+    Effects,
+    DisplayRate,
+    SimCadence,
+    SimResolution,
+}
+
+impl Shed {
+    fn label(self) -> &'static str {
+        match self {
+            Shed::Effects => "effects off",
+            Shed::DisplayRate => "display at 30 Hz",
+            Shed::SimCadence => "coarser heat and chemistry",
+            Shed::SimResolution => "one tick a frame",
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct DegradationPolicy {
+    pub enabled: bool,
+    // The longest a frame should take, in milliseconds.
+    pub target_ms: f32,
+    // What is shed, first to last, while frames take longer.
+    pub order: Vec<Shed>,
+}
+
+impl Default for DegradationPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            target_ms: 20.0,
+            order: vec![Shed::Effects, Shed::DisplayRate, Shed::SimCadence, Shed::SimResolution],
+        }
+    }
+}
+
+// What is shed right now, in the order it was. The quality settings, the display and the
+// simulation read it and hold back accordingly.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct Degradation {
+    shed: Vec<Shed>,
+}
+
+impl Degradation {
+    pub fn sheds(&self, shed: Shed) -> bool {
+        self.shed.contains(&shed)
+    }
+
+    // The tick schedule to run instead of `schedule`.
+    pub fn schedule(&self, schedule: &TickSchedule) -> TickSchedule {
+        let mut schedule = schedule.clone();
+        if self.sheds(Shed::SimCadence) {
+            for subsystem in [Subsystem::Heat, Subsystem::Chemistry, Subsystem::Aging] {
+                schedule.cadence_mut(subsystem).every *= 2;
+            }
+        }
+        if self.sheds(Shed::SimResolution) {
+            schedule.max_ticks_per_frame = 1;
+        }
+        schedule
+    }
+
+    // The world texture's refresh rate to use instead of `rate`; `None` refreshes every frame.
+    pub fn upload_rate(&self, rate: Option<f32>) -> Option<f32> {
+        if self.sheds(Shed::DisplayRate) {
+            Some(rate.map_or(SHED_UPLOAD_RATE, |rate| rate.min(SHED_UPLOAD_RATE)))
+        } else {
+            rate
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct DegradationLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_degradation_label(mut commands: Commands) {
+    commands.spawn((
+        DegradationLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.0),
+            left: Val::Px(8.0),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.8, 0.3)),
+    ));
+}
+
+// Sheds the next thing in the policy's order while frames stay over target, and brings back the
+// last thing shed while they stay well under it. Frames in the background are slowed down on
+// purpose (see focus.rs), so they don't count.
+fn adjust_degradation(
+    time: Res<Time>,
+    policy: Res<DegradationPolicy>,
+    diagnostics: Res<DiagnosticsStore>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut degradation: ResMut<Degradation>,
+    mut over: Local<f32>,
+    mut under: Local<f32>,
+) {
+    // A new policy starts over from nothing shed.
+    if policy.is_changed() && !policy.is_added() && !degradation.shed.is_empty() {
+        degradation.shed.clear();
+    }
+    let focused = q_window.single().is_ok_and(|window| window.focused);
+    let frame_ms = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame_time| frame_time.smoothed());
+    let Some(frame_ms) = frame_ms.filter(|_| policy.enabled && focused) else {
+        (*over, *under) = (0.0, 0.0);
+        if !policy.enabled && !degradation.shed.is_empty() {
+            degradation.shed.clear();
+        }
+        return;
+    };
+
+    let (frame_ms, delta) = (frame_ms as f32, time.delta_secs());
+    *over = if frame_ms > policy.target_ms { *over + delta } else { 0.0 };
+    *under = if frame_ms < policy.target_ms * RECOVER_SHARE { *under + delta } else { 0.0 };
+    if *over >= SHED_AFTER_SECS
+        && let Some(&next) = policy.order.get(degradation.shed.len())
+    {
+        *over = 0.0;
+        degradation.shed.push(next);
+        info!("Frames over {} ms, shedding: {}", policy.target_ms, next.label());
+    } else if *under >= RECOVER_AFTER_SECS && !degradation.shed.is_empty() {
+        *under = 0.0;
+        if let Some(restored) = degradation.shed.pop() {
+            info!("Frames back under budget, restored: {}", restored.label());
+        }
+    }
+}
+
+fn update_degradation_label(
+    degradation: Res<Degradation>,
+    mut q_label: Query<&mut Text, With<DegradationLabel>>,
+) {
+    if !degradation.is_changed() {
+        return;
+    }
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let shed: Vec<&str> = degradation.shed.iter().map(|shed| shed.label()).collect();
+    label.0 = if shed.is_empty() {
+        String::new()
+    } else {
+        format!("Under load: {}", shed.join(", "))
+    };
+}
@@ -0,0 +1,359 @@
+// --- IMPORTS ---
+use bevy::input::gamepad::GamepadButtonChangedEvent;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseMotion};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::ron_asset::RonAssetLoader;
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::coords::CellPos;
+use crate::player::BrushShape;
+use crate::{Particle, ScreenCamera, WorldLayout};
+
+// --- CONSTANTS ---
+const ATTRACT_SCRIPT: &str = "demos/attract.demo.ron";
+const IDLE_TIMEOUT_SECS: f32 = 60.0;
+
+// --- PLUGIN ---
+pub struct DemoPlugin;
+
+impl Plugin for DemoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DemoScript>()
+            .register_asset_loader(RonAssetLoader::<DemoScript>::new(&["demo.ron"]))
+            .init_resource::<DemoSettings>()
+            .init_resource::<DemoPlayback>()
+            .init_resource::<IdleTimer>()
+            .add_systems(Startup, spawn_caption)
+            .add_systems(
+                Update,
+                (track_idle, toggle_demo, start_attract_demo, advance_demo)
+                    .chain()
+                    .before(WorldCommandSet),
+            );
+    }
+}
+
+// --- ASSETS ---
+
+// A scripted sequence of timed actions, loaded from `*.demo.ron`. Times are in seconds from the
+// start of playback and positions are in simulation cells, with (0, 0) at the bottom left.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct DemoScript {
+    pub steps: Vec<DemoStep>,
+    #[serde(default)]
+    pub looping: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DemoStep {
+    pub at: f32,
+    pub action: DemoAction,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum DemoAction {
+    // Drag a brush from `from` to `to` over `duration` seconds, painting every frame.
+    Paint {
+        particle: Particle,
+        from: (f32, f32),
+        to: (f32, f32),
+        brush: i32,
+        duration: f32,
+    },
+    // Glide the screen camera so it centers `center` at the given zoom (1.0 = whole sim).
+    Camera {
+        center: (f32, f32),
+        zoom: f32,
+        duration: f32,
+    },
+    Caption {
+        text: String,
+        duration: f32,
+    },
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct DemoSettings {
+    // Played when nobody has touched any input for `idle_timeout` seconds.
+    pub attract_script: Option<String>,
+    pub idle_timeout: f32,
+}
+
+impl Default for DemoSettings {
+    fn default() -> Self {
+        Self {
+            attract_script: Some(ATTRACT_SCRIPT.to_string()),
+            idle_timeout: IDLE_TIMEOUT_SECS,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct IdleTimer(f32);
+
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct DemoPlayback {
+    script: Option<Handle<DemoScript>>,
+    // Attract-mode demos stop as soon as the user does anything.
+    attract: bool,
+    elapsed: f32,
+    next_step: usize,
+    paints: Vec<ActivePaint>,
+    camera: Option<CameraTween>,
+    caption_until: f32,
+    // Set when playback ends so the view and caption get handed back to the user.
+    needs_reset: bool,
+}
+
+impl DemoPlayback {
+    pub fn is_playing(&self) -> bool {
+        self.script.is_some()
+    }
+
+    pub fn play(&mut self, script: Handle<DemoScript>, attract: bool) {
+        *self = Self {
+            script: Some(script),
+            attract,
+            ..default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        *self = Self {
+            needs_reset: true,
+            ..default()
+        };
+    }
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct ActivePaint {
+    particle: Particle,
+    from: Vec2,
+    to: Vec2,
+    brush: i32,
+    start: f32,
+    duration: f32,
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct CameraTween {
+    from: (Vec3, f32),
+    to: (Vec3, f32),
+    start: f32,
+    duration: f32,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct DemoCaption;
+
+// --- SYSTEMS ---
+
+fn spawn_caption(mut commands: Commands) {
+    commands.spawn((
+        DemoCaption,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(24.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 28.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn track_idle(
+    time: Res<Time>,
+    mut idle: ResMut<IdleTimer>,
+    mut playback: ResMut<DemoPlayback>,
+    mut keys: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut motion: EventReader<MouseMotion>,
+    mut gamepad: EventReader<GamepadButtonChangedEvent>,
+) {
+    let activity = keys.read().count()
+        + buttons.read().count()
+        + motion.read().count()
+        + gamepad.read().count()
+        > 0;
+
+    if activity {
+        idle.0 = 0.0;
+        if playback.attract {
+            playback.stop();
+        }
+    } else {
+        idle.0 += time.delta_secs();
+    }
+}
+
+fn toggle_demo(
+    keys: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<DemoSettings>,
+    mut playback: ResMut<DemoPlayback>,
+) {
+    if !keys.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    if playback.is_playing() {
+        playback.stop();
+        info!("Demo stopped");
+    } else if let Some(path) = &settings.attract_script {
+        playback.play(asset_server.load(path), false);
+        info!("Demo started: {}", path);
+    }
+}
+
+fn start_attract_demo(
+    asset_server: Res<AssetServer>,
+    settings: Res<DemoSettings>,
+    idle: Res<IdleTimer>,
+    mut playback: ResMut<DemoPlayback>,
+) {
+    if playback.is_playing() || idle.0 < settings.idle_timeout {
+        return;
+    }
+    if let Some(path) = &settings.attract_script {
+        playback.play(asset_server.load(path), true);
+    }
+}
+
+fn advance_demo(
+    time: Res<Time>,
+    layout: Res<WorldLayout>,
+    scripts: Res<Assets<DemoScript>>,
+    mut playback: ResMut<DemoPlayback>,
+    mut commands: ResMut<WorldCommands>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+    mut q_caption: Query<&mut Text, With<DemoCaption>>,
+) {
+    let Ok((mut camera_transform, mut projection)) = q_camera.single_mut() else { return };
+    let Ok(mut caption) = q_caption.single_mut() else { return };
+
+    if playback.needs_reset {
+        playback.needs_reset = false;
+        *camera_transform = Transform::default();
+        set_projection_scale(&mut projection, 1.0);
+        caption.0.clear();
+    }
+
+    let Some(handle) = playback.script.clone() else { return };
+    // Still loading; don't start the clock yet.
+    let Some(script) = scripts.get(&handle) else { return };
+
+    playback.elapsed += time.delta_secs();
+    let now = playback.elapsed;
+
+    // Kick off every step whose time has come.
+    while let Some(step) = script.steps.get(playback.next_step) {
+        if step.at > now {
+            break;
+        }
+        playback.next_step += 1;
+
+        match &step.action {
+            DemoAction::Paint { particle, from, to, brush, duration } => {
+                playback.paints.push(ActivePaint {
+                    particle: *particle,
+                    from: Vec2::from(*from),
+                    to: Vec2::from(*to),
+                    brush: *brush,
+                    start: step.at,
+                    duration: *duration,
+                });
+            }
+            DemoAction::Camera { center, zoom, duration } => {
+                let center = layout.cell_to_world(Vec2::from(*center)).extend(0.0);
+                playback.camera = Some(CameraTween {
+                    from: (camera_transform.translation, projection_scale(&projection)),
+                    to: (center, 1.0 / zoom.max(0.01)),
+                    start: step.at,
+                    duration: *duration,
+                });
+            }
+            DemoAction::Caption { text, duration } => {
+                caption.0 = text.clone();
+                playback.caption_until = step.at + duration;
+            }
+        }
+    }
+
+    // Paint strokes in progress.
+    for paint in &playback.paints {
+        let t = progress(now, paint.start, paint.duration);
+        let position = CellPos(paint.from.lerp(paint.to, t).round().as_ivec2());
+        commands.push(WorldCommand::Place {
+            particle: paint.particle,
+            data: 0,
+            cells: BrushShape::Square.cells(position, paint.brush).collect(),
+        });
+    }
+    playback.paints.retain(|p| now < p.start + p.duration);
+
+    // Camera glide in progress.
+    if let Some(tween) = &playback.camera {
+        let t = progress(now, tween.start, tween.duration);
+        camera_transform.translation = tween.from.0.lerp(tween.to.0, t);
+        set_projection_scale(&mut projection, tween.from.1.lerp(tween.to.1, t));
+        if t >= 1.0 {
+            playback.camera = None;
+        }
+    }
+
+    if now >= playback.caption_until {
+        caption.0.clear();
+    }
+
+    let finished = playback.next_step >= script.steps.len()
+        && playback.paints.is_empty()
+        && playback.camera.is_none()
+        && caption.0.is_empty();
+    if finished {
+        if script.looping {
+            let attract = playback.attract;
+            playback.play(handle, attract);
+        } else {
+            playback.stop();
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn progress(now: f32, start: f32, duration: f32) -> f32 {
+    if duration <= 0.0 {
+        1.0
+    } else {
+        ((now - start) / duration).clamp(0.0, 1.0)
+    }
+}
+
+fn projection_scale(projection: &Projection) -> f32 {
+    match projection {
+        Projection::Orthographic(ortho) => ortho.scale,
+        _ => 1.0,
+    }
+}
+
+fn set_projection_scale(projection: &mut Projection, scale: f32) {
+    if let Projection::Orthographic(ortho) = projection {
+        ortho.scale = scale;
+    }
+}
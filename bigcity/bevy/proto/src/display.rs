@@ -0,0 +1,624 @@
+// --- IMPORTS ---
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::camera::{CameraUpdateSystem, ScalingMode};
+use bevy::window::{
+    Monitor, MonitorSelection, PrimaryWindow, VideoModeSelection, WindowMode, WindowPosition,
+};
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+use crate::cpu_display::DisplayBackend;
+use crate::degradation::Degradation;
+use crate::focus::{Background, FocusPolicy};
+use crate::frame::FrameStyle;
+use crate::persist::{load_user_ron, save_user_ron};
+use crate::resolution::{Border, BorderFill, GrowWorld, TrimWorld, WorldResize};
+use crate::sim::SimulationBackend;
+use crate::{
+    DISPLAY_SCALE, Particle, SIMULATION_HEIGHT, SIMULATION_WIDTH, ScreenCamera, SimulationDisplay,
+    SimulationMaterial, WORLD_UNITS_PER_CELL, WorldLayout,
+};
+
+// --- CONSTANTS ---
+const DISPLAY_FILE: &str = "display.ron";
+const MIN_WINDOW_SIZE: u32 = 256;
+const MAX_WINDOW_SIZE: u32 = 7680;
+// The sizes a world can be, in cells along either side.
+pub const WORLD_SIZES: std::ops::RangeInclusive<u32> = 64..=2048;
+// The window pixels per cell a window sized to the world can have.
+pub const SCALES: std::ops::RangeInclusive<f32> = 1.0..=8.0;
+// The range a paced world display can be refreshed at, in hertz.
+const UPLOAD_RATES: std::ops::RangeInclusive<f32> = 10.0..=120.0;
+const DEFAULT_UPLOAD_RATE: f32 = 30.0;
+// What a picture frame starts out as when picked in the panel.
+const DEFAULT_FRAME_PICTURE: &str = "frames/frame.png";
+const DEFAULT_FRAME_BORDER: u32 = 16;
+
+// --- PLUGIN ---
+
+// The world's size in cells, the window mode, monitor and size, how the world is upscaled into it
+// and how often it is refreshed, what happens while the window is in the background, kept in
+// `display.ron` in the user data directory and edited in the display window (F11). The primary
+// window and the world's layout are created from the settings `main` loads, and every later
+// change is applied live; a new world size resizes the running world, and the window's Grow and
+// Trim buttons grow it in place on one side or crop it to what's built (see resolution.rs). The
+// crisp mode keeps every cell a whole number of the monitor's physical pixels across, however the
+// window is scaled, zoomed or moved to another monitor.
+pub struct DisplayPlugin(pub DisplaySettings);
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0.clone())
+            .init_resource::<DisplayPanel>()
+            .add_systems(Update, (toggle_display_panel, apply_display_settings, apply_upscaler))
+            .add_systems(PostUpdate, snap_to_pixels.before(CameraUpdateSystem))
+            .add_systems(EguiContextPass, draw_display_panel);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub enum DisplayMode {
+    #[default]
+    Windowed,
+    // A window without decorations covering the whole monitor.
+    Borderless,
+    // Exclusive fullscreen in the monitor's current video mode.
+    Fullscreen,
+}
+
+impl DisplayMode {
+    const ALL: [DisplayMode; 3] =
+        [DisplayMode::Windowed, DisplayMode::Borderless, DisplayMode::Fullscreen];
+}
+
+// How cells are blown up to screen pixels. Only the display pass changes; the world doesn't.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub enum Upscaler {
+    // Every cell a sharp square.
+    #[default]
+    Nearest,
+    // xBR edge detection: staircases of cells along a slope are drawn as smooth diagonals.
+    Xbr,
+}
+
+impl Upscaler {
+    const ALL: [Upscaler; 2] = [Upscaler::Nearest, Upscaler::Xbr];
+
+    fn label(self) -> &'static str {
+        match self {
+            Upscaler::Nearest => "Sharp pixels",
+            Upscaler::Xbr => "xBR (smooth edges)",
+        }
+    }
+
+    // What the shader's `upscaler` uniform is set to.
+    fn shader_mode(self) -> u32 {
+        match self {
+            Upscaler::Nearest => 0,
+            Upscaler::Xbr => 1,
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct DisplaySettings {
+    // The world's size in cells, and how a running world is carried over to a new one.
+    pub world: (u32, u32),
+    pub resize: WorldResize,
+    pub mode: DisplayMode,
+    // The monitor to open on, in the order the system lists them; 0 is usually the primary one.
+    pub monitor: usize,
+    // The windowed size in logical pixels. `None` sizes the window to the world times `scale`.
+    pub resolution: Option<(u32, u32)>,
+    // Window pixels per cell when the window is sized to the world, so a 512x512 world at 2 opens
+    // the same 1024 pixel window as a 128x128 one at 8.
+    pub scale: f32,
+    pub upscaler: Upscaler,
+    // Whether every cell is drawn a whole number of physical pixels across. Otherwise the world is
+    // stretched to fill the window exactly, which at 125% or 150% display scaling makes some cells
+    // a pixel wider than others and blurs their edges.
+    pub crisp: bool,
+    // Where the world's colors are worked out; see cpu_display.rs.
+    pub backend: DisplayBackend,
+    // Where the world steps; see gpu_sim.rs.
+    pub simulation: SimulationBackend,
+    // What surrounds the world where it doesn't fill the window; see frame.rs.
+    pub frame: FrameStyle,
+    // How many times a second the world's texture is refreshed from the grid at most; `None`
+    // refreshes it every frame. The rest of the frame (UI, cursor, brush preview) isn't held back.
+    pub upload_rate: Option<f32>,
+    // What the game does while the window is in the background; see focus.rs.
+    pub focus: FocusPolicy,
+    // Whether the handheld layout is on; `None` until it has been chosen either way, so a launch
+    // that looks handheld can offer it. See handheld.rs.
+    pub handheld: Option<bool>,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            world: (SIMULATION_WIDTH, SIMULATION_HEIGHT),
+            resize: WorldResize::default(),
+            mode: DisplayMode::Windowed,
+            monitor: 0,
+            resolution: None,
+            scale: DISPLAY_SCALE,
+            upscaler: Upscaler::Nearest,
+            crisp: false,
+            backend: DisplayBackend::Gpu,
+            simulation: SimulationBackend::Cpu,
+            frame: FrameStyle::None,
+            upload_rate: None,
+            focus: FocusPolicy::default(),
+            handheld: None,
+        }
+    }
+}
+
+impl DisplaySettings {
+    // The saved settings, or the defaults on first launch.
+    pub fn load() -> Self {
+        load_user_ron(DISPLAY_FILE).unwrap_or_default()
+    }
+
+    // The primary window as these settings describe it, for a world laid out as `layout`.
+    pub fn window(&self, title: &str, layout: &WorldLayout) -> Window {
+        Window {
+            title: title.into(),
+            mode: self.window_mode(),
+            position: WindowPosition::Centered(self.monitor_selection()),
+            resolution: self.window_size(layout).into(),
+            ..default()
+        }
+    }
+
+    fn monitor_selection(&self) -> MonitorSelection {
+        MonitorSelection::Index(self.monitor)
+    }
+
+    fn window_mode(&self) -> WindowMode {
+        let monitor = self.monitor_selection();
+        match self.mode {
+            DisplayMode::Windowed => WindowMode::Windowed,
+            DisplayMode::Borderless => WindowMode::BorderlessFullscreen(monitor),
+            DisplayMode::Fullscreen => WindowMode::Fullscreen(monitor, VideoModeSelection::Current),
+        }
+    }
+
+    fn window_size(&self, layout: &WorldLayout) -> Vec2 {
+        match self.resolution {
+            Some((width, height)) => Vec2::new(width as f32, height as f32),
+            None => layout.size() * self.scale,
+        }
+    }
+}
+
+// Whether the world's texture is due a refresh this frame, for `upload_rate` or the slower rate
+// shedding load asks for. Changes that come in between refreshes are held until the next one.
+#[derive(SystemParam)]
+pub struct UploadPacing<'w, 's> {
+    time: Res<'w, Time>,
+    settings: Res<'w, DisplaySettings>,
+    degradation: Res<'w, Degradation>,
+    since_upload: Local<'s, f32>,
+    waiting: Local<'s, bool>,
+}
+
+impl UploadPacing<'_, '_> {
+    // Whether to upload now, given whether the world changed since last frame.
+    pub fn due(&mut self, changed: bool) -> bool {
+        *self.waiting |= changed;
+        *self.since_upload += self.time.delta_secs();
+        let rate = self.degradation.upload_rate(self.settings.upload_rate);
+        let paced = rate.is_some_and(|rate| *self.since_upload < 1.0 / rate);
+        if !*self.waiting || paced {
+            return false;
+        }
+        *self.waiting = false;
+        *self.since_upload = 0.0;
+        true
+    }
+}
+
+// Whether the display window (F11) is open, and how it would grow or trim the world.
+#[derive(Resource, Default)]
+struct DisplayPanel {
+    open: bool,
+    growth: GrowWorld,
+    trim: TrimWorld,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_display_panel(keys: Res<ButtonInput<KeyCode>>, mut panel: ResMut<DisplayPanel>) {
+    if keys.just_pressed(KeyCode::F11) {
+        panel.open = !panel.open;
+    }
+}
+
+// Applies changed settings to the primary window and saves them. The window was created from the
+// initial settings, so there is nothing to do until they or the world's size change, and it is only
+// touched when something about the window itself changed, so picking an upscaler doesn't undo a
+// manual resize.
+fn apply_display_settings(
+    settings: Res<DisplaySettings>,
+    layout: Res<WorldLayout>,
+    mut applied: Local<Option<(WindowMode, usize, Vec2)>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let window_settings = (settings.window_mode(), settings.monitor, settings.window_size(&layout));
+    if settings.is_added() {
+        *applied = Some(window_settings);
+    }
+    if !(settings.is_changed() || layout.is_changed()) || settings.is_added() {
+        return;
+    }
+    if settings.is_changed() {
+        save_user_ron(DISPLAY_FILE, &*settings);
+    }
+    if *applied == Some(window_settings) {
+        return;
+    }
+    *applied = Some(window_settings);
+    let Ok(mut window) = q_window.single_mut() else { return };
+    let (mode, _, size) = window_settings;
+    window.mode = mode;
+    if settings.mode == DisplayMode::Windowed {
+        window.resolution.set(size.x, size.y);
+        window.position = WindowPosition::Centered(settings.monitor_selection());
+    }
+}
+
+// Hands the upscaler to the world's shader. The material only exists once Startup has run, which
+// is also the first time the settings count as changed here.
+fn apply_upscaler(
+    settings: Res<DisplaySettings>,
+    display: Res<SimulationDisplay>,
+    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Some(material) = sim_materials.get_mut(&display.material) {
+        material.upscaler = settings.upscaler.shader_mode();
+    }
+}
+
+// Frames the world with the screen camera so that, in the crisp mode, a cell is a whole number of
+// the window's physical pixels across and its edges fall between pixels, as long as the whole
+// world fits at one pixel a cell. Zooming rounds to the nearest whole number of pixels, and a
+// window moved to a monitor with another scale factor is snapped to its pixels on the next frame.
+// Only the projection is changed, so the camera's position and zoom stay where panning, following
+// and bookmarks put them. Turning the mode off goes back to fitting the world to the window.
+fn snap_to_pixels(
+    settings: Res<DisplaySettings>,
+    layout: Res<WorldLayout>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_camera: Query<(&Transform, &mut Projection), With<ScreenCamera>>,
+    mut snapped: Local<bool>,
+) {
+    let Ok(window) = q_window.single() else { return };
+    let Ok((transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &*projection else { return };
+    let pixels = window.physical_size().as_vec2();
+    let fit = (pixels / layout.size()).min_element().floor();
+    if !settings.crisp || fit < 1.0 {
+        if std::mem::take(&mut *snapped) {
+            let Projection::Orthographic(ortho) = &mut *projection else { return };
+            ortho.scaling_mode = ScalingMode::AutoMin {
+                min_width: layout.world_size().x,
+                min_height: layout.world_size().y,
+            };
+            ortho.viewport_origin = Vec2::splat(0.5);
+        }
+        return;
+    }
+    let camera = transform.translation.truncate();
+    let (frame, origin) = pixel_frame(pixels, fit, ortho.scale, camera, layout.world_size());
+    let framed = matches!(ortho.scaling_mode, ScalingMode::Fixed { width, height }
+        if Vec2::new(width, height) == frame);
+    if !framed || ortho.viewport_origin != origin {
+        let Projection::Orthographic(ortho) = &mut *projection else { return };
+        ortho.scaling_mode = ScalingMode::Fixed {
+            width: frame.x,
+            height: frame.y,
+        };
+        ortho.viewport_origin = origin;
+    }
+    *snapped = true;
+}
+
+// Edits a copy of the settings and only writes it back when something changed.
+fn draw_display_panel(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<DisplayPanel>,
+    mut settings: ResMut<DisplaySettings>,
+    layout: Res<WorldLayout>,
+    q_monitors: Query<(Entity, &Monitor)>,
+    mut grow: EventWriter<GrowWorld>,
+    mut trim_world: EventWriter<TrimWorld>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    // Monitors are spawned in the order the system lists them, which is what indices refer to.
+    let mut monitors: Vec<(Entity, &Monitor)> = q_monitors.iter().collect();
+    monitors.sort_by_key(|(entity, _)| *entity);
+    let monitor_name = |index: usize| match monitors.get(index) {
+        Some((_, monitor)) => format!(
+            "{}: {} ({}x{})",
+            index + 1,
+            monitor.name.as_deref().unwrap_or("Monitor"),
+            monitor.physical_width,
+            monitor.physical_height,
+        ),
+        None => format!("{}: not connected", index + 1),
+    };
+
+    let mut edited = settings.clone();
+    let DisplayPanel { open, growth, trim } = &mut *panel;
+    let (mut grown, mut trimmed) = (false, false);
+    egui::Window::new("Display")
+        .open(open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("display_settings").num_columns(2).show(ui, |ui| {
+                ui.label("World size (cells)");
+                ui.horizontal(|ui| {
+                    let (width, height) = &mut edited.world;
+                    ui.add(egui::DragValue::new(width).range(WORLD_SIZES));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(height).range(WORLD_SIZES));
+                });
+                ui.end_row();
+
+                ui.label("On resizing");
+                egui::ComboBox::from_id_salt("world_resize")
+                    .selected_text(edited.resize.label())
+                    .show_ui(ui, |ui| {
+                        for resize in WorldResize::ALL {
+                            ui.selectable_value(&mut edited.resize, resize, resize.label());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Mode");
+                egui::ComboBox::from_id_salt("display_mode")
+                    .selected_text(format!("{:?}", edited.mode))
+                    .show_ui(ui, |ui| {
+                        for mode in DisplayMode::ALL {
+                            ui.selectable_value(&mut edited.mode, mode, format!("{:?}", mode));
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Monitor");
+                egui::ComboBox::from_id_salt("display_monitor")
+                    .selected_text(monitor_name(edited.monitor))
+                    .show_ui(ui, |ui| {
+                        for index in 0..monitors.len().max(edited.monitor + 1) {
+                            ui.selectable_value(&mut edited.monitor, index, monitor_name(index));
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Size to the world");
+                let mut fit = edited.resolution.is_none();
+                if ui.checkbox(&mut fit, "").changed() {
+                    let size = edited.window_size(&layout).as_uvec2();
+                    edited.resolution = (!fit).then_some((size.x, size.y));
+                }
+                ui.end_row();
+
+                match &mut edited.resolution {
+                    Some((width, height)) => {
+                        ui.label("Window size (px)");
+                        ui.horizontal(|ui| {
+                            let range = MIN_WINDOW_SIZE..=MAX_WINDOW_SIZE;
+                            ui.add(egui::DragValue::new(width).range(range.clone()));
+                            ui.label("x");
+                            ui.add(egui::DragValue::new(height).range(range));
+                        });
+                    }
+                    None => {
+                        ui.label("Scale (px / cell)");
+                        ui.add(egui::Slider::new(&mut edited.scale, SCALES).step_by(0.5));
+                    }
+                }
+                ui.end_row();
+
+                ui.label("Refresh the world every frame");
+                let mut every_frame = edited.upload_rate.is_none();
+                if ui.checkbox(&mut every_frame, "").changed() {
+                    edited.upload_rate = (!every_frame).then_some(DEFAULT_UPLOAD_RATE);
+                }
+                ui.end_row();
+
+                if let Some(rate) = &mut edited.upload_rate {
+                    ui.label("World refresh rate (Hz)");
+                    ui.add(egui::Slider::new(rate, UPLOAD_RATES).step_by(1.0));
+                    ui.end_row();
+                }
+
+                ui.label("Upscaling");
+                egui::ComboBox::from_id_salt("display_upscaler")
+                    .selected_text(edited.upscaler.label())
+                    .show_ui(ui, |ui| {
+                        for upscaler in Upscaler::ALL {
+                            ui.selectable_value(&mut edited.upscaler, upscaler, upscaler.label());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Whole pixels per cell");
+                ui.checkbox(&mut edited.crisp, "");
+                ui.end_row();
+
+                ui.label("Draw the world on");
+                egui::ComboBox::from_id_salt("display_backend")
+                    .selected_text(edited.backend.label())
+                    .show_ui(ui, |ui| {
+                        for backend in DisplayBackend::ALL {
+                            ui.selectable_value(&mut edited.backend, backend, backend.label());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Step the world on");
+                egui::ComboBox::from_id_salt("simulation_backend")
+                    .selected_text(edited.simulation.label())
+                    .show_ui(ui, |ui| {
+                        for backend in SimulationBackend::ALL {
+                            let label = backend.label();
+                            ui.selectable_value(&mut edited.simulation, backend, label);
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Frame");
+                egui::ComboBox::from_id_salt("display_frame")
+                    .selected_text(edited.frame.label())
+                    .show_ui(ui, |ui| {
+                        let picture = FrameStyle::Texture {
+                            path: DEFAULT_FRAME_PICTURE.into(),
+                            border: DEFAULT_FRAME_BORDER,
+                        };
+                        for style in [FrameStyle::None, FrameStyle::Procedural, picture] {
+                            let selected = edited.frame.label() == style.label();
+                            if ui.selectable_label(selected, style.label()).clicked() && !selected {
+                                edited.frame = style;
+                            }
+                        }
+                    });
+                ui.end_row();
+
+                if let FrameStyle::Texture { path, border } = &mut edited.frame {
+                    ui.label("Frame picture");
+                    ui.text_edit_singleline(path);
+                    ui.end_row();
+
+                    ui.label("Frame border (texels)");
+                    ui.add(egui::DragValue::new(border).range(1..=256));
+                    ui.end_row();
+                }
+
+                let policies = [
+                    ("When unfocused", &mut edited.focus.unfocused),
+                    ("When minimized", &mut edited.focus.minimized),
+                ];
+                for (label, policy) in policies {
+                    ui.label(label);
+                    egui::ComboBox::from_id_salt(label)
+                        .selected_text(format!("{:?}", policy))
+                        .show_ui(ui, |ui| {
+                            for background in Background::ALL {
+                                let name = format!("{:?}", background);
+                                ui.selectable_value(policy, background, name);
+                            }
+                        });
+                    ui.end_row();
+                }
+
+                ui.label("Handheld layout");
+                let mut handheld = edited.handheld == Some(true);
+                if ui.checkbox(&mut handheld, "").changed() {
+                    edited.handheld = Some(handheld);
+                }
+                ui.end_row();
+            });
+            if edited.mode != DisplayMode::Windowed {
+                ui.label("The size applies once the window is windowed again.");
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Grow the world by");
+                ui.add(egui::DragValue::new(&mut growth.cells).range(1..=*WORLD_SIZES.end()));
+                ui.label("cells on the");
+                egui::ComboBox::from_id_salt("grow_side")
+                    .selected_text(format!("{:?}", growth.side))
+                    .show_ui(ui, |ui| {
+                        for side in Border::ALL {
+                            ui.selectable_value(&mut growth.side, side, format!("{:?}", side));
+                        }
+                    });
+                ui.label("with");
+                egui::ComboBox::from_id_salt("grow_fill")
+                    .selected_text(growth.fill.label())
+                    .show_ui(ui, |ui| {
+                        let materials = Particle::ALL.map(BorderFill::Material);
+                        for fill in [BorderFill::Terrain].into_iter().chain(materials) {
+                            ui.selectable_value(&mut growth.fill, fill, fill.label());
+                        }
+                    });
+                grown = ui.button("Grow").clicked();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Trim the world to what's built, leaving");
+                ui.add(egui::DragValue::new(&mut trim.padding).range(0..=*WORLD_SIZES.end()));
+                ui.label("cells around it");
+                trimmed = ui.button("Trim").clicked();
+            });
+            if ui.button("Reset to defaults").clicked() {
+                edited = DisplaySettings::default();
+            }
+        });
+    if grown {
+        grow.write(*growth);
+    }
+    if trimmed {
+        trim_world.write(*trim);
+    }
+    if edited != *settings {
+        *settings = edited;
+    }
+}
+
+// --- HELPERS ---
+
+// What a camera at `camera`, zoomed to `scale`, frames at scale 1 and where its viewport's origin
+// sits, for every cell of a world `world` units across to be a whole number of `pixels` across,
+// `fit` of them at scale 1, with the world's corner on a pixel's corner.
+fn pixel_frame(pixels: Vec2, fit: f32, scale: f32, camera: Vec2, world: Vec2) -> (Vec2, Vec2) {
+    let per_cell = (fit / scale).round().max(1.0);
+    let units_per_pixel = WORLD_UNITS_PER_CELL / per_cell;
+    // Where the world's bottom-left corner lands, in pixels from the viewport's, with the camera
+    // in the middle; whatever part of a pixel it is off by moves the viewport's origin instead.
+    let corner = (-world / 2.0 - camera) / units_per_pixel + pixels / 2.0;
+    let origin = Vec2::splat(0.5) - (corner - corner.round()) / pixels;
+    (pixels * units_per_pixel / scale, origin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_land_on_whole_pixels() {
+        // A 320x200 world in a 1000x700 pixel window, as 125% scaling makes an 800x560 one.
+        let (pixels, world) = (Vec2::new(1000.0, 700.0), Vec2::new(320.0, 200.0) * 4.0);
+        for (scale, camera) in [(1.0, Vec2::ZERO), (0.4, Vec2::new(37.3, -11.9))] {
+            let (frame, origin) = pixel_frame(pixels, 3.0, scale, camera, world);
+            let units_per_pixel = frame * scale / pixels;
+            assert_eq!(units_per_pixel.x, units_per_pixel.y);
+            let per_cell = WORLD_UNITS_PER_CELL / units_per_pixel.x;
+            assert!((per_cell - per_cell.round()).abs() < 1e-4, "{} pixels a cell", per_cell);
+            // The viewport's bottom-left corner in world units, and the world's from there.
+            let viewport = camera - origin * frame * scale;
+            let corner = (-world / 2.0 - viewport) / units_per_pixel;
+            assert!((corner - corner.round()).abs().max_element() < 1e-3, "{:?}", corner);
+        }
+        let (frame, _) = pixel_frame(pixels, 3.0, 1.0, Vec2::ZERO, world);
+        assert!((frame / 4.0 - pixels / 3.0).abs().max_element() < 1e-3, "{:?}", frame);
+    }
+}
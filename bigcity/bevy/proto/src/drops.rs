@@ -0,0 +1,214 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::levels::PaintRules;
+use crate::material_import::import_noita_materials;
+use crate::persist::user_data_dir;
+use crate::picture_import::PictureImporter;
+use crate::postcard::{PostcardError, read_postcard};
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::snapshot::WorldSnapshot;
+use crate::stamps::{Stamp, StampLibrary, save_stamp};
+
+// --- CONSTANTS ---
+const MODS_FOLDER: &str = "mods";
+
+// --- PLUGIN ---
+
+// Opens files dropped onto the window, going by their extension: pictures become the world
+// (postcards load the world they carry, and others are imported as picture_import.rs says),
+// blueprints become stamps, saved worlds load, material files from other games tune the world's
+// materials, and mod archives are installed into the mods folder once the player confirms.
+pub struct DropsPlugin;
+
+impl Plugin for DropsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PendingInstall>()
+            .add_systems(Update, open_dropped_files.before(SimulationSet))
+            .add_systems(EguiContextPass, confirm_install);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DroppedKind {
+    // A .png: a postcard or any other picture.
+    Picture,
+    // A .sandblueprint (or .stamp.ron): a stamp.
+    Blueprint,
+    // Any other .ron: a saved world.
+    World,
+    // A .zip of a mod or scenario pack.
+    ModArchive,
+    // A .xml of material definitions, like Noita's materials.xml.
+    Materials,
+}
+
+impl DroppedKind {
+    fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".png") {
+            Some(DroppedKind::Picture)
+        } else if name.ends_with(".sandblueprint") || name.ends_with(".stamp.ron") {
+            Some(DroppedKind::Blueprint)
+        } else if name.ends_with(".ron") {
+            Some(DroppedKind::World)
+        } else if name.ends_with(".zip") {
+            Some(DroppedKind::ModArchive)
+        } else if name.ends_with(".xml") {
+            Some(DroppedKind::Materials)
+        } else {
+            None
+        }
+    }
+
+    // Whether opening it replaces the world or changes its rules, which levels don't allow.
+    fn replaces_world(self) -> bool {
+        matches!(self, DroppedKind::Picture | DroppedKind::World | DroppedKind::Materials)
+    }
+}
+
+// --- RESOURCES ---
+
+// A dropped mod archive waiting for the player to confirm installing it.
+#[derive(Resource, Default)]
+struct PendingInstall(Option<PathBuf>);
+
+// --- SYSTEMS ---
+
+fn open_dropped_files(
+    mut drops: EventReader<FileDragAndDrop>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+    mut library: ResMut<StampLibrary>,
+    mut pending: ResMut<PendingInstall>,
+    importer: PictureImporter,
+) {
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf: path, .. } = drop else { continue };
+        let Some(kind) = DroppedKind::of(path) else {
+            info!("Ignoring dropped {:?}: not a file the game knows", path);
+            continue;
+        };
+        if kind.replaces_world() && rules.protect_world {
+            info!("Ignoring dropped {:?}: the world can't be replaced during a level", path);
+            continue;
+        }
+
+        let result = match kind {
+            DroppedKind::Picture => open_picture(path, &mut grid, &importer),
+            DroppedKind::Blueprint => open_blueprint(path, &mut library),
+            DroppedKind::World => open_world(path, &mut grid),
+            DroppedKind::ModArchive => {
+                pending.0 = Some(path.clone());
+                Ok("Asking whether to install it".to_string())
+            }
+            DroppedKind::Materials => open_materials(path, &mut grid),
+        };
+        match result {
+            Ok(done) => info!("Dropped {:?}: {}", path, done),
+            Err(err) => warn!("Could not open dropped {:?}: {}", path, err),
+        }
+    }
+}
+
+// Asks before copying a dropped archive into the mods folder.
+fn confirm_install(mut contexts: EguiContexts, mut pending: ResMut<PendingInstall>) {
+    let Some(path) = pending.0.clone() else { return };
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into());
+    egui::Window::new("Install mod")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!("Install \"{}\" into the mods folder?", name));
+            ui.horizontal(|ui| {
+                if ui.button("Install").clicked() {
+                    match install_mod(&path) {
+                        Ok(target) => {
+                            info!("Installed {:?} as {:?}, for the next start", path, target)
+                        }
+                        Err(err) => warn!("Could not install {:?}: {}", path, err),
+                    }
+                    pending.0 = None;
+                }
+                if ui.button("Cancel").clicked() {
+                    pending.0 = None;
+                }
+            });
+        });
+}
+
+// --- HELPERS ---
+
+// Loads the world a postcard carries, or turns any other picture into a world.
+fn open_picture(
+    path: &Path,
+    grid: &mut SimulationGrid,
+    importer: &PictureImporter,
+) -> Result<String, String> {
+    match read_postcard(path) {
+        Ok(snapshot) => {
+            snapshot.apply_to(grid);
+            return Ok("opened the postcard's world".to_string());
+        }
+        Err(PostcardError::NoWorld) => {}
+        Err(err) => return Err(err.to_string()),
+    }
+    let picture = image::open(path).map_err(|err| err.to_string())?;
+    Ok(importer.to_world(&picture, grid))
+}
+
+fn open_blueprint(path: &Path, library: &mut StampLibrary) -> Result<String, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let stamp: Stamp = ron::from_str(&text).map_err(|err| err.to_string())?;
+    save_stamp(&stamp);
+    let done = format!("added stamp \"{}\" to the library", stamp.name);
+    library.add(stamp);
+    Ok(done)
+}
+
+fn open_world(path: &Path, grid: &mut SimulationGrid) -> Result<String, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let snapshot: WorldSnapshot = ron::from_str(&text).map_err(|err| err.to_string())?;
+    snapshot.apply_to(grid);
+    Ok("opened the world".to_string())
+}
+
+// Gives the world's materials the parameters of the ones with the same names in the file, keeping
+// the world's other material overrides.
+fn open_materials(path: &Path, grid: &mut SimulationGrid) -> Result<String, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let import = import_noita_materials(&text);
+    if import.overrides.is_empty() {
+        return Err("it defines none of this game's materials".to_string());
+    }
+    let mut overrides = grid.material_overrides().to_vec();
+    overrides.retain(|kept| import.overrides.iter().all(|new| new.material != kept.material));
+    let names: Vec<String> =
+        import.overrides.iter().map(|o| format!("{:?}", o.material)).collect();
+    overrides.extend(import.overrides);
+    grid.set_material_overrides(overrides);
+    Ok(format!(
+        "tuned {} from it, skipping {} other materials",
+        names.join(", "),
+        import.skipped.len()
+    ))
+}
+
+// Copies the archive into the mods folder under its own name, replacing an older copy.
+fn install_mod(path: &Path) -> std::io::Result<PathBuf> {
+    let dir = user_data_dir()
+        .map(|dir| dir.join(MODS_FOLDER))
+        .ok_or_else(|| std::io::Error::other("no user data directory"))?;
+    let name = path.file_name().ok_or_else(|| std::io::Error::other("not a file"))?;
+    std::fs::create_dir_all(&dir)?;
+    let target = dir.join(name);
+    std::fs::copy(path, &target)?;
+    Ok(target)
+}
@@ -0,0 +1,258 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::player::{InputSource, PlayerInputSet, SelectedParticle};
+use crate::resolution::WorldShifted;
+use crate::sim::{SimulationGrid, SimulationStats, SimulationTickRate, roll};
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::{MaterialClass, Particle, WorldView};
+
+// --- CONSTANTS ---
+// Particles per second at the tick rate, so emitters slow down, speed up and pause along with
+// the world, and how far from their center, in cells, they put them or take them.
+const EMITTER_RATE: f32 = 40.0;
+const EMITTER_RADIUS: i32 = 2;
+const DRAIN_RATE: f32 = 80.0;
+const DRAIN_RADIUS: i32 = 3;
+const DRAIN_COLOR: Color = Color::srgb(0.6, 0.3, 0.9);
+const TOOL_COLOR: Color = Color::WHITE;
+
+// --- PLUGIN ---
+
+// Emitters and drains keep putting particles in and taking them out at fixed places in the world,
+// for fountains, hourglasses that never run dry and endurance tests without anyone holding the
+// mouse button. W turns the emitter tool on; with it on, W places an emitter of the selected
+// material under the cursor, Shift+W a drain, which takes away anything loose around it, and W
+// over either takes it away again. Esc turns the tool off; emitters and drains keep running, as a
+// ring in their material's color or a purple one. Levels have no emitters.
+pub struct EmittersPlugin;
+
+impl Plugin for EmittersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EmitterTool>().add_systems(
+            Update,
+            (use_emitter_tool, run_emitters, draw_emitters)
+                .chain()
+                .after(PlayerInputSet)
+                .before(WorldCommandSet),
+        )
+        .add_systems(Update, follow_world_shifts);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct EmitterTool {
+    active: bool,
+}
+
+// --- COMPONENTS ---
+
+// Puts `rate` particles a second into the air within `radius` cells of where it is.
+#[derive(Component, Clone, Copy, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct ParticleEmitter {
+    pub particle: Particle,
+    pub rate: f32,
+    pub radius: i32,
+}
+
+// Takes `rate` loose particles a second from within `radius` cells of where it is. Solids are left
+// alone, so a drain in a basin doesn't eat the basin.
+#[derive(Component, Clone, Copy, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct ParticleDrain {
+    pub rate: f32,
+    pub radius: i32,
+}
+
+// Where an emitter or drain is, and the particles it is owed but hasn't had room for yet, as a
+// fraction.
+#[derive(Component, Clone, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+pub(crate) struct Outlet {
+    center: CellPos,
+    owed: f32,
+}
+
+// --- SYSTEMS ---
+
+fn use_emitter_tool(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    view: WorldView,
+    q_players: Query<(&InputSource, &SelectedParticle)>,
+    q_outlets: Query<(Entity, &Outlet, Option<&ParticleEmitter>, Option<&ParticleDrain>)>,
+    mut tool: ResMut<EmitterTool>,
+) {
+    if tool.active && keys.just_pressed(KeyCode::Escape) {
+        tool.active = false;
+        info!("Emitter tool off");
+        return;
+    }
+    if !keys.just_pressed(KeyCode::KeyW) {
+        return;
+    }
+    // A level's objectives would be no challenge with a fountain of whatever they ask for.
+    if rules.protect_world {
+        info!("Emitters aren't allowed in levels");
+        return;
+    }
+    if !tool.active {
+        tool.active = true;
+        info!(
+            "Emitter tool: W places an emitter of the selected material or takes one away, \
+             Shift+W places a drain, Esc closes"
+        );
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+
+    let under_cursor = q_outlets.iter().find(|(_, outlet, emitter, drain)| {
+        let radius = emitter.map(|e| e.radius).or(drain.map(|d| d.radius)).unwrap_or(0);
+        (*outlet.center - *cell).abs().max_element() <= radius
+    });
+    if let Some((entity, outlet, _, _)) = under_cursor {
+        commands.entity(entity).despawn();
+        info!("Took away the outlet at {:?}", outlet.center.0);
+        return;
+    }
+
+    let outlet = Outlet {
+        center: cell,
+        owed: 0.0,
+    };
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        commands.spawn((
+            outlet,
+            ParticleDrain {
+                rate: DRAIN_RATE,
+                radius: DRAIN_RADIUS,
+            },
+        ));
+        info!("Placed a drain at {:?}", cell.0);
+        return;
+    }
+    let selected = q_players.iter().find(|(source, _)| **source == InputSource::Mouse);
+    let Some((_, &SelectedParticle(particle))) = selected else { return };
+    if particle == Particle::Air {
+        info!("Select a material to emit first (Shift+W places a drain)");
+        return;
+    }
+    commands.spawn((
+        outlet,
+        ParticleEmitter {
+            particle,
+            rate: EMITTER_RATE,
+            radius: EMITTER_RADIUS,
+        },
+    ));
+    info!("Placed an emitter of {:?} at {:?}", particle, cell.0);
+}
+
+// Puts in and takes out what the ticks that ran this frame are owed. Cells are picked at random
+// within the radius, so a fountain sprays rather than stacking into a column.
+fn run_emitters(
+    tick_rate: Res<SimulationTickRate>,
+    stats: Res<SimulationStats>,
+    rules: Res<PaintRules>,
+    grid: Res<SimulationGrid>,
+    mut commands: ResMut<WorldCommands>,
+    mut q_outlets: Query<(&mut Outlet, Option<&ParticleEmitter>, Option<&ParticleDrain>)>,
+) {
+    if stats.ticks_last_frame == 0 || tick_rate.ticks_per_second <= 0.0 || q_outlets.is_empty() {
+        return;
+    }
+    // Emitters placed before a level was loaded wait for free play again.
+    if rules.protect_world {
+        return;
+    }
+    let seconds = stats.ticks_last_frame as f32 / tick_rate.ticks_per_second;
+    for (mut outlet, emitter, drain) in &mut q_outlets {
+        let (rate, radius) = match (emitter, drain) {
+            (Some(emitter), _) => (emitter.rate, emitter.radius),
+            (None, Some(drain)) => (drain.rate, drain.radius),
+            (None, None) => continue,
+        };
+        outlet.owed += rate * seconds;
+        let wanted = outlet.owed.floor() as usize;
+        if wanted == 0 {
+            continue;
+        }
+        let center = *outlet.center;
+        let mut cells: Vec<IVec2> = disk(radius)
+            .map(|offset| center + offset)
+            .filter(|cell| match (emitter, grid.get(cell.x, cell.y)) {
+                (_, None) => false,
+                (Some(_), Some(particle)) => particle == Particle::Air,
+                (None, Some(particle)) => {
+                    particle != Particle::Air && particle.class() != MaterialClass::Solid
+                }
+            })
+            .collect();
+        cells.sort_by(|a, b| roll(a.x, a.y, stats.tick).total_cmp(&roll(b.x, b.y, stats.tick)));
+        let cells: Vec<_> = cells.into_iter().take(wanted).map(CellPos).collect();
+        let done = cells.len();
+        if done > 0 {
+            let particle = emitter.map_or(Particle::Air, |emitter| emitter.particle);
+            commands.push(WorldCommand::Set { particle, cells });
+        }
+        // A buried emitter or a dry drain doesn't save up a burst for later.
+        outlet.owed = if done < wanted { 0.0 } else { outlet.owed - done as f32 };
+    }
+}
+
+// A ring around every emitter in its material's color and every drain in purple, and, while the
+// tool is on, the square the cursor would place one in.
+fn draw_emitters(
+    tool: Res<EmitterTool>,
+    view: WorldView,
+    keys: Res<ButtonInput<KeyCode>>,
+    q_outlets: Query<(&Outlet, Option<&ParticleEmitter>, Option<&ParticleDrain>)>,
+    mut gizmos: Gizmos,
+) {
+    for (outlet, emitter, drain) in &q_outlets {
+        let (radius, color) = match (emitter, drain) {
+            (Some(emitter), _) => (emitter.radius, emitter.particle.color()),
+            (None, Some(drain)) => (drain.radius, DRAIN_COLOR),
+            (None, None) => continue,
+        };
+        let center = *view.cell_to_world(outlet.center.center());
+        let size = view.cell_size().x * (radius as f32 + 0.5);
+        gizmos.circle_2d(Isometry2d::from_translation(center), size, color);
+    }
+    if !tool.active {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let radius = if shift { DRAIN_RADIUS } else { EMITTER_RADIUS };
+    let reach = IVec2::splat(radius);
+    let rect = view.cells_to_world(CellPos(*cell - reach), CellPos(*cell + reach));
+    gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), TOOL_COLOR);
+}
+
+// Keeps emitters and drains over the same cells when the world grows under them.
+fn follow_world_shifts(
+    mut shifts: EventReader<WorldShifted>,
+    mut q_outlets: Query<&mut Outlet>,
+) {
+    for shift in shifts.read() {
+        for mut outlet in &mut q_outlets {
+            outlet.center = outlet.center + shift.offset;
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// The offsets of the cells within `radius` of a center cell.
+fn disk(radius: i32) -> impl Iterator<Item = IVec2> {
+    (-radius..=radius)
+        .flat_map(move |y| (-radius..=radius).map(move |x| IVec2::new(x, y)))
+        .filter(move |offset| offset.length_squared() <= radius * radius)
+}
@@ -0,0 +1,115 @@
+// --- IMPORTS ---
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use bevy::prelude::*;
+use thiserror::Error;
+
+// --- CONSTANTS ---
+// How hard the GIF encoder works at picking each frame's 256 colors, from 1 (best, slowest) to 30.
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+// --- TYPES ---
+
+// What a recording is written as.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordingFormat {
+    // One animated GIF that loops.
+    Gif,
+    // A folder of numbered PNGs, frame_00000.png onwards, for a video editor.
+    PngSequence,
+}
+
+impl RecordingFormat {
+    pub fn label(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "an animated GIF",
+            RecordingFormat::PngSequence => "a PNG sequence",
+        }
+    }
+}
+
+// One frame, rows top first, four bytes (sRGB and alpha) a pixel.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    #[error("could not write the file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode the GIF: {0}")]
+    Gif(#[from] gif::EncodingError),
+    #[error("could not encode a PNG: {0}")]
+    Png(#[from] image::ImageError),
+    #[error("the world is too big for a GIF")]
+    TooBig,
+}
+
+// Writes frames to disk on a thread of its own, as they come, so encoding never holds up a frame.
+// Dropping it ends the recording: the thread writes what is left, finishes the file and logs
+// where it went.
+pub struct FrameEncoder {
+    frames: Sender<Frame>,
+}
+
+impl FrameEncoder {
+    // Starts writing to `path`, a .gif file or the folder for a PNG sequence. `delay` is how long
+    // each frame of a GIF shows, in hundredths of a second.
+    pub fn start(format: RecordingFormat, path: PathBuf, delay: u16) -> Self {
+        let (frames, received) = channel();
+        std::thread::spawn(move || {
+            let written = match format {
+                RecordingFormat::Gif => write_gif(&path, delay, received),
+                RecordingFormat::PngSequence => write_pngs(&path, received),
+            };
+            match written {
+                Ok(count) => info!("Recording saved to {:?} ({} frames)", path, count),
+                Err(err) => warn!("Recording to {:?} failed: {}", path, err),
+            }
+        });
+        Self { frames }
+    }
+
+    pub fn push(&self, frame: Frame) {
+        // The thread only hangs up after failing, and says why then.
+        let _ = self.frames.send(frame);
+    }
+}
+
+// --- HELPERS ---
+
+// Sized to the first frame; later frames are expected to be the same size.
+fn write_gif(path: &Path, delay: u16, frames: Receiver<Frame>) -> Result<u32, EncodeError> {
+    let Ok(first) = frames.recv() else { return Ok(0) };
+    let size = |value: u32| u16::try_from(value).map_err(|_| EncodeError::TooBig);
+    let (width, height) = (size(first.width)?, size(first.height)?);
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = gif::Encoder::new(file, width, height, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+    let mut count = 0;
+    for mut frame in std::iter::once(first).chain(frames) {
+        let mut gif_frame =
+            gif::Frame::from_rgba_speed(width, height, &mut frame.rgba, GIF_QUANTIZE_SPEED);
+        gif_frame.delay = delay;
+        encoder.write_frame(&gif_frame)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn write_pngs(dir: &Path, frames: Receiver<Frame>) -> Result<u32, EncodeError> {
+    std::fs::create_dir_all(dir)?;
+    let mut count = 0;
+    for frame in frames {
+        let path = dir.join(format!("frame_{:05}.png", count));
+        let color = image::ExtendedColorType::Rgba8;
+        image::save_buffer(path, &frame.rgba, frame.width, frame.height, color)?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,45 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::sim::Reaction;
+
+// --- PLUGIN ---
+pub struct SimEventsPlugin;
+
+impl Plugin for SimEventsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SimEvent>();
+    }
+}
+
+// --- EVENTS ---
+
+// Gameplay-level notifications about what happened to the world and who did it. Tutorials,
+// objectives and statistics listen to these instead of polling input devices or textures.
+#[derive(Event, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum SimEvent {
+    // A player's brush wrote `cells` cells of `particle` this frame.
+    Painted {
+        player: usize,
+        particle: Particle,
+        cells: u32,
+    },
+    ParticleSelected {
+        player: usize,
+        particle: Particle,
+    },
+    BrushResized {
+        player: usize,
+        size: i32,
+    },
+    // The world generator finished a new world.
+    WorldCreated,
+    // The simulation's ticks this frame changed `cells` cells by `reaction`: chemistry, phase
+    // changes (water boiling, sand melting, steam condensing...) and explosions.
+    Reacted {
+        reaction: Reaction,
+        cells: u32,
+    },
+}
@@ -0,0 +1,229 @@
+// --- IMPORTS ---
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bevy::color::ColorToPacked;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::behavior::MaterialBehaviors;
+use crate::persist::{file_stem, user_data_dir};
+use crate::quality::Quality;
+use crate::reaction_rules::ReactionTable;
+use crate::sim::{SimParams, SimulationGrid};
+use crate::snapshot::WorldSnapshot;
+use crate::{MaterialClass, Particle, SIMULATION_HEIGHT, SIMULATION_WIDTH};
+
+// --- CONSTANTS ---
+const EXPERIMENT_FOLDER: &str = "experiments";
+const RESULTS_FILE: &str = "results.csv";
+
+// --- RUNNER ---
+
+// `--experiment <manifest.ron>` runs a parameter sweep instead of the game: every combination of
+// the manifest's sweeps runs for its ticks from the same starting world, without a window, and
+// each run adds a row of outcome metrics to `results.csv` and a picture of its final world to the
+// experiment's folder in the user data directory. Returns the path of the manifest, if given.
+pub fn manifest_arg() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--experiment" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+// Runs the experiment `manifest` describes and returns the process exit code.
+pub fn run(manifest: &Path) -> i32 {
+    match run_experiment(manifest) {
+        Ok(folder) => {
+            println!("Experiment finished; results are in {:?}", folder);
+            0
+        }
+        Err(err) => {
+            eprintln!("Experiment {:?} failed: {}", manifest, err);
+            1
+        }
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Deserialize, Debug)]
+struct Experiment {
+    name: String,
+    // The world every run starts from, relative to the manifest. Without one runs start empty.
+    #[serde(default)]
+    snapshot: Option<PathBuf>,
+    ticks: u64,
+    // The parameters every run starts from, before the sweeps change them.
+    #[serde(default)]
+    params: SimParams,
+    // The tick schedule runs use, as a quality level.
+    #[serde(default = "full_quality")]
+    quality: Quality,
+    // Every combination of one value from each sweep is a run.
+    sweeps: Vec<Sweep>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Sweep {
+    param: SweptParam,
+    values: Vec<f32>,
+}
+
+// The parameters a sweep can vary. Whole-numbered parameters round their values.
+#[derive(Deserialize, Debug, Clone, Copy)]
+enum SweptParam {
+    Gravity,
+    Dispersion,
+    Cohesion,
+    BoilChance,
+    MeltChance,
+    HeatDiffusion,
+    CoolingRate,
+    AmbientTemperature,
+    Wind,
+}
+
+impl SweptParam {
+    fn set(self, params: &mut SimParams, value: f32) {
+        match self {
+            SweptParam::Gravity => params.gravity = value,
+            SweptParam::Dispersion => params.dispersion = value.round().max(0.0) as u32,
+            SweptParam::Cohesion => params.cohesion = value,
+            SweptParam::BoilChance => params.boil_chance = value,
+            SweptParam::MeltChance => params.melt_chance = value,
+            SweptParam::HeatDiffusion => params.heat_diffusion = value,
+            SweptParam::CoolingRate => params.cooling_rate = value,
+            SweptParam::AmbientTemperature => params.ambient_temperature = value,
+            SweptParam::Wind => params.wind = value,
+        }
+    }
+}
+
+// What a run ended with.
+struct Outcome {
+    ms_per_tick: f32,
+    counts: [u32; Particle::ALL.len()],
+    // The mean row of all powder and liquid cells, from the bottom: how far things settled.
+    mean_height: f32,
+    // The mean temperature over the whole world.
+    mean_temperature: f32,
+}
+
+#[derive(Debug, Error)]
+enum ExperimentError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not save a thumbnail: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("no user data directory")]
+    NoDataDir,
+    #[error("sweep over {0:?} has no values")]
+    EmptySweep(SweptParam),
+}
+
+// --- HELPERS ---
+
+fn full_quality() -> Quality {
+    Quality::Ultra
+}
+
+fn run_experiment(manifest: &Path) -> Result<PathBuf, ExperimentError> {
+    let experiment: Experiment = ron::from_str(&std::fs::read_to_string(manifest)?)?;
+    if let Some(sweep) = experiment.sweeps.iter().find(|sweep| sweep.values.is_empty()) {
+        return Err(ExperimentError::EmptySweep(sweep.param));
+    }
+    // Runs start from the snapshot's world at its own size, or an empty one of the default size.
+    let mut start = SimulationGrid::new(SIMULATION_WIDTH, SIMULATION_HEIGHT);
+    if let Some(snapshot) = &experiment.snapshot {
+        let path = manifest.parent().unwrap_or(Path::new("")).join(snapshot);
+        let snapshot: WorldSnapshot = ron::from_str(&std::fs::read_to_string(path)?)?;
+        start = SimulationGrid::new(snapshot.width, snapshot.height);
+        snapshot.apply_to(&mut start);
+    }
+
+    let folder = user_data_dir()
+        .ok_or(ExperimentError::NoDataDir)?
+        .join(EXPERIMENT_FOLDER)
+        .join(file_stem(&experiment.name));
+    std::fs::create_dir_all(&folder)?;
+    let mut results = BufWriter::new(File::create(folder.join(RESULTS_FILE))?);
+    let mut columns = vec!["run".to_string()];
+    columns.extend(experiment.sweeps.iter().map(|sweep| format!("{:?}", sweep.param)));
+    columns.extend(["ms_per_tick", "mean_height", "mean_temperature"].map(String::from));
+    columns.extend(Particle::ALL.iter().map(|particle| format!("{:?}", particle)));
+    writeln!(results, "{}", columns.join(","))?;
+
+    let runs: usize = experiment.sweeps.iter().map(|sweep| sweep.values.len()).product();
+    let schedule = experiment.quality.schedule();
+    // Only the built-in chemistry runs; rules files are assets, and experiments load none.
+    let (reactions, behaviors) = (ReactionTable::default(), MaterialBehaviors::default());
+    for run in 0..runs {
+        // The run number, written in mixed radix, picks one value from each sweep.
+        let mut params = experiment.params.clone();
+        let mut rest = run;
+        let mut values = Vec::new();
+        for sweep in &experiment.sweeps {
+            let value = sweep.values[rest % sweep.values.len()];
+            rest /= sweep.values.len();
+            sweep.param.set(&mut params, value);
+            values.push(value);
+        }
+
+        let mut grid = start.clone();
+        let started = Instant::now();
+        for tick in 0..experiment.ticks {
+            crate::sim::step(&mut grid, tick, &params, &schedule, &reactions, &behaviors);
+        }
+        let elapsed = started.elapsed().as_secs_f32() * 1000.0;
+        let outcome = measure(&grid, elapsed / experiment.ticks.max(1) as f32);
+
+        let mut row = vec![run.to_string()];
+        row.extend(values.iter().map(|value| value.to_string()));
+        row.push(format!("{:.4}", outcome.ms_per_tick));
+        row.push(format!("{:.2}", outcome.mean_height));
+        row.push(format!("{:.2}", outcome.mean_temperature));
+        row.extend(outcome.counts.iter().map(|count| count.to_string()));
+        writeln!(results, "{}", row.join(","))?;
+        picture(&grid).save(folder.join(format!("run_{:03}.png", run)))?;
+        println!("Run {}/{} done ({:.3} ms per tick)", run + 1, runs, outcome.ms_per_tick);
+    }
+    results.flush()?;
+    Ok(folder)
+}
+
+fn measure(grid: &SimulationGrid, ms_per_tick: f32) -> Outcome {
+    let mut counts = [0; Particle::ALL.len()];
+    let (mut falling, mut height) = (0u64, 0u64);
+    let width = grid.width() as usize;
+    for (i, &particle) in grid.cells().iter().enumerate() {
+        counts[particle as usize] += 1;
+        if matches!(particle.class(), MaterialClass::Powder | MaterialClass::Liquid) {
+            falling += 1;
+            height += (i / width) as u64;
+        }
+    }
+    let temperatures = grid.temperatures();
+    Outcome {
+        ms_per_tick,
+        counts,
+        mean_height: height as f32 / falling.max(1) as f32,
+        mean_temperature: temperatures.iter().sum::<f32>() / temperatures.len().max(1) as f32,
+    }
+}
+
+// The world with every cell in its material's color, top row first.
+fn picture(grid: &SimulationGrid) -> image::RgbaImage {
+    let height = grid.height();
+    image::RgbaImage::from_fn(grid.width(), height, |x, y| {
+        let particle = grid.get(x as i32, (height - 1 - y) as i32).unwrap_or(Particle::Air);
+        image::Rgba(particle.color().to_srgba().to_u8_array())
+    })
+}
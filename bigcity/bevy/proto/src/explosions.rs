@@ -0,0 +1,85 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::sim::{SimulationGrid, SimulationSet};
+
+// --- CONSTANTS ---
+// Cells just outside the blast are scorched out to this multiple of its radius.
+const SCORCH_REACH: f32 = 1.5;
+// Degrees added at the edge of the blast, fading to nothing at the edge of the scorch ring.
+const SCORCH_HEAT: f32 = 250.0;
+// Powders, water and oil anywhere in reach are flung outwards at up to this many cells per tick
+// instead.
+const FLING_SPEED: f32 = 7.0;
+
+// --- PLUGIN ---
+pub struct ExplosionsPlugin;
+
+impl Plugin for ExplosionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Explosion>()
+            .add_systems(Update, detonate.before(SimulationSet));
+    }
+}
+
+// --- EVENTS ---
+
+// A blast centered on `center` (in cells). It blows away everything but bedrock within `radius`
+// cells, heats what is left around it and throws dust into the air. Anything can set one off by sending this event.
+#[derive(Event, Debug, Clone, Copy)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Explosion {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+// --- SYSTEMS ---
+
+fn detonate(
+    mut explosions: EventReader<Explosion>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    for explosion in explosions.read() {
+        blast(&mut grid, explosion, rules.protect_world);
+    }
+}
+
+// --- HELPERS ---
+
+// Blows `explosion` into the grid. With `protect_world`, only cells a player placed are blown
+// away or flung, like in a level.
+pub fn blast(grid: &mut SimulationGrid, explosion: &Explosion, protect_world: bool) {
+    let reach = explosion.radius * SCORCH_REACH;
+    let min = (explosion.center - Vec2::splat(reach)).floor().as_ivec2();
+    let max = (explosion.center + Vec2::splat(reach)).ceil().as_ivec2();
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let Some(particle) = grid.get(x, y) else { continue };
+            let center = CellPos::new(x, y).center();
+            let distance = center.distance(explosion.center);
+            if distance > reach {
+                continue;
+            }
+            // Like brushes, blasts in a level leave the level's own cells alone.
+            let protected = protect_world && !grid.is_placed(x, y);
+            // Whatever can fly (see `SimulationGrid::launch`) is thrown rather than destroyed.
+            if grid.velocity(x, y).is_some() && !protected {
+                let away = (center - explosion.center).normalize_or(Vec2::Y);
+                let speed = FLING_SPEED * (1.0 - distance / reach);
+                grid.launch(x, y, (away * speed).round().as_ivec2());
+            } else if distance <= explosion.radius && particle != Particle::Bedrock && !protected {
+                grid.set(x, y, Particle::Air);
+            } else {
+                let falloff =
+                    1.0 - (distance - explosion.radius).max(0.0) / (reach - explosion.radius);
+                grid.add_heat(x, y, SCORCH_HEAT * falloff);
+            }
+        }
+    }
+    info!("Explosion at {:?}", explosion.center.floor().as_ivec2());
+}
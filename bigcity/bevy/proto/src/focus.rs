@@ -0,0 +1,124 @@
+// --- IMPORTS ---
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::window::{PrimaryWindow, WindowOccluded};
+use bevy::winit::{UpdateMode, WinitSettings};
+use serde::{Deserialize, Serialize};
+
+use crate::display::DisplaySettings;
+use crate::sim::SimulationSet;
+
+// --- CONSTANTS ---
+// How often the window still updates in the background while throttled, and while paused.
+const THROTTLED_FRAME_TIME: Duration = Duration::from_millis(100);
+const PAUSED_FRAME_TIME: Duration = Duration::from_millis(500);
+
+// --- PLUGIN ---
+
+// What the game does while its window is in the background, as the display settings' focus policy
+// says: keep running at full speed, throttle (the world keeps going, but the window only updates
+// ten times a second, so ticks are capped by the frame budget), or pause (the world stops and the
+// window updates twice a second). A minimized or fully covered window has a policy of its own.
+// Coming back resumes where the world left off, without catching up on the missed time.
+pub struct FocusPlugin;
+
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(Update, SimulationSet.run_if(not(resource_exists::<Suspended>)))
+            .configure_sets(FixedUpdate, SimulationSet.run_if(not(resource_exists::<Suspended>)))
+            .add_systems(PreUpdate, apply_focus_policy);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum Background {
+    Run,
+    Throttle,
+    Pause,
+}
+
+impl Background {
+    pub const ALL: [Background; 3] = [Background::Run, Background::Throttle, Background::Pause];
+
+    fn update_mode(self) -> UpdateMode {
+        match self {
+            Background::Run => UpdateMode::Continuous,
+            Background::Throttle => UpdateMode::reactive_low_power(THROTTLED_FRAME_TIME),
+            Background::Pause => UpdateMode::reactive_low_power(PAUSED_FRAME_TIME),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct FocusPolicy {
+    // While another window has focus.
+    pub unfocused: Background,
+    // While the window is minimized or hidden behind others.
+    pub minimized: Background,
+}
+
+impl Default for FocusPolicy {
+    fn default() -> Self {
+        Self {
+            unfocused: Background::Throttle,
+            minimized: Background::Pause,
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// Present while the world is paused because the window is in the background.
+#[derive(Resource)]
+struct Suspended;
+
+// --- SYSTEMS ---
+
+fn apply_focus_policy(
+    mut commands: Commands,
+    settings: Res<DisplaySettings>,
+    mut occlusions: EventReader<WindowOccluded>,
+    q_window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    mut winit: ResMut<WinitSettings>,
+    mut occluded: Local<bool>,
+    mut current: Local<Option<Background>>,
+) {
+    let Ok((primary, window)) = q_window.single() else { return };
+    for occlusion in occlusions.read().filter(|occlusion| occlusion.window == primary) {
+        *occluded = occlusion.occluded;
+    }
+    let background = if *occluded {
+        Some(settings.focus.minimized)
+    } else if !window.focused {
+        Some(settings.focus.unfocused)
+    } else {
+        None
+    };
+    let moved = background != *current;
+    if !moved && !settings.is_changed() {
+        return;
+    }
+    *current = background;
+
+    // Winit switches between the two modes on focus changes by itself; a covered window may
+    // still have focus, though.
+    let (unfocused, minimized) = (settings.focus.unfocused, settings.focus.minimized);
+    winit.unfocused_mode = if *occluded { minimized } else { unfocused }.update_mode();
+    winit.focused_mode = if *occluded { minimized.update_mode() } else { UpdateMode::Continuous };
+    if background == Some(Background::Pause) {
+        commands.insert_resource(Suspended);
+    } else {
+        commands.remove_resource::<Suspended>();
+    }
+    match background {
+        _ if !moved => {}
+        Some(background) => info!("Window in the background: {:?}", background),
+        None => info!("Window in the foreground"),
+    }
+}
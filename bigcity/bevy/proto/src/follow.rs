@@ -0,0 +1,199 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::access::SimulationAccess;
+use crate::coords::CellPos;
+use crate::sim::SimulationSet;
+use crate::{Particle, ScreenCamera, WORLD_UNITS_PER_CELL, WorldView};
+
+// --- CONSTANTS ---
+// The camera's zoom while following, as an orthographic scale (1 shows the whole world).
+const FOLLOW_SCALE: f32 = 0.4;
+// How quickly the camera catches up with its target, per second; higher is snappier.
+const CATCH_UP: f32 = 5.0;
+// How close to the cursor, in cells, a moving thing has to be for F to pick it.
+const PICK_RADIUS: f32 = 12.0;
+// How far from where it was last seen, in cells, a followed cluster is looked for again.
+const SEARCH_RADIUS: i32 = 4;
+// How long the camera stays put where its target disappeared, in seconds, before pulling back.
+const LINGER_SECS: f32 = 1.5;
+
+// --- PLUGIN ---
+
+// Follow camera (F): zooms in on the thing under the cursor and keeps it in view as it moves, for
+// watching long falls in big worlds or recording a shot. Grenades and meteors in flight are
+// followed directly; anything else under the cursor is followed as its cluster (the cells of the
+// same material connected to it), tracked by its centroid. When the target is gone, like a meteor
+// that struck, the camera holds the spot for a moment and then pulls back to the whole world. F
+// again stops following.
+pub struct FollowPlugin;
+
+impl Plugin for FollowPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FollowCamera>().add_systems(
+            Update,
+            (pick_target, track_target, move_camera).chain().after(SimulationSet),
+        );
+    }
+}
+
+// --- TYPES ---
+
+enum FollowTarget {
+    Entity(Entity),
+    // The cluster of `particle` last seen around `cell`.
+    Cluster { particle: Particle, cell: CellPos },
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct FollowCamera {
+    target: Option<FollowTarget>,
+    // Where the camera is heading, in world space, while it follows or lingers.
+    focus: Option<Vec2>,
+    // How long the camera has lingered since the target disappeared.
+    lingered: f32,
+    // Whether the camera is still easing back to the whole world.
+    returning: bool,
+}
+
+impl FollowCamera {
+    fn stop(&mut self) {
+        self.target = None;
+        self.focus = None;
+        self.returning = true;
+    }
+}
+
+// --- COMPONENTS ---
+
+// Marks moving things the follow camera can pick, like grenades and meteors. Their transform is
+// what gets followed.
+#[derive(Component)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct Followable;
+
+// --- SYSTEMS ---
+
+fn pick_target(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    access: SimulationAccess,
+    q_followable: Query<(Entity, &Transform), With<Followable>>,
+    mut follow: ResMut<FollowCamera>,
+) {
+    if !keys.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+    if follow.target.is_some() {
+        follow.stop();
+        info!("Stopped following");
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    let cursor = *view.cell_to_world(cell.center());
+    let nearest = q_followable
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(cursor)))
+        .filter(|(_, distance)| *distance <= PICK_RADIUS * WORLD_UNITS_PER_CELL)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    let target = match (nearest, access.particle(cell)) {
+        (Some((entity, _)), _) => FollowTarget::Entity(entity),
+        (None, Some(particle)) if particle != Particle::Air => {
+            FollowTarget::Cluster { particle, cell }
+        }
+        _ => {
+            info!("Nothing to follow under the cursor");
+            return;
+        }
+    };
+    match &target {
+        FollowTarget::Entity(entity) => info!("Following {:?}", entity),
+        FollowTarget::Cluster { particle, .. } => info!("Following a cluster of {:?}", particle),
+    }
+    follow.target = Some(target);
+    follow.lingered = 0.0;
+}
+
+// Finds where the target is now. Once it can't be found any more the camera lingers where it was
+// last seen, then gives up.
+fn track_target(
+    time: Res<Time>,
+    view: WorldView,
+    mut access: SimulationAccess,
+    q_followable: Query<&Transform, With<Followable>>,
+    mut follow: ResMut<FollowCamera>,
+) {
+    let found = match &mut follow.target {
+        None => None,
+        Some(FollowTarget::Entity(entity)) => {
+            q_followable.get(*entity).ok().map(|transform| transform.translation.truncate())
+        }
+        Some(FollowTarget::Cluster { particle, cell }) => {
+            let centroid = locate_cluster(&mut access, *particle, cell);
+            centroid.map(|centroid| *view.cell_to_world(centroid))
+        }
+    };
+    match found {
+        Some(position) => follow.focus = Some(position),
+        None if follow.target.is_some() => {
+            follow.lingered += time.delta_secs();
+            if follow.lingered >= LINGER_SECS {
+                follow.stop();
+                info!("Lost the followed target");
+            }
+        }
+        None => {}
+    }
+}
+
+// Eases the camera towards the focus, or back to the whole world once following ended. The camera
+// is left alone otherwise, so demos can move it.
+fn move_camera(
+    time: Res<Time>,
+    mut follow: ResMut<FollowCamera>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+    let (goal, scale) = match follow.focus {
+        Some(focus) => (focus, FOLLOW_SCALE),
+        None if follow.returning => (Vec2::ZERO, 1.0),
+        None => return,
+    };
+    let t = 1.0 - (-CATCH_UP * time.delta_secs()).exp();
+    let position = transform.translation.truncate().lerp(goal, t);
+    ortho.scale += (scale - ortho.scale) * t;
+    transform.translation = position.extend(transform.translation.z);
+
+    let settled = position.distance(goal) < 0.5 && (ortho.scale - scale).abs() < 0.005;
+    if follow.returning && settled {
+        transform.translation = goal.extend(transform.translation.z);
+        ortho.scale = scale;
+        follow.returning = false;
+    }
+}
+
+// --- HELPERS ---
+
+// The centroid, in cells, of the cluster of `particle` around `cell`, looking a few cells around
+// it if it has moved off. `cell` is moved into the cluster for next time.
+fn locate_cluster(
+    access: &mut SimulationAccess,
+    particle: Particle,
+    cell: &mut CellPos,
+) -> Option<Vec2> {
+    let seed = (-SEARCH_RADIUS..=SEARCH_RADIUS)
+        .flat_map(|dy| (-SEARCH_RADIUS..=SEARCH_RADIUS).map(move |dx| IVec2::new(dx, dy)))
+        .map(|offset| *cell + offset)
+        .filter(|&candidate| access.particle(candidate) == Some(particle))
+        .min_by_key(|candidate| candidate.distance_squared(cell.0))?;
+    let region = access.connected_region(seed)?;
+    let sum: Vec2 = region.cells.iter().map(|c| c.center()).sum();
+    let centroid = sum / region.cells.len() as f32;
+    // A hollow or bent cluster's centroid can lie outside it; then the seed is kept.
+    let middle = CellPos::containing(centroid);
+    *cell = if access.particle(middle) == Some(particle) { middle } else { seed };
+    Some(centroid)
+}
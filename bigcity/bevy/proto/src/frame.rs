@@ -0,0 +1,196 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::{BorderRect, SliceScaleMode, SpriteImageMode, TextureSlicer};
+use serde::{Deserialize, Serialize};
+
+use crate::display::DisplaySettings;
+use crate::sim::roll;
+use crate::{WORLD_UNITS_PER_CELL, WorldLayout};
+
+// --- CONSTANTS ---
+// How thick the procedural frame is, in texels, which are drawn one to a world unit.
+const FRAME_TEXELS: u32 = 12;
+// How far out from the world's edge the backdrop fades to black, in cells.
+const VIGNETTE_CELLS: u32 = 40;
+const FRAME_COLOR: [f32; 3] = [0.32, 0.29, 0.26];
+const BACKDROP_COLOR: [f32; 3] = [0.11, 0.11, 0.13];
+// The frame sits behind the world, the backdrop behind the frame.
+const FRAME_Z: f32 = -0.5;
+const BACKDROP_Z: f32 = -1.0;
+
+// --- PLUGIN ---
+
+// A decorative frame around the world, so a world that doesn't fill the window (one of another
+// shape, or a zoomed-out camera) doesn't float on an empty background. The display settings pick
+// none, a procedural bevelled frame, or a picture of the player's own cut into nine slices; either
+// frame comes with a backdrop that fades from the frame into black further out.
+pub struct FramePlugin;
+
+impl Plugin for FramePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            apply_frame
+                .run_if(resource_changed::<DisplaySettings>.or(resource_changed::<WorldLayout>)),
+        );
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub enum FrameStyle {
+    #[default]
+    None,
+    Procedural,
+    // A picture loaded from the assets (a `mods://` path works too), whose outer `border` texels
+    // on every side are the frame; the corners keep their size and the sides are stretched.
+    Texture { path: String, border: u32 },
+}
+
+impl FrameStyle {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FrameStyle::None => "None",
+            FrameStyle::Procedural => "Procedural",
+            FrameStyle::Texture { .. } => "Picture",
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct FramePart;
+
+// --- SYSTEMS ---
+
+// Replaces the frame whenever its style or the world's size changes; other display settings leave
+// it be.
+fn apply_frame(
+    mut commands: Commands,
+    settings: Res<DisplaySettings>,
+    layout: Res<WorldLayout>,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut applied: Local<Option<(FrameStyle, WorldLayout)>>,
+    q_parts: Query<Entity, With<FramePart>>,
+) {
+    if applied.as_ref() == Some(&(settings.frame.clone(), *layout)) {
+        return;
+    }
+    *applied = Some((settings.frame.clone(), *layout));
+    for entity in &q_parts {
+        commands.entity(entity).despawn();
+    }
+    let (image, border) = match &settings.frame {
+        FrameStyle::None => {
+            commands.insert_resource(ClearColor::default());
+            return;
+        }
+        FrameStyle::Procedural => (images.add(frame_image()), FRAME_TEXELS),
+        FrameStyle::Texture { path, border } => (asset_server.load(path.clone()), *border),
+    };
+    // Past the backdrop there is only black, which it fades into.
+    commands.insert_resource(ClearColor(Color::BLACK));
+
+    let world = layout.world_size();
+    let border = border as f32;
+    commands.spawn((
+        FramePart,
+        sliced_sprite(image, world + Vec2::splat(border * 2.0), border),
+        Transform::from_xyz(0.0, 0.0, FRAME_Z),
+    ));
+    let fade = (VIGNETTE_CELLS as f32 * WORLD_UNITS_PER_CELL).round();
+    let backdrop = images.add(backdrop_image(fade as u32));
+    commands.spawn((
+        FramePart,
+        sliced_sprite(backdrop, world + Vec2::splat(fade * 2.0), fade),
+        Transform::from_xyz(0.0, 0.0, BACKDROP_Z),
+    ));
+}
+
+// --- HELPERS ---
+
+// A sprite of `size` world units cut into nine slices, `border` texels in from every edge, and
+// drawn one texel to a world unit.
+fn sliced_sprite(image: Handle<Image>, size: Vec2, border: f32) -> Sprite {
+    Sprite {
+        image,
+        custom_size: Some(size),
+        image_mode: SpriteImageMode::Sliced(TextureSlicer {
+            border: BorderRect::all(border),
+            center_scale_mode: SliceScaleMode::Stretch,
+            sides_scale_mode: SliceScaleMode::Stretch,
+            max_corner_scale: 1.0,
+        }),
+        ..default()
+    }
+}
+
+// The procedural frame: a slightly mottled band, lit from the top left, with a bevel along its
+// outer edge and a shadowed lip along the inner one. The middle, which the world covers, is clear.
+fn frame_image() -> Image {
+    let side = FRAME_TEXELS * 2 + 2;
+    let mut data = Vec::with_capacity((side * side * 4) as usize);
+    for y in 0..side {
+        for x in 0..side {
+            // Texture rows run top-down.
+            let edges = [(x, true), (y, true), (side - 1 - x, false), (side - 1 - y, false)];
+            let nearest = edges.into_iter().min_by_key(|(depth, _)| *depth);
+            let (depth, lit) = nearest.unwrap_or_default();
+            if depth >= FRAME_TEXELS {
+                data.extend_from_slice(&[0, 0, 0, 0]);
+                continue;
+            }
+            let shade = if depth < 2 {
+                if lit { 1.35 } else { 0.65 }
+            } else if depth >= FRAME_TEXELS - 2 {
+                if lit { 0.55 } else { 1.15 }
+            } else {
+                0.95 + roll(x as i32, y as i32, 0) * 0.1
+            };
+            let [r, g, b] = FRAME_COLOR.map(|c| ((c * shade).clamp(0.0, 1.0) * 255.0) as u8);
+            data.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    let mut image = rgba_image(side, data);
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+// The backdrop: the backdrop color in the middle, behind the world and frame, fading to black
+// over `fade` texels out to every edge.
+fn backdrop_image(fade: u32) -> Image {
+    let side = fade * 2 + 2;
+    let mut data = Vec::with_capacity((side * side * 4) as usize);
+    for y in 0..side {
+        for x in 0..side {
+            let depth = x.min(y).min(side - 1 - x).min(side - 1 - y);
+            let light = (depth as f32 / fade as f32).min(1.0).powi(2);
+            let [r, g, b] = BACKDROP_COLOR.map(|c| (c * light * 255.0) as u8);
+            data.extend_from_slice(&[r, g, b, 255]);
+        }
+    }
+    rgba_image(side, data)
+}
+
+fn rgba_image(side: u32, data: Vec<u8>) -> Image {
+    let mut image = Image::new(
+        Extent3d {
+            width: side,
+            height: side,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::linear();
+    image
+}
@@ -0,0 +1,123 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::player::PlayerInputSet;
+use crate::sim::SimulationSet;
+use crate::world_commands::{WorldCommandSet, WorldCommands};
+
+// --- PLUGIN ---
+
+// The order a frame runs in, as system sets, configured here and nowhere else:
+//
+// 1. `PlayerInputSet`: devices are read into every player's cursor, material and brush.
+// 2. `WorldCommandSet`: the `WorldCommand`s pushed so far go into the grid. Anything that changes
+//    the world the way a player does pushes its commands before this set.
+// 3. `SimulationSet`: the world ticks, in `FixedUpdate` (which runs before `Update`, with its own
+//    `WorldCommandSet` ahead of every tick), and its statistics are refreshed in `Update`.
+// 4. `SwapSet`: the finished grid is handed to the render side, copied into the state texture,
+//    or, while the world steps on the GPU, its edits are sent up to the compute pass (gpu_sim.rs).
+// 5. `DisplaySet`: whatever draws from that copy, like the CPU display, and the brush outlines.
+//
+// Systems order themselves against the sets rather than against each other's functions, so new
+// ones slot in without knowing who else is there. Debug builds check the sets ran in this order
+// every frame, and that every command pushed was in the world before the ticks and the swap.
+pub struct FrameOrderPlugin;
+
+impl Plugin for FrameOrderPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            (PlayerInputSet, WorldCommandSet, SimulationSet, SwapSet, DisplaySet).chain(),
+        )
+        .configure_sets(FixedUpdate, (WorldCommandSet, SimulationSet).chain());
+        #[cfg(debug_assertions)]
+        app.init_resource::<FramePhase>()
+            .add_systems(First, start_frame)
+            .add_systems(
+                Update,
+                (
+                    enter(FramePhase::Commands).after(PlayerInputSet).before(WorldCommandSet),
+                    enter(FramePhase::Step).after(WorldCommandSet).before(SimulationSet),
+                    enter(FramePhase::Swap).after(SimulationSet).before(SwapSet),
+                    enter(FramePhase::Display).after(SwapSet).before(DisplaySet),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                check_applied.after(WorldCommandSet).before(SimulationSet),
+            );
+    }
+}
+
+// The finished grid is copied into the state texture in this set, after the frame's ticks.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SwapSet;
+
+// Drawing from what `SwapSet` copied runs in this set, last in the frame.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DisplaySet;
+
+// --- RESOURCES ---
+
+// How far `Update` has got through the sets this frame, in debug builds.
+#[cfg(debug_assertions)]
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum FramePhase {
+    #[default]
+    Input,
+    Commands,
+    Step,
+    Swap,
+    Display,
+}
+
+#[cfg(debug_assertions)]
+impl FramePhase {
+    fn previous(self) -> Option<Self> {
+        match self {
+            Self::Input => None,
+            Self::Commands => Some(Self::Input),
+            Self::Step => Some(Self::Commands),
+            Self::Swap => Some(Self::Step),
+            Self::Display => Some(Self::Swap),
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+#[cfg(debug_assertions)]
+fn start_frame(mut phase: ResMut<FramePhase>) {
+    *phase = FramePhase::Input;
+}
+
+// Moves on to `next`, which has to follow the phase before it; the grid isn't handed on to the
+// render side while commands are still waiting to go in.
+#[cfg(debug_assertions)]
+fn enter(next: FramePhase) -> impl FnMut(ResMut<FramePhase>, Res<WorldCommands>) {
+    move |mut phase, commands| {
+        assert_eq!(
+            next.previous(),
+            Some(*phase),
+            "frame sets ran out of order: {:?} after {:?}",
+            next,
+            *phase
+        );
+        if next == FramePhase::Swap {
+            assert!(
+                commands.is_empty(),
+                "world commands were pushed after WorldCommandSet; push them before it"
+            );
+        }
+        *phase = next;
+    }
+}
+
+// Every command pushed by tick went in before the tick runs.
+#[cfg(debug_assertions)]
+fn check_applied(commands: Res<WorldCommands>) {
+    assert!(
+        commands.is_empty(),
+        "world commands were pushed in FixedUpdate after WorldCommandSet; push them before it"
+    );
+}
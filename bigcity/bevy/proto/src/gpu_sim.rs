@@ -0,0 +1,1100 @@
+// --- IMPORTS ---
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bevy::asset::RenderAssetUsages;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
+use bevy::render::render_resource::binding_types::{
+    storage_buffer_read_only_sized, storage_buffer_sized, texture_2d, texture_storage_2d,
+    uniform_buffer_sized,
+};
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, BufferInitDescriptor,
+    BufferUsages, CachedComputePipelineId, CachedPipelineState, ComputePassDescriptor,
+    ComputePipelineDescriptor, Extent3d, Origin3d, PipelineCache, PipelineCacheError,
+    ShaderStages, StorageTextureAccess, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
+    TextureUsages, TextureView, TextureViewDescriptor,
+};
+use bevy::render::renderer::{RenderAdapter, RenderContext, RenderDevice, RenderQueue};
+use bevy::render::storage::{GpuShaderStorageBuffer, ShaderStorageBuffer};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSet};
+
+use crate::behavior::MaterialBehaviors;
+use crate::coords::CellPos;
+use crate::degradation::Degradation;
+use crate::display::DisplaySettings;
+use crate::frame_order::SwapSet;
+use crate::reaction_rules::ReactionTable;
+use crate::region_readback::{RegionId, RegionRead, RegionReadbacks};
+use crate::sim::{
+    AMBIENT_TEMPERATURE, CellState, SimParams, SimulationBackend, SimulationControl,
+    SimulationGrid, SimulationSet, SimulationStats, SimulationTickRate, TickSchedule, ViewOnly,
+};
+use crate::thermal::ThermalView;
+use crate::{MaterialClass, Particle, SimulationDisplay, SimulationMaterial};
+
+// --- CONSTANTS ---
+const GPU_SIM_SHADER: &str = "shaders/gpu_sim.wgsl";
+// Keep in sync with the shader's `@workgroup_size`.
+const WORKGROUP_SIZE: u32 = 8;
+// A tick is this many passes of 2x2 blocks, one for each way of laying the blocks over the world,
+// so every pair of neighbouring cells shares a block once a tick.
+const BLOCK_OFFSETS: [[u32; 2]; 4] = [[0, 0], [1, 1], [0, 1], [1, 0]];
+// The size of the shader's `Params`: the world's size, the blocks' offset, two seeds and how many
+// regions there are along a row, padded.
+const PARAMS_SIZE: u64 = 32;
+// Edits to more than this share of the world's cells go up as the whole world instead.
+const FULL_UPLOAD_SHARE: usize = 16;
+// The side of the squares of cells the pass flags as changed, and only which are read back. Keep
+// in sync with the shader's `REGION`.
+const REGION: u32 = 32;
+// The materials whose only rules are the movement the pass runs. A world with anything else in it
+// steps on the CPU; water is left out too, for the foam falling water churns up.
+const MOVEMENT_ONLY: [Particle; 7] = [
+    Particle::Air,
+    Particle::Bedrock,
+    Particle::Sand,
+    Particle::Mirror,
+    Particle::Glass,
+    Particle::Lead,
+    Particle::Oil,
+];
+// How far from the air's temperature a cell may be before the world needs the CPU's heat rules.
+const TEMPERATURE_SLACK: f32 = 1.0;
+
+// --- PLUGIN ---
+
+// Stepping the world on the GPU instead of the CPU, in a compute pass of its own in the render
+// graph, ahead of the cameras: the world lives in a pair of storage textures the pass steps back
+// and forth between, and the result is copied into the state texture the world is drawn from, so
+// there is no extra camera or sprite. Every tick is four passes over the world in 2x2 blocks
+// (8x8 blocks of threads each), the blocks shifted a cell along x, y or both from one pass to the
+// next so that no two threads ever touch the same cell; powders and liquids sink through what is
+// lighter and slide off what they can't, liquids spread and gases rise. The fixed timestep paces
+// the ticks as on the CPU, so a slow frame runs several ticks' passes in one go and the speed and
+// pause controls work the same.
+//
+// The GPU only runs those movement rules; `SimulationBackend` lists what it leaves out, and the
+// world stays on the CPU while it holds anything those rules would miss: a material with rules of
+// its own, a cell hotter or colder than the air, zones, overrides, loops, hourglass mode, frozen
+// tags or the thermostat. Cells keep all their state through the GPU: the pass moves an id along
+// with every texel, the id of the cell as the grid last held it, and flags the 32x32 regions
+// where something moved. Only those regions' ids are read back (see region_readback.rs), a frame
+// or two late, and the grid takes the cells they name, tags and all, so tools, stats and saves
+// carry on working. Cells changed in the grid meanwhile (painting, pastes, loading a world) go up
+// as edits under new ids. The display settings pick the backend at any time; it also stays on the
+// CPU where the GPU has no compute shaders (WebGL) or the pass fails to build, and while the world
+// is mirrored from a host.
+pub struct GpuSimPlugin;
+
+impl Plugin for GpuSimPlugin {
+    fn build(&self, app: &mut App) {
+        let shared = SharedFrames::default();
+        app.insert_resource(shared.clone())
+            .add_systems(
+                FixedUpdate,
+                count_gpu_ticks.run_if(resource_exists::<GpuStepping>).in_set(SimulationSet),
+            )
+            .add_systems(
+                Update,
+                (
+                    pick_simulation_backend,
+                    switch_simulation_backend.run_if(resource_changed::<SimulationBackend>),
+                )
+                    .chain()
+                    .before(SimulationSet),
+            )
+            .add_systems(PreUpdate, mirror_gpu_world.run_if(resource_exists::<GpuStepping>))
+            .add_systems(
+                Update,
+                send_to_gpu.in_set(SwapSet).run_if(resource_exists::<GpuStepping>),
+            );
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(shared)
+                .init_resource::<GpuWorld>()
+                .add_systems(Render, prepare_passes.in_set(RenderSet::PrepareBindGroups));
+            let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+            graph.add_node(GpuSimLabel, GpuSimNode);
+            graph.add_node_edge(GpuSimLabel, bevy::render::graph::CameraDriverLabel);
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<GpuSimPipeline>();
+        }
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct GpuSimLabel;
+
+// What one frame sends the render world: how many ticks to run and what changed in the grid.
+struct GpuFrame {
+    // Counted up from 1 by `send_to_gpu`.
+    number: u64,
+    ticks: u32,
+    // The first of those ticks, and the world's seed, for the shader's dice.
+    first_tick: u64,
+    seed: u64,
+    size: UVec2,
+    upload: Upload,
+    state_image: AssetId<Image>,
+    // Where the pass's cell ids are copied for reading back, and its flags of changed regions.
+    ids_image: AssetId<Image>,
+    dirty: AssetId<ShaderStorageBuffer>,
+}
+
+enum Upload {
+    // Texels of the state texture to change, by texel (rows top-down), and the ids of the cells
+    // going there.
+    Edits(Vec<(UVec2, [u8; 4], u32)>),
+    // The whole world, as the state texture holds it, and every texel's id.
+    Full { texels: Vec<u8>, ids: Vec<u32> },
+}
+
+impl GpuFrame {
+    // Folds a later frame into this one, for when the render world hasn't taken this one yet.
+    fn merge(&mut self, later: GpuFrame) {
+        self.number = later.number;
+        self.ticks += later.ticks;
+        self.seed = later.seed;
+        self.state_image = later.state_image;
+        self.ids_image = later.ids_image;
+        self.dirty = later.dirty;
+        let resized = self.size != later.size;
+        self.size = later.size;
+        let width = self.size.x;
+        match later.upload {
+            Upload::Full { texels, ids } => self.upload = Upload::Full { texels, ids },
+            // Edits to a world of another size can't be kept; the next full upload replaces them.
+            Upload::Edits(later) if resized => self.upload = Upload::Edits(later),
+            Upload::Edits(later) => match &mut self.upload {
+                Upload::Edits(edits) => edits.extend(later),
+                Upload::Full { texels, ids } => {
+                    for (texel, value, id) in later {
+                        let i = (texel.y * width + texel.x) as usize;
+                        texels[i * 4..i * 4 + 4].copy_from_slice(&value);
+                        ids[i] = id;
+                    }
+                }
+            },
+        }
+    }
+}
+
+// A cell as the grid holds it, tag and all, which is what a cell id on the GPU stands for.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+struct KeptCell {
+    state: CellState,
+    tag: Option<usize>,
+}
+
+// --- RESOURCES ---
+
+// Present while the world steps on the GPU: the ticks due since the last frame was sent, and how
+// the grid and the GPU's world are kept in step.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct GpuStepping {
+    ticks: u32,
+    // The last frame sent, and the last that sent the whole world.
+    frame: u64,
+    full_frame: u64,
+    // The world's size and the thermal view's scale as last sent; either changing sends the
+    // whole world again.
+    size: UVec2,
+    scale: Option<(f32, f32)>,
+    // What every cell id on the GPU stands for: the cells as they were when the whole world went
+    // up, by grid index, then every cell edited since, in order.
+    store: Vec<KeptCell>,
+    // Every cell of the grid as the GPU was last known to have it, to tell edits from, and the
+    // frame each was last sent up as an edit in.
+    synced: Vec<KeptCell>,
+    edited: Vec<u64>,
+    // Regions of ids asked for and not yet read, each with the last frame the render world had
+    // taken when it was asked for, which the read is at least as new as.
+    reading: Vec<(RegionId, u64)>,
+    ids_image: Handle<Image>,
+    dirty: Handle<ShaderStorageBuffer>,
+    // The readback of `dirty`, every frame.
+    readback: Option<Entity>,
+}
+
+// The frame waiting for the render world, shared by both worlds, the number of the last frame
+// the render world took, and whether the pass failed to build.
+#[derive(Resource, Clone, Default)]
+struct SharedFrames {
+    frame: Arc<Mutex<Option<GpuFrame>>>,
+    taken: Arc<AtomicU64>,
+    failed: Arc<AtomicBool>,
+}
+
+impl SharedFrames {
+    fn send(&self, frame: GpuFrame) {
+        let mut waiting = self.frame.lock().unwrap();
+        match waiting.as_mut() {
+            Some(waiting) => waiting.merge(frame),
+            None => *waiting = Some(frame),
+        }
+    }
+}
+
+// Render world: the pass's pipeline, its bind group layout and every material's class and density.
+#[derive(Resource)]
+struct GpuSimPipeline {
+    layout: BindGroupLayout,
+    materials: bevy::render::render_resource::Buffer,
+    step: CachedComputePipelineId,
+}
+
+impl FromWorld for GpuSimPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let shader = world.load_asset(GPU_SIM_SHADER);
+        let device = world.resource::<RenderDevice>();
+        let layout = device.create_bind_group_layout(
+            "gpu_sim_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    texture_2d(TextureSampleType::Uint),
+                    texture_storage_2d(TextureFormat::Rgba8Uint, StorageTextureAccess::WriteOnly),
+                    uniform_buffer_sized(false, NonZeroU64::new(PARAMS_SIZE)),
+                    storage_buffer_read_only_sized(false, None),
+                    texture_2d(TextureSampleType::Uint),
+                    texture_storage_2d(TextureFormat::R32Uint, StorageTextureAccess::WriteOnly),
+                    storage_buffer_sized(false, None),
+                ),
+            ),
+        );
+        let materials = device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("gpu_sim_materials"),
+            contents: &material_table(),
+            usage: BufferUsages::STORAGE,
+        });
+        let step = world.resource::<PipelineCache>().queue_compute_pipeline(
+            ComputePipelineDescriptor {
+                label: Some("gpu_sim_step".into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader,
+                shader_defs: Vec::new(),
+                entry_point: "step_world".into(),
+                zero_initialize_workgroup_memory: false,
+            },
+        );
+        Self {
+            layout,
+            materials,
+            step,
+        }
+    }
+}
+
+// Render world: the two textures the world steps between and the two its cell ids step between,
+// which of each holds it now, and this frame's passes, each a bind group reading one and writing
+// the other.
+#[derive(Resource, Default)]
+struct GpuWorld {
+    textures: Vec<(Texture, TextureView)>,
+    ids: Vec<(Texture, TextureView)>,
+    size: UVec2,
+    current: usize,
+    passes: Vec<BindGroup>,
+    // The state texture and the ids image to copy the result into, while the GPU steps.
+    state_image: Option<AssetId<Image>>,
+    ids_image: Option<AssetId<Image>>,
+    // The pass's flags of changed regions, cleared before every frame's passes.
+    dirty: Option<AssetId<ShaderStorageBuffer>>,
+}
+
+// --- SYSTEM PARAM ---
+
+// What decides whether the GPU can step the world at all.
+#[derive(SystemParam)]
+struct GpuSupport<'w> {
+    shared: Res<'w, SharedFrames>,
+    view_only: Option<Res<'w, ViewOnly>>,
+    adapter: Option<Res<'w, RenderAdapter>>,
+    device: Option<Res<'w, RenderDevice>>,
+}
+
+// What decides whether the world needs the CPU's rules.
+#[derive(SystemParam)]
+struct WorldRules<'w> {
+    grid: Res<'w, SimulationGrid>,
+    params: Res<'w, SimParams>,
+    behaviors: Res<'w, MaterialBehaviors>,
+    reactions: Res<'w, ReactionTable>,
+}
+
+// The assets the GPU's world is kept in and shown through.
+#[derive(SystemParam)]
+struct GpuAssets<'w> {
+    display: Res<'w, SimulationDisplay>,
+    images: ResMut<'w, Assets<Image>>,
+    buffers: ResMut<'w, Assets<ShaderStorageBuffer>>,
+    sim_materials: ResMut<'w, Assets<SimulationMaterial>>,
+}
+
+// --- SYSTEMS ---
+
+// The settings' backend, or the CPU wherever the GPU can't step the world, or the world needs
+// rules the GPU doesn't run.
+fn pick_simulation_backend(
+    settings: Res<DisplaySettings>,
+    support: GpuSupport,
+    rules: WorldRules,
+    mut backend: ResMut<SimulationBackend>,
+    mut refused: Local<Option<String>>,
+) {
+    let mut wanted = settings.simulation;
+    if wanted == SimulationBackend::Gpu {
+        let reason = match (&support.adapter, &support.device) {
+            _ if support.view_only.is_some() => {
+                Some("the world is mirrored from a host".to_string())
+            }
+            _ if support.shared.failed.load(Ordering::Relaxed) => {
+                Some("its compute pass failed to build".to_string())
+            }
+            (Some(adapter), Some(device)) if supports_compute(adapter, device) => {
+                needs_cpu_rules(&rules)
+            }
+            _ => Some("this GPU can't run compute shaders on storage textures".to_string()),
+        };
+        if let Some(reason) = &reason {
+            if Some(reason) != refused.as_ref() {
+                warn!("Stepping the world on the CPU: {}", reason);
+            }
+            wanted = SimulationBackend::Cpu;
+        }
+        *refused = reason;
+    }
+    if *backend != wanted {
+        info!("Stepping the world on the {} backend", wanted.label());
+        *backend = wanted;
+    }
+}
+
+// Starts stepping the world on the GPU, or stops and leaves the grid where the GPU had got to.
+fn switch_simulation_backend(
+    mut commands: Commands,
+    backend: Res<SimulationBackend>,
+    stepping: Option<Res<GpuStepping>>,
+) {
+    match (*backend, stepping) {
+        (SimulationBackend::Gpu, None) => commands.insert_resource(GpuStepping::default()),
+        (SimulationBackend::Cpu, Some(stepping)) => {
+            if let Some(readback) = stepping.readback {
+                commands.entity(readback).despawn();
+            }
+            commands.remove_resource::<GpuStepping>();
+        }
+        _ => {}
+    }
+}
+
+// The CPU's `step_simulation`, for the GPU: the same pacing, pause and cap on ticks a frame, but
+// the ticks are only counted here, to be run by the compute pass.
+fn count_gpu_ticks(
+    tick_rate: Res<SimulationTickRate>,
+    schedule: Res<TickSchedule>,
+    degradation: Res<Degradation>,
+    mut control: ResMut<SimulationControl>,
+    mut stepping: ResMut<GpuStepping>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    let most = degradation.schedule(&schedule).max_ticks_per_frame.max(1);
+    if tick_rate.ticks_per_second <= 0.0 || stats.ticks_last_frame >= most || !control.running() {
+        return;
+    }
+    if control.paused {
+        control.pending_steps -= 1;
+    }
+    stepping.ticks += 1;
+    stats.tick += 1;
+    stats.ticks_last_frame += 1;
+}
+
+// Sends the frame's ticks, and every cell changed in the grid since the GPU last had it, to the
+// render world. Each edited cell goes up under a new id; the whole world goes up instead, under
+// fresh ids, when it was resized, the thermal view's scale changed, or edits or ids pile up.
+fn send_to_gpu(
+    mut commands: Commands,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    thermal: Res<ThermalView>,
+    shared: Res<SharedFrames>,
+    mut stepping: ResMut<GpuStepping>,
+    mut assets: GpuAssets,
+) {
+    let stepping = &mut *stepping;
+    stepping.frame += 1;
+    let size = UVec2::new(grid.width(), grid.height());
+    let scale = thermal.scale(&grid);
+    let kept = kept_cells(&grid);
+    let mut edits = Vec::new();
+    if stepping.size == size {
+        for (i, (now, was)) in kept.iter().zip(&stepping.synced).enumerate() {
+            if now == was {
+                continue;
+            }
+            let id = stepping.store.len() as u32;
+            stepping.store.push(*now);
+            stepping.edited[i] = stepping.frame;
+            let CellState { particle, temperature, age, stain, .. } = now.state;
+            let charge = grid.charges()[i];
+            let texel = crate::state_texel(particle, temperature, age, stain, charge, scale);
+            edits.push((texel_of(i, size), texel, id));
+        }
+    }
+    let full = stepping.size != size
+        || stepping.scale != scale
+        || edits.len() > kept.len() / FULL_UPLOAD_SHARE
+        || stepping.store.len() > kept.len() * 2;
+    let upload = if full {
+        if stepping.size != size {
+            stepping.dirty = assets.buffers.add(dirty_flags(size));
+            match stepping.readback {
+                Some(readback) => {
+                    commands.entity(readback).insert(Readback::buffer(stepping.dirty.clone()));
+                }
+                None => {
+                    let readback = commands
+                        .spawn(Readback::buffer(stepping.dirty.clone()))
+                        .observe(note_dirty_regions)
+                        .id();
+                    stepping.readback = Some(readback);
+                }
+            }
+        }
+        if stepping.scale != scale {
+            // Touching the material also makes its bind group pick up the texture.
+            if let Some(material) = assets.sim_materials.get_mut(&assets.display.material) {
+                material.view_mode = scale.is_some() as u32;
+            }
+        }
+        // A new image, so no read of the old ids can be taken for the new ones.
+        stepping.ids_image = assets.images.add(ids_image(size));
+        stepping.full_frame = stepping.frame;
+        stepping.size = size;
+        stepping.scale = scale;
+        stepping.store.clone_from(&kept);
+        stepping.edited = vec![0; kept.len()];
+        let mut texels = vec![0; kept.len() * 4];
+        crate::write_state_texels(&grid, scale, &mut texels);
+        let ids = (0..kept.len()).map(|t| index_of(t, size) as u32).collect();
+        Upload::Full { texels, ids }
+    } else {
+        Upload::Edits(edits)
+    };
+    stepping.synced = kept;
+    let ticks = std::mem::take(&mut stepping.ticks);
+    shared.send(GpuFrame {
+        number: stepping.frame,
+        ticks,
+        first_tick: stats.tick - ticks as u64,
+        seed: grid.seed(),
+        size,
+        upload,
+        state_image: assets.display.state_image.id(),
+        ids_image: stepping.ids_image.id(),
+        dirty: stepping.dirty.id(),
+    });
+}
+
+// Asks for the ids of every region the pass flagged as changed.
+fn note_dirty_regions(
+    trigger: Trigger<ReadbackComplete>,
+    stepping: Option<ResMut<GpuStepping>>,
+    shared: Res<SharedFrames>,
+    mut readbacks: ResMut<RegionReadbacks>,
+) {
+    let Some(mut stepping) = stepping else { return };
+    let size = stepping.size;
+    let across = size.x.div_ceil(REGION);
+    let flags = trigger.event().0.chunks_exact(4);
+    // Flags of a world since resized.
+    if flags.len() != (across * size.y.div_ceil(REGION)) as usize {
+        return;
+    }
+    let taken = shared.taken.load(Ordering::Acquire);
+    for (region, flag) in flags.enumerate() {
+        if flag == [0; 4] {
+            continue;
+        }
+        // Regions run top-down like texture rows, cells bottom-up.
+        let (x, y) = (region as u32 % across * REGION, region as u32 / across * REGION);
+        let min = CellPos(IVec2::new(x as i32, size.y as i32 - (y + REGION) as i32));
+        let id = readbacks.request(&stepping.ids_image, min, UVec2::splat(REGION));
+        stepping.reading.push((id, taken));
+    }
+}
+
+// Takes the cells the ids read back name into the grid, where they differ, leaving alone cells
+// edited since and any read from before the whole world last went up.
+fn mirror_gpu_world(
+    mut reads: EventReader<RegionRead>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stepping: ResMut<GpuStepping>,
+) {
+    let stepping = &mut *stepping;
+    let resized = UVec2::new(grid.width(), grid.height()) != stepping.size;
+    for read in reads.read() {
+        let Some(at) = stepping.reading.iter().position(|(id, _)| *id == read.id) else {
+            continue;
+        };
+        let (_, taken) = stepping.reading.swap_remove(at);
+        if taken < stepping.full_frame || resized {
+            continue;
+        }
+        let (min, size) = (read.min(), read.size().as_ivec2());
+        for y in min.y..min.y + size.y {
+            for x in min.x..min.x + size.x {
+                let Some(texel) = read.texel(CellPos(IVec2::new(x, y))) else { continue };
+                let Ok(id) = <[u8; 4]>::try_from(texel).map(u32::from_le_bytes) else { continue };
+                let Some(&kept) = stepping.store.get(id as usize) else { continue };
+                let i = (y * stepping.size.x as i32 + x) as usize;
+                if stepping.edited[i] > taken || stepping.synced[i] == kept {
+                    continue;
+                }
+                // A cell changed in the grid since the last frame was sent goes up as an edit.
+                let was = stepping.synced[i];
+                if grid.cell(x, y) != Some(was.state) || grid.tag(x, y) != was.tag {
+                    continue;
+                }
+                grid.set_cell(x, y, kept.state);
+                grid.set_tag(x, y, kept.tag);
+                stepping.synced[i] = kept;
+            }
+        }
+    }
+}
+
+// Render world: takes the frame sent, puts what changed into the textures holding the world and
+// its ids, and lays out the frame's passes.
+fn prepare_passes(
+    shared: Res<SharedFrames>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    cache: Res<PipelineCache>,
+    pipeline: Res<GpuSimPipeline>,
+    buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    mut world: ResMut<GpuWorld>,
+) {
+    world.passes.clear();
+    let Some(frame) = shared.frame.lock().unwrap().take() else {
+        world.state_image = None;
+        return;
+    };
+    shared.taken.store(frame.number, Ordering::Release);
+    world.state_image = Some(frame.state_image);
+    world.ids_image = Some(frame.ids_image);
+    world.dirty = Some(frame.dirty);
+    match frame.upload {
+        Upload::Full { texels, ids } => {
+            if world.size != frame.size || world.textures.is_empty() {
+                let texture = |format| world_texture(&device, frame.size, format);
+                world.textures = (0..2).map(|_| texture(TextureFormat::Rgba8Uint)).collect();
+                world.ids = (0..2).map(|_| texture(TextureFormat::R32Uint)).collect();
+                world.size = frame.size;
+                world.current = 0;
+            }
+            let ids: Vec<u8> = ids.into_iter().flat_map(u32::to_le_bytes).collect();
+            let current = world.current;
+            write_texels(&queue, &world.textures[current].0, UVec2::ZERO, frame.size, &texels);
+            write_texels(&queue, &world.ids[current].0, UVec2::ZERO, frame.size, &ids);
+        }
+        Upload::Edits(edits) => {
+            if world.size != frame.size || world.textures.is_empty() {
+                return;
+            }
+            let (texture, ids) = (&world.textures[world.current].0, &world.ids[world.current].0);
+            for (texel, value, id) in edits {
+                write_texels(&queue, texture, texel, UVec2::ONE, &value);
+                write_texels(&queue, ids, texel, UVec2::ONE, &id.to_le_bytes());
+            }
+        }
+    }
+
+    if let CachedPipelineState::Err(err) = cache.get_compute_pipeline_state(pipeline.step)
+        && !matches!(
+            err,
+            PipelineCacheError::ShaderNotLoaded(_) | PipelineCacheError::ShaderImportNotYetAvailable
+        )
+    {
+        shared.failed.store(true, Ordering::Relaxed);
+    }
+    let Some(dirty) = buffers.get(frame.dirty) else { return };
+    if cache.get_compute_pipeline(pipeline.step).is_none() {
+        return;
+    }
+    for tick in 0..frame.ticks as u64 {
+        for (pass, offset) in BLOCK_OFFSETS.into_iter().enumerate() {
+            let dice = (frame.first_tick + tick) * BLOCK_OFFSETS.len() as u64 + pass as u64;
+            let params = pass_params(frame.size, offset, dice, frame.seed);
+            let params = device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("gpu_sim_params"),
+                contents: &params.map(u32::to_le_bytes).concat(),
+                usage: BufferUsages::UNIFORM,
+            });
+            let (from, to) = (world.current, 1 - world.current);
+            let bind_group = device.create_bind_group(
+                "gpu_sim_pass",
+                &pipeline.layout,
+                &BindGroupEntries::sequential((
+                    &world.textures[from].1,
+                    &world.textures[to].1,
+                    params.as_entire_binding(),
+                    pipeline.materials.as_entire_binding(),
+                    &world.ids[from].1,
+                    &world.ids[to].1,
+                    dirty.buffer.as_entire_binding(),
+                )),
+            );
+            world.passes.push(bind_group);
+            world.current = to;
+        }
+    }
+}
+
+// --- NODES ---
+
+// Clears the flags of changed regions and runs the frame's passes, then copies the world into the
+// state texture for the cameras and its ids into the image they are read back from.
+struct GpuSimNode;
+
+impl render_graph::Node for GpuSimNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let gpu_world = world.resource::<GpuWorld>();
+        let buffers = world.resource::<RenderAssets<GpuShaderStorageBuffer>>();
+        if let Some(dirty) = gpu_world.dirty.and_then(|dirty| buffers.get(dirty)) {
+            render_context.command_encoder().clear_buffer(&dirty.buffer, 0, None);
+        }
+        let Some(state_image) = gpu_world.state_image else { return Ok(()) };
+        let Some((texture, _)) = gpu_world.textures.get(gpu_world.current) else { return Ok(()) };
+        let pipeline = world.resource::<GpuSimPipeline>();
+        let cache = world.resource::<PipelineCache>();
+        if let Some(step) = cache.get_compute_pipeline(pipeline.step) {
+            let groups = workgroups(gpu_world.size);
+            let mut pass = render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor::default());
+            pass.set_pipeline(step);
+            for bind_group in &gpu_world.passes {
+                pass.set_bind_group(0, bind_group, &[]);
+                pass.dispatch_workgroups(groups.x, groups.y, 1);
+            }
+        }
+        // The images are made over whenever the world is resized; wait for the new ones.
+        let images = world.resource::<RenderAssets<GpuImage>>();
+        let ids = &gpu_world.ids[gpu_world.current].0;
+        let copies = [(texture, Some(state_image)), (ids, gpu_world.ids_image)];
+        for (texture, image) in copies {
+            let Some(image) = image.and_then(|image| images.get(image)) else { continue };
+            if image.size.width != gpu_world.size.x || image.size.height != gpu_world.size.y {
+                continue;
+            }
+            render_context.command_encoder().copy_texture_to_texture(
+                texel_copy(texture, UVec2::ZERO),
+                texel_copy(&image.texture, UVec2::ZERO),
+                extent(gpu_world.size),
+            );
+        }
+        Ok(())
+    }
+}
+
+// --- HELPERS ---
+
+// Whether the GPU can run the pass: compute shaders, and storage textures of the state's format.
+fn supports_compute(adapter: &RenderAdapter, device: &RenderDevice) -> bool {
+    let usages = adapter.get_texture_format_features(TextureFormat::Rgba8Uint).allowed_usages;
+    device.limits().max_compute_workgroups_per_dimension > 0
+        && usages.contains(TextureUsages::STORAGE_BINDING)
+}
+
+// Every material's class and density, by particle id, as the shader's `materials` reads them.
+fn material_table() -> Vec<u8> {
+    Particle::ALL
+        .iter()
+        .flat_map(|particle| {
+            let class: u32 = match particle.class() {
+                MaterialClass::Gas => 0,
+                MaterialClass::Solid => 1,
+                MaterialClass::Powder => 2,
+                MaterialClass::Liquid => 3,
+            };
+            [class.to_le_bytes(), particle.density().to_le_bytes()]
+        })
+        .flatten()
+        .collect()
+}
+
+// Whether the world needs rules the pass doesn't run, and which.
+fn needs_cpu_rules(rules: &WorldRules) -> Option<String> {
+    let WorldRules { grid, params, behaviors, reactions } = rules;
+    let present = grid.cells().iter().fold(0u64, |mask, &p| mask | (1 << p as u64));
+    let mut held = Particle::ALL.iter().filter(|&&p| present & (1 << p as u64) != 0);
+    if let Some(particle) = held.clone().find(|p| !MOVEMENT_ONLY.contains(*p)) {
+        return Some(format!("{:?} has rules the GPU doesn't run", particle));
+    }
+    if let Some(particle) = held.find(|&&p| behaviors.has_behavior(p)) {
+        return Some(format!("{:?} has a behavior the GPU doesn't run", particle));
+    }
+    let off_ambient = |t: &f32| (t - AMBIENT_TEMPERATURE).abs() > TEMPERATURE_SLACK;
+    let reason = if reactions.reacts_among(present) {
+        "the world's materials react with each other"
+    } else if params.thermostat.is_some() {
+        "the thermostat is on"
+    } else if grid.temperatures().iter().any(off_ambient) {
+        "parts of the world are hotter or colder than the air"
+    } else if !grid.zones().is_empty() || !grid.material_overrides().is_empty() {
+        "the world has zones or material overrides"
+    } else if !grid.loops().is_empty() || grid.hourglass().is_some() {
+        "the world has loops or is in hourglass mode"
+    } else if grid.tags().freezes() {
+        "the world has frozen tags"
+    } else {
+        return None;
+    };
+    Some(reason.to_string())
+}
+
+// The shader's `Params` for one pass.
+fn pass_params(size: UVec2, offset: [u32; 2], dice: u64, seed: u64) -> [u32; 8] {
+    let seed = seed as u32 ^ (seed >> 32) as u32;
+    [size.x, size.y, offset[0], offset[1], dice as u32, seed, size.x.div_ceil(REGION), 0]
+}
+
+// How many workgroups a pass over a world of `size` dispatches, a thread to a 2x2 block.
+fn workgroups(size: UVec2) -> UVec2 {
+    let blocks = size / 2 + UVec2::ONE;
+    (blocks + UVec2::splat(WORKGROUP_SIZE - 1)) / WORKGROUP_SIZE
+}
+
+// Every cell of the grid as `KeptCell`s, by grid index.
+fn kept_cells(grid: &SimulationGrid) -> Vec<KeptCell> {
+    let (width, height) = (grid.width() as i32, grid.height() as i32);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let state = grid.cell(x, y)?;
+            Some(KeptCell {
+                state,
+                tag: grid.tag(x, y),
+            })
+        })
+        .collect()
+}
+
+// The texel of the cell at grid index `i`, and back: grid rows run bottom-up, texture rows
+// top-down.
+fn texel_of(i: usize, size: UVec2) -> UVec2 {
+    let (x, y) = (i as u32 % size.x, i as u32 / size.x);
+    UVec2::new(x, size.y - 1 - y)
+}
+
+fn index_of(texel: usize, size: UVec2) -> usize {
+    let (x, row) = (texel as u32 % size.x, texel as u32 / size.x);
+    ((size.y - 1 - row) * size.x + x) as usize
+}
+
+// The image the pass's cell ids are copied into for reading back, every id at first one no cell
+// has.
+fn ids_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        extent(size),
+        TextureDimension::D2,
+        &u32::MAX.to_le_bytes(),
+        TextureFormat::R32Uint,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC;
+    image
+}
+
+// The pass's flags of changed regions, a u32 for each region.
+fn dirty_flags(size: UVec2) -> ShaderStorageBuffer {
+    let regions = size.x.div_ceil(REGION) * size.y.div_ceil(REGION);
+    let mut buffer =
+        ShaderStorageBuffer::with_size(regions as usize * 4, RenderAssetUsages::RENDER_WORLD);
+    buffer.buffer_description.usage |= BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+    buffer
+}
+
+fn world_texture(
+    device: &RenderDevice,
+    size: UVec2,
+    format: TextureFormat,
+) -> (Texture, TextureView) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("gpu_sim_world"),
+        size: extent(size),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING
+            | TextureUsages::STORAGE_BINDING
+            | TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    (texture, view)
+}
+
+fn write_texels(queue: &RenderQueue, texture: &Texture, min: UVec2, size: UVec2, texels: &[u8]) {
+    let layout = TexelCopyBufferLayout {
+        offset: 0,
+        bytes_per_row: Some(size.x * 4),
+        rows_per_image: None,
+    };
+    queue.write_texture(texel_copy(texture, min), texels, layout, extent(size));
+}
+
+fn texel_copy(texture: &Texture, min: UVec2) -> TexelCopyTextureInfo<'_> {
+    TexelCopyTextureInfo {
+        texture,
+        mip_level: 0,
+        origin: Origin3d {
+            x: min.x,
+            y: min.y,
+            z: 0,
+        },
+        aspect: TextureAspect::All,
+    }
+}
+
+fn extent(size: UVec2) -> Extent3d {
+    Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Stepper, WorldBuilder, to_ascii};
+
+    fn frame(ticks: u32, upload: Upload) -> GpuFrame {
+        GpuFrame {
+            number: 0,
+            ticks,
+            first_tick: 0,
+            seed: 0,
+            size: UVec2::new(2, 2),
+            upload,
+            state_image: AssetId::default(),
+            ids_image: AssetId::default(),
+            dirty: AssetId::default(),
+        }
+    }
+
+    #[test]
+    fn frames_not_yet_taken_add_up() {
+        let shared = SharedFrames::default();
+        shared.send(frame(1, Upload::Edits(vec![(UVec2::new(0, 0), [2, 0, 0, 0], 4)])));
+        shared.send(frame(2, Upload::Edits(vec![(UVec2::new(1, 0), [3, 0, 0, 0], 5)])));
+        let taken = shared.frame.lock().unwrap().take().unwrap();
+        assert_eq!(taken.ticks, 3);
+        let Upload::Edits(edits) = taken.upload else { panic!("edits should stay edits") };
+        assert_eq!(edits.len(), 2);
+
+        // Edits after the whole world went up are worked into it, ids too.
+        let full = Upload::Full {
+            texels: vec![0; 16],
+            ids: vec![2, 3, 0, 1],
+        };
+        shared.send(frame(0, full));
+        shared.send(frame(1, Upload::Edits(vec![(UVec2::new(1, 1), [2, 0, 0, 0], 4)])));
+        let taken = shared.frame.lock().unwrap().take().unwrap();
+        let Upload::Full { texels, ids } = taken.upload else {
+            panic!("the world should go up whole")
+        };
+        assert_eq!(texels[12..16], [2, 0, 0, 0]);
+        assert_eq!(ids, [2, 3, 0, 4]);
+        assert_eq!(material_table().len(), Particle::ALL.len() * 8);
+        assert_eq!((0..4).map(|t| index_of(t, UVec2::new(2, 2))).collect::<Vec<_>>(), [2, 3, 0, 1]);
+        assert_eq!(texel_of(index_of(3, UVec2::new(2, 2)), UVec2::new(2, 2)), UVec2::new(1, 1));
+    }
+
+    // Steps `grid` `ticks` ticks with the pass on whatever adapter wgpu finds, outside Bevy and
+    // the way `prepare_passes` lays the passes out, and returns the world it ends up with; None
+    // where there is no GPU to run it on.
+    fn step_on_gpu(grid: &SimulationGrid, ticks: u64) -> Option<SimulationGrid> {
+        use bevy::tasks::block_on;
+        use wgpu::util::DeviceExt;
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = block_on(instance.request_adapter(&Default::default()))?;
+        let (device, queue) = block_on(adapter.request_device(&Default::default(), None)).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(include_str!("../assets/shaders/gpu_sim.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("step_world"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let layout = pipeline.get_bind_group_layout(0);
+
+        let size = UVec2::new(grid.width(), grid.height());
+        let cells = (size.x * size.y) as usize;
+        let texture = |format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: extent(size),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::STORAGE_BINDING
+                    | TextureUsages::COPY_DST
+                    | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let worlds = [texture(TextureFormat::Rgba8Uint), texture(TextureFormat::Rgba8Uint)];
+        let ids = [texture(TextureFormat::R32Uint), texture(TextureFormat::R32Uint)];
+        let mut texels = vec![0; cells * 4];
+        crate::write_state_texels(grid, None, &mut texels);
+        let id_texels: Vec<u8> =
+            (0..cells).flat_map(|t| (index_of(t, size) as u32).to_le_bytes()).collect();
+        let rows = TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(size.x * 4),
+            rows_per_image: None,
+        };
+        queue.write_texture(worlds[0].as_image_copy(), &texels, rows, extent(size));
+        queue.write_texture(ids[0].as_image_copy(), &id_texels, rows, extent(size));
+        let materials = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: &material_table(),
+            usage: BufferUsages::STORAGE,
+        });
+        let dirty = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: dirty_flags(size).buffer_description.size,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let view = |texture: &wgpu::Texture| texture.create_view(&Default::default());
+        let (worlds_views, ids_views) = (worlds.each_ref().map(view), ids.each_ref().map(view));
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let mut current = 0;
+        for tick in 0..ticks {
+            for (pass, offset) in BLOCK_OFFSETS.into_iter().enumerate() {
+                let dice = tick * BLOCK_OFFSETS.len() as u64 + pass as u64;
+                let params = pass_params(size, offset, dice, grid.seed());
+                let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: None,
+                    contents: &params.map(u32::to_le_bytes).concat(),
+                    usage: BufferUsages::UNIFORM,
+                });
+                let (from, to) = (current, 1 - current);
+                let resources = [
+                    wgpu::BindingResource::TextureView(&worlds_views[from]),
+                    wgpu::BindingResource::TextureView(&worlds_views[to]),
+                    params.as_entire_binding(),
+                    materials.as_entire_binding(),
+                    wgpu::BindingResource::TextureView(&ids_views[from]),
+                    wgpu::BindingResource::TextureView(&ids_views[to]),
+                    dirty.as_entire_binding(),
+                ];
+                let entries: Vec<_> = (0..)
+                    .zip(resources)
+                    .map(|(binding, resource)| wgpu::BindGroupEntry { binding, resource })
+                    .collect();
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: None,
+                    layout: &layout,
+                    entries: &entries,
+                });
+                let mut compute = encoder.begin_compute_pass(&Default::default());
+                compute.set_pipeline(&pipeline);
+                compute.set_bind_group(0, &bind_group, &[]);
+                let groups = workgroups(size);
+                compute.dispatch_workgroups(groups.x, groups.y, 1);
+                drop(compute);
+                current = to;
+            }
+        }
+
+        let stride = (size.x * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: (stride * size.y) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let layout = TexelCopyBufferLayout {
+            bytes_per_row: Some(stride),
+            ..rows
+        };
+        let copy = wgpu::TexelCopyBufferInfo {
+            buffer: &readback,
+            layout,
+        };
+        encoder.copy_texture_to_buffer(worlds[current].as_image_copy(), copy, extent(size));
+        queue.submit([encoder.finish()]);
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+
+        let mut stepped = grid.clone();
+        let texels = readback.slice(..).get_mapped_range();
+        for (row, texels) in texels.chunks(stride as usize).enumerate() {
+            let y = size.y as i32 - 1 - row as i32;
+            for (x, texel) in texels[..size.x as usize * 4].chunks(4).enumerate() {
+                stepped.set(x as i32, y, Particle::ALL[texel[0] as usize]);
+            }
+        }
+        Some(stepped)
+    }
+
+    #[test]
+    fn the_gpu_settles_layers_as_the_cpu_does() {
+        // Sand over oil in a box: however the two get there, sand ends up in the bottom four rows
+        // and oil in the five above.
+        let grid = WorldBuilder::new(16, 24)
+            .boxed()
+            .fill(Particle::Oil, (1, 1), (15, 6))
+            .fill(Particle::Sand, (1, 6), (15, 10))
+            .seed(7)
+            .build();
+        let Some(gpu) = step_on_gpu(&grid, 600) else {
+            eprintln!("No GPU adapter, not comparing the GPU's world with the CPU's");
+            return;
+        };
+        let mut cpu = Stepper::new(grid);
+        cpu.run(600);
+        assert_eq!(to_ascii(&gpu), to_ascii(cpu.grid()));
+        assert!(to_ascii(&gpu).lines().rev().nth(1).unwrap().contains("ssss"));
+    }
+}
@@ -0,0 +1,389 @@
+// --- IMPORTS ---
+use std::f32::consts::TAU;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContextPass, EguiContextSettings, EguiContexts, egui};
+
+use crate::Particle;
+use crate::bindings::{GamepadBindings, InputBindings};
+use crate::control::SPEEDS;
+use crate::display::{DisplayMode, DisplaySettings};
+use crate::events::SimEvent;
+use crate::levels::PaintRules;
+use crate::player::{
+    Brush, BrushShape, InputSource, Player, PlayerCursor, PlayerInputSet, SelectedParticle,
+};
+use crate::quality::Quality;
+use crate::sim::SimulationControl;
+
+// --- CONSTANTS ---
+// The handheld layout: a 16:10 window of 1280x800, filled by a 320x200 world at four pixels a
+// cell, with the world refreshed 30 times a second to spare integrated GPUs.
+const HANDHELD_RESOLUTION: (u32, u32) = (1280, 800);
+const HANDHELD_WORLD: (u32, u32) = (320, 200);
+const HANDHELD_SCALE: f32 = 4.0;
+const HANDHELD_UPLOAD_RATE: f32 = 30.0;
+// How much bigger panels, buttons and labels are drawn, so thumbs can hit them on a touch screen.
+const HANDHELD_EGUI_SCALE: f32 = 1.5;
+const HANDHELD_UI_SCALE: f32 = 1.25;
+// How long after launch a gamepad, and no key pressed, counts as a handheld.
+const DETECT_SECS: f32 = 3.0;
+// How far the stick has to lean to pick a slice of a radial menu, and how big menus are,
+// in logical pixels.
+const RADIAL_DEADZONE: f32 = 0.5;
+const RADIAL_RADIUS: f32 = 90.0;
+const RADIAL_MATERIALS: [Particle; 12] = [
+    Particle::Sand,
+    Particle::Water,
+    Particle::Bedrock,
+    Particle::Lava,
+    Particle::Oil,
+    Particle::Fire,
+    Particle::Ice,
+    Particle::Snow,
+    Particle::Salt,
+    Particle::Glass,
+    Particle::Steam,
+    Particle::Air,
+];
+
+// --- PLUGIN ---
+
+// The handheld layout, for the Steam Deck and other handhelds: a borderless 1280x800 window with
+// a 320x200 world filling it, panels and labels drawn bigger for touch, Medium quality and the
+// world refreshed 30 times a second, which suits integrated GPUs. Gamepad players get two radial
+// menus: holding the left bumper opens a ring of materials and holding Menu one of actions
+// (pause, brush shape, faster, slower); the left stick, the one not moving the cursor, picks a
+// slice and letting go takes it. Both buttons can be rebound (see bindings.rs).
+// On a launch that looks gamepad-only (the Steam Deck says so, or a gamepad is connected and no
+// key gets pressed) before the layout has been chosen either way, the game offers it; the display
+// window (F11) switches it afterwards.
+pub struct HandheldPlugin;
+
+impl Plugin for HandheldPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HandheldOffer>()
+            .init_resource::<OpenRadials>()
+            .add_systems(
+                Update,
+                (
+                    (detect_handheld, apply_handheld_layout).chain(),
+                    use_radial_menus.in_set(PlayerInputSet).run_if(handheld_layout),
+                ),
+            )
+            .add_systems(
+                EguiContextPass,
+                (draw_handheld_offer, draw_radial_menus.run_if(handheld_layout)),
+            );
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RadialMenu {
+    Materials,
+    Actions,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum RadialAction {
+    Pause,
+    BrushShape,
+    Faster,
+    Slower,
+}
+
+impl RadialAction {
+    const ALL: [RadialAction; 4] =
+        [RadialAction::Pause, RadialAction::BrushShape, RadialAction::Faster, RadialAction::Slower];
+
+    fn label(self) -> &'static str {
+        match self {
+            RadialAction::Pause => "Pause",
+            RadialAction::BrushShape => "Brush shape",
+            RadialAction::Faster => "Faster",
+            RadialAction::Slower => "Slower",
+        }
+    }
+}
+
+impl RadialMenu {
+    // The button held to keep the menu open.
+    fn button(self, bindings: &GamepadBindings) -> GamepadButton {
+        match self {
+            RadialMenu::Materials => bindings.materials_menu.0,
+            RadialMenu::Actions => bindings.actions_menu.0,
+        }
+    }
+
+    fn labels(self) -> Vec<String> {
+        match self {
+            RadialMenu::Materials => RADIAL_MATERIALS
+                .iter()
+                .map(|particle| match particle {
+                    Particle::Air => "Eraser".to_string(),
+                    particle => format!("{:?}", particle),
+                })
+                .collect(),
+            RadialMenu::Actions => RadialAction::ALL.iter().map(|a| a.label().into()).collect(),
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// Whether the handheld layout is being offered, and what was answered.
+#[derive(Resource, Default)]
+struct HandheldOffer {
+    open: bool,
+    // Whether the launch has been looked at, and whether a key was pressed meanwhile.
+    checked: bool,
+    typed: bool,
+    since_launch: f32,
+    answer: Option<bool>,
+}
+
+// The radial menu each gamepad player holds open, by player entity, with the slice the stick
+// points at.
+#[derive(Resource, Default)]
+struct OpenRadials(HashMap<Entity, (RadialMenu, Option<usize>)>);
+
+// --- SYSTEMS ---
+
+fn handheld_layout(settings: Res<DisplaySettings>) -> bool {
+    settings.handheld == Some(true)
+}
+
+// Offers the layout a few seconds into a launch that looks gamepad-only, if it hasn't been chosen
+// either way, and takes the answer from the offer's buttons or a gamepad's A or B.
+fn detect_handheld(
+    time: Res<Time<Real>>,
+    mut keyboard: EventReader<KeyboardInput>,
+    q_gamepads: Query<&Gamepad>,
+    mut offer: ResMut<HandheldOffer>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if settings.handheld.is_some() {
+        return;
+    }
+    if !offer.checked {
+        offer.typed |= keyboard.read().count() > 0;
+        offer.since_launch += time.delta_secs();
+        if offer.since_launch < DETECT_SECS {
+            return;
+        }
+        offer.checked = true;
+        let steam_deck = std::env::var_os("SteamDeck").is_some_and(|value| value == "1");
+        offer.open = steam_deck || (!offer.typed && !q_gamepads.is_empty());
+    }
+    if !offer.open {
+        return;
+    }
+    for gamepad in &q_gamepads {
+        if gamepad.just_pressed(GamepadButton::South) {
+            offer.answer = Some(true);
+        } else if gamepad.just_pressed(GamepadButton::East) {
+            offer.answer = Some(false);
+        }
+    }
+    if let Some(answer) = offer.answer.take() {
+        offer.open = false;
+        settings.handheld = Some(answer);
+        info!("{} layout picked", if answer { "Handheld" } else { "Desktop" });
+    }
+}
+
+// Switches to the handheld layout's settings when it is turned on, and back to the desktop
+// window's when it is turned off, and keeps the UI at the layout's size.
+fn apply_handheld_layout(
+    mut settings: ResMut<DisplaySettings>,
+    mut quality: ResMut<Quality>,
+    mut ui_scale: ResMut<UiScale>,
+    mut q_egui: Query<&mut EguiContextSettings, With<PrimaryWindow>>,
+    mut applied: Local<Option<Option<bool>>>,
+) {
+    let handheld = settings.handheld == Some(true);
+    if applied.is_some_and(|applied| applied != settings.handheld) {
+        let desktop = DisplaySettings::default();
+        if handheld {
+            settings.mode = DisplayMode::Borderless;
+            settings.resolution = Some(HANDHELD_RESOLUTION);
+            settings.world = HANDHELD_WORLD;
+            settings.scale = HANDHELD_SCALE;
+            settings.upload_rate = Some(HANDHELD_UPLOAD_RATE);
+            *quality = Quality::Medium;
+        } else if applied.is_some_and(|applied| applied == Some(true)) {
+            settings.mode = desktop.mode;
+            settings.resolution = desktop.resolution;
+            settings.world = desktop.world;
+            settings.scale = desktop.scale;
+            settings.upload_rate = desktop.upload_rate;
+        }
+    }
+    *applied = Some(settings.handheld);
+
+    let (egui_scale, scale) = match handheld {
+        true => (HANDHELD_EGUI_SCALE, HANDHELD_UI_SCALE),
+        false => (1.0, 1.0),
+    };
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+    for mut egui_settings in &mut q_egui {
+        if egui_settings.scale_factor != egui_scale {
+            egui_settings.scale_factor = egui_scale;
+        }
+    }
+}
+
+fn draw_handheld_offer(mut contexts: EguiContexts, mut offer: ResMut<HandheldOffer>) {
+    if !offer.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    egui::Window::new("Handheld layout")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("This looks like a handheld. Use the layout made for it?");
+            ui.label("A full-screen 1280x800 window, bigger buttons, radial menus on the gamepad");
+            ui.label("and lighter graphics. The display window (F11) switches it later.");
+            ui.horizontal(|ui| {
+                if ui.button("Use the handheld layout (A)").clicked() {
+                    offer.answer = Some(true);
+                }
+                if ui.button("Keep the desktop layout (B)").clicked() {
+                    offer.answer = Some(false);
+                }
+            });
+        });
+}
+
+// Opens a player's radial menu while its button is held, follows the stick that isn't moving the
+// cursor around it, and carries out the slice it points at when the button is let go.
+fn use_radial_menus(
+    rules: Res<PaintRules>,
+    bindings: Res<InputBindings>,
+    q_gamepads: Query<&Gamepad>,
+    mut q_players: Query<(Entity, &Player, &InputSource, &mut SelectedParticle, &mut Brush)>,
+    mut control: ResMut<SimulationControl>,
+    mut radials: ResMut<OpenRadials>,
+    mut sim_events: EventWriter<SimEvent>,
+) {
+    for (entity, player, source, mut selected, mut brush) in &mut q_players {
+        let InputSource::Gamepad(gamepad) = *source else { continue };
+        let Ok(gamepad) = q_gamepads.get(gamepad) else {
+            radials.0.remove(&entity);
+            continue;
+        };
+        let Some((menu, pointed)) = radials.0.get_mut(&entity) else {
+            for menu in [RadialMenu::Materials, RadialMenu::Actions] {
+                if gamepad.just_pressed(menu.button(&bindings.gamepad)) {
+                    radials.0.insert(entity, (menu, None));
+                }
+            }
+            continue;
+        };
+
+        let slices = menu.labels().len();
+        let stick = bindings.gamepad.cursor.other().read(gamepad);
+        if stick.length() > RADIAL_DEADZONE {
+            // Slices go clockwise from the top.
+            let angle = stick.x.atan2(stick.y).rem_euclid(TAU);
+            *pointed = Some((angle / TAU * slices as f32).round() as usize % slices);
+        }
+        if gamepad.pressed(menu.button(&bindings.gamepad)) {
+            continue;
+        }
+        let (menu, pointed) = (*menu, *pointed);
+        radials.0.remove(&entity);
+        let Some(slice) = pointed else { continue };
+        match menu {
+            RadialMenu::Materials => {
+                let particle = RADIAL_MATERIALS[slice];
+                if !rules.is_allowed(particle) || selected.0 == particle {
+                    continue;
+                }
+                selected.0 = particle;
+                info!("Player {} switched to {:?}", player.index + 1, particle);
+                sim_events.write(SimEvent::ParticleSelected {
+                    player: player.index,
+                    particle,
+                });
+            }
+            RadialMenu::Actions => run_action(RadialAction::ALL[slice], &mut control, &mut brush),
+        }
+    }
+}
+
+fn draw_radial_menus(
+    mut contexts: EguiContexts,
+    radials: Res<OpenRadials>,
+    rules: Res<PaintRules>,
+    q_players: Query<&PlayerCursor>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let layer = egui::LayerId::new(egui::Order::Foreground, egui::Id::new("radial_menus"));
+    let painter = ctx.layer_painter(layer);
+    for (&entity, &(menu, pointed)) in &radials.0 {
+        let Some(position) = q_players.get(entity).ok().and_then(|cursor| cursor.position) else {
+            continue;
+        };
+        // Cursors are in logical pixels; the UI is drawn bigger than that.
+        let center = egui::pos2(position.x, position.y) / HANDHELD_EGUI_SCALE;
+        let radius = RADIAL_RADIUS / HANDHELD_EGUI_SCALE;
+        painter.circle_filled(center, radius * 1.45, egui::Color32::from_black_alpha(170));
+        let labels = menu.labels();
+        for (slice, label) in labels.iter().enumerate() {
+            let angle = slice as f32 / labels.len() as f32 * TAU;
+            let at = center + egui::vec2(angle.sin(), -angle.cos()) * radius;
+            let material = (menu == RadialMenu::Materials).then(|| RADIAL_MATERIALS[slice]);
+            let allowed = material.is_none_or(|particle| rules.is_allowed(particle));
+            if pointed == Some(slice) {
+                painter.circle_filled(at, radius * 0.3, egui::Color32::from_white_alpha(60));
+            }
+            if let Some(particle) = material {
+                let [r, g, b, _] = particle.color().to_srgba().to_u8_array();
+                let swatch = egui::Color32::from_rgb(r, g, b);
+                painter.circle_filled(at - egui::vec2(0.0, 9.0), 5.0, swatch);
+            }
+            let color = if allowed { egui::Color32::WHITE } else { egui::Color32::DARK_GRAY };
+            let font = egui::FontId::proportional(12.0);
+            let label_at = at + egui::vec2(0.0, 4.0);
+            painter.text(label_at, egui::Align2::CENTER_CENTER, label, font, color);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn run_action(action: RadialAction, control: &mut SimulationControl, brush: &mut Brush) {
+    let speed = SPEEDS.iter().position(|&speed| speed >= control.speed).unwrap_or(3);
+    match action {
+        RadialAction::Pause => {
+            control.paused = !control.paused;
+            control.pending_steps = 0;
+            info!("Simulation {}", if control.paused { "paused" } else { "resumed" });
+        }
+        RadialAction::BrushShape => {
+            let shape = BrushShape::ALL.iter().position(|&shape| shape == brush.shape);
+            let next = shape.map_or(0, |shape| shape + 1) % BrushShape::ALL.len();
+            brush.shape = BrushShape::ALL[next];
+            info!("Brush shape {:?}", brush.shape);
+        }
+        RadialAction::Faster | RadialAction::Slower => {
+            let next = if action == RadialAction::Faster {
+                (speed + 1).min(SPEEDS.len() - 1)
+            } else {
+                speed.saturating_sub(1)
+            };
+            control.speed = SPEEDS[next];
+            info!("Simulation speed x{}", control.speed);
+        }
+    }
+}
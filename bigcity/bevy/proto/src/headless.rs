@@ -0,0 +1,340 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use bevy::asset::LoadedFolder;
+use bevy::prelude::*;
+use thiserror::Error;
+
+use crate::Particle;
+use crate::behavior::MaterialBehaviors;
+use crate::mods::ModsPlugin;
+use crate::quality::Quality;
+use crate::reaction_rules::{ReactionRules, ReactionRulesPlugin, ReactionTable, RuleFolders};
+use crate::replay::checksum;
+use crate::sim::{SimParams, SimulationGrid};
+use crate::snapshot::WorldSnapshot;
+use crate::world_file::{WorldFileError, WorldSerializer};
+
+// --- CONSTANTS ---
+const DEFAULT_TICKS: u64 = 1000;
+const WORLD_FILE_EXTENSION: &str = "png";
+
+// --- RUNNER ---
+
+// `--headless <world> [--ticks N] [--seed N] [--out <file>]` steps a world without a window or a
+// GPU, for CI: the world (a snapshot `.ron`, or a world file's `.png` with its `.ron` sidecar
+// beside it) runs N ticks at full quality under the bundled and modded reaction rules, then the
+// time a tick took, the world's checksum and how many cells of each material it ended with are
+// printed, and the final world is written to `--out`, as a world file if that ends in `.png` and
+// as a snapshot otherwise. The app is `MinimalPlugins` and the asset server, only there to load
+// the rules; the ticks themselves are the same `sim::step` the game runs.
+pub fn headless_arg() -> Option<HeadlessRun> {
+    let mut args = std::env::args().skip(1).peekable();
+    let (mut world, mut ticks, mut seed, mut out) = (None, DEFAULT_TICKS, None, None);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" => match args.next_if(|s| !s.starts_with("--")) {
+                Some(path) => world = Some(PathBuf::from(path)),
+                None => eprintln!("--headless takes the world to run"),
+            },
+            "--ticks" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => ticks = n,
+                None => eprintln!("--ticks takes a number of ticks"),
+            },
+            "--seed" => seed = args.next().and_then(|n| n.parse().ok()),
+            "--out" => out = args.next().map(PathBuf::from),
+            _ => {}
+        }
+    }
+    Some(HeadlessRun {
+        world: world?,
+        ticks,
+        seed,
+        out,
+    })
+}
+
+// Runs `run` and returns the process exit code: 0 once the world has run and been written out,
+// 1 if it couldn't be loaded or written.
+pub fn run(run: HeadlessRun) -> i32 {
+    let grid = match load_world(&run.world) {
+        Ok(grid) => grid,
+        Err(err) => {
+            eprintln!("Can't load {:?}: {}", run.world, err);
+            return 1;
+        }
+    };
+    let mut app = App::new();
+    // Mods are an asset source, and sources have to exist before the asset server does.
+    app.add_plugins(ModsPlugin)
+        .add_plugins((MinimalPlugins, AssetPlugin::default(), ReactionRulesPlugin))
+        .init_resource::<ReactionTable>()
+        .insert_resource(HeadlessWorld { run, grid })
+        .add_systems(Update, run_when_loaded);
+    match app.run() {
+        AppExit::Success => 0,
+        AppExit::Error(code) => code.get() as i32,
+    }
+}
+
+// --- TYPES ---
+
+pub struct HeadlessRun {
+    world: PathBuf,
+    ticks: u64,
+    seed: Option<u64>,
+    out: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum HeadlessError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not write: {0}")]
+    Write(#[from] ron::Error),
+    #[error("{0}")]
+    WorldFile(#[from] WorldFileError),
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct HeadlessWorld {
+    run: HeadlessRun,
+    grid: SimulationGrid,
+}
+
+// --- SYSTEMS ---
+
+// Waits for the rules to load, then runs the world out, reports on it and ends the app.
+fn run_when_loaded(
+    asset_server: Res<AssetServer>,
+    folders: Option<Res<RuleFolders>>,
+    loaded: Res<Assets<LoadedFolder>>,
+    assets: Res<Assets<ReactionRules>>,
+    mut world: ResMut<HeadlessWorld>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(folders) = folders.filter(|folders| folders.settled(&asset_server)) else { return };
+    let reactions = folders.table(&loaded, &assets);
+    let HeadlessWorld { run, grid } = &mut *world;
+    if let Some(seed) = run.seed {
+        grid.set_seed(seed);
+    }
+    let started = Instant::now();
+    settle(grid, run.ticks, &reactions);
+    let elapsed = started.elapsed().as_secs_f32() * 1000.0;
+
+    println!(
+        "Ran {:?} for {} ticks under {} reaction rules ({:.4} ms per tick)",
+        run.world,
+        run.ticks,
+        reactions.rule_count(),
+        elapsed / run.ticks.max(1) as f32
+    );
+    println!("checksum {}", checksum(grid));
+    let mut counts = [0u32; Particle::ALL.len()];
+    for &particle in grid.cells() {
+        counts[particle as usize] += 1;
+    }
+    for particle in Particle::ALL {
+        if counts[particle as usize] > 0 {
+            println!("{:?} {}", particle, counts[particle as usize]);
+        }
+    }
+    let written = run.out.as_deref().map_or(Ok(()), |out| save_world(grid, out));
+    exit.write(match written {
+        Ok(()) => AppExit::Success,
+        Err(err) => {
+            eprintln!("Can't write {:?}: {}", run.out, err);
+            AppExit::from_code(1)
+        }
+    });
+}
+
+// --- HELPERS ---
+
+// Steps `grid` for `ticks` ticks at full quality with the default parameters.
+fn settle(grid: &mut SimulationGrid, ticks: u64, reactions: &ReactionTable) {
+    let (params, schedule) = (SimParams::default(), Quality::Ultra.schedule());
+    let behaviors = MaterialBehaviors::default();
+    for tick in 0..ticks {
+        crate::sim::step(grid, tick, &params, &schedule, reactions, &behaviors);
+    }
+}
+
+fn is_world_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(WORLD_FILE_EXTENSION))
+}
+
+pub(crate) fn load_world(path: &Path) -> Result<SimulationGrid, HeadlessError> {
+    let snapshot = if is_world_file(path) {
+        WorldSerializer::load(path, &path.with_extension("ron"))?.snapshot
+    } else {
+        ron::from_str::<WorldSnapshot>(&std::fs::read_to_string(path)?)?
+    };
+    let mut grid = SimulationGrid::new(snapshot.width, snapshot.height);
+    snapshot.apply_to(&mut grid);
+    Ok(grid)
+}
+
+pub(crate) fn save_world(grid: &SimulationGrid, path: &Path) -> Result<(), HeadlessError> {
+    if is_world_file(path) {
+        WorldSerializer::save(grid, path, &path.with_extension("ron"))?;
+    } else {
+        let snapshot = WorldSnapshot::from_grid(grid);
+        std::fs::write(path, ron::ser::to_string_pretty(&snapshot, default())?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explosions::{Explosion, blast};
+    use crate::test_utils::{Scratch, WorldBuilder, bundled_rules, count};
+    use crate::zones::{MaterialOverride, ParamOverrides};
+
+    // Regression tests on how materials behave: each builds a small world, runs it headless under
+    // the bundled rules, and checks where things ended up.
+    fn boxed(width: u32, height: u32) -> WorldBuilder {
+        WorldBuilder::new(width, height).boxed()
+    }
+
+    // The highest row holding `particle`, from the bottom.
+    fn top(grid: &SimulationGrid, particle: Particle) -> Option<i32> {
+        (0..grid.height() as i32)
+            .rev()
+            .find(|&y| (0..grid.width() as i32).any(|x| grid.get(x, y) == Some(particle)))
+    }
+
+    #[test]
+    fn sand_falls_and_piles_up() {
+        let mut grid = boxed(48, 40).fill(Particle::Sand, (20, 30), (28, 38)).build();
+        settle(&mut grid, 300, &bundled_rules());
+        assert_eq!(count(&grid, Particle::Sand), 64);
+        // It lies on the floor now, heaped in the middle rather than spread flat.
+        assert!(top(&grid, Particle::Sand).unwrap() < 20);
+        assert_eq!(grid.get(24, 1), Some(Particle::Sand));
+        assert!((1..47).any(|x| grid.get(x, 1) != Some(Particle::Sand)));
+    }
+
+    #[test]
+    fn water_levels_out() {
+        let mut grid = boxed(48, 40).fill(Particle::Water, (2, 1), (10, 31)).build();
+        settle(&mut grid, 1500, &bundled_rules());
+        assert_eq!(count(&grid, Particle::Water), 240);
+        // A column 30 deep spreads to cover the floor, about five deep.
+        assert!(top(&grid, Particle::Water).unwrap() < 10);
+        assert!((1..47).all(|x| grid.get(x, 1) == Some(Particle::Water)));
+    }
+
+    #[test]
+    fn water_finds_its_level_through_a_u_bend() {
+        // A wall down the middle, open at the bottom, with only the left side filled.
+        let mut grid = boxed(48, 40)
+            .fill(Particle::Bedrock, (23, 6), (25, 40))
+            .fill(Particle::Water, (1, 1), (23, 30))
+            .build();
+        settle(&mut grid, 1500, &bundled_rules());
+        let top = |xs: std::ops::Range<i32>| {
+            (1..40).rev().find(|&y| xs.clone().any(|x| grid.get(x, y) == Some(Particle::Water)))
+        };
+        // 638 cells across both sides and the channel under the wall come to about 15 deep.
+        let (left, right) = (top(1..23).unwrap(), top(25..47).unwrap());
+        assert!(right >= 12, "the far side only rose to {}", right);
+        assert!((left - right).abs() <= 3, "levels at {} and {}", left, right);
+    }
+
+    #[test]
+    fn cohesive_water_beads_up() {
+        let drop = boxed(48, 40).fill(Particle::Water, (20, 1), (22, 3));
+        let mut loose = drop.clone().build();
+        settle(&mut loose, 300, &bundled_rules());
+        assert_eq!(top(&loose, Particle::Water), Some(1));
+
+        // The same drop holds together with the most cohesion.
+        let mut beaded = drop.build();
+        beaded.set_material_overrides(vec![MaterialOverride {
+            material: Particle::Water,
+            overrides: ParamOverrides {
+                cohesion: Some(1.0),
+                ..default()
+            },
+        }]);
+        settle(&mut beaded, 300, &bundled_rules());
+        assert_eq!(count(&beaded, Particle::Water), 4);
+        assert_eq!(top(&beaded, Particle::Water), Some(2));
+    }
+
+    #[test]
+    fn blasts_fling_water() {
+        let mut grid = boxed(48, 40).fill(Particle::Water, (1, 1), (47, 10)).build();
+        let explosion = Explosion {
+            center: Vec2::new(24.0, 8.0),
+            radius: 2.0,
+        };
+        blast(&mut grid, &explosion, false);
+        assert_eq!(count(&grid, Particle::Water), 46 * 9);
+        settle(&mut grid, 2, &bundled_rules());
+        assert!(top(&grid, Particle::Water).unwrap() > 12);
+    }
+
+    #[test]
+    fn lit_tnt_blows_a_crater() {
+        let mut grid = boxed(48, 40)
+            .fill(Particle::Sand, (1, 1), (47, 12))
+            .fill(Particle::Tnt, (22, 12), (26, 16))
+            .cell(21, 12, Particle::Fire)
+            .build();
+        settle(&mut grid, 2, &bundled_rules());
+        assert_eq!(count(&grid, Particle::Tnt), 0);
+        // The whole charge went off at once, blowing a crater into the sand around it.
+        assert_ne!(grid.get(24, 10), Some(Particle::Sand));
+        assert!(count(&grid, Particle::Sand) < 46 * 11 - 40);
+        assert!(count(&grid, Particle::Fire) + count(&grid, Particle::Smoke) > 10);
+    }
+
+    #[test]
+    fn batteries_spark_and_split_water() {
+        let mut grid = boxed(48, 40)
+            .fill(Particle::Water, (1, 1), (47, 8))
+            .fill(Particle::Battery, (4, 8), (6, 12))
+            .fill(Particle::Mirror, (6, 10), (30, 11))
+            .fill(Particle::Rope, (30, 10), (31, 14))
+            .build();
+        settle(&mut grid, 1, &bundled_rules());
+        // The wire carries most of the charge all the way along, the water only near the battery.
+        assert!(grid.charge(29, 10).unwrap() > 150);
+        assert_eq!(grid.charge(45, 1), Some(0));
+        settle(&mut grid, 300, &bundled_rules());
+        assert!(count(&grid, Particle::Rope) < 4);
+        assert!(count(&grid, Particle::Hydrogen) + count(&grid, Particle::Fire) > 0);
+    }
+
+    #[test]
+    fn water_quenches_lava() {
+        let mut grid = boxed(48, 40)
+            .fill(Particle::Lava, (1, 1), (47, 5))
+            .fill(Particle::Water, (16, 10), (32, 16))
+            .build();
+        settle(&mut grid, 200, &bundled_rules());
+        assert!(count(&grid, Particle::Glass) > 0);
+        assert!(count(&grid, Particle::Water) < 96);
+    }
+
+    #[test]
+    fn worlds_round_trip_through_dumps() {
+        let scratch = Scratch::new("headless-dumps");
+        let mut grid = boxed(32, 24).fill(Particle::Sand, (4, 10), (12, 20)).build();
+        settle(&mut grid, 50, &ReactionTable::default());
+        for file in ["snapshot.ron", "world.png"] {
+            save_world(&grid, &scratch.path(file)).unwrap();
+            let loaded = load_world(&scratch.path(file)).unwrap();
+            assert_eq!(loaded.cells(), grid.cells());
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::packed::PackedCells;
+use crate::persist::user_data_dir;
+use crate::sim::{LowMemory, SimulationGrid, SimulationSet, SimulationStats};
+
+// --- CONSTANTS ---
+const EXPORT_FOLDER: &str = "heatmaps";
+
+// --- PLUGIN ---
+pub struct HeatmapPlugin;
+
+impl Plugin for HeatmapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActivityMap>().add_systems(
+            Update,
+            (accumulate_activity, export_heatmap).chain().after(SimulationSet),
+        );
+    }
+}
+
+// --- RESOURCES ---
+
+// How many times each cell has changed since the map was last reset (grid order, y = 0 at the
+// bottom). Painting counts as well as the simulation itself.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ActivityMap {
+    width: u32,
+    height: u32,
+    counts: Vec<u32>,
+    previous: Option<PackedCells>,
+}
+
+impl ActivityMap {
+    pub fn reset(&mut self) {
+        self.counts.fill(0);
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    // Renders the counts as an image, normalized to the busiest cell. A square-root curve keeps
+    // quieter areas visible next to hot spots.
+    pub fn to_image(&self) -> image::RgbImage {
+        let max = self.max().max(1) as f32;
+        image::RgbImage::from_fn(self.width, self.height, |x, y| {
+            let row = self.height - 1 - y;
+            let count = self.counts[(row * self.width + x) as usize];
+            image::Rgb(heat_color((count as f32 / max).sqrt()))
+        })
+    }
+}
+
+// --- SYSTEMS ---
+
+fn accumulate_activity(
+    grid: Res<SimulationGrid>,
+    low_memory: Option<Res<LowMemory>>,
+    mut activity: ResMut<ActivityMap>,
+) {
+    if !grid.is_changed() {
+        return;
+    }
+    let activity = activity.bypass_change_detection();
+    let Some(previous) = activity
+        .previous
+        .as_mut()
+        .filter(|previous| previous.cell_count() == grid.cells().len())
+    else {
+        *activity = ActivityMap {
+            width: grid.width(),
+            height: grid.height(),
+            counts: vec![0; grid.cells().len()],
+            previous: Some(PackedCells::new(grid.cells(), low_memory.is_some())),
+        };
+        return;
+    };
+
+    for (i, (count, &cell)) in activity.counts.iter_mut().zip(grid.cells()).enumerate() {
+        if previous.get(i) != cell {
+            *count = count.saturating_add(1);
+            previous.set(i, cell);
+        }
+    }
+}
+
+fn export_heatmap(
+    keys: Res<ButtonInput<KeyCode>>,
+    stats: Res<SimulationStats>,
+    mut activity: ResMut<ActivityMap>,
+) {
+    if !keys.just_pressed(KeyCode::F8) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        activity.reset();
+        info!("Activity heatmap reset");
+        return;
+    }
+
+    let Some(dir) = user_data_dir().map(|dir| dir.join(EXPORT_FOLDER)) else { return };
+    let path = dir.join(format!("heatmap_{}.png", stats.tick));
+    let result = std::fs::create_dir_all(&dir)
+        .map_err(image::ImageError::IoError)
+        .and_then(|_| activity.to_image().save(&path));
+    match result {
+        Ok(()) => info!("Exported heatmap to {:?} (busiest cell: {} changes)", path, activity.max()),
+        Err(err) => warn!("Could not export heatmap to {:?}: {}", path, err),
+    }
+}
+
+// --- HELPERS ---
+
+// Black -> red -> yellow -> white as `t` goes from 0 to 1.
+fn heat_color(t: f32) -> [u8; 3] {
+    let channel = |start: f32| ((t * 3.0 - start).clamp(0.0, 1.0) * 255.0) as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
@@ -0,0 +1,503 @@
+// --- IMPORTS ---
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::Particle;
+use crate::bodies::BodyOperation;
+use crate::chaos::{Disaster, DisasterKind, Earthquake};
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::explosions::Explosion;
+use crate::persist::user_data_dir;
+use crate::sim::{SimulationGrid, SimulationSet, SimulationStats};
+use crate::snapshot::WorldSnapshot;
+use crate::stamps::Stamp;
+use crate::tags::TagOperation;
+use crate::world_commands::{AppliedCommand, WorldCommand, WorldCommandSet};
+use crate::worldgen::WorldGeneration;
+
+// --- CONSTANTS ---
+const HISTORY_FOLDER: &str = "history";
+
+// --- PLUGIN ---
+
+// `--history` (optionally with a path) logs the session's whole history for analysis: one JSON
+// object per line, each with the tick it belongs to, appended to `history` in the user data
+// directory as it happens, so a crash loses nothing but the frame it crashed in. It opens with the
+// world's seed and a snapshot of the world once it is in, and goes on with every `WorldCommand`
+// applied, in order and with the tick it went in before, every `SimEvent`, every explosion,
+// earthquake and chaos mode disaster, and a new snapshot whenever a world is generated. Starting
+// from a snapshot and putting the commands and explosions in before their ticks with the same seed
+// plays the session out again, up to the first disaster: earthquakes, meteors and acid rain roll
+// chaos mode's own dice, and meteors fly by the frame, so the log only marks where they struck,
+// and keeps the world as each earthquake left it to go on from. Whole worlds loaded from saves
+// aren't logged, as they aren't commands. Pointing `--history` at an existing file appends to it.
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1).peekable();
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            if arg == "--history" {
+                let given = args.next_if(|path| !path.starts_with("--"));
+                path = given.map(PathBuf::from).or_else(history_path);
+                if path.is_none() {
+                    warn!("No user data directory to keep the history in");
+                }
+            }
+        }
+        let Some(path) = path else { return };
+        app.insert_resource(HistoryLog::new(path))
+            .add_systems(
+                FixedUpdate,
+                open_history
+                    .after(WorldCommandSet)
+                    .before(SimulationSet)
+                    .run_if(not(resource_exists::<WorldGeneration>)),
+            )
+            .add_systems(Last, log_history);
+    }
+}
+
+// --- TYPES ---
+
+// One line of a history file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryLine {
+    pub tick: u64,
+    #[serde(flatten)]
+    pub entry: HistoryEntry,
+}
+
+// What happened at a line's tick. Commands are kept as `WorldCommand` has them, with cells as
+// `[x, y]` pairs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum HistoryEntry {
+    // The session's first line: the seed the world's dice are rolled with.
+    Session { seed: u64 },
+    // The whole world, as it was when the log opened or a world was generated.
+    World(WorldSnapshot),
+    Paint {
+        player: usize,
+        particle: Particle,
+        data: u8,
+        cells: Vec<(i32, i32)>,
+        max_cells: u32,
+    },
+    EndStroke { player: usize },
+    Place {
+        particle: Particle,
+        data: u8,
+        cells: Vec<(i32, i32)>,
+    },
+    Paste { stamp: Stamp, at: (i32, i32) },
+    Set {
+        particle: Particle,
+        cells: Vec<(i32, i32)>,
+    },
+    FillRect {
+        min: (i32, i32),
+        max: (i32, i32),
+        particle: Particle,
+    },
+    FillCircle {
+        center: (i32, i32),
+        radius: i32,
+        particle: Particle,
+    },
+    Backdrop {
+        particle: Particle,
+        cells: Vec<(i32, i32)>,
+    },
+    Heat {
+        min: (i32, i32),
+        max: (i32, i32),
+        degrees: f32,
+    },
+    Tags { operation: TagOperation },
+    Bodies { operation: BodyOperation },
+    Clear,
+    Undo,
+    Redo,
+    // Sim events, as `SimEvent` has them.
+    Painted {
+        player: usize,
+        particle: Particle,
+        cells: u32,
+    },
+    ParticleSelected { player: usize, particle: Particle },
+    BrushResized { player: usize, size: i32 },
+    WorldCreated,
+    // A blast, as `Explosion` has it, from a grenade, a meteor, a timeline or anything else. Blasts
+    // play out the same from the same world, so replaying one is sending it again.
+    Explosion { center: (f32, f32), radius: f32 },
+    // An earthquake, from chaos mode or the X tool. It is followed by the world it left.
+    Earthquake { center: (f32, f32), radius: f32 },
+    // Chaos mode set off a disaster.
+    Disaster { disaster: DisasterKind },
+}
+
+impl HistoryEntry {
+    fn from_command(command: &WorldCommand) -> Self {
+        let cells = |cells: &[CellPos]| cells.iter().map(|cell| (cell.x, cell.y)).collect();
+        match command.clone() {
+            WorldCommand::Paint {
+                player,
+                particle,
+                data,
+                cells: painted,
+                max_cells,
+            } => HistoryEntry::Paint {
+                player,
+                particle,
+                data,
+                cells: cells(&painted),
+                max_cells,
+            },
+            WorldCommand::EndStroke { player } => HistoryEntry::EndStroke { player },
+            WorldCommand::Place {
+                particle,
+                data,
+                cells: placed,
+            } => HistoryEntry::Place {
+                particle,
+                data,
+                cells: cells(&placed),
+            },
+            WorldCommand::Paste { stamp, at } => HistoryEntry::Paste {
+                stamp,
+                at: (at.x, at.y),
+            },
+            WorldCommand::Set { particle, cells: set } => HistoryEntry::Set {
+                particle,
+                cells: cells(&set),
+            },
+            WorldCommand::FillRect { min, max, particle } => HistoryEntry::FillRect {
+                min: (min.x, min.y),
+                max: (max.x, max.y),
+                particle,
+            },
+            WorldCommand::FillCircle {
+                center,
+                radius,
+                particle,
+            } => HistoryEntry::FillCircle {
+                center: (center.x, center.y),
+                radius,
+                particle,
+            },
+            WorldCommand::Backdrop { particle, cells: walls } => HistoryEntry::Backdrop {
+                particle,
+                cells: cells(&walls),
+            },
+            WorldCommand::Heat { min, max, degrees } => HistoryEntry::Heat {
+                min: (min.x, min.y),
+                max: (max.x, max.y),
+                degrees,
+            },
+            WorldCommand::Explode { center, radius } => HistoryEntry::Explosion {
+                center: center.into(),
+                radius,
+            },
+            WorldCommand::Tags(operation) => HistoryEntry::Tags { operation },
+            WorldCommand::Bodies(operation) => HistoryEntry::Bodies { operation },
+            WorldCommand::Clear => HistoryEntry::Clear,
+            WorldCommand::Undo => HistoryEntry::Undo,
+            WorldCommand::Redo => HistoryEntry::Redo,
+        }
+    }
+
+    // `None` for what the world did by itself, which replaying the session does again.
+    fn from_event(event: &SimEvent) -> Option<Self> {
+        Some(match *event {
+            SimEvent::Painted {
+                player,
+                particle,
+                cells,
+            } => HistoryEntry::Painted {
+                player,
+                particle,
+                cells,
+            },
+            SimEvent::ParticleSelected { player, particle } => {
+                HistoryEntry::ParticleSelected { player, particle }
+            }
+            SimEvent::BrushResized { player, size } => HistoryEntry::BrushResized { player, size },
+            SimEvent::WorldCreated => HistoryEntry::WorldCreated,
+            SimEvent::Reacted { .. } => return None,
+        })
+    }
+
+    // The command this line applied, to put it in again; `None` for what wasn't a command.
+    pub fn command(&self) -> Option<WorldCommand> {
+        let cells = |cells: &[(i32, i32)]| cells.iter().map(|&(x, y)| CellPos::new(x, y)).collect();
+        let cell = |(x, y): (i32, i32)| CellPos::new(x, y);
+        Some(match self.clone() {
+            HistoryEntry::Paint {
+                player,
+                particle,
+                data,
+                cells: painted,
+                max_cells,
+            } => WorldCommand::Paint {
+                player,
+                particle,
+                data,
+                cells: cells(&painted),
+                max_cells,
+            },
+            HistoryEntry::EndStroke { player } => WorldCommand::EndStroke { player },
+            HistoryEntry::Place {
+                particle,
+                data,
+                cells: placed,
+            } => WorldCommand::Place {
+                particle,
+                data,
+                cells: cells(&placed),
+            },
+            HistoryEntry::Paste { stamp, at } => WorldCommand::Paste { stamp, at: cell(at) },
+            HistoryEntry::Set { particle, cells: set } => WorldCommand::Set {
+                particle,
+                cells: cells(&set),
+            },
+            HistoryEntry::FillRect { min, max, particle } => WorldCommand::FillRect {
+                min: cell(min),
+                max: cell(max),
+                particle,
+            },
+            HistoryEntry::FillCircle {
+                center,
+                radius,
+                particle,
+            } => WorldCommand::FillCircle {
+                center: cell(center),
+                radius,
+                particle,
+            },
+            HistoryEntry::Backdrop { particle, cells: walls } => WorldCommand::Backdrop {
+                particle,
+                cells: cells(&walls),
+            },
+            HistoryEntry::Heat { min, max, degrees } => WorldCommand::Heat {
+                min: cell(min),
+                max: cell(max),
+                degrees,
+            },
+            HistoryEntry::Tags { operation } => WorldCommand::Tags(operation),
+            HistoryEntry::Bodies { operation } => WorldCommand::Bodies(operation),
+            HistoryEntry::Clear => WorldCommand::Clear,
+            HistoryEntry::Undo => WorldCommand::Undo,
+            HistoryEntry::Redo => WorldCommand::Redo,
+            HistoryEntry::Session { .. }
+            | HistoryEntry::World(_)
+            | HistoryEntry::Painted { .. }
+            | HistoryEntry::ParticleSelected { .. }
+            | HistoryEntry::BrushResized { .. }
+            | HistoryEntry::WorldCreated
+            | HistoryEntry::Explosion { .. }
+            | HistoryEntry::Earthquake { .. }
+            | HistoryEntry::Disaster { .. } => return None,
+        })
+    }
+
+    // The blast this line logged, to set it off again.
+    pub fn explosion(&self) -> Option<Explosion> {
+        let HistoryEntry::Explosion { center, radius } = *self else { return None };
+        Some(Explosion {
+            center: center.into(),
+            radius,
+        })
+    }
+}
+
+// Reads back every line of the history file at `path`, in order.
+pub fn read_history(path: &Path) -> std::io::Result<Vec<HistoryLine>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| line.as_ref().is_ok_and(|line| !line.trim().is_empty()))
+        .map(|line| serde_json::from_str(&line?).map_err(std::io::Error::other))
+        .collect()
+}
+
+// --- RESOURCES ---
+
+// The history file being appended to, once it is open.
+#[derive(Resource)]
+struct HistoryLog {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+}
+
+impl HistoryLog {
+    fn new(path: PathBuf) -> Self {
+        Self { path, file: None }
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn write(&mut self, tick: u64, entry: HistoryEntry) {
+        let Some(file) = &mut self.file else { return };
+        let line = HistoryLine { tick, entry };
+        let result = serde_json::to_writer(&mut *file, &line)
+            .map_err(std::io::Error::other)
+            .and_then(|()| writeln!(file));
+        if let Err(err) = result {
+            warn!("Could not write to {:?}, stopping the history: {}", self.path, err);
+            self.file = None;
+        }
+    }
+
+    fn flush(&mut self) {
+        let Some(file) = &mut self.file else { return };
+        if let Err(err) = file.flush() {
+            warn!("Could not write to {:?}, stopping the history: {}", self.path, err);
+            self.file = None;
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+// Once the world is in and has its seed: opens the file with the seed and the world.
+fn open_history(
+    mut log: ResMut<HistoryLog>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    mut opened: Local<bool>,
+) {
+    if std::mem::replace(&mut *opened, true) {
+        return;
+    }
+    if let Err(err) = log.open() {
+        warn!("Could not open the history {:?}: {}", log.path, err);
+        return;
+    }
+    info!("Logging the session's history to {:?}", log.path);
+    log.write(stats.tick, HistoryEntry::Session { seed: grid.seed() });
+    log.write(stats.tick, HistoryEntry::World(WorldSnapshot::from_grid(&grid)));
+    log.flush();
+}
+
+// What else changed the world this frame, besides commands.
+#[derive(SystemParam)]
+struct Upheavals<'w, 's> {
+    explosions: EventReader<'w, 's, Explosion>,
+    earthquakes: EventReader<'w, 's, Earthquake>,
+    disasters: EventReader<'w, 's, Disaster>,
+}
+
+// Appends the frame's commands and events, and the world whenever one was generated or shaken.
+// Runs last, so nothing applied this frame is missed.
+fn log_history(
+    mut log: ResMut<HistoryLog>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    mut applied: EventReader<AppliedCommand>,
+    mut sim_events: EventReader<SimEvent>,
+    mut upheavals: Upheavals,
+) {
+    if log.file.is_none() {
+        applied.clear();
+        sim_events.clear();
+        upheavals.explosions.clear();
+        upheavals.earthquakes.clear();
+        upheavals.disasters.clear();
+        return;
+    }
+    for AppliedCommand { tick, command } in applied.read() {
+        log.write(*tick, HistoryEntry::from_command(command));
+    }
+    for event in sim_events.read() {
+        let Some(entry) = HistoryEntry::from_event(event) else { continue };
+        log.write(stats.tick, entry);
+        if *event == SimEvent::WorldCreated {
+            log.write(stats.tick, HistoryEntry::World(WorldSnapshot::from_grid(&grid)));
+        }
+    }
+    for &Disaster(disaster) in upheavals.disasters.read() {
+        log.write(stats.tick, HistoryEntry::Disaster { disaster });
+    }
+    for explosion in upheavals.explosions.read() {
+        let (center, radius) = (explosion.center.into(), explosion.radius);
+        log.write(stats.tick, HistoryEntry::Explosion { center, radius });
+    }
+    let mut shaken = false;
+    for earthquake in upheavals.earthquakes.read() {
+        let (center, radius) = (earthquake.center.into(), earthquake.radius);
+        log.write(stats.tick, HistoryEntry::Earthquake { center, radius });
+        shaken = true;
+    }
+    if shaken {
+        log.write(stats.tick, HistoryEntry::World(WorldSnapshot::from_grid(&grid)));
+    }
+    log.flush();
+}
+
+// --- HELPERS ---
+
+fn history_path() -> Option<PathBuf> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Some(user_data_dir()?.join(HISTORY_FOLDER).join(format!("history_{}.jsonl", stamp)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_read_back_as_the_commands_they_logged() {
+        let command = WorldCommand::FillCircle {
+            center: CellPos::new(10, 20),
+            radius: 3,
+            particle: Particle::Water,
+        };
+        let line = HistoryLine {
+            tick: 42,
+            entry: HistoryEntry::from_command(&command),
+        };
+        let json = serde_json::to_string(&line).unwrap();
+        assert!(json.starts_with(r#"{"tick":42,"kind":"FillCircle""#), "{}", json);
+        let read: HistoryLine = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, line);
+        let again = read.entry.command().unwrap();
+        assert_eq!(HistoryEntry::from_command(&again), line.entry);
+        assert!(HistoryEntry::WorldCreated.command().is_none());
+
+        let blast = HistoryEntry::Explosion {
+            center: (3.5, 7.0),
+            radius: 10.0,
+        };
+        let json = serde_json::to_string(&blast).unwrap();
+        assert_eq!(serde_json::from_str::<HistoryEntry>(&json).unwrap(), blast);
+        assert!(blast.command().is_none());
+        assert_eq!(blast.explosion().map(|e| e.center), Some(Vec2::new(3.5, 7.0)));
+    }
+
+    #[test]
+    fn tag_edits_log_their_cells_as_pairs() {
+        let cells = vec![CellPos::new(1, 2), CellPos::new(3, 4)];
+        let command = WorldCommand::Tags(TagOperation::Tag(0, cells));
+        let entry = HistoryEntry::from_command(&command);
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.contains("[[1,2],[3,4]]"), "{}", json);
+        let read: HistoryEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, entry);
+        assert!(matches!(read.command(), Some(WorldCommand::Tags(TagOperation::Tag(0, _)))));
+    }
+}
@@ -0,0 +1,214 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::player::{InputSource, PlayerInputSet, SelectedParticle};
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{MaterialClass, Particle};
+
+// --- CONSTANTS ---
+const LABEL_COLOR: Color = Color::srgb(1.0, 0.85, 0.5);
+// Throughput is measured over windows of this many seconds.
+const RATE_WINDOW: f32 = 1.0;
+// The label lists this many of the busiest materials.
+const TOP_MATERIALS: usize = 3;
+
+// --- PLUGIN ---
+
+// Hourglass mode opens the bottom edge: powders and liquids that reach the bottom row reappear at
+// the top of the same column, so the world never runs out of falling material. It keeps count of
+// what went round, which makes it a soak test as much as a screensaver.
+pub struct HourglassPlugin;
+
+impl Plugin for HourglassPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HourglassStats>()
+            .add_systems(Startup, spawn_hourglass_label)
+            .add_systems(
+                Update,
+                (
+                    toggle_hourglass.after(PlayerInputSet).before(SimulationSet),
+                    (count_recycled, update_hourglass_label).chain().after(SimulationSet),
+                ),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// Which particles hourglass mode recycles: every powder and liquid, or only one material.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hourglass {
+    All,
+    Only(Particle),
+}
+
+impl Hourglass {
+    fn recycles(self, particle: Particle) -> bool {
+        let falls = matches!(particle.class(), MaterialClass::Powder | MaterialClass::Liquid);
+        falls && (self == Hourglass::All || self == Hourglass::Only(particle))
+    }
+}
+
+// Moves every recycled particle in the bottom row to the top of its column, if that cell is free,
+// keeping its temperature, state and owner.
+pub fn recycle_bottom_row(grid: &mut SimulationGrid) {
+    let Some(hourglass) = grid.hourglass() else { return };
+    let top = grid.height() as i32 - 1;
+    for x in 0..grid.width() as i32 {
+        let Some(particle) = grid.get(x, 0).filter(|&p| hourglass.recycles(p)) else { continue };
+        if grid.get(x, top) == Some(Particle::Air) {
+            grid.swap_cells(CellPos::new(x, 0), CellPos::new(x, top));
+            grid.count_recycled(particle);
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// What went round since hourglass mode was turned on.
+#[derive(Resource, Default)]
+struct HourglassStats {
+    total: [u64; Particle::ALL.len()],
+    // Cells per second per material, over the last full window.
+    rate: [f32; Particle::ALL.len()],
+    window: [u32; Particle::ALL.len()],
+    window_time: f32,
+    running_time: f32,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct HourglassLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_hourglass_label(mut commands: Commands) {
+    commands.spawn((
+        HourglassLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(LABEL_COLOR),
+    ));
+}
+
+// G turns hourglass mode on for everything, Shift+G for the mouse player's material only; either
+// turns it off again. Turning it on opens the bedrock floor: full rows of bedrock along the bottom
+// edge become air, so there is a bottom to fall out of. Levels keep their floors.
+fn toggle_hourglass(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    q_players: Query<(&InputSource, &SelectedParticle)>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stats: ResMut<HourglassStats>,
+) {
+    if !keys.just_pressed(KeyCode::KeyG) || rules.protect_world {
+        return;
+    }
+    if grid.hourglass().is_some() {
+        grid.set_hourglass(None);
+        info!("Hourglass mode off");
+        return;
+    }
+    let hourglass = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        let selected = q_players.iter().find(|(source, _)| **source == InputSource::Mouse);
+        let Some((_, particle)) = selected else { return };
+        Hourglass::Only(particle.0)
+    } else {
+        Hourglass::All
+    };
+
+    let width = grid.width() as i32;
+    for y in 0..grid.height() as i32 {
+        if (0..width).any(|x| grid.get(x, y) != Some(Particle::Bedrock)) {
+            break;
+        }
+        for x in 0..width {
+            grid.set(x, y, Particle::Air);
+        }
+    }
+    grid.set_hourglass(Some(hourglass));
+    *stats = HourglassStats::default();
+    info!("Hourglass mode on: {:?}", hourglass);
+}
+
+fn count_recycled(
+    time: Res<Time>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stats: ResMut<HourglassStats>,
+) {
+    if grid.hourglass().is_none() {
+        return;
+    }
+    // Reading the counts isn't a change to the world.
+    let recycled = grid.bypass_change_detection().take_recycled();
+    let stats = &mut *stats;
+    for ((total, window), count) in stats.total.iter_mut().zip(&mut stats.window).zip(recycled) {
+        *total += count as u64;
+        *window += count;
+    }
+    stats.running_time += time.delta_secs();
+    stats.window_time += time.delta_secs();
+    if stats.window_time >= RATE_WINDOW {
+        for (rate, window) in stats.rate.iter_mut().zip(&mut stats.window) {
+            *rate = *window as f32 / stats.window_time;
+            *window = 0;
+        }
+        stats.window_time = 0.0;
+    }
+}
+
+fn update_hourglass_label(
+    grid: Res<SimulationGrid>,
+    stats: Res<HourglassStats>,
+    mut q_label: Query<&mut Text, With<HourglassLabel>>,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let Some(hourglass) = grid.hourglass() else {
+        if !label.0.is_empty() {
+            label.0.clear();
+        }
+        return;
+    };
+    let what = match hourglass {
+        Hourglass::All => "everything".to_string(),
+        Hourglass::Only(particle) => format!("{:?} only", particle),
+    };
+    let seconds = stats.running_time as u64;
+    let mut lines = vec![format!(
+        "Hourglass ({}): {:.0} cells/s, {} recycled in {}:{:02}",
+        what,
+        stats.rate.iter().sum::<f32>(),
+        stats.total.iter().sum::<u64>(),
+        seconds / 60,
+        seconds % 60,
+    )];
+    let mut busiest: Vec<(Particle, f32)> = Particle::ALL
+        .iter()
+        .map(|&particle| (particle, stats.rate[particle as usize]))
+        .filter(|&(_, rate)| rate > 0.0)
+        .collect();
+    busiest.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let busiest: Vec<String> = busiest
+        .iter()
+        .take(TOP_MATERIALS)
+        .map(|(particle, rate)| format!("{:?} {:.0}/s", particle, rate))
+        .collect();
+    if !busiest.is_empty() {
+        lines.push(busiest.join(", "));
+    }
+    label.0 = lines.join("\n");
+}
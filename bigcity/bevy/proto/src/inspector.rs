@@ -0,0 +1,158 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::player::{InputSource, Player, PlayerInputSet, SelectedParticle};
+use crate::region_readback::{RegionId, RegionRead, RegionReadbacks};
+use crate::sim::SimulationGrid;
+use crate::{SimulationDisplay, WorldView};
+
+// --- PLUGIN ---
+
+// The cell inspector (Shift+I): a window showing everything about the cell under the cursor, its
+// material, temperature, velocity, state byte, age, stain and shade and whether a player placed
+// it, next to the raw texel the GPU was given for it. The world lives on the CPU, so the grid is
+// what the cell really holds; the texel under the cursor is read back from the state texture on
+// its own (see region_readback.rs) while the inspector is open, a frame or so behind, which shows
+// what the shader and the CPU display actually draw from. Shift+middle-click is the eyedropper: it picks the material under the
+// cursor for the mouse player's brush.
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inspector>()
+            .add_systems(
+                Update,
+                (toggle_inspector, read_texel, pick_material.in_set(PlayerInputSet)),
+            )
+            .add_systems(EguiContextPass, draw_inspector);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct Inspector {
+    open: bool,
+    // The texel last read back and the cell it is of, and the read still on its way.
+    texel: Option<(CellPos, [u8; 4])>,
+    pending: Option<RegionId>,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_inspector(keys: Res<ButtonInput<KeyCode>>, mut inspector: ResMut<Inspector>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !(shift && keys.just_pressed(KeyCode::KeyI)) {
+        return;
+    }
+    inspector.open = !inspector.open;
+    if !inspector.open {
+        inspector.texel = None;
+    }
+}
+
+// Keeps one read of the texel under the cursor on its way while the inspector is open.
+fn read_texel(
+    mut reads: EventReader<RegionRead>,
+    mut readbacks: ResMut<RegionReadbacks>,
+    mut inspector: ResMut<Inspector>,
+    display: Res<SimulationDisplay>,
+    view: WorldView,
+) {
+    for read in reads.read() {
+        if inspector.pending != Some(read.id) {
+            continue;
+        }
+        inspector.pending = None;
+        let cell = read.min();
+        if let Some(&[r, g, b, a]) = read.texel(cell) {
+            inspector.texel = Some((cell, [r, g, b, a]));
+        }
+    }
+    if !inspector.open || inspector.pending.is_some() {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    inspector.pending = Some(readbacks.request(&display.state_image, cell, UVec2::ONE));
+}
+
+// Shift+middle-click picks the material under the cursor for the mouse player.
+fn pick_material(
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    grid: Res<SimulationGrid>,
+    mut q_players: Query<(&Player, &InputSource, &mut SelectedParticle)>,
+    mut sim_events: EventWriter<SimEvent>,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !(shift && mouse.just_pressed(MouseButton::Middle)) {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    let Some(particle) = grid.get(cell.x, cell.y) else { return };
+    for (player, source, mut selected) in &mut q_players {
+        if *source != InputSource::Mouse || selected.0 == particle {
+            continue;
+        }
+        selected.0 = particle;
+        info!("Player {} picked {:?}", player.index + 1, particle);
+        sim_events.write(SimEvent::ParticleSelected {
+            player: player.index,
+            particle,
+        });
+    }
+}
+
+fn draw_inspector(
+    mut contexts: EguiContexts,
+    mut inspector: ResMut<Inspector>,
+    view: WorldView,
+    grid: Res<SimulationGrid>,
+) {
+    if !inspector.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let cell = view.cursor_cell();
+    let state = cell.and_then(|cell| grid.cell(cell.x, cell.y));
+    let texel = inspector.texel.filter(|&(read, _)| Some(read) == cell);
+    let texel = texel.map(|(_, texel)| format!("{:?}", texel));
+
+    let mut open = true;
+    egui::Window::new("Inspector").open(&mut open).resizable(false).show(ctx, |ui| {
+        let (Some(cell), Some(state)) = (cell, state) else {
+            ui.label("Point at the world to inspect a cell.");
+            return;
+        };
+        egui::Grid::new("inspected_cell").num_columns(2).show(ui, |ui| {
+            let mut row = |name: &str, value: String| {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            };
+            row("Cell", format!("({}, {})", cell.x, cell.y));
+            row("Material", format!("{:?} ({:?})", state.particle, state.particle.class()));
+            row("Temperature", format!("{:.1} C", state.temperature));
+            let velocity = grid.velocity(cell.x, cell.y);
+            row("Velocity", velocity.map_or("-".to_string(), |v| format!("({}, {})", v.x, v.y)));
+            let pressure = grid.pressure(cell.x, cell.y);
+            row("Pressure", pressure.map_or("-".to_string(), |p| format!("{} cells of head", p)));
+            let charge = grid.charge(cell.x, cell.y);
+            row("Charge", charge.map_or("-".to_string(), |c| format!("{} / 255", c)));
+            row("State byte", format!("{} (0x{:02x})", state.data, state.data));
+            row("Age", format!("{} ticks", state.age));
+            row("Stain", state.stain.to_string());
+            row("Shade", state.shade.to_string());
+            row("Placed", if state.placed { "by a player" } else { "no" }.to_string());
+            row("GPU texel", texel.unwrap_or_else(|| "reading back...".to_string()));
+        });
+        ui.weak("Id, heat, weathering, stain");
+    });
+    if !open {
+        inspector.open = false;
+    }
+}
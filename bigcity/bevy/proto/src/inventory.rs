@@ -0,0 +1,174 @@
+// --- IMPORTS ---
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::levels::ActiveLevel;
+
+// --- CONSTANTS ---
+// What the free-play challenge mode (I) starts you with.
+const CHALLENGE_STOCK: [(Particle, u32); 27] = [
+    (Particle::Sand, 3000),
+    (Particle::Water, 3000),
+    (Particle::Bedrock, 300),
+    (Particle::Laser, 4),
+    (Particle::Mirror, 200),
+    (Particle::Glass, 300),
+    (Particle::Turbine, 40),
+    (Particle::Snow, 2000),
+    (Particle::Ice, 200),
+    (Particle::Dust, 1500),
+    (Particle::Salt, 1000),
+    (Particle::Crystal, 20),
+    (Particle::Magnet, 40),
+    (Particle::IronPowder, 1000),
+    (Particle::Uranium, 20),
+    (Particle::Radium, 10),
+    (Particle::Lead, 500),
+    (Particle::Goo, 1000),
+    (Particle::Rope, 300),
+    (Particle::Lava, 200),
+    (Particle::Steam, 500),
+    (Particle::Fire, 200),
+    (Particle::Smoke, 500),
+    (Particle::Oil, 1000),
+    (Particle::Gunpowder, 300),
+    (Particle::Tnt, 40),
+    (Particle::Battery, 20),
+];
+
+// --- PLUGIN ---
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .add_systems(Startup, spawn_inventory_panel)
+            .add_systems(Update, (toggle_challenge_mode, update_inventory_panel).chain());
+    }
+}
+
+// --- RESOURCES ---
+
+// Optional limit on how many cells of each material may be painted. Painting takes from the
+// stock, and erasing or overwriting a cell a player placed gives its particle back. While
+// disabled (the default) every material is unlimited.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct Inventory {
+    // Materials missing from the map are unlimited even while the inventory is enabled.
+    stock: Option<HashMap<Particle, u32>>,
+}
+
+impl Inventory {
+    pub fn is_enabled(&self) -> bool {
+        self.stock.is_some()
+    }
+
+    pub fn enable(&mut self, stock: impl IntoIterator<Item = (Particle, u32)>) {
+        self.stock = Some(stock.into_iter().collect());
+    }
+
+    pub fn disable(&mut self) {
+        self.stock = None;
+    }
+
+    // How many more cells of `particle` may be painted; None means unlimited.
+    pub fn remaining(&self, particle: Particle) -> Option<u32> {
+        self.stock.as_ref()?.get(&particle).copied()
+    }
+
+    // Takes one cell of `particle` from the stock; returns false if there is none left.
+    pub fn take(&mut self, particle: Particle) -> bool {
+        match self.stock.as_mut().and_then(|stock| stock.get_mut(&particle)) {
+            Some(0) => false,
+            Some(left) => {
+                *left -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn refund(&mut self, particle: Particle) {
+        if let Some(left) = self.stock.as_mut().and_then(|stock| stock.get_mut(&particle)) {
+            *left = left.saturating_add(1);
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct InventoryPanel;
+
+// --- SYSTEMS ---
+
+fn spawn_inventory_panel(mut commands: Commands) {
+    commands.spawn((
+        InventoryPanel,
+        Name::new("inventory_panel"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            left: Val::Percent(50.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+// A level's budget is the level's: the key does nothing while one runs.
+fn toggle_challenge_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    level: Res<ActiveLevel>,
+    mut inventory: ResMut<Inventory>,
+) {
+    // Shift+I is the inspector.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift || !keys.just_pressed(KeyCode::KeyI) {
+        return;
+    }
+    if level.is_active() {
+        info!("Challenge mode can't be changed during a level");
+        return;
+    }
+    if inventory.is_enabled() {
+        inventory.disable();
+        info!("Challenge mode off");
+    } else {
+        inventory.enable(CHALLENGE_STOCK);
+        info!("Challenge mode on");
+    }
+}
+
+fn update_inventory_panel(
+    inventory: Res<Inventory>,
+    mut q_panel: Query<(&mut Node, &mut Text), With<InventoryPanel>>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+    let Ok((mut node, mut text)) = q_panel.single_mut() else { return };
+    let Some(stock) = &inventory.stock else {
+        node.display = Display::None;
+        return;
+    };
+
+    node.display = Display::Flex;
+    let mut lines: Vec<String> = stock
+        .iter()
+        .map(|(particle, left)| format!("{:?}: {}", particle, left))
+        .collect();
+    lines.sort();
+    text.0 = format!("Inventory\n{}", lines.join("\n"));
+}
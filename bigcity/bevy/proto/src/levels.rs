@@ -0,0 +1,554 @@
+// --- IMPORTS ---
+use std::collections::{HashMap, HashSet};
+
+use bevy::asset::LoadedFolder;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::coords::CellPos;
+use crate::daily::{StartDailyChallenge, daily_id, today};
+use crate::inventory::Inventory;
+use crate::persist::{load_user_ron, save_user_ron};
+use crate::player::SelectedParticle;
+use crate::mods::mod_folder;
+use crate::pan_zoom::ctrl_held;
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{SimulationGrid, SimulationSet, SimulationStats};
+use crate::snapshot::WorldSnapshot;
+use crate::timeline::Timeline;
+use crate::loops::LoopBand;
+use crate::zones::ParamZone;
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+pub const LEVEL_FOLDER: &str = "levels";
+const PROGRESS_FILE: &str = "levels.ron";
+// Where Shift+L writes the current world, ready to paste into a level's `snapshot`.
+const SNAPSHOT_EXPORT_FILE: &str = "level_snapshot.ron";
+const REGION_COLOR: Color = Color::srgb(0.2, 1.0, 0.6);
+
+// --- PLUGIN ---
+pub struct LevelsPlugin;
+
+impl Plugin for LevelsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Level>()
+            .register_asset_loader(RonAssetLoader::<Level>::new(&["level.ron"]))
+            .add_event::<LevelCompleted>()
+            .init_resource::<ActiveLevel>()
+            .init_resource::<PaintRules>()
+            .insert_resource(load_user_ron::<LevelProgress>(PROGRESS_FILE).unwrap_or_default())
+            .add_systems(Startup, (load_levels, spawn_level_hud))
+            .add_systems(
+                Update,
+                (
+                    toggle_level_select,
+                    export_level_snapshot,
+                    handle_level_buttons,
+                    start_pending_level.before(SimulationSet),
+                    evaluate_win_conditions.after(SimulationSet),
+                    update_level_hud,
+                    draw_level_regions,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// --- ASSETS ---
+
+// A puzzle loaded from `levels/*.level.ron`: a starting world, the materials the player may use
+// and how much of each, and the conditions that win it.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Level {
+    // Stable key for completion tracking.
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // The starting world. Shapes are painted on top of it, so simple levels can skip it.
+    #[serde(default)]
+    pub snapshot: Option<WorldSnapshot>,
+    #[serde(default)]
+    pub shapes: Vec<LevelShape>,
+    // Zones that change the rules in parts of the level, on top of any in the snapshot.
+    #[serde(default)]
+    pub zones: Vec<ParamZone>,
+    #[serde(default)]
+    pub loops: Vec<LoopBand>,
+    // What happens by itself as the level runs, by the tick from its start.
+    #[serde(default)]
+    pub timeline: Option<Timeline>,
+    // Materials the player may paint, each with an optional inventory budget (None = unlimited).
+    pub materials: Vec<(Particle, Option<u32>)>,
+    // All conditions must hold at the same time to win.
+    pub win: Vec<WinCondition>,
+}
+
+impl Level {
+    // Puts the level's starting world in `grid`.
+    pub fn set_up(&self, grid: &mut SimulationGrid) {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.apply_to(grid),
+            None => grid.clear(),
+        }
+        for shape in &self.shapes {
+            for y in shape.min.1..=shape.max.1 {
+                for x in shape.min.0..=shape.max.0 {
+                    grid.set(x, y, shape.particle);
+                }
+            }
+        }
+        for zone in &self.zones {
+            grid.add_zone(zone.clone());
+        }
+        for band in &self.loops {
+            grid.add_loop(band.clone());
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LevelShape {
+    pub particle: Particle,
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum WinCondition {
+    // Between `at_least` and `at_most` cells of `particle` inside the region (inclusive, in
+    // cells). Either bound may be left out.
+    InRegion {
+        particle: Particle,
+        min: (i32, i32),
+        max: (i32, i32),
+        #[serde(default)]
+        at_least: u32,
+        #[serde(default)]
+        at_most: Option<u32>,
+    },
+    // At least `at_least` cells of `particle` exist for `ticks` consecutive ticks.
+    KeepAlive {
+        particle: Particle,
+        at_least: u32,
+        ticks: u32,
+    },
+}
+
+// --- EVENTS ---
+
+#[derive(Event, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct LevelCompleted {
+    pub id: String,
+    pub ticks: u64,
+}
+
+// --- RESOURCES ---
+
+// The bundled levels and those mods add.
+#[derive(Resource)]
+struct LevelLibrary([Handle<LoadedFolder>; 2]);
+
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ActiveLevel {
+    level: Option<Handle<Level>>,
+    // Set when a level was picked but its world hasn't been loaded into the grid yet.
+    pending: bool,
+    start_tick: u64,
+    // The tick the conditions were last checked at; ticks don't advance exactly once per frame.
+    checked_tick: u64,
+    // Consecutive ticks each `KeepAlive` condition has held, by condition index.
+    streaks: HashMap<usize, u32>,
+    won: bool,
+}
+
+impl ActiveLevel {
+    pub fn start(&mut self, level: Handle<Level>) {
+        *self = Self {
+            level: Some(level),
+            pending: true,
+            ..default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        *self = Self::default();
+    }
+
+    // Whether a level (or the daily challenge) is being played or set up.
+    pub fn is_active(&self) -> bool {
+        self.level.is_some()
+    }
+
+    // The level being played, once its world is set up, with the tick it started at.
+    pub fn running(&self) -> Option<(&Handle<Level>, u64)> {
+        let level = self.level.as_ref().filter(|_| !self.pending)?;
+        Some((level, self.start_tick))
+    }
+}
+
+// What painting is allowed to do right now. Free play allows everything; levels restrict the
+// materials and stop brushes from overwriting the level itself. How much may be painted is up to
+// the `Inventory`.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct PaintRules {
+    allowed: Option<HashSet<Particle>>,
+    // Brushes may only change air and cells a player placed.
+    pub protect_world: bool,
+}
+
+impl PaintRules {
+    // Erasing is always allowed; `protect_world` keeps it to the players' own placements.
+    pub fn is_allowed(&self, particle: Particle) -> bool {
+        particle == Particle::Air
+            || self.allowed.as_ref().is_none_or(|allowed| allowed.contains(&particle))
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+pub struct LevelProgress {
+    // Level id -> fewest ticks it was won in.
+    best_ticks: HashMap<String, u64>,
+}
+
+impl LevelProgress {
+    pub fn is_completed(&self, id: &str) -> bool {
+        self.best_ticks.contains_key(id)
+    }
+}
+
+// --- SYSTEM PARAM ---
+
+// The scenarios a world can start from, as bases for saving it as a patch: the bundled and
+// modded levels, by their ids.
+#[derive(SystemParam)]
+pub struct ScenarioBases<'w> {
+    active: Res<'w, ActiveLevel>,
+    levels: Res<'w, Assets<Level>>,
+}
+
+impl ScenarioBases<'_> {
+    // The name of the scenario the world being played started from, if any.
+    pub fn current(&self) -> Option<String> {
+        let (level, _) = self.active.running()?;
+        let level = self.levels.get(level)?;
+        Some(format!("level:{}", level.id))
+    }
+
+    // The scenario called `name` as it starts in a world of `size`, if it is available.
+    pub fn base(&self, name: &str, size: UVec2) -> Option<SimulationGrid> {
+        let id = name.strip_prefix("level:")?;
+        let (_, level) = self.levels.iter().find(|(_, level)| level.id == id)?;
+        let mut grid = SimulationGrid::new(size.x, size.y);
+        level.set_up(&mut grid);
+        Some(grid)
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct LevelSelectScreen;
+
+#[derive(Component)]
+enum LevelButton {
+    Level(Handle<Level>),
+    Daily,
+    Sandbox,
+}
+
+#[derive(Component)]
+struct LevelHud;
+
+// --- SYSTEMS ---
+
+fn load_levels(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LevelLibrary([
+        asset_server.load_folder(LEVEL_FOLDER),
+        asset_server.load_folder(mod_folder(LEVEL_FOLDER)),
+    ]));
+}
+
+fn spawn_level_hud(mut commands: Commands) {
+    commands.spawn((
+        LevelHud,
+        Name::new("level_hud"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            right: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn toggle_level_select(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    library: Res<LevelLibrary>,
+    folders: Res<Assets<LoadedFolder>>,
+    levels: Res<Assets<Level>>,
+    progress: Res<LevelProgress>,
+    q_screen: Query<Entity, With<LevelSelectScreen>>,
+) {
+    // Ctrl+L is the log.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift || ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyL) {
+        return;
+    }
+    if let Ok(screen) = q_screen.single() {
+        commands.entity(screen).despawn();
+        return;
+    }
+
+    let mut entries: Vec<(Handle<Level>, &Level)> = (library.0.iter())
+        .filter_map(|folder| folders.get(folder))
+        .flat_map(|folder| folder.handles.iter())
+        .filter_map(|h| h.clone().try_typed::<Level>().ok())
+        .filter_map(|h| levels.get(&h).map(|level| (h, level)))
+        .collect();
+    entries.sort_by(|a, b| a.1.id.cmp(&b.1.id));
+
+    commands
+        .spawn((
+            LevelSelectScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new("Select a level"),
+                TextFont {
+                    font_size: 32.0,
+                    ..default()
+                },
+            ));
+            for (handle, level) in entries {
+                let mark = if progress.is_completed(&level.id) { "[x]" } else { "[ ]" };
+                spawn_level_button(
+                    screen,
+                    LevelButton::Level(handle),
+                    format!("{} {}", mark, level.name),
+                );
+            }
+            let mark = if progress.is_completed(&daily_id(today())) { "[x]" } else { "[ ]" };
+            spawn_level_button(screen, LevelButton::Daily, format!("{} Daily challenge", mark));
+            spawn_level_button(screen, LevelButton::Sandbox, "Free play".into());
+        });
+}
+
+fn export_level_snapshot(keys: Res<ButtonInput<KeyCode>>, grid: Res<SimulationGrid>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift && !ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyL) {
+        save_user_ron(SNAPSHOT_EXPORT_FILE, &WorldSnapshot::from_grid(&grid));
+        info!("Wrote the current world to '{}'", SNAPSHOT_EXPORT_FILE);
+    }
+}
+
+fn spawn_level_button(parent: &mut ChildSpawnerCommands, button: LevelButton, label: String) {
+    parent
+        .spawn((
+            button,
+            Button,
+            Node {
+                width: Val::Px(320.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.2, 0.2, 0.25)),
+        ))
+        .with_child((
+            Text::new(label),
+            TextFont {
+                font_size: 20.0,
+                ..default()
+            },
+        ));
+}
+
+fn handle_level_buttons(
+    mut commands: Commands,
+    mut active: ResMut<ActiveLevel>,
+    mut rules: ResMut<PaintRules>,
+    mut inventory: ResMut<Inventory>,
+    mut daily_starts: EventWriter<StartDailyChallenge>,
+    mut q_buttons: Query<(&Interaction, &LevelButton, &mut BackgroundColor), Changed<Interaction>>,
+    q_screen: Query<Entity, With<LevelSelectScreen>>,
+) {
+    for (interaction, button, mut color) in &mut q_buttons {
+        match interaction {
+            Interaction::Pressed => {
+                match button {
+                    LevelButton::Level(handle) => active.start(handle.clone()),
+                    LevelButton::Daily => {
+                        daily_starts.write(StartDailyChallenge);
+                    }
+                    LevelButton::Sandbox => {
+                        active.stop();
+                        *rules = PaintRules::default();
+                        inventory.disable();
+                    }
+                }
+                for screen in &q_screen {
+                    commands.entity(screen).despawn();
+                }
+            }
+            Interaction::Hovered => color.0 = Color::srgb(0.3, 0.3, 0.4),
+            Interaction::None => color.0 = Color::srgb(0.2, 0.2, 0.25),
+        }
+    }
+}
+
+fn start_pending_level(
+    levels: Res<Assets<Level>>,
+    stats: Res<SimulationStats>,
+    mut active: ResMut<ActiveLevel>,
+    mut rules: ResMut<PaintRules>,
+    mut inventory: ResMut<Inventory>,
+    mut grid: ResMut<SimulationGrid>,
+    mut q_selected: Query<&mut SelectedParticle>,
+) {
+    if !active.pending {
+        return;
+    }
+    let Some(level) = active.level.as_ref().and_then(|h| levels.get(h)) else { return };
+
+    level.set_up(&mut grid);
+    *rules = PaintRules {
+        allowed: Some(level.materials.iter().map(|(p, _)| *p).collect()),
+        protect_world: true,
+    };
+    inventory.enable(level.materials.iter().filter_map(|(p, budget)| budget.map(|b| (*p, b))));
+    // Hand every player a material they can actually use.
+    if let Some((first, _)) = level.materials.first() {
+        for mut selected in &mut q_selected {
+            if !rules.is_allowed(selected.0) {
+                selected.0 = *first;
+            }
+        }
+    }
+
+    info!("Starting level '{}'", level.name);
+    active.pending = false;
+    active.start_tick = stats.tick;
+    active.checked_tick = stats.tick;
+}
+
+fn evaluate_win_conditions(
+    levels: Res<Assets<Level>>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    mut active: ResMut<ActiveLevel>,
+    mut progress: ResMut<LevelProgress>,
+    mut completions: EventWriter<LevelCompleted>,
+) {
+    if active.pending || active.won {
+        return;
+    }
+    let Some(level) = active.level.as_ref().and_then(|h| levels.get(h)) else { return };
+
+    let elapsed = (stats.tick - active.checked_tick) as u32;
+    active.checked_tick = stats.tick;
+    let mut all_met = true;
+    for (index, condition) in level.win.iter().enumerate() {
+        let met = match condition {
+            WinCondition::InRegion { particle, min, max, at_least, at_most } => {
+                let (min, max) = (CellPos::new(min.0, min.1), CellPos::new(max.0, max.1));
+                let count = grid.count_in_rect(*particle, min, max);
+                count >= *at_least && at_most.is_none_or(|at_most| count <= at_most)
+            }
+            WinCondition::KeepAlive { particle, at_least, ticks } => {
+                let streak = active.streaks.entry(index).or_default();
+                *streak = if stats.count(*particle) >= *at_least { *streak + elapsed } else { 0 };
+                *streak >= *ticks
+            }
+        };
+        all_met &= met;
+    }
+    if !all_met {
+        return;
+    }
+
+    let ticks = stats.tick - active.start_tick;
+    let id = level.id.clone();
+    active.won = true;
+
+    let best = progress.best_ticks.entry(id.clone()).or_insert(ticks);
+    *best = (*best).min(ticks);
+    save_user_ron(PROGRESS_FILE, &*progress);
+    completions.write(LevelCompleted { id, ticks });
+}
+
+fn update_level_hud(
+    levels: Res<Assets<Level>>,
+    active: Res<ActiveLevel>,
+    progress: Res<LevelProgress>,
+    mut completions: EventReader<LevelCompleted>,
+    mut q_hud: Query<(&mut Node, &mut Text), With<LevelHud>>,
+    mut result: Local<String>,
+) {
+    for completion in completions.read() {
+        let best = progress.best_ticks.get(&completion.id).copied().unwrap_or(completion.ticks);
+        info!("Level '{}' complete in {} ticks", completion.id, completion.ticks);
+        *result = format!("Level complete in {} ticks (best {})", completion.ticks, best);
+    }
+
+    let Ok((mut node, mut text)) = q_hud.single_mut() else { return };
+    let Some(level) = active.level.as_ref().and_then(|h| levels.get(h)) else {
+        node.display = Display::None;
+        return;
+    };
+
+    node.display = Display::Flex;
+    let mut lines = vec![level.name.clone()];
+    if !level.description.is_empty() {
+        lines.push(level.description.clone());
+    }
+    let materials: Vec<String> = level.materials.iter().map(|(p, _)| format!("{:?}", p)).collect();
+    lines.push(format!("Materials: {}", materials.join(", ")));
+    if active.won {
+        lines.push(result.clone());
+        lines.push("Press L for the next one.".into());
+    }
+    text.0 = lines.join("\n");
+}
+
+fn draw_level_regions(
+    layout: Res<WorldLayout>,
+    levels: Res<Assets<Level>>,
+    active: Res<ActiveLevel>,
+    mut gizmos: Gizmos,
+) {
+    let Some(level) = active.level.as_ref().and_then(|h| levels.get(h)) else { return };
+
+    for condition in &level.win {
+        let WinCondition::InRegion { min, max, .. } = condition else { continue };
+        let rect = layout.cells_to_world(CellPos::new(min.0, min.1), CellPos::new(max.0, max.1));
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), REGION_COLOR);
+    }
+}
@@ -0,0 +1,1450 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy::app::PluginGroupBuilder;
+use bevy::asset::RenderAssetUsages;
+use bevy::ecs::system::SystemParam;
+use bevy::image::ImageSampler;
+use bevy::log::LogPlugin;
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+use bevy::render::camera::ScalingMode;
+use bevy::render::mesh::Mesh2d;
+use bevy::sprite::{Material2d, Material2dPlugin, MeshMaterial2d};
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiGlobalSettings, EguiPlugin};
+use serde::{Deserialize, Serialize};
+
+mod access;
+mod autosave;
+mod autotile;
+mod backdrop;
+mod bindings;
+mod behavior;
+mod bodies;
+mod benchmark;
+mod bookmarks;
+mod chaos;
+mod chunks;
+mod collision;
+mod config;
+mod control;
+mod coords;
+mod cpu_display;
+mod crash;
+mod daily;
+mod degradation;
+mod demo;
+mod display;
+mod drops;
+mod emitters;
+mod encoder;
+mod events;
+mod experiment;
+mod explosions;
+mod focus;
+mod follow;
+mod frame;
+mod frame_order;
+mod gpu_sim;
+mod handheld;
+mod headless;
+mod heatmap;
+mod history;
+mod hourglass;
+mod inspector;
+mod inventory;
+mod levels;
+mod loading;
+mod locks;
+mod log_overlay;
+mod loops;
+mod material_import;
+mod meteors;
+#[cfg(feature = "mobile")]
+mod mobile;
+mod mods;
+mod museum;
+mod objectives;
+mod optics;
+mod packed;
+mod paint_tools;
+#[cfg(feature = "physics")]
+mod physics;
+mod palette;
+mod pan_zoom;
+mod persist;
+mod picture_import;
+mod postcard;
+mod player;
+mod power;
+mod presets;
+mod quality;
+mod quicksave;
+mod probes;
+mod profile;
+mod profiling;
+mod reaction_rules;
+mod reaction_view;
+mod recording;
+mod reference;
+mod resolution;
+mod projectiles;
+mod region_readback;
+mod regions;
+mod replay;
+mod ron_asset;
+mod saves;
+#[cfg(feature = "reflect")]
+mod scenes;
+#[cfg(feature = "scripting")]
+mod scripting;
+mod selection;
+mod server;
+mod shading;
+mod sim;
+mod snapshot;
+mod spectator;
+mod stamps;
+mod stats_log;
+mod svg;
+mod tags;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod thermal;
+mod timelapse;
+mod timeline;
+mod tuning;
+mod tutorial;
+mod undo;
+mod user_stats;
+mod web;
+mod wind_tunnel;
+#[cfg(feature = "workshop")]
+mod workshop;
+mod world_commands;
+mod world_file;
+mod world_patch;
+mod worldgen;
+mod zones;
+
+use access::SimulationAccess;
+use autosave::AutosavePlugin;
+use autotile::AutotilePlugin;
+use backdrop::BackdropPlugin;
+use bindings::BindingsPlugin;
+use benchmark::BenchmarkPlugin;
+use bookmarks::BookmarksPlugin;
+use chaos::ChaosPlugin;
+use chunks::ChunkViewPlugin;
+use collision::CollisionPlugin;
+use control::ControlPlugin;
+use coords::WorldPos;
+use cpu_display::CpuDisplayPlugin;
+use crash::CrashHandlerPlugin;
+use daily::DailyPlugin;
+use degradation::DegradationPlugin;
+use demo::DemoPlugin;
+use display::{DisplayPlugin, UploadPacing};
+use drops::DropsPlugin;
+use emitters::EmittersPlugin;
+use events::SimEventsPlugin;
+use explosions::ExplosionsPlugin;
+use focus::FocusPlugin;
+use follow::FollowPlugin;
+use frame::FramePlugin;
+use frame_order::FrameOrderPlugin;
+use gpu_sim::{GpuSimPlugin, GpuStepping};
+use handheld::HandheldPlugin;
+use heatmap::HeatmapPlugin;
+use history::HistoryPlugin;
+use hourglass::HourglassPlugin;
+use inspector::InspectorPlugin;
+use inventory::InventoryPlugin;
+use levels::LevelsPlugin;
+use loading::LoadingPlugin;
+use locks::PaintLocksPlugin;
+use log_overlay::LogOverlayPlugin;
+use loops::LoopsPlugin;
+use meteors::MeteorsPlugin;
+use museum::MuseumPlugin;
+use objectives::ObjectivesPlugin;
+use optics::{MirrorTilt, OpticsPlugin, painted_state};
+use paint_tools::{PaintToolSet, PaintToolsPlugin};
+use palette::PalettePlugin;
+use picture_import::PictureImportPlugin;
+use player::{PlayerCursor, PlayerPlugin};
+use pan_zoom::PanZoomPlugin;
+use postcard::PostcardPlugin;
+use power::PowerPlugin;
+use presets::PresetsPlugin;
+use quality::QualityPlugin;
+use quicksave::QuickSavePlugin;
+use probes::ProbesPlugin;
+use profile::DensityProfilePlugin;
+use profiling::ProfilingPlugin;
+use reaction_rules::ReactionRulesPlugin;
+use reaction_view::ReactionViewPlugin;
+use recording::RecordingPlugin;
+use reference::MaterialReferencePlugin;
+use resolution::{ResolutionPlugin, WorldRequest};
+use projectiles::ProjectilesPlugin;
+use region_readback::RegionReadbackPlugin;
+use regions::RegionsPlugin;
+use replay::{ReplayPlayback, ReplayPlugin};
+use saves::SavesPlugin;
+use selection::SelectionPlugin;
+use sim::{AMBIENT_TEMPERATURE, SimulationPlugin, roll};
+use shading::{CellShadingPlugin, NEUTRAL_SHADE};
+use spectator::SpectatorPlugin;
+use stamps::StampsPlugin;
+use stats_log::StatsLogPlugin;
+use svg::SvgExportPlugin;
+use tags::TagsPlugin;
+use thermal::{ThermalPlugin, ThermalView};
+use timelapse::TimelapsePlugin;
+use timeline::TimelinePlugin;
+use tuning::TuningPlugin;
+use tutorial::TutorialPlugin;
+use undo::UndoPlugin;
+use user_stats::UserStatsPlugin;
+use wind_tunnel::WindTunnelPlugin;
+use world_commands::WorldCommandsPlugin;
+use worldgen::WorldgenPlugin;
+use zones::ZonesPlugin;
+
+// What other Bevy apps embedding the sandbox build on.
+pub use bodies::{Body, BodyOperation, CellBodies};
+pub use chaos::{Disaster, DisasterKind, Earthquake};
+pub use collision::{CollisionArea, CollisionShape, Polyline};
+pub use config::{ConfigError, SimulationConfig};
+pub use coords::CellPos;
+pub use cpu_display::DisplayBackend;
+pub use display::Upscaler;
+pub use frame_order::{DisplaySet, SwapSet};
+pub use history::{HistoryEntry, HistoryLine, read_history};
+pub use events::SimEvent;
+pub use explosions::Explosion;
+pub use mods::ModsPlugin;
+#[cfg(feature = "physics")]
+pub use physics::{PhysicsPlugin, WorldBody};
+pub use player::{
+    Brush, BrushConform, BrushShape, PaintLayer, Player, PlayerInputSet, SelectedParticle,
+};
+pub use reaction_rules::{ReactionRules, ReactionTable};
+pub use sim::{
+    CellState, Reaction, SimParams, SimulationBackend, SimulationGrid, SimulationSet,
+    SimulationStats, SimulationTickRate,
+};
+pub use snapshot::WorldSnapshot;
+pub use svg::outlines_svg;
+pub use tags::TagOperation;
+pub use world_commands::{
+    AppliedCommand, SimulationCommands, WorldCommand, WorldCommandSet, WorldCommands,
+};
+pub use world_file::WorldSerializer;
+
+// --- CONSTANTS ---
+// The world's size in cells unless the display settings or `--world` pick another.
+const SIMULATION_WIDTH: u32 = 256;
+const SIMULATION_HEIGHT: u32 = 256;
+const BRUSH_SIZE: i32 = 5;
+// How many cells a brush may paint per second: the default brush repainted 60 times a second, as
+// painting once per frame did at 60 Hz.
+const BRUSH_FLOW: f32 = 7260.0;
+const BRUSH_OUTLINE_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+// How many window pixels one simulation cell covers in a window sized to the world.
+const DISPLAY_SCALE: f32 = 4.0;
+// How many world-space units one cell spans, whatever the world's or the window's size, so
+// sprites sized in cells stay that many cells big.
+const WORLD_UNITS_PER_CELL: f32 = 4.0;
+
+// --- PARTICLE DEFINITION ---
+// The discriminant is the id written to the state texture, which picks the particle's colors from
+// the palette texture.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub enum Particle {
+    #[default]
+    Air,
+    Bedrock,
+    Sand,
+    Water,
+    // Fires a beam along the current laser aim.
+    Laser,
+    // Reflects beams about its tilt, which is kept in the cell's state byte.
+    Mirror,
+    // A solid that beams pass through. Sand turns into it when heated enough.
+    Glass,
+    // Lets liquids flow straight through it and puts out a signal proportional to that flow,
+    // which is kept in the cell's state byte.
+    Turbine,
+    // A light powder that piles up steeply, floats on water and compacts into ice under its own
+    // weight.
+    Snow,
+    // A solid that beams pass through; snow turns into it when buried deep enough.
+    Ice,
+    // A fine powder that wind and blasts lift into the air, where it drifts for a while before
+    // settling. While airborne, its velocity is kept in the cell's state byte.
+    Dust,
+    // Aerated water churned up where falling water lands. It rises to the surface and turns back
+    // into water once the ticks left in its state byte run out. Never painted.
+    Foam,
+    // A powder that sinks in water and dissolves into it, up to saturation. Water keeps how much
+    // salt it holds in its state byte.
+    Salt,
+    // A solid that grows from seeds into saturated salt water, using up the salt.
+    Crystal,
+    // A solid that pulls iron powder towards it.
+    Magnet,
+    // A heavy powder that magnets attract; it clings to them where the pull beats gravity.
+    IronPowder,
+    // A radioactive solid that slowly decays into radium.
+    Uranium,
+    // A hot, radioactive powder that decays into lead fairly quickly.
+    Radium,
+    // A heavy powder that stops radiation.
+    Lead,
+    // A thick liquid whose cells cling to each other, so it holds together in blobs that sag,
+    // drip and merge again.
+    Goo,
+    // A powder whose cells link up into chains that hang from whatever solid they are tied to,
+    // until heat or the eraser cuts them. Rope keeps its links in its state byte.
+    Rope,
+    // A molten, slow-flowing liquid that is painted glowing hot and holds its heat, so it melts
+    // sand into glass and boils water around it until it cools into obsidian glass itself.
+    Lava,
+    // A gas that water boils into. It rises through air and water and condenses back into water
+    // once it has cooled.
+    Steam,
+    // Flames that flammable materials burn into. Fire flickers upwards through air, heating what
+    // it passes and setting fire to what burns, and dies down into smoke once the ticks left in
+    // its state byte run out.
+    Fire,
+    // A gas that billows up and out through air and thins away into it once the ticks left in its
+    // state byte run out.
+    Smoke,
+    // A light liquid that floats on water and burns readily.
+    Oil,
+    // A powder that goes off once it is lit or heated enough: every connected heap of it goes off
+    // at once, in a blast that grows with the heap.
+    Gunpowder,
+    // A solid explosive, several times as strong as gunpowder, that goes off the same way.
+    Tnt,
+    // A solid that charges every conductor connected to it, less the further the current has to
+    // go and the worse what it goes through conducts.
+    Battery,
+    // A very light gas that charged water splits into at the electrodes. It rises quickly and
+    // burns at a touch.
+    Hydrogen,
+}
+
+impl Particle {
+    const ALL: [Particle; 30] = [
+        Particle::Air,
+        Particle::Bedrock,
+        Particle::Sand,
+        Particle::Water,
+        Particle::Laser,
+        Particle::Mirror,
+        Particle::Glass,
+        Particle::Turbine,
+        Particle::Snow,
+        Particle::Ice,
+        Particle::Dust,
+        Particle::Foam,
+        Particle::Salt,
+        Particle::Crystal,
+        Particle::Magnet,
+        Particle::IronPowder,
+        Particle::Uranium,
+        Particle::Radium,
+        Particle::Lead,
+        Particle::Goo,
+        Particle::Rope,
+        Particle::Lava,
+        Particle::Steam,
+        Particle::Fire,
+        Particle::Smoke,
+        Particle::Oil,
+        Particle::Gunpowder,
+        Particle::Tnt,
+        Particle::Battery,
+        Particle::Hydrogen,
+    ];
+
+    fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    // How the particle looks on screen.
+    fn color(&self) -> Color {
+        match self {
+            Particle::Air => Color::linear_rgb(0.0, 0.0, 0.0),
+            Particle::Bedrock => Color::linear_rgb(0.3, 0.3, 0.3),
+            Particle::Sand => Color::linear_rgb(0.8, 0.7, 0.1),
+            Particle::Water => Color::linear_rgb(0.1, 0.2, 0.9),
+            Particle::Laser => Color::linear_rgb(0.9, 0.05, 0.05),
+            Particle::Mirror => Color::linear_rgb(0.75, 0.8, 0.85),
+            Particle::Glass => Color::linear_rgb(0.45, 0.7, 0.75),
+            Particle::Turbine => Color::linear_rgb(0.7, 0.45, 0.2),
+            Particle::Snow => Color::linear_rgb(0.9, 0.92, 0.95),
+            Particle::Ice => Color::linear_rgb(0.55, 0.75, 0.95),
+            Particle::Dust => Color::linear_rgb(0.55, 0.5, 0.4),
+            Particle::Foam => Color::linear_rgb(0.75, 0.85, 0.95),
+            Particle::Salt => Color::linear_rgb(0.85, 0.75, 0.75),
+            Particle::Crystal => Color::linear_rgb(0.6, 0.3, 0.85),
+            Particle::Magnet => Color::linear_rgb(0.5, 0.15, 0.2),
+            Particle::IronPowder => Color::linear_rgb(0.35, 0.35, 0.4),
+            Particle::Uranium => Color::linear_rgb(0.3, 0.55, 0.2),
+            Particle::Radium => Color::linear_rgb(0.55, 0.95, 0.45),
+            Particle::Lead => Color::linear_rgb(0.4, 0.4, 0.5),
+            Particle::Goo => Color::linear_rgb(0.35, 0.8, 0.3),
+            Particle::Rope => Color::linear_rgb(0.65, 0.5, 0.3),
+            Particle::Lava => Color::linear_rgb(1.0, 0.35, 0.05),
+            Particle::Steam => Color::linear_rgb(0.8, 0.8, 0.85),
+            Particle::Fire => Color::linear_rgb(1.0, 0.6, 0.1),
+            Particle::Smoke => Color::linear_rgb(0.3, 0.3, 0.32),
+            Particle::Oil => Color::linear_rgb(0.35, 0.22, 0.05),
+            Particle::Gunpowder => Color::linear_rgb(0.22, 0.21, 0.2),
+            Particle::Tnt => Color::linear_rgb(0.8, 0.12, 0.1),
+            Particle::Battery => Color::linear_rgb(0.15, 0.45, 0.3),
+            Particle::Hydrogen => Color::linear_rgb(0.7, 0.75, 0.9),
+        }
+    }
+
+    // What the particle weathers into as it ages, and after how many ticks it gets there: sand
+    // bleaches in the sun, mirrors tarnish, and turbines and iron rust.
+    fn weathering(&self) -> Option<(Color, u16)> {
+        match self {
+            Particle::Sand => Some((Color::linear_rgb(0.85, 0.8, 0.55), 3600)),
+            Particle::Mirror => Some((Color::linear_rgb(0.45, 0.45, 0.4), 7200)),
+            Particle::Turbine => Some((Color::linear_rgb(0.45, 0.2, 0.1), 7200)),
+            Particle::IronPowder => Some((Color::linear_rgb(0.5, 0.25, 0.1), 7200)),
+            Particle::Air
+            | Particle::Bedrock
+            | Particle::Water
+            | Particle::Laser
+            | Particle::Glass
+            | Particle::Snow
+            | Particle::Ice
+            | Particle::Dust
+            | Particle::Foam
+            | Particle::Salt
+            | Particle::Crystal
+            | Particle::Magnet
+            | Particle::Uranium
+            | Particle::Radium
+            | Particle::Lead
+            | Particle::Goo
+            | Particle::Rope
+            | Particle::Lava
+            | Particle::Steam
+            | Particle::Fire
+            | Particle::Smoke
+            | Particle::Oil
+            | Particle::Gunpowder
+            | Particle::Tnt
+            | Particle::Battery
+            | Particle::Hydrogen => None,
+        }
+    }
+
+    // How the particle decays, if it is radioactive. Keep the chain ending in a stable material.
+    fn decay(&self) -> Option<Decay> {
+        match self {
+            Particle::Uranium => Some(Decay {
+                half_life: 36_000.0,
+                product: Particle::Radium,
+                heat: 0.5,
+            }),
+            Particle::Radium => Some(Decay {
+                half_life: 1800.0,
+                product: Particle::Lead,
+                heat: 3.0,
+            }),
+            _ => None,
+        }
+    }
+
+    // How the particle takes in and gives off heat. Keep painted temperatures clear of the ones
+    // the particle turns into something else at.
+    fn thermal(&self) -> Thermal {
+        let conductivity = match self {
+            Particle::Mirror
+            | Particle::Turbine
+            | Particle::Magnet
+            | Particle::IronPowder
+            | Particle::Lead => 2.0,
+            Particle::Snow | Particle::Steam | Particle::Smoke => 0.5,
+            Particle::Lava => 0.01,
+            _ => 1.0,
+        };
+        let painted_at = match self {
+            Particle::Lava => 1200.0,
+            Particle::Steam => 110.0,
+            Particle::Fire => 300.0,
+            _ => AMBIENT_TEMPERATURE,
+        };
+        Thermal {
+            conductivity,
+            painted_at,
+        }
+    }
+
+    // Per-tick chance that the particle catches fire while it touches fire or is hot enough to
+    // ignite; zero for the ones that don't burn.
+    fn flammability(&self) -> f32 {
+        match self {
+            Particle::Hydrogen => 0.9,
+            Particle::Oil => 0.3,
+            Particle::Rope => 0.2,
+            Particle::Dust => 0.1,
+            _ => 0.0,
+        }
+    }
+
+    // How much blast a cell of the particle adds when it goes off, in cells of crater radius
+    // squared; zero for the ones that don't explode.
+    fn blast_yield(&self) -> f32 {
+        match self {
+            Particle::Gunpowder => 0.5,
+            Particle::Tnt => 3.0,
+            _ => 0.0,
+        }
+    }
+
+    // How well current passes through the particle, from 0 for the ones it doesn't pass through
+    // at all to 1 for batteries and the best metals; what it loses on the way through a cell goes
+    // up as this goes down.
+    fn conductivity(&self) -> f32 {
+        match self {
+            Particle::Battery => 1.0,
+            Particle::Mirror => 0.9,
+            Particle::Turbine | Particle::Magnet => 0.8,
+            Particle::Lead => 0.7,
+            Particle::IronPowder => 0.6,
+            Particle::Water => 0.3,
+            _ => 0.0,
+        }
+    }
+
+    // How heavy the particle is for its size, in g/cm³. Powders and liquids sink through liquids
+    // lighter than themselves, which rise out of their way; solids stay put whatever they weigh.
+    fn density(&self) -> f32 {
+        match self {
+            Particle::Hydrogen => 0.0001,
+            Particle::Air | Particle::Steam | Particle::Fire | Particle::Smoke => 0.001,
+            Particle::Snow => 0.3,
+            Particle::Foam => 0.5,
+            Particle::Dust => 0.6,
+            Particle::Oil => 0.8,
+            Particle::Ice | Particle::Rope => 0.9,
+            Particle::Water => 1.0,
+            Particle::Goo => 1.3,
+            Particle::Sand | Particle::Tnt => 1.6,
+            Particle::Gunpowder => 1.7,
+            Particle::Salt => 2.2,
+            Particle::Bedrock
+            | Particle::Laser
+            | Particle::Mirror
+            | Particle::Glass
+            | Particle::Turbine
+            | Particle::Crystal
+            | Particle::Battery => 2.5,
+            Particle::Lava => 2.6,
+            Particle::Radium => 5.5,
+            Particle::Magnet | Particle::IronPowder => 7.9,
+            Particle::Lead => 11.3,
+            Particle::Uranium => 19.1,
+        }
+    }
+
+    // How firmly a surface of the particle holds the grains resting on it, from 0, which lets
+    // them skate on across it, to 1, which stops them where they land.
+    fn friction(&self) -> f32 {
+        match self {
+            Particle::Ice => 0.02,
+            Particle::Glass | Particle::Mirror => 0.3,
+            Particle::Crystal => 0.5,
+            Particle::Bedrock | Particle::Turbine | Particle::Magnet => 0.8,
+            _ => 1.0,
+        }
+    }
+
+    // How many ticks the particle lasts, counted down in its state byte, and what it turns into
+    // after them.
+    fn lifetime(&self) -> Option<(u8, Particle)> {
+        match self {
+            Particle::Foam => Some((90, Particle::Water)),
+            Particle::Fire => Some((40, Particle::Smoke)),
+            Particle::Smoke => Some((150, Particle::Air)),
+            _ => None,
+        }
+    }
+
+    // Whether the particle stops radiation.
+    fn shields_radiation(&self) -> bool {
+        matches!(self, Particle::Lead | Particle::Bedrock)
+    }
+
+    // Whether beams pass through the particle.
+    fn is_transparent(&self) -> bool {
+        matches!(
+            self,
+            Particle::Air
+                | Particle::Water
+                | Particle::Glass
+                | Particle::Ice
+                | Particle::Foam
+                | Particle::Steam
+                | Particle::Fire
+                | Particle::Hydrogen
+        )
+    }
+
+    fn class(&self) -> MaterialClass {
+        match self {
+            Particle::Air
+            | Particle::Steam
+            | Particle::Fire
+            | Particle::Smoke
+            | Particle::Hydrogen => MaterialClass::Gas,
+            Particle::Bedrock
+            | Particle::Laser
+            | Particle::Mirror
+            | Particle::Glass
+            | Particle::Turbine
+            | Particle::Ice
+            | Particle::Crystal
+            | Particle::Magnet
+            | Particle::Uranium
+            | Particle::Tnt
+            | Particle::Battery => MaterialClass::Solid,
+            Particle::Sand
+            | Particle::Snow
+            | Particle::Dust
+            | Particle::Salt
+            | Particle::IronPowder
+            | Particle::Radium
+            | Particle::Lead
+            | Particle::Rope
+            | Particle::Gunpowder => MaterialClass::Powder,
+            Particle::Water | Particle::Foam | Particle::Goo | Particle::Lava | Particle::Oil => {
+                MaterialClass::Liquid
+            }
+        }
+    }
+}
+
+// How a radioactive particle decays: on average half of its cells turn into `product` every
+// `half_life` ticks, and until then each one radiates `heat` degrees per tick into a cell it hits.
+#[derive(Clone, Copy, Debug)]
+struct Decay {
+    half_life: f32,
+    product: Particle,
+    heat: f32,
+}
+
+impl Decay {
+    // The chance that a cell decays in any one tick.
+    fn chance_per_tick(&self) -> f32 {
+        1.0 - 0.5f32.powf(1.0 / self.half_life)
+    }
+}
+
+// How a particle takes part in heat: `conductivity` scales how quickly it evens out with its
+// neighbours and drifts back to ambient (metals are quick, lava holds its heat), and `painted_at`
+// is the temperature its cells start at when painted.
+#[derive(Clone, Copy, Debug)]
+struct Thermal {
+    conductivity: f32,
+    painted_at: f32,
+}
+
+// Broad families of materials that behave alike; connected-region queries can group by these.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum MaterialClass {
+    Gas,
+    Solid,
+    Powder,
+    Liquid,
+}
+
+// The camera that renders the final result to the window.
+#[derive(Component)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+struct ScreenCamera;
+
+// --- PLUGIN ---
+
+// The whole sandbox as one plugin: the world, its simulation and display, the tools and windows,
+// the game modes and saving, for the game's own binary and for embedding in other Bevy apps. It
+// expects Bevy's default plugins with `ModsPlugin` ahead of the asset plugin, which is what
+// `default_plugins` gives, and adds egui unless the app already has it. The world's size, scale,
+// backend and seed come from its `SimulationConfig`, and whatever that leaves unset from the
+// display settings and the command line, as in the game.
+#[derive(Default)]
+pub struct FallingSandPlugin {
+    config: SimulationConfig,
+}
+
+impl FallingSandPlugin {
+    // The sandbox set up as `config` says, or why it can't run that way.
+    pub fn new(config: SimulationConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self { config })
+    }
+}
+
+impl Plugin for FallingSandPlugin {
+    fn build(&self, app: &mut App) {
+        let display = self.config.display_settings();
+        let request = WorldRequest::new(&display);
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin {
+                enable_multipass_for_primary_context: true,
+            });
+        }
+        app.insert_resource(request.layout())
+            .add_plugins((
+                DisplayPlugin(display),
+                FocusPlugin,
+                Material2dPlugin::<SimulationMaterial>::default(),
+                AutotilePlugin,
+                CpuDisplayPlugin,
+                FramePlugin,
+                SimEventsPlugin,
+                SimulationPlugin,
+                ControlPlugin,
+                QualityPlugin,
+                ProfilingPlugin,
+                BindingsPlugin,
+                PlayerPlugin,
+                WorldgenPlugin,
+            ))
+            // Waiting for the shader, rules and content behind a splash screen before the world runs.
+            .add_plugins(LoadingPlugin)
+            // Every change made to the world, in one queue applied between ticks.
+            .add_plugins(WorldCommandsPlugin)
+            // The order every frame runs in, from input to display.
+            .add_plugins(FrameOrderPlugin)
+            // Stepping the world in a compute pass instead, when the display settings ask.
+            .add_plugins(GpuSimPlugin)
+            // Resizing the world while it runs.
+            .add_plugins(ResolutionPlugin(request))
+            // Grains and textures on top of the materials' colors.
+            .add_plugins(CellShadingPlugin)
+            // Walls behind the particles.
+            .add_plugins(BackdropPlugin)
+            // Shedding load to hold the frame rate.
+            .add_plugins(DegradationPlugin)
+            // Timing this machine on a few set worlds, when asked to.
+            .add_plugins(BenchmarkPlugin)
+            // A layout and radial gamepad menus for handhelds.
+            .add_plugins(HandheldPlugin)
+            // Chemistry defined in data.
+            .add_plugins(ReactionRulesPlugin)
+            // Crash reports that come with the world they crashed in, and the log in the game.
+            .add_plugins((CrashHandlerPlugin, LogOverlayPlugin))
+            // Gameplay modes.
+            .add_plugins((
+                DemoPlugin,
+                TutorialPlugin,
+                ObjectivesPlugin,
+                LevelsPlugin,
+                DailyPlugin,
+                TimelinePlugin,
+                InventoryPlugin,
+                SpectatorPlugin,
+                HourglassPlugin,
+                ChaosPlugin,
+                MeteorsPlugin,
+                UserStatsPlugin,
+                MuseumPlugin,
+            ))
+            // Tools and analysis.
+            .add_plugins((
+                TimelapsePlugin,
+                HeatmapPlugin,
+                StatsLogPlugin,
+                RegionsPlugin,
+                ProbesPlugin,
+                OpticsPlugin,
+                ExplosionsPlugin,
+                ProjectilesPlugin,
+                PowerPlugin,
+                ThermalPlugin,
+                TuningPlugin,
+                PresetsPlugin,
+                ZonesPlugin,
+                LoopsPlugin,
+                StampsPlugin,
+            ))
+            // Recording the world as an animated GIF or a PNG sequence.
+            .add_plugins(RecordingPlugin)
+            // Picking materials and brushes with the mouse, with live stats alongside.
+            .add_plugins(PalettePlugin)
+            // Lines, rectangles and bucket fills besides the freehand brush.
+            .add_plugins(PaintToolsPlugin)
+            // Keeping finished builds safe from stray brushes.
+            .add_plugins(PaintLocksPlugin)
+            // Taking strokes and pastes back.
+            .add_plugins(UndoPlugin)
+            // Copying, turning and pasting parts of the world.
+            .add_plugins(SelectionPlugin)
+            // Fountains and drains that run on their own.
+            .add_plugins(EmittersPlugin)
+            // Naming groups of cells and acting on them all at once.
+            .add_plugins(TagsPlugin)
+            // Debugging views and charts, the cell inspector and the wind tunnel.
+            .add_plugins((
+                ReactionViewPlugin,
+                ChunkViewPlugin,
+                DensityProfilePlugin,
+                CollisionPlugin,
+                InspectorPlugin,
+                WindTunnelPlugin,
+            ))
+            // Reading small rectangles of the world's textures back from the GPU.
+            .add_plugins(RegionReadbackPlugin)
+            // Every material, what it does and what it reacts with, with a demo of each.
+            .add_plugins(MaterialReferencePlugin)
+            // Camera.
+            .add_plugins((FollowPlugin, BookmarksPlugin, PanZoomPlugin))
+            // Recording runs and playing them back tick for tick, and logging a session's history.
+            .add_plugins((ReplayPlugin(self.config.seed), HistoryPlugin))
+            // Saving and sharing.
+            .add_plugins((
+                SavesPlugin,
+                QuickSavePlugin,
+                AutosavePlugin,
+                PostcardPlugin,
+                SvgExportPlugin,
+                DropsPlugin,
+                PictureImportPlugin,
+            ))
+            // Clicks and key presses aimed at a panel never reach the world or hotkeys behind it.
+            .insert_resource(EguiGlobalSettings {
+                enable_absorb_bevy_input_system: true,
+                ..default()
+            })
+            .add_systems(Startup, setup)
+            .add_systems(
+                Update,
+                (
+                    paint_on_texture
+                        .after(PaintToolSet)
+                        .before(WorldCommandSet)
+                        .run_if(not(resource_exists::<ReplayPlayback>)),
+                    draw_brush_outlines.in_set(DisplaySet),
+                    // While the GPU steps the world it keeps the state texture itself.
+                    upload_grid.in_set(SwapSet).run_if(not(resource_exists::<GpuStepping>)),
+                ),
+            );
+        // Inspectors and scenes can see the sandbox's own types, in builds that ask for it; the
+        // types inside them are registered along with them. The grid, the rule tables and replays
+        // are opaque, a value to copy or leave as it is, and resources that hold a file, a socket,
+        // a task or assets still loading (the stats log, the spectator host, world generation,
+        // region readbacks and the loading screen) aren't reflected at all.
+        #[cfg(feature = "reflect")]
+        app.register_type::<Player>()
+            .register_type::<SelectedParticle>()
+            .register_type::<Brush>()
+            .register_type::<PlayerCursor>()
+            .register_type::<player::InputSource>()
+            .register_type::<follow::Followable>()
+            .register_type::<CollisionArea>()
+            .register_type::<CollisionShape>()
+            .register_type::<CellState>()
+            .register_type::<SimulationGrid>()
+            .register_type::<SimParams>()
+            .register_type::<SimulationTickRate>()
+            .register_type::<SimulationStats>()
+            .register_type::<sim::SimulationControl>()
+            .register_type::<SimulationBackend>()
+            .register_type::<sim::TickSchedule>()
+            .register_type::<sim::ViewOnly>()
+            .register_type::<sim::LowMemory>()
+            .register_type::<WorldLayout>()
+            .register_type::<SimulationConfig>()
+            .register_type::<display::DisplaySettings>()
+            .register_type::<DisplayBackend>()
+            .register_type::<quality::Quality>()
+            .register_type::<quality::Effects>()
+            .register_type::<degradation::DegradationPolicy>()
+            .register_type::<degradation::Degradation>()
+            .register_type::<autosave::AutosaveSettings>()
+            .register_type::<bindings::InputBindings>()
+            .register_type::<backdrop::BackdropView>()
+            .register_type::<ThermalView>()
+            .register_type::<optics::LaserAim>()
+            .register_type::<MirrorTilt>()
+            .register_type::<heatmap::ActivityMap>()
+            .register_type::<locks::PaintLocks>()
+            .register_type::<undo::PaintHistory>()
+            .register_type::<inventory::Inventory>()
+            .register_type::<stamps::StampLibrary>()
+            .register_type::<WorldCommands>()
+            .register_type::<WorldRequest>()
+            .register_type::<worldgen::StartingTerrain>()
+            .register_type::<behavior::MaterialBehaviors>()
+            .register_type::<ReactionTable>()
+            .register_type::<reaction_rules::RuleFolders>()
+            .register_type::<levels::ActiveLevel>()
+            .register_type::<levels::PaintRules>()
+            .register_type::<levels::LevelProgress>()
+            .register_type::<objectives::ObjectiveProgress>()
+            .register_type::<tutorial::ActiveTutorial>()
+            .register_type::<presets::ActivePreset>()
+            .register_type::<demo::DemoSettings>()
+            .register_type::<demo::DemoPlayback>()
+            .register_type::<replay::ReplayRecording>()
+            .register_type::<ReplayPlayback>()
+            .register_type::<timelapse::TimelapseSettings>()
+            .register_type::<timelapse::Timelapse>()
+            .register_type::<stats_log::StatsLogSettings>()
+            .register_type::<user_stats::LifetimeStats>()
+            .register_type::<GpuStepping>()
+            .register_type::<SimEvent>()
+            .register_type::<AppliedCommand>()
+            .register_type::<Disaster>()
+            .register_type::<Earthquake>()
+            .register_type::<Explosion>()
+            .register_type::<levels::LevelCompleted>()
+            .register_type::<objectives::ObjectiveCompleted>()
+            .register_type::<presets::ApplyPreset>()
+            .register_type::<presets::SavePreset>()
+            .register_type::<resolution::GrowWorld>()
+            .register_type::<resolution::TrimWorld>()
+            .register_type::<resolution::WorldShifted>()
+            .register_type::<daily::StartDailyChallenge>()
+            .register_type::<region_readback::RegionRead>()
+            // Writing the world's entities out as a scene and reading them back (Ctrl+F7).
+            .add_plugins(scenes::ScenesPlugin);
+        // Material behaviors scripted in Lua in mods.
+        #[cfg(feature = "scripting")]
+        app.add_plugins(scripting::ScriptingPlugin);
+        // Online sharing is opt in, so default builds make no network requests.
+        #[cfg(feature = "workshop")]
+        app.add_plugins(workshop::WorkshopPlugin);
+        // Rigid bodies that collide with and carry the world's cells (Ctrl+B).
+        #[cfg(feature = "physics")]
+        app.add_plugins(physics::PhysicsPlugin);
+        // Suspend and resume and the safe area, on phones and tablets.
+        #[cfg(feature = "mobile")]
+        app.add_plugins(mobile::MobilePlugin);
+    }
+}
+
+// --- RUNNER ---
+
+// Bevy's default plugins as the game runs with them: mods as an asset source (sources have to
+// exist before the asset server does), a window titled `title` and sized as `config` and the
+// display settings ask, and the log captured for the log window as well as printed. In the browser
+// the window is the page's canvas, and bundled assets come through the web build's own source.
+pub fn default_plugins(title: &str, config: &SimulationConfig) -> PluginGroupBuilder {
+    let display = config.display_settings();
+    let layout = WorldRequest::new(&display).layout();
+    let mut window = display.window(title, &layout);
+    let web = cfg!(target_arch = "wasm32");
+    if web {
+        web::embed_in_page(&mut window);
+    }
+    let plugins = DefaultPlugins
+        .set(WindowPlugin {
+            primary_window: Some(window),
+            ..default()
+        })
+        .set(LogPlugin {
+            custom_layer: log_overlay::capture_layer,
+            ..default()
+        })
+        .add_before::<AssetPlugin>(ModsPlugin);
+    if web { plugins.add_before::<AssetPlugin>(web::WebPlugin) } else { plugins }
+}
+
+// Runs the command-line tool asked for instead of the game, if any, and returns its exit code.
+// Experiments, replay checks and headless runs all run without a window and exit.
+pub fn run_tool() -> Option<i32> {
+    if let Some(manifest) = experiment::manifest_arg() {
+        return Some(experiment::run(&manifest));
+    }
+    if let Some(replay) = replay::check_arg() {
+        return Some(replay::check(&replay));
+    }
+    headless::headless_arg().map(headless::run)
+}
+
+// Runs the dedicated server until it is told to quit, and returns its exit code; this is all the
+// `jules-server` binary does. See server.rs.
+pub fn run_server() -> i32 {
+    server::run()
+}
+
+// --- COMPONENTS AND RESOURCES ---
+
+// The texture the grid is copied into every frame, the one holding how bright each cell is drawn,
+// the one holding the walls behind the cells, and the material that colors it on screen.
+#[derive(Resource)]
+struct SimulationDisplay {
+    state_image: Handle<Image>,
+    shade_image: Handle<Image>,
+    backdrop_image: Handle<Image>,
+    material: Handle<SimulationMaterial>,
+}
+
+#[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
+struct SimulationMaterial {
+    // What every cell holds, as integers; see `write_state_texture`.
+    #[texture(0, sample_type = "u_int")]
+    source_image: Handle<Image>,
+    // 0 draws materials, 1 draws the temperature stored in the green channel.
+    #[uniform(2)]
+    view_mode: u32,
+    // Each cell's autotile neighbor mask and the atlas it picks a tile from; see autotile.rs.
+    #[texture(3)]
+    #[sampler(4)]
+    tile_image: Handle<Image>,
+    #[texture(5)]
+    #[sampler(6)]
+    tile_atlas: Handle<Image>,
+    // 1 while solid materials are drawn autotiled.
+    #[uniform(7)]
+    autotile: u32,
+    // 0 draws cells as sharp squares, 1 smooths their edges with xBR; see display.rs.
+    #[uniform(8)]
+    upscaler: u32,
+    // How much brighter or darker each cell is drawn; see shading.rs.
+    #[texture(9)]
+    #[sampler(10)]
+    shade_image: Handle<Image>,
+    // What the state texture's particle ids look like; see `palette_texture`.
+    #[texture(11)]
+    palette: Handle<Image>,
+    // The wall behind every cell, as integers; see backdrop.rs.
+    #[texture(12, sample_type = "u_int")]
+    backdrop_image: Handle<Image>,
+}
+
+impl Material2d for SimulationMaterial {
+    fn fragment_shader() -> ShaderRef {
+        cpu_display::WORLD_SHADER.into()
+    }
+}
+
+// The world's size in cells, which everything that maps between cells and world space derives
+// from. World space here is the world quad's own: the quad is centered on the origin, one cell
+// WORLD_UNITS_PER_CELL wide. Window pixels only map onto it through the screen camera, however it
+// is panned or zoomed; see WorldView.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct WorldLayout {
+    width: u32,
+    height: u32,
+}
+
+impl WorldLayout {
+    // The world's size in cells.
+    fn size(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    // The whole world's size in world units, as the camera frames it and its quad covers it.
+    fn world_size(&self) -> Vec2 {
+        self.size() * WORLD_UNITS_PER_CELL
+    }
+
+    // Converts a point in cell units (origin bottom left) to screen-camera world space.
+    fn cell_to_world(&self, cell: Vec2) -> WorldPos {
+        WorldPos((cell - self.size() / 2.0) * WORLD_UNITS_PER_CELL)
+    }
+
+    fn world_to_cell(&self, world: WorldPos) -> Vec2 {
+        world.0 / WORLD_UNITS_PER_CELL + self.size() / 2.0
+    }
+
+    // The rectangle of world space covering the cells from `min` to `max`, both included.
+    fn cells_to_world(&self, min: CellPos, max: CellPos) -> Rect {
+        let max = (max.0 + IVec2::ONE).as_vec2();
+        Rect::from_corners(*self.cell_to_world(min.as_vec2()), *self.cell_to_world(max))
+    }
+}
+
+// The primary window, the screen camera, the world quad and the world's layout, for systems that
+// map cursors onto cells and cells onto the screen.
+#[derive(SystemParam)]
+struct WorldView<'w, 's> {
+    q_window: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    q_camera: Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<ScreenCamera>>,
+    q_quad: Query<'w, 's, &'static GlobalTransform, With<MeshMaterial2d<SimulationMaterial>>>,
+    layout: Res<'w, WorldLayout>,
+}
+
+impl WorldView<'_, '_> {
+    fn window(&self) -> Option<&Window> {
+        self.q_window.single().ok()
+    }
+
+    // The cell under the window's own cursor, while it is over the window.
+    fn cursor_cell(&self) -> Option<CellPos> {
+        let window = self.window()?;
+        self.cell_at(window.cursor_position()?)
+    }
+
+    // The cell under `position`, in window coordinates.
+    fn cell_at(&self, position: Vec2) -> Option<CellPos> {
+        Some(CellPos::containing(self.layout.world_to_cell(self.screen_to_world(position)?)))
+    }
+
+    // The point of the world quad under `position`, in window coordinates, as the screen camera
+    // shows it: letterboxed, resized, panned or zoomed alike. None while the camera can't say,
+    // such as before its first frame.
+    fn screen_to_world(&self, position: Vec2) -> Option<WorldPos> {
+        let (camera, camera_transform) = self.q_camera.single().ok()?;
+        let world = camera.viewport_to_world_2d(camera_transform, position).ok()?;
+        let quad = self.q_quad.single().map_or(default(), |transform| transform.affine());
+        Some(WorldPos(quad.inverse().transform_point3(world.extend(0.0)).truncate()))
+    }
+
+    fn cell_to_world(&self, cell: Vec2) -> WorldPos {
+        self.layout.cell_to_world(cell)
+    }
+
+    fn cells_to_world(&self, min: CellPos, max: CellPos) -> Rect {
+        self.layout.cells_to_world(min, max)
+    }
+
+    // How big one cell is in world space.
+    fn cell_size(&self) -> Vec2 {
+        Vec2::splat(WORLD_UNITS_PER_CELL)
+    }
+}
+
+// What a brush pass goes by besides the brush: the tilt painted mirrors get, and the world, for
+// brushes that conform to what is already there.
+#[derive(SystemParam)]
+struct PaintSurroundings<'w, 's> {
+    mirror_tilt: Res<'w, MirrorTilt>,
+    access: SimulationAccess<'w, 's>,
+}
+
+// --- SYSTEMS ---
+
+fn setup(
+    mut commands: Commands,
+    layout: Res<WorldLayout>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
+) {
+    let size = Extent3d {
+        width: layout.width,
+        height: layout.height,
+        ..default()
+    };
+    // Starts empty; the world generator streams the starting world in.
+    let grid = SimulationGrid::new(layout.width, layout.height);
+
+    // The state texture holds particle ids and other state, not colors, so it is an integer
+    // texture that the shader only reads whole texels of.
+    let texture_descriptor = TextureDescriptor {
+        label: None,
+        size,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Uint,
+        mip_level_count: 1,
+        sample_count: 1,
+        // Copied back for the inspector to show what the GPU has.
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+        view_formats: &[TextureFormat::Rgba8Uint],
+    };
+
+    let mut state_image = Image {
+        data: Some(vec![0; (layout.width * layout.height * 4) as usize]),
+        texture_descriptor,
+        sampler: ImageSampler::nearest(),
+        ..default()
+    };
+    write_state_texture(&grid, None, &mut state_image);
+
+    let h_state_image = images.add(state_image);
+    // Neutral until the shading plugin works out the shades.
+    let mut shade_image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[NEUTRAL_SHADE],
+        TextureFormat::R8Unorm,
+        RenderAssetUsages::default(),
+    );
+    shade_image.sampler = ImageSampler::nearest();
+    let h_shade_image = images.add(shade_image);
+    // No walls until the backdrop plugin finds some.
+    let mut backdrop_image = Image::new_fill(
+        size,
+        TextureDimension::D2,
+        &[0],
+        TextureFormat::R8Uint,
+        RenderAssetUsages::default(),
+    );
+    backdrop_image.sampler = ImageSampler::nearest();
+    let h_backdrop_image = images.add(backdrop_image);
+
+    // This camera renders the final result TO the screen, fitting the whole world into the window
+    // whatever its size.
+    commands.spawn((
+        Camera2d,
+        ScreenCamera,
+        Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::AutoMin {
+                min_width: layout.world_size().x,
+                min_height: layout.world_size().y,
+            },
+            ..OrthographicProjection::default_2d()
+        }),
+    ));
+
+    let material = sim_materials.add(SimulationMaterial {
+        source_image: h_state_image.clone(),
+        view_mode: 0,
+        // The autotile plugin fills these in once the material exists.
+        tile_image: Handle::default(),
+        tile_atlas: Handle::default(),
+        autotile: 0,
+        upscaler: 0,
+        shade_image: h_shade_image.clone(),
+        palette: images.add(palette_texture()),
+        backdrop_image: h_backdrop_image.clone(),
+    });
+
+    let quad_handle = meshes.add(Rectangle::from_size(layout.world_size()));
+
+    commands.spawn((
+        Mesh2d(quad_handle),
+        MeshMaterial2d(material.clone()),
+        Transform::default(),
+        Visibility::default(),
+    ));
+
+    commands.insert_resource(grid);
+    commands.insert_resource(SimulationDisplay {
+        state_image: h_state_image,
+        shade_image: h_shade_image,
+        backdrop_image: h_backdrop_image,
+        material,
+    });
+}
+
+fn upload_grid(
+    grid: Res<SimulationGrid>,
+    display: Res<SimulationDisplay>,
+    thermal: Res<ThermalView>,
+    mut pacing: UploadPacing,
+    mut images: ResMut<Assets<Image>>,
+    mut sim_materials: ResMut<Assets<SimulationMaterial>>,
+) {
+    if !pacing.due(grid.is_changed() || thermal.is_changed()) {
+        return;
+    }
+    let scale = thermal.scale(&grid);
+    if let Some(image) = images.get_mut(&display.state_image) {
+        write_state_texture(&grid, scale, image);
+    }
+    // Touching the material also makes its bind group pick up the re-uploaded texture.
+    if let Some(material) = sim_materials.get_mut(&display.material) {
+        material.view_mode = scale.is_some() as u32;
+    }
+}
+
+// Turns every player's brush into `WorldCommand::Paint` passes, one a frame while they paint. How
+// many cells last frame's pass painted comes back as a `SimEvent::Painted` and is taken off the
+// budget before this frame's.
+fn paint_on_texture(
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    mut q_players: Query<(&Player, &PlayerCursor, &SelectedParticle, &mut Brush)>,
+    surroundings: PaintSurroundings,
+    mut commands: ResMut<WorldCommands>,
+    mut sim_events: EventReader<SimEvent>,
+) {
+    for event in sim_events.read() {
+        let &SimEvent::Painted { player, cells, .. } = event else { continue };
+        let Some((.., mut brush)) = q_players.iter_mut().find(|(p, ..)| p.index == player) else {
+            continue;
+        };
+        brush.budget -= cells as f32;
+        brush.painted += cells;
+    }
+    for (player, cursor, selected_particle, mut brush) in &mut q_players {
+        if !cursor.painting {
+            // One line per stroke rather than per frame, once the button is let go.
+            if brush.painted > 0 {
+                debug!(player = player.index + 1, cells = brush.painted, "Stroke painted");
+            }
+            brush.budget = 0.0;
+            brush.last_cell = None;
+            brush.painted = 0;
+            commands.push(WorldCommand::EndStroke {
+                player: player.index,
+            });
+            continue;
+        }
+        // A line, rectangle or fill is painted by paint_tools.rs instead.
+        if brush.drag.is_some() {
+            continue;
+        }
+        let Some(cursor_pos) = cursor.stroke else {
+            brush.last_cell = None;
+            continue;
+        };
+        let Some(center) = view.cell_at(cursor_pos) else { continue };
+
+        // The brush earns cells with time rather than per frame, so it paints as much at 240 Hz
+        // as at 60 Hz. Whatever a full brush can't use is dropped instead of saved up. Moving
+        // paints the whole way from last frame's spot, and the cells swept on the way come on
+        // top, so a fast stroke is as solid as a slow one.
+        let from = brush.last_cell.replace(center).unwrap_or(center);
+        let swept = brush.shape.swept_cells(from, center, brush.size);
+        // Holding Alt paints the walls behind, as the background layer does, in one go.
+        let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+        if brush.layer == PaintLayer::Background || alt {
+            commands.push(WorldCommand::Backdrop {
+                particle: cursor.paint(selected_particle),
+                cells: swept,
+            });
+            continue;
+        }
+        let area = brush.shape.area(brush.size) as f32;
+        let on_the_way = swept.len() as f32 - area;
+        brush.budget = (brush.budget + brush.flow * time.delta_secs()).min(area) + on_the_way;
+
+        let particle = cursor.paint(selected_particle);
+        let data = painted_state(particle, &surroundings.mirror_tilt);
+        // A spray fills a different random share of the brush on every pass, so holding it
+        // still slowly fills the brush in.
+        let density = if particle.class() == MaterialClass::Powder { brush.density } else { 1.0 };
+        let pass = time.elapsed().as_micros() as u64;
+        let sprayed: Vec<_> = swept
+            .into_iter()
+            .filter(|cell| density >= 1.0 || roll(cell.x, cell.y, pass) < density)
+            .collect();
+        let sprayed = match particle {
+            Particle::Air => sprayed,
+            _ => brush.conform.conform(sprayed, &surroundings.access),
+        };
+        commands.push(WorldCommand::Paint {
+            player: player.index,
+            particle,
+            data,
+            cells: sprayed,
+            max_cells: brush.budget as u32,
+        });
+    }
+}
+
+// Outlines every player's brush under their cursor, so its shape and size show before painting.
+fn draw_brush_outlines(
+    view: WorldView,
+    q_players: Query<(&PlayerCursor, &Brush)>,
+    mut gizmos: Gizmos,
+) {
+    let cell = view.cell_size();
+    for (cursor, brush) in &q_players {
+        let Some(position) = cursor.stroke.or(cursor.position) else { continue };
+        let Some(center) = view.cell_at(position) else { continue };
+        let at = Isometry2d::from_translation(*view.cell_to_world(center.center()));
+        let across = (brush.size * 2 + 1) as f32;
+        match brush.shape {
+            BrushShape::Square => gizmos.rect_2d(at, cell * across, BRUSH_OUTLINE_COLOR),
+            BrushShape::Line => {
+                gizmos.rect_2d(at, cell * Vec2::new(across, 1.0), BRUSH_OUTLINE_COLOR)
+            }
+            BrushShape::Circle => {
+                gizmos.circle_2d(at, cell.x * across / 2.0, BRUSH_OUTLINE_COLOR);
+            }
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// Every material's color for the shader to look particle ids up in, a column per id: fresh in the
+// top row and fully weathered in the bottom one, the same as fresh for materials that don't
+// weather. Colors are linear, as the shader blends them.
+fn palette_texture() -> Image {
+    let fresh = Particle::ALL.map(|particle| particle.color());
+    let weathered = Particle::ALL.map(|particle| {
+        particle.weathering().map_or(particle.color(), |(aged, _)| aged)
+    });
+    let data = fresh
+        .iter()
+        .chain(&weathered)
+        .flat_map(|color| color.to_linear().to_u8_array())
+        .collect();
+    let mut palette = Image::new(
+        Extent3d {
+            width: Particle::ALL.len() as u32,
+            height: 2,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::default(),
+    );
+    palette.sampler = ImageSampler::nearest();
+    palette
+}
+
+// Encodes the grid into the state texture: particle id in the red channel and, given a
+// temperature `scale`, the temperature mapped from its min..max onto 0..255 in the green one, or
+// else how charged the cell is, how weathered the particle is in the blue one and how stained it
+// is in the alpha one.
+// Texture rows run top-down while grid rows run bottom-up, so rows are flipped on the way.
+fn write_state_texture(grid: &SimulationGrid, scale: Option<(f32, f32)>, image: &mut Image) {
+    if let Some(data) = image.data.as_mut() {
+        write_state_texels(grid, scale, data);
+    }
+}
+
+// The same, into bare texels laid out as the state texture's are, four bytes a cell.
+fn write_state_texels(grid: &SimulationGrid, scale: Option<(f32, f32)>, data: &mut [u8]) {
+    let width = grid.width() as usize;
+    for (y, row) in grid.cells().chunks(width).enumerate() {
+        let texture_row = grid.height() as usize - 1 - y;
+        let temperatures = &grid.temperatures()[y * width..(y + 1) * width];
+        let ages = &grid.ages()[y * width..(y + 1) * width];
+        let stains = &grid.stains()[y * width..(y + 1) * width];
+        let charges = &grid.charges()[y * width..(y + 1) * width];
+        let cells = row.iter().zip(temperatures).zip(ages).zip(stains).zip(charges);
+        for (x, ((((particle, temperature), age), stain), charge)) in cells.enumerate() {
+            let texel = state_texel(*particle, *temperature, *age, *stain, *charge, scale);
+            let i = (texture_row * width + x) * 4;
+            data[i..i + 4].copy_from_slice(&texel);
+        }
+    }
+}
+
+// One cell's texel of the state texture: its particle id, its temperature on `scale` or else its
+// charge, how far along its weathering it is and its stain.
+fn state_texel(
+    particle: Particle,
+    temperature: f32,
+    age: u16,
+    stain: u8,
+    charge: u8,
+    scale: Option<(f32, f32)>,
+) -> [u8; 4] {
+    let heat = match scale {
+        Some((min, max)) => ((temperature - min) / (max - min) * 255.0).clamp(0.0, 255.0) as u8,
+        None => charge,
+    };
+    // How far along its weathering the particle is, from fresh (0) to fully weathered.
+    let weathered = match particle.weathering() {
+        Some((_, full)) => (age as u32 * 255 / full as u32).min(255) as u8,
+        None => 0,
+    };
+    [particle.id(), heat, weathered, stain]
+}
@@ -0,0 +1,273 @@
+// --- IMPORTS ---
+use bevy::asset::{LoadState, RecursiveDependencyLoadState, UntypedAssetId};
+use bevy::prelude::*;
+
+use crate::cpu_display::{WORLD_SHADER, fall_back};
+use crate::levels::LEVEL_FOLDER;
+use crate::mods::mod_folder;
+use crate::objectives::OBJECTIVE_FOLDER;
+use crate::picture_import::PALETTE_FOLDER;
+use crate::player::PlayerInputSet;
+use crate::presets::PRESET_FOLDER;
+use crate::reaction_rules::RULES_FOLDER;
+#[cfg(feature = "scripting")]
+use crate::scripting::SCRIPTS_FOLDER;
+use crate::sim::SimulationSet;
+use crate::tutorial::TUTORIAL_FOLDER;
+
+// --- CONSTANTS ---
+// The folders startup waits for, by what they hold; every one but the tutorials and objectives
+// also comes from mods.
+const STARTUP_FOLDERS: [(&str, &str, bool); 6] = [
+    ("reaction rules", RULES_FOLDER, true),
+    ("levels", LEVEL_FOLDER, true),
+    ("presets", PRESET_FOLDER, true),
+    ("palettes", PALETTE_FOLDER, true),
+    ("objectives", OBJECTIVE_FOLDER, false),
+    ("tutorials", TUTORIAL_FOLDER, false),
+];
+// Material scripts only load in builds that can run them.
+#[cfg(feature = "scripting")]
+const SCRIPT_FOLDERS: [(&str, &str, bool); 1] = [("material scripts", SCRIPTS_FOLDER, true)];
+#[cfg(not(feature = "scripting"))]
+const SCRIPT_FOLDERS: [(&str, &str, bool); 0] = [];
+const BAR_WIDTH: f32 = 320.0;
+const ERROR_COLOR: Color = Color::srgb(1.0, 0.55, 0.45);
+
+// --- PLUGIN ---
+
+// Startup waits for what the game is made of before the world runs: the world's shader, the
+// reaction rules, material scripts, levels, presets, palettes, objectives and tutorials, bundled
+// and from mods. They load in the background behind a splash screen that shows how many are in and
+// what is still loading, and the world doesn't step and can't be painted until they are. Whatever
+// failed, such as a missing shader or a material file that doesn't parse, is listed on an error
+// screen afterwards, which Enter dismisses to carry on without it; without its shader the world is
+// drawn on the CPU. Mods needn't have any of the folders.
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            (SimulationSet, PlayerInputSet).run_if(not(resource_exists::<StartupLoading>)),
+        )
+        .configure_sets(FixedUpdate, SimulationSet.run_if(not(resource_exists::<StartupLoading>)))
+        .add_systems(Startup, start_loading)
+        .add_systems(
+            Update,
+            (track_loading, update_splash)
+                .chain()
+                .run_if(resource_exists::<StartupLoading>)
+                .before(SimulationSet),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// One thing startup waits for.
+struct StartupAsset {
+    label: String,
+    id: UntypedAssetId,
+    // Mods' folders may not be there at all, which is no error.
+    optional: bool,
+    // Without it, the world is drawn on the CPU.
+    shader: bool,
+}
+
+// --- RESOURCES ---
+
+// What startup is still waiting for, and what failed so far. Exists until everything is in and
+// any errors are dismissed.
+#[derive(Resource)]
+pub struct StartupLoading {
+    pending: Vec<StartupAsset>,
+    total: usize,
+    errors: Vec<String>,
+    // Kept so nothing waited on is dropped before it is in.
+    _handles: Vec<UntypedHandle>,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct Splash;
+
+#[derive(Component)]
+struct SplashStatus;
+
+#[derive(Component)]
+struct SplashBar;
+
+#[derive(Component)]
+struct SplashErrors;
+
+// --- SYSTEMS ---
+
+fn start_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let mut handles = vec![asset_server.load::<Shader>(WORLD_SHADER).untyped()];
+    let mut pending = vec![StartupAsset {
+        label: "the world's shader".to_string(),
+        id: handles[0].id(),
+        optional: false,
+        shader: true,
+    }];
+    for (label, folder, modded) in STARTUP_FOLDERS.into_iter().chain(SCRIPT_FOLDERS) {
+        let sources = [(folder.to_string(), false), (mod_folder(folder), true)];
+        for (path, optional) in sources.into_iter().take(if modded { 2 } else { 1 }) {
+            let handle = asset_server.load_folder(path).untyped();
+            pending.push(StartupAsset {
+                label: if optional { format!("mods' {}", label) } else { label.to_string() },
+                id: handle.id(),
+                optional,
+                shader: false,
+            });
+            handles.push(handle);
+        }
+    }
+    commands.insert_resource(StartupLoading {
+        total: pending.len(),
+        pending,
+        errors: Vec::new(),
+        _handles: handles,
+    });
+    spawn_splash(&mut commands);
+}
+
+// Takes whatever finished off the list, noting what failed and why. Once nothing is left and
+// nothing failed, or Enter dismissed the errors, startup is over and the splash goes.
+fn track_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut loading: ResMut<StartupLoading>,
+    q_splash: Query<Entity, With<Splash>>,
+) {
+    let mut failed = Vec::new();
+    loading.pending.retain(|asset| {
+        // A mod folder that isn't there fails itself; one with a bad file in it fails below.
+        if asset.optional && matches!(asset_server.load_state(asset.id), LoadState::Failed(_)) {
+            return false;
+        }
+        match asset_server.recursive_dependency_load_state(asset.id) {
+            RecursiveDependencyLoadState::Loaded => false,
+            RecursiveDependencyLoadState::Failed(error) => {
+                failed.push((asset.label.clone(), error.to_string(), asset.shader));
+                false
+            }
+            _ => true,
+        }
+    });
+    for (label, error, shader) in failed {
+        warn!("Couldn't load {}: {}", label, error);
+        if shader {
+            fall_back(&mut commands, "the world's shader is missing");
+        }
+        loading.errors.push(format!("{}: {}", label, error));
+    }
+    let dismissed = loading.errors.is_empty() || keys.just_pressed(KeyCode::Enter);
+    if loading.pending.is_empty() && dismissed {
+        commands.remove_resource::<StartupLoading>();
+        for splash in &q_splash {
+            commands.entity(splash).despawn();
+        }
+        info!("Loaded the game's assets");
+    }
+}
+
+fn update_splash(
+    loading: Res<StartupLoading>,
+    mut q_status: Query<&mut Text, (With<SplashStatus>, Without<SplashErrors>)>,
+    mut q_errors: Query<&mut Text, (With<SplashErrors>, Without<SplashStatus>)>,
+    mut q_bar: Query<&mut Node, With<SplashBar>>,
+) {
+    let done = 1.0 - loading.pending.len() as f32 / loading.total.max(1) as f32;
+    for mut bar in &mut q_bar {
+        bar.width = Val::Px(BAR_WIDTH * done);
+    }
+    let status = match loading.pending.first() {
+        Some(asset) => format!("Loading {}...", asset.label),
+        None => "Some of the game's files didn't load. Press Enter to carry on without them."
+            .to_string(),
+    };
+    for mut text in &mut q_status {
+        if text.0 != status {
+            text.0.clone_from(&status);
+        }
+    }
+    let errors = loading.errors.join("\n");
+    for mut text in &mut q_errors {
+        if text.0 != errors {
+            text.0.clone_from(&errors);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn spawn_splash(commands: &mut Commands) {
+    commands
+        .spawn((
+            Splash,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                padding: UiRect::all(Val::Px(24.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.05, 0.05, 0.07)),
+            // Over the world generation's loading screen.
+            GlobalZIndex(11),
+        ))
+        .with_children(|splash| {
+            splash.spawn((
+                Text::new("Falling Sand"),
+                TextFont {
+                    font_size: 32.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            splash
+                .spawn((
+                    Node {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.2, 0.25)),
+                ))
+                .with_child((
+                    SplashBar,
+                    Node {
+                        width: Val::Px(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.9, 0.8, 0.5)),
+                ));
+            splash.spawn((
+                SplashStatus,
+                Text::new("Loading..."),
+                TextFont {
+                    font_size: 18.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            splash.spawn((
+                SplashErrors,
+                Text::default(),
+                TextFont {
+                    font_size: 14.0,
+                    ..default()
+                },
+                TextColor(ERROR_COLOR),
+            ));
+        });
+}
@@ -0,0 +1,180 @@
+// --- IMPORTS ---
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::coords::CellPos;
+use crate::player::PlayerInputSet;
+use crate::resolution::WorldShifted;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+const LOCK_COLOR: Color = Color::srgba(1.0, 0.35, 0.3, 0.7);
+// Locked cells are hatched along every this-many-th diagonal.
+const HATCH_SPACING: i32 = 4;
+
+// --- PLUGIN ---
+
+// Paint locks keep finished builds from being smudged: brushes and stamps leave locked regions and
+// locked materials alone, painting and erasing alike, while the simulation still moves, burns and
+// blasts them as usual. A turns the lock tool on, which hatches everything locked. With it on, A
+// marks a region's first corner and then its opposite one to lock it, or unlocks the region under
+// the cursor, and Shift+A locks or unlocks the material under the cursor wherever it is. Esc turns
+// the tool off again; the locks stay.
+pub struct PaintLocksPlugin;
+
+impl Plugin for PaintLocksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PaintLocks>().init_resource::<LockTool>().add_systems(
+            Update,
+            (follow_world_shifts, use_lock_tool, draw_locks)
+                .chain()
+                .after(PlayerInputSet)
+                .before(SimulationSet),
+        );
+    }
+}
+
+// --- RESOURCES ---
+
+// What brushes and stamps must leave alone.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct PaintLocks {
+    // Inclusive corners.
+    regions: Vec<(CellPos, CellPos)>,
+    materials: HashSet<Particle>,
+}
+
+impl PaintLocks {
+    // Whether `cell`, which holds `particle`, is locked.
+    pub fn is_locked(&self, cell: CellPos, particle: Particle) -> bool {
+        self.materials.contains(&particle)
+            || self.regions.iter().any(|&region| within(region, cell))
+    }
+
+    // Locks the rectangle with corners `a` and `b`, both included.
+    pub fn lock_region(&mut self, a: CellPos, b: CellPos) {
+        self.regions.push((CellPos(a.min(*b)), CellPos(a.max(*b))));
+    }
+}
+
+#[derive(Resource, Default)]
+struct LockTool {
+    active: bool,
+    // The first corner of the region being marked.
+    corner: Option<CellPos>,
+}
+
+// --- SYSTEMS ---
+
+fn use_lock_tool(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    grid: Res<SimulationGrid>,
+    mut tool: ResMut<LockTool>,
+    mut locks: ResMut<PaintLocks>,
+) {
+    if tool.active && keys.just_pressed(KeyCode::Escape) {
+        *tool = LockTool::default();
+        info!("Lock tool off");
+        return;
+    }
+    if !keys.just_pressed(KeyCode::KeyA) {
+        return;
+    }
+    if !tool.active {
+        tool.active = true;
+        info!(
+            "Lock tool: A locks a region or unlocks the one under the cursor, Shift+A locks or \
+             unlocks a material, Esc closes"
+        );
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift {
+        // Locking air would lock every empty cell, so there would be nowhere left to paint.
+        let Some(particle) = grid.get(cell.x, cell.y).filter(|&p| p != Particle::Air) else {
+            return;
+        };
+        if locks.materials.remove(&particle) {
+            info!("Unlocked {:?}", particle);
+        } else {
+            locks.materials.insert(particle);
+            info!("Locked {:?} everywhere", particle);
+        }
+        return;
+    }
+    if let Some(corner) = tool.corner.take() {
+        locks.lock_region(corner, cell);
+        let (min, max) = locks.regions[locks.regions.len() - 1];
+        info!("Locked the region from {:?} to {:?}", min.0, max.0);
+    } else if let Some(index) = locks.regions.iter().rposition(|&region| within(region, cell)) {
+        let (min, max) = locks.regions.remove(index);
+        info!("Unlocked the region from {:?} to {:?}", min.0, max.0);
+    } else {
+        tool.corner = Some(cell);
+        info!("Locking a region: press A at the opposite corner (Esc cancels)");
+    }
+}
+
+// Outlines the locked regions and hatches every locked cell while the tool is on.
+fn draw_locks(
+    tool: Res<LockTool>,
+    locks: Res<PaintLocks>,
+    grid: Res<SimulationGrid>,
+    view: WorldView,
+    mut gizmos: Gizmos,
+) {
+    if !tool.active {
+        return;
+    }
+    let mut outline = |min, max, color| {
+        let rect = view.cells_to_world(min, max);
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), color);
+    };
+    for &(min, max) in &locks.regions {
+        outline(min, max, LOCK_COLOR);
+    }
+    // The region being marked, from its first corner to the cursor.
+    if let Some(corner) = tool.corner
+        && let Some(cell) = view.cursor_cell()
+    {
+        outline(CellPos(corner.min(*cell)), CellPos(corner.max(*cell)), Color::WHITE);
+    }
+
+    // A stroke from corner to corner of every locked cell on the hatched diagonals; the strokes of
+    // neighbouring cells join up into stripes.
+    for y in 0..grid.height() as i32 {
+        for x in 0..grid.width() as i32 {
+            if (x + y) % HATCH_SPACING != 0 {
+                continue;
+            }
+            let cell = CellPos::new(x, y);
+            if !locks.is_locked(cell, grid.get(x, y).unwrap_or_default()) {
+                continue;
+            }
+            let top_left = view.cell_to_world(Vec2::new(x as f32, y as f32 + 1.0));
+            let bottom_right = view.cell_to_world(Vec2::new(x as f32 + 1.0, y as f32));
+            gizmos.line_2d(*top_left, *bottom_right, LOCK_COLOR);
+        }
+    }
+}
+
+// Keeps locked regions on the same cells when the world grows under them.
+fn follow_world_shifts(mut shifts: EventReader<WorldShifted>, mut locks: ResMut<PaintLocks>) {
+    for shift in shifts.read() {
+        for (min, max) in &mut locks.regions {
+            (*min, *max) = (*min + shift.offset, *max + shift.offset);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn within((min, max): (CellPos, CellPos), cell: CellPos) -> bool {
+    cell.cmpge(*min).all() && cell.cmple(*max).all()
+}
@@ -0,0 +1,200 @@
+// --- IMPORTS ---
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy::log::tracing::field::{Field, Visit};
+use bevy::log::tracing::{Event, Subscriber};
+use bevy::log::tracing_subscriber::Layer;
+use bevy::log::tracing_subscriber::layer::Context;
+use bevy::log::{BoxedLayer, Level};
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use web_time::Instant;
+
+use crate::pan_zoom::ctrl_held;
+
+// --- CONSTANTS ---
+// How many of the latest messages are kept.
+const KEPT_LINES: usize = 500;
+// How long warnings and errors stay on screen while the log is closed.
+const ALERT_TIME: Duration = Duration::from_secs(8);
+const ALERT_LINES: usize = 4;
+const LEVELS: [Level; 4] = [Level::ERROR, Level::WARN, Level::INFO, Level::DEBUG];
+
+// --- PLUGIN ---
+
+// The log overlay (Ctrl+L): the latest messages the game logged, newest at the bottom, with their
+// level, where they came from and any fields they carry, filtered by level and by text. Messages
+// reach it through a layer on the log plugin (see `capture_layer`), so they still go to the
+// console as well. While it's closed, warnings and errors stay in the bottom left corner for a
+// few seconds, so they aren't lost among everything else.
+pub struct LogOverlayPlugin;
+
+impl Plugin for LogOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        // Without the log plugin's layer nothing is captured, and the overlay stays empty.
+        app.init_resource::<CapturedLog>()
+            .init_resource::<LogOverlay>()
+            .add_systems(Update, toggle_log_overlay)
+            .add_systems(EguiContextPass, draw_log_overlay);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Debug)]
+struct LogLine {
+    level: Level,
+    target: String,
+    message: String,
+    logged_at: Instant,
+}
+
+// Passes every message on to the overlay.
+struct CaptureLayer(Arc<Mutex<VecDeque<LogLine>>>);
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        let line = LogLine {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: message.0,
+            logged_at: Instant::now(),
+        };
+        let Ok(mut lines) = self.0.lock() else { return };
+        if lines.len() == KEPT_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+// The message, followed by the event's other fields as `name=value`.
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// What the capture layer has seen, shared with it.
+#[derive(Resource, Default)]
+struct CapturedLog(Arc<Mutex<VecDeque<LogLine>>>);
+
+// Whether the overlay is open, and what it shows.
+#[derive(Resource)]
+struct LogOverlay {
+    open: bool,
+    // Whether each of `LEVELS` is shown.
+    shown: [bool; LEVELS.len()],
+    filter: String,
+}
+
+impl Default for LogOverlay {
+    fn default() -> Self {
+        Self {
+            open: false,
+            shown: [true; LEVELS.len()],
+            filter: String::new(),
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_log_overlay(keys: Res<ButtonInput<KeyCode>>, mut overlay: ResMut<LogOverlay>) {
+    if ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyL) {
+        overlay.open = !overlay.open;
+    }
+}
+
+fn draw_log_overlay(
+    mut contexts: EguiContexts,
+    captured: Res<CapturedLog>,
+    mut overlay: ResMut<LogOverlay>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    // Copied out, so nothing logged while drawing waits on the lock.
+    let lines: Vec<LogLine> = match captured.0.lock() {
+        Ok(lines) => lines.iter().cloned().collect(),
+        Err(_) => return,
+    };
+
+    if !overlay.open {
+        let now = Instant::now();
+        let alerts: Vec<_> = lines
+            .iter()
+            .filter(|line| line.level <= Level::WARN && now - line.logged_at < ALERT_TIME)
+            .collect();
+        if alerts.is_empty() {
+            return;
+        }
+        egui::Area::new(egui::Id::new("log_alerts"))
+            .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(8.0, -8.0))
+            .show(ctx, |ui| {
+                for line in alerts.iter().rev().take(ALERT_LINES).rev() {
+                    ui.colored_label(level_color(line.level), &line.message);
+                }
+            });
+        return;
+    }
+
+    let LogOverlay { open, shown, filter } = &mut *overlay;
+    egui::Window::new("Log").open(open).default_width(640.0).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            for (level, shown) in LEVELS.iter().zip(shown.iter_mut()) {
+                ui.checkbox(shown, level.as_str());
+            }
+            ui.label("Filter:");
+            ui.text_edit_singleline(filter);
+        });
+        ui.separator();
+        let filter = filter.to_lowercase();
+        let visible = lines.iter().filter(|line| {
+            let level = LEVELS.iter().position(|&level| level == line.level);
+            level.is_some_and(|level| shown[level])
+                && (filter.is_empty()
+                    || line.message.to_lowercase().contains(&filter)
+                    || line.target.to_lowercase().contains(&filter))
+        });
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            for line in visible {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(level_color(line.level), line.level.as_str());
+                    ui.weak(&line.target);
+                    ui.label(&line.message);
+                });
+            }
+        });
+    });
+}
+
+// --- HELPERS ---
+
+// Hands the log plugin the layer that feeds the overlay; see `LogPlugin::custom_layer`.
+pub fn capture_layer(app: &mut App) -> Option<BoxedLayer> {
+    let lines = Arc::new(Mutex::new(VecDeque::with_capacity(KEPT_LINES)));
+    app.insert_resource(CapturedLog(lines.clone()));
+    Some(Box::new(CaptureLayer(lines)))
+}
+
+fn level_color(level: Level) -> egui::Color32 {
+    match level {
+        Level::ERROR => egui::Color32::from_rgb(255, 90, 90),
+        Level::WARN => egui::Color32::from_rgb(255, 200, 80),
+        Level::INFO => egui::Color32::from_rgb(200, 200, 200),
+        _ => egui::Color32::from_rgb(140, 140, 140),
+    }
+}
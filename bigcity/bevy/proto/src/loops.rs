@@ -0,0 +1,191 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::player::PlayerInputSet;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{MaterialClass, WorldView};
+
+// --- CONSTANTS ---
+const BAND_COLOR: Color = Color::srgb(0.2, 1.0, 0.9);
+
+// --- PLUGIN ---
+pub struct LoopsPlugin;
+
+impl Plugin for LoopsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoopTool>()
+            .add_systems(Startup, spawn_loop_label)
+            .add_systems(
+                Update,
+                (place_loop_bands, draw_loop_bands, update_loop_label)
+                    .chain()
+                    .after(PlayerInputSet)
+                    .before(SimulationSet),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// A pair of horizontal strips over the same columns: powders and liquids that reach the bottom
+// strip reappear in the top one, so a waterfall can pour forever. Bands belong to the world
+// like zones do.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LoopBand {
+    // Inclusive column range shared by both strips.
+    pub columns: (i32, i32),
+    pub bottom: i32,
+    pub top: i32,
+}
+
+impl LoopBand {
+    fn touches(&self, cell: CellPos) -> bool {
+        (self.columns.0..=self.columns.1).contains(&cell.x)
+            && (cell.y == self.bottom || cell.y == self.top)
+    }
+}
+
+// Moves every powder or liquid cell in a band's bottom strip to the same column of its top strip,
+// if that cell is free. Particles keep their temperature, state and owner on the way.
+pub fn wrap_loop_bands(grid: &mut SimulationGrid) {
+    for band in grid.loops().to_vec() {
+        for x in band.columns.0..=band.columns.1 {
+            let Some(particle) = grid.get(x, band.bottom) else { continue };
+            if !matches!(particle.class(), MaterialClass::Powder | MaterialClass::Liquid) {
+                continue;
+            }
+            if grid.get(x, band.top) == Some(crate::Particle::Air) {
+                grid.swap_cells(CellPos::new(x, band.bottom), CellPos::new(x, band.top));
+            }
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// B marks, in turn, one end of the bottom strip, its other end and the row of the top strip.
+// Shift+B removes the band under the cursor.
+#[derive(Resource, Default)]
+struct LoopTool {
+    marks: Vec<CellPos>,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct LoopLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_loop_label(mut commands: Commands) {
+    commands.spawn((
+        LoopLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(43.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(BAND_COLOR),
+    ));
+}
+
+fn place_loop_bands(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    view: WorldView,
+    mut tool: ResMut<LoopTool>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    if keys.just_pressed(KeyCode::Escape) && !tool.marks.is_empty() {
+        tool.marks.clear();
+    }
+    // A level's bands are part of the puzzle. Ctrl+B drops rigid blocks (physics.rs).
+    if !keys.just_pressed(KeyCode::KeyB) || ctrl_held(&keys) || rules.protect_world {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        if let Some(index) = grid.loops().iter().rposition(|band| band.touches(cell)) {
+            grid.remove_loop(index);
+            info!("Removed the loop band under {:?}", cell);
+        }
+        return;
+    }
+
+    tool.marks.push(cell);
+    let &[start, end, top] = tool.marks.as_slice() else { return };
+    tool.marks.clear();
+    let band = LoopBand {
+        columns: (start.x.min(end.x), start.x.max(end.x)),
+        bottom: start.y,
+        top: top.y,
+    };
+    if band.top == band.bottom {
+        return;
+    }
+    info!("Added a loop band: {:?}", band);
+    grid.add_loop(band);
+}
+
+fn draw_loop_bands(
+    tool: Res<LoopTool>,
+    grid: Res<SimulationGrid>,
+    view: WorldView,
+    mut gizmos: Gizmos,
+) {
+    // Each strip is drawn along the middle of its row, joined by a faint line on the left.
+    let mut strip = |columns: (i32, i32), y: i32, color: Color| {
+        let left = view.cell_to_world(Vec2::new(columns.0 as f32, y as f32 + 0.5));
+        let right = view.cell_to_world(Vec2::new(columns.1 as f32 + 1.0, y as f32 + 0.5));
+        gizmos.line_2d(*left, *right, color);
+        *left
+    };
+    let mut links = Vec::new();
+    for band in grid.loops() {
+        let bottom = strip(band.columns, band.bottom, BAND_COLOR);
+        let top = strip(band.columns, band.top, BAND_COLOR);
+        links.push((bottom, top));
+    }
+
+    // The band being marked, up to the cursor.
+    match (tool.marks.as_slice(), view.cursor_cell()) {
+        (&[start], Some(cell)) => {
+            strip((start.x.min(cell.x), start.x.max(cell.x)), start.y, BAND_COLOR);
+        }
+        (&[start, end], Some(cell)) => {
+            let columns = (start.x.min(end.x), start.x.max(end.x));
+            let bottom = strip(columns, start.y, BAND_COLOR);
+            let top = strip(columns, cell.y, BAND_COLOR.with_alpha(0.5));
+            links.push((bottom, top));
+        }
+        _ => {}
+    }
+    for (bottom, top) in links {
+        gizmos.line_2d(bottom, top, BAND_COLOR.with_alpha(0.3));
+    }
+}
+
+fn update_loop_label(tool: Res<LoopTool>, mut q_label: Query<&mut Text, With<LoopLabel>>) {
+    if !tool.is_changed() {
+        return;
+    }
+    let Ok(mut label) = q_label.single_mut() else { return };
+    label.0 = match tool.marks.len() {
+        1 => "Loop band: press B at the other end of the bottom strip (Esc cancels)".to_string(),
+        2 => "Loop band: press B on the row of the top strip (Esc cancels)".to_string(),
+        _ => String::new(),
+    };
+}
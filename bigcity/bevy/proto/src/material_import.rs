@@ -0,0 +1,146 @@
+// --- IMPORTS ---
+use std::collections::HashMap;
+
+use crate::Particle;
+use crate::zones::{MaterialOverride, ParamOverrides};
+
+// --- CONSTANTS ---
+// The elements of a Noita materials file that define materials. A child starts from the
+// attributes of the material named in its `_parent` and changes some of them.
+const CELL_DATA: &str = "CellData";
+const CELL_DATA_CHILD: &str = "CellDataChild";
+// Noita's liquid gravity that counts as our normal gravity of 1.
+const NORMAL_LIQUID_GRAVITY: f32 = 0.5;
+// How far a liquid with no stiffness at all spreads per tick, in cells; a fully stiff one doesn't.
+const LOOSEST_DISPERSION: f32 = 4.0;
+
+// --- TYPES ---
+
+// What came of importing a materials file: overrides for the materials it matched, and the
+// names it had nothing for.
+pub struct MaterialImport {
+    pub overrides: Vec<MaterialOverride>,
+    pub skipped: Vec<String>,
+}
+
+// --- IMPORT ---
+
+// Reads the part of a Noita `materials.xml` that maps onto this game: every `CellData` (and
+// `CellDataChild`) whose name is one of our materials, like "sand" or "iron_powder", becomes a
+// material override. Its `liquid_gravity` sets the gravity, with 0.5 as normal, and its
+// `liquid_stiffness` the dispersion, from 4 cells a tick at 0 down to none at 1. Everything else
+// in the file (fire, graphics, reactions, materials we don't have) is left out.
+pub fn import_noita_materials(xml: &str) -> MaterialImport {
+    let mut defined: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut order = Vec::new();
+    for (element, mut attributes) in elements(xml) {
+        if element != CELL_DATA && element != CELL_DATA_CHILD {
+            continue;
+        }
+        if let Some(parent) = attributes.get("_parent").and_then(|name| defined.get(name)) {
+            let mut inherited = parent.clone();
+            inherited.extend(attributes);
+            attributes = inherited;
+        }
+        let Some(name) = attributes.get("name").cloned() else { continue };
+        if !defined.contains_key(&name) {
+            order.push(name.clone());
+        }
+        defined.insert(name, attributes);
+    }
+
+    let mut import = MaterialImport {
+        overrides: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for name in order {
+        let attributes = &defined[&name];
+        let number = |key: &str| attributes.get(key).and_then(|value| value.trim().parse().ok());
+        let overrides = ParamOverrides {
+            gravity: number("liquid_gravity").map(|g: f32| (g / NORMAL_LIQUID_GRAVITY).max(0.0)),
+            dispersion: number("liquid_stiffness")
+                .map(|s: f32| ((1.0 - s.clamp(0.0, 1.0)) * LOOSEST_DISPERSION).round() as u32),
+            ..Default::default()
+        };
+        match our_material(&name) {
+            Some(material) if overrides != ParamOverrides::default() => {
+                import.overrides.retain(|existing| existing.material != material);
+                import.overrides.push(MaterialOverride {
+                    material,
+                    overrides,
+                });
+            }
+            _ => import.skipped.push(name),
+        }
+    }
+    import
+}
+
+// --- HELPERS ---
+
+// Our material with the same name, ignoring case and underscores.
+fn our_material(name: &str) -> Option<Particle> {
+    let wanted = name.replace('_', "").to_ascii_lowercase();
+    Particle::ALL.into_iter().find(|particle| {
+        format!("{:?}", particle).to_ascii_lowercase() == wanted && *particle != Particle::Air
+    })
+}
+
+// Every start tag in the document, with its attributes. Comments are skipped; this is not a full
+// XML parser, only enough of one for attribute-style files like Noita's.
+fn elements(xml: &str) -> Vec<(String, HashMap<String, String>)> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        if let Some(comment) = rest.strip_prefix("!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        if rest.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let Some(end) = tag_end(rest) else { break };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        let name_end = tag.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(tag.len());
+        found.push((tag[..name_end].to_string(), attributes(&tag[name_end..])));
+    }
+    found
+}
+
+// Where the tag that `text` starts in ends, skipping any '>' inside quoted values.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn attributes(mut text: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    while let Some(equals) = text.find('=') {
+        let key = text[..equals].trim().to_string();
+        let value = text[equals + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else { break };
+        let Some(close) = value[1..].find(quote) else { break };
+        attributes.insert(key, unescape(&value[1..close + 1]));
+        text = &value[close + 2..];
+    }
+    attributes
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
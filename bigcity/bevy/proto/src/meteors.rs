@@ -0,0 +1,218 @@
+// --- IMPORTS ---
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::access::RayWalk;
+use crate::coords::CellPos;
+use crate::explosions::Explosion;
+use crate::follow::Followable;
+use crate::levels::PaintRules;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{MaterialClass, Particle, WORLD_UNITS_PER_CELL, WorldLayout};
+
+// --- CONSTANTS ---
+const METEOR_COLOR: Color = Color::srgb(1.0, 0.55, 0.15);
+// Sprite size, in cells.
+const METEOR_SIZE: f32 = 2.5;
+// In cells per second squared; meteors fall faster than they slow in air.
+const GRAVITY: f32 = 60.0;
+// Every loose cell a meteor ploughs through takes this fraction of its speed and this much heat
+// from it. Below this speed, in cells per second, it stops and strikes where it is.
+const PLOUGH_DRAG: f32 = 0.03;
+const PLOUGH_HEAT: f32 = 450.0;
+const MIN_SPEED: f32 = 20.0;
+// Dust in the way is flung ahead at this many cells per tick.
+const DUST_SPEED: f32 = 5.0;
+const BLAST_RADIUS: f32 = 10.0;
+// The payload fills this fraction of the crater, from its bottom, at this temperature.
+const PAYLOAD_FILL: f32 = 0.6;
+const PAYLOAD_HEAT: f32 = 300.0;
+
+// --- PLUGIN ---
+
+// Meteors fall in from above the world at an angle and plough through anything loose on the way,
+// heating it (so sand they pass through may melt into glass and water boil), packing snow into ice
+// and flinging dust ahead. Where they hit something solid, or once the ground has slowed them
+// down, they explode and leave their payload material in the bottom of the crater.
+pub struct MeteorsPlugin;
+
+impl Plugin for MeteorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Craters>().add_systems(
+            Update,
+            (
+                fly_meteors.before(SimulationSet),
+                fill_craters.after(SimulationSet),
+            ),
+        );
+    }
+}
+
+// --- RESOURCES ---
+
+// Impacts whose blast clears the crater this frame, to be filled with payload once it has.
+#[derive(Resource, Default)]
+struct Craters(Vec<(Vec2, Particle)>);
+
+// A meteor's blast, and the crater it leaves to be filled.
+#[derive(SystemParam)]
+struct Impacts<'w> {
+    craters: ResMut<'w, Craters>,
+    explosions: EventWriter<'w, Explosion>,
+}
+
+impl Impacts<'_> {
+    fn strike(&mut self, point: Vec2, payload: Particle) {
+        self.explosions.write(Explosion {
+            center: point,
+            radius: BLAST_RADIUS,
+        });
+        self.craters.0.push((point, payload));
+    }
+}
+
+// --- COMPONENTS ---
+
+// A meteor in flight. Positions are in cells, velocities in cells per second.
+#[derive(Component)]
+struct Meteor {
+    position: Vec2,
+    velocity: Vec2,
+    payload: Particle,
+}
+
+// --- SYSTEMS ---
+
+fn fly_meteors(
+    mut commands: Commands,
+    time: Res<Time>,
+    rules: Res<PaintRules>,
+    layout: Res<WorldLayout>,
+    mut grid: ResMut<SimulationGrid>,
+    mut impacts: Impacts,
+    mut q_meteors: Query<(Entity, &mut Meteor, &mut Transform)>,
+) {
+    let dt = time.delta_secs();
+    for (entity, mut meteor, mut transform) in &mut q_meteors {
+        let velocity = meteor.velocity + Vec2::NEG_Y * GRAVITY * dt;
+        let next = meteor.position + velocity * dt;
+        let distance = meteor.position.distance(next);
+        let path: Vec<_> =
+            RayWalk::new(&grid, meteor.position, next - meteor.position, distance).collect();
+
+        let mut speed = velocity.length();
+        let mut impact = None;
+        for step in path {
+            let (x, y) = (step.cell.x, step.cell.y);
+            match step.particle.class() {
+                MaterialClass::Gas => continue,
+                MaterialClass::Solid => impact = Some(step.point),
+                MaterialClass::Powder | MaterialClass::Liquid => {
+                    // Like blasts, meteors in a level leave the level's own cells alone.
+                    if !rules.protect_world || grid.is_placed(x, y) {
+                        plough(&mut grid, x, y, step.particle, velocity);
+                    }
+                    speed *= 1.0 - PLOUGH_DRAG;
+                    if speed < MIN_SPEED {
+                        impact = Some(step.point);
+                    }
+                }
+            }
+            if impact.is_some() {
+                break;
+            }
+        }
+
+        if let Some(point) = impact {
+            impacts.strike(point, meteor.payload);
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // Meteors that leave the sides or the bottom of the world are gone; they only ever come
+        // in from above.
+        if next.x < 0.0 || next.y < 0.0 || next.x >= grid.width() as f32 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        meteor.velocity = velocity.normalize_or_zero() * speed;
+        meteor.position = next;
+        transform.translation = layout.cell_to_world(next).extend(transform.translation.z);
+    }
+}
+
+// Lines the bottom of every fresh crater with its meteor's payload, still hot.
+fn fill_craters(
+    rules: Res<PaintRules>,
+    mut craters: ResMut<Craters>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    if craters.0.is_empty() {
+        return;
+    }
+    for (center, payload) in craters.0.drain(..) {
+        let radius = BLAST_RADIUS * PAYLOAD_FILL;
+        // The payload sits in the lower part of the crater, touching its floor.
+        let middle = center - Vec2::Y * (BLAST_RADIUS - radius);
+        let min = (middle - Vec2::splat(radius)).floor().as_ivec2();
+        let max = (middle + Vec2::splat(radius)).ceil().as_ivec2();
+        let mut filled = 0;
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let cell = CellPos::new(x, y).center();
+                if cell.distance(middle) > radius || grid.get(x, y) != Some(Particle::Air) {
+                    continue;
+                }
+                // Payloads in a level count as placed, so they can't overwrite the level's cells
+                // but can be cleared again.
+                let placed = if rules.protect_world {
+                    grid.place(x, y, payload, 0)
+                } else {
+                    grid.set(x, y, payload)
+                };
+                if placed {
+                    grid.add_heat(x, y, PAYLOAD_HEAT);
+                    filled += 1;
+                }
+            }
+        }
+        info!("Meteor crater at {:?} holds {} cells of {:?}", center.floor(), filled, payload);
+    }
+}
+
+// --- HELPERS ---
+
+// Sets a meteor carrying `payload` falling from `position` (in cells) with `velocity` (in cells
+// per second).
+pub fn spawn_meteor(
+    commands: &mut Commands,
+    layout: &WorldLayout,
+    position: Vec2,
+    velocity: Vec2,
+    payload: Particle,
+) {
+    commands.spawn((
+        Meteor {
+            position,
+            velocity,
+            payload,
+        },
+        Followable,
+        Sprite::from_color(METEOR_COLOR, Vec2::splat(METEOR_SIZE * WORLD_UNITS_PER_CELL)),
+        Transform::from_translation(layout.cell_to_world(position).extend(1.0)),
+    ));
+}
+
+// What a meteor does to a loose cell in its way.
+fn plough(grid: &mut SimulationGrid, x: i32, y: i32, particle: Particle, velocity: Vec2) {
+    match particle {
+        Particle::Snow => {
+            grid.set(x, y, Particle::Ice);
+        }
+        Particle::Dust => {
+            let ahead = velocity.normalize_or(Vec2::NEG_Y) * DUST_SPEED;
+            grid.launch(x, y, ahead.round().as_ivec2());
+        }
+        _ => {}
+    }
+    grid.add_heat(x, y, PLOUGH_HEAT);
+}
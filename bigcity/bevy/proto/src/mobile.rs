@@ -0,0 +1,82 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy::window::{AppLifecycle, PrimaryWindow};
+use bevy_egui::{EguiContextSettings, EguiInput, EguiPreUpdateSet, egui};
+
+use crate::sim::{SimulationGrid, SimulationSet};
+
+// --- CONSTANTS ---
+// How far the UI keeps from the left, top, right and bottom edges of the screen, in logical
+// pixels, to stay clear of notches, rounded corners and the home indicator. Winit doesn't report
+// the real safe area, so this clears the usual phones held either way round.
+const SAFE_AREA: [f32; 4] = [44.0, 24.0, 44.0, 24.0];
+
+// --- PLUGIN ---
+
+// Phones and tablets, with `--features mobile` (built without the default `dynamic_linking`
+// feature, which mobile targets don't support). When the system sends the app to the background
+// the world stops, and picks up where it left off when it comes back; Bevy drops the window's
+// surface while suspended and makes a new one on resume, and the world's texture is uploaded again
+// from the grid then, in case the driver lost it. Panels and windows stay inside the screen's safe
+// area. Touch painting and pinching come with every touch build (see `TOUCH_INPUT`).
+pub struct MobilePlugin;
+
+impl Plugin for MobilePlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(Update, SimulationSet.run_if(not(resource_exists::<Backgrounded>)))
+            .configure_sets(FixedUpdate, SimulationSet.run_if(not(resource_exists::<Backgrounded>)))
+            .add_systems(
+                PreUpdate,
+                keep_ui_in_safe_area
+                    .after(EguiPreUpdateSet::InitContexts)
+                    .before(EguiPreUpdateSet::ProcessInput),
+            )
+            .add_systems(Update, follow_lifecycle);
+    }
+}
+
+// --- RESOURCES ---
+
+// Present while the system has the app in the background.
+#[derive(Resource)]
+struct Backgrounded;
+
+// --- SYSTEMS ---
+
+fn follow_lifecycle(
+    mut commands: Commands,
+    mut lifecycle: EventReader<AppLifecycle>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    for event in lifecycle.read() {
+        match event {
+            AppLifecycle::WillSuspend | AppLifecycle::Suspended => {
+                commands.insert_resource(Backgrounded);
+                info!("App suspended");
+            }
+            AppLifecycle::WillResume | AppLifecycle::Running => {
+                commands.remove_resource::<Backgrounded>();
+                // Marking the grid changed has the display upload the whole world again.
+                grid.set_changed();
+                info!("App resumed");
+            }
+            AppLifecycle::Idle => {}
+        }
+    }
+}
+
+// Shrinks the screen egui lays itself out in to the safe area, so windows and panels anchored
+// to the edges stay clear of the notch.
+fn keep_ui_in_safe_area(
+    mut q_egui: Query<(&mut EguiInput, &EguiContextSettings), With<PrimaryWindow>>,
+) {
+    for (mut input, settings) in &mut q_egui {
+        let Some(screen) = input.screen_rect else { continue };
+        let [left, top, right, bottom] = SAFE_AREA.map(|inset| inset / settings.scale_factor);
+        let min = screen.min + egui::vec2(left, top);
+        let max = screen.max - egui::vec2(right, bottom);
+        if min.x < max.x && min.y < max.y {
+            input.screen_rect = Some(egui::Rect::from_min_max(min, max));
+        }
+    }
+}
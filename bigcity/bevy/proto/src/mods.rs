@@ -0,0 +1,302 @@
+// --- IMPORTS ---
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::asset::io::{AssetReader, AssetReaderError, AssetSource, PathStream, VecReader};
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::stream;
+
+use crate::persist::user_data_dir;
+
+// --- CONSTANTS ---
+// The asset source mod content is loaded from, as in "mods://levels".
+const MODS_SOURCE: &str = "mods";
+// ..which reads this folder of the user data directory.
+const MODS_FOLDER: &str = "mods";
+const ARCHIVE_EXTENSION: &str = "zip";
+
+// ZIP record signatures and the fixed sizes of the records read here.
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const END_OF_DIRECTORY_SIZE: usize = 22;
+const DIRECTORY_ENTRY_SIZE: usize = 46;
+const LOCAL_HEADER_SIZE: usize = 30;
+// The end record sits within its own size plus the longest possible comment of the file's end.
+const END_OF_DIRECTORY_SEARCH: u64 = END_OF_DIRECTORY_SIZE as u64 + u16::MAX as u64;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+// --- PLUGIN ---
+
+// Mods live in the `mods` folder of the user data directory, either as loose files or as `.zip`
+// archives laid out the same way, which are read in place without extracting them. Both are served
+// through the "mods" asset source, so content loads from "mods://levels" just like it loads from
+// "levels". Archives are indexed when the game starts. The source is registered on the asset
+// plugin, so this plugin has to be added before `DefaultPlugins`.
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_asset_source(
+            MODS_SOURCE,
+            AssetSource::build().with_reader(|| {
+                let root = user_data_dir().map(|dir| dir.join(MODS_FOLDER));
+                Box::new(ModsReader::new(root.as_deref()))
+            }),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// Where the bytes of a mod file are.
+#[derive(Debug, Clone)]
+enum ModFile {
+    Loose(PathBuf),
+    Archived(ArchivedFile),
+}
+
+#[derive(Debug, Clone)]
+struct ArchivedFile {
+    archive: Arc<PathBuf>,
+    // Where its local header starts in the archive.
+    offset: u64,
+    method: u16,
+    compressed_size: u64,
+}
+
+// Every file in the mods folder, loose or archived, by its path in the mods source. A loose file
+// wins over an archived one with the same path, and an earlier archive (by name) over a later one.
+#[derive(Default)]
+struct ModsReader {
+    files: BTreeMap<PathBuf, ModFile>,
+    directories: BTreeSet<PathBuf>,
+}
+
+impl ModsReader {
+    fn new(root: Option<&Path>) -> Self {
+        let mut reader = Self::default();
+        let Some(root) = root else { return reader };
+        let mut archives = Vec::new();
+        reader.add_loose(root, Path::new(""), &mut archives);
+        archives.sort();
+        for archive in archives {
+            match read_archive_directory(&archive) {
+                Ok(entries) => reader.add_archive(archive, entries),
+                Err(err) => warn!("Ignoring unreadable mod archive {:?}: {}", archive, err),
+            }
+        }
+        reader
+    }
+
+    // Indexes the loose files under `root/folder`, collecting the archives in `root` itself.
+    fn add_loose(&mut self, root: &Path, folder: &Path, archives: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(root.join(folder)) else { return };
+        for entry in entries.filter_map(Result::ok) {
+            let path = folder.join(entry.file_name());
+            let full = entry.path();
+            if full.is_dir() {
+                self.add_directory(&path);
+                self.add_loose(root, &path, archives);
+            } else if folder.as_os_str().is_empty() && has_extension(&path, ARCHIVE_EXTENSION) {
+                archives.push(full);
+            } else if !has_extension(&path, "meta") {
+                self.add_directory(folder);
+                self.files.insert(path, ModFile::Loose(full));
+            }
+        }
+    }
+
+    fn add_archive(&mut self, archive: PathBuf, entries: Vec<(String, ArchivedFile)>) {
+        // Zipping a folder usually puts everything inside a folder named after the archive; that
+        // one is skipped so the archive's `levels` lines up with the mods folder's.
+        let stem = archive.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        let wrapper = format!("{}/", stem);
+        let wrapped = entries.iter().all(|(name, _)| name.starts_with(&wrapper));
+        for (name, file) in entries {
+            let name = if wrapped { &name[wrapper.len()..] } else { &name[..] };
+            let path = PathBuf::from(name);
+            if name.is_empty() || name.ends_with('/') || has_extension(&path, "meta") {
+                continue;
+            }
+            if let Some(folder) = path.parent() {
+                self.add_directory(folder);
+            }
+            self.files.entry(path).or_insert(ModFile::Archived(file));
+        }
+    }
+
+    // Records `folder` and every folder above it.
+    fn add_directory(&mut self, folder: &Path) {
+        for ancestor in folder.ancestors().filter(|a| !a.as_os_str().is_empty()) {
+            self.directories.insert(ancestor.to_path_buf());
+        }
+    }
+
+    fn bytes(&self, path: &Path) -> Result<Vec<u8>, AssetReaderError> {
+        let file = self.files.get(path).ok_or_else(|| AssetReaderError::NotFound(path.into()));
+        let bytes = match file? {
+            ModFile::Loose(full) => std::fs::read(full),
+            ModFile::Archived(file) => read_archived(file),
+        };
+        bytes.map_err(|err| AssetReaderError::Io(Arc::new(err)))
+    }
+}
+
+impl AssetReader for ModsReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<VecReader, AssetReaderError> {
+        self.bytes(path).map(VecReader::new)
+    }
+
+    // Mods carry no .meta files; assets load with their loader's default settings.
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<VecReader, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.into()))
+    }
+
+    async fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<Box<PathStream>, AssetReaderError> {
+        if !path.as_os_str().is_empty() && !self.directories.contains(path) {
+            return Err(AssetReaderError::NotFound(path.into()));
+        }
+        let is_child = |child: &&PathBuf| child.parent() == Some(path);
+        let children: Vec<PathBuf> = (self.directories.iter().filter(is_child))
+            .chain(self.files.keys().filter(is_child))
+            .cloned()
+            .collect();
+        Ok(Box::new(stream::iter(children)))
+    }
+
+    // A folder no mod provides counts as a plain (missing) file, so loading it as a folder gives an
+    // empty folder rather than an error for everyone without mods.
+    async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+        Ok(path.as_os_str().is_empty() || self.directories.contains(path))
+    }
+}
+
+// --- HELPERS ---
+
+// `folder` of the mods source, for loading mod content next to the bundled one.
+pub fn mod_folder(folder: &str) -> String {
+    format!("{}://{}", MODS_SOURCE, folder)
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+// The files an archive holds, from its central directory. Only plain ZIP archives are read: no
+// ZIP64 (over 4 GiB or 65535 files), no encryption, and files stored or deflated.
+fn read_archive_directory(archive: &Path) -> std::io::Result<Vec<(String, ArchivedFile)>> {
+    let mut file = File::open(archive)?;
+    let length = file.seek(SeekFrom::End(0))?;
+    let tail_start = length.saturating_sub(END_OF_DIRECTORY_SEARCH);
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(tail_start))?;
+    file.read_to_end(&mut tail)?;
+    if tail.len() < END_OF_DIRECTORY_SIZE {
+        return Err(invalid("not a ZIP archive"));
+    }
+    let end = (0..=tail.len() - END_OF_DIRECTORY_SIZE)
+        .rev()
+        .find(|&at| u32_at(&tail, at) == END_OF_DIRECTORY)
+        .ok_or_else(|| invalid("not a ZIP archive"))?;
+    let count = u16_at(&tail, end + 10) as usize;
+    let directory_size = u32_at(&tail, end + 12) as usize;
+    let directory_offset = u32_at(&tail, end + 16) as u64;
+    // A directory reaching past the end of the file is damaged, and not worth allocating for.
+    if directory_offset + directory_size as u64 > length {
+        return Err(invalid("damaged central directory"));
+    }
+
+    let mut directory = vec![0; directory_size];
+    file.seek(SeekFrom::Start(directory_offset))?;
+    file.read_exact(&mut directory)?;
+
+    let archive = Arc::new(archive.to_path_buf());
+    let mut entries = Vec::with_capacity(count);
+    let mut at = 0;
+    for _ in 0..count {
+        if at + DIRECTORY_ENTRY_SIZE > directory.len() || u32_at(&directory, at) != DIRECTORY_ENTRY
+        {
+            return Err(invalid("damaged central directory"));
+        }
+        let name_length = u16_at(&directory, at + 28) as usize;
+        let extra_length = u16_at(&directory, at + 30) as usize;
+        let comment_length = u16_at(&directory, at + 32) as usize;
+        let name_start = at + DIRECTORY_ENTRY_SIZE;
+        let name = directory
+            .get(name_start..name_start + name_length)
+            .ok_or_else(|| invalid("damaged central directory"))?;
+        entries.push((
+            String::from_utf8_lossy(name).replace('\\', "/"),
+            ArchivedFile {
+                archive: archive.clone(),
+                offset: u32_at(&directory, at + 42) as u64,
+                method: u16_at(&directory, at + 10),
+                compressed_size: u32_at(&directory, at + 20) as u64,
+            },
+        ));
+        at = name_start + name_length + extra_length + comment_length;
+    }
+    Ok(entries)
+}
+
+fn read_archived(file: &ArchivedFile) -> std::io::Result<Vec<u8>> {
+    let mut archive = File::open(file.archive.as_path())?;
+    let mut header = [0; LOCAL_HEADER_SIZE];
+    archive.seek(SeekFrom::Start(file.offset))?;
+    archive.read_exact(&mut header)?;
+    if u32_at(&header, 0) != LOCAL_HEADER {
+        return Err(invalid("damaged archive"));
+    }
+    // The local header repeats the name and may carry a different extra field.
+    let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+    archive.seek(SeekFrom::Current(skip))?;
+    let mut data = archive.take(file.compressed_size);
+    let mut bytes = Vec::new();
+    match file.method {
+        STORED => data.read_to_end(&mut bytes)?,
+        DEFLATED => flate2::read::DeflateDecoder::new(data).read_to_end(&mut bytes)?,
+        _ => return Err(invalid("compressed with an unsupported method")),
+    };
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Scratch;
+
+    #[test]
+    fn short_archives_are_refused_rather_than_read() {
+        let scratch = Scratch::new("mods-short-archives");
+        let mut lying = END_OF_DIRECTORY.to_le_bytes().to_vec();
+        lying.extend([0; 8]);
+        // An end of directory claiming a directory far bigger than the file.
+        let mut huge = END_OF_DIRECTORY.to_le_bytes().to_vec();
+        huge.extend([0, 0, 0, 0, 1, 0, 1, 0, 0xFF, 0xFF, 0xFF, 0x7F, 0, 0, 0, 0, 0, 0]);
+        for (name, bytes) in [("empty.zip", vec![]), ("lying.zip", lying), ("huge.zip", huge)] {
+            let path = scratch.path(name);
+            std::fs::write(&path, bytes).unwrap();
+            assert!(read_archive_directory(&path).is_err(), "{} was read", name);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// --- IMPORTS ---
+use bevy::input::InputSystem;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseWheel};
+use bevy::input::touch::TouchInput;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiPostUpdateSet, EguiRenderOutput};
+
+use crate::bookmarks::Bookmarks;
+use crate::player::{PlayerCursor, PlayerInputSet};
+use crate::{ScreenCamera, WorldLayout};
+
+// --- CONSTANTS ---
+// How long the tour takes to glide from one bookmark to the next, and how long it stays there.
+const GLIDE_SECS: f32 = 4.0;
+const HOLD_SECS: f32 = 8.0;
+
+// --- PLUGIN ---
+
+// Museum mode, for showing a finished world on a kiosk or during a stream's intermission: the
+// world keeps running, but every key, click, scroll, touch and dropped file is swallowed before
+// anything sees it, players' brushes and cursors go away and the HUD, panels included, is hidden.
+// Ctrl+M starts it, or Ctrl+Shift+M with a guided tour that glides the camera from one of the
+// world's bookmarks to the next, in slot order and round and round. `--museum` and
+// `--museum-tour` start the game that way. Ctrl+M is the only key still heard, and leaves again.
+pub struct MuseumPlugin;
+
+impl Plugin for MuseumPlugin {
+    fn build(&self, app: &mut App) {
+        let mut museum = None;
+        for arg in std::env::args().skip(1) {
+            match arg.as_str() {
+                "--museum" => museum = Some(Museum::new(false)),
+                "--museum-tour" => museum = Some(Museum::new(true)),
+                _ => {}
+            }
+        }
+        if let Some(museum) = museum {
+            app.insert_resource(museum);
+        }
+
+        let open = resource_exists::<Museum>;
+        app.configure_sets(Update, PlayerInputSet.run_if(not(open)))
+            .add_systems(PreUpdate, swallow_input.before(InputSystem).run_if(open))
+            .add_systems(
+                Update,
+                (
+                    enter_museum.run_if(not(open)),
+                    (leave_museum, hide_hud).chain().run_if(open),
+                ),
+            )
+            .add_systems(
+                PostUpdate,
+                (
+                    hide_panels
+                        .after(EguiPostUpdateSet::ProcessOutput)
+                        .before(EguiPostUpdateSet::PostProcessOutput),
+                    play_tour.before(TransformSystem::TransformPropagate),
+                )
+                    .run_if(open),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// Where the tour is: gliding from `from` towards the bookmark at `stop` among the set ones, then
+// holding there.
+#[derive(Default)]
+struct Tour {
+    stop: usize,
+    clock: f32,
+    // The camera's center in world space and its orthographic scale when the glide began.
+    from: Option<(Vec2, f32)>,
+}
+
+// --- RESOURCES ---
+
+// Present while museum mode is on.
+#[derive(Resource)]
+struct Museum {
+    tour: Option<Tour>,
+    // Whether Ctrl is held, going by the key events swallowed so far.
+    ctrl: bool,
+    leaving: bool,
+    // The HUD nodes hidden, with the visibility each had, to give back on leaving.
+    hidden: Vec<(Entity, Visibility)>,
+}
+
+impl Museum {
+    fn new(tour: bool) -> Self {
+        Self {
+            tour: tour.then(Tour::default),
+            ctrl: false,
+            leaving: false,
+            hidden: Vec::new(),
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn enter_museum(
+    mut commands: Commands,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut mouse: ResMut<ButtonInput<MouseButton>>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if !(ctrl && keys.just_pressed(KeyCode::KeyM)) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // Nothing held down now is let go of as far as the rest of the game can tell.
+    keys.reset_all();
+    mouse.reset_all();
+    commands.insert_resource(Museum {
+        ctrl: true,
+        ..Museum::new(shift)
+    });
+    info!("Museum mode{} (Ctrl+M leaves)", if shift { " with a tour" } else { "" });
+}
+
+// Empties the input events before Bevy and egui read them, watching only for Ctrl+M.
+fn swallow_input(
+    mut museum: ResMut<Museum>,
+    mut keyboard: ResMut<Events<KeyboardInput>>,
+    mut buttons: ResMut<Events<MouseButtonInput>>,
+    mut wheel: ResMut<Events<MouseWheel>>,
+    mut touches: ResMut<Events<TouchInput>>,
+    mut drops: ResMut<Events<FileDragAndDrop>>,
+) {
+    for event in keyboard.drain() {
+        match event.key_code {
+            KeyCode::ControlLeft | KeyCode::ControlRight => museum.ctrl = event.state.is_pressed(),
+            KeyCode::KeyM if museum.ctrl && event.state.is_pressed() => museum.leaving = true,
+            _ => {}
+        }
+    }
+    buttons.clear();
+    wheel.clear();
+    touches.clear();
+    drops.clear();
+}
+
+fn leave_museum(
+    mut commands: Commands,
+    museum: Res<Museum>,
+    mut q_visibility: Query<&mut Visibility>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !museum.leaving {
+        return;
+    }
+    for &(entity, visibility) in &museum.hidden {
+        if let Ok(mut hidden) = q_visibility.get_mut(entity) {
+            *hidden = visibility;
+        }
+    }
+    if let Ok(mut window) = q_window.single_mut() {
+        window.cursor_options.visible = true;
+    }
+    commands.remove_resource::<Museum>();
+    info!("Left museum mode");
+}
+
+// Hides every top-level UI node, including ones that show up later, the OS cursor and the brushes
+// players were painting with.
+fn hide_hud(
+    mut museum: ResMut<Museum>,
+    mut q_nodes: Query<(Entity, &mut Visibility, Has<ChildOf>), With<Node>>,
+    mut q_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut q_cursors: Query<&mut PlayerCursor>,
+) {
+    if museum.leaving {
+        return;
+    }
+    for (entity, mut visibility, child) in &mut q_nodes {
+        if child || *visibility == Visibility::Hidden {
+            continue;
+        }
+        // A node that shows itself again keeps the visibility it had when it was first hidden.
+        if !museum.hidden.iter().any(|&(hidden, _)| hidden == entity) {
+            museum.hidden.push((entity, *visibility));
+        }
+        *visibility = Visibility::Hidden;
+    }
+    if let Ok(mut window) = q_window.single_mut() {
+        window.cursor_options.visible = false;
+    }
+    for mut cursor in &mut q_cursors {
+        cursor.position = None;
+        cursor.painting = false;
+        cursor.stroke = None;
+    }
+}
+
+// Throws away what egui drew this frame, so its windows and panels don't show.
+fn hide_panels(mut q_outputs: Query<&mut EguiRenderOutput>) {
+    for mut output in &mut q_outputs {
+        output.paint_jobs = Default::default();
+    }
+}
+
+fn play_tour(
+    time: Res<Time<Real>>,
+    layout: Res<WorldLayout>,
+    bookmarks: Res<Bookmarks>,
+    mut museum: ResMut<Museum>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let Some(tour) = &mut museum.tour else { return };
+    let stops: Vec<_> = bookmarks.slots.iter().flatten().collect();
+    if stops.is_empty() {
+        return;
+    }
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+
+    let stop = stops[tour.stop % stops.len()];
+    let to = (*layout.cell_to_world(Vec2::from(stop.center)), stop.zoom);
+    let from = *tour.from.get_or_insert((transform.translation.truncate(), ortho.scale));
+    tour.clock += time.delta_secs();
+    let t = (tour.clock / GLIDE_SECS).clamp(0.0, 1.0);
+    let eased = t * t * (3.0 - 2.0 * t);
+    transform.translation = from.0.lerp(to.0, eased).extend(transform.translation.z);
+    // Zooming by the same factor every moment feels steadier than by the same amount.
+    ortho.scale = from.1 * (to.1 / from.1).powf(eased);
+
+    if tour.clock >= GLIDE_SECS + HOLD_SECS {
+        tour.stop = (tour.stop + 1) % stops.len();
+        tour.clock = 0.0;
+        tour.from = Some(to);
+    }
+}
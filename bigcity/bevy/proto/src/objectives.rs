@@ -0,0 +1,347 @@
+// --- IMPORTS ---
+use std::collections::HashMap;
+
+use bevy::asset::LoadedFolder;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::events::SimEvent;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::{load_user_ron, save_user_ron};
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{Reaction, SimulationSet, SimulationStats};
+
+// --- CONSTANTS ---
+pub const OBJECTIVE_FOLDER: &str = "objectives";
+const PROGRESS_FILE: &str = "objectives.ron";
+// Progress is flushed to disk at most this often, and whenever an objective completes.
+const SAVE_INTERVAL_SECS: f32 = 10.0;
+const TOAST_SECS: f32 = 3.0;
+
+// --- PLUGIN ---
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ObjectiveSet>()
+            .register_asset_loader(RonAssetLoader::<ObjectiveSet>::new(&["objectives.ron"]))
+            .add_event::<ObjectiveCompleted>()
+            .insert_resource(load_user_ron::<ObjectiveProgress>(PROGRESS_FILE).unwrap_or_default())
+            .add_systems(Startup, (load_objectives, spawn_objective_panel, spawn_toast))
+            .add_systems(
+                Update,
+                (
+                    evaluate_objectives,
+                    announce_completions,
+                    save_objective_progress,
+                    toggle_objective_panel,
+                    update_objective_panel,
+                )
+                    .chain()
+                    .after(SimulationSet),
+            );
+    }
+}
+
+// --- ASSETS ---
+
+// A list of objectives loaded from `objectives/*.objectives.ron`.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct ObjectiveSet {
+    pub objectives: Vec<Objective>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Objective {
+    // Stable key for saved progress; renaming it resets the objective.
+    pub id: String,
+    pub title: String,
+    pub goal: ObjectiveGoal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum ObjectiveGoal {
+    // Paint `cells` cells in total (of `particle`, if given), accumulated across sessions.
+    Paint { particle: Option<Particle>, cells: u32 },
+    // Have at least `at_least` cells of `particle` in the world at the same time.
+    Population { particle: Particle, at_least: u32 },
+    // Keep that population up for `ticks` consecutive simulation ticks.
+    Sustain {
+        particle: Particle,
+        at_least: u32,
+        ticks: u32,
+    },
+    // Have `reaction` change `cells` cells in total, accumulated across sessions: boil 1000 water
+    // cells, melt sand into glass.
+    React { reaction: Reaction, cells: u32 },
+}
+
+impl ObjectiveGoal {
+    fn target(&self) -> u32 {
+        match self {
+            ObjectiveGoal::Paint { cells, .. } => *cells,
+            ObjectiveGoal::Population { at_least, .. } => *at_least,
+            ObjectiveGoal::Sustain { ticks, .. } => *ticks,
+            ObjectiveGoal::React { cells, .. } => *cells,
+        }
+    }
+}
+
+// --- EVENTS ---
+
+#[derive(Event, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct ObjectiveCompleted {
+    pub id: String,
+    pub title: String,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct ObjectiveLibrary(Handle<LoadedFolder>);
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub struct ObjectiveState {
+    pub progress: u32,
+    pub completed: bool,
+}
+
+// Progress for every objective ever seen, keyed by objective id and persisted in the user's
+// data directory so it survives restarts.
+#[derive(Resource, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ObjectiveProgress {
+    entries: HashMap<String, ObjectiveState>,
+    #[serde(skip)]
+    dirty: bool,
+    #[serde(skip)]
+    since_save: f32,
+}
+
+impl ObjectiveProgress {
+    pub fn get(&self, id: &str) -> Option<&ObjectiveState> {
+        self.entries.get(id)
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct ObjectivePanel;
+
+// Briefly announces a completed objective at the top of the screen.
+#[derive(Component, Default)]
+struct ObjectiveToast {
+    remaining: f32,
+}
+
+// --- SYSTEMS ---
+
+fn load_objectives(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ObjectiveLibrary(asset_server.load_folder(OBJECTIVE_FOLDER)));
+}
+
+fn spawn_objective_panel(mut commands: Commands) {
+    commands.spawn((
+        ObjectivePanel,
+        Name::new("objective_panel"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            left: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn spawn_toast(mut commands: Commands) {
+    commands.spawn((
+        ObjectiveToast::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::srgb(1.0, 0.85, 0.2)),
+    ));
+}
+
+// Iterates every loaded objective across all objective files.
+fn loaded_objectives<'a>(
+    library: &ObjectiveLibrary,
+    folders: &'a Assets<LoadedFolder>,
+    sets: &'a Assets<ObjectiveSet>,
+) -> impl Iterator<Item = &'a Objective> {
+    folders
+        .get(&library.0)
+        .into_iter()
+        .flat_map(|folder| folder.handles.iter())
+        .filter_map(|h| sets.get(h.id().try_typed::<ObjectiveSet>().ok()?))
+        .flat_map(|set| set.objectives.iter())
+}
+
+fn evaluate_objectives(
+    library: Res<ObjectiveLibrary>,
+    folders: Res<Assets<LoadedFolder>>,
+    sets: Res<Assets<ObjectiveSet>>,
+    stats: Res<SimulationStats>,
+    mut progress: ResMut<ObjectiveProgress>,
+    mut events: EventReader<SimEvent>,
+    mut completions: EventWriter<ObjectiveCompleted>,
+) {
+    let events: Vec<SimEvent> = events.read().cloned().collect();
+    // Leaves `progress` untouched (and not dirty) on frames where nothing moves.
+    let progress = progress.bypass_change_detection();
+
+    for objective in loaded_objectives(&library, &folders, &sets) {
+        let state = progress.entries.entry(objective.id.clone()).or_default();
+        if state.completed {
+            continue;
+        }
+
+        let before = state.progress;
+        state.progress = match &objective.goal {
+            ObjectiveGoal::Paint { particle: want, .. } => {
+                let painted: u32 = events
+                    .iter()
+                    .map(|event| match event {
+                        SimEvent::Painted { particle, cells, .. }
+                            if want.is_none_or(|want| want == *particle) =>
+                        {
+                            *cells
+                        }
+                        _ => 0,
+                    })
+                    .sum();
+                state.progress.saturating_add(painted)
+            }
+            ObjectiveGoal::Population { particle, .. } => stats.count(*particle),
+            // Counted in simulation ticks, so a frame with no ticks (paused) doesn't count.
+            ObjectiveGoal::Sustain { particle, at_least, .. } => {
+                if stats.count(*particle) >= *at_least {
+                    state.progress.saturating_add(stats.ticks_last_frame)
+                } else {
+                    0
+                }
+            }
+            ObjectiveGoal::React { reaction: want, .. } => {
+                let reacted: u32 = events
+                    .iter()
+                    .map(|event| match event {
+                        SimEvent::Reacted { reaction, cells } if reaction == want => *cells,
+                        _ => 0,
+                    })
+                    .sum();
+                state.progress.saturating_add(reacted)
+            }
+        };
+
+        // Population goals track a live value; only persist real progress.
+        if state.progress > before {
+            progress.dirty = true;
+        }
+        if state.progress >= objective.goal.target() {
+            state.completed = true;
+            completions.write(ObjectiveCompleted {
+                id: objective.id.clone(),
+                title: objective.title.clone(),
+            });
+            // Completion is worth saving right away.
+            progress.dirty = true;
+            progress.since_save = SAVE_INTERVAL_SECS;
+        }
+    }
+}
+
+fn announce_completions(
+    time: Res<Time>,
+    mut completions: EventReader<ObjectiveCompleted>,
+    mut q_toast: Query<(&mut ObjectiveToast, &mut Text)>,
+) {
+    let Ok((mut toast, mut text)) = q_toast.single_mut() else { return };
+
+    for completion in completions.read() {
+        info!("Objective complete: {} ({})", completion.title, completion.id);
+        text.0 = format!("Objective complete: {}", completion.title);
+        toast.remaining = TOAST_SECS;
+    }
+
+    if toast.remaining > 0.0 {
+        toast.remaining -= time.delta_secs();
+        if toast.remaining <= 0.0 {
+            text.0.clear();
+        }
+    }
+}
+
+fn save_objective_progress(time: Res<Time>, mut progress: ResMut<ObjectiveProgress>) {
+    let progress = progress.bypass_change_detection();
+    progress.since_save += time.delta_secs();
+    if progress.dirty && progress.since_save >= SAVE_INTERVAL_SECS {
+        save_user_ron(PROGRESS_FILE, progress);
+        progress.dirty = false;
+        progress.since_save = 0.0;
+    }
+}
+
+fn toggle_objective_panel(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut q_panel: Query<&mut Node, With<ObjectivePanel>>,
+) {
+    // Ctrl+O imports a picture (see picture_import.rs).
+    if !keys.just_pressed(KeyCode::KeyO) || ctrl_held(&keys) {
+        return;
+    }
+    for mut node in &mut q_panel {
+        node.display = match node.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+fn update_objective_panel(
+    library: Res<ObjectiveLibrary>,
+    folders: Res<Assets<LoadedFolder>>,
+    sets: Res<Assets<ObjectiveSet>>,
+    progress: Res<ObjectiveProgress>,
+    mut q_panel: Query<(&Node, &mut Text), With<ObjectivePanel>>,
+) {
+    let Ok((node, mut text)) = q_panel.single_mut() else { return };
+    if node.display == Display::None {
+        return;
+    }
+
+    let lines: Vec<String> = loaded_objectives(&library, &folders, &sets)
+        .map(|objective| {
+            let state = progress.get(&objective.id).cloned().unwrap_or_default();
+            let target = objective.goal.target();
+            if state.completed {
+                format!("[x] {}", objective.title)
+            } else {
+                format!("[ ] {} ({}/{})", objective.title, state.progress.min(target), target)
+            }
+        })
+        .collect();
+    text.0 = format!("Objectives\n{}", lines.join("\n"));
+}
@@ -0,0 +1,268 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::access::{RayFilter, RayWalk};
+use crate::coords::CellPos;
+use crate::player::{InputSource, Player, PlayerCursor, PlayerInputSet, SelectedParticle};
+use crate::sim::{SimulationGrid, SimulationSet, SimulationStats};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+const BEAM_COLOR: Color = Color::srgb(1.0, 0.15, 0.1);
+const BEAM_RANGE: f32 = 1024.0;
+// A beam stops after this many reflections, so mirror boxes can't trap it forever.
+const MAX_BOUNCES: usize = 16;
+// Degrees added per tick to every cell a beam passes through, and to the cell that stops it.
+const PASS_HEAT: f32 = 1.5;
+const HIT_HEAT: f32 = 12.0;
+// Laser aim and mirror tilt both turn in steps of this many degrees.
+const AIM_STEP_DEGREES: f32 = 15.0;
+const TILT_STEPS: u8 = (180.0 / AIM_STEP_DEGREES) as u8;
+
+// --- PLUGIN ---
+pub struct OpticsPlugin;
+
+impl Plugin for OpticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LaserAim>()
+            .init_resource::<MirrorTilt>()
+            .add_systems(Startup, spawn_optics_label)
+            .add_systems(
+                Update,
+                (aim_lasers, fire_beams, update_optics_label)
+                    .chain()
+                    .after(PlayerInputSet)
+                    .before(SimulationSet),
+            );
+    }
+}
+
+// --- RESOURCES ---
+
+// The direction every laser emitter, and the handheld laser, fires in. Q / E rotate it.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct LaserAim {
+    pub degrees: f32,
+}
+
+impl Default for LaserAim {
+    fn default() -> Self {
+        Self { degrees: 45.0 }
+    }
+}
+
+impl LaserAim {
+    pub fn direction(&self) -> Vec2 {
+        Vec2::from_angle(self.degrees.to_radians())
+    }
+}
+
+// The tilt newly painted mirrors get. Step 0 is a flat mirror, which bounces beams off whichever
+// face they hit; steps 1..=12 tilt the mirror's surface to (step - 1) * 15 degrees, and every beam
+// reflects about that line. Shift+Q / Shift+E change it.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct MirrorTilt {
+    pub step: u8,
+}
+
+impl Default for MirrorTilt {
+    fn default() -> Self {
+        Self { step: 4 }
+    }
+}
+
+impl MirrorTilt {
+    fn describe(step: u8) -> String {
+        match step {
+            0 => "flat".into(),
+            step => format!("{:.0} deg", (step - 1) as f32 * AIM_STEP_DEGREES),
+        }
+    }
+}
+
+// The state byte a freshly painted `particle` carries.
+pub fn painted_state(particle: Particle, tilt: &MirrorTilt) -> u8 {
+    match particle {
+        Particle::Mirror => tilt.step,
+        _ => 0,
+    }
+}
+
+// Where a beam went and what it did along the way.
+#[derive(Default)]
+struct BeamPath {
+    segments: Vec<(Vec2, Vec2)>,
+    heated: Vec<(CellPos, f32)>,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct OpticsLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_optics_label(mut commands: Commands) {
+    commands.spawn((
+        OpticsLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(BEAM_COLOR),
+    ));
+}
+
+fn aim_lasers(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut aim: ResMut<LaserAim>,
+    mut tilt: ResMut<MirrorTilt>,
+) {
+    let turn = keys.just_pressed(KeyCode::KeyQ) as i32 - keys.just_pressed(KeyCode::KeyE) as i32;
+    if turn == 0 {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        tilt.step = (tilt.step as i32 + turn).rem_euclid(TILT_STEPS as i32 + 1) as u8;
+    } else {
+        aim.degrees = (aim.degrees + turn as f32 * AIM_STEP_DEGREES).rem_euclid(360.0);
+    }
+}
+
+// Shows the aim and tilt while someone holds a laser or mirror.
+fn update_optics_label(
+    aim: Res<LaserAim>,
+    tilt: Res<MirrorTilt>,
+    q_selected: Query<&SelectedParticle>,
+    mut q_label: Query<&mut Text, With<OpticsLabel>>,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let holding = q_selected
+        .iter()
+        .any(|s| matches!(s.0, Particle::Laser | Particle::Mirror));
+    let text = if holding {
+        format!(
+            "Laser aim: {:.0} deg (Q / E)   Mirror tilt: {} (Shift+Q / Shift+E)",
+            aim.degrees,
+            MirrorTilt::describe(tilt.step)
+        )
+    } else {
+        String::new()
+    };
+    if label.0 != text {
+        label.0 = text;
+    }
+}
+
+// Traces a beam from every emitter cell, plus one from the mouse player's cursor while the right
+// button is held, and heats what they touch.
+fn fire_beams(
+    mouse: Res<ButtonInput<MouseButton>>,
+    aim: Res<LaserAim>,
+    stats: Res<SimulationStats>,
+    view: WorldView,
+    q_players: Query<(&InputSource, &PlayerCursor), With<Player>>,
+    mut grid: ResMut<SimulationGrid>,
+    mut gizmos: Gizmos,
+) {
+    let dir = aim.direction();
+    let mut beams = Vec::new();
+
+    if stats.count(Particle::Laser) > 0 {
+        let width = grid.width() as usize;
+        for (i, _) in grid.cells().iter().enumerate().filter(|(_, p)| **p == Particle::Laser) {
+            let cell = CellPos::new((i % width) as i32, (i / width) as i32);
+            beams.push(trace_beam(&grid, cell.center(), dir, true));
+        }
+    }
+
+    if mouse.pressed(MouseButton::Right) {
+        for (source, cursor) in &q_players {
+            let InputSource::Mouse = source else { continue };
+            let Some(cell) = cursor.position.and_then(|position| view.cell_at(position)) else {
+                continue;
+            };
+            let origin = cell.center();
+            beams.push(trace_beam(&grid, origin, dir, false));
+        }
+    }
+
+    if beams.is_empty() {
+        return;
+    }
+    for beam in &beams {
+        for (from, to) in &beam.segments {
+            gizmos.line_2d(*view.cell_to_world(*from), *view.cell_to_world(*to), BEAM_COLOR);
+        }
+        for (cell, degrees) in &beam.heated {
+            grid.add_heat(cell.x, cell.y, *degrees);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// Follows a beam through transparent cells, bouncing off mirrors, until something opaque stops
+// it, it leaves the grid or it runs out of range or bounces. `from_emitter` skips the cell the
+// beam starts in, which is the emitter itself.
+fn trace_beam(grid: &SimulationGrid, mut origin: Vec2, mut dir: Vec2, from_emitter: bool) -> BeamPath {
+    let mut path = BeamPath::default();
+    let mut range = BEAM_RANGE;
+    let mut skip_first = from_emitter;
+
+    for _ in 0..=MAX_BOUNCES {
+        let mut walk = RayWalk::new(grid, origin, dir, range);
+        if std::mem::take(&mut skip_first) {
+            walk.next();
+        }
+
+        let mut end = origin + dir * range;
+        let mut stop = None;
+        for step in walk {
+            end = step.point;
+            if RayFilter::Opaque.matches(step.particle) {
+                stop = Some(step);
+                break;
+            }
+            path.heated.push((step.cell, PASS_HEAT));
+        }
+        path.segments.push((origin, end));
+
+        let Some(hit) = stop else { break };
+        range -= hit.distance;
+        if hit.particle == Particle::Mirror && hit.normal != IVec2::ZERO {
+            let tilt = grid.data(hit.cell.x, hit.cell.y).unwrap_or(0);
+            dir = reflect(dir, hit.normal, tilt);
+            origin = hit.point + hit.normal.as_vec2() * 1e-3;
+            continue;
+        }
+        path.heated.push((hit.cell, HIT_HEAT));
+        break;
+    }
+    path
+}
+
+// Bounces `dir` off a mirror cell entered through the face with `normal`. Tilted mirrors reflect
+// about their surface line, unless that would send the beam straight back into the same cell, in
+// which case they act like flat ones.
+fn reflect(dir: Vec2, normal: IVec2, tilt: u8) -> Vec2 {
+    let normal = normal.as_vec2();
+    let flat = dir - 2.0 * dir.dot(normal) * normal;
+    if tilt == 0 {
+        return flat;
+    }
+    let surface = Vec2::from_angle(((tilt - 1) as f32 * AIM_STEP_DEGREES).to_radians());
+    let tilted = 2.0 * dir.dot(surface) * surface - dir;
+    if tilted.dot(normal) > 0.0 { tilted } else { flat }
+}
@@ -0,0 +1,176 @@
+// --- IMPORTS ---
+use std::borrow::Cow;
+
+#[cfg(feature = "reflect")]
+use bevy::prelude::Reflect;
+
+use crate::Particle;
+
+// --- CONSTANTS ---
+// A nibble tells this many materials apart.
+const NIBBLE_MATERIALS: usize = 16;
+
+// --- TYPES ---
+
+// A layer of materials, without temperatures, states or anything else about the cells: the walls
+// behind the world (see `SimulationGrid::backdrops`), or a copy of the world's materials such as
+// the world as last sent to spectators. In low-memory mode a layer with at most 16 different
+// materials keeps two cells per byte, indexing a palette of the materials it holds; otherwise (or
+// once a 17th material comes along) it keeps one byte per cell. Either way it reads and writes
+// like a plain list of cells. Two layers are equal if they are stored the same way, so a clone
+// compares equal to what it was cloned from without unpacking either.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(opaque))]
+pub struct PackedCells {
+    storage: Storage,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Storage {
+    Bytes(Vec<Particle>),
+    Nibbles {
+        palette: Vec<Particle>,
+        codes: Vec<u8>,
+        count: usize,
+    },
+}
+
+impl PackedCells {
+    pub fn new(cells: &[Particle], low_memory: bool) -> Self {
+        let mut palette = Vec::new();
+        for &cell in cells {
+            if !palette.contains(&cell) {
+                palette.push(cell);
+            }
+            if palette.len() > NIBBLE_MATERIALS {
+                break;
+            }
+        }
+        if !low_memory || palette.len() > NIBBLE_MATERIALS {
+            return Self {
+                storage: Storage::Bytes(cells.to_vec()),
+            };
+        }
+        let mut packed = Self {
+            storage: Storage::Nibbles {
+                palette,
+                codes: vec![0; cells.len().div_ceil(2)],
+                count: cells.len(),
+            },
+        };
+        packed.copy_from(0, cells);
+        packed
+    }
+
+    // `count` cells of `particle`.
+    pub fn filled(particle: Particle, count: usize, low_memory: bool) -> Self {
+        if !low_memory {
+            return Self {
+                storage: Storage::Bytes(vec![particle; count]),
+            };
+        }
+        Self {
+            storage: Storage::Nibbles {
+                palette: vec![particle],
+                codes: vec![0; count.div_ceil(2)],
+                count,
+            },
+        }
+    }
+
+    // How many bytes the cells take: one each, or half of one plus the palette packed.
+    pub fn memory_bytes(&self) -> usize {
+        match &self.storage {
+            Storage::Bytes(cells) => std::mem::size_of_val(cells.as_slice()),
+            Storage::Nibbles { palette, codes, .. } => {
+                codes.len() + std::mem::size_of_val(palette.as_slice())
+            }
+        }
+    }
+
+    // How many cells the copy holds.
+    pub fn cell_count(&self) -> usize {
+        match &self.storage {
+            Storage::Bytes(cells) => cells.len(),
+            Storage::Nibbles { count, .. } => *count,
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Particle {
+        match &self.storage {
+            Storage::Bytes(cells) => cells[i],
+            Storage::Nibbles { palette, codes, .. } => palette[nibble(codes, i) as usize],
+        }
+    }
+
+    pub fn set(&mut self, i: usize, particle: Particle) {
+        let code = match &mut self.storage {
+            Storage::Bytes(cells) => {
+                cells[i] = particle;
+                return;
+            }
+            Storage::Nibbles { palette, .. } => match palette.iter().position(|&p| p == particle) {
+                Some(code) => Some(code),
+                None if palette.len() < NIBBLE_MATERIALS => {
+                    palette.push(particle);
+                    Some(palette.len() - 1)
+                }
+                None => None,
+            },
+        };
+        // A 17th material doesn't fit in a nibble any more.
+        let Some(code) = code else {
+            let mut cells = self.to_vec();
+            cells[i] = particle;
+            self.storage = Storage::Bytes(cells);
+            return;
+        };
+        if let Storage::Nibbles { codes, .. } = &mut self.storage {
+            let shift = (i % 2) * 4;
+            codes[i / 2] = (codes[i / 2] & !(0xF << shift)) | ((code as u8) << shift);
+        }
+    }
+
+    // Whether the cells from `start` on are the same as `cells`.
+    pub fn matches(&self, start: usize, cells: &[Particle]) -> bool {
+        match &self.storage {
+            Storage::Bytes(copy) => copy[start..start + cells.len()] == *cells,
+            Storage::Nibbles { .. } => {
+                cells.iter().enumerate().all(|(i, &cell)| self.get(start + i) == cell)
+            }
+        }
+    }
+
+    // Overwrites the cells from `start` on with `cells`.
+    pub fn copy_from(&mut self, start: usize, cells: &[Particle]) {
+        match &mut self.storage {
+            Storage::Bytes(copy) => copy[start..start + cells.len()].copy_from_slice(cells),
+            Storage::Nibbles { .. } => {
+                for (i, &cell) in cells.iter().enumerate() {
+                    self.set(start + i, cell);
+                }
+            }
+        }
+    }
+
+    // All the cells, unpacked only if they are packed.
+    pub fn cells(&self) -> Cow<'_, [Particle]> {
+        match &self.storage {
+            Storage::Bytes(cells) => Cow::Borrowed(cells),
+            Storage::Nibbles { .. } => Cow::Owned(self.to_vec()),
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Particle> {
+        match &self.storage {
+            Storage::Bytes(cells) => cells.clone(),
+            Storage::Nibbles { count, .. } => (0..*count).map(|i| self.get(i)).collect(),
+        }
+    }
+}
+
+// --- HELPERS ---
+
+fn nibble(codes: &[u8], i: usize) -> u8 {
+    (codes[i / 2] >> ((i % 2) * 4)) & 0xF
+}
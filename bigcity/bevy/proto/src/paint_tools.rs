@@ -0,0 +1,254 @@
+// --- IMPORTS ---
+use std::collections::VecDeque;
+
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+
+use crate::WorldView;
+use crate::coords::CellPos;
+use crate::frame_order::DisplaySet;
+use crate::optics::{MirrorTilt, painted_state};
+use crate::player::{
+    Brush, InputSource, PaintLayer, PaintTool, Player, PlayerCursor, PlayerInputSet,
+    SelectedParticle, ShapeDrag,
+};
+use crate::replay::ReplayPlayback;
+use crate::sim::SimulationGrid;
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+
+// --- CONSTANTS ---
+// The most cells a bucket fill changes; a region any bigger, such as the open sky, is left alone
+// rather than filled by one careless click.
+const FILL_LIMIT: usize = 40_000;
+const PREVIEW_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+
+// --- PLUGIN ---
+
+// The paint tools besides the freehand brush, picked in the tool palette: a line and a
+// rectangle's outline, dragged out from where the button goes down to where it is let go and
+// painted with the brush's shape and size then, and a bucket fill that replaces the connected
+// region of one material under the cursor, up to FILL_LIMIT cells. Holding Shift as the mouse's
+// button goes down draws a line with the freehand brush too. A shape shows as an outline while it
+// is dragged, and goes into the world as one brush pass, under the paint rules, locks and
+// inventory and undone in one go like any stroke; on the background layer, or with Alt held, they
+// paint walls instead.
+pub struct PaintToolsPlugin;
+
+impl Plugin for PaintToolsPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            Update,
+            PaintToolSet
+                .after(PlayerInputSet)
+                .before(WorldCommandSet)
+                .run_if(not(resource_exists::<ReplayPlayback>)),
+        )
+        .add_systems(
+            Update,
+            (
+                paint_shapes.in_set(PaintToolSet),
+                draw_shapes.in_set(DisplaySet),
+            ),
+        );
+    }
+}
+
+// Shapes are started, followed and painted in this set, and the freehand brush orders itself after
+// it so it leaves a player dragging one alone.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PaintToolSet;
+
+// --- SYSTEMS ---
+
+// Starts a shape where a player's button goes down with one of the tools, follows it while held
+// and paints it once let go. A fill goes in straight away.
+fn paint_shapes(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    grid: Res<SimulationGrid>,
+    mirror_tilt: Res<MirrorTilt>,
+    mut commands: ResMut<WorldCommands>,
+    mut q_players: Query<(
+        &Player,
+        &InputSource,
+        &PlayerCursor,
+        &SelectedParticle,
+        &mut Brush,
+    )>,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    for (player, source, cursor, selected, mut brush) in &mut q_players {
+        let walls = brush.layer == PaintLayer::Background || alt;
+        let particle = cursor.paint(selected);
+        if !cursor.painting {
+            let Some(drag) = brush.drag.take() else {
+                continue;
+            };
+            if drag.tool == PaintTool::Fill {
+                continue;
+            }
+            let cells = shape_cells(&brush, drag);
+            if walls {
+                commands.push(WorldCommand::Backdrop { particle, cells });
+                continue;
+            }
+            commands.push(WorldCommand::Paint {
+                player: player.index,
+                particle,
+                data: painted_state(particle, &mirror_tilt),
+                max_cells: cells.len() as u32,
+                cells,
+            });
+            commands.push(WorldCommand::EndStroke {
+                player: player.index,
+            });
+            continue;
+        }
+        let Some(cell) = cursor.position.and_then(|position| view.cell_at(position)) else {
+            continue;
+        };
+        if let Some(drag) = &mut brush.drag {
+            drag.to = cell;
+            continue;
+        }
+        // A freehand stroke already under way stays one, Shift or not.
+        if brush.last_cell.is_some() {
+            continue;
+        }
+        let tool = match brush.tool {
+            PaintTool::Freehand if shift && *source == InputSource::Mouse => PaintTool::Line,
+            tool => tool,
+        };
+        if tool == PaintTool::Freehand {
+            continue;
+        }
+        brush.drag = Some(ShapeDrag {
+            tool,
+            from: cell,
+            to: cell,
+        });
+        if tool != PaintTool::Fill {
+            continue;
+        }
+        let at = |cell: CellPos| {
+            if walls { grid.backdrop(cell.x, cell.y) } else { grid.get(cell.x, cell.y) }
+        };
+        let Some(target) = at(cell).filter(|&target| target != particle) else { continue };
+        let Some(region) = flood(cell, FILL_LIMIT, |cell| at(cell) == Some(target)) else {
+            warn!(
+                "That region is too big to fill, more than {} cells",
+                FILL_LIMIT
+            );
+            continue;
+        };
+        if walls {
+            commands.push(WorldCommand::Backdrop {
+                particle,
+                cells: region,
+            });
+            continue;
+        }
+        commands.push(WorldCommand::Paint {
+            player: player.index,
+            particle,
+            data: painted_state(particle, &mirror_tilt),
+            max_cells: region.len() as u32,
+            cells: region,
+        });
+        commands.push(WorldCommand::EndStroke {
+            player: player.index,
+        });
+    }
+}
+
+// Outlines every line and rectangle being dragged out, as far as the brush will reach.
+fn draw_shapes(view: WorldView, q_players: Query<&Brush>, mut gizmos: Gizmos) {
+    for brush in &q_players {
+        let Some(drag) = brush.drag else { continue };
+        let from = *view.cell_to_world(drag.from.center());
+        let to = *view.cell_to_world(drag.to.center());
+        match drag.tool {
+            PaintTool::Line => gizmos.line_2d(from, to, PREVIEW_COLOR),
+            PaintTool::Rectangle => {
+                let reach = view.cell_size() * (brush.size * 2 + 1) as f32;
+                let size = (to - from).abs() + reach;
+                let at = Isometry2d::from_translation((from + to) / 2.0);
+                gizmos.rect_2d(at, size, PREVIEW_COLOR);
+            }
+            PaintTool::Freehand | PaintTool::Fill => {}
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// Every cell the brush covers along the line, or around the rectangle's edges, each once.
+fn shape_cells(brush: &Brush, drag: ShapeDrag) -> Vec<CellPos> {
+    let (from, to) = (drag.from, drag.to);
+    if drag.tool != PaintTool::Rectangle {
+        return brush.shape.swept_cells(from, to, brush.size);
+    }
+    let corners = [
+        from,
+        CellPos::new(to.x, from.y),
+        to,
+        CellPos::new(from.x, to.y),
+    ];
+    let mut seen = HashSet::new();
+    (0..4)
+        .flat_map(|side| {
+            brush
+                .shape
+                .swept_cells(corners[side], corners[(side + 1) % 4], brush.size)
+        })
+        .filter(|&cell| seen.insert(cell))
+        .collect()
+}
+
+// Every cell joined to `start` side by side through cells that are `inside`, or None if there are
+// more than `limit` of them.
+fn flood(start: CellPos, limit: usize, inside: impl Fn(CellPos) -> bool) -> Option<Vec<CellPos>> {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    let mut region = Vec::new();
+    while let Some(cell) = queue.pop_front() {
+        region.push(cell);
+        if region.len() > limit {
+            return None;
+        }
+        for step in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+            let next = CellPos(cell.0 + step);
+            if inside(next) && seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    Some(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flood_stays_inside_walls() {
+        // A 5x3 box walled in on every side, with a wall through it at x == 2.
+        let inside = |cell: CellPos| (0..5).contains(&cell.x) && (0..3).contains(&cell.y);
+        let open = |cell: CellPos| inside(cell) && cell.x != 2;
+        let region = flood(CellPos::new(0, 0), 100, open).unwrap();
+        assert_eq!(region.len(), 6);
+        assert!(region.iter().all(|cell| cell.x < 2));
+    }
+
+    #[test]
+    fn flood_gives_up_past_the_limit() {
+        // A 19x19 square around the origin.
+        let inside = |cell: CellPos| cell.x.abs() < 10 && cell.y.abs() < 10;
+        assert!(flood(CellPos::new(0, 0), 360, inside).is_none());
+        assert_eq!(
+            flood(CellPos::new(0, 0), 361, inside).map(|region| region.len()),
+            Some(361)
+        );
+    }
+}
@@ -0,0 +1,283 @@
+// --- IMPORTS ---
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::backdrop::BackdropView;
+use crate::bindings::InputBindings;
+use crate::control::SPEEDS;
+use crate::events::SimEvent;
+use crate::inventory::Inventory;
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::player::{
+    Brush, BrushConform, BrushShape, InputSource, MAX_BRUSH_SIZE, MIN_BRUSH_SIZE, PaintLayer,
+    PaintTool, Player, PlayerCursor, SelectedParticle,
+};
+use crate::sim::{SimulationControl, SimulationGrid, SimulationStats};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+const PANEL_WIDTH: f32 = 220.0;
+const SWATCH_SIZE: f32 = 14.0;
+
+// --- PLUGIN ---
+
+// The tool palette (S shows or hides it), a panel down the right of the window with every material,
+// each with its color, to pick for the mouse player's brush, the brush's tool, shape, size, flow
+// and spray density, whether it paints anywhere, onto surfaces or into enclosed pockets and whether
+// it paints particles or the walls behind them, a switch for drawing those walls, the simulation's
+// speed and pause, and live stats: the frame rate, how long ticks take, what is under each player's
+// cursor and how much of each material there is. In levels, materials the level doesn't allow are
+// greyed out, and with an inventory every material shows how much is left. The number keys pick
+// materials too.
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Palette { open: true })
+            .add_systems(Update, toggle_palette)
+            .add_systems(EguiContextPass, draw_palette);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct Palette {
+    open: bool,
+}
+
+// --- SYSTEM PARAM ---
+
+// The mouse player's material and brush, where picking a material is announced, and whether the
+// walls it paints are drawn.
+#[derive(SystemParam)]
+struct MouseTools<'w, 's> {
+    q_players: Query<
+        'w,
+        's,
+        (&'static Player, &'static InputSource, &'static mut SelectedParticle, &'static mut Brush),
+    >,
+    sim_events: EventWriter<'w, SimEvent>,
+    backdrop: ResMut<'w, BackdropView>,
+}
+
+// What the stats section shows.
+#[derive(SystemParam)]
+struct LiveStats<'w, 's> {
+    diagnostics: Res<'w, DiagnosticsStore>,
+    stats: Res<'w, SimulationStats>,
+    grid: Res<'w, SimulationGrid>,
+    view: WorldView<'w, 's>,
+    q_cursors: Query<'w, 's, (&'static Player, &'static PlayerCursor)>,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_palette(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut palette: ResMut<Palette>,
+) {
+    // Ctrl+S saves the selection tool's copy as a stamp.
+    if keys.just_pressed(bindings.palette.0) && !ctrl_held(&keys) {
+        palette.open = !palette.open;
+    }
+}
+
+// Edits copies of the brush and controls and only writes them back when something moved, so the
+// rest of the app can tell real changes apart from the panel merely being drawn.
+fn draw_palette(
+    mut contexts: EguiContexts,
+    palette: Res<Palette>,
+    rules: Res<PaintRules>,
+    inventory: Res<Inventory>,
+    mut tools: MouseTools,
+    mut control: ResMut<SimulationControl>,
+    live: LiveStats,
+) {
+    if !palette.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let mut mouse =
+        tools.q_players.iter_mut().find(|(_, source, ..)| **source == InputSource::Mouse);
+    let mut picked = mouse.as_ref().map(|(_, _, selected, _)| selected.0);
+    let mut edited = mouse.as_ref().map(|(.., b)| (b.shape, b.size, b.flow, b.density));
+    let mut layer = mouse.as_ref().map(|(.., b)| b.layer);
+    let mut conform = mouse.as_ref().map(|(.., b)| b.conform);
+    let mut tool = mouse.as_ref().map(|(.., b)| b.tool);
+    let mut walls_shown = tools.backdrop.shown;
+    let mut controls = (control.paused, control.speed, false);
+
+    egui::SidePanel::right("tool_palette").default_width(PANEL_WIDTH).show(ctx, |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.heading("Materials");
+            for particle in Particle::ALL {
+                ui.add_enabled_ui(rules.is_allowed(particle), |ui| {
+                    ui.horizontal(|ui| {
+                        let [r, g, b, _] = particle.color().to_srgba().to_u8_array();
+                        let size = egui::Vec2::splat(SWATCH_SIZE);
+                        let (swatch, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                        ui.painter().rect_filled(swatch, 2.0, egui::Color32::from_rgb(r, g, b));
+                        let mut name = match particle {
+                            Particle::Air => "Air (eraser)".to_string(),
+                            _ => format!("{:?}", particle),
+                        };
+                        if let Some(left) = inventory.remaining(particle) {
+                            name = format!("{} ({} left)", name, left);
+                        }
+                        let selected = picked == Some(particle);
+                        if ui.selectable_label(selected, name).clicked() {
+                            picked = Some(particle);
+                        }
+                    });
+                });
+            }
+
+            if let Some((shape, size, flow, density)) = &mut edited {
+                ui.separator();
+                ui.heading("Brush");
+                if let Some(tool) = &mut tool {
+                    egui::ComboBox::from_label("Tool")
+                        .selected_text(tool.label())
+                        .show_ui(ui, |ui| {
+                            for option in PaintTool::ALL {
+                                ui.selectable_value(tool, option, option.label());
+                            }
+                        });
+                }
+                egui::ComboBox::from_label("Shape")
+                    .selected_text(format!("{:?}", shape))
+                    .show_ui(ui, |ui| {
+                        for option in BrushShape::ALL {
+                            ui.selectable_value(shape, option, format!("{:?}", option));
+                        }
+                    });
+                ui.add(egui::Slider::new(size, MIN_BRUSH_SIZE..=MAX_BRUSH_SIZE).text("Size"));
+                ui.add(egui::Slider::new(flow, 10.0..=20000.0).logarithmic(true).text("Flow"));
+                ui.add(egui::Slider::new(density, 0.05..=1.0).text("Spray (powders)"));
+            }
+            if let Some(conform) = &mut conform {
+                egui::ComboBox::from_label("Paint where")
+                    .selected_text(conform.label())
+                    .show_ui(ui, |ui| {
+                        for option in BrushConform::ALL {
+                            ui.selectable_value(conform, option, option.label());
+                        }
+                    });
+            }
+            if let Some(layer) = &mut layer {
+                egui::ComboBox::from_label("Paint on")
+                    .selected_text(layer.label())
+                    .show_ui(ui, |ui| {
+                        for option in PaintLayer::ALL {
+                            ui.selectable_value(layer, option, option.label());
+                        }
+                    });
+            }
+            ui.checkbox(&mut walls_shown, "Show background walls");
+
+            ui.separator();
+            ui.heading("Simulation");
+            let (paused, speed, step) = &mut controls;
+            let (slowest, fastest) = (SPEEDS[0], SPEEDS[SPEEDS.len() - 1]);
+            ui.add(egui::Slider::new(speed, slowest..=fastest).logarithmic(true).prefix("x"));
+            ui.horizontal(|ui| {
+                ui.checkbox(paused, "Paused");
+                *step = ui.add_enabled(*paused, egui::Button::new("Step")).clicked();
+            });
+
+            ui.separator();
+            ui.heading("Stats");
+            let smoothed = |path: &_| live.diagnostics.get(path).and_then(|d| d.smoothed());
+            let fps = smoothed(&FrameTimeDiagnosticsPlugin::FPS).unwrap_or_default();
+            ui.label(format!("{:.0} FPS, tick {}", fps, live.stats.tick));
+            ui.label(format!(
+                "{} ticks in {:.2} ms",
+                live.stats.ticks_last_frame,
+                live.stats.step_time.as_secs_f64() * 1000.0
+            ));
+            for (player, cursor) in &live.q_cursors {
+                let cell = cursor.position.and_then(|position| live.view.cell_at(position));
+                let under = cell.and_then(|cell| {
+                    let particle = live.grid.get(cell.x, cell.y)?;
+                    let temperature = live.grid.temperature(cell.x, cell.y)?;
+                    Some(format!(
+                        "({}, {}): {:?}, {:.0} C",
+                        cell.x, cell.y, particle, temperature
+                    ))
+                });
+                let under = under.unwrap_or_else(|| "off the world".to_string());
+                ui.label(format!("P{} {}", player.index + 1, under));
+            }
+            let mut counts: Vec<_> = Particle::ALL
+                .into_iter()
+                .skip(1)
+                .map(|particle| (particle, live.stats.count(particle)))
+                .filter(|&(_, count)| count > 0)
+                .collect();
+            counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+            egui::Grid::new("material_counts").num_columns(2).show(ui, |ui| {
+                for (particle, count) in counts {
+                    ui.label(format!("{:?}", particle));
+                    ui.label(count.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+    });
+
+    if let Some((player, _, selected, brush)) = &mut mouse {
+        if let Some(particle) = picked
+            && selected.0 != particle
+        {
+            selected.0 = particle;
+            tools.sim_events.write(SimEvent::ParticleSelected {
+                player: player.index,
+                particle,
+            });
+        }
+        if let Some((shape, size, flow, density)) = edited
+            && (brush.shape, brush.size, brush.flow, brush.density) != (shape, size, flow, density)
+        {
+            brush.shape = shape;
+            brush.flow = flow;
+            brush.density = density;
+            if brush.size != size {
+                brush.size = size;
+                tools.sim_events.write(SimEvent::BrushResized {
+                    player: player.index,
+                    size,
+                });
+            }
+        }
+        if let Some(layer) = layer
+            && brush.layer != layer
+        {
+            brush.layer = layer;
+        }
+        if let Some(conform) = conform
+            && brush.conform != conform
+        {
+            brush.conform = conform;
+        }
+        if let Some(tool) = tool {
+            brush.tool = tool;
+        }
+    }
+    if walls_shown != tools.backdrop.shown {
+        tools.backdrop.shown = walls_shown;
+    }
+    let (paused, speed, step) = controls;
+    if (paused, speed) != (control.paused, control.speed) {
+        control.paused = paused;
+        control.speed = speed;
+    }
+    if step {
+        control.pending_steps += 1;
+    }
+}
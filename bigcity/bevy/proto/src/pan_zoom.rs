@@ -0,0 +1,146 @@
+// --- IMPORTS ---
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+
+use crate::coords::WorldPos;
+use crate::player::TOUCH_INPUT;
+use crate::{ScreenCamera, WorldLayout, WorldView};
+
+// --- CONSTANTS ---
+// How much one notch of the wheel zooms in or out, and how far in the camera goes at most, as an
+// orthographic scale (1 shows the whole world).
+const ZOOM_STEP: f32 = 1.25;
+const CLOSEST_SCALE: f32 = 0.05;
+
+// --- PLUGIN ---
+
+// Pan and zoom, for looking at a big world close up: Ctrl with the mouse wheel zooms in and out
+// around the cursor, and Ctrl with a middle-button drag pans, keeping the point grabbed under the
+// cursor. Without Ctrl the wheel still sizes the brush and the middle button still throws grenades.
+// On touch screens two fingers pinch to zoom and drag to pan. The camera never zooms out past the
+// whole world or pans off its edges.
+pub struct PanZoomPlugin;
+
+impl Plugin for PanZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PanGrab>().add_systems(Update, (zoom_camera, pan_camera).chain());
+        if TOUCH_INPUT {
+            app.add_systems(Update, pinch_camera);
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+// The point of the world grabbed by the pan in progress, if any.
+#[derive(Resource, Default)]
+struct PanGrab {
+    point: Option<WorldPos>,
+}
+
+// --- SYSTEMS ---
+
+fn zoom_camera(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut wheel: EventReader<MouseWheel>,
+    view: WorldView,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    // Each notch of the wheel is one zoom step, like it is one brush size step without Ctrl.
+    let notches: i32 = wheel.read().map(|event| event.y.signum() as i32).sum();
+    if notches == 0 || !ctrl_held(&keys) {
+        return;
+    }
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+    let scale = (ortho.scale * ZOOM_STEP.powi(-notches)).clamp(CLOSEST_SCALE, 1.0);
+    // Scaling the camera's offset from the point under the cursor keeps that point in place.
+    let camera = transform.translation.truncate();
+    let pivot = cursor_world(&view).map_or(camera, |point| point.0);
+    let position = pivot + (camera - pivot) * scale / ortho.scale;
+    place_camera(&mut transform, ortho, position, scale, &view.layout);
+}
+
+fn pan_camera(
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    view: WorldView,
+    mut grab: ResMut<PanGrab>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let cursor = cursor_world(&view);
+    if mouse.just_pressed(MouseButton::Middle) && ctrl_held(&keys) {
+        grab.point = cursor;
+    }
+    if !mouse.pressed(MouseButton::Middle) {
+        grab.point = None;
+    }
+    let (Some(grabbed), Some(cursor)) = (grab.point, cursor) else { return };
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+    // Moves the camera so the grabbed point comes back under the cursor.
+    let position = transform.translation.truncate() + (grabbed.0 - cursor.0);
+    let scale = ortho.scale;
+    place_camera(&mut transform, ortho, position, scale, &view.layout);
+}
+
+// Two fingers zoom the camera by how far they spread or pinch, around the point between them,
+// and pan it by how far that point moves.
+fn pinch_camera(
+    touches: Res<Touches>,
+    view: WorldView,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let fingers: Vec<_> = touches.iter().take(3).collect();
+    let [first, second] = fingers[..] else { return };
+    let (before, now) = (
+        (first.previous_position(), second.previous_position()),
+        (first.position(), second.position()),
+    );
+    let spread = |(a, b): (Vec2, Vec2)| a.distance(b).max(1.0);
+    let middle = |(a, b): (Vec2, Vec2)| (a + b) / 2.0;
+    let (Some(grabbed), Some(under)) =
+        (view.screen_to_world(middle(before)), view.screen_to_world(middle(now)))
+    else {
+        return;
+    };
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+    let scale = ortho.scale * spread(before) / spread(now);
+    // As with the wheel, scaling the camera's offset from the grabbed point keeps it in place,
+    // and the pan then brings it back under the fingers.
+    let camera = transform.translation.truncate();
+    let position = grabbed.0 + (camera - grabbed.0) * scale / ortho.scale + (grabbed.0 - under.0);
+    place_camera(&mut transform, ortho, position, scale, &view.layout);
+}
+
+// --- HELPERS ---
+
+pub fn ctrl_held(keys: &ButtonInput<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
+// Moves the camera to look at `position` zoomed to `scale`, as far as the world's edges and the
+// closest zoom allow.
+pub fn place_camera(
+    transform: &mut Transform,
+    ortho: &mut OrthographicProjection,
+    position: Vec2,
+    scale: f32,
+    layout: &WorldLayout,
+) {
+    ortho.scale = scale.clamp(CLOSEST_SCALE, 1.0);
+    transform.translation =
+        clamp_to_world(position, ortho.scale, layout).extend(transform.translation.z);
+}
+
+fn cursor_world(view: &WorldView) -> Option<WorldPos> {
+    view.screen_to_world(view.window()?.cursor_position()?)
+}
+
+// Keeps a camera zoomed to `scale` from showing anything past the world's edges. The camera frames
+// at least the whole world at scale 1, so at `scale` it frames at least that share of it.
+fn clamp_to_world(position: Vec2, scale: f32, layout: &WorldLayout) -> Vec2 {
+    let reach = layout.world_size() / 2.0 * (1.0 - scale);
+    position.clamp(-reach, reach)
+}
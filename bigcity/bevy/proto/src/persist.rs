@@ -0,0 +1,44 @@
+// --- IMPORTS ---
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+// --- USER DATA ---
+
+// Where per-user files (progress, settings, saves) live, e.g. ~/.local/share/falling-sand.
+pub fn user_data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("falling-sand"))
+}
+
+// Reads `file` from the user data directory. Missing files are normal (first launch) and give
+// `None` quietly; unreadable ones are reported and also give `None`.
+pub fn load_user_ron<T: DeserializeOwned>(file: &str) -> Option<T> {
+    let path = user_data_dir()?.join(file);
+    let text = std::fs::read_to_string(&path).ok()?;
+    ron::from_str(&text)
+        .inspect_err(|err| warn!("Ignoring unreadable {:?}: {}", path, err))
+        .ok()
+}
+
+// A file name made from a display name, e.g. "Moon gravity" -> "moon_gravity".
+pub fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect()
+}
+
+pub fn save_user_ron<T: Serialize>(file: &str, value: &T) {
+    let Some(dir) = user_data_dir() else { return };
+    let path = dir.join(file);
+    // `file` may name a subfolder, like "presets/moon.preset.ron".
+    let folder = path.parent().unwrap_or(&dir);
+    let result = std::fs::create_dir_all(folder).and_then(|_| {
+        let text = ron::ser::to_string_pretty(value, default()).map_err(std::io::Error::other)?;
+        std::fs::write(&path, text)
+    });
+    if let Err(err) = result {
+        warn!("Could not save {:?}: {}", path, err);
+    }
+}
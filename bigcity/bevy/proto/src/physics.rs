@@ -0,0 +1,341 @@
+// --- IMPORTS ---
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::bodies::{Body, BodyOperation, is_obstacle, submersion};
+use crate::collision::{simplify, trace};
+use crate::coords::ChunkPos;
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimulationGrid, SimulationStats, SimulationTickRate, ViewOnly};
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+// Rapier works in meters and the world in cells, this many to the meter, which puts gravity at
+// about 60 cells per second squared, as for meteors.
+const CELLS_PER_METER: f32 = 6.0;
+// How much of its speed a liquid takes each second from a body wholly under it.
+const LIQUID_DRAG: f32 = 3.0;
+const FRICTION: f32 = 0.8;
+// How far, in cells, the world's colliders may stray from the edges of its cells.
+const COLLIDER_TOLERANCE: f32 = 0.5;
+// Ctrl+B drops a block this many cells across of this material, which floats in water.
+const BLOCK_SIZE: f32 = 6.0;
+const BLOCK_MATERIAL: Particle = Particle::Glass;
+const BLOCK_DENSITY: f32 = 0.7;
+
+// --- PLUGIN ---
+
+// Rigid bodies in the world, with `--features physics`, simulated by Rapier. The bodies are the
+// world's own (see bodies.rs), and each gets a dynamic Rapier body with a box collider. Rapier
+// steps once with every tick, after it, against fixed colliders traced from the world's solids and
+// powders; before the next tick every body that moved sends a `WorldCommand::Bodies` to stamp its
+// cells in where it has got to, and liquids hold bodies up by how much of them is under and slow
+// them down. Ctrl+B drops a block at the cursor and Ctrl+Shift+B removes every body; neither works
+// during a level.
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(
+            RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(CELLS_PER_METER)
+                .in_fixed_schedule(),
+        )
+        .init_resource::<WorldColliders>()
+        .add_systems(
+            Update,
+            drop_blocks.before(WorldCommandSet).run_if(not(resource_exists::<ViewOnly>)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (follow_world_bodies, send_body_moves)
+                .chain()
+                .before(WorldCommandSet)
+                .run_if(not(resource_exists::<ViewOnly>)),
+        )
+        .add_systems(
+            FixedPostUpdate,
+            (pace_physics, update_world_colliders, float_bodies)
+                .before(PhysicsSet::SyncBackend)
+                .run_if(not(resource_exists::<ViewOnly>)),
+        );
+        #[cfg(feature = "reflect")]
+        app.register_type::<WorldBody>();
+    }
+}
+
+// --- TYPES ---
+
+// One chunk's colliding cells, row by row, and the fixed body made of their outlines, if any.
+#[derive(Default)]
+struct ChunkCollider {
+    obstacles: Vec<bool>,
+    entity: Option<Entity>,
+}
+
+// --- COMPONENTS ---
+
+// The Rapier body of the world's body `id`, and the world's body as it was last moved to.
+#[derive(Component, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct WorldBody {
+    pub id: u32,
+    moved: Body,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct WorldColliders {
+    size: UVec2,
+    chunks: HashMap<ChunkPos, ChunkCollider>,
+}
+
+// --- SYSTEMS ---
+
+fn drop_blocks(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    view: WorldView,
+    grid: Res<SimulationGrid>,
+    mut commands: ResMut<WorldCommands>,
+) {
+    // A level's materials are counted out, and a block would be more.
+    if !ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyB) || rules.protect_world {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        for body in &grid.bodies().bodies {
+            commands.push(WorldCommand::Bodies(BodyOperation::Remove(body.id)));
+        }
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    commands.push(WorldCommand::Bodies(BodyOperation::Add {
+        position: cell.center().into(),
+        half_size: (BLOCK_SIZE / 2.0, BLOCK_SIZE / 2.0),
+        material: BLOCK_MATERIAL,
+        density: BLOCK_DENSITY,
+    }));
+}
+
+// Keeps a Rapier body for every body in the world and none for any other. Bodies come and go by
+// command, and with whole worlds loaded, cleared or resized, which can also put one somewhere else:
+// its Rapier body is made again where the world has it.
+fn follow_world_bodies(
+    mut commands: Commands,
+    grid: Res<SimulationGrid>,
+    q_bodies: Query<(Entity, &WorldBody)>,
+) {
+    let mut followed = HashSet::new();
+    for (entity, body) in &q_bodies {
+        match grid.bodies().get(body.id) {
+            Some(kept) if *kept == body.moved => {
+                followed.insert(body.id);
+            }
+            _ => commands.entity(entity).despawn(),
+        }
+    }
+    for kept in grid.bodies().bodies.iter().filter(|kept| !followed.contains(&kept.id)) {
+        commands.spawn(rapier_body(kept));
+    }
+}
+
+// Sends where Rapier has moved every body to the world, to stamp its cells in there before the
+// tick. Bodies at rest send nothing.
+fn send_body_moves(
+    mut commands: ResMut<WorldCommands>,
+    mut q_bodies: Query<(&mut WorldBody, &Transform, &Velocity)>,
+) {
+    for (mut body, transform, velocity) in &mut q_bodies {
+        let position = transform.translation.truncate().into();
+        let velocity = velocity.linvel.into();
+        if (position, velocity) == (body.moved.position, body.moved.velocity) {
+            continue;
+        }
+        (body.moved.position, body.moved.velocity) = (position, velocity);
+        commands.push(WorldCommand::Bodies(BodyOperation::Move {
+            id: body.id,
+            position,
+            velocity,
+        }));
+    }
+}
+
+// Steps Rapier by a tick's worth of the world's time whenever the world ticked, and not at all
+// when it didn't: paused, or with more ticks due than a frame runs.
+fn pace_physics(
+    tick_rate: Res<SimulationTickRate>,
+    stats: Res<SimulationStats>,
+    mut last_tick: Local<u64>,
+    mut timestep: ResMut<TimestepMode>,
+    mut q_config: Query<&mut RapierConfiguration>,
+) {
+    let ticked = stats.tick != *last_tick;
+    *last_tick = stats.tick;
+    for mut config in &mut q_config {
+        config.physics_pipeline_active = ticked;
+    }
+    if tick_rate.ticks_per_second > 0.0 {
+        let dt = 1.0 / tick_rate.ticks_per_second;
+        *timestep = TimestepMode::Fixed { dt, substeps: 1 };
+    }
+}
+
+// Traces the colliding cells of every chunk that changed, and of the chunks left of and below it,
+// whose outlines reach into it, into one fixed collider per chunk. Only awake chunks can have
+// changed, so only they are looked at, unless the world has a new size.
+fn update_world_colliders(
+    mut commands: Commands,
+    grid: Res<SimulationGrid>,
+    mut colliders: ResMut<WorldColliders>,
+) {
+    let size = UVec2::new(grid.width(), grid.height());
+    let changed: Vec<ChunkPos> = if colliders.size != size {
+        for entity in colliders.chunks.drain().filter_map(|(_, chunk)| chunk.entity) {
+            commands.entity(entity).despawn();
+        }
+        colliders.size = size;
+        ChunkPos::covering(size.x, size.y).collect()
+    } else {
+        grid.activity().awake().collect()
+    };
+
+    let mut dirty = HashSet::new();
+    for chunk in changed {
+        let obstacles = obstacle_cells(&grid, chunk);
+        let kept = colliders.chunks.entry(chunk).or_default();
+        if kept.obstacles != obstacles {
+            kept.obstacles = obstacles;
+            for (dx, dy) in [(0, 0), (-1, 0), (0, -1), (-1, -1)] {
+                dirty.insert(ChunkPos(chunk.0 + IVec2::new(dx, dy)));
+            }
+        }
+    }
+    for chunk in dirty {
+        let Some(kept) = colliders.chunks.get_mut(&chunk) else { continue };
+        match (kept.entity, chunk_collider(&grid, chunk)) {
+            (Some(entity), Some(collider)) => {
+                commands.entity(entity).insert(collider);
+            }
+            (Some(entity), None) => {
+                commands.entity(entity).despawn();
+                kept.entity = None;
+            }
+            (None, Some(collider)) => {
+                let fixed = (Name::new("chunk_collider"), RigidBody::Fixed, collider);
+                kept.entity = Some(commands.spawn((fixed, Transform::default())).id());
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+// Liquids hold a body up by how much of it is under and how dense they are against it, and take
+// its speed.
+fn float_bodies(
+    grid: Res<SimulationGrid>,
+    mut q_bodies: Query<(&WorldBody, &Transform, &mut GravityScale, &mut Damping)>,
+) {
+    for (body, transform, mut gravity, mut damping) in &mut q_bodies {
+        let (position, half_size) = (transform.translation.truncate(), body.moved.half_size);
+        let (under, liquid_density) = submersion(&grid, position, half_size.into());
+        gravity.0 = 1.0 - under * liquid_density / body.moved.density.max(0.01);
+        damping.linear_damping = LIQUID_DRAG * under;
+    }
+}
+
+// --- HELPERS ---
+
+// A dynamic Rapier body for the world's body `kept`, kept upright, as its cells are stamped in.
+fn rapier_body(kept: &Body) -> impl Bundle {
+    let (position, half_size) = (Vec2::from(kept.position), Vec2::from(kept.half_size));
+    (
+        Name::new("body"),
+        WorldBody {
+            id: kept.id,
+            moved: *kept,
+        },
+        RigidBody::Dynamic,
+        Collider::cuboid(half_size.x, half_size.y),
+        ColliderMassProperties::Density(kept.density),
+        Friction::coefficient(FRICTION),
+        Velocity::linear(kept.velocity.into()),
+        GravityScale(1.0),
+        Damping::default(),
+        LockedAxes::ROTATION_LOCKED,
+        Ccd::enabled(),
+        Transform::from_translation(position.extend(0.0)),
+    )
+}
+
+// The chunk's cells inside the world, inclusive.
+fn chunk_cells(grid: &SimulationGrid, chunk: ChunkPos) -> (IVec2, IVec2) {
+    let top_right = IVec2::new(grid.width() as i32 - 1, grid.height() as i32 - 1);
+    let end = ChunkPos(chunk.0 + IVec2::ONE).origin().0 - IVec2::ONE;
+    (chunk.origin().0, end.min(top_right))
+}
+
+// Solids and powders collide, and so does everything outside the world, which keeps bodies in
+// it. Bodies' own cells don't: they collide by their own boxes.
+fn is_world_obstacle(grid: &SimulationGrid, x: i32, y: i32) -> bool {
+    grid.get(x, y).is_none_or(is_obstacle) && grid.body(x, y).is_none()
+}
+
+fn obstacle_cells(grid: &SimulationGrid, chunk: ChunkPos) -> Vec<bool> {
+    let (from, to) = chunk_cells(grid, chunk);
+    (from.y..=to.y)
+        .flat_map(|y| (from.x..=to.x).map(move |x| (x, y)))
+        .map(|(x, y)| is_world_obstacle(grid, x, y))
+        .collect()
+}
+
+// The outlines of the chunk's colliding cells as one polyline collider, `None` if it has none. The
+// chunks along the bottom and left of the world take in the row and column outside it, so outlines
+// run along every edge.
+fn chunk_collider(grid: &SimulationGrid, chunk: ChunkPos) -> Option<Collider> {
+    let (mut from, to) = chunk_cells(grid, chunk);
+    if from.x == 0 {
+        from.x -= 1;
+    }
+    if from.y == 0 {
+        from.y -= 1;
+    }
+    let (mut points, mut segments) = (Vec::new(), Vec::new());
+    for outline in trace(from, to, |x, y| is_world_obstacle(grid, x, y)) {
+        let outline = simplify(outline, COLLIDER_TOLERANCE);
+        let (first, count) = (points.len() as u32, outline.points.len() as u32);
+        points.extend(outline.points);
+        segments.extend((1..count).map(|i| [first + i - 1, first + i]));
+        if outline.closed && count > 2 {
+            segments.push([first + count - 1, first]);
+        }
+    }
+    (!segments.is_empty()).then(|| Collider::polyline(points, Some(segments)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bodies::edit_bodies;
+    use crate::test_utils::WorldBuilder;
+
+    #[test]
+    fn bodies_are_left_out_of_the_world_colliders() {
+        let mut grid = WorldBuilder::new(16, 16).boxed().cell(3, 8, Particle::Sand).build();
+        let add = BodyOperation::Add {
+            position: (8.0, 8.0),
+            half_size: (2.0, 2.0),
+            material: Particle::Glass,
+            density: 0.5,
+        };
+        edit_bodies(&mut grid, &add, false);
+        assert!(is_world_obstacle(&grid, 3, 8) && is_world_obstacle(&grid, 8, 0));
+        assert!(is_world_obstacle(&grid, -1, 8) && is_world_obstacle(&grid, 8, 16));
+        assert!(!is_world_obstacle(&grid, 8, 8) && !is_world_obstacle(&grid, 4, 8));
+        assert!(chunk_collider(&grid, ChunkPos::default()).is_some());
+    }
+}
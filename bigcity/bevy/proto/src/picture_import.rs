@@ -0,0 +1,325 @@
+// --- IMPORTS ---
+use bevy::asset::LoadedFolder;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::Deserialize;
+
+use crate::Particle;
+use crate::levels::PaintRules;
+use crate::mods::mod_folder;
+use crate::pan_zoom::ctrl_held;
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{SimulationGrid, SimulationSet};
+
+// --- CONSTANTS ---
+// Bundled palettes live in this asset folder, and mods add theirs to the same-named folder.
+pub const PALETTE_FOLDER: &str = "palettes";
+const PALETTE_EXTENSION: &str = "palette.ron";
+// Without a palette, pictures are turned into worlds from these materials, each pixel becoming
+// the one whose color is closest. Lasers, foam and radioactive materials are left out so that a
+// red or pale pixel doesn't turn into something that fires, vanishes or decays.
+const PICTURE_MATERIALS: [Particle; 14] = [
+    Particle::Air,
+    Particle::Bedrock,
+    Particle::Sand,
+    Particle::Water,
+    Particle::Mirror,
+    Particle::Glass,
+    Particle::Turbine,
+    Particle::Snow,
+    Particle::Ice,
+    Particle::Dust,
+    Particle::Salt,
+    Particle::Crystal,
+    Particle::IronPowder,
+    Particle::Lead,
+];
+// Pixels more transparent than this become air.
+const OPAQUE_ALPHA: u8 = 128;
+
+// --- PLUGIN ---
+
+// Pictures as worlds, for building terrain and test scenarios in a paint program: Ctrl+O opens a
+// window to load a PNG by its path, and pictures dropped on the window (see drops.rs) go the same
+// way. Every pixel becomes the material whose palette color is closest, and transparent pixels
+// become air. Palettes are RON files in `palettes` (and mods' `palettes`) pairing colors with
+// materials; without one, the materials' own colors are used. The picture is scaled to fit the
+// world and stood on its bottom edge, scaled to fill it and cropped, or stretched over it.
+pub struct PictureImportPlugin;
+
+impl Plugin for PictureImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ImportPalette>()
+            .register_asset_loader(RonAssetLoader::<ImportPalette>::new(&[PALETTE_EXTENSION]))
+            .init_resource::<PictureImport>()
+            .add_event::<ImportPicture>()
+            .add_systems(Startup, load_palettes)
+            .add_systems(Update, (toggle_picture_import, import_pictures.before(SimulationSet)))
+            .add_systems(EguiContextPass, draw_picture_import);
+    }
+}
+
+// --- TYPES ---
+
+// How a picture of another shape is laid over the world.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+enum PictureFit {
+    // Scaled to fit inside the world, keeping its proportions, and stood on the bottom edge.
+    #[default]
+    Fit,
+    // Scaled to cover the whole world, keeping its proportions, with what sticks out cropped.
+    Fill,
+    // Stretched to the world's size.
+    Stretch,
+}
+
+impl PictureFit {
+    const ALL: [PictureFit; 3] = [PictureFit::Fit, PictureFit::Fill, PictureFit::Stretch];
+
+    fn label(self) -> &'static str {
+        match self {
+            PictureFit::Fit => "Fit, standing on the bottom",
+            PictureFit::Fill => "Fill, cropped",
+            PictureFit::Stretch => "Stretch",
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+struct PaletteColor {
+    color: (u8, u8, u8),
+    particle: Particle,
+}
+
+// --- ASSETS ---
+
+// Which material each color of a picture becomes, e.g. "Terrain".
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+struct ImportPalette {
+    name: String,
+    colors: Vec<PaletteColor>,
+}
+
+// --- EVENTS ---
+
+// Replaces the world with the picture at this path, as the window is set to.
+#[derive(Event, Debug, Clone)]
+struct ImportPicture(String);
+
+// --- RESOURCES ---
+
+// The bundled palettes and those mods add.
+#[derive(Resource)]
+struct BundledPalettes([Handle<LoadedFolder>; 2]);
+
+// The import window, and how pictures are turned into worlds.
+#[derive(Resource, Default)]
+struct PictureImport {
+    open: bool,
+    path: String,
+    // The selected palette's name; `None` goes by the materials' colors.
+    palette: Option<String>,
+    fit: PictureFit,
+}
+
+// --- SYSTEM PARAM ---
+
+// Turns pictures into worlds the way the import window is set to.
+#[derive(SystemParam)]
+pub struct PictureImporter<'w> {
+    import: Res<'w, PictureImport>,
+    bundled: Option<Res<'w, BundledPalettes>>,
+    folders: Res<'w, Assets<LoadedFolder>>,
+    palettes: Res<'w, Assets<ImportPalette>>,
+}
+
+impl PictureImporter<'_> {
+    // Replaces the world with the picture, and says how it was read.
+    pub fn to_world(&self, picture: &image::DynamicImage, grid: &mut SimulationGrid) -> String {
+        let palette = self.palette();
+        let colors: Vec<(Particle, [u8; 3])> = match palette {
+            Some(palette) => palette
+                .colors
+                .iter()
+                .map(|entry| (entry.particle, <[u8; 3]>::from(entry.color)))
+                .collect(),
+            None => PICTURE_MATERIALS
+                .iter()
+                .map(|&p| {
+                    let [r, g, b, _] = p.color().to_srgba().to_u8_array();
+                    (p, [r, g, b])
+                })
+                .collect(),
+        };
+        picture_to_world(picture, grid, &colors, self.import.fit);
+        let palette = palette.map_or("the materials' colors", |palette| palette.name.as_str());
+        format!("built a world from the picture by {}", palette)
+    }
+
+    fn palette(&self) -> Option<&ImportPalette> {
+        let name = self.import.palette.as_ref()?;
+        let all = all_palettes(self.bundled.as_deref(), &self.folders, &self.palettes);
+        all.into_iter().find(|palette| palette.name == *name)
+    }
+}
+
+// --- SYSTEMS ---
+
+fn load_palettes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BundledPalettes([
+        asset_server.load_folder(PALETTE_FOLDER),
+        asset_server.load_folder(mod_folder(PALETTE_FOLDER)),
+    ]));
+}
+
+fn toggle_picture_import(keys: Res<ButtonInput<KeyCode>>, mut import: ResMut<PictureImport>) {
+    if ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyO) {
+        import.open = !import.open;
+    }
+}
+
+fn import_pictures(
+    mut requests: EventReader<ImportPicture>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+    importer: PictureImporter,
+) {
+    for ImportPicture(path) in requests.read() {
+        if rules.protect_world {
+            info!("The world can't be replaced during a level");
+            continue;
+        }
+        match image::open(path) {
+            Ok(picture) => {
+                info!("Imported {:?}: {}", path, importer.to_world(&picture, &mut grid))
+            }
+            Err(err) => warn!("Could not import {:?}: {}", path, err),
+        }
+    }
+}
+
+fn draw_picture_import(
+    mut contexts: EguiContexts,
+    mut import: ResMut<PictureImport>,
+    folders: Res<Assets<LoadedFolder>>,
+    palettes: Res<Assets<ImportPalette>>,
+    bundled: Option<Res<BundledPalettes>>,
+    mut requests: EventWriter<ImportPicture>,
+) {
+    if !import.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let all = all_palettes(bundled.as_deref(), &folders, &palettes);
+
+    let PictureImport {
+        open,
+        path,
+        palette,
+        fit,
+    } = &mut *import;
+    egui::Window::new("Import picture").open(open).resizable(false).show(ctx, |ui| {
+        egui::Grid::new("picture_import").num_columns(2).show(ui, |ui| {
+            ui.label("Picture (PNG)");
+            ui.text_edit_singleline(path);
+            ui.end_row();
+
+            ui.label("Palette");
+            let selected = palette.as_deref().unwrap_or("Material colors");
+            egui::ComboBox::from_id_salt("picture_palette").selected_text(selected).show_ui(
+                ui,
+                |ui| {
+                    ui.selectable_value(palette, None, "Material colors");
+                    for option in &all {
+                        ui.selectable_value(palette, Some(option.name.clone()), &option.name);
+                    }
+                },
+            );
+            ui.end_row();
+
+            ui.label("Shape");
+            egui::ComboBox::from_id_salt("picture_fit").selected_text(fit.label()).show_ui(
+                ui,
+                |ui| {
+                    for option in PictureFit::ALL {
+                        ui.selectable_value(fit, option, option.label());
+                    }
+                },
+            );
+            ui.end_row();
+        });
+
+        // The chosen palette's colors, with the material each becomes on hover.
+        if let Some(chosen) = all.iter().find(|option| Some(&option.name) == palette.as_ref()) {
+            ui.horizontal_wrapped(|ui| {
+                for entry in &chosen.colors {
+                    let (r, g, b) = entry.color;
+                    let (rect, response) =
+                        ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+                    ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+                    response.on_hover_text(format!("{:?}", entry.particle));
+                }
+            });
+        }
+        let named = !path.trim().is_empty();
+        if ui.add_enabled(named, egui::Button::new("Import")).clicked() {
+            requests.write(ImportPicture(path.trim().to_string()));
+        }
+        ui.weak("Pictures dropped on the window are imported the same way.");
+    });
+}
+
+// --- HELPERS ---
+
+// The bundled and modded palettes that have loaded, sorted by name.
+fn all_palettes<'a>(
+    bundled: Option<&BundledPalettes>,
+    folders: &'a Assets<LoadedFolder>,
+    palettes: &'a Assets<ImportPalette>,
+) -> Vec<&'a ImportPalette> {
+    let mut all: Vec<&ImportPalette> = bundled
+        .iter()
+        .flat_map(|bundled| &bundled.0)
+        .filter_map(|folder| folders.get(folder))
+        .flat_map(|folder| &folder.handles)
+        .filter_map(|handle| palettes.get(&handle.clone().typed::<ImportPalette>()))
+        .collect();
+    all.sort_by(|a, b| a.name.cmp(&b.name));
+    all
+}
+
+// Lays the picture over the world as `fit` says, and turns every pixel into the material whose
+// color in `colors` is closest.
+fn picture_to_world(
+    picture: &image::DynamicImage,
+    grid: &mut SimulationGrid,
+    colors: &[(Particle, [u8; 3])],
+    fit: PictureFit,
+) {
+    let (width, height) = (grid.width(), grid.height());
+    let filter = image::imageops::FilterType::Nearest;
+    let fitted = match fit {
+        PictureFit::Fit => picture.resize(width, height, filter),
+        PictureFit::Fill => picture.resize_to_fill(width, height, filter),
+        PictureFit::Stretch => picture.resize_exact(width, height, filter),
+    }
+    .to_rgba8();
+    let left = (width as i32 - fitted.width() as i32) / 2;
+    grid.clear();
+    for (x, y, pixel) in fitted.enumerate_pixels() {
+        let [r, g, b, a] = pixel.0;
+        let distance = |[pr, pg, pb]: [u8; 3]| {
+            let d = |c: u8, p: u8| (c as i32 - p as i32).pow(2);
+            d(r, pr) + d(g, pg) + d(b, pb)
+        };
+        let particle = if a < OPAQUE_ALPHA {
+            Particle::Air
+        } else {
+            colors.iter().min_by_key(|(_, color)| distance(*color)).map_or(Particle::Air, |p| p.0)
+        };
+        // Picture rows run top-down, grid rows bottom-up.
+        let row = fitted.height() as i32 - 1 - y as i32;
+        grid.set(left + x as i32, row, particle);
+    }
+}
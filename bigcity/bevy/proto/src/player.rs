@@ -0,0 +1,644 @@
+// --- IMPORTS ---
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
+use bevy::input::mouse::MouseWheel;
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::input::EguiWantsInput;
+
+use crate::access::SimulationAccess;
+use crate::bindings::{InputBindings, any_held};
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::pan_zoom::ctrl_held;
+use crate::{MaterialClass, Particle, BRUSH_FLOW, BRUSH_SIZE};
+
+// --- CONSTANTS ---
+pub const MIN_BRUSH_SIZE: i32 = 0;
+pub const MAX_BRUSH_SIZE: i32 = 32;
+// Whether touches paint and pan: on phones and tablets, and in the browser, which may be either.
+pub const TOUCH_INPUT: bool = cfg!(any(feature = "mobile", target_arch = "wasm32"));
+// How fast the gamepad's virtual cursor travels at full stick deflection, in logical pixels/sec.
+const VIRTUAL_CURSOR_SPEED: f32 = 600.0;
+const VIRTUAL_CURSOR_SIZE: f32 = 12.0;
+// How long the smoothing stabilizer takes to close most of the gap to the cursor, in seconds.
+const SMOOTHING_TIME: f32 = 0.08;
+// How far the cursor runs ahead of the brush on the pull-string stabilizer, in logical pixels.
+const STRING_LENGTH: f32 = 24.0;
+// The spray densities Shift+J steps through, as the share of the brush's cells filled per pass.
+const SPRAY_DENSITIES: [f32; 4] = [1.0, 0.5, 0.25, 0.1];
+// The most air a pocket the brush fills can hold; anything bigger counts as open air.
+const MAX_POCKET_CELLS: usize = 8192;
+const PLAYER_COLORS: [Color; 4] = [
+    Color::srgb(1.0, 1.0, 1.0),
+    Color::srgb(1.0, 0.4, 0.4),
+    Color::srgb(0.4, 1.0, 0.4),
+    Color::srgb(0.4, 0.6, 1.0),
+];
+
+// --- PLUGIN ---
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_mouse_player).add_systems(
+            Update,
+            (
+                handle_gamepad_connections,
+                (
+                    update_mouse_cursor,
+                    update_gamepad_cursor,
+                    switch_particle_type,
+                    resize_brush,
+                    cycle_brush_shape,
+                    cycle_stabilizer,
+                ),
+                (stabilize_strokes, sync_virtual_cursor_markers),
+            )
+                .chain()
+                .in_set(PlayerInputSet),
+        );
+    }
+}
+
+// Everything that reads raw devices and writes per-player state runs in this set,
+// so consumers like painting can order themselves after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlayerInputSet;
+
+// --- COMPONENTS ---
+
+// One local player. Each player owns its own input source, material and brush, so several
+// people can paint into the same world at once.
+#[derive(Component)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct Player {
+    pub index: usize,
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub enum InputSource {
+    Mouse,
+    Gamepad(Entity),
+}
+
+#[derive(Component, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+pub struct SelectedParticle(pub Particle);
+
+#[derive(Component)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component))]
+pub struct Brush {
+    pub size: i32,
+    pub shape: BrushShape,
+    // The share of the brush's cells a pass fills with powder, so sand can be sprayed on bit by bit
+    // rather than laid down as a block. Other materials always fill the whole brush.
+    pub density: f32,
+    // Cells painted per second while the button is held.
+    pub flow: f32,
+    // Cells earned but not painted yet in the current stroke, and cells painted in it so far.
+    pub budget: f32,
+    pub painted: u32,
+    // Where the brush was painted last frame in the current stroke, so a fast stroke is filled in
+    // all the way from there instead of leaving a trail of separate stamps.
+    pub last_cell: Option<CellPos>,
+    pub stabilizer: Stabilizer,
+    pub layer: PaintLayer,
+    pub conform: BrushConform,
+    pub tool: PaintTool,
+    // The line, rectangle or fill the button is held down for, which the freehand brush leaves
+    // alone (see paint_tools.rs).
+    pub drag: Option<ShapeDrag>,
+}
+
+// The cells a brush covers around its center, `size` cells out in every direction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum BrushShape {
+    Square,
+    Circle,
+    // A single row, for floors and ledges.
+    Line,
+}
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Square, BrushShape::Circle, BrushShape::Line];
+
+    fn next(self) -> Self {
+        match self {
+            BrushShape::Square => BrushShape::Circle,
+            BrushShape::Circle => BrushShape::Line,
+            BrushShape::Line => BrushShape::Square,
+        }
+    }
+
+    // Every cell a brush of this shape and size covers around `center`, row by row.
+    pub fn cells(self, center: CellPos, size: i32) -> impl Iterator<Item = CellPos> {
+        let rows = if self == BrushShape::Line { 0 } else { size };
+        (-rows..=rows)
+            .flat_map(move |y| (-size..=size).map(move |x| IVec2::new(x, y)))
+            // Cells whose centers are within half a cell of the circle's edge count as inside.
+            .filter(move |offset| {
+                self != BrushShape::Circle || offset.length_squared() <= size * size + size
+            })
+            .map(move |offset| center + offset)
+    }
+
+    pub fn area(self, size: i32) -> usize {
+        self.cells(CellPos::default(), size).count()
+    }
+
+    // Every cell the brush sweeps over moving from `from` to `to`, each once, the cells around
+    // `from` first.
+    pub fn swept_cells(self, from: CellPos, to: CellPos, size: i32) -> Vec<CellPos> {
+        if from == to {
+            return self.cells(to, size).collect();
+        }
+        let mut seen = HashSet::new();
+        from.line_to(to)
+            .into_iter()
+            .flat_map(|center| self.cells(center, size))
+            .filter(|&cell| seen.insert(cell))
+            .collect()
+    }
+}
+
+// How the brush follows the cursor while painting, for steadier lines than the hand holding it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum Stabilizer {
+    // The brush is wherever the cursor is.
+    Off,
+    // The brush eases towards the cursor, closing most of the gap in this many seconds.
+    Smooth(f32),
+    // The brush is pulled along on a string this many pixels long and stays put while the cursor
+    // moves within it, which takes out jitter without lagging behind on long strokes.
+    String(f32),
+}
+
+impl Stabilizer {
+    fn next(self) -> Self {
+        match self {
+            Stabilizer::Off => Stabilizer::Smooth(SMOOTHING_TIME),
+            Stabilizer::Smooth(_) => Stabilizer::String(STRING_LENGTH),
+            Stabilizer::String(_) => Stabilizer::Off,
+        }
+    }
+
+    // Where the brush goes this frame, from where it was and where the cursor is.
+    fn follow(self, brush: Vec2, cursor: Vec2, delta_secs: f32) -> Vec2 {
+        match self {
+            Stabilizer::Off => cursor,
+            // Exponential, so the brush moves the same way at any frame rate.
+            Stabilizer::Smooth(time) => {
+                brush.lerp(cursor, 1.0 - (-delta_secs * 3.0 / time.max(f32::EPSILON)).exp())
+            }
+            Stabilizer::String(length) => {
+                let slack = brush.distance(cursor) - length;
+                if slack > 0.0 { brush.move_towards(cursor, slack) } else { brush }
+            }
+        }
+    }
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            size: BRUSH_SIZE,
+            shape: BrushShape::Square,
+            density: 1.0,
+            flow: BRUSH_FLOW,
+            budget: 0.0,
+            painted: 0,
+            last_cell: None,
+            stabilizer: Stabilizer::Off,
+            layer: PaintLayer::Foreground,
+            conform: BrushConform::Anywhere,
+            tool: PaintTool::Freehand,
+            drag: None,
+        }
+    }
+}
+
+// Which layer of the world a brush paints: the particles, or the walls behind them (see
+// backdrop.rs).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum PaintLayer {
+    #[default]
+    Foreground,
+    Background,
+}
+
+impl PaintLayer {
+    pub const ALL: [PaintLayer; 2] = [PaintLayer::Foreground, PaintLayer::Background];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaintLayer::Foreground => "Particles",
+            PaintLayer::Background => "Background walls",
+        }
+    }
+}
+
+// Which of its cells a freehand brush paints, going by what is already there: anywhere, only
+// air touching something that stands (solids and powders), for moss on rocks or snow dusting
+// their tops, or only air closed off from the world's edges, for filling caves and cups without
+// spilling over their rims. The eraser always erases the whole brush.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum BrushConform {
+    #[default]
+    Anywhere,
+    Surfaces,
+    Pockets,
+}
+
+impl BrushConform {
+    pub const ALL: [BrushConform; 3] =
+        [BrushConform::Anywhere, BrushConform::Surfaces, BrushConform::Pockets];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BrushConform::Anywhere => "Anywhere",
+            BrushConform::Surfaces => "Onto surfaces",
+            BrushConform::Pockets => "Into enclosed pockets",
+        }
+    }
+
+    // Which of `cells` a brush conforming like this paints, with the world as `access` has it.
+    pub fn conform(self, cells: Vec<CellPos>, access: &SimulationAccess) -> Vec<CellPos> {
+        let air = |cell| access.particle(cell) == Some(Particle::Air);
+        match self {
+            BrushConform::Anywhere => cells,
+            BrushConform::Surfaces => cells
+                .into_iter()
+                .filter(|&cell| {
+                    air(cell)
+                        && access.neighbors(cell).any(|(_, particle)| {
+                            matches!(particle.class(), MaterialClass::Solid | MaterialClass::Powder)
+                        })
+                })
+                .collect(),
+            BrushConform::Pockets => {
+                // Cells of one pocket share the answer, so each pocket is only searched once.
+                let (mut enclosed, mut open) = (HashSet::new(), HashSet::new());
+                cells
+                    .into_iter()
+                    .filter(|&cell| {
+                        if !air(cell) || open.contains(&cell) {
+                            return false;
+                        }
+                        if enclosed.contains(&cell) {
+                            return true;
+                        }
+                        let Some(pocket) = access.pocket(cell, MAX_POCKET_CELLS) else {
+                            return false;
+                        };
+                        let found = if pocket.enclosed { &mut enclosed } else { &mut open };
+                        found.extend(pocket.cells);
+                        pocket.enclosed
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// What pressing the button does: paint freehand strokes with the brush, drag out a straight line
+// or a rectangle's outline with it, or fill the region of one material under the cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum PaintTool {
+    #[default]
+    Freehand,
+    Line,
+    Rectangle,
+    Fill,
+}
+
+impl PaintTool {
+    pub const ALL: [PaintTool; 4] =
+        [PaintTool::Freehand, PaintTool::Line, PaintTool::Rectangle, PaintTool::Fill];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaintTool::Freehand => "Freehand",
+            PaintTool::Line => "Line",
+            PaintTool::Rectangle => "Rectangle",
+            PaintTool::Fill => "Bucket fill",
+        }
+    }
+}
+
+// A shape being dragged out with `tool`, from the cell the button went down on to the one under
+// the cursor now.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct ShapeDrag {
+    pub tool: PaintTool,
+    pub from: CellPos,
+    pub to: CellPos,
+}
+
+// Where the player is pointing this frame, in window coordinates, and whether they paint, and
+// with the eraser rather than their material. While painting, `stroke` is where the brush is,
+// which trails the cursor when a stabilizer is on.
+#[derive(Component, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Component, Default))]
+pub struct PlayerCursor {
+    pub position: Option<Vec2>,
+    pub painting: bool,
+    pub erasing: bool,
+    pub stroke: Option<Vec2>,
+}
+
+impl PlayerCursor {
+    // What the player paints with.
+    pub fn paint(&self, selected: &SelectedParticle) -> Particle {
+        if self.erasing { Particle::Air } else { selected.0 }
+    }
+}
+
+// The on-screen marker for a cursor that the OS doesn't draw for us.
+#[derive(Component)]
+struct VirtualCursorMarker(Entity);
+
+// --- SYSTEMS ---
+
+fn spawn_mouse_player(mut commands: Commands) {
+    commands.spawn(player_bundle(0, InputSource::Mouse));
+}
+
+fn player_bundle(index: usize, source: InputSource) -> impl Bundle {
+    (
+        Player { index },
+        source,
+        SelectedParticle(Particle::Sand),
+        Brush::default(),
+        PlayerCursor::default(),
+    )
+}
+
+fn handle_gamepad_connections(
+    mut commands: Commands,
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    q_players: Query<(Entity, &Player, &InputSource)>,
+    q_markers: Query<(Entity, &VirtualCursorMarker)>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+) {
+    for event in connection_events.read() {
+        match &event.connection {
+            GamepadConnection::Connected { name, .. } => {
+                if q_players
+                    .iter()
+                    .any(|(_, _, source)| *source == InputSource::Gamepad(event.gamepad))
+                {
+                    continue;
+                }
+
+                // Reuse the lowest free slot so a reconnecting pad keeps its color.
+                let index = (0..)
+                    .find(|i| q_players.iter().all(|(_, p, _)| p.index != *i))
+                    .unwrap_or_default();
+                let start = q_window
+                    .single()
+                    .map(|w| Vec2::new(w.width(), w.height()) / 2.0)
+                    .unwrap_or_default();
+
+                let player = commands
+                    .spawn(player_bundle(index, InputSource::Gamepad(event.gamepad)))
+                    .insert(PlayerCursor {
+                        position: Some(start),
+                        ..default()
+                    })
+                    .id();
+                commands.spawn((
+                    VirtualCursorMarker(player),
+                    Node {
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(VIRTUAL_CURSOR_SIZE),
+                        height: Val::Px(VIRTUAL_CURSOR_SIZE),
+                        border: UiRect::all(Val::Px(2.0)),
+                        ..default()
+                    },
+                    BorderColor(PLAYER_COLORS[index % PLAYER_COLORS.len()]),
+                ));
+                info!("Player {} joined with gamepad '{}'", index + 1, name);
+            }
+            GamepadConnection::Disconnected => {
+                for (entity, player, source) in &q_players {
+                    if *source != InputSource::Gamepad(event.gamepad) {
+                        continue;
+                    }
+                    for (marker, VirtualCursorMarker(owner)) in &q_markers {
+                        if *owner == entity {
+                            commands.entity(marker).despawn();
+                        }
+                    }
+                    commands.entity(entity).despawn();
+                    info!("Player {} left", player.index + 1);
+                }
+            }
+        }
+    }
+}
+
+fn update_mouse_cursor(
+    buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    touches: Res<Touches>,
+    egui_input: Res<EguiWantsInput>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut q_players: Query<(&InputSource, &mut PlayerCursor)>,
+) {
+    let Ok(window) = q_window.single() else { return };
+
+    for (source, mut cursor) in &mut q_players {
+        if *source != InputSource::Mouse {
+            continue;
+        }
+        // On touch screens one finger paints where it touches, and two pan and zoom the camera
+        // instead (see pan_zoom.rs). Egui doesn't swallow touches the way it does clicks, so a
+        // finger on a panel is kept from painting the world behind it here.
+        if TOUCH_INPUT
+            && let Some(position) = touches.first_pressed_position()
+        {
+            cursor.position = Some(position);
+            cursor.painting =
+                touches.iter().count() == 1 && !egui_input.wants_any_pointer_input();
+            continue;
+        }
+        cursor.position = window.cursor_position();
+        // Dragging with Ctrl held selects instead (see selection.rs).
+        cursor.painting = buttons.pressed(MouseButton::Left) && !ctrl_held(&keys);
+    }
+}
+
+fn update_gamepad_cursor(
+    time: Res<Time>,
+    bindings: Res<InputBindings>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    q_gamepads: Query<&Gamepad>,
+    mut q_players: Query<(&InputSource, &mut PlayerCursor)>,
+) {
+    let Ok(window) = q_window.single() else { return };
+    let bounds = Vec2::new(window.width(), window.height());
+
+    for (source, mut cursor) in &mut q_players {
+        let InputSource::Gamepad(entity) = *source else { continue };
+        let Ok(gamepad) = q_gamepads.get(entity) else { continue };
+
+        // Window coordinates grow downwards, the stick's y axis grows upwards.
+        let stick = bindings.gamepad.cursor.read(gamepad) * Vec2::new(1.0, -1.0);
+        let position = cursor.position.unwrap_or(bounds / 2.0)
+            + stick * VIRTUAL_CURSOR_SPEED * time.delta_secs();
+        cursor.position = Some(position.clamp(Vec2::ZERO, bounds));
+        cursor.erasing = any_held(gamepad, &bindings.gamepad.erase);
+        cursor.painting = cursor.erasing || any_held(gamepad, &bindings.gamepad.paint);
+    }
+}
+
+fn switch_particle_type(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    q_gamepads: Query<&Gamepad>,
+    mut q_players: Query<(&Player, &InputSource, &mut SelectedParticle)>,
+    mut sim_events: EventWriter<SimEvent>,
+) {
+    // Ctrl and Alt with a number key are camera bookmarks.
+    let bookmarking = keys.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::AltLeft,
+        KeyCode::AltRight,
+    ]);
+    for (player, source, mut selected) in &mut q_players {
+        let choice = match *source {
+            InputSource::Mouse if bookmarking => None,
+            InputSource::Mouse => bindings.picked_material(&keys),
+            InputSource::Gamepad(entity) => {
+                let Ok(gamepad) = q_gamepads.get(entity) else { continue };
+                (bindings.gamepad.materials.iter())
+                    .find(|(button, _)| gamepad.just_pressed(button.0))
+                    .map(|&(_, particle)| particle)
+            }
+        };
+
+        if let Some(particle) = choice {
+            selected.0 = particle;
+            info!("Player {} switched to {:?}", player.index + 1, particle);
+            sim_events.write(SimEvent::ParticleSelected {
+                player: player.index,
+                particle,
+            });
+        }
+    }
+}
+
+fn resize_brush(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut wheel: EventReader<MouseWheel>,
+    q_gamepads: Query<&Gamepad>,
+    mut q_players: Query<(&Player, &InputSource, &mut Brush)>,
+    mut sim_events: EventWriter<SimEvent>,
+) {
+    // Each notch of the wheel is one size step, however far a touchpad scrolls in one event. With
+    // Ctrl held the wheel zooms the camera instead.
+    let scrolled: i32 = wheel.read().map(|event| event.y.signum() as i32).sum();
+    let scrolled = if ctrl_held(&keys) { 0 } else { scrolled };
+    for (player, source, mut brush) in &mut q_players {
+        let delta = match *source {
+            InputSource::Mouse => {
+                keys.just_pressed(bindings.grow_brush.0) as i32
+                    - keys.just_pressed(bindings.shrink_brush.0) as i32
+                    + scrolled
+            }
+            InputSource::Gamepad(entity) => {
+                let Ok(gamepad) = q_gamepads.get(entity) else { continue };
+                gamepad.just_pressed(bindings.gamepad.grow_brush.0) as i32
+                    - gamepad.just_pressed(bindings.gamepad.shrink_brush.0) as i32
+            }
+        };
+        let size = (brush.size + delta).clamp(MIN_BRUSH_SIZE, MAX_BRUSH_SIZE);
+        if size != brush.size {
+            brush.size = size;
+            sim_events.write(SimEvent::BrushResized {
+                player: player.index,
+                size,
+            });
+        }
+    }
+}
+
+// The brush shape key (J) switches the mouse player's brush between square, circle and line;
+// with Shift it steps through the spray densities for powders.
+fn cycle_brush_shape(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut q_players: Query<(&Player, &InputSource, &mut Brush)>,
+) {
+    if !keys.just_pressed(bindings.brush_shape.0) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    for (player, source, mut brush) in &mut q_players {
+        if *source != InputSource::Mouse {
+            continue;
+        }
+        if shift {
+            let current = SPRAY_DENSITIES.iter().position(|&d| d <= brush.density).unwrap_or(0);
+            brush.density = SPRAY_DENSITIES[(current + 1) % SPRAY_DENSITIES.len()];
+            info!("Player {} spray density: {}%", player.index + 1, brush.density * 100.0);
+        } else {
+            brush.shape = brush.shape.next();
+            info!("Player {} brush shape: {:?}", player.index + 1, brush.shape);
+        }
+    }
+}
+
+// The stabilizer key (K) switches the mouse player's stabilizer between off, smoothing and
+// pull-string.
+fn cycle_stabilizer(
+    keys: Res<ButtonInput<KeyCode>>,
+    bindings: Res<InputBindings>,
+    mut q_players: Query<(&Player, &InputSource, &mut Brush)>,
+) {
+    if !keys.just_pressed(bindings.stabilizer.0) {
+        return;
+    }
+    for (player, source, mut brush) in &mut q_players {
+        if *source == InputSource::Mouse {
+            brush.stabilizer = brush.stabilizer.next();
+            info!("Player {} stabilizer: {:?}", player.index + 1, brush.stabilizer);
+        }
+    }
+}
+
+// Moves every painting brush after its cursor. A stroke starts right under the cursor and ends
+// when the button is let go.
+fn stabilize_strokes(time: Res<Time>, mut q_players: Query<(&Brush, &mut PlayerCursor)>) {
+    for (brush, mut cursor) in &mut q_players {
+        let (true, Some(position)) = (cursor.painting, cursor.position) else {
+            cursor.stroke = None;
+            continue;
+        };
+        let from = cursor.stroke.unwrap_or(position);
+        cursor.stroke = Some(brush.stabilizer.follow(from, position, time.delta_secs()));
+    }
+}
+
+fn sync_virtual_cursor_markers(
+    q_players: Query<&PlayerCursor>,
+    mut q_markers: Query<(&VirtualCursorMarker, &mut Node)>,
+) {
+    for (VirtualCursorMarker(owner), mut node) in &mut q_markers {
+        let Some(position) = q_players.get(*owner).ok().and_then(|c| c.position) else {
+            continue;
+        };
+        node.left = Val::Px(position.x - VIRTUAL_CURSOR_SIZE / 2.0);
+        node.top = Val::Px(position.y - VIRTUAL_CURSOR_SIZE / 2.0);
+    }
+}
@@ -0,0 +1,114 @@
+// --- IMPORTS ---
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use bevy::prelude::*;
+use thiserror::Error;
+
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::{SimulationGrid, SimulationStats};
+use crate::snapshot::WorldSnapshot;
+
+// --- CONSTANTS ---
+const EXPORT_FOLDER: &str = "postcards";
+// The iTXt chunk holding the world, as a compressed RON snapshot.
+const WORLD_KEYWORD: &str = "falling-sand-world";
+// Every cell becomes a square of this many pixels.
+const PIXELS_PER_CELL: u32 = 2;
+
+// --- PLUGIN ---
+
+// Postcards are PNG pictures of the world that carry the world itself in a text chunk, so they
+// can be shared anywhere images go and dropped back onto the game (see `drops`) to load what they
+// show.
+pub struct PostcardPlugin;
+
+impl Plugin for PostcardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, export_postcard);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Error, Debug)]
+pub enum PostcardError {
+    #[error("could not write the picture: {0}")]
+    Encode(#[from] png::EncodingError),
+    #[error("not a readable PNG: {0}")]
+    Decode(#[from] png::DecodingError),
+    #[error("could not encode the world: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("the world in it is unreadable: {0}")]
+    Snapshot(#[from] ron::error::SpannedError),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("the picture carries no world")]
+    NoWorld,
+}
+
+// --- SYSTEMS ---
+
+// F5 saves a postcard of the current world; Ctrl+F5 is a quick-save and Alt+F5 saves outlines
+// (see svg.rs).
+fn export_postcard(
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+) {
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if ctrl_held(&keys) || alt || !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let Some(dir) = user_data_dir().map(|dir| dir.join(EXPORT_FOLDER)) else { return };
+    let path = dir.join(format!("postcard_{}.png", stats.tick));
+    let result = std::fs::create_dir_all(&dir)
+        .map_err(PostcardError::from)
+        .and_then(|_| write_postcard(&grid, &path));
+    match result {
+        Ok(()) => info!("Saved postcard to {:?}", path),
+        Err(err) => warn!("Could not save postcard to {:?}: {}", path, err),
+    }
+}
+
+// --- HELPERS ---
+
+// Draws the grid with every cell in its material's color, top row first, and stores a snapshot
+// of it alongside.
+fn write_postcard(grid: &SimulationGrid, path: &Path) -> Result<(), PostcardError> {
+    let (width, height) = (grid.width() * PIXELS_PER_CELL, grid.height() * PIXELS_PER_CELL);
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let cell = grid.get((x / PIXELS_PER_CELL) as i32, (y / PIXELS_PER_CELL) as i32);
+            let color = cell.unwrap_or_default().color().to_srgba().to_u8_array();
+            pixels.extend_from_slice(&color);
+        }
+    }
+
+    let mut world = png::text_metadata::ITXtChunk::new(
+        WORLD_KEYWORD,
+        ron::to_string(&WorldSnapshot::from_grid(grid))?,
+    );
+    world.compress_text()?;
+    world.compressed = true;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_text_chunk(&world)?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+// The world a postcard carries.
+pub fn read_postcard(path: &Path) -> Result<WorldSnapshot, PostcardError> {
+    let reader = png::Decoder::new(File::open(path)?).read_info()?;
+    let chunk = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == WORLD_KEYWORD);
+    let text = chunk.ok_or(PostcardError::NoWorld)?.get_text()?;
+    Ok(ron::from_str(&text)?)
+}
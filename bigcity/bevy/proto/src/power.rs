@@ -0,0 +1,87 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::coords::CellPos;
+use crate::quality::Effects;
+use crate::sim::{SimulationGrid, SimulationSet, SimulationStats};
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+const POWER_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+
+// --- PLUGIN ---
+pub struct PowerPlugin;
+
+impl Plugin for PowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_power_label)
+            .add_systems(Update, show_turbine_output.after(SimulationSet));
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct PowerLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_power_label(mut commands: Commands) {
+    commands.spawn((
+        PowerLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            left: Val::Px(5.0),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(POWER_COLOR),
+    ));
+}
+
+// Marks every spinning turbine cell with a spark sized by its signal, and totals the signal of
+// all turbines in the bottom left corner while any exist.
+fn show_turbine_output(
+    layout: Res<WorldLayout>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    effects: Res<Effects>,
+    mut q_label: Query<&mut Text, With<PowerLabel>>,
+    mut gizmos: Gizmos,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    if stats.count(Particle::Turbine) == 0 {
+        if !label.0.is_empty() {
+            label.0.clear();
+        }
+        return;
+    }
+
+    let width = grid.width() as usize;
+    let (mut total, mut spinning) = (0u32, 0u32);
+    for (i, _) in grid.cells().iter().enumerate().filter(|(_, p)| **p == Particle::Turbine) {
+        let cell = CellPos::new((i % width) as i32, (i / width) as i32);
+        let signal = grid.signal(cell.x, cell.y);
+        if signal == 0 {
+            continue;
+        }
+        total += signal as u32;
+        spinning += 1;
+        if !effects.decorations {
+            continue;
+        }
+        let center = layout.cell_to_world(cell.center());
+        gizmos.circle_2d(*center, 0.5 + 1.5 * signal as f32 / u8::MAX as f32, POWER_COLOR);
+    }
+    label.0 = format!(
+        "Power: {} ({} of {} turbine cells spinning)",
+        total,
+        spinning,
+        stats.count(Particle::Turbine)
+    );
+}
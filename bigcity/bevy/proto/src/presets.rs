@@ -0,0 +1,186 @@
+// --- IMPORTS ---
+use bevy::asset::LoadedFolder;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::mods::mod_folder;
+use crate::persist::{file_stem, load_user_ron, save_user_ron, user_data_dir};
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{SimParams, SimulationTickRate};
+
+// --- CONSTANTS ---
+// Bundled presets live in this asset folder, the player's own in the same-named folder in the
+// user data directory.
+pub const PRESET_FOLDER: &str = "presets";
+const PRESET_EXTENSION: &str = "preset.ron";
+
+// --- PLUGIN ---
+pub struct PresetsPlugin;
+
+impl Plugin for PresetsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ParamsPreset>()
+            .register_asset_loader(RonAssetLoader::<ParamsPreset>::new(&[PRESET_EXTENSION]))
+            .add_event::<ApplyPreset>()
+            .add_event::<SavePreset>()
+            .insert_resource(UserPresets(load_user_presets()))
+            .init_resource::<ActivePreset>()
+            .add_systems(Startup, load_bundled_presets)
+            .add_systems(Update, (save_presets, apply_presets).chain());
+    }
+}
+
+// --- ASSETS ---
+
+// A named set of simulation parameters, e.g. "Moon gravity", and the tick rate to run them at,
+// if it sets one.
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(type_path = false, Serialize, Deserialize))]
+pub struct ParamsPreset {
+    pub name: String,
+    pub params: SimParams,
+    #[serde(default)]
+    pub ticks_per_second: Option<f32>,
+}
+
+impl ParamsPreset {
+    // Whether the preset leaves `tick_rate` as it is.
+    pub fn sets(&self, tick_rate: SimulationTickRate) -> bool {
+        self.ticks_per_second.is_none_or(|rate| rate == tick_rate.ticks_per_second)
+    }
+}
+
+// --- EVENTS ---
+
+// Switches the simulation to the preset with this name. The parameters panel sends these, and so
+// can anything else that takes commands.
+#[derive(Event, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct ApplyPreset(pub String);
+
+// Saves the current parameters as a user preset with this name, replacing any user preset that
+// already has it.
+#[derive(Event, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct SavePreset(pub String);
+
+// --- RESOURCES ---
+
+// The presets that ship with the game and those mods add.
+#[derive(Resource)]
+struct BundledPresets([Handle<LoadedFolder>; 2]);
+
+#[derive(Resource, Default)]
+struct UserPresets(Vec<ParamsPreset>);
+
+// The preset applied last, with the parameters it set, so later edits on top of it show.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ActivePreset(pub Option<ParamsPreset>);
+
+// --- SYSTEM PARAM ---
+
+// Every preset there is: the bundled and modded ones, sorted by name, then the player's in file order.
+#[derive(SystemParam)]
+pub struct Presets<'w> {
+    bundled: Res<'w, BundledPresets>,
+    folders: Res<'w, Assets<LoadedFolder>>,
+    assets: Res<'w, Assets<ParamsPreset>>,
+    user: Res<'w, UserPresets>,
+}
+
+impl Presets<'_> {
+    pub fn all(&self) -> Vec<&ParamsPreset> {
+        let mut bundled: Vec<&ParamsPreset> = self
+            .bundled
+            .0
+            .iter()
+            .filter_map(|folder| self.folders.get(folder))
+            .flat_map(|folder| &folder.handles)
+            .filter_map(|handle| self.assets.get(&handle.clone().typed::<ParamsPreset>()))
+            .collect();
+        bundled.sort_by(|a, b| a.name.cmp(&b.name));
+        bundled.extend(&self.user.0);
+        bundled
+    }
+}
+
+// --- SYSTEMS ---
+
+fn load_bundled_presets(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BundledPresets([
+        asset_server.load_folder(PRESET_FOLDER),
+        asset_server.load_folder(mod_folder(PRESET_FOLDER)),
+    ]));
+}
+
+fn save_presets(
+    mut requests: EventReader<SavePreset>,
+    params: Res<SimParams>,
+    tick_rate: Res<SimulationTickRate>,
+    mut user: ResMut<UserPresets>,
+    mut active: ResMut<ActivePreset>,
+) {
+    for SavePreset(name) in requests.read() {
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+        let preset = ParamsPreset {
+            name: name.to_string(),
+            params: params.clone(),
+            ticks_per_second: Some(tick_rate.ticks_per_second),
+        };
+        save_user_ron(&preset_file(name), &preset);
+        match user.0.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = preset.clone(),
+            None => user.0.push(preset.clone()),
+        }
+        info!("Saved preset '{}'", name);
+        active.0 = Some(preset);
+    }
+}
+
+fn apply_presets(
+    mut requests: EventReader<ApplyPreset>,
+    presets: Presets,
+    mut params: ResMut<SimParams>,
+    mut tick_rate: ResMut<SimulationTickRate>,
+    mut active: ResMut<ActivePreset>,
+) {
+    for ApplyPreset(name) in requests.read() {
+        // The player's presets shadow bundled ones of the same name.
+        let Some(preset) = presets.all().into_iter().rev().find(|p| p.name == *name) else {
+            warn!("No preset called '{}'", name);
+            continue;
+        };
+        *params = preset.params.clone();
+        if let Some(ticks_per_second) = preset.ticks_per_second {
+            tick_rate.ticks_per_second = ticks_per_second;
+        }
+        active.0 = Some(preset.clone());
+        info!("Applied preset '{}'", name);
+    }
+}
+
+// --- HELPERS ---
+
+fn load_user_presets() -> Vec<ParamsPreset> {
+    let Some(dir) = user_data_dir().map(|dir| dir.join(PRESET_FOLDER)) else { return Vec::new() };
+    let Ok(entries) = std::fs::read_dir(&dir) else { return Vec::new() };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|file| file.ends_with(&format!(".{}", PRESET_EXTENSION)))
+        .collect();
+    files.sort();
+    files
+        .iter()
+        .filter_map(|file| load_user_ron(&format!("{}/{}", PRESET_FOLDER, file)))
+        .collect()
+}
+
+// The user data file a preset called `name` is saved to, e.g. "presets/moon_gravity.preset.ron".
+fn preset_file(name: &str) -> String {
+    format!("{}/{}.{}", PRESET_FOLDER, file_stem(name), PRESET_EXTENSION)
+}
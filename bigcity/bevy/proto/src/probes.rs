@@ -0,0 +1,134 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::access::{RayFilter, SimulationAccess};
+use crate::player::{Player, PlayerCursor, SelectedParticle};
+use crate::sim::SimulationSet;
+use crate::{MaterialClass, WorldView};
+
+// --- CONSTANTS ---
+const PROBE_COLOR: Color = Color::srgb(1.0, 0.4, 0.8);
+const PROBE_RANGE: f32 = 512.0;
+
+// --- PLUGIN ---
+pub struct ProbesPlugin;
+
+impl Plugin for ProbesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ProbeMode>()
+            .add_systems(Startup, spawn_probe_label)
+            .add_systems(Update, draw_ground_probes.after(SimulationSet));
+    }
+}
+
+// --- RESOURCES ---
+
+// What the ground probe stops at; Ctrl+Tab cycles through these.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Eq, Debug)]
+enum ProbeMode {
+    #[default]
+    Anything,
+    Solids,
+    Liquids,
+    Powders,
+    // The material the player has selected.
+    Selected,
+}
+
+impl ProbeMode {
+    fn next(self) -> Self {
+        match self {
+            ProbeMode::Anything => ProbeMode::Solids,
+            ProbeMode::Solids => ProbeMode::Liquids,
+            ProbeMode::Liquids => ProbeMode::Powders,
+            ProbeMode::Powders => ProbeMode::Selected,
+            ProbeMode::Selected => ProbeMode::Anything,
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct ProbeLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_probe_label(mut commands: Commands) {
+    commands.spawn((
+        ProbeLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(24.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(PROBE_COLOR),
+    ));
+}
+
+// Holding Ctrl drops a plumb line from each player's cursor to the first matching cell below it,
+// showing where things would land.
+fn draw_ground_probes(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut mode: ResMut<ProbeMode>,
+    view: WorldView,
+    q_players: Query<(&Player, &PlayerCursor, &SelectedParticle)>,
+    mut q_label: Query<&mut Text, With<ProbeLabel>>,
+    access: SimulationAccess,
+    mut gizmos: Gizmos,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if !label.0.is_empty() {
+            label.0.clear();
+        }
+        return;
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        *mode = mode.next();
+    }
+
+    let mut lines = Vec::new();
+    for (player, cursor, selected) in &q_players {
+        let Some(position) = cursor.position else { continue };
+        let filter = match *mode {
+            ProbeMode::Anything => RayFilter::Occupied,
+            ProbeMode::Solids => RayFilter::Solid,
+            ProbeMode::Liquids => RayFilter::Liquid,
+            ProbeMode::Powders => RayFilter::Class(MaterialClass::Powder),
+            ProbeMode::Selected => RayFilter::Material(selected.0),
+        };
+        let Some(cell) = view.cell_at(position) else { continue };
+        let origin = cell.center();
+        let start = *view.cell_to_world(origin);
+
+        let Some(hit) = access.raycast(origin, Vec2::NEG_Y, filter, PROBE_RANGE) else {
+            gizmos.line_2d(start, *view.cell_to_world(Vec2::new(origin.x, 0.0)), PROBE_COLOR);
+            lines.push(format!("P{}: nothing below", player.index + 1));
+            continue;
+        };
+
+        let end = *view.cell_to_world(hit.point);
+        gizmos.line_2d(start, end, PROBE_COLOR);
+        gizmos.line_2d(end, end + hit.normal.as_vec2() * 8.0, PROBE_COLOR);
+        let temperature = access.temperature(hit.cell).unwrap_or_default();
+        lines.push(format!(
+            "P{}: {:?} at ({}, {}), {:.0} cells down, {:.0} C",
+            player.index + 1,
+            hit.particle,
+            hit.cell.x,
+            hit.cell.y,
+            hit.distance,
+            temperature
+        ));
+    }
+    label.0 = format!("Probe: {:?} (Ctrl+Tab to change)\n{}", *mode, lines.join("\n"));
+}
@@ -0,0 +1,222 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::coords::CellPos;
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+const SLICE_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+// The chart's size in points along the slice and across it; the slice's rows or columns are
+// squeezed or stretched to fit.
+const CHART_LENGTH: f32 = 256.0;
+const CHART_DEPTH: f32 = 160.0;
+
+// --- PLUGIN ---
+
+// Density profile (D), for studying sediment layers and stratified liquids: D marks a slice's
+// first corner and then its opposite one, and a chart shows every row of the slice, top to bottom,
+// as a bar of how much of it each material fills, stacked in the materials' colors like a
+// spectrogram. It follows the world as it runs. Shift+D switches to a bar per column, hovering a
+// bar lists its counts, and Esc closes the chart.
+pub struct DensityProfilePlugin;
+
+impl Plugin for DensityProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DensityProfile>()
+            .add_systems(Update, (place_slice, draw_slice).chain().after(SimulationSet))
+            .add_systems(EguiContextPass, draw_profile);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum Axis {
+    #[default]
+    Rows,
+    Columns,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct DensityProfile {
+    // The first corner of the slice being marked.
+    corner: Option<CellPos>,
+    // The slice the chart shows, as inclusive corners.
+    slice: Option<(CellPos, CellPos)>,
+    axis: Axis,
+}
+
+// --- SYSTEMS ---
+
+fn place_slice(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    mut profile: ResMut<DensityProfile>,
+) {
+    // Ctrl+D is the daily challenge.
+    if ctrl_held(&keys) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::KeyD) && shift {
+        profile.axis = match profile.axis {
+            Axis::Rows => Axis::Columns,
+            Axis::Columns => Axis::Rows,
+        };
+        info!("Density profile by {:?}", profile.axis);
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        profile.corner = None;
+        profile.slice = None;
+        return;
+    }
+    if !keys.just_pressed(KeyCode::KeyD) {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    match profile.corner.take() {
+        None => {
+            profile.corner = Some(cell);
+            info!("Density profile: press D at the slice's opposite corner (Esc cancels)");
+        }
+        Some(corner) => {
+            profile.slice = Some((CellPos(corner.min(*cell)), CellPos(corner.max(*cell))));
+        }
+    }
+}
+
+fn draw_slice(profile: Res<DensityProfile>, view: WorldView, mut gizmos: Gizmos) {
+    // The slice being marked, from its first corner to the cursor, or else the one charted.
+    let slice = match (profile.corner, view.cursor_cell()) {
+        (Some(corner), Some(cell)) => {
+            Some((CellPos(corner.min(*cell)), CellPos(corner.max(*cell))))
+        }
+        _ => profile.slice,
+    };
+    let Some((min, max)) = slice else { return };
+    let rect = view.cells_to_world(min, max);
+    gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), SLICE_COLOR);
+}
+
+fn draw_profile(
+    mut contexts: EguiContexts,
+    mut profile: ResMut<DensityProfile>,
+    grid: Res<SimulationGrid>,
+) {
+    let Some((min, max)) = profile.slice else { return };
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let axis = profile.axis;
+    let (lines, span) = count_lines(&grid, min, max, axis);
+    if lines.is_empty() {
+        profile.slice = None;
+        return;
+    }
+
+    let mut open = true;
+    egui::Window::new("Density profile").open(&mut open).resizable(false).show(ctx, |ui| {
+        let size = match axis {
+            Axis::Rows => egui::vec2(CHART_DEPTH, CHART_LENGTH),
+            Axis::Columns => egui::vec2(CHART_LENGTH, CHART_DEPTH),
+        };
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let chart = response.rect;
+        painter.rect_filled(chart, 0.0, egui::Color32::from_gray(16));
+        let step = CHART_LENGTH / lines.len() as f32;
+        for (i, counts) in lines.iter().enumerate() {
+            let mut filled = 0.0;
+            for (particle, &count) in Particle::ALL.iter().zip(counts) {
+                if *particle == Particle::Air || count == 0 {
+                    continue;
+                }
+                let depth = count as f32 / span as f32 * CHART_DEPTH;
+                let bar = match axis {
+                    Axis::Rows => egui::Rect::from_min_size(
+                        chart.min + egui::vec2(filled, i as f32 * step),
+                        egui::vec2(depth, step),
+                    ),
+                    Axis::Columns => egui::Rect::from_min_size(
+                        egui::pos2(chart.min.x + i as f32 * step, chart.max.y - filled - depth),
+                        egui::vec2(step, depth),
+                    ),
+                };
+                painter.rect_filled(bar, 0.0, egui_color(*particle));
+                filled += depth;
+            }
+        }
+
+        // The row or column under the pointer, with what it holds.
+        let hovered = response.hover_pos().map(|pointer| {
+            let along = match axis {
+                Axis::Rows => pointer.y - chart.min.y,
+                Axis::Columns => pointer.x - chart.min.x,
+            };
+            ((along / step) as usize).min(lines.len() - 1)
+        });
+        match hovered {
+            Some(i) => {
+                let (name, at) = match axis {
+                    Axis::Rows => ("Row", max.y - i as i32),
+                    Axis::Columns => ("Column", min.x + i as i32),
+                };
+                let held: Vec<String> = Particle::ALL
+                    .iter()
+                    .zip(&lines[i])
+                    .filter(|(particle, count)| **particle != Particle::Air && **count > 0)
+                    .map(|(particle, count)| format!("{:?} {}", particle, count))
+                    .collect();
+                let held = if held.is_empty() { "empty".to_string() } else { held.join(", ") };
+                ui.label(format!("{} {}: {}", name, at, held));
+            }
+            None => {
+                ui.weak(format!("{} cells x {} cells, by {:?}", span, lines.len(), axis));
+            }
+        }
+    });
+    if !open {
+        profile.slice = None;
+    }
+}
+
+// --- HELPERS ---
+
+// How many cells of each material every row of the slice holds, top row first, or every column,
+// left to right; and how many cells long each of them is. The slice is clipped to the world.
+fn count_lines(
+    grid: &SimulationGrid,
+    min: CellPos,
+    max: CellPos,
+    axis: Axis,
+) -> (Vec<[u32; Particle::ALL.len()]>, u32) {
+    let top_right = IVec2::new(grid.width() as i32 - 1, grid.height() as i32 - 1);
+    let (min, max) = (min.max(IVec2::ZERO), max.min(top_right));
+    if min.x > max.x || min.y > max.y {
+        return (Vec::new(), 0);
+    }
+    let (lines, span) = match axis {
+        Axis::Rows => (max.y - min.y + 1, max.x - min.x + 1),
+        Axis::Columns => (max.x - min.x + 1, max.y - min.y + 1),
+    };
+    let mut counts = vec![[0; Particle::ALL.len()]; lines as usize];
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let line = match axis {
+                Axis::Rows => max.y - y,
+                Axis::Columns => x - min.x,
+            };
+            let particle = grid.get(x, y).unwrap_or_default();
+            counts[line as usize][particle as usize] += 1;
+        }
+    }
+    (counts, span as u32)
+}
+
+fn egui_color(particle: Particle) -> egui::Color32 {
+    let [r, g, b, _] = particle.color().to_srgba().to_u8_array();
+    egui::Color32::from_rgb(r, g, b)
+}
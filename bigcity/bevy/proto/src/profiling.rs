@@ -0,0 +1,134 @@
+// --- IMPORTS ---
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::prelude::*;
+use bevy::render::diagnostic::RenderDiagnosticsPlugin;
+
+use crate::Particle;
+use crate::sim::{SimulationSet, SimulationStats};
+
+// --- CONSTANTS ---
+// The GPU counts as the bottleneck once its passes take this share of the frame.
+const GPU_BOUND_SHARE: f64 = 0.8;
+
+// --- PLUGIN ---
+pub struct ProfilingPlugin;
+
+impl Plugin for ProfilingPlugin {
+    fn build(&self, app: &mut App) {
+        // Render diagnostics time every pass with wgpu timestamp queries where the backend
+        // supports them (Vulkan, DX12), and fall back to CPU times elsewhere.
+        app.add_plugins((FrameTimeDiagnosticsPlugin::default(), RenderDiagnosticsPlugin))
+            .add_systems(Startup, spawn_stats_overlay)
+            .add_systems(
+                Update,
+                (toggle_stats_overlay, update_stats_overlay).chain().after(SimulationSet),
+            );
+    }
+}
+
+// --- COMPONENTS ---
+
+// Frame, simulation and per-pass GPU times, awake chunks and material counts (F3).
+#[derive(Component)]
+struct StatsOverlay;
+
+// --- SYSTEMS ---
+
+fn spawn_stats_overlay(mut commands: Commands) {
+    commands.spawn((
+        StatsOverlay,
+        Name::new("stats_overlay"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            right: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn toggle_stats_overlay(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut q_overlay: Query<&mut Node, With<StatsOverlay>>,
+) {
+    // Shift+F3 is the lifetime stats screen.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !keys.just_pressed(KeyCode::F3) || shift {
+        return;
+    }
+    let Ok(mut node) = q_overlay.single_mut() else { return };
+    node.display = match node.display {
+        Display::None => Display::Flex,
+        _ => Display::None,
+    };
+}
+
+fn update_stats_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    stats: Res<SimulationStats>,
+    mut q_overlay: Query<(&Node, &mut Text), With<StatsOverlay>>,
+) {
+    let Ok((node, mut text)) = q_overlay.single_mut() else { return };
+    if node.display == Display::None {
+        return;
+    }
+    let smoothed = |path: &_| diagnostics.get(path).and_then(|d| d.smoothed());
+    let fps = smoothed(&FrameTimeDiagnosticsPlugin::FPS).unwrap_or_default();
+    let frame_ms = smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME).unwrap_or_default();
+
+    let mut lines = vec![
+        format!("FPS: {:.0} ({:.2} ms / frame)", fps, frame_ms),
+        format!(
+            "Simulation (CPU): {:.2} ms for {} ticks",
+            stats.step_time.as_secs_f64() * 1000.0,
+            stats.ticks_last_frame
+        ),
+        format!("Awake chunks: {} of {}", stats.awake_chunks, stats.chunks),
+    ];
+
+    // Every pass that recorded a GPU span, e.g. "render/main_opaque_pass_2d/elapsed_gpu".
+    let mut gpu_ms = 0.0;
+    let mut passes: Vec<(String, f64)> = diagnostics
+        .iter()
+        .filter(|d| d.path().as_str().starts_with("render/"))
+        .filter(|d| d.path().as_str().ends_with("/elapsed_gpu"))
+        .filter_map(|d| {
+            let components: Vec<&str> = d.path().components().collect();
+            let name = components[1..components.len() - 1].join("/");
+            Some((name, d.smoothed()?))
+        })
+        .collect();
+    passes.sort_by(|a, b| a.0.cmp(&b.0));
+    if passes.is_empty() {
+        lines.push("GPU: no timestamp queries on this backend".to_string());
+    } else {
+        lines.push("GPU passes:".to_string());
+        for (name, ms) in &passes {
+            lines.push(format!("  {}: {:.3} ms", name, ms));
+            gpu_ms += ms;
+        }
+        let bound = if gpu_ms > frame_ms * GPU_BOUND_SHARE { "GPU" } else { "CPU" };
+        lines.push(format!("GPU total: {:.3} ms, likely {}-bound", gpu_ms, bound));
+    }
+
+    // Everything but air there is any of, the most first.
+    let mut counts: Vec<_> = (Particle::ALL.into_iter().skip(1))
+        .map(|particle| (particle, stats.count(particle)))
+        .filter(|&(_, count)| count > 0)
+        .collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    lines.push("Particles:".to_string());
+    for (particle, count) in counts {
+        lines.push(format!("  {:?}: {}", particle, count));
+    }
+    text.0 = lines.join("\n");
+}
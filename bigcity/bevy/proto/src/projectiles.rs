@@ -0,0 +1,171 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::access::{RayFilter, RayHit, RayWalk};
+use crate::coords::CellPos;
+use crate::explosions::Explosion;
+use crate::follow::Followable;
+use crate::pan_zoom::ctrl_held;
+use crate::player::PlayerInputSet;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{WORLD_UNITS_PER_CELL, WorldLayout, WorldView};
+
+// --- CONSTANTS ---
+const GRENADE_COLOR: Color = Color::srgb(0.2, 0.9, 0.3);
+// Sprite size, in cells.
+const GRENADE_SIZE: f32 = 2.0;
+const BLAST_RADIUS: f32 = 9.0;
+// In cells per second squared.
+const GRAVITY: f32 = 150.0;
+// Throw speed, in cells per second, for every cell the mouse is dragged, and its upper bound.
+const THROW_SPEED_PER_CELL: f32 = 4.0;
+const MAX_THROW_SPEED: f32 = 400.0;
+// The trajectory preview is sampled at this interval and drawn for at most this long.
+const PREVIEW_STEP_SECS: f32 = 1.0 / 30.0;
+const PREVIEW_SECS: f32 = 3.0;
+
+// --- PLUGIN ---
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThrowAim>().add_systems(
+            Update,
+            (aim_and_throw, fly_projectiles)
+                .chain()
+                .after(PlayerInputSet)
+                .before(SimulationSet),
+        );
+    }
+}
+
+// --- RESOURCES ---
+
+// The cell a middle-mouse drag started in, which is where the grenade is thrown from.
+#[derive(Resource, Default)]
+struct ThrowAim {
+    anchor: Option<CellPos>,
+}
+
+// --- COMPONENTS ---
+
+// A grenade in flight. Positions are in cells, velocities in cells per second.
+#[derive(Component)]
+struct Projectile {
+    position: Vec2,
+    velocity: Vec2,
+}
+
+// --- SYSTEMS ---
+
+// Dragging with the middle mouse button aims: the grenade flies in the drag direction, faster the
+// longer the drag, and leaves when the button is released. The predicted arc is drawn meanwhile.
+// Dragging with Ctrl held pans the camera instead.
+fn aim_and_throw(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    grid: Res<SimulationGrid>,
+    view: WorldView,
+    mut aim: ResMut<ThrowAim>,
+    mut gizmos: Gizmos,
+) {
+    let Some(cell) = view.cursor_cell() else {
+        // Losing the cursor mid-drag cancels the throw.
+        aim.anchor = None;
+        return;
+    };
+    // Ctrl+middle pans, and Shift+middle is the eyedropper.
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if mouse.just_pressed(MouseButton::Middle) && !ctrl_held(&keys) && !shift {
+        aim.anchor = Some(cell);
+    }
+    let Some(anchor) = aim.anchor else { return };
+
+    let origin = anchor.center();
+    let velocity = throw_velocity(cell - anchor);
+    if mouse.just_released(MouseButton::Middle) {
+        aim.anchor = None;
+        if velocity != Vec2::ZERO {
+            commands.spawn((
+                Projectile {
+                    position: origin,
+                    velocity,
+                },
+                Followable,
+                Sprite::from_color(GRENADE_COLOR, Vec2::splat(GRENADE_SIZE * WORLD_UNITS_PER_CELL)),
+                Transform::from_translation(view.cell_to_world(origin).extend(1.0)),
+            ));
+        }
+        return;
+    }
+
+    gizmos.line_2d(*view.cell_to_world(origin), *view.cell_to_world(cell.center()), GRENADE_COLOR);
+    let (mut position, mut velocity) = (origin, velocity);
+    for _ in 0..(PREVIEW_SECS / PREVIEW_STEP_SECS) as usize {
+        let (next, next_velocity, hit) = advance(&grid, position, velocity, PREVIEW_STEP_SECS);
+        let end = hit.map_or(next, |hit| hit.point);
+        let (start, end) = (*view.cell_to_world(position), *view.cell_to_world(end));
+        gizmos.line_2d(start, end, GRENADE_COLOR.with_alpha(0.4));
+        if hit.is_some() || !in_flight_bounds(&grid, next) {
+            break;
+        }
+        (position, velocity) = (next, next_velocity);
+    }
+}
+
+// Moves grenades along their arcs and blows them up where they touch anything. Grenades that fly
+// off the sides or the bottom of the world are dropped; ones thrown over the top come back down.
+fn fly_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    layout: Res<WorldLayout>,
+    grid: Res<SimulationGrid>,
+    mut q_projectiles: Query<(Entity, &mut Projectile, &mut Transform)>,
+    mut explosions: EventWriter<Explosion>,
+) {
+    for (entity, mut projectile, mut transform) in &mut q_projectiles {
+        let (position, velocity, hit) =
+            advance(&grid, projectile.position, projectile.velocity, time.delta_secs());
+        if let Some(hit) = hit {
+            explosions.write(Explosion {
+                center: hit.point,
+                radius: BLAST_RADIUS,
+            });
+            commands.entity(entity).despawn();
+            continue;
+        }
+        if !in_flight_bounds(&grid, position) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        projectile.position = position;
+        projectile.velocity = velocity;
+        transform.translation = layout.cell_to_world(position).extend(transform.translation.z);
+    }
+}
+
+// --- HELPERS ---
+
+fn throw_velocity(drag: IVec2) -> Vec2 {
+    (drag.as_vec2() * THROW_SPEED_PER_CELL).clamp_length_max(MAX_THROW_SPEED)
+}
+
+// One step of flight: the new position and velocity, and the first occupied cell on the way
+// there, if any.
+fn advance(
+    grid: &SimulationGrid,
+    position: Vec2,
+    velocity: Vec2,
+    dt: f32,
+) -> (Vec2, Vec2, Option<RayHit>) {
+    let velocity = velocity + Vec2::NEG_Y * GRAVITY * dt;
+    let next = position + velocity * dt;
+    let hit = RayWalk::new(grid, position, next - position, position.distance(next))
+        .find(|step| RayFilter::Occupied.matches(step.particle));
+    (next, velocity, hit)
+}
+
+fn in_flight_bounds(grid: &SimulationGrid, position: Vec2) -> bool {
+    position.x >= 0.0 && position.y >= 0.0 && position.x < grid.width() as f32
+}
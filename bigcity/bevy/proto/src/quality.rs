@@ -0,0 +1,158 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+use crate::behavior::MaterialBehaviors;
+use crate::degradation::{Degradation, Shed};
+use crate::persist::{load_user_ron, save_user_ron};
+use crate::reaction_rules::ReactionTable;
+use crate::sim::{Cadence, SimParams, SimulationGrid, TickSchedule, step};
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+const QUALITY_FILE: &str = "quality.ron";
+// The first-launch benchmark times this many ticks of a busy test world.
+const BENCHMARK_TICKS: u64 = 30;
+
+// --- PLUGIN ---
+pub struct QualityPlugin;
+
+impl Plugin for QualityPlugin {
+    fn build(&self, app: &mut App) {
+        let quality = load_user_ron(QUALITY_FILE).unwrap_or_else(|| {
+            let (level, tick_ms) = benchmark(*app.world().resource::<WorldLayout>());
+            info!("First launch: a tick takes {:.2} ms, picked {:?} quality", tick_ms, level);
+            save_user_ron(QUALITY_FILE, &level);
+            level
+        });
+        app.insert_resource(quality)
+            .init_resource::<Effects>()
+            .add_systems(Update, apply_quality);
+    }
+}
+
+// --- RESOURCES ---
+
+// One setting for every performance and visual knob at once. Changing it rewrites the knobs; they
+// can still be fine-tuned afterwards.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Serialize, Deserialize))]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 4] = [Quality::Low, Quality::Medium, Quality::High, Quality::Ultra];
+
+    // The tick schedule this level runs with.
+    pub fn schedule(self) -> TickSchedule {
+        let every = |every| Cadence {
+            enabled: true,
+            every,
+        };
+        let (heat, chemistry, aging, max_ticks_per_frame) = match self {
+            Quality::Low => (4, 4, 8, 1),
+            Quality::Medium => (2, 4, 4, 2),
+            Quality::High => (1, 1, 2, 4),
+            Quality::Ultra => (1, 1, 1, 8),
+        };
+        TickSchedule {
+            movement: every(1),
+            heat: every(heat),
+            chemistry: every(chemistry),
+            aging: every(aging),
+            max_ticks_per_frame,
+        }
+    }
+
+    fn effects(self) -> Effects {
+        Effects {
+            decorations: self != Quality::Low,
+        }
+    }
+
+    fn msaa(self) -> Msaa {
+        match self {
+            Quality::Low | Quality::Medium => Msaa::Off,
+            Quality::High | Quality::Ultra => Msaa::Sample4,
+        }
+    }
+}
+
+// Purely decorative extras, like the sparks on spinning turbines.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct Effects {
+    pub decorations: bool,
+}
+
+impl Default for Effects {
+    fn default() -> Self {
+        Quality::High.effects()
+    }
+}
+
+// --- SYSTEMS ---
+
+// Resolves the quality level into the resources behind it whenever it changes, and remembers it.
+// Effects shed under load stay off whatever the level.
+fn apply_quality(
+    mut commands: Commands,
+    quality: Res<Quality>,
+    degradation: Res<Degradation>,
+    mut schedule: ResMut<TickSchedule>,
+    mut effects: ResMut<Effects>,
+    q_cameras: Query<Entity, With<Camera>>,
+) {
+    if quality.is_changed() || degradation.is_changed() {
+        *effects = quality.effects();
+        effects.decorations &= !degradation.sheds(Shed::Effects);
+    }
+    // Cameras spawn in Startup, so the first run still reaches them.
+    if !quality.is_changed() {
+        return;
+    }
+    *schedule = quality.schedule();
+    for camera in &q_cameras {
+        commands.entity(camera).insert(quality.msaa());
+    }
+    if !quality.is_added() {
+        save_user_ron(QUALITY_FILE, &*quality);
+    }
+}
+
+// --- HELPERS ---
+
+// Times the rules on a world the size the game runs at, half-filled with sand and water, and picks
+// the best level that keeps a tick well inside a 60 Hz frame. Returns the level and the time per
+// tick in milliseconds.
+fn benchmark(layout: WorldLayout) -> (Quality, f32) {
+    let (width, height) = (layout.width as i32, layout.height as i32);
+    let mut grid = SimulationGrid::new(layout.width, layout.height);
+    for y in height / 4..height * 3 / 4 {
+        for x in 0..width {
+            let particle = if (x / 8 + y / 8) % 2 == 0 { Particle::Sand } else { Particle::Water };
+            grid.set(x, y, particle);
+        }
+    }
+
+    let (params, schedule) = (SimParams::default(), TickSchedule::default());
+    let (reactions, behaviors) = (ReactionTable::default(), MaterialBehaviors::default());
+    let start = Instant::now();
+    for tick in 0..BENCHMARK_TICKS {
+        step(&mut grid, tick, &params, &schedule, &reactions, &behaviors);
+    }
+    let tick_ms = start.elapsed().as_secs_f32() * 1000.0 / BENCHMARK_TICKS as f32;
+
+    let level = match tick_ms {
+        ms if ms < 1.0 => Quality::Ultra,
+        ms if ms < 2.5 => Quality::High,
+        ms if ms < 6.0 => Quality::Medium,
+        _ => Quality::Low,
+    };
+    (level, tick_ms)
+}
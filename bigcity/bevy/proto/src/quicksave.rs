@@ -0,0 +1,260 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::coords::WorldPos;
+use crate::levels::PaintRules;
+use crate::persist::{load_user_ron, save_user_ron, user_data_dir};
+use crate::player::PlayerInputSet;
+use crate::saves::SaveError;
+use crate::sim::{SimParams, SimulationGrid, SimulationSet, ViewOnly};
+use crate::world_file::WorldSerializer;
+use crate::{ScreenCamera, WorldLayout};
+
+// --- CONSTANTS ---
+const QUICK_SLOTS: usize = 3;
+const SETTINGS_FILE: &str = "quicksaves.ron";
+const QUICKSAVES_FOLDER: &str = "quicksaves";
+// Each slot on disk is the world as a picture with its sidecar (see `world_file`), and the rest
+// of the quick-save.
+const CELLS_EXTENSION: &str = "cells.png";
+const SIDECAR_EXTENSION: &str = "cells.ron";
+const META_EXTENSION: &str = "meta.ron";
+
+// --- PLUGIN ---
+
+// Quick-saves, to make a destructive experiment cheap to undo without reaching for the save
+// browser: Ctrl+F5 keeps the whole world, the simulation parameters and the camera in one of
+// three slots, taking turns so the oldest is written over, and Ctrl+F9 puts the newest back at
+// once. Ctrl+Shift+F9 goes back one quick-save further each time. Quick-saves live in memory and
+// are gone when the game closes, unless Ctrl+Shift+F5 has them kept on disk too, in `quicksaves`
+// in the user data directory, which keeps only what a saved world keeps and loads them back on
+// the next start.
+pub struct QuickSavePlugin;
+
+impl Plugin for QuickSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<QuickSaves>()
+            .add_systems(Startup, load_quick_saves.after(crate::setup))
+            .add_systems(
+                Update,
+                use_quick_saves
+                    .after(PlayerInputSet)
+                    .before(SimulationSet)
+                    .run_if(not(resource_exists::<ViewOnly>)),
+            );
+    }
+}
+
+// --- TYPES ---
+
+struct QuickSave {
+    grid: SimulationGrid,
+    params: SimParams,
+    camera: CameraPose,
+    // Counts up with every quick-save, so the newest has the highest.
+    sequence: u64,
+}
+
+// Where the camera looks, in cells, and its orthographic scale (1 shows the whole world).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct CameraPose {
+    center: (f32, f32),
+    zoom: f32,
+}
+
+// What a slot on disk keeps besides the world.
+#[derive(Serialize, Deserialize, Debug)]
+struct QuickSaveMeta {
+    params: SimParams,
+    camera: CameraPose,
+    sequence: u64,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct QuickSaveSettings {
+    keep_on_disk: bool,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct QuickSaves {
+    slots: [Option<QuickSave>; QUICK_SLOTS],
+    // The slot put back last, which Ctrl+Shift+F9 steps back from.
+    restored: Option<usize>,
+    settings: QuickSaveSettings,
+}
+
+impl QuickSaves {
+    fn newest(&self) -> Option<usize> {
+        self.by_sequence().last().copied()
+    }
+
+    // The slot to quick-save into: an empty one, or else the oldest.
+    fn next(&self) -> usize {
+        let empty = self.slots.iter().position(Option::is_none);
+        empty.or_else(|| self.by_sequence().first().copied()).unwrap_or(0)
+    }
+
+    // The quick-save made before the one in `slot`, or the newest if there is none before it.
+    fn before(&self, slot: usize) -> Option<usize> {
+        let sequence = self.slots[slot].as_ref()?.sequence;
+        let filled = self.by_sequence();
+        let older = filled.iter().rev().find(|&&i| self.sequence_of(i) < sequence).copied();
+        older.or_else(|| self.newest())
+    }
+
+    // The filled slots, oldest first.
+    fn by_sequence(&self) -> Vec<usize> {
+        let mut filled: Vec<usize> =
+            (0..QUICK_SLOTS).filter(|&i| self.slots[i].is_some()).collect();
+        filled.sort_by_key(|&i| self.sequence_of(i));
+        filled
+    }
+
+    fn sequence_of(&self, slot: usize) -> u64 {
+        self.slots[slot].as_ref().map_or(0, |save| save.sequence)
+    }
+}
+
+// --- SYSTEMS ---
+
+// Reads the setting, and the quick-saves kept on disk if they are.
+fn load_quick_saves(layout: Res<WorldLayout>, mut saves: ResMut<QuickSaves>) {
+    saves.settings = load_user_ron(SETTINGS_FILE).unwrap_or_default();
+    if !saves.settings.keep_on_disk {
+        return;
+    }
+    let Some(dir) = user_data_dir().map(|dir| dir.join(QUICKSAVES_FOLDER)) else { return };
+    for slot in 0..QUICK_SLOTS {
+        if !slot_path(&dir, slot, META_EXTENSION).exists() {
+            continue;
+        }
+        match read_slot(&dir, slot, &layout) {
+            Ok(save) => saves.slots[slot] = Some(save),
+            Err(err) => warn!("Could not load quick-save {}: {}", slot + 1, err),
+        }
+    }
+}
+
+fn use_quick_saves(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    layout: Res<WorldLayout>,
+    mut saves: ResMut<QuickSaves>,
+    mut grid: ResMut<SimulationGrid>,
+    mut params: ResMut<SimParams>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !ctrl {
+        return;
+    }
+    let Ok((mut transform, mut projection)) = q_camera.single_mut() else { return };
+    let Projection::Orthographic(ortho) = &mut *projection else { return };
+
+    if keys.just_pressed(KeyCode::F5) && shift {
+        saves.settings.keep_on_disk = !saves.settings.keep_on_disk;
+        save_user_ron(SETTINGS_FILE, &saves.settings);
+        if saves.settings.keep_on_disk {
+            for slot in 0..QUICK_SLOTS {
+                if let Some(save) = &saves.slots[slot] {
+                    write_slot_or_warn(slot, save);
+                }
+            }
+        }
+        let kept = if saves.settings.keep_on_disk { "kept on disk" } else { "kept in memory only" };
+        info!("Quick-saves are {}", kept);
+    } else if keys.just_pressed(KeyCode::F5) {
+        let slot = saves.next();
+        let save = QuickSave {
+            grid: grid.clone(),
+            params: params.clone(),
+            camera: CameraPose {
+                center: layout.world_to_cell(WorldPos(transform.translation.truncate())).into(),
+                zoom: ortho.scale,
+            },
+            sequence: saves.newest().map_or(0, |newest| saves.sequence_of(newest) + 1),
+        };
+        if saves.settings.keep_on_disk {
+            write_slot_or_warn(slot, &save);
+        }
+        saves.slots[slot] = Some(save);
+        saves.restored = Some(slot);
+        info!("Quick-saved into slot {} (Ctrl+F9 puts it back)", slot + 1);
+    } else if keys.just_pressed(KeyCode::F9) {
+        // Levels that protect their world couldn't be finished by loading a better one.
+        if rules.protect_world {
+            info!("This level can't be quick-loaded");
+            return;
+        }
+        let slot = match (shift, saves.restored) {
+            (true, Some(restored)) => saves.before(restored),
+            _ => saves.newest(),
+        };
+        let Some(save) = slot.and_then(|slot| saves.slots[slot].as_ref()) else {
+            info!("No quick-saves yet (Ctrl+F5 makes one)");
+            return;
+        };
+        grid.restore(&save.grid);
+        *params = save.params.clone();
+        let center = layout.cell_to_world(Vec2::from(save.camera.center));
+        transform.translation = center.extend(transform.translation.z);
+        ortho.scale = save.camera.zoom;
+        saves.restored = slot;
+        info!("Quick-loaded slot {}", slot.map_or(0, |slot| slot + 1));
+    }
+}
+
+// --- HELPERS ---
+
+fn slot_path(dir: &Path, slot: usize, extension: &str) -> PathBuf {
+    dir.join(format!("slot_{}.{}", slot + 1, extension))
+}
+
+fn write_slot_or_warn(slot: usize, save: &QuickSave) {
+    if let Err(err) = write_slot(slot, save) {
+        warn!("Could not keep quick-save {} on disk: {}", slot + 1, err);
+    }
+}
+
+fn write_slot(slot: usize, save: &QuickSave) -> Result<(), SaveError> {
+    let dir = user_data_dir().ok_or(SaveError::NoDataDir)?.join(QUICKSAVES_FOLDER);
+    std::fs::create_dir_all(&dir)?;
+    let (cells, sidecar) =
+        (slot_path(&dir, slot, CELLS_EXTENSION), slot_path(&dir, slot, SIDECAR_EXTENSION));
+    WorldSerializer::save(&save.grid, &cells, &sidecar)?;
+    let meta = QuickSaveMeta {
+        params: save.params.clone(),
+        camera: save.camera,
+        sequence: save.sequence,
+    };
+    std::fs::write(
+        slot_path(&dir, slot, META_EXTENSION),
+        ron::ser::to_string_pretty(&meta, default())?,
+    )?;
+    Ok(())
+}
+
+// A slot kept on disk, as a world the size of the current one; see `WorldSnapshot::apply_to` for
+// one of another size.
+fn read_slot(dir: &Path, slot: usize, layout: &WorldLayout) -> Result<QuickSave, SaveError> {
+    let world = WorldSerializer::load(
+        &slot_path(dir, slot, CELLS_EXTENSION),
+        &slot_path(dir, slot, SIDECAR_EXTENSION),
+    )?;
+    let meta: QuickSaveMeta =
+        ron::from_str(&std::fs::read_to_string(slot_path(dir, slot, META_EXTENSION))?)?;
+    let mut grid = SimulationGrid::new(layout.width, layout.height);
+    world.snapshot.apply_to(&mut grid);
+    Ok(QuickSave {
+        grid,
+        params: meta.params,
+        camera: meta.camera,
+        sequence: meta.sequence,
+    })
+}
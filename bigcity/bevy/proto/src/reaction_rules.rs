@@ -0,0 +1,247 @@
+// --- IMPORTS ---
+use bevy::asset::{LoadedFolder, RecursiveDependencyLoadState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::mods::mod_folder;
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{Reaction, SimulationGrid, compound, roll};
+use crate::{MaterialClass, Particle};
+
+// --- CONSTANTS ---
+// Bundled rules live in this asset folder, and mods add theirs in the same-named folder.
+pub const RULES_FOLDER: &str = "reactions";
+const RULES_EXTENSION: &str = "reactions.ron";
+
+// --- PLUGIN ---
+
+// Chemistry defined in data: every `.reactions.ron` file in `assets/reactions` and in a mod's
+// `reactions` folder holds `ReactionRules`, and all of their rules are compiled into one
+// `ReactionTable` that the chemistry step evaluates after the built-in rules. The table is compiled
+// again whenever a rules file is loaded or changes.
+pub struct ReactionRulesPlugin;
+
+impl Plugin for ReactionRulesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<ReactionRules>()
+            .register_asset_loader(RonAssetLoader::<ReactionRules>::new(&[RULES_EXTENSION]))
+            .add_systems(Startup, load_reaction_rules)
+            .add_systems(Update, compile_reaction_rules);
+    }
+}
+
+// --- ASSETS ---
+
+// A file of reaction rules, e.g. "Water + Lava -> Steam + Glass".
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone)]
+pub struct ReactionRules {
+    pub rules: Vec<ReactionRule>,
+}
+
+// --- TYPES ---
+
+// A cell of `reactant` with one of its four neighbours matching `touching` turns into `becomes`
+// with `chance` per tick, and that neighbour into `neighbour_becomes` if given. Both keep their
+// temperature, plus `heat` degrees (negative for a reaction that cools).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReactionRule {
+    pub name: String,
+    pub reactant: Particle,
+    pub touching: MaterialMatch,
+    pub becomes: Particle,
+    #[serde(default)]
+    pub neighbour_becomes: Option<Particle>,
+    #[serde(default = "always")]
+    pub chance: f32,
+    #[serde(default)]
+    pub heat: f32,
+}
+
+// Which neighbours a rule reacts with: one material, every material of a class, or anything but
+// air.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialMatch {
+    Material(Particle),
+    Class(MaterialClass),
+    Any,
+}
+
+impl MaterialMatch {
+    pub fn matches(self, particle: Particle) -> bool {
+        match self {
+            MaterialMatch::Material(material) => particle == material,
+            MaterialMatch::Class(class) => particle.class() == class,
+            MaterialMatch::Any => particle != Particle::Air,
+        }
+    }
+}
+
+// A rule as the chemistry step evaluates it: what it touches is resolved into a set of materials
+// up front, so matching a neighbour is a bit test.
+#[derive(Clone, Copy, Debug)]
+struct CompiledRule {
+    // Bit n is set if the rule reacts with the material whose index is n.
+    touching: u64,
+    becomes: Particle,
+    neighbour_becomes: Option<Particle>,
+    chance: f32,
+    heat: f32,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct RuleFolders([Handle<LoadedFolder>; 2]);
+
+impl RuleFolders {
+    // Whether every folder is done loading, its files with it, whether they loaded or not. A mod
+    // folder no mod provides loads empty.
+    pub fn settled(&self, asset_server: &AssetServer) -> bool {
+        self.0.iter().all(|folder| {
+            matches!(
+                asset_server.recursive_dependency_load_state(folder),
+                RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed(_)
+            )
+        })
+    }
+
+    // The bundled rules, then the mods', each folder's files in the order they were found.
+    pub fn table(
+        &self,
+        loaded: &Assets<LoadedFolder>,
+        assets: &Assets<ReactionRules>,
+    ) -> ReactionTable {
+        let rules = self
+            .0
+            .iter()
+            .filter_map(|folder| loaded.get(folder))
+            .flat_map(|folder| &folder.handles)
+            .filter_map(|handle| assets.get(&handle.clone().typed::<ReactionRules>()))
+            .flat_map(|file| &file.rules);
+        ReactionTable::compile(rules)
+    }
+}
+
+// Every reaction rule there is, compiled and grouped by reactant. The CPU rules are the only
+// simulation there is for now; anything else that simulates should evaluate this same table.
+#[derive(Resource, Clone, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(opaque, Resource, Default))]
+pub struct ReactionTable {
+    by_reactant: [Vec<CompiledRule>; Particle::ALL.len()],
+    // The rules as written, for the material reference.
+    rules: Vec<ReactionRule>,
+}
+
+impl ReactionTable {
+    // Compiles `rules` in order; a cell tries its reactant's rules in that order and stops at the
+    // first one that fires.
+    pub fn compile<'a>(rules: impl IntoIterator<Item = &'a ReactionRule>) -> Self {
+        let mut table = Self::default();
+        for rule in rules {
+            let touching = Particle::ALL
+                .iter()
+                .filter(|&&particle| rule.touching.matches(particle))
+                .fold(0, |mask, &particle| mask | (1 << particle as u64));
+            if !(0.0..=1.0).contains(&rule.chance) {
+                warn!("Reaction rule '{}' has a chance outside 0..=1, clamping it", rule.name);
+            }
+            table.by_reactant[rule.reactant as usize].push(CompiledRule {
+                touching,
+                becomes: rule.becomes,
+                neighbour_becomes: rule.neighbour_becomes,
+                chance: rule.chance.clamp(0.0, 1.0),
+                heat: rule.heat,
+            });
+            table.rules.push(rule.clone());
+        }
+        table
+    }
+
+    pub fn rules(&self) -> &[ReactionRule] {
+        &self.rules
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.by_reactant.iter().map(Vec::len).sum()
+    }
+
+    // Whether any rule could fire in a world of just the materials in `present`, a mask of
+    // particle ids.
+    pub fn reacts_among(&self, present: u64) -> bool {
+        self.by_reactant.iter().enumerate().any(|(reactant, rules)| {
+            present & (1 << reactant) != 0 && rules.iter().any(|rule| rule.touching & present != 0)
+        })
+    }
+
+    // Fires the rules on every cell that has a neighbour they react with, with the chances of
+    // `ticks` ticks, leaving the chunks that are asleep alone.
+    pub fn run(&self, grid: &mut SimulationGrid, tick: u64, ticks: u32) {
+        if self.rule_count() == 0 {
+            return;
+        }
+        let width = grid.width() as i32;
+        for i in 0..grid.cells().len() {
+            let rules = &self.by_reactant[grid.cells()[i] as usize];
+            if rules.is_empty() {
+                continue;
+            }
+            let (x, y) = (i as i32 % width, i as i32 / width);
+            if !grid.awake(x, y) {
+                continue;
+            }
+            // Rolled apart from the built-in rules, so they don't fire on the same ticks.
+            let dice = roll(x, y, tick.rotate_left(32));
+            for rule in rules {
+                let neighbour = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .into_iter()
+                    .find(|&(nx, ny)| {
+                        grid.get(nx, ny).is_some_and(|p| rule.touching & (1 << p as u64) != 0)
+                    });
+                let Some((nx, ny)) = neighbour else { continue };
+                if dice >= compound(rule.chance, ticks) {
+                    continue;
+                }
+                grid.transmute(x, y, rule.becomes);
+                grid.add_heat(x, y, rule.heat);
+                if let Some(particle) = rule.neighbour_becomes {
+                    grid.transmute(nx, ny, particle);
+                    grid.add_heat(nx, ny, rule.heat);
+                }
+                grid.note_reaction(IVec2::new(x, y), Reaction::Rule);
+                break;
+            }
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn load_reaction_rules(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(RuleFolders([
+        asset_server.load_folder(RULES_FOLDER),
+        asset_server.load_folder(mod_folder(RULES_FOLDER)),
+    ]));
+}
+
+fn compile_reaction_rules(
+    mut events: EventReader<AssetEvent<ReactionRules>>,
+    mut folder_events: EventReader<AssetEvent<LoadedFolder>>,
+    folders: Res<RuleFolders>,
+    loaded: Res<Assets<LoadedFolder>>,
+    assets: Res<Assets<ReactionRules>>,
+    mut table: ResMut<ReactionTable>,
+) {
+    let changed = events.read().count() + folder_events.read().count() > 0;
+    if !changed {
+        return;
+    }
+    *table = folders.table(&loaded, &assets);
+    info!("Compiled {} reaction rules", table.rule_count());
+}
+
+// --- HELPERS ---
+
+fn always() -> f32 {
+    1.0
+}
@@ -0,0 +1,129 @@
+// --- IMPORTS ---
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::WorldView;
+use crate::coords::CellPos;
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{Reaction, SimulationGrid, SimulationSet};
+
+// --- CONSTANTS ---
+// How long a cell stays lit after a reaction fired in it, in seconds.
+const FLASH_SECS: f32 = 0.5;
+
+// --- PLUGIN ---
+
+// Reaction view (R), for working on chemistry: every cell a reaction rule fired in flashes in that
+// rule's color and fades out, and a legend names the colors and counts the cells lit by each, so
+// it shows at a glance whether a rule triggers at all and where. The grid only notes reactions
+// while the view is on. Shift+R is the chunk view instead.
+pub struct ReactionViewPlugin;
+
+impl Plugin for ReactionViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReactionView>()
+            .add_systems(
+                Update,
+                (toggle_reaction_view, collect_reactions.after(SimulationSet), draw_flashes)
+                    .chain(),
+            )
+            .add_systems(EguiContextPass, draw_legend);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct ReactionView {
+    enabled: bool,
+    // The cells lit up, with the reaction that fired last in each and how long ago.
+    flashes: HashMap<IVec2, (Reaction, f32)>,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_reaction_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<ReactionView>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // Ctrl+R turns the selection tool's copy.
+    if keys.just_pressed(KeyCode::KeyR) && !shift && !ctrl_held(&keys) {
+        view.enabled = !view.enabled;
+        view.flashes.clear();
+        info!("Reaction view {}", if view.enabled { "on" } else { "off" });
+    }
+}
+
+// Turns the reactions the grid noted this frame into flashes, and fades the older ones. Keeping
+// the log switched on or off goes around change detection, so it doesn't count as editing the
+// world; it is reapplied every frame since loading a world can replace the grid.
+fn collect_reactions(
+    time: Res<Time>,
+    mut view: ResMut<ReactionView>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    let grid = grid.bypass_change_detection();
+    grid.set_reaction_log(view.enabled);
+    if !view.enabled {
+        return;
+    }
+    let delta = time.delta_secs();
+    view.flashes.retain(|_, (_, age)| {
+        *age += delta;
+        *age < FLASH_SECS
+    });
+    for (cell, reaction) in grid.take_reactions() {
+        view.flashes.insert(cell, (reaction, 0.0));
+    }
+}
+
+fn draw_flashes(view: Res<ReactionView>, world: WorldView, mut gizmos: Gizmos) {
+    if !view.enabled {
+        return;
+    }
+    let size = world.cell_size();
+    for (cell, (reaction, age)) in &view.flashes {
+        let center = *world.cell_to_world(CellPos(*cell).center());
+        let color = reaction_color(*reaction).with_alpha(1.0 - age / FLASH_SECS);
+        gizmos.rect_2d(Isometry2d::from_translation(center), size, color);
+    }
+}
+
+fn draw_legend(mut contexts: EguiContexts, view: Res<ReactionView>) {
+    if !view.enabled {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    egui::Window::new("Reactions").resizable(false).show(ctx, |ui| {
+        egui::Grid::new("reaction_legend").num_columns(3).show(ui, |ui| {
+            for reaction in Reaction::ALL {
+                let lit = view.flashes.values().filter(|(r, _)| *r == reaction).count();
+                let [r, g, b, _] = reaction_color(reaction).to_srgba().to_u8_array();
+                ui.colored_label(egui::Color32::from_rgb(r, g, b), "■");
+                ui.label(reaction.label());
+                ui.label(lit.to_string());
+                ui.end_row();
+            }
+        });
+        ui.weak(format!("Cells lit in the last {} s", FLASH_SECS));
+    });
+}
+
+// --- HELPERS ---
+
+fn reaction_color(reaction: Reaction) -> Color {
+    match reaction {
+        Reaction::Boil => Color::srgb(0.95, 0.95, 1.0),
+        Reaction::Melt => Color::srgb(1.0, 0.55, 0.1),
+        Reaction::Thaw => Color::srgb(0.3, 0.7, 1.0),
+        Reaction::Compact => Color::srgb(0.6, 0.3, 1.0),
+        Reaction::Dissolve => Color::srgb(1.0, 0.3, 0.7),
+        Reaction::Crystallize => Color::srgb(0.2, 1.0, 0.8),
+        Reaction::Behavior => Color::srgb(0.6, 1.0, 0.1),
+        Reaction::Burn => Color::srgb(1.0, 0.2, 0.1),
+        Reaction::Set => Color::srgb(0.55, 0.2, 0.6),
+        Reaction::Condense => Color::srgb(0.7, 0.9, 0.9),
+        Reaction::Rule => Color::srgb(1.0, 1.0, 0.3),
+        Reaction::Explode => Color::srgb(1.0, 1.0, 1.0),
+        Reaction::Electrolyze => Color::srgb(0.5, 0.8, 1.0),
+    }
+}
@@ -0,0 +1,219 @@
+// --- IMPORTS ---
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu_display::texel_color;
+use crate::encoder::{Frame, FrameEncoder, RecordingFormat};
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::{SimulationStats, SimulationTickRate};
+use crate::thermal::ThermalView;
+use crate::{SimulationDisplay, WorldLayout};
+
+// --- CONSTANTS ---
+const RECORDING_FOLDER: &str = "recordings";
+// A frame is kept every this many ticks.
+const INTERVAL_TICKS: u64 = 4;
+
+// --- PLUGIN ---
+
+// Recording the world (F12): while recording, the world's state and shade textures are read back
+// from the GPU every frame, and every few ticks the newest are turned into colors the way the
+// display draws them (or the thermal view, while it is on) and handed to the encoder, which writes
+// an animated GIF, or with Shift+F12 a numbered PNG sequence, on a thread of its own (see
+// encoder.rs). F12 again stops, and the file is finished in the background. A paused world adds
+// no frames, so the recording plays the world at its normal speed. Recordings go to `recordings`
+// in the user data directory.
+pub struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recording>().add_systems(
+            Update,
+            (toggle_recording, stop_on_resize.run_if(resource_changed::<WorldLayout>)),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// The shade texture as last read back: rows top first, each `stride` bytes long.
+struct ReadShades {
+    data: Vec<u8>,
+    stride: usize,
+}
+
+struct Recorder {
+    encoder: FrameEncoder,
+    // The tick the last frame was kept at.
+    last_tick: Option<u64>,
+    shades: Option<ReadShades>,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct Recording {
+    format: RecordingFormat,
+    // Present while recording.
+    recorder: Option<Recorder>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            format: RecordingFormat::Gif,
+            recorder: None,
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+// Reads one of the world's textures back every frame while recording.
+#[derive(Component)]
+struct RecordingReadback;
+
+// --- SYSTEMS ---
+
+fn toggle_recording(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    display: Res<SimulationDisplay>,
+    tick_rate: Res<SimulationTickRate>,
+    mut recording: ResMut<Recording>,
+    q_readbacks: Query<Entity, With<RecordingReadback>>,
+) {
+    // Ctrl+F12 is the workshop's.
+    if !keys.just_pressed(KeyCode::F12) || ctrl_held(&keys) {
+        return;
+    }
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        if recording.recorder.is_some() {
+            info!("Stop recording (F12) before switching what it records to");
+            return;
+        }
+        recording.format = match recording.format {
+            RecordingFormat::Gif => RecordingFormat::PngSequence,
+            RecordingFormat::PngSequence => RecordingFormat::Gif,
+        };
+        info!("Recording to {}", recording.format.label());
+        return;
+    }
+
+    if recording.recorder.is_some() {
+        stop_recording(&mut commands, &mut recording, &q_readbacks);
+        return;
+    }
+    let Some(path) = recording_path(recording.format) else {
+        warn!("Can't record: there is no user data directory");
+        return;
+    };
+    // Hundredths of a second per frame, at the world's normal speed; GIFs can't go much faster.
+    let delay = (INTERVAL_TICKS as f32 * 100.0 / tick_rate.ticks_per_second.max(1.0)).round();
+    let delay = delay.clamp(2.0, u16::MAX as f32) as u16;
+    info!("Recording {} to {:?} (F12 stops)", recording.format.label(), path);
+    recording.recorder = Some(Recorder {
+        encoder: FrameEncoder::start(recording.format, path, delay),
+        last_tick: None,
+        shades: None,
+    });
+    commands
+        .spawn((RecordingReadback, Readback::texture(display.shade_image.clone())))
+        .observe(store_shades);
+    commands
+        .spawn((RecordingReadback, Readback::texture(display.state_image.clone())))
+        .observe(record_frame);
+}
+
+// Every frame of a recording is the same size, so resizing the world ends it.
+fn stop_on_resize(
+    mut commands: Commands,
+    mut recording: ResMut<Recording>,
+    q_readbacks: Query<Entity, With<RecordingReadback>>,
+) {
+    if recording.recorder.is_some() {
+        stop_recording(&mut commands, &mut recording, &q_readbacks);
+    }
+}
+
+fn store_shades(
+    trigger: Trigger<ReadbackComplete>,
+    layout: Res<WorldLayout>,
+    mut recording: ResMut<Recording>,
+) {
+    let Some(recorder) = &mut recording.recorder else { return };
+    let data = trigger.event().0.clone();
+    let stride = data.len() / layout.height.max(1) as usize;
+    recorder.shades = Some(ReadShades { data, stride });
+}
+
+// Colors the state texture just read back, over the walls behind it, and keeps it as a frame,
+// once enough ticks have passed.
+fn record_frame(
+    trigger: Trigger<ReadbackComplete>,
+    layout: Res<WorldLayout>,
+    stats: Res<SimulationStats>,
+    thermal: Res<ThermalView>,
+    display: Res<SimulationDisplay>,
+    images: Res<Assets<Image>>,
+    mut recording: ResMut<Recording>,
+) {
+    let Some(recorder) = &mut recording.recorder else { return };
+    if recorder.last_tick.is_some_and(|last| stats.tick < last + INTERVAL_TICKS) {
+        return;
+    }
+    let Some(shades) = &recorder.shades else { return };
+    let walls = images.get(&display.backdrop_image).and_then(|image| image.data.as_deref());
+    let (width, height) = (layout.width as usize, layout.height as usize);
+    let texels = &trigger.event().0;
+    let stride = texels.len() / height.max(1);
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * stride + x * 4;
+            let (Some(texel), Some(&shade)) =
+                (texels.get(i..i + 4), shades.data.get(y * shades.stride + x))
+            else {
+                return;
+            };
+            let texel = [texel[0], texel[1], texel[2], texel[3]];
+            let wall = walls.and_then(|walls| walls.get(y * width + x)).copied().unwrap_or(0);
+            rgba.extend(texel_color(texel, shade, wall, thermal.enabled));
+        }
+    }
+    recorder.encoder.push(Frame {
+        width: layout.width,
+        height: layout.height,
+        rgba,
+    });
+    recorder.last_tick = Some(stats.tick);
+}
+
+// --- HELPERS ---
+
+fn stop_recording(
+    commands: &mut Commands,
+    recording: &mut Recording,
+    q_readbacks: &Query<Entity, With<RecordingReadback>>,
+) {
+    recording.recorder = None;
+    for entity in q_readbacks {
+        commands.entity(entity).despawn();
+    }
+    info!("Recording stopped; finishing the file");
+}
+
+// A new file (or folder, for a PNG sequence) in the recordings folder, named by when it started.
+fn recording_path(format: RecordingFormat) -> Option<PathBuf> {
+    let dir = user_data_dir()?.join(RECORDING_FOLDER);
+    std::fs::create_dir_all(&dir).ok()?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Some(match format {
+        RecordingFormat::Gif => dir.join(format!("recording_{}.gif", stamp)),
+        RecordingFormat::PngSequence => dir.join(format!("recording_{}", stamp)),
+    })
+}
@@ -0,0 +1,347 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::backdrop::wall_texel;
+use crate::behavior::MaterialBehaviors;
+use crate::cpu_display::texel_color;
+use crate::pan_zoom::ctrl_held;
+use crate::reaction_rules::{MaterialMatch, ReactionTable};
+use crate::sim::{SimParams, SimulationGrid, TickSchedule, builtin_changes};
+use crate::{MaterialClass, Particle};
+
+// --- CONSTANTS ---
+// The size of the demo world, in cells, and how much bigger each cell is drawn.
+const DEMO_SIZE: (u32, u32) = (48, 32);
+const DEMO_CELL: f32 = 5.0;
+// The demo starts over after this many ticks, and runs this many ticks a frame.
+const DEMO_TICKS: u64 = 300;
+const DEMO_TICKS_PER_FRAME: u64 = 2;
+
+// --- PLUGIN ---
+
+// The material reference (Ctrl+H): a window listing every material with what it is made of,
+// density, friction, burning, heat, lifetime and decay, written out from the materials
+// themselves, and what turns it into something else: the built-in rules (see `builtin_changes`)
+// and every reaction rule loaded from the bundled and modded rules files, both those it reacts
+// by and those it sets off in its neighbours. Next to it runs a tiny world of its own showing
+// the material, dropped on a floor or rising from it, beside whatever it reacts with, stepped by
+// the same rules as the real world and started over every few seconds. Nothing here is written
+// by hand, so a new material or a mod's rules show up by themselves.
+pub struct MaterialReferencePlugin;
+
+impl Plugin for MaterialReferencePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MaterialReference>()
+            .add_systems(Update, (toggle_reference, step_demo))
+            .add_systems(EguiContextPass, draw_reference);
+    }
+}
+
+// --- TYPES ---
+
+// The selected material's own little world.
+struct Demo {
+    particle: Particle,
+    grid: SimulationGrid,
+    tick: u64,
+    texture: Option<egui::TextureHandle>,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct MaterialReference {
+    open: bool,
+    selected: Particle,
+    demo: Option<Demo>,
+}
+
+impl Default for MaterialReference {
+    fn default() -> Self {
+        Self {
+            open: false,
+            selected: Particle::Sand,
+            demo: None,
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_reference(keys: Res<ButtonInput<KeyCode>>, mut reference: ResMut<MaterialReference>) {
+    if ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyH) {
+        reference.open = !reference.open;
+        if !reference.open {
+            reference.demo = None;
+        }
+    }
+}
+
+// Steps the demo with the rules the world runs by, and starts it over once it has played out or
+// another material is picked.
+fn step_demo(
+    mut reference: ResMut<MaterialReference>,
+    params: Res<SimParams>,
+    schedule: Res<TickSchedule>,
+    reactions: Res<ReactionTable>,
+    behaviors: Res<MaterialBehaviors>,
+) {
+    if !reference.open {
+        return;
+    }
+    let selected = reference.selected;
+    let stale = reference.demo.as_ref().is_none_or(|demo| {
+        demo.particle != selected || demo.tick >= DEMO_TICKS
+    });
+    if stale {
+        let texture = reference.demo.take().and_then(|demo| demo.texture);
+        reference.demo = Some(Demo {
+            particle: selected,
+            grid: demo_world(selected, &reactions),
+            tick: 0,
+            texture,
+        });
+    }
+    let Some(demo) = &mut reference.demo else { return };
+    for _ in 0..DEMO_TICKS_PER_FRAME {
+        crate::sim::step(&mut demo.grid, demo.tick, &params, &schedule, &reactions, &behaviors);
+        demo.tick += 1;
+    }
+}
+
+fn draw_reference(
+    mut contexts: EguiContexts,
+    mut reference: ResMut<MaterialReference>,
+    reactions: Res<ReactionTable>,
+    behaviors: Res<MaterialBehaviors>,
+) {
+    if !reference.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let MaterialReference {
+        open,
+        selected,
+        demo,
+    } = &mut *reference;
+    if let Some(demo) = demo {
+        let image = demo_image(&demo.grid);
+        match &mut demo.texture {
+            Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+            None => {
+                let options = egui::TextureOptions::NEAREST;
+                demo.texture = Some(ctx.load_texture("material_demo", image, options));
+            }
+        }
+    }
+
+    egui::Window::new("Material reference").open(open).show(ctx, |ui| {
+        ui.horizontal_top(|ui| {
+            egui::ScrollArea::vertical().id_salt("reference_materials").show(ui, |ui| {
+                ui.set_width(120.0);
+                for particle in Particle::ALL {
+                    ui.horizontal(|ui| {
+                        swatch(ui, particle);
+                        ui.selectable_value(selected, particle, format!("{:?}", particle));
+                    });
+                }
+            });
+            ui.separator();
+            ui.vertical(|ui| {
+                ui.set_width(320.0);
+                let particle = *selected;
+                ui.heading(format!("{:?}", particle));
+                if let Some(texture) = demo.as_ref().and_then(|demo| demo.texture.as_ref()) {
+                    let (width, height) = DEMO_SIZE;
+                    let size = egui::vec2(width as f32, height as f32) * DEMO_CELL;
+                    ui.image((texture.id(), size));
+                }
+                properties(ui, particle);
+                changes(ui, particle, &reactions, &behaviors);
+            });
+        });
+    });
+    if !*open {
+        *demo = None;
+    }
+}
+
+// --- HELPERS ---
+
+fn swatch(ui: &mut egui::Ui, particle: Particle) {
+    let [r, g, b, _] = particle.color().to_srgba().to_u8_array();
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+}
+
+fn properties(ui: &mut egui::Ui, particle: Particle) {
+    egui::Grid::new("reference_properties").num_columns(2).show(ui, |ui| {
+        let mut row = |name: &str, value: String| {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        };
+        let transparent = if particle.is_transparent() { ", lets light through" } else { "" };
+        row("Class", format!("{:?}{}", particle.class(), transparent));
+        row("Density", format!("{}", particle.density()));
+        row("Friction", format!("{}", particle.friction()));
+        let thermal = particle.thermal();
+        row("Conducts heat", format!("x{}", thermal.conductivity));
+        row("Painted at", format!("{} C", thermal.painted_at));
+        if particle.conductivity() > 0.0 {
+            row("Conducts current", format!("x{}", particle.conductivity()));
+        }
+        if particle.flammability() > 0.0 {
+            row("Burns", format!("{:.0}% a tick", particle.flammability() * 100.0));
+        }
+        if let Some((ticks, product)) = particle.lifetime() {
+            row("Lasts", format!("{} ticks, then {:?}", ticks, product));
+        }
+        if let Some(decay) = particle.decay() {
+            row("Half-life", format!("{} ticks, into {:?}", decay.half_life, decay.product));
+        }
+        if particle.shields_radiation() {
+            row("Radiation", "stops it".to_string());
+        }
+        if let Some((_, ticks)) = particle.weathering() {
+            row("Weathers", format!("over {} ticks", ticks));
+        }
+    });
+}
+
+// What turns the material into something else, and what it does to its neighbours.
+fn changes(
+    ui: &mut egui::Ui,
+    particle: Particle,
+    reactions: &ReactionTable,
+    behaviors: &MaterialBehaviors,
+) {
+    let mut turns_into = builtin_changes(particle);
+    for rule in reactions.rules().iter().filter(|rule| rule.reactant == particle) {
+        turns_into.push(format!(
+            "{}: into {:?} touching {}{} ({})",
+            rule.name,
+            rule.becomes,
+            touching(rule.touching),
+            rule.neighbour_becomes.map_or(String::new(), |n| format!(", which becomes {:?}", n)),
+            chance(rule.chance)
+        ));
+    }
+    if behaviors.has_behavior(particle) && particle.decay().is_none() {
+        turns_into.push("Runs a behavior of its own".to_string());
+    }
+    let affects: Vec<String> = reactions
+        .rules()
+        .iter()
+        .filter(|rule| rule.reactant != particle && rule.touching.matches(particle))
+        .map(|rule| {
+            let neighbour = rule.neighbour_becomes.map_or(String::new(), |n| {
+                format!(", and turns into {:?}", n)
+            });
+            format!(
+                "{}: turns {:?} into {:?}{} ({})",
+                rule.name,
+                rule.reactant,
+                rule.becomes,
+                neighbour,
+                chance(rule.chance)
+            )
+        })
+        .collect();
+
+    ui.strong("Turns into");
+    if turns_into.is_empty() {
+        ui.weak("Nothing; it stays as it is.");
+    }
+    for change in turns_into {
+        ui.label(format!("- {}", change));
+    }
+    ui.strong("Affects");
+    if affects.is_empty() {
+        ui.weak("No rule reacts with it.");
+    }
+    for rule in affects {
+        ui.label(format!("- {}", rule));
+    }
+}
+
+fn touching(touching: MaterialMatch) -> String {
+    match touching {
+        MaterialMatch::Material(material) => format!("{:?}", material),
+        MaterialMatch::Class(class) => format!("any {:?}", class),
+        MaterialMatch::Any => "anything".to_string(),
+    }
+}
+
+fn chance(chance: f32) -> String {
+    format!("{:.0}% a tick", chance.clamp(0.0, 1.0) * 100.0)
+}
+
+// A bedrock box with a block of `particle` in it, where it will fall or rise, next to the first
+// thing the rules say it reacts with.
+fn demo_world(particle: Particle, reactions: &ReactionTable) -> SimulationGrid {
+    let (width, height) = (DEMO_SIZE.0 as i32, DEMO_SIZE.1 as i32);
+    let mut grid = SimulationGrid::new(DEMO_SIZE.0, DEMO_SIZE.1);
+    for x in 0..width {
+        grid.set(x, 0, Particle::Bedrock);
+    }
+    for y in 0..height {
+        grid.set(0, y, Particle::Bedrock);
+        grid.set(width - 1, y, Particle::Bedrock);
+    }
+    let mut fill = |(x0, y0): (i32, i32), (x1, y1): (i32, i32), with: Particle| {
+        for y in y0..y1 {
+            for x in x0..x1 {
+                grid.set(x, y, with);
+            }
+        }
+    };
+    // Powders and liquids drop onto what they react with, gases rise into it, and solids stand
+    // beside it.
+    let (block, partner_at) = match particle.class() {
+        MaterialClass::Powder | MaterialClass::Liquid => (((16, 20), (32, 30)), ((14, 1), (34, 5))),
+        MaterialClass::Gas => (((16, 1), (32, 9)), ((14, 24), (34, 28))),
+        MaterialClass::Solid => (((8, 1), (22, 12)), ((22, 1), (28, 12))),
+    };
+    if let Some(partner) = demo_partner(particle, reactions) {
+        fill(partner_at.0, partner_at.1, partner);
+    }
+    fill(block.0, block.1, particle);
+    grid
+}
+
+// What the demo puts beside `particle`: what its first reaction rule needs, or what sets off its
+// built-in changes.
+fn demo_partner(particle: Particle, reactions: &ReactionTable) -> Option<Particle> {
+    let ruled = reactions.rules().iter().find_map(|rule| match rule.touching {
+        MaterialMatch::Material(material) if rule.reactant == particle => Some(material),
+        _ if rule.reactant != particle && rule.touching.matches(particle) => Some(rule.reactant),
+        _ => None,
+    });
+    ruled.or(match particle {
+        Particle::Water | Particle::Sand | Particle::Snow | Particle::Ice => Some(Particle::Lava),
+        Particle::Lava | Particle::Salt => Some(Particle::Water),
+        Particle::Laser => Some(Particle::Mirror),
+        Particle::Battery => Some(Particle::Water),
+        _ if particle.flammability() > 0.0 || particle.blast_yield() > 0.0 => Some(Particle::Fire),
+        _ => None,
+    })
+}
+
+fn demo_image(grid: &SimulationGrid) -> egui::ColorImage {
+    let (width, height) = (grid.width() as usize, grid.height() as usize);
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    let walls = grid.backdrops();
+    // Grid rows run bottom-up, pictures top-down.
+    for y in (0..height).rev() {
+        for x in 0..width {
+            let i = y * width + x;
+            let id = grid.cells()[i] as u8;
+            let charge = grid.charges()[i];
+            let wall = wall_texel(walls[i], x, y);
+            rgba.extend(texel_color([id, charge, 0, 0], grid.shades()[i], wall, false));
+        }
+    }
+    egui::ColorImage::from_rgba_unmultiplied([width, height], &rgba)
+}
@@ -0,0 +1,243 @@
+// --- IMPORTS ---
+use std::sync::{Arc, Mutex};
+
+use bevy::image::TextureFormatPixelInfo;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, MapMode, Origin3d,
+    TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue, render_system};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderSet};
+
+use crate::coords::CellPos;
+
+// --- PLUGIN ---
+
+// Reading small rectangles of the world's textures back from the GPU, for queries that only care
+// about a few cells, like the texel under the inspector's cursor, without copying back the whole
+// texture every frame. `RegionReadbacks::request` asks for a rectangle of cells of a texture and
+// returns an id; the render world copies just those rows and columns into a buffer of their own
+// after the frame's commands, and once the copy has been mapped, usually a frame or two later,
+// a `RegionRead` event with the same id carries the texels. Rectangles are in cells like the grid,
+// bottom row first, and are kept inside the texture; one the texture doesn't overlap at all still
+// completes, with no texels. A texture that isn't on the GPU yet is tried again the next frame.
+pub struct RegionReadbackPlugin;
+
+impl Plugin for RegionReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        let shared = SharedReadbacks::default();
+        app.insert_resource(RegionReadbacks {
+            next_id: 0,
+            shared: shared.clone(),
+        })
+        .add_event::<RegionRead>()
+        .add_systems(First, send_completed_reads);
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(shared)
+                .add_systems(Render, copy_regions.after(render_system).in_set(RenderSet::Render));
+        }
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct RegionId(u64);
+
+struct RegionRequest {
+    id: RegionId,
+    image: Handle<Image>,
+    min: CellPos,
+    size: UVec2,
+}
+
+// Requests waiting for the render world, and reads waiting to be sent, shared by both worlds.
+#[derive(Resource, Clone, Default)]
+struct SharedReadbacks(Arc<Mutex<Readbacks>>);
+
+#[derive(Default)]
+struct Readbacks {
+    requested: Vec<RegionRequest>,
+    completed: Vec<RegionRead>,
+}
+
+// --- EVENTS ---
+
+// The texels of a rectangle asked for with `RegionReadbacks::request`.
+#[derive(Event, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct RegionRead {
+    pub id: RegionId,
+    // The rectangle actually read, kept inside the texture.
+    min: CellPos,
+    size: UVec2,
+    texel_size: usize,
+    // Rows bottom first, tightly packed.
+    data: Vec<u8>,
+}
+
+impl RegionRead {
+    // The bottom left cell read.
+    pub fn min(&self) -> CellPos {
+        self.min
+    }
+
+    // How many cells across and up were read.
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    // The raw texel of `cell`, if the rectangle read holds it.
+    pub fn texel(&self, cell: CellPos) -> Option<&[u8]> {
+        let at = cell - self.min;
+        if at.x < 0 || at.y < 0 || at.x >= self.size.x as i32 || at.y >= self.size.y as i32 {
+            return None;
+        }
+        let i = (at.y as usize * self.size.x as usize + at.x as usize) * self.texel_size;
+        self.data.get(i..i + self.texel_size)
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+pub struct RegionReadbacks {
+    next_id: u64,
+    shared: SharedReadbacks,
+}
+
+impl RegionReadbacks {
+    // Asks for the `size` cells of `image` from `min`, in grid cells; a `RegionRead` with the
+    // returned id follows once they have been copied back.
+    pub fn request(&mut self, image: &Handle<Image>, min: CellPos, size: UVec2) -> RegionId {
+        let id = RegionId(self.next_id);
+        self.next_id += 1;
+        let request = RegionRequest {
+            id,
+            image: image.clone(),
+            min,
+            size,
+        };
+        self.shared.0.lock().unwrap().requested.push(request);
+        id
+    }
+}
+
+// --- SYSTEMS ---
+
+fn send_completed_reads(readbacks: Res<RegionReadbacks>, mut reads: EventWriter<RegionRead>) {
+    let completed = std::mem::take(&mut readbacks.shared.0.lock().unwrap().completed);
+    reads.write_batch(completed);
+}
+
+// Copies every rectangle asked for into a buffer of its own and maps it; the mapped rows are
+// unpadded and flipped to the grid's order as they come in.
+fn copy_regions(
+    shared: Res<SharedReadbacks>,
+    device: Res<RenderDevice>,
+    queue: Res<RenderQueue>,
+    images: Res<RenderAssets<GpuImage>>,
+) {
+    let requested = std::mem::take(&mut shared.0.lock().unwrap().requested);
+    if requested.is_empty() {
+        return;
+    }
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("region_readback"),
+    });
+    let mut copies = Vec::new();
+    let mut waiting = Vec::new();
+    for request in requested {
+        let Some(image) = images.get(&request.image) else {
+            waiting.push(request);
+            continue;
+        };
+        // Grid rows run bottom-up, texture rows top-down.
+        let height = image.size.height as i32;
+        let min = request.min.max(IVec2::ZERO);
+        let max = (*request.min + request.size.as_ivec2())
+            .min(IVec2::new(image.size.width as i32, height));
+        let texel_size = image.texture_format.pixel_size();
+        let mut read = RegionRead {
+            id: request.id,
+            min: CellPos(min),
+            size: (max - min).max(IVec2::ZERO).as_uvec2(),
+            texel_size,
+            data: Vec::new(),
+        };
+        if read.size.x == 0 || read.size.y == 0 {
+            shared.0.lock().unwrap().completed.push(read);
+            continue;
+        }
+        let row_bytes = read.size.x as usize * texel_size;
+        let stride = RenderDevice::align_copy_bytes_per_row(row_bytes);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("region_readback_buffer"),
+            size: (stride * read.size.y as usize) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture: &image.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: min.x as u32,
+                    y: (height - max.y) as u32,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(stride as u32),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: read.size.x,
+                height: read.size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+        read.data.reserve(row_bytes * read.size.y as usize);
+        copies.push((read, buffer, stride));
+    }
+    shared.0.lock().unwrap().requested.extend(waiting);
+    if copies.is_empty() {
+        return;
+    }
+    queue.submit([encoder.finish()]);
+
+    for (mut read, buffer, stride) in copies {
+        let (mapped, shared) = (buffer.clone(), shared.clone());
+        buffer.slice(..).map_async(MapMode::Read, move |result| {
+            // A read that failed still completes, with no texels, so nobody waits on it forever.
+            match result {
+                Ok(()) => finish_read(&mut read, &mapped, stride),
+                Err(err) => warn!("Couldn't read back a region of a texture: {}", err),
+            }
+            shared.0.lock().unwrap().completed.push(read);
+        });
+    }
+}
+
+// --- HELPERS ---
+
+fn finish_read(read: &mut RegionRead, buffer: &Buffer, stride: usize) {
+    let row_bytes = read.size.x as usize * read.texel_size;
+    {
+        let rows = buffer.slice(..).get_mapped_range();
+        for row in rows.chunks(stride).rev() {
+            read.data.extend_from_slice(&row[..row_bytes]);
+        }
+    }
+    buffer.unmap();
+}
@@ -0,0 +1,201 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+
+use crate::access::SimulationAccess;
+use crate::coords::CellPos;
+use crate::player::{Player, PlayerCursor};
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+const HIGHLIGHT_COLOR: Color = Color::srgb(0.3, 0.9, 1.0);
+
+// --- PLUGIN ---
+pub struct RegionsPlugin;
+
+impl Plugin for RegionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_region_label)
+            .add_systems(Update, highlight_hovered_region.after(SimulationSet));
+    }
+}
+
+// --- TYPES ---
+
+// What counts as "the same" when growing a region.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Connectivity {
+    #[default]
+    Material,
+    // Any materials of the same `MaterialClass`, e.g. all liquids together.
+    Class,
+}
+
+impl Connectivity {
+    fn joins(self, a: Particle, b: Particle) -> bool {
+        match self {
+            Connectivity::Material => a == b,
+            Connectivity::Class => a.class() == b.class(),
+        }
+    }
+}
+
+// The cells 4-connected to a seed cell, and their inclusive bounding box.
+#[derive(Clone, Debug)]
+pub struct ConnectedRegion {
+    // The seed cell's material.
+    pub particle: Particle,
+    pub cells: Vec<CellPos>,
+    pub min: CellPos,
+    pub max: CellPos,
+}
+
+// Connected components of the whole grid as a union-find forest. Each root also tracks its
+// component's size and bounding box, so those queries don't have to walk the region.
+#[derive(Default)]
+pub struct RegionIndex {
+    width: u32,
+    parent: Vec<u32>,
+    size: Vec<u32>,
+    min: Vec<IVec2>,
+    max: Vec<IVec2>,
+}
+
+impl RegionIndex {
+    // Rebuilds the forest in one pass, joining every cell with its left and lower neighbours.
+    pub fn build(grid: &SimulationGrid, connectivity: Connectivity) -> Self {
+        let (width, height) = (grid.width() as i32, grid.height() as i32);
+        let len = grid.cells().len();
+        let mut index = Self {
+            width: grid.width(),
+            parent: (0..len as u32).collect(),
+            size: vec![1; len],
+            min: Vec::with_capacity(len),
+            max: Vec::with_capacity(len),
+        };
+        for y in 0..height {
+            for x in 0..width {
+                index.min.push(IVec2::new(x, y));
+                index.max.push(IVec2::new(x, y));
+            }
+        }
+
+        let cells = grid.cells();
+        for y in 0..height {
+            for x in 0..width {
+                let i = (y * width + x) as usize;
+                if x > 0 && connectivity.joins(cells[i], cells[i - 1]) {
+                    index.union(i, i - 1);
+                }
+                if y > 0 && connectivity.joins(cells[i], cells[i - width as usize]) {
+                    index.union(i, i - width as usize);
+                }
+            }
+        }
+        index
+    }
+
+    // The representative of (x, y)'s component; two cells are connected iff their roots match.
+    pub fn root(&mut self, x: i32, y: i32) -> usize {
+        self.find(y as usize * self.width as usize + x as usize)
+    }
+
+    // Cell count and bounding box of (x, y)'s component.
+    pub fn bounds(&mut self, x: i32, y: i32) -> (u32, IVec2, IVec2) {
+        let root = self.root(x, y);
+        (self.size[root], self.min[root], self.max[root])
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] as usize != i {
+            // Path halving keeps later lookups close to constant time.
+            let grandparent = self.parent[self.parent[i] as usize];
+            self.parent[i] = grandparent;
+            i = grandparent as usize;
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a as u32;
+        self.size[a] += self.size[b];
+        self.min[a] = self.min[a].min(self.min[b]);
+        self.max[a] = self.max[a].max(self.max[b]);
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct RegionLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_region_label(mut commands: Commands) {
+    commands.spawn((
+        RegionLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(HIGHLIGHT_COLOR),
+    ));
+}
+
+// Holding Alt outlines the region under each player's cursor (Alt+Shift groups by class).
+fn highlight_hovered_region(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    q_players: Query<(&Player, &PlayerCursor)>,
+    mut q_label: Query<&mut Text, With<RegionLabel>>,
+    mut access: SimulationAccess,
+    mut gizmos: Gizmos,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    if !keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]) {
+        if !label.0.is_empty() {
+            label.0.clear();
+        }
+        return;
+    }
+    let by_class = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    let mut lines = Vec::new();
+    for (player, cursor) in &q_players {
+        let Some(position) = cursor.position else { continue };
+        let Some(cell) = view.cell_at(position) else { continue };
+        let region = if by_class {
+            access.connected_region_by(cell, Connectivity::Class)
+        } else {
+            access.connected_region(cell)
+        };
+        let Some(region) = region else { continue };
+
+        let kind = if by_class {
+            format!("{:?}", region.particle.class())
+        } else {
+            format!("{:?}", region.particle)
+        };
+        lines.push(format!("P{}: {} region, {} cells", player.index + 1, kind, region.cells.len()));
+
+        let rect = view.cells_to_world(region.min, region.max);
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), HIGHLIGHT_COLOR);
+    }
+    label.0 = lines.join("\n");
+}
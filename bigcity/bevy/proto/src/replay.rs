@@ -0,0 +1,606 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::Particle;
+use crate::behavior::MaterialBehaviors;
+use crate::coords::CellPos;
+use crate::degradation::DegradationPolicy;
+use crate::persist::user_data_dir;
+use crate::reaction_rules::{ReactionRule, ReactionTable};
+use crate::sim::{
+    SimParams, SimulationControl, SimulationGrid, SimulationSet, SimulationStats, TickSchedule,
+};
+use crate::snapshot::WorldSnapshot;
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::worldgen::WorldGeneration;
+
+// --- CONSTANTS ---
+const REPLAY_FOLDER: &str = "replays";
+const REPLAY_EXTENSION: &str = "replay.ron";
+// Bumped whenever replays stop playing back the same.
+const REPLAY_VERSION: u32 = 2;
+
+// --- PLUGIN ---
+
+// Replays, for reproducing a run exactly when chasing or sharing a bug. The world's dice are all
+// rolled from the tick, the cell and the world's seed (`--seed <n>`, 0 by default), so a world
+// started the same way and painted the same way at the same ticks plays out the same. `--record`
+// (optionally with a path) keeps the starting world, the seed and every paint stroke with the tick
+// it went in before, along with changes to the parameters, the tick schedule and the reaction
+// rules, and writes them to `replays` in the user data directory on exit, with a checksum of the
+// world it ended on. `--replay <file>` starts from that world, puts every stroke and change in
+// before the same tick, the strokes as `WorldCommand::Place`, and pauses on the last one, saying
+// whether the world matches. The brush
+// stays off while a replay plays. Other tools (stamps, undo, explosions and the like) aren't
+// recorded, and load shedding is switched off while recording or replaying since it changes the
+// schedule with the frame rate. The plugin holds the seed an embedding app set up the sandbox
+// with, which `--seed` overrides.
+pub struct ReplayPlugin(pub Option<u64>);
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1).peekable();
+        let mut session = ReplayArgs {
+            seed: self.0,
+            ..default()
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => match args.next().and_then(|seed| seed.parse().ok()) {
+                    Some(seed) => session.seed = Some(seed),
+                    None => warn!("--seed takes a number"),
+                },
+                "--record" => {
+                    let path = args.next_if(|path| !path.starts_with("--"));
+                    session.record = Some(path.map(PathBuf::from).or_else(replay_path));
+                }
+                "--replay" => session.replay = args.next().map(PathBuf::from),
+                _ => {}
+            }
+        }
+
+        app.insert_resource(session)
+            .add_systems(
+                FixedUpdate,
+                (start_session, note_changes, play_replay)
+                    .chain()
+                    .before(WorldCommandSet)
+                    .run_if(not(resource_exists::<WorldGeneration>)),
+            )
+            .add_systems(
+                FixedUpdate,
+                finish_replay
+                    .after(WorldCommandSet)
+                    .before(SimulationSet)
+                    .run_if(resource_exists::<ReplayPlayback>),
+            )
+            .add_systems(Last, save_on_exit.run_if(resource_exists::<ReplayRecording>));
+    }
+}
+
+// --- RUNNER ---
+
+// `--check-replay <file>` plays a replay without a window instead of the game, as fast as it
+// goes, and says whether it ends on the world it was recorded with. Returns the replay's path, if
+// given.
+pub fn check_arg() -> Option<PathBuf> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--check-replay" {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+// Plays the replay at `path` and returns the process exit code: 0 if the world matches.
+pub fn check(path: &Path) -> i32 {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("Could not replay {:?}: {}", path, err);
+            return 2;
+        }
+    };
+    let ended = checksum(&replay.run(&MaterialBehaviors::default()));
+    if ended == replay.checksum {
+        println!("{:?} plays back the same ({} ticks)", path, replay.end_tick - replay.start_tick);
+        0
+    } else {
+        println!(
+            "{:?} plays back differently: {:016x} against {:016x}",
+            path, ended, replay.checksum
+        );
+        1
+    }
+}
+
+// --- TYPES ---
+
+// A recorded run: the world it started from, and what went in before which tick.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(opaque, Serialize, Deserialize))]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub start_tick: u64,
+    pub world: WorldSnapshot,
+    pub inputs: Vec<(u64, ReplayInput)>,
+    // The tick the recording ended on, and the world's checksum (see `checksum`) then.
+    pub end_tick: u64,
+    pub checksum: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ReplayInput {
+    // A brush stroke's cells for this frame, in the order they were painted.
+    Paint {
+        particle: Particle,
+        data: u8,
+        cells: Vec<(i32, i32)>,
+    },
+    Params(SimParams),
+    Schedule(TickSchedule),
+    Rules(Vec<ReactionRule>),
+}
+
+impl Replay {
+    // The world a replay starts from, exactly as the recording started.
+    pub fn start_grid(&self) -> SimulationGrid {
+        start_grid(&self.world, self.seed)
+    }
+
+    // Plays the whole replay without a window, the way the game would, and returns the world it
+    // ends on.
+    pub fn run(&self, behaviors: &MaterialBehaviors) -> SimulationGrid {
+        let mut grid = self.start_grid();
+        let (mut params, mut schedule) = (SimParams::default(), TickSchedule::default());
+        let mut reactions = ReactionTable::default();
+        let mut inputs = self.inputs.iter().peekable();
+        for tick in self.start_tick..=self.end_tick {
+            while let Some((_, input)) = inputs.next_if(|(at, _)| *at <= tick) {
+                input.apply(&mut grid, &mut params, &mut schedule, &mut reactions);
+            }
+            if tick == self.end_tick {
+                break;
+            }
+            crate::sim::step(&mut grid, tick, &params, &schedule, &reactions, behaviors);
+        }
+        grid
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let replay: Replay = ron::from_str(&std::fs::read_to_string(path)?)?;
+        if replay.version > REPLAY_VERSION {
+            return Err(ReplayError::Version(replay.version));
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder)?;
+        }
+        std::fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl ReplayInput {
+    fn apply(
+        &self,
+        grid: &mut SimulationGrid,
+        params: &mut SimParams,
+        schedule: &mut TickSchedule,
+        reactions: &mut ReactionTable,
+    ) {
+        match self {
+            ReplayInput::Paint {
+                particle,
+                data,
+                cells,
+            } => {
+                for &(x, y) in cells {
+                    grid.place(x, y, *particle, *data);
+                }
+            }
+            ReplayInput::Params(recorded) => *params = recorded.clone(),
+            ReplayInput::Schedule(recorded) => *schedule = recorded.clone(),
+            ReplayInput::Rules(rules) => *reactions = ReactionTable::compile(rules),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not read or write the file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write: {0}")]
+    Write(#[from] ron::Error),
+    #[error("recorded by a newer version (replay version {0})")]
+    Version(u32),
+}
+
+// --- RESOURCES ---
+
+// What the command line asked for.
+#[derive(Resource, Default)]
+struct ReplayArgs {
+    seed: Option<u64>,
+    // Where to write the recording; `Some(None)` when there is nowhere to.
+    record: Option<Option<PathBuf>>,
+    replay: Option<PathBuf>,
+}
+
+// The run being recorded. Present while recording.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct ReplayRecording {
+    replay: Replay,
+    path: Option<PathBuf>,
+    // The parameters and schedule as last recorded, to notice when they change.
+    params: Option<SimParams>,
+    schedule: Option<TickSchedule>,
+}
+
+impl ReplayRecording {
+    // Notes a brush stroke's cells, painted before `tick` runs.
+    pub fn note_paint(&mut self, tick: u64, particle: Particle, data: u8, cells: &[CellPos]) {
+        if cells.is_empty() {
+            return;
+        }
+        let cells = cells.iter().map(|cell| (cell.x, cell.y)).collect();
+        self.replay.inputs.push((
+            tick,
+            ReplayInput::Paint {
+                particle,
+                data,
+                cells,
+            },
+        ));
+    }
+}
+
+// The replay being played. Present until it has played out.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct ReplayPlayback {
+    replay: Replay,
+    // How many of its inputs have gone in.
+    next: usize,
+    // The reaction rules it runs by, put back if the bundled ones load over them.
+    reactions: ReactionTable,
+}
+
+// --- SYSTEM PARAM ---
+
+// What a replay puts its inputs into.
+#[derive(SystemParam)]
+struct ReplayTarget<'w> {
+    grid: ResMut<'w, SimulationGrid>,
+    params: ResMut<'w, SimParams>,
+    schedule: ResMut<'w, TickSchedule>,
+    reactions: ResMut<'w, ReactionTable>,
+    commands: ResMut<'w, WorldCommands>,
+}
+
+// --- SYSTEMS ---
+
+// Once the world is in: takes up the seed, and starts the recording or the replay.
+fn start_session(
+    mut commands: Commands,
+    mut args: ResMut<ReplayArgs>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stats: ResMut<SimulationStats>,
+    mut policy: ResMut<DegradationPolicy>,
+    mut started: Local<bool>,
+) {
+    if std::mem::replace(&mut *started, true) {
+        return;
+    }
+    if let Some(seed) = args.seed {
+        grid.set_seed(seed);
+    }
+    if args.record.is_some() || args.replay.is_some() {
+        policy.enabled = false;
+    }
+
+    if let Some(path) = args.replay.take() {
+        match Replay::load(&path) {
+            Ok(replay) => {
+                info!(
+                    "Replaying {:?}: ticks {} to {}, {} inputs",
+                    path,
+                    replay.start_tick,
+                    replay.end_tick,
+                    replay.inputs.len()
+                );
+                grid.restore(&replay.start_grid());
+                stats.tick = replay.start_tick;
+                commands.insert_resource(ReplayPlayback {
+                    replay,
+                    next: 0,
+                    reactions: ReactionTable::default(),
+                });
+            }
+            Err(err) => warn!("Could not replay {:?}: {}", path, err),
+        }
+        return;
+    }
+    let Some(path) = args.record.take() else { return };
+    if path.is_none() {
+        warn!("Recording without a user data directory; the replay won't be saved");
+    }
+    // The live world starts over from what the replay keeps of it, so both start alike.
+    let world = WorldSnapshot::from_grid(&grid);
+    let start = start_grid(&world, grid.seed());
+    grid.restore(&start);
+    info!("Recording a replay from tick {} (seed {})", stats.tick, grid.seed());
+    commands.insert_resource(ReplayRecording {
+        replay: Replay {
+            version: REPLAY_VERSION,
+            seed: grid.seed(),
+            start_tick: stats.tick,
+            world,
+            inputs: Vec::new(),
+            end_tick: stats.tick,
+            checksum: 0,
+        },
+        path,
+        params: None,
+        schedule: None,
+    });
+}
+
+// Notes changes to the parameters, the schedule and the rules before the tick they apply to.
+fn note_changes(
+    stats: Res<SimulationStats>,
+    params: Res<SimParams>,
+    schedule: Res<TickSchedule>,
+    reactions: Res<ReactionTable>,
+    recording: Option<ResMut<ReplayRecording>>,
+) {
+    let Some(mut recording) = recording else { return };
+    let tick = stats.tick;
+    if recording.params.as_ref() != Some(&*params) {
+        recording.params = Some(params.clone());
+        recording.replay.inputs.push((tick, ReplayInput::Params(params.clone())));
+    }
+    if recording.schedule.as_ref() != Some(&*schedule) {
+        recording.schedule = Some(schedule.clone());
+        recording.replay.inputs.push((tick, ReplayInput::Schedule(schedule.clone())));
+    }
+    // Every resource counts as changed the first time a system looks, so the rules the recording
+    // starts with are noted too.
+    if reactions.is_changed() {
+        let rules = reactions.rules().to_vec();
+        recording.replay.inputs.push((tick, ReplayInput::Rules(rules)));
+    }
+}
+
+// Puts in everything recorded before the coming tick.
+fn play_replay(
+    stats: Res<SimulationStats>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    mut world: ReplayTarget,
+) {
+    let Some(mut playback) = playback else { return };
+    let ReplayPlayback {
+        replay,
+        next,
+        reactions,
+    } = &mut *playback;
+    let mut new_rules = false;
+    while let Some((_, input)) = replay.inputs.get(*next).filter(|(at, _)| *at <= stats.tick) {
+        match input {
+            ReplayInput::Paint {
+                particle,
+                data,
+                cells,
+            } => world.commands.push(WorldCommand::Place {
+                particle: *particle,
+                data: *data,
+                cells: cells.iter().map(|&(x, y)| CellPos::new(x, y)).collect(),
+            }),
+            _ => input.apply(&mut world.grid, &mut world.params, &mut world.schedule, reactions),
+        }
+        new_rules |= matches!(input, ReplayInput::Rules(_));
+        *next += 1;
+    }
+    // The bundled rules may finish loading after the replay's went in.
+    if new_rules || world.reactions.is_changed() {
+        *world.reactions = reactions.clone();
+    }
+}
+
+// Once the last tick is reached and its strokes are in, pauses and checks the world against the
+// recording.
+fn finish_replay(
+    mut commands: Commands,
+    stats: Res<SimulationStats>,
+    playback: Res<ReplayPlayback>,
+    grid: Res<SimulationGrid>,
+    mut control: ResMut<SimulationControl>,
+) {
+    let replay = &playback.replay;
+    if stats.tick < replay.end_tick {
+        return;
+    }
+    control.paused = true;
+    let checksum = checksum(&grid);
+    if checksum == replay.checksum {
+        info!("Replay finished at tick {}: the world matches the recording", stats.tick);
+    } else {
+        warn!(
+            "Replay finished at tick {}, but the world differs from the recording ({:016x} \
+             against {:016x})",
+            stats.tick, checksum, replay.checksum
+        );
+    }
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+fn save_on_exit(
+    mut exits: EventReader<AppExit>,
+    stats: Res<SimulationStats>,
+    grid: Res<SimulationGrid>,
+    mut recording: ResMut<ReplayRecording>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+    recording.replay.end_tick = stats.tick;
+    recording.replay.checksum = checksum(&grid);
+    let Some(path) = &recording.path else { return };
+    match recording.replay.save(path) {
+        Ok(()) => info!("Replay saved to {:?} ({} ticks)", path, stats.tick),
+        Err(err) => warn!("Could not save the replay to {:?}: {}", path, err),
+    }
+}
+
+// --- HELPERS ---
+
+fn start_grid(world: &WorldSnapshot, seed: u64) -> SimulationGrid {
+    let mut grid = SimulationGrid::new(world.width, world.height);
+    grid.set_seed(seed);
+    world.apply_to(&mut grid);
+    grid
+}
+
+// A hash of everything kept about every cell, to tell whether two runs ended alike.
+pub fn checksum(grid: &SimulationGrid) -> u64 {
+    // FNV-1a.
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+    let mut add = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    };
+    add(&grid.width().to_le_bytes());
+    add(&grid.height().to_le_bytes());
+    for i in 0..grid.cells().len() {
+        let (x, y) = ((i as u32 % grid.width()) as i32, (i as u32 / grid.width()) as i32);
+        add(&[grid.cells()[i] as u8, grid.states()[i], grid.is_placed(x, y) as u8]);
+        add(&grid.temperatures()[i].to_bits().to_le_bytes());
+        add(&grid.ages()[i].to_le_bytes());
+        add(&[grid.stains()[i], grid.shades()[i]]);
+    }
+    hash
+}
+
+// A new file in the replays folder, named by when it started.
+fn replay_path() -> Option<PathBuf> {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let file = format!("replay_{}.{}", stamp, REPLAY_EXTENSION);
+    Some(user_data_dir()?.join(REPLAY_FOLDER).join(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{WorldBuilder, bundled_rule_list};
+
+    // Golden replays: each `*.replay.ron` here is played back and must end on the checksum it
+    // holds. `BLESS_REPLAYS=1 cargo test` writes the checksums they end on instead, after a rule
+    // change that is meant to change how worlds play out, and writes `sample.replay.ron` afresh.
+    const GOLDEN_FOLDER: &str = "tests/replays";
+
+    fn golden_folder() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FOLDER)
+    }
+
+    // A box of bedrock with sand, water and lava in it, painted into over a few hundred ticks
+    // while the wind picks up.
+    fn sample_replay(seed: u64) -> Replay {
+        let world = WorldBuilder::new(48, 32)
+            .boxed()
+            .fill(Particle::Water, (4, 1), (20, 6))
+            .fill(Particle::Lava, (30, 1), (40, 6))
+            .snapshot();
+        let square = |x0: i32, y0: i32| {
+            (y0..y0 + 4).flat_map(|y| (x0..x0 + 4).map(move |x| (x, y))).collect::<Vec<_>>()
+        };
+        let paint = |particle, cells| ReplayInput::Paint {
+            particle,
+            data: 0,
+            cells,
+        };
+        let windy = SimParams {
+            wind: 0.5,
+            ..default()
+        };
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            start_tick: 0,
+            world,
+            inputs: vec![
+                (0, ReplayInput::Params(SimParams::default())),
+                (0, ReplayInput::Rules(bundled_rule_list())),
+                (10, paint(Particle::Sand, square(8, 20))),
+                (40, paint(Particle::Water, square(32, 24))),
+                (60, ReplayInput::Params(windy)),
+                (90, paint(Particle::Dust, square(20, 16))),
+                (120, paint(Particle::Oil, square(10, 24))),
+            ],
+            end_tick: 300,
+            checksum: 0,
+        }
+    }
+
+    #[test]
+    fn replays_are_deterministic() {
+        let behaviors = MaterialBehaviors::default();
+        let replay = sample_replay(7);
+        let first = checksum(&replay.run(&behaviors));
+        assert_eq!(first, checksum(&replay.run(&behaviors)));
+        assert_ne!(first, checksum(&replay.start_grid()));
+        // The seed changes how the dice fall.
+        assert_ne!(first, checksum(&sample_replay(8).run(&behaviors)));
+    }
+
+    #[test]
+    fn golden_replays_play_back() {
+        let behaviors = MaterialBehaviors::default();
+        let folder = golden_folder();
+        if std::env::var_os("BLESS_REPLAYS").is_some() {
+            sample_replay(7).save(&folder.join("sample.replay.ron")).unwrap();
+        }
+        let mut played = 0;
+        for entry in std::fs::read_dir(&folder).unwrap() {
+            let path = entry.unwrap().path();
+            if !path.to_string_lossy().ends_with(REPLAY_EXTENSION) {
+                continue;
+            }
+            let mut replay = Replay::load(&path).unwrap();
+            let ended = checksum(&replay.run(&behaviors));
+            if std::env::var_os("BLESS_REPLAYS").is_some() {
+                replay.checksum = ended;
+                replay.save(&path).unwrap();
+            }
+            assert_eq!(ended, replay.checksum, "{:?} no longer plays back the same", path);
+            played += 1;
+        }
+        assert!(played > 0, "no golden replays in {:?}", folder);
+    }
+
+    #[test]
+    fn newer_replays_are_refused() {
+        let path = std::env::temp_dir().join(format!("replay-newer-{}.ron", std::process::id()));
+        let mut replay = sample_replay(0);
+        replay.version = REPLAY_VERSION + 1;
+        replay.save(&path).unwrap();
+        let result = Replay::load(&path);
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(result, Err(ReplayError::Version(v)) if v == REPLAY_VERSION + 1));
+    }
+}
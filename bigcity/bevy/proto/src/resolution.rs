@@ -0,0 +1,549 @@
+// --- IMPORTS ---
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::render::render_resource::Extent3d;
+use bevy::sprite::MeshMaterial2d;
+use serde::{Deserialize, Serialize};
+
+use crate::bodies::Body;
+use crate::display::{DisplaySettings, WORLD_SIZES};
+use crate::levels::PaintRules;
+use crate::pan_zoom::place_camera;
+use crate::shading::NEUTRAL_SHADE;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::worldgen::{StartingTerrain, WorldGeneration};
+use crate::{Particle, ScreenCamera, SimulationDisplay, SimulationMaterial, WorldLayout};
+
+// --- CONSTANTS ---
+// How many cells are left around what has been built when the world is trimmed to it.
+pub const TRIM_PADDING: u32 = 16;
+
+// --- PLUGIN ---
+
+// The world's size while it runs. `WorldRequest` is the size asked for: `--world 512x256` on
+// the command line, or else the display settings' (`display.ron`, edited in the display window),
+// which it follows whenever they change. Once it no longer matches the world's layout, the grid
+// is carried over to the new size, scaled to it or cropped around its bottom middle as the
+// settings say, tags, zones and loop bands with it, and the state and shade textures, the world
+// quad and the camera are made over to match. The rest of the game goes by the layout and the
+// grid, and follows them.
+//
+// A world can also be grown in place, from the display window: `GrowWorld` adds cells on one side,
+// filled with a material or with the starting terrain going on past the old edge, and the world
+// size asked for becomes the grown one. `TrimWorld` does the opposite, cropping the world to what
+// has been built in it, air and bedrock aside, with some padding, after experiments that only used
+// a corner of a big world. Growing on the left or at the bottom and trimming move every cell, and
+// `WorldShifted` tells whatever keeps cells of its own (emitters, bookmarks, paint locks and the
+// undo history) to move them along, so they stay where they were in the world and are saved there.
+pub struct ResolutionPlugin(pub WorldRequest);
+
+impl Plugin for ResolutionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.0)
+            .add_event::<GrowWorld>()
+            .add_event::<TrimWorld>()
+            .add_event::<WorldShifted>()
+            .add_systems(
+                Update,
+                (follow_display_settings, reshape_world, resize_world)
+                    .chain()
+                    .before(SimulationSet)
+                    .run_if(not(resource_exists::<WorldGeneration>)),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// How a world's cells are carried over to a new size.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub enum WorldResize {
+    // Stretched or squeezed to the new size, cell by cell.
+    #[default]
+    Scale,
+    // Kept cell for cell around the bottom middle, cutting off what doesn't fit and filling the
+    // rest with air.
+    Crop,
+}
+
+impl WorldResize {
+    pub const ALL: [WorldResize; 2] = [WorldResize::Scale, WorldResize::Crop];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WorldResize::Scale => "Scale the world",
+            WorldResize::Crop => "Crop the world",
+        }
+    }
+}
+
+// A side of the world.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+pub enum Border {
+    Left,
+    Right,
+    Bottom,
+    #[default]
+    Top,
+}
+
+impl Border {
+    pub const ALL: [Border; 4] = [Border::Left, Border::Right, Border::Bottom, Border::Top];
+}
+
+// What the cells a world grows by are filled with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+pub enum BorderFill {
+    // The terrain the world started in, going on past the old edge.
+    #[default]
+    Terrain,
+    Material(Particle),
+}
+
+impl BorderFill {
+    pub fn label(self) -> String {
+        match self {
+            BorderFill::Terrain => "Starting terrain".to_string(),
+            BorderFill::Material(particle) => format!("{:?}", particle),
+        }
+    }
+}
+
+// --- EVENTS ---
+
+// Asks for the world to grow by `cells` cells on `side`, the new cells filled with `fill`.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct GrowWorld {
+    pub side: Border,
+    pub cells: u32,
+    pub fill: BorderFill,
+}
+
+impl Default for GrowWorld {
+    fn default() -> Self {
+        Self {
+            side: Border::default(),
+            cells: 64,
+            fill: BorderFill::default(),
+        }
+    }
+}
+
+impl GrowWorld {
+    // The size a world laid out as `layout` grows to.
+    fn size(&self, layout: &WorldLayout) -> (u32, u32) {
+        match self.side {
+            Border::Left | Border::Right => (layout.width + self.cells, layout.height),
+            Border::Bottom | Border::Top => (layout.width, layout.height + self.cells),
+        }
+    }
+
+    // How far the world's cells move.
+    fn offset(&self) -> IVec2 {
+        match self.side {
+            Border::Left => IVec2::new(self.cells as i32, 0),
+            Border::Bottom => IVec2::new(0, self.cells as i32),
+            Border::Right | Border::Top => IVec2::ZERO,
+        }
+    }
+}
+
+// Asks for the world to be cropped to what has been built in it, with `padding` cells around it.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct TrimWorld {
+    pub padding: u32,
+}
+
+impl Default for TrimWorld {
+    fn default() -> Self {
+        Self {
+            padding: TRIM_PADDING,
+        }
+    }
+}
+
+// The world grew at its left or bottom edge, or was trimmed, which moved every cell by `offset`.
+#[derive(Event, Clone, Copy, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct WorldShifted {
+    pub offset: IVec2,
+}
+
+// --- RESOURCES ---
+
+// The world's size in cells as asked for, and how the world is carried over when it changes.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct WorldRequest {
+    pub width: u32,
+    pub height: u32,
+    pub resize: WorldResize,
+}
+
+impl WorldRequest {
+    // The display settings' world size, unless `--world WIDTHxHEIGHT` picks another.
+    pub fn new(settings: &DisplaySettings) -> Self {
+        let mut request = Self {
+            width: settings.world.0,
+            height: settings.world.1,
+            resize: settings.resize,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg != "--world" {
+                continue;
+            }
+            let size = args.next().and_then(|size| {
+                let (width, height) = size.split_once('x')?;
+                Some((width.parse().ok()?, height.parse().ok()?))
+            });
+            match size {
+                Some((width, height)) => (request.width, request.height) = (width, height),
+                None => warn!("--world takes a size like 512x256"),
+            }
+        }
+        request
+    }
+
+    // The world's layout at this size, kept to the sizes a world can be.
+    pub fn layout(&self) -> WorldLayout {
+        let clamp = |size: u32| size.clamp(*WORLD_SIZES.start(), *WORLD_SIZES.end());
+        WorldLayout {
+            width: clamp(self.width),
+            height: clamp(self.height),
+        }
+    }
+}
+
+// --- SYSTEM PARAM ---
+
+// What the world is drawn with, which has to be the world's size.
+#[derive(SystemParam)]
+struct WorldDrawing<'w, 's> {
+    display: Res<'w, SimulationDisplay>,
+    images: ResMut<'w, Assets<Image>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    sim_materials: ResMut<'w, Assets<SimulationMaterial>>,
+    q_quad: Query<'w, 's, &'static Mesh2d, With<MeshMaterial2d<SimulationMaterial>>>,
+    q_camera: Query<'w, 's, (&'static mut Transform, &'static mut Projection), With<ScreenCamera>>,
+}
+
+// Growing and trimming the world as asked, and what new cells are made of.
+#[derive(SystemParam)]
+struct Reshaping<'w, 's> {
+    grows: EventReader<'w, 's, GrowWorld>,
+    trims: EventReader<'w, 's, TrimWorld>,
+    shifted: EventWriter<'w, WorldShifted>,
+    rules: Res<'w, PaintRules>,
+    terrain: Option<Res<'w, StartingTerrain>>,
+    // Where the starting terrain's bottom-left cell is now, as growing and trimming move it.
+    origin: Local<'s, IVec2>,
+}
+
+// The world and its size, as laid out and as asked for.
+#[derive(SystemParam)]
+struct WorldShape<'w> {
+    request: ResMut<'w, WorldRequest>,
+    settings: ResMut<'w, DisplaySettings>,
+    layout: ResMut<'w, WorldLayout>,
+    grid: ResMut<'w, SimulationGrid>,
+}
+
+impl WorldShape<'_> {
+    // Puts `reshaped` in place of the world. The size asked for becomes its size, so the world
+    // isn't resized back, and it's kept.
+    fn take_up(&mut self, reshaped: &SimulationGrid) {
+        let (width, height) = (reshaped.width(), reshaped.height());
+        self.grid.restore(reshaped);
+        *self.layout = WorldLayout { width, height };
+        (self.request.width, self.request.height) = (width, height);
+        self.settings.world = (width, height);
+    }
+}
+
+// --- SYSTEMS ---
+
+// Takes up the display settings' world size whenever they change, leaving the size asked for on
+// the command line until then.
+fn follow_display_settings(
+    settings: Res<DisplaySettings>,
+    mut request: ResMut<WorldRequest>,
+    mut applied: Local<Option<((u32, u32), WorldResize)>>,
+) {
+    let asked = (settings.world, settings.resize);
+    if applied.replace(asked).is_none_or(|applied| applied == asked) {
+        return;
+    }
+    request.set_if_neq(WorldRequest {
+        width: asked.0.0,
+        height: asked.0.1,
+        resize: asked.1,
+    });
+}
+
+fn resize_world(
+    request: Res<WorldRequest>,
+    mut layout: ResMut<WorldLayout>,
+    mut grid: ResMut<SimulationGrid>,
+    mut drawing: WorldDrawing,
+) {
+    let resized = request.layout();
+    if !request.is_changed() || resized == *layout {
+        return;
+    }
+    info!(
+        "Resizing the world from {}x{} to {}x{} ({:?})",
+        layout.width, layout.height, resized.width, resized.height, request.resize
+    );
+    let carried = resized_grid(&grid, resized.width, resized.height, request.resize);
+    grid.restore(&carried);
+    *layout = resized;
+    fit_drawing(&mut drawing, &resized);
+}
+
+fn reshape_world(mut reshaping: Reshaping, mut shape: WorldShape, mut drawing: WorldDrawing) {
+    let Reshaping {
+        grows,
+        trims,
+        shifted,
+        rules,
+        terrain,
+        origin,
+    } = &mut reshaping;
+    if rules.protect_world {
+        if !grows.is_empty() || !trims.is_empty() {
+            info!("Levels can't be grown or trimmed");
+        }
+        grows.clear();
+        trims.clear();
+        return;
+    }
+    for request in grows.read() {
+        let (width, height) = request.size(&shape.layout);
+        if request.cells == 0 || width.max(height) > *WORLD_SIZES.end() {
+            info!("A world can be at most {} cells along either side", WORLD_SIZES.end());
+            continue;
+        }
+        let offset = request.offset();
+        let start = **origin + offset;
+        let fill = |cell: IVec2| match request.fill {
+            BorderFill::Material(particle) => particle,
+            BorderFill::Terrain => terrain
+                .as_ref()
+                .map_or(Particle::Air, |terrain| terrain.particle(cell - start)),
+        };
+        let grown = grown_grid(&shape.grid, width, height, offset, fill);
+        shape.take_up(&grown);
+        **origin = start;
+        fit_drawing(&mut drawing, &shape.layout);
+        if offset != IVec2::ZERO {
+            shifted.write(WorldShifted { offset });
+        }
+        info!(
+            "Grew the world by {} cells on the {} to {}x{}",
+            request.cells,
+            format!("{:?}", request.side).to_lowercase(),
+            width,
+            height
+        );
+    }
+    for request in trims.read() {
+        let Some((trimmed, offset)) = trimmed_grid(&shape.grid, request.padding) else {
+            info!("There is nothing to trim the world to");
+            continue;
+        };
+        shape.take_up(&trimmed);
+        **origin += offset;
+        fit_drawing(&mut drawing, &shape.layout);
+        if offset != IVec2::ZERO {
+            shifted.write(WorldShifted { offset });
+        }
+        info!("Trimmed the world to {}x{}", trimmed.width(), trimmed.height());
+    }
+}
+
+// --- HELPERS ---
+
+// Makes the state, shade and backdrop textures, the world quad and the camera over for a world
+// laid out as `layout`, showing all of it.
+fn fit_drawing(drawing: &mut WorldDrawing, layout: &WorldLayout) {
+    let size = Extent3d {
+        width: layout.width,
+        height: layout.height,
+        ..default()
+    };
+    // The new texels are filled in as the grid uploads and the shades are worked out again.
+    if let Some(image) = drawing.images.get_mut(&drawing.display.state_image) {
+        image.resize(size);
+    }
+    if let Some(image) = drawing.images.get_mut(&drawing.display.shade_image) {
+        image.resize(size);
+        if let Some(data) = image.data.as_mut() {
+            data.fill(NEUTRAL_SHADE);
+        }
+    }
+    // The walls are drawn again from the grid's.
+    if let Some(image) = drawing.images.get_mut(&drawing.display.backdrop_image) {
+        image.resize(size);
+    }
+    // Touching the material makes its bind group pick up the resized textures.
+    drawing.sim_materials.get_mut(&drawing.display.material);
+    for quad in &drawing.q_quad {
+        if let Some(mesh) = drawing.meshes.get_mut(&quad.0) {
+            *mesh = Rectangle::from_size(layout.world_size()).into();
+        }
+    }
+    for (mut transform, mut projection) in &mut drawing.q_camera {
+        let Projection::Orthographic(ortho) = &mut *projection else { continue };
+        ortho.scaling_mode = ScalingMode::AutoMin {
+            min_width: layout.world_size().x,
+            min_height: layout.world_size().y,
+        };
+        place_camera(&mut transform, ortho, Vec2::ZERO, 1.0, layout);
+    }
+}
+
+// `grid` carried over to `width` x `height` as `resize` says, with its tags, zones, loop bands
+// and material overrides. Zones and bands that end up outside the new world are dropped.
+fn resized_grid(
+    grid: &SimulationGrid,
+    width: u32,
+    height: u32,
+    resize: WorldResize,
+) -> SimulationGrid {
+    let from = IVec2::new(grid.width() as i32, grid.height() as i32);
+    let to = IVec2::new(width as i32, height as i32);
+    let shift = IVec2::new((to.x - from.x) / 2, 0);
+    // Where the corner of an old cell lands in the new world, and where a new cell comes from.
+    let carry = |cell: IVec2| match resize {
+        WorldResize::Scale => cell * to / from,
+        WorldResize::Crop => cell + shift,
+    };
+    let source = |cell: IVec2| match resize {
+        WorldResize::Scale => cell * from / to,
+        WorldResize::Crop => cell - shift,
+    };
+    carried_grid(grid, to, carry, source)
+}
+
+// The inclusive corners of what has been built in `grid`, everything but air and bedrock, with
+// `padding` cells around it, kept inside the world and at least as big as a world can be small.
+// `None` for a world with nothing built in it.
+fn content_bounds(grid: &SimulationGrid, padding: u32) -> Option<(IVec2, IVec2)> {
+    let (mut min, mut max) = (IVec2::MAX, IVec2::MIN);
+    for y in 0..grid.height() as i32 {
+        for x in 0..grid.width() as i32 {
+            let built = grid.get(x, y).filter(|&p| p != Particle::Air && p != Particle::Bedrock);
+            if built.is_some() {
+                (min, max) = (min.min(IVec2::new(x, y)), max.max(IVec2::new(x, y)));
+            }
+        }
+    }
+    if min.x > max.x {
+        return None;
+    }
+    let world = IVec2::new(grid.width() as i32, grid.height() as i32);
+    let padding = IVec2::splat(padding as i32);
+    let (min, max) = ((min - padding).max(IVec2::ZERO), (max + padding).min(world - IVec2::ONE));
+    // A box smaller than that grows on both sides, and is pushed back inside the world.
+    let padded = max - min + IVec2::ONE;
+    let size = padded.max(IVec2::splat(*WORLD_SIZES.start() as i32).min(world));
+    let min = (min - (size - padded) / 2).clamp(IVec2::ZERO, world - size);
+    Some((min, min + size - IVec2::ONE))
+}
+
+// `grid` cropped to `content_bounds`, and how far its cells moved; `None` if nothing has been
+// built or there is nothing around it to crop.
+pub fn trimmed_grid(grid: &SimulationGrid, padding: u32) -> Option<(SimulationGrid, IVec2)> {
+    let (min, max) = content_bounds(grid, padding)?;
+    let to = max - min + IVec2::ONE;
+    if to == IVec2::new(grid.width() as i32, grid.height() as i32) {
+        return None;
+    }
+    Some((carried_grid(grid, to, |cell| cell - min, |cell| cell + min), -min))
+}
+
+// `grid` grown to `width` x `height` with its cells moved by `offset`, everything else carried
+// over as it was, and every new cell made of what `fill` says is there.
+fn grown_grid(
+    grid: &SimulationGrid,
+    width: u32,
+    height: u32,
+    offset: IVec2,
+    fill: impl Fn(IVec2) -> Particle,
+) -> SimulationGrid {
+    let to = IVec2::new(width as i32, height as i32);
+    let mut grown = carried_grid(grid, to, |cell| cell + offset, |cell| cell - offset);
+    for y in 0..to.y {
+        for x in 0..to.x {
+            let from = IVec2::new(x, y) - offset;
+            if grid.get(from.x, from.y).is_none() {
+                grown.set(x, y, fill(IVec2::new(x, y)));
+            }
+        }
+    }
+    grown
+}
+
+// A `to`-sized world of `grid`'s cells, tags, bodies, walls, zones, loop bands and material
+// overrides.
+// `carry` is where the corner of an old cell lands in the new world and `source` where a new cell
+// comes from; new cells from outside the old world are air.
+fn carried_grid(
+    grid: &SimulationGrid,
+    to: IVec2,
+    carry: impl Fn(IVec2) -> IVec2,
+    source: impl Fn(IVec2) -> IVec2,
+) -> SimulationGrid {
+    let mut resized = SimulationGrid::new(to.x as u32, to.y as u32);
+    resized.tags_mut().tags = grid.tags().tags.clone();
+    resized.set_seed(grid.seed());
+    // A body goes where the cell its center is in goes. Its cells are carried with the rest, and
+    // its next move stamps them in whole again.
+    for &body in &grid.bodies().bodies {
+        let center = Vec2::from(body.position);
+        let cell = carry(center.floor().as_ivec2()).as_vec2() + center - center.floor();
+        resized.bodies_mut().bodies.push(Body {
+            position: cell.into(),
+            ..body
+        });
+    }
+    for y in 0..to.y {
+        for x in 0..to.x {
+            let from = source(IVec2::new(x, y));
+            if let Some(cell) = grid.cell(from.x, from.y) {
+                resized.set_cell(x, y, cell);
+                resized.set_tag(x, y, grid.tag(from.x, from.y));
+                resized.set_backdrop(x, y, grid.backdrop(from.x, from.y).unwrap_or_default());
+            }
+        }
+    }
+
+    // Inclusive ranges of cells, as zones and bands keep them, carried and kept inside the world.
+    let span = |min: IVec2, max: IVec2| {
+        let min = carry(min).max(IVec2::ZERO);
+        let max = (carry(max + IVec2::ONE) - IVec2::ONE).min(to - IVec2::ONE);
+        (min.x <= max.x && min.y <= max.y).then_some((min, max))
+    };
+    for zone in grid.zones() {
+        let Some((min, max)) = span(zone.min.into(), zone.max.into()) else { continue };
+        let mut zone = zone.clone();
+        (zone.min, zone.max) = (min.into(), max.into());
+        resized.add_zone(zone);
+    }
+    for band in grid.loops() {
+        let min = IVec2::new(band.columns.0, band.bottom);
+        let Some((min, max)) = span(min, IVec2::new(band.columns.1, band.top)) else { continue };
+        let mut band = band.clone();
+        (band.columns, band.bottom, band.top) = ((min.x, max.x), min.y, max.y);
+        resized.add_loop(band);
+    }
+    resized.set_material_overrides(grid.material_overrides().to_vec());
+    resized
+}
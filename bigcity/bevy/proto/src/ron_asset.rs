@@ -0,0 +1,55 @@
+// --- IMPORTS ---
+use std::marker::PhantomData;
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+// --- LOADER ---
+
+// Loads any deserializable asset from a RON file. Data-driven content (demos, presets, levels...)
+// registers one of these per asset type instead of writing a loader each time.
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RonAssetLoaderError {
+    #[error("could not read asset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse asset: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<A, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
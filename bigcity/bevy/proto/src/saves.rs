@@ -0,0 +1,547 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::autosave::{AUTOSAVE_INTERVALS, AUTOSAVE_SLOTS, AutosaveSettings};
+use crate::bookmarks::{BOOKMARK_SLOTS, Bookmark, Bookmarks};
+use crate::levels::{PaintRules, ScenarioBases};
+use crate::persist::{file_stem, user_data_dir};
+use crate::resolution::{TRIM_PADDING, trimmed_grid};
+use crate::sim::{
+    SimulationControl, SimulationGrid, SimulationSet, SimulationTickRate, ViewOnly,
+};
+use crate::Particle;
+use crate::snapshot::WorldSnapshot;
+use crate::world_file::{LoadedWorld, WorldFileError, WorldSerializer};
+use crate::world_patch::{PatchError, WorldPatch};
+
+// --- CONSTANTS ---
+const SAVES_FOLDER: &str = "saves";
+// The world's picture and its sidecar (see `world_file`). Older saves kept it as one snapshot.
+const CELLS_EXTENSION: &str = "cells.png";
+const SIDECAR_EXTENSION: &str = "cells.ron";
+const SNAPSHOT_EXTENSION: &str = "world.ron";
+// Or, for a world that started from a scenario, a patch against it (see `world_patch`).
+const PATCH_EXTENSION: &str = "patch";
+const META_EXTENSION: &str = "meta.ron";
+const THUMBNAIL_EXTENSION: &str = "png";
+// Thumbnails are the world shrunk to this many pixels wide, keeping its proportions.
+const THUMBNAIL_WIDTH: u32 = 96;
+const DEFAULT_NAME: &str = "My world";
+
+// --- PLUGIN ---
+
+// The saved worlds browser (N): saves the current world under a name, and lists every saved world
+// with a thumbnail, when it was saved, its size and how long it had been played, to load, rename
+// or delete. Each save is four files in `saves` in the user data directory: the world as a
+// picture of its cells with a sidecar, a small RON file of metadata and the thumbnail. A world
+// that started from a level is saved as a patch against the level's starting world instead of
+// the picture and sidecar, a file of a few hundred bytes to share, and falls back to a full save
+// when the level isn't available to be the base. With Trim to what's built ticked, a save only
+// keeps the part of the world something has been built in (see `trimmed_grid`), bookmarks moved
+// to match, so experiments in a corner of a huge world make small saves. How often the world
+// autosaves is set here too (see autosave.rs).
+pub struct SavesPlugin;
+
+impl Plugin for SavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveBrowser>()
+            .add_systems(Update, (toggle_browser, count_playtime.in_set(SimulationSet)))
+            .add_systems(EguiContextPass, draw_browser);
+    }
+}
+
+// --- TYPES ---
+
+// What the browser shows about a save without loading its world.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct SaveMeta {
+    name: String,
+    // When it was saved, in seconds since the Unix epoch.
+    saved_at: u64,
+    width: u32,
+    height: u32,
+    // How long the world had been played, in seconds, over every session it was loaded in.
+    playtime: f64,
+    #[serde(default)]
+    bookmarks: [Option<Bookmark>; BOOKMARK_SLOTS],
+}
+
+struct SaveEntry {
+    // The shared file name of the save's files, without extensions.
+    stem: String,
+    meta: SaveMeta,
+    thumbnail: Option<egui::ColorImage>,
+    texture: Option<egui::TextureHandle>,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode: {0}")]
+    Encode(#[from] ron::Error),
+    #[error("unreadable: {0}")]
+    Decode(#[from] ron::error::SpannedError),
+    #[error("{0}")]
+    World(#[from] WorldFileError),
+    #[error("{0}")]
+    Patch(#[from] PatchError),
+    #[error("could not save the thumbnail: {0}")]
+    Thumbnail(#[from] image::ImageError),
+    #[error("no user data directory")]
+    NoDataDir,
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct SaveBrowser {
+    open: bool,
+    entries: Vec<SaveEntry>,
+    // The name the current world is saved under; the last one it was saved or loaded as.
+    name: String,
+    // How long the current world has been played, carried over from its save when loaded.
+    playtime: f64,
+    // The save being renamed, and its new name.
+    renaming: Option<(String, String)>,
+    // The save waiting for a second click to be deleted.
+    deleting: Option<String>,
+    // Whether saves are cropped to what has been built in the world.
+    trim: bool,
+    status: String,
+}
+
+impl Default for SaveBrowser {
+    fn default() -> Self {
+        Self {
+            open: false,
+            entries: Vec::new(),
+            name: DEFAULT_NAME.to_string(),
+            playtime: 0.0,
+            renaming: None,
+            deleting: None,
+            trim: false,
+            status: String::new(),
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_browser(keys: Res<ButtonInput<KeyCode>>, mut browser: ResMut<SaveBrowser>) {
+    if !keys.just_pressed(KeyCode::KeyN) {
+        return;
+    }
+    browser.open = !browser.open;
+    if browser.open {
+        browser.entries = list_saves();
+    }
+}
+
+// Playtime only counts while the world runs, not while it is paused in the background (when the
+// simulation set sits out), while paused, at a tick rate of 0 or mirrored from somewhere else.
+fn count_playtime(
+    time: Res<Time>,
+    tick_rate: Res<SimulationTickRate>,
+    control: Res<SimulationControl>,
+    view_only: Option<Res<ViewOnly>>,
+    mut browser: ResMut<SaveBrowser>,
+) {
+    if tick_rate.ticks_per_second > 0.0 && !control.paused && view_only.is_none() {
+        browser.bypass_change_detection().playtime += time.delta_secs_f64();
+    }
+}
+
+fn draw_browser(
+    mut contexts: EguiContexts,
+    mut browser: ResMut<SaveBrowser>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+    mut bookmarks: ResMut<Bookmarks>,
+    scenarios: ScenarioBases,
+    mut autosave: ResMut<AutosaveSettings>,
+) {
+    if !browser.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let browser = &mut *browser;
+    for entry in &mut browser.entries {
+        if let (None, Some(thumbnail)) = (&entry.texture, entry.thumbnail.take()) {
+            let id = format!("save_thumbnail_{}", entry.stem);
+            entry.texture = Some(ctx.load_texture(id, thumbnail, egui::TextureOptions::NEAREST));
+        }
+    }
+
+    let mut open = true;
+    let mut load = None;
+    let mut rename = None;
+    let mut delete = None;
+    egui::Window::new("Saved worlds").open(&mut open).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut browser.name);
+            let stem = file_stem(browser.name.trim());
+            let exists = browser.entries.iter().any(|entry| entry.stem == stem);
+            let button = egui::Button::new(if exists { "Overwrite" } else { "Save" });
+            if ui.add_enabled(!browser.name.trim().is_empty(), button).clicked() {
+                let name = browser.name.trim().to_string();
+                let size = UVec2::new(grid.width(), grid.height());
+                let base = scenarios.current().and_then(|scenario| {
+                    let base = scenarios.base(&scenario, size)?;
+                    Some((scenario, base))
+                });
+                // A patch has to be the size of its level, and a level can't be cropped anyway.
+                let trimmed = (browser.trim && base.is_none())
+                    .then(|| trimmed_grid(&grid, TRIM_PADDING))
+                    .flatten();
+                let mut slots = bookmarks.slots.clone();
+                if let Some((_, offset)) = &trimmed {
+                    slots.iter_mut().flatten().for_each(|bookmark| bookmark.shift(*offset));
+                }
+                let saved_grid = trimmed.as_ref().map_or(&*grid, |(trimmed, _)| trimmed);
+                let saved = save_world(saved_grid, &name, base, browser.playtime, &slots);
+                browser.status = match saved {
+                    Ok(None) => format!("Saved \"{}\"", name),
+                    Ok(Some((scenario, bytes))) => format!(
+                        "Saved \"{}\" as a patch against {} ({} bytes)",
+                        name, scenario, bytes
+                    ),
+                    Err(err) => format!("Could not save \"{}\": {}", name, err),
+                };
+                browser.entries = list_saves();
+            }
+        });
+        ui.checkbox(&mut browser.trim, "Trim to what's built")
+            .on_hover_text("Saves only the part of the world with something built in it");
+        let mut settings = autosave.clone();
+        ui.horizontal(|ui| {
+            ui.label("Autosave every");
+            let interval = egui::DragValue::new(&mut settings.interval_minutes)
+                .range(AUTOSAVE_INTERVALS)
+                .suffix(" min");
+            ui.add(interval).on_hover_text("0 turns autosaves off");
+            ui.label("keeping");
+            ui.add(egui::DragValue::new(&mut settings.slots).range(AUTOSAVE_SLOTS));
+        });
+        if settings != *autosave {
+            settings.save();
+            *autosave = settings;
+        }
+
+        ui.separator();
+        if browser.entries.is_empty() {
+            ui.label("No saved worlds yet.");
+        }
+        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+            egui::Grid::new("saved_worlds").num_columns(3).striped(true).show(ui, |ui| {
+                for entry in &browser.entries {
+                    match &entry.texture {
+                        Some(texture) => {
+                            ui.add(egui::Image::new(texture).fit_to_original_size(1.0));
+                        }
+                        None => {
+                            ui.label("-");
+                        }
+                    }
+                    ui.vertical(|ui| match &mut browser.renaming {
+                        Some((stem, name)) if *stem == entry.stem => {
+                            ui.text_edit_singleline(name);
+                            ui.horizontal(|ui| {
+                                if ui.button("Rename").clicked() {
+                                    rename = Some(true);
+                                }
+                                if ui.button("Cancel").clicked() {
+                                    rename = Some(false);
+                                }
+                            });
+                        }
+                        _ => {
+                            ui.strong(&entry.meta.name);
+                            ui.label(format!("Saved {}", date_time(entry.meta.saved_at)));
+                            ui.label(format!(
+                                "{}x{} cells, played {}",
+                                entry.meta.width,
+                                entry.meta.height,
+                                duration(entry.meta.playtime)
+                            ));
+                        }
+                    });
+                    ui.vertical(|ui| {
+                        let button = egui::Button::new("Load");
+                        if ui.add_enabled(!rules.protect_world, button).clicked() {
+                            load = Some(entry.stem.clone());
+                        }
+                        if ui.button("Rename").clicked() {
+                            browser.renaming = Some((entry.stem.clone(), entry.meta.name.clone()));
+                        }
+                        let confirming = browser.deleting.as_ref() == Some(&entry.stem);
+                        if ui.button(if confirming { "Really delete?" } else { "Delete" }).clicked()
+                        {
+                            delete = Some(entry.stem.clone());
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+
+        if !browser.status.is_empty() {
+            ui.separator();
+            ui.label(&browser.status);
+        }
+    });
+    browser.open &= open;
+
+    if let Some(stem) = load {
+        match load_world(&stem, &scenarios) {
+            Ok((LoadedWorld { snapshot, unknown, .. }, meta)) => {
+                snapshot.apply_to(&mut grid);
+                browser.status = match unknown.as_slice() {
+                    [] => format!("Loaded \"{}\"", meta.name),
+                    unknown => format!(
+                        "Loaded \"{}\"; materials this version lacks became air: {}",
+                        meta.name,
+                        unknown.join(", ")
+                    ),
+                };
+                browser.playtime = meta.playtime;
+                browser.name = meta.name;
+                bookmarks.slots = meta.bookmarks;
+            }
+            Err(err) => browser.status = format!("Could not load {:?}: {}", stem, err),
+        }
+    }
+    if let Some(confirmed) = rename
+        && let Some((stem, name)) = browser.renaming.take()
+        && confirmed
+        && !name.trim().is_empty()
+    {
+        if let Err(err) = rename_save(&stem, name.trim()) {
+            browser.status = format!("Could not rename {:?}: {}", stem, err);
+        }
+        browser.entries = list_saves();
+    }
+    if let Some(stem) = delete {
+        if browser.deleting.as_ref() != Some(&stem) {
+            browser.deleting = Some(stem);
+            return;
+        }
+        browser.deleting = None;
+        browser.status = match delete_save(&stem) {
+            Ok(()) => format!("Deleted {:?}", stem),
+            Err(err) => format!("Could not delete {:?}: {}", stem, err),
+        };
+        browser.entries = list_saves();
+    }
+}
+
+// --- HELPERS ---
+
+fn saves_dir() -> Result<PathBuf, SaveError> {
+    Ok(user_data_dir().ok_or(SaveError::NoDataDir)?.join(SAVES_FOLDER))
+}
+
+fn save_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{}.{}", stem, extension))
+}
+
+// Every save with readable metadata, the most recent first.
+fn list_saves() -> Vec<SaveEntry> {
+    let Ok(dir) = saves_dir() else { return Vec::new() };
+    let Ok(files) = std::fs::read_dir(&dir) else { return Vec::new() };
+    let suffix = format!(".{}", META_EXTENSION);
+    let mut entries: Vec<SaveEntry> = files
+        .filter_map(|file| {
+            let file_name = file.ok()?.file_name().into_string().ok()?;
+            let stem = file_name.strip_suffix(&suffix)?.to_string();
+            let meta = read_meta(&dir, &stem)
+                .inspect_err(|err| warn!("Ignoring save {:?}: {}", stem, err))
+                .ok()?;
+            let thumbnail = image::open(save_path(&dir, &stem, THUMBNAIL_EXTENSION)).ok();
+            let thumbnail = thumbnail.map(|image| {
+                let image = image.to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw())
+            });
+            Some(SaveEntry {
+                stem,
+                meta,
+                thumbnail,
+                texture: None,
+            })
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.meta.saved_at));
+    entries
+}
+
+fn read_meta(dir: &Path, stem: &str) -> Result<SaveMeta, SaveError> {
+    let text = std::fs::read_to_string(save_path(dir, stem, META_EXTENSION))?;
+    Ok(ron::from_str(&text)?)
+}
+
+fn write_meta(dir: &Path, stem: &str, meta: &SaveMeta) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(meta, default())?;
+    std::fs::write(save_path(dir, stem, META_EXTENSION), text)?;
+    Ok(())
+}
+
+// Saves the world under a file name made from `name`, replacing a save of the same name: as a
+// patch against `base`, the scenario it started from with that scenario's name, if given. The
+// scenario and the patch's size in bytes come back for a patch.
+fn save_world(
+    grid: &SimulationGrid,
+    name: &str,
+    base: Option<(String, SimulationGrid)>,
+    playtime: f64,
+    bookmarks: &[Option<Bookmark>; BOOKMARK_SLOTS],
+) -> Result<Option<(String, usize)>, SaveError> {
+    let dir = saves_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let stem = file_stem(name);
+    let (cells, sidecar) =
+        (save_path(&dir, &stem, CELLS_EXTENSION), save_path(&dir, &stem, SIDECAR_EXTENSION));
+    let patch = save_path(&dir, &stem, PATCH_EXTENSION);
+    let patched = match base {
+        Some((scenario, base)) => {
+            let bytes = WorldPatch::save(grid, &scenario, &base, &patch)?;
+            remove_if_present(&cells)?;
+            remove_if_present(&sidecar)?;
+            Some((scenario, bytes))
+        }
+        None => {
+            WorldSerializer::save(grid, &cells, &sidecar)?;
+            remove_if_present(&patch)?;
+            None
+        }
+    };
+    // Overwriting a save from before the sidecar format drops its old snapshot.
+    remove_if_present(&save_path(&dir, &stem, SNAPSHOT_EXTENSION))?;
+    thumbnail(grid).save(save_path(&dir, &stem, THUMBNAIL_EXTENSION))?;
+    let meta = SaveMeta {
+        name: name.to_string(),
+        saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        width: grid.width(),
+        height: grid.height(),
+        playtime,
+        bookmarks: bookmarks.clone(),
+    };
+    write_meta(&dir, &stem, &meta)?;
+    Ok(patched)
+}
+
+fn load_world(
+    stem: &str,
+    scenarios: &ScenarioBases,
+) -> Result<(LoadedWorld, SaveMeta), SaveError> {
+    let dir = saves_dir()?;
+    let sidecar = save_path(&dir, stem, SIDECAR_EXTENSION);
+    let patch = save_path(&dir, stem, PATCH_EXTENSION);
+    let world = if patch.exists() {
+        let (scenario, size) = WorldPatch::base_of(&patch)?;
+        let base = scenarios.base(&scenario, size).ok_or(PatchError::MissingBase(scenario))?;
+        WorldPatch::load(&patch, &base)?
+    } else if sidecar.exists() {
+        WorldSerializer::load(&save_path(&dir, stem, CELLS_EXTENSION), &sidecar)?
+    } else {
+        let text = std::fs::read_to_string(save_path(&dir, stem, SNAPSHOT_EXTENSION))?;
+        let snapshot: WorldSnapshot = ron::from_str(&text)?;
+        LoadedWorld {
+            snapshot,
+            name: None,
+            unknown: Vec::new(),
+        }
+    };
+    Ok((world, read_meta(&dir, stem)?))
+}
+
+// Only the name shown changes; the files keep theirs.
+fn rename_save(stem: &str, name: &str) -> Result<(), SaveError> {
+    let dir = saves_dir()?;
+    let meta = SaveMeta {
+        name: name.to_string(),
+        ..read_meta(&dir, stem)?
+    };
+    write_meta(&dir, stem, &meta)
+}
+
+fn delete_save(stem: &str) -> Result<(), SaveError> {
+    let dir = saves_dir()?;
+    // The metadata goes first, so a save that is only partly deleted drops out of the list.
+    let extensions = [
+        META_EXTENSION,
+        SIDECAR_EXTENSION,
+        CELLS_EXTENSION,
+        PATCH_EXTENSION,
+        SNAPSHOT_EXTENSION,
+        THUMBNAIL_EXTENSION,
+    ];
+    for extension in extensions {
+        remove_if_present(&save_path(&dir, stem, extension))?;
+    }
+    Ok(())
+}
+
+pub fn remove_if_present(path: &Path) -> Result<(), SaveError> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+// The world shrunk to the thumbnail's width, every pixel the color of the cell it lands on.
+fn thumbnail(grid: &SimulationGrid) -> image::RgbaImage {
+    let (width, height) = (grid.width(), grid.height());
+    let thumbnail_height = (height * THUMBNAIL_WIDTH).div_ceil(width.max(1)).max(1);
+    image::RgbaImage::from_fn(THUMBNAIL_WIDTH, thumbnail_height, |x, y| {
+        let cell_x = x * width / THUMBNAIL_WIDTH;
+        // Picture rows run top-down, grid rows bottom-up.
+        let cell_y = height - 1 - (y * height / thumbnail_height).min(height - 1);
+        let particle = grid.get(cell_x as i32, cell_y as i32).unwrap_or(Particle::Air);
+        image::Rgba(particle.color().to_srgba().to_u8_array())
+    })
+}
+
+// "2026-10-14 09:30 UTC", from seconds since the Unix epoch.
+pub fn date_time(seconds: u64) -> String {
+    let (days, rest) = ((seconds / 86_400) as i64, seconds % 86_400);
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60
+    )
+}
+
+// "1h 05m", "12m 30s" or "45s".
+fn duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (seconds / 3_600, seconds % 3_600 / 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,247 @@
+// --- IMPORTS ---
+use std::path::PathBuf;
+
+use bevy::ecs::entity::EntityHashMap;
+use bevy::prelude::*;
+use bevy::scene::SceneSpawnError;
+use bevy::scene::serde::SceneDeserializer;
+use serde::de::DeserializeSeed;
+use thiserror::Error;
+
+use crate::ScreenCamera;
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::emitters::{Outlet, ParticleDrain, ParticleEmitter};
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::SimulationGrid;
+use crate::world_file::{LoadedWorld, WorldFileError, WorldSerializer};
+
+// --- CONSTANTS ---
+const SCENES_FOLDER: &str = "scenes";
+// The entities as a Bevy scene, and the grid beside it as a picture of its cells with a sidecar
+// (see `world_file`), zones and all.
+const SCENE_FILE: &str = "world.scn.ron";
+const CELLS_FILE: &str = "world.cells.png";
+const SIDECAR_FILE: &str = "world.cells.ron";
+
+// --- PLUGIN ---
+
+// The world as a Bevy scene, in builds with the `reflect` feature: Ctrl+F7 writes what lives in
+// the world as entities, the emitters and drains and the screen camera, together with the camera
+// bookmarks, to `scenes/world.scn.ron` in the user data directory, and the grid next to it as
+// `world.cells.png` and `world.cells.ron`, which hold the world's zones too. The scene is plain
+// Bevy scene RON, so it can be edited by hand or with Bevy's scene tooling, and Ctrl+Shift+F7
+// brings both files back: the grid is loaded, the emitters and drains replace the ones there are,
+// the camera moves to where the scene has it and the bookmarks are the scene's. Levels can't be
+// replaced this way.
+pub struct ScenesPlugin;
+
+impl Plugin for ScenesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Outlet>()
+            .register_type::<ParticleEmitter>()
+            .register_type::<ParticleDrain>()
+            .register_type::<ScreenCamera>()
+            .register_type::<Bookmarks>()
+            .register_type::<Bookmark>()
+            .add_systems(Update, (export_scene.run_if(exporting), import_scene.run_if(importing)));
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Debug, Error)]
+enum SceneError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not encode: {0}")]
+    Encode(#[from] ron::Error),
+    #[error("unreadable: {0}")]
+    Decode(#[from] ron::error::SpannedError),
+    #[error("{0}")]
+    World(#[from] WorldFileError),
+    #[error("{0}")]
+    Spawn(#[from] SceneSpawnError),
+    #[error("no user data directory")]
+    NoDataDir,
+}
+
+// --- SYSTEMS ---
+
+fn exporting(keys: Res<ButtonInput<KeyCode>>) -> bool {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    ctrl_held(&keys) && !shift && keys.just_pressed(KeyCode::F7)
+}
+
+fn importing(keys: Res<ButtonInput<KeyCode>>) -> bool {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    ctrl_held(&keys) && shift && keys.just_pressed(KeyCode::F7)
+}
+
+fn export_scene(world: &World) {
+    match write_scene(world) {
+        Ok(path) => info!("Exported the world as a scene to {}", path.display()),
+        Err(err) => warn!("Couldn't export the world as a scene: {}", err),
+    }
+}
+
+fn import_scene(
+    mut commands: Commands,
+    registry: Res<AppTypeRegistry>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+    mut bookmarks: ResMut<Bookmarks>,
+    q_outlets: Query<Entity, With<Outlet>>,
+    mut q_camera: Query<(&mut Transform, &mut Projection), With<ScreenCamera>>,
+) {
+    if rules.protect_world {
+        info!("Levels can't be replaced with a scene");
+        return;
+    }
+    let (mut scene, loaded) = match read_scene(&registry) {
+        Ok(read) => read,
+        Err(err) => {
+            warn!("Couldn't import the scene: {}", err);
+            return;
+        }
+    };
+
+    loaded.snapshot.apply_to(&mut grid);
+    if !loaded.unknown.is_empty() {
+        warn!("Materials this version lacks became air: {}", loaded.unknown.join(", "));
+    }
+    for outlet in &q_outlets {
+        commands.entity(outlet).despawn();
+    }
+    let mut q_imported =
+        scene.query::<(&Outlet, Option<&ParticleEmitter>, Option<&ParticleDrain>)>();
+    let mut outlets = 0;
+    for (outlet, emitter, drain) in q_imported.iter(&scene) {
+        let mut entity = commands.spawn(outlet.clone());
+        if let Some(&emitter) = emitter {
+            entity.insert(emitter);
+        }
+        if let Some(&drain) = drain {
+            entity.insert(drain);
+        }
+        outlets += 1;
+    }
+    let mut q_imported_camera =
+        scene.query_filtered::<(&Transform, &Projection), With<ScreenCamera>>();
+    if let (Ok((transform, projection)), Ok((mut camera, mut camera_projection))) =
+        (q_imported_camera.single(&scene), q_camera.single_mut())
+    {
+        *camera = *transform;
+        *camera_projection = projection.clone();
+    }
+    if let Some(imported) = scene.remove_resource::<Bookmarks>() {
+        *bookmarks = imported;
+    }
+    info!("Imported the scene with {} emitters and drains", outlets);
+}
+
+// --- HELPERS ---
+
+fn scenes_dir() -> Result<PathBuf, SceneError> {
+    Ok(user_data_dir().ok_or(SceneError::NoDataDir)?.join(SCENES_FOLDER))
+}
+
+// Writes the scene and the grid beside it, returning where the scene went.
+fn write_scene(world: &World) -> Result<PathBuf, SceneError> {
+    let dir = scenes_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let text = scene_ron(world)?;
+    let grid = world.resource::<SimulationGrid>();
+    WorldSerializer::save(grid, &dir.join(CELLS_FILE), &dir.join(SIDECAR_FILE))?;
+    let path = dir.join(SCENE_FILE);
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+// Reads the scene into a world of its own, to be taken apart, and the grid beside it.
+fn read_scene(registry: &AppTypeRegistry) -> Result<(World, LoadedWorld), SceneError> {
+    let dir = scenes_dir()?;
+    let scene = scene_world(&std::fs::read_to_string(dir.join(SCENE_FILE))?, registry)?;
+    let loaded = WorldSerializer::load(&dir.join(CELLS_FILE), &dir.join(SIDECAR_FILE))?;
+    Ok((scene, loaded))
+}
+
+// The outlets, the screen camera and the bookmarks of `world` as scene RON.
+fn scene_ron(world: &World) -> Result<String, SceneError> {
+    let entities = world
+        .iter_entities()
+        .filter(|entity| entity.contains::<Outlet>() || entity.contains::<ScreenCamera>())
+        .map(|entity| entity.id());
+    let scene = DynamicSceneBuilder::from_world(world)
+        .deny_all()
+        .allow_component::<Outlet>()
+        .allow_component::<ParticleEmitter>()
+        .allow_component::<ParticleDrain>()
+        .allow_component::<ScreenCamera>()
+        .allow_component::<Transform>()
+        .allow_component::<Projection>()
+        .allow_resource::<Bookmarks>()
+        .extract_entities(entities)
+        .extract_resources()
+        .build();
+    Ok(scene.serialize(&world.resource::<AppTypeRegistry>().read())?)
+}
+
+// Scene RON spawned into a new world.
+fn scene_world(text: &str, registry: &AppTypeRegistry) -> Result<World, SceneError> {
+    let mut deserializer = ron::de::Deserializer::from_str(text)?;
+    let scene = SceneDeserializer {
+        type_registry: &registry.read(),
+    }
+    .deserialize(&mut deserializer)?;
+    let mut world = World::new();
+    scene.write_to_world_with(&mut world, &mut EntityHashMap::default(), registry)?;
+    Ok(world)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Particle;
+
+    #[test]
+    fn entities_round_trip_through_scenes() {
+        let mut app = App::new();
+        // Bevy's own plugins register these in the game.
+        app.add_plugins(ScenesPlugin)
+            .register_type::<Transform>()
+            .register_type::<Projection>();
+        let world = app.world_mut();
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.slots[2] = Some(Bookmark {
+            name: "Pool".to_string(),
+            center: (40.0, 12.5),
+            zoom: 0.25,
+        });
+        world.insert_resource(bookmarks);
+        let emitter = ParticleEmitter {
+            particle: Particle::Water,
+            rate: 40.0,
+            radius: 2,
+        };
+        world.spawn((Outlet::default(), emitter));
+        world.spawn((Outlet::default(), ParticleDrain { rate: 80.0, radius: 3 }));
+        world.spawn((ScreenCamera, Transform::from_xyz(5.0, 6.0, 0.0), Projection::default()));
+        // Only what the scene is for goes into it.
+        world.spawn(Transform::from_xyz(1.0, 1.0, 1.0));
+
+        let text = scene_ron(world).unwrap();
+        let registry = world.resource::<AppTypeRegistry>().clone();
+        let mut scene = scene_world(&text, &registry).unwrap();
+        assert_eq!(scene.query::<&Outlet>().iter(&scene).count(), 2);
+        let emitter = scene.query::<&ParticleEmitter>().single(&scene).unwrap();
+        assert_eq!(emitter.particle, Particle::Water);
+        assert_eq!(scene.query::<&ParticleDrain>().single(&scene).unwrap().radius, 3);
+        let camera = scene.query_filtered::<&Transform, With<ScreenCamera>>().single(&scene);
+        assert_eq!(camera.unwrap().translation, Vec3::new(5.0, 6.0, 0.0));
+        assert_eq!(scene.query::<&Transform>().iter(&scene).count(), 1);
+        let bookmark = scene.resource::<Bookmarks>().slots[2].clone().unwrap();
+        assert_eq!(bookmark.name, "Pool");
+    }
+}
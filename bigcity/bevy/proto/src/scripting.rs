@@ -0,0 +1,358 @@
+// --- IMPORTS ---
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext, LoadedFolder};
+use bevy::prelude::*;
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Table, VmState};
+use thiserror::Error;
+
+use crate::behavior::{CellCtx, MaterialBehavior, MaterialBehaviors};
+use crate::coords::CellPos;
+use crate::mods::mod_folder;
+use crate::sim::{AMBIENT_TEMPERATURE, compound};
+use crate::{MaterialClass, Particle};
+
+// --- CONSTANTS ---
+// Scripts come from this folder of the mods, one material's rules to a file.
+pub const SCRIPTS_FOLDER: &str = "behaviors";
+const SCRIPT_EXTENSION: &str = "lua";
+// A script's update is stopped once it has run this many times HOOK_INSTRUCTIONS Lua instructions
+// for one cell, so a script stuck in a loop stops with an error instead of hanging the game.
+const HOOK_INSTRUCTIONS: u32 = 1000;
+const MAX_HOOKS: u32 = 100;
+
+// --- PLUGIN ---
+
+// Behavior scripts, in builds with the `scripting` feature: every `.lua` file in a mod's
+// `behaviors` folder returns the rules of one material, a table with the material's name and an
+// `update` function, and is registered as that material's `MaterialBehavior` when it loads, so
+// `update` runs on every cell of the material after the built-in chemistry, at the chemistry's
+// cadence, without recompiling the game. It looks at the cells around and changes them through a
+// handful of global functions (see the README). Each script gets a Lua of its own with only the
+// `math`, `string` and `table` libraries, so it can't reach files or the rest of the machine. One
+// that fails to load is reported and left out, and one that fails while it runs is reported once
+// and stopped. They are registered again whenever a file changes.
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<BehaviorScript>()
+            .init_asset_loader::<BehaviorScriptLoader>()
+            .add_systems(Startup, load_behavior_scripts)
+            .add_systems(Update, register_behavior_scripts);
+    }
+}
+
+// --- ASSETS ---
+
+// A loaded behavior script and the material it is for. Copies share one Lua.
+#[derive(Asset, TypePath, Clone)]
+pub struct BehaviorScript {
+    pub material: Particle,
+    lua: Lua,
+    update: Function,
+    // How many times the instruction hook has run in this update.
+    hooks: Arc<AtomicU32>,
+    // Set by the first error while running, after which the script is left alone.
+    failed: Arc<AtomicBool>,
+}
+
+impl BehaviorScript {
+    pub fn load(name: &str, source: &str) -> Result<Self, ScriptError> {
+        let libraries = StdLib::MATH | StdLib::STRING | StdLib::TABLE;
+        let lua = Lua::new_with(libraries, LuaOptions::default())?;
+        let hooks = Arc::new(AtomicU32::new(0));
+        let counted = hooks.clone();
+        let triggers = HookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS);
+        lua.set_hook(triggers, move |_, _| {
+            if counted.fetch_add(1, Ordering::Relaxed) >= MAX_HOOKS {
+                return Err(mlua::Error::runtime("ran for too long on one cell"));
+            }
+            Ok(VmState::Continue)
+        });
+
+        let script: Table = lua.load(source).set_name(name).eval()?;
+        let material: String = script.get("material")?;
+        let material = particle_named(&material).ok_or(ScriptError::Material(material))?;
+        Ok(Self {
+            material,
+            update: script.get("update")?,
+            lua,
+            hooks,
+            failed: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // Runs `update` for the cell, with the world functions pointing at it for as long as it runs.
+    fn run(&self, ctx: &mut CellCtx) -> mlua::Result<()> {
+        self.hooks.store(0, Ordering::Relaxed);
+        let cell = RefCell::new(ScriptCell { ctx, dice: 0 });
+        let cell = &cell;
+        self.lua.scope(|scope| {
+            let globals = self.lua.globals();
+            let material = |particle: Particle| format!("{:?}", particle);
+            let class = |name: String, class| {
+                let particle = material_arg(&name)?;
+                Ok(particle.class() == class && particle != Particle::Air)
+            };
+
+            let particle = move |_: &Lua, (dx, dy): (f32, f32)| {
+                let cell = cell.borrow();
+                let at = cell.at(dx, dy);
+                Ok(material(cell.ctx.grid().get(at.x, at.y).unwrap_or(Particle::Bedrock)))
+            };
+            globals.set("particle", scope.create_function(particle)?)?;
+            let temperature = move |_: &Lua, (dx, dy): (f32, f32)| {
+                let cell = cell.borrow();
+                let at = cell.at(dx, dy);
+                Ok(cell.ctx.grid().temperature(at.x, at.y).unwrap_or(AMBIENT_TEMPERATURE))
+            };
+            globals.set("temperature", scope.create_function(temperature)?)?;
+            let chance = move |_: &Lua, per_tick: f32| {
+                let mut cell = cell.borrow_mut();
+                let chance = compound(per_tick, cell.ctx.ticks());
+                Ok(cell.roll() < chance)
+            };
+            globals.set("chance", scope.create_function(chance)?)?;
+            let random = move |_: &Lua, ()| Ok(cell.borrow_mut().roll());
+            globals.set("random", scope.create_function(random)?)?;
+            let classes = [
+                ("solid", MaterialClass::Solid),
+                ("powder", MaterialClass::Powder),
+                ("liquid", MaterialClass::Liquid),
+                ("gas", MaterialClass::Gas),
+            ];
+            for (name, of) in classes {
+                let is = move |_: &Lua, name: String| class(name, of);
+                globals.set(name, scope.create_function(is)?)?;
+            }
+
+            let become_ = move |_: &Lua, name: String| {
+                cell.borrow_mut().ctx.turn_into(material_arg(&name)?);
+                Ok(())
+            };
+            globals.set("become", scope.create_function(become_)?)?;
+            let set = move |_: &Lua, (dx, dy, name): (f32, f32, String)| {
+                let mut cell = cell.borrow_mut();
+                let at = cell.at(dx, dy);
+                cell.ctx.set(at, material_arg(&name)?);
+                Ok(())
+            };
+            globals.set("set", scope.create_function(set)?)?;
+            let swap = move |_: &Lua, (dx, dy): (f32, f32)| {
+                let mut cell = cell.borrow_mut();
+                let at = cell.at(dx, dy);
+                cell.ctx.swap(at);
+                Ok(())
+            };
+            globals.set("swap", scope.create_function(swap)?)?;
+            let heat = move |_: &Lua, (dx, dy, degrees): (f32, f32, f32)| {
+                let mut cell = cell.borrow_mut();
+                let at = cell.at(dx, dy);
+                let degrees = degrees * cell.ctx.ticks() as f32;
+                cell.ctx.add_heat(at, degrees);
+                Ok(())
+            };
+            globals.set("heat", scope.create_function(heat)?)?;
+
+            self.update.call::<()>(())
+        })
+    }
+}
+
+impl MaterialBehavior for BehaviorScript {
+    fn update(&self, ctx: &mut CellCtx) {
+        if self.failed.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(err) = self.run(ctx) {
+            self.failed.store(true, Ordering::Relaxed);
+            warn!("The {:?} behavior script stopped: {}", self.material, err);
+        }
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("{0}")]
+    Lua(#[from] mlua::Error),
+    #[error("no material `{0}`")]
+    Material(String),
+}
+
+#[derive(Debug, Error)]
+pub enum BehaviorScriptLoaderError {
+    #[error("could not read script: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not load script: {0}")]
+    Script(#[from] ScriptError),
+}
+
+#[derive(Default)]
+struct BehaviorScriptLoader;
+
+impl AssetLoader for BehaviorScriptLoader {
+    type Asset = BehaviorScript;
+    type Settings = ();
+    type Error = BehaviorScriptLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<BehaviorScript, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let name = load_context.path().display().to_string();
+        Ok(BehaviorScript::load(&name, &String::from_utf8_lossy(&bytes))?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[SCRIPT_EXTENSION]
+    }
+}
+
+// The cell a script is updating, as its world functions see it. Offsets are in cells from it, up
+// and right positive, and the world beyond its edges reads as bedrock at the ambient temperature.
+struct ScriptCell<'c, 'g> {
+    ctx: &'c mut CellCtx<'g>,
+    dice: u32,
+}
+
+impl ScriptCell<'_, '_> {
+    fn at(&self, dx: f32, dy: f32) -> CellPos {
+        CellPos(self.ctx.cell().0 + IVec2::new(dx.round() as i32, dy.round() as i32))
+    }
+
+    // The next of the cell's dice.
+    fn roll(&mut self) -> f32 {
+        self.dice += 1;
+        self.ctx.dice(self.dice - 1)
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct ScriptFolder(Handle<LoadedFolder>);
+
+// --- SYSTEMS ---
+
+fn load_behavior_scripts(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(ScriptFolder(asset_server.load_folder(mod_folder(SCRIPTS_FOLDER))));
+}
+
+// Registers every script that loaded for its material, afresh whenever one loads or changes. The
+// materials scripted before get their built-in behavior back first, so taking a script away takes
+// its rules with it. Of two scripts for one material, the later file wins.
+fn register_behavior_scripts(
+    mut events: EventReader<AssetEvent<BehaviorScript>>,
+    mut folder_events: EventReader<AssetEvent<LoadedFolder>>,
+    folder: Res<ScriptFolder>,
+    loaded: Res<Assets<LoadedFolder>>,
+    scripts: Res<Assets<BehaviorScript>>,
+    mut behaviors: ResMut<MaterialBehaviors>,
+    mut scripted: Local<Vec<Particle>>,
+) {
+    let changed = events.read().count() + folder_events.read().count() > 0;
+    if !changed {
+        return;
+    }
+    for particle in scripted.drain(..) {
+        behaviors.restore(particle);
+    }
+    let Some(folder) = loaded.get(&folder.0) else { return };
+    let loaded_scripts = (folder.handles.iter())
+        .filter_map(|handle| handle.clone().try_typed::<BehaviorScript>().ok())
+        .filter_map(|handle| scripts.get(&handle));
+    for script in loaded_scripts {
+        behaviors.register(script.material, script.clone());
+        scripted.push(script.material);
+    }
+    if !scripted.is_empty() {
+        info!("Registered {} behavior scripts", scripted.len());
+    }
+}
+
+// --- HELPERS ---
+
+// The material called `name`, as the palette spells it.
+fn particle_named(name: &str) -> Option<Particle> {
+    Particle::ALL.into_iter().find(|particle| format!("{:?}", particle) == name)
+}
+
+// A material a script passed to a world function, which is an error in the script if there is no
+// such material.
+fn material_arg(name: &str) -> mlua::Result<Particle> {
+    particle_named(name).ok_or_else(|| mlua::Error::runtime(format!("no material `{}`", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::SimulationGrid;
+
+    fn run(source: &str, grid: &mut SimulationGrid) {
+        let script = BehaviorScript::load("test", source).unwrap();
+        let mut behaviors = MaterialBehaviors::default();
+        behaviors.register(script.material, script);
+        behaviors.run(grid, 1, 1);
+    }
+
+    #[test]
+    fn scripts_change_the_cells_around() {
+        // Sand that falls through air like a slow rain, and turns what it lands on to glass.
+        let source = r#"
+            return {
+                material = "Sand",
+                update = function()
+                    local below = particle(0, -1)
+                    if below == "Air" then
+                        swap(0, -1)
+                    elseif below ~= "Bedrock" and not solid(below) then
+                        set(0, -1, "Glass")
+                    end
+                end,
+            }
+        "#;
+        let mut grid = SimulationGrid::new(3, 4);
+        grid.set(0, 3, Particle::Sand);
+        grid.set(1, 1, Particle::Sand);
+        grid.set(1, 0, Particle::Water);
+        grid.set(2, 0, Particle::Sand);
+        run(source, &mut grid);
+        assert_eq!(grid.get(0, 2), Some(Particle::Sand));
+        assert_eq!(grid.get(0, 3), Some(Particle::Air));
+        assert_eq!(grid.get(1, 0), Some(Particle::Glass));
+        // Below the bottom row the world reads as bedrock.
+        assert_eq!(grid.get(2, 0), Some(Particle::Sand));
+    }
+
+    #[test]
+    fn broken_scripts_are_reported_or_stopped() {
+        let load = |source| BehaviorScript::load("test", source).err().unwrap().to_string();
+        assert_eq!(
+            load(r#"return { material = "Plasma", update = function() end }"#),
+            "no material `Plasma`"
+        );
+        assert!(load("return {").contains("test"));
+
+        // A script stuck in a loop, or calling the world wrong, stops without touching the world.
+        for update in ["while true do end", r#"become("Plasma")"#] {
+            let source = format!(
+                r#"return {{ material = "Sand", update = function() {} end }}"#,
+                update
+            );
+            let mut grid = SimulationGrid::new(1, 1);
+            grid.set(0, 0, Particle::Sand);
+            run(&source, &mut grid);
+            assert_eq!(grid.get(0, 0), Some(Particle::Sand));
+        }
+    }
+}
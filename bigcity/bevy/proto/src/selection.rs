@@ -0,0 +1,313 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::coords::CellPos;
+use crate::pan_zoom::ctrl_held;
+use crate::player::PlayerInputSet;
+use crate::sim::SimulationGrid;
+use crate::stamps::{PasteTarget, Stamp, StampLibrary, save_stamp, stamp_name};
+use crate::world_commands::WorldCommandSet;
+use crate::{MaterialClass, Particle, WorldView};
+
+// --- CONSTANTS ---
+const SELECTION_COLOR: Color = Color::srgb(1.0, 1.0, 1.0);
+const PASTE_COLOR: Color = Color::srgb(0.3, 0.9, 0.5);
+// How opaque the ghost of a paste is, and the color of its cells that would land on solids.
+const GHOST_ALPHA: u8 = 140;
+const CONFLICT_COLOR: [u8; 4] = [235, 40, 40, 210];
+// In front of the world quad.
+const GHOST_Z: f32 = 1.0;
+
+// --- PLUGIN ---
+
+// The selection tool: dragging with Ctrl and the left button selects a rectangle of the world,
+// Ctrl+C copies what is in it, trimmed to the box around everything that isn't air, and Ctrl+V
+// pastes the copy centered on the cursor, as often as wanted, like a stamp that hasn't been saved.
+// While Ctrl is held a ghost of the copy follows the cursor, and otherwise one of the stamp held
+// from the library, if any: see-through, with the cells that would land on solids in red. Ctrl+R
+// turns what the ghost shows a quarter turn clockwise and Ctrl+Shift+R flips it left to right, so
+// every way round can be reached before pasting it. Ctrl+S names the copy and adds it to the stamp
+// library, saved like a world. Esc drops the selection and the copy. Pastes are undone like
+// stamps, and are off where stamps are.
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_systems(Startup, spawn_paste_ghost)
+            .add_systems(
+                Update,
+                (select_region, copy_selection, use_clipboard, draw_selection, update_paste_ghost)
+                    .chain()
+                    .after(PlayerInputSet)
+                    .before(WorldCommandSet),
+            )
+            .add_systems(EguiContextPass, draw_save_prompt);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+struct Selection {
+    // Where the drag began, while it goes on.
+    anchor: Option<CellPos>,
+    // The selected rectangle's bottom left and top right cells.
+    corners: Option<(CellPos, CellPos)>,
+    clipboard: Option<Stamp>,
+    // The name being typed for the copy, while it is being saved as a stamp.
+    naming: Option<String>,
+}
+
+// --- COMPONENTS ---
+
+// The see-through picture of what a paste would put down, over the cells it would cover.
+#[derive(Component)]
+struct PasteGhost;
+
+// --- SYSTEMS ---
+
+fn select_region(
+    mouse: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    mut selection: ResMut<Selection>,
+) {
+    let held = selection.corners.is_some() || selection.clipboard.is_some();
+    if keys.just_pressed(KeyCode::Escape) && held {
+        *selection = Selection::default();
+        info!("Selection cleared");
+        return;
+    }
+    if !mouse.pressed(MouseButton::Left) {
+        selection.anchor = None;
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+    if mouse.just_pressed(MouseButton::Left) && ctrl_held(&keys) {
+        selection.anchor = Some(cell);
+    }
+    if let Some(anchor) = selection.anchor {
+        selection.corners = Some((CellPos(anchor.min(*cell)), CellPos(anchor.max(*cell))));
+    }
+}
+
+fn copy_selection(
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Res<SimulationGrid>,
+    library: Res<StampLibrary>,
+    mut selection: ResMut<Selection>,
+) {
+    if !(ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyC)) {
+        return;
+    }
+    let Some((min, max)) = selection.corners else {
+        info!("Nothing selected to copy (drag with Ctrl held to select)");
+        return;
+    };
+    let cells = (min.y..=max.y)
+        .flat_map(|y| (min.x..=max.x).map(move |x| (x, y)))
+        .filter_map(|(x, y)| grid.get(x, y));
+    let name = stamp_name(cells, library.stamps.len() + 1);
+    let Some(stamp) = Stamp::capture(&grid, *min, *max, name) else {
+        info!("Nothing but air to copy");
+        return;
+    };
+    info!(
+        "Copied {}x{} cells (Ctrl+V pastes, Ctrl+R turns, Ctrl+Shift+R flips, Ctrl+S saves it)",
+        stamp.width, stamp.height
+    );
+    selection.clipboard = Some(stamp);
+}
+
+fn use_clipboard(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    mut selection: ResMut<Selection>,
+    mut library: ResMut<StampLibrary>,
+    mut target: PasteTarget,
+) {
+    if !ctrl_held(&keys) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::KeyR) {
+        // What the ghost shows turns: the copy, or else the stamp held from the library.
+        let Some(stamp) = selection.clipboard.as_mut().or(library.held_mut()) else { return };
+        *stamp = if shift { stamp.mirrored() } else { stamp.rotated() };
+        return;
+    }
+    let Selection {
+        clipboard, naming, ..
+    } = &mut *selection;
+    let Some(clipboard) = clipboard else { return };
+
+    if keys.just_pressed(KeyCode::KeyV) && !shift {
+        let Some(cell) = view.cursor_cell() else { return };
+        if !target.allowed() {
+            info!("Pasting is off in levels and challenge mode");
+            return;
+        }
+        target.paste(clipboard, cell);
+    } else if keys.just_pressed(KeyCode::KeyS) && !shift {
+        *naming = Some(clipboard.name.clone());
+    }
+}
+
+// Outlines the selection, and where a paste would land.
+fn draw_selection(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    selection: Res<Selection>,
+    library: Res<StampLibrary>,
+    mut gizmos: Gizmos,
+) {
+    let mut outline = |min: CellPos, max: CellPos, color: Color| {
+        let rect = view.cells_to_world(min, max);
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), color);
+    };
+    if let Some((min, max)) = selection.corners {
+        outline(min, max, SELECTION_COLOR);
+    }
+    let Some(stamp) = attached(&keys, &selection, &library) else { return };
+    let Some(cell) = view.cursor_cell() else { return };
+    let (min, max) = covered(stamp, cell);
+    outline(min, max, PASTE_COLOR);
+}
+
+fn spawn_paste_ghost(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let mut image = Image::new_fill(
+        Extent3d::default(),
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    commands.spawn((
+        PasteGhost,
+        Name::new("paste_ghost"),
+        Sprite::from_image(images.add(image)),
+        Transform::from_xyz(0.0, 0.0, GHOST_Z),
+        Visibility::Hidden,
+    ));
+}
+
+// Draws the ghost of what is attached to the cursor over the cells it would cover, each in its
+// material's color, or red where it would land on a solid. The picture is made again every frame,
+// as the world under it moves, but only goes to the GPU when it changed.
+fn update_paste_ghost(
+    keys: Res<ButtonInput<KeyCode>>,
+    view: WorldView,
+    selection: Res<Selection>,
+    library: Res<StampLibrary>,
+    grid: Res<SimulationGrid>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_ghost: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<PasteGhost>>,
+) {
+    let Ok((mut sprite, mut transform, mut visibility)) = q_ghost.single_mut() else { return };
+    let attached = attached(&keys, &selection, &library);
+    let Some((stamp, cell)) = attached.zip(view.cursor_cell()) else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    let (min, max) = covered(stamp, cell);
+    let rect = view.cells_to_world(min, max);
+    transform.translation = rect.center().extend(GHOST_Z);
+    sprite.custom_size = Some(rect.size());
+    visibility.set_if_neq(Visibility::Inherited);
+
+    let (width, height) = (stamp.width as usize, stamp.height as usize);
+    let mut pixels = vec![0; width * height * 4];
+    for (offset, particle, _) in stamp.cells().filter(|(_, p, _)| *p != Particle::Air) {
+        let under = grid.get(min.x + offset.x, min.y + offset.y);
+        let color = match under {
+            Some(under) if under != Particle::Air && under.class() == MaterialClass::Solid => {
+                CONFLICT_COLOR
+            }
+            _ => {
+                let [r, g, b, _] = particle.color().to_srgba().to_u8_array();
+                [r, g, b, GHOST_ALPHA]
+            }
+        };
+        // Picture rows run top-down, stamp rows bottom-up.
+        let i = ((height - 1 - offset.y as usize) * width + offset.x as usize) * 4;
+        pixels[i..i + 4].copy_from_slice(&color);
+    }
+    let size = Extent3d {
+        width: stamp.width,
+        height: stamp.height,
+        ..default()
+    };
+    let current = images.get(&sprite.image);
+    if current.is_some_and(|image| image.texture_descriptor.size == size)
+        && current.and_then(|image| image.data.as_ref()).is_some_and(|data| *data == pixels)
+    {
+        return;
+    }
+    if let Some(image) = images.get_mut(&sprite.image) {
+        image.resize(size);
+        image.data = Some(pixels);
+    }
+}
+
+fn draw_save_prompt(
+    mut contexts: EguiContexts,
+    mut selection: ResMut<Selection>,
+    mut library: ResMut<StampLibrary>,
+) {
+    let Selection {
+        naming, clipboard, ..
+    } = &mut *selection;
+    let (Some(name), Some(clipboard)) = (naming.as_mut(), clipboard.as_ref()) else { return };
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let (mut save, mut cancel) = (false, false);
+    egui::Window::new("Save as a stamp").collapsible(false).resizable(false).show(ctx, |ui| {
+        ui.label(format!("{}x{} cells", clipboard.width, clipboard.height));
+        let field = ui.text_edit_singleline(name);
+        let entered = field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+        ui.horizontal(|ui| {
+            let named = !name.trim().is_empty();
+            save = ui.add_enabled(named, egui::Button::new("Save")).clicked() || (entered && named);
+            cancel = ui.button("Cancel").clicked();
+        });
+    });
+
+    if save {
+        let stamp = Stamp {
+            name: name.trim().to_string(),
+            ..clipboard.clone()
+        };
+        save_stamp(&stamp);
+        info!("Saved stamp \"{}\" ({}x{})", stamp.name, stamp.width, stamp.height);
+        library.add(stamp);
+    }
+    if save || cancel {
+        *naming = None;
+    }
+}
+
+// --- HELPERS ---
+
+// What a paste would put down right now: the copy while Ctrl is held, or else the stamp held from
+// the library.
+fn attached<'a>(
+    keys: &ButtonInput<KeyCode>,
+    selection: &'a Selection,
+    library: &'a StampLibrary,
+) -> Option<&'a Stamp> {
+    let copy = selection.clipboard.as_ref().filter(|_| ctrl_held(keys));
+    copy.or(library.held())
+}
+
+// The bottom left and top right cells `stamp` covers when pasted centered on `cell`.
+fn covered(stamp: &Stamp, cell: CellPos) -> (CellPos, CellPos) {
+    let size = IVec2::new(stamp.width as i32, stamp.height as i32);
+    let origin = cell - size / 2;
+    (origin, origin + (size - IVec2::ONE))
+}
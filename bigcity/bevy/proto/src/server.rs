@@ -0,0 +1,371 @@
+// --- IMPORTS ---
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, channel};
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::asset::LoadedFolder;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+
+use crate::behavior::MaterialBehaviors;
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::headless::{load_world, save_world};
+use crate::inventory::Inventory;
+use crate::levels::PaintRules;
+use crate::locks::PaintLocks;
+use crate::mods::ModsPlugin;
+use crate::quality::Quality;
+use crate::reaction_rules::{ReactionRules, ReactionRulesPlugin, ReactionTable, RuleFolders};
+use crate::sim::{SimParams, SimulationGrid, SimulationStats, TickSchedule};
+use crate::snapshot::WorldSnapshot;
+use crate::spectator::SpectatorHost;
+use crate::undo::PaintHistory;
+use crate::world_commands::{AppliedCommand, WorldCommand, WorldCommands, apply_world_commands};
+use crate::{SIMULATION_HEIGHT, SIMULATION_WIDTH};
+
+// --- CONSTANTS ---
+const DEFAULT_PORT: &str = "7777";
+const DEFAULT_WORLD: &str = "server-world.png";
+// The world steps this many times a second, as it would in the game at 60 fps.
+const TICKS_PER_SECOND: f64 = 60.0;
+const DEFAULT_AUTOSAVE_SECS: f32 = 300.0;
+// How many saves back `rollback` can go, counting the world as it was loaded.
+const ROLLBACK_DEPTH: usize = 8;
+const HELP: &str = "\
+players          who is connected, by id
+kick <id>        hangs up on a player
+save             writes the world out now
+rollback [n]     puts the world back as it was n saves ago (1, the last save, by default)
+undo, redo       takes back the latest stroke anyone painted, or puts it back
+lock x y x y     keeps strokes off the rectangle with those corners, in cells
+quit             saves and stops the server";
+
+// --- RUNNER ---
+
+// `jules-server [--port N] [--world <file>] [--autosave SECS] [--seed N]` keeps one world running
+// for players who join it from the game with `--join <host>:<port>`. It is the headless runner's
+// app (`MinimalPlugins` and the asset server, for the reaction rules) run forever at 60 ticks a
+// second, with the spectator host taking strokes: every player is streamed the world as a
+// spectator is, and what they paint goes into it here, as the game's own brush strokes do: one
+// `WorldCommand::Paint` each, so the paint locks and rules hold and every stroke can be undone. The world is loaded from `--world` (a world
+// file's `.png` with its `.ron` sidecar, or a snapshot `.ron`) and written back there every
+// `--autosave` seconds and on `quit`; a missing file starts an empty world. Lines typed on
+// standard input are the admin console; see HELP.
+pub fn run() -> i32 {
+    let args = ServerArgs::parse();
+    let mut grid = if args.world.exists() {
+        match load_world(&args.world) {
+            Ok(grid) => grid,
+            Err(err) => {
+                eprintln!("Can't load {:?}: {}", args.world, err);
+                return 1;
+            }
+        }
+    } else {
+        println!("No world at {:?} yet; starting an empty one", args.world);
+        SimulationGrid::new(SIMULATION_WIDTH, SIMULATION_HEIGHT)
+    };
+    if let Some(seed) = args.seed {
+        grid.set_seed(seed);
+    }
+    let host = match SpectatorHost::bind(&args.port) {
+        Ok(host) => host.taking_strokes(),
+        Err(err) => {
+            eprintln!("Can't listen on port {}: {}", args.port, err);
+            return 1;
+        }
+    };
+    println!(
+        "Serving {:?} ({}x{}) on port {}; type `help` for commands",
+        args.world,
+        grid.width(),
+        grid.height(),
+        host.port()
+    );
+
+    let tick = Duration::from_secs_f64(1.0 / TICKS_PER_SECOND);
+    let mut app = App::new();
+    // Mods are an asset source, and sources have to exist before the asset server does.
+    app.add_plugins(ModsPlugin)
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(tick)),
+            LogPlugin::default(),
+            AssetPlugin::default(),
+            ReactionRulesPlugin,
+        ))
+        .init_resource::<ReactionTable>()
+        .init_resource::<WorldCommands>()
+        .init_resource::<PaintRules>()
+        .init_resource::<PaintLocks>()
+        .init_resource::<Inventory>()
+        .init_resource::<PaintHistory>()
+        .init_resource::<SimulationStats>()
+        .add_event::<AppliedCommand>()
+        .add_event::<SimEvent>()
+        .insert_resource(Server::new(args, &grid))
+        .insert_resource(grid)
+        .insert_resource(host)
+        .insert_resource(Console::spawn())
+        .add_systems(
+            Update,
+            (
+                load_rules,
+                take_strokes,
+                run_console,
+                apply_world_commands,
+                step_world,
+                send_world,
+                autosave,
+            )
+                .chain(),
+        );
+    match app.run() {
+        AppExit::Success => 0,
+        AppExit::Error(code) => code.get() as i32,
+    }
+}
+
+// --- TYPES ---
+
+struct ServerArgs {
+    port: String,
+    world: PathBuf,
+    autosave: f32,
+    seed: Option<u64>,
+}
+
+impl ServerArgs {
+    fn parse() -> Self {
+        let mut parsed = Self {
+            port: DEFAULT_PORT.to_string(),
+            world: PathBuf::from(DEFAULT_WORLD),
+            autosave: DEFAULT_AUTOSAVE_SECS,
+            seed: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--port" => match args.next() {
+                    Some(port) => parsed.port = port,
+                    None => eprintln!("--port takes the port to listen on"),
+                },
+                "--world" => match args.next() {
+                    Some(path) => parsed.world = PathBuf::from(path),
+                    None => eprintln!("--world takes the world file to serve"),
+                },
+                "--autosave" => match args.next().and_then(|secs| secs.parse().ok()) {
+                    Some(secs) => parsed.autosave = secs,
+                    None => eprintln!("--autosave takes a number of seconds, 0 for never"),
+                },
+                "--seed" => parsed.seed = args.next().and_then(|n| n.parse().ok()),
+                other => eprintln!("Ignoring {:?}", other),
+            }
+        }
+        parsed
+    }
+}
+
+// --- RESOURCES ---
+
+// Where the world is kept, how it steps and the last few saves, oldest first, to roll back to.
+// Nothing steps until the reaction rules have loaded.
+#[derive(Resource)]
+struct Server {
+    world: PathBuf,
+    autosave: f32,
+    since_save: f32,
+    tick: u64,
+    reactions: Option<ReactionTable>,
+    params: SimParams,
+    schedule: TickSchedule,
+    behaviors: MaterialBehaviors,
+    saves: VecDeque<WorldSnapshot>,
+}
+
+impl Server {
+    fn new(args: ServerArgs, grid: &SimulationGrid) -> Self {
+        Self {
+            world: args.world,
+            autosave: args.autosave,
+            since_save: 0.0,
+            tick: 0,
+            reactions: None,
+            params: SimParams::default(),
+            schedule: Quality::Ultra.schedule(),
+            behaviors: MaterialBehaviors::default(),
+            saves: VecDeque::from([WorldSnapshot::from_grid(grid)]),
+        }
+    }
+
+    // Writes the world out and keeps it to roll back to.
+    fn save(&mut self, grid: &SimulationGrid) {
+        self.since_save = 0.0;
+        if self.saves.len() == ROLLBACK_DEPTH {
+            self.saves.pop_front();
+        }
+        self.saves.push_back(WorldSnapshot::from_grid(grid));
+        match save_world(grid, &self.world) {
+            Ok(()) => info!("Saved {:?}", self.world),
+            Err(err) => error!("Can't save {:?}: {}", self.world, err),
+        }
+    }
+}
+
+// Lines typed on standard input, read on a thread of their own. A closed input (a server run as
+// a service) just leaves the console quiet.
+#[derive(Resource)]
+struct Console(Mutex<Receiver<String>>);
+
+impl Console {
+    fn spawn() -> Self {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lines() {
+                let Ok(line) = line else { return };
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Self(Mutex::new(lines))
+    }
+}
+
+// --- SYSTEMS ---
+
+fn load_rules(
+    asset_server: Res<AssetServer>,
+    folders: Option<Res<RuleFolders>>,
+    loaded: Res<Assets<LoadedFolder>>,
+    assets: Res<Assets<ReactionRules>>,
+    mut server: ResMut<Server>,
+) {
+    if server.reactions.is_some() {
+        return;
+    }
+    let Some(folders) = folders.filter(|folders| folders.settled(&asset_server)) else { return };
+    let reactions = folders.table(&loaded, &assets);
+    info!("Loaded {} reaction rules", reactions.rule_count());
+    server.reactions = Some(reactions);
+}
+
+// Lets new players in and queues what everyone sent, in the order it arrived, each stroke as one
+// brush pass of its own player's that ends with it.
+fn take_strokes(
+    mut host: ResMut<SpectatorHost>,
+    grid: Res<SimulationGrid>,
+    mut commands: ResMut<WorldCommands>,
+) {
+    for (id, address) in host.accept(&grid, false) {
+        info!("Player {} joined from {}", id, address);
+    }
+    for (id, stroke) in host.strokes() {
+        let player = id as usize;
+        commands.push(WorldCommand::Paint {
+            player,
+            particle: stroke.particle,
+            data: stroke.data,
+            cells: stroke.cells.iter().map(|&(x, y)| CellPos::new(x.into(), y.into())).collect(),
+            max_cells: u32::MAX,
+        });
+        commands.push(WorldCommand::EndStroke { player });
+    }
+}
+
+fn step_world(
+    mut server: ResMut<Server>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    let server = &mut *server;
+    let Some(reactions) = &server.reactions else { return };
+    crate::sim::step(
+        &mut grid,
+        server.tick,
+        &server.params,
+        &server.schedule,
+        reactions,
+        &server.behaviors,
+    );
+    server.tick += 1;
+    stats.tick = server.tick;
+}
+
+fn send_world(time: Res<Time>, grid: Res<SimulationGrid>, mut host: ResMut<SpectatorHost>) {
+    host.send_changes(time.delta_secs(), &grid, false);
+}
+
+fn run_console(
+    mut console: ResMut<Console>,
+    mut host: ResMut<SpectatorHost>,
+    mut server: ResMut<Server>,
+    mut grid: ResMut<SimulationGrid>,
+    mut commands: ResMut<WorldCommands>,
+    mut locks: ResMut<PaintLocks>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let lines: Vec<String> =
+        console.0.get_mut().map_or(Vec::new(), |lines| lines.try_iter().collect());
+    for line in lines {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => {}
+            (Some("help"), _) => println!("{}", HELP),
+            (Some("players"), _) => {
+                let players: Vec<_> = host.clients().collect();
+                if players.is_empty() {
+                    println!("Nobody is connected");
+                }
+                for (id, address) in players {
+                    println!("{} {}", id, address);
+                }
+            }
+            (Some("kick"), Some(id)) => match id.parse() {
+                Ok(id) if host.kick(id) => println!("Kicked player {}", id),
+                _ => println!("No player {}; `players` lists who is connected", id),
+            },
+            (Some("save"), _) => server.save(&grid),
+            (Some("rollback"), back) => {
+                let back = back.map_or(Some(1), |n| n.parse().ok()).unwrap_or(0);
+                let saves = server.saves.len();
+                if back == 0 || back > saves {
+                    println!("rollback goes back 1 to {} saves", saves);
+                    continue;
+                }
+                server.saves[saves - back].apply_to(&mut grid);
+                println!("Rolled the world back {} save(s)", back);
+            }
+            (Some("undo"), _) => commands.push(WorldCommand::Undo),
+            (Some("redo"), _) => commands.push(WorldCommand::Redo),
+            (Some("lock"), first) => {
+                let corners: Result<Vec<i32>, _> =
+                    first.into_iter().chain(words).map(str::parse).collect();
+                let Ok(&[x0, y0, x1, y1]) = corners.as_deref() else {
+                    println!("lock takes two corners, `lock x y x y`");
+                    continue;
+                };
+                locks.lock_region(CellPos::new(x0, y0), CellPos::new(x1, y1));
+                println!("Locked ({}, {}) to ({}, {})", x0, y0, x1, y1);
+            }
+            (Some("quit" | "stop"), _) => {
+                server.save(&grid);
+                exit.write(AppExit::Success);
+            }
+            (Some(other), _) => println!("Unknown command {:?}; try `help`", other),
+        }
+    }
+}
+
+fn autosave(time: Res<Time>, mut server: ResMut<Server>, grid: Res<SimulationGrid>) {
+    if server.autosave <= 0.0 {
+        return;
+    }
+    server.since_save += time.delta_secs();
+    if server.since_save >= server.autosave {
+        server.save(&grid);
+    }
+}
@@ -0,0 +1,142 @@
+// --- IMPORTS ---
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::sim::{SimulationGrid, SimulationSet, roll};
+use crate::{MaterialClass, Particle, SimulationDisplay};
+
+// --- CONSTANTS ---
+// Every material's texture is a square this many cells wide, tiled across the whole world.
+const TEXTURE_SIZE: usize = 16;
+// How far a particle's shade can lighten or darken it, by class.
+const POWDER_VARIATION: f32 = 0.14;
+const SOLID_VARIATION: f32 = 0.05;
+const LIQUID_VARIATION: f32 = 0.04;
+// A shade texel of this value leaves the cell's color as it is; 0 draws it black, 255 about
+// twice as bright.
+pub const NEUTRAL_SHADE: u8 = 128;
+
+// --- PLUGIN ---
+
+// Cell shading (Shift+Y): every particle is given a shade of its own when it appears and keeps it
+// as it moves, and is drawn that much lighter or darker, so a pile of sand is a heap of grains
+// rather than a flat yellow blob. Powders vary the most, solids and liquids a little and gases not
+// at all. Some materials also have a small texture that tiles the world and stays put as they come
+// and go, like the strata of bedrock or the fibers of rope. Both are worked into one brightness
+// texel per cell, which the shader and the CPU backend multiply the cell's color by.
+pub struct CellShadingPlugin;
+
+impl Plugin for CellShadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CellShading {
+            enabled: true,
+            looks: Particle::ALL.map(Look::of),
+        })
+        .add_systems(Update, (toggle_shading, update_shades.after(SimulationSet)).chain());
+    }
+}
+
+// --- TYPES ---
+
+// How a material is shaded: how far its particles' shades reach, and the brightness of each texel
+// of its texture, rows bottom first.
+struct Look {
+    variation: f32,
+    texture: [f32; TEXTURE_SIZE * TEXTURE_SIZE],
+}
+
+impl Look {
+    fn of(particle: Particle) -> Self {
+        let variation = match particle.class() {
+            MaterialClass::Powder => POWDER_VARIATION,
+            MaterialClass::Solid => SOLID_VARIATION,
+            MaterialClass::Liquid => LIQUID_VARIATION,
+            MaterialClass::Gas => 0.0,
+        };
+        let mut texture = [1.0; TEXTURE_SIZE * TEXTURE_SIZE];
+        for (i, brightness) in texture.iter_mut().enumerate() {
+            let (x, y) = ((i % TEXTURE_SIZE) as i32, (i / TEXTURE_SIZE) as i32);
+            // Where in the texture the texel is, 0..1 in either direction; everything below is
+            // periodic in both, so the texture tiles without seams.
+            let (u, v) = (x as f32 / TEXTURE_SIZE as f32, y as f32 / TEXTURE_SIZE as f32);
+            *brightness = match particle {
+                // Wavy layers of rock.
+                Particle::Bedrock => {
+                    1.0 + 0.07 * (TAU * (2.0 * v + 0.2 * (TAU * u).sin())).sin()
+                }
+                // A glint running diagonally across the pane.
+                Particle::Glass | Particle::Ice => {
+                    1.0 + 0.15 * (TAU * 2.0 * (u + v)).sin().max(0.0).powi(8)
+                }
+                // Twisted strands.
+                Particle::Rope => 1.0 + 0.08 * (TAU * 4.0 * (u - v)).sin(),
+                // Flat faces of 4x4 cells, each caught by the light differently.
+                Particle::Crystal => 0.9 + 0.2 * roll(x / 4, y / 4, particle as u64),
+                // Grains of ore in the rock.
+                Particle::Uranium => 0.95 + 0.1 * roll(x, y, particle as u64),
+                _ => 1.0,
+            };
+        }
+        Self { variation, texture }
+    }
+
+    // The brightness of a particle of this material with `shade` at (x, y).
+    fn brightness(&self, x: usize, y: usize, shade: u8) -> f32 {
+        let texel = self.texture[y % TEXTURE_SIZE * TEXTURE_SIZE + x % TEXTURE_SIZE];
+        texel * (1.0 + self.variation * (shade as f32 / 255.0 * 2.0 - 1.0))
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct CellShading {
+    enabled: bool,
+    looks: [Look; Particle::ALL.len()],
+}
+
+// --- SYSTEMS ---
+
+fn toggle_shading(keys: Res<ButtonInput<KeyCode>>, mut shading: ResMut<CellShading>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !(shift && keys.just_pressed(KeyCode::KeyY)) {
+        return;
+    }
+    shading.enabled = !shading.enabled;
+    info!("Cell shading {}", if shading.enabled { "on" } else { "off" });
+}
+
+// Works out every cell's brightness again whenever the world changed, or every cell's is neutral
+// while shading is off.
+fn update_shades(
+    grid: Res<SimulationGrid>,
+    shading: Res<CellShading>,
+    display: Res<SimulationDisplay>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let due = match shading.enabled {
+        true => grid.is_changed() || shading.is_changed(),
+        false => shading.is_changed(),
+    };
+    if !due {
+        return;
+    }
+    let Some(data) = images.get_mut(&display.shade_image).and_then(|image| image.data.as_mut())
+    else {
+        return;
+    };
+    if !shading.enabled {
+        data.fill(NEUTRAL_SHADE);
+        return;
+    }
+    let (width, height) = (grid.width() as usize, grid.height() as usize);
+    let cells = grid.cells().iter().zip(grid.shades());
+    for (i, (particle, shade)) in cells.enumerate() {
+        let (x, y) = (i % width, i / width);
+        let brightness = shading.looks[*particle as usize].brightness(x, y, *shade);
+        // Texture rows run top-down, grid rows bottom-up.
+        data[(height - 1 - y) * width + x] =
+            (brightness * NEUTRAL_SHADE as f32).round().clamp(0.0, 255.0) as u8;
+    }
+}
@@ -0,0 +1,2695 @@
+// --- IMPORTS ---
+use std::borrow::Cow;
+use std::time::Duration;
+
+use bevy::ecs::system::SystemParam;
+use bevy::math::I8Vec2;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use web_time::Instant;
+
+use crate::behavior::MaterialBehaviors;
+use crate::bodies::CellBodies;
+use crate::chunks::ChunkActivity;
+use crate::coords::{CellPos, ChunkPos};
+use crate::degradation::Degradation;
+use crate::events::SimEvent;
+use crate::gpu_sim::GpuStepping;
+use crate::hourglass::{Hourglass, recycle_bottom_row};
+use crate::{MaterialClass, Particle};
+use crate::loops::{LoopBand, wrap_loop_bands};
+use crate::packed::PackedCells;
+use crate::reaction_rules::ReactionTable;
+use crate::tags::CellTags;
+use crate::zones::{LocalParams, MaterialOverride, ParamZone};
+
+// --- CONSTANTS ---
+// Temperatures are in degrees Celsius. New particles start at this temperature.
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+const WATER_BOILS_AT: f32 = 100.0;
+const SAND_MELTS_AT: f32 = 400.0;
+// Snow and ice are a game's version of the real thing: both keep at room temperature and only melt
+// near a source of heat.
+const SNOW_MELTS_AT: f32 = 40.0;
+const ICE_MELTS_AT: f32 = 60.0;
+// A cell whose temperature moves by more than this in a tick keeps its chunk awake, so heat wakes
+// the chunks it spreads into and they settle once it has evened out.
+const STIRRING_HEAT: f32 = 0.01;
+// Lava that has cooled this far sets into obsidian, which is glass. Lava is thick: it only tries
+// to flow on this share of ticks.
+const LAVA_SETS_AT: f32 = 700.0;
+const LAVA_FLOW_CHANCE: f32 = 0.3;
+// Chance per tick that steam cooled below this temperature condenses back into water.
+const STEAM_CONDENSES_AT: f32 = 60.0;
+const STEAM_CONDENSE_CHANCE: f32 = 0.02;
+// Snow with at least this many cells resting on it slowly compacts into ice.
+const SNOW_COMPACTS_UNDER: u32 = 12;
+const SNOW_COMPACT_CHANCE: f32 = 0.01;
+// Snow is sticky: it only tries to slide off a pile this often, so its piles stand steeper.
+const SNOW_SLIDE_CHANCE: f32 = 0.25;
+// A surface touching water holds grains this much less firmly than when dry.
+const WET_FRICTION: f32 = 0.4;
+// Settled dust falls at this fraction of gravity. Each tick, airborne dust has this chance to lose
+// a cell per tick of speed on each axis; once it has none left it settles again.
+const DUST_FALL: f32 = 0.3;
+const DUST_DRAG: f32 = 0.15;
+// Chance per tick, per cell per tick of wind, that settled dust under open air is lifted.
+const DUST_LIFT_CHANCE: f32 = 0.05;
+// Water and oil squirted sideways out of a body gain a cell per tick of speed for every
+// SQUIRT_HEAD cells of head pushing them out, up to MAX_SQUIRT. A body moves at most
+// MAX_LEVEL_MOVES cells from its top to its lowest openings a tick.
+const SQUIRT_HEAD: i32 = 4;
+const MAX_SQUIRT: i32 = 7;
+const MAX_LEVEL_MOVES: usize = 4;
+// Chance that falling water churns the water it lands on into foam.
+const FOAM_CHANCE: f32 = 0.03;
+// How strongly goo cells cling together: a move that leaves a goo cell touching one goo cell fewer
+// than before is taken with 1 - GOO_COHESION chance, two fewer with that chance squared, and so
+// on. Goo moves at most a cell per tick.
+const GOO_COHESION: f32 = 0.9;
+// A liquid with a cohesion (see `SimParams`) beads up where fewer than DROPLET_SIZE cells of it are
+// within two cells of each other; bigger bodies flow and level out as usual.
+const DROPLET_SIZE: u32 = 12;
+// Rope cells link to up to MAX_ROPE_LINKS neighbouring rope cells, a bit of the state byte per
+// direction in ROPE_LINKS, orthogonal ones first.
+const MAX_ROPE_LINKS: u32 = 2;
+const ROPE_LINKS: [(i32, i32); 8] =
+    [(0, 1), (1, 0), (0, -1), (-1, 0), (1, 1), (1, -1), (-1, -1), (-1, 1)];
+// Flammable cells this hot catch fire as if they touched it. Fire and smoke rise on these shares
+// of ticks and otherwise wander up to this many cells sideways, unless fire has something to burn
+// next to it; fire puffs smoke into the air above it with this chance per tick.
+const IGNITES_AT: f32 = 150.0;
+// An explosive charge's crater is at most this many cells across the radius. Out to BLAST_REACH
+// times the radius, loose particles are flung out at up to BLAST_SPEED cells per tick and
+// everything is heated by up to BLAST_HEAT degrees, which sets off any explosive close enough;
+// inside it, what is blown apart turns into fire and smoke on these shares of cells.
+const MAX_BLAST_RADIUS: f32 = 40.0;
+const BLAST_REACH: f32 = 1.6;
+const BLAST_SPEED: f32 = 7.0;
+const BLAST_HEAT: f32 = 400.0;
+const BLAST_FIRE: f32 = 0.3;
+const BLAST_SMOKE: f32 = 0.3;
+// A battery charges what it touches to BATTERY_CHARGE, and current loses CHARGE_DROP over every
+// cell of the best conductors it passes, more over worse ones, until none is left. Where it flows
+// it heats the conductor, the worse one the more, by up to CURRENT_HEAT degrees a tick, and it
+// sparks into flammable and explosive cells touching a conductor charged at least SPARK_CHARGE,
+// heating them until they ignite. Charged water splits into hydrogen where it touches an electrode
+// (any other charged conductor), with ELECTROLYSIS_CHANCE a tick at full charge.
+const BATTERY_CHARGE: u8 = 255;
+const CHARGE_DROP: f32 = 2.0;
+const CURRENT_HEAT: f32 = 2.0;
+const SPARK_CHARGE: u8 = 64;
+const ELECTROLYSIS_CHANCE: f32 = 0.02;
+const FIRE_BUOYANCY: f32 = 0.7;
+const FIRE_DISPERSION: u32 = 1;
+const SMOKE_BUOYANCY: f32 = 0.5;
+const SMOKE_DISPERSION: u32 = 3;
+const FIRE_SMOKE_CHANCE: f32 = 0.05;
+// Water holds up to 255 units of dissolved salt; from this much on it is saturated, so salt stops
+// dissolving into it and crystals grow into it. Every unit of salt dissolved into water makes
+// several cells' worth of salt water, which a crystal cell uses up again as it grows.
+const SALT_SATURATION: u8 = 160;
+const SALT_PER_GRAIN: u8 = 255;
+const SALT_PER_CRYSTAL: u8 = 48;
+// Per-tick chance that a grain touching unsaturated water dissolves, and fraction of the gap to
+// its water neighbours' mean salinity that a water cell closes.
+const SALT_DISSOLVE_CHANCE: f32 = 0.05;
+const SALT_DIFFUSION: f32 = 0.25;
+// Per-tick chance that saturated water next to a crystal crystallizes. Only cells touching one or
+// two crystal cells grow, which keeps the tips growing and the crystals branching.
+const CRYSTAL_GROWTH_CHANCE: f32 = 0.02;
+// Every magnet cell pulls iron powder within this many cells towards itself, with a pull of
+// MAGNET_STRENGTH / distance. Pulls add up; iron under a pull of MAGNET_HOLD or more always moves
+// towards the magnets and no longer falls, weaker pulls only move it that often.
+const MAGNET_REACH: i32 = 10;
+const MAGNET_STRENGTH: f32 = 1.5;
+const MAGNET_HOLD: f32 = 1.0;
+// Cells at least this hot leave soot on themselves and their solid and powder neighbours, this
+// much per tick out of 255. Water next to dust gets this much dirtier per tick, and closes this
+// fraction of the gap to the stain of each solid or powder it touches per tick, washing it clean
+// or tinting it.
+const SOOT_TEMPERATURE: f32 = 200.0;
+const SOOT_PER_TICK: u8 = 2;
+const DIRT_PER_TICK: u8 = 1;
+const STAIN_EXCHANGE: f32 = 0.1;
+// A turbine's signal gains this much for every liquid cell that flows through it in a tick, and
+// loses 1 / TURBINE_DECAY of itself every tick, so under steady flow it settles at
+// TURBINE_GAIN * TURBINE_DECAY per cell per tick.
+const TURBINE_GAIN: u8 = 4;
+const TURBINE_DECAY: u8 = 8;
+
+// --- PLUGIN ---
+
+// The world steps on the CPU in `FixedUpdate`, at `SimulationTickRate` ticks a second whatever the
+// frame rate (up to the quality schedule's cap of ticks per frame), independently of drawing: the
+// render side only ever gets a copy of the finished grid in the state texture (see
+// `upload_grid`). Nearly every tool, mode and save reads or edits the grid between ticks, and the
+// rules beyond plain falling (heat, chemistry, magnets, loops, zones) are written against it, so
+// they stay here. Set to step on the GPU, the world moves in a compute pass instead, with the
+// movement rules only, and the grid mirrors it (see gpu_sim.rs).
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        if std::env::args().any(|arg| arg == "--low-memory") {
+            app.insert_resource(LowMemory);
+        }
+        app.init_resource::<SimulationStats>()
+            .init_resource::<SimParams>()
+            .init_resource::<SimulationTickRate>()
+            .init_resource::<TickSchedule>()
+            .init_resource::<SimulationControl>()
+            .init_resource::<SimulationBackend>()
+            .init_resource::<MaterialBehaviors>()
+            .init_resource::<ReactionTable>()
+            .add_systems(PreUpdate, pace_ticks)
+            .add_systems(
+                FixedUpdate,
+                step_simulation
+                    .run_if(not(resource_exists::<ViewOnly>))
+                    .run_if(not(resource_exists::<GpuStepping>))
+                    .in_set(SimulationSet),
+            )
+            .add_systems(
+                Update,
+                (
+                    update_stats,
+                    report_reactions,
+                    pack_walls.run_if(resource_exists::<LowMemory>),
+                )
+                    .in_set(SimulationSet),
+            );
+    }
+}
+
+// The world advances in this set in `FixedUpdate`, before the frame's `Update`, and its statistics
+// are refreshed in it in `Update`. Anything that edits the grid (painting, scripts) runs before
+// it, and so is in the world for the next frame's ticks; readers of the stats run after it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SimulationSet;
+
+// --- RESOURCES ---
+
+// Present while the world is mirrored from somewhere else, like a spectator host, so it must not
+// step on its own.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct ViewOnly;
+
+// Present in low-memory mode (`--low-memory`): the grid's wall layer and the copies of the world's
+// materials kept on the side, like the one spectators are diffed against, pack two cells per byte
+// where they can; see packed.rs. The grid's other channels stay as they are, so it shrinks by
+// only half a byte a cell (see `SimulationGrid::memory_bytes`); the copies halve.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct LowMemory;
+
+// The authoritative state of the world, one particle per cell, row-major with y = 0 at the bottom.
+// Alongside each cell it keeps the particle's temperature, a state byte whose meaning depends on
+// the material (a mirror's tilt, for example), its age in ticks, how stained it is, the shade it
+// was given when it appeared and whether a player's brush put it there. All of them travel with the
+// particle as it moves, so inventories can refund players for erasing their own placements. Zones
+// that override the rules locally, materials that follow rules of their own and loop bands belong
+// to the world as well. The pull of all magnets on every cell is cached until a magnet changes, and
+// which chunks are still busy is kept track of, so movement can skip the settled ones.
+// In hourglass mode the grid also counts what it recycled, until someone takes the counts, and
+// while its reaction log is on it notes where reactions fired, until someone takes the notes.
+// Water and oil carry a velocity, while squirted or flung, and the pressure they are under, and
+// conductors the charge batteries put on them, none of which last longer than the world runs or
+// are saved with it. Behind the cells is a background layer of walls, which is saved with the world
+// but only ever drawn: nothing that happens in the world touches it.
+#[derive(Resource, Clone)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(opaque, Resource))]
+pub struct SimulationGrid {
+    width: u32,
+    height: u32,
+    cells: Vec<Particle>,
+    placed: Vec<bool>,
+    temperature: Vec<f32>,
+    data: Vec<u8>,
+    age: Vec<u16>,
+    stain: Vec<u8>,
+    shade: Vec<u8>,
+    velocity: Vec<I8Vec2>,
+    pressure: Vec<u16>,
+    charge: Vec<u8>,
+    backdrop: PackedCells,
+    // Whether the walls are kept packed; see `LowMemory`.
+    low_memory: bool,
+    // How many particles have been written so far, which seeds the next one's shade.
+    written: u64,
+    zones: Vec<ParamZone>,
+    materials: Vec<MaterialOverride>,
+    loops: Vec<LoopBand>,
+    magnet_field: Option<Vec<Vec2>>,
+    hourglass: Option<Hourglass>,
+    recycled: [u32; Particle::ALL.len()],
+    reactions: Option<Vec<(IVec2, Reaction)>>,
+    reacted: [u32; Reaction::ALL.len()],
+    activity: ChunkActivity,
+    tags: CellTags,
+    bodies: CellBodies,
+    seed: u64,
+}
+
+impl SimulationGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Particle::Air; (width * height) as usize],
+            placed: vec![false; (width * height) as usize],
+            temperature: vec![AMBIENT_TEMPERATURE; (width * height) as usize],
+            data: vec![0; (width * height) as usize],
+            age: vec![0; (width * height) as usize],
+            stain: vec![0; (width * height) as usize],
+            shade: vec![0; (width * height) as usize],
+            velocity: vec![I8Vec2::ZERO; (width * height) as usize],
+            pressure: vec![0; (width * height) as usize],
+            charge: vec![0; (width * height) as usize],
+            backdrop: PackedCells::filled(Particle::Air, (width * height) as usize, false),
+            low_memory: false,
+            written: 0,
+            zones: Vec::new(),
+            materials: Vec::new(),
+            loops: Vec::new(),
+            magnet_field: None,
+            hourglass: None,
+            recycled: [0; Particle::ALL.len()],
+            reactions: None,
+            reacted: [0; Reaction::ALL.len()],
+            activity: ChunkActivity::new(width, height),
+            tags: CellTags::default(),
+            bodies: CellBodies::default(),
+            seed: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn cells(&self) -> &[Particle] {
+        &self.cells
+    }
+
+    // The seed the world's dice are rolled with, besides the tick and the cell (see `step`).
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn activity(&self) -> &ChunkActivity {
+        &self.activity
+    }
+
+    // Whether the chunk holding (x, y) is awake, so the rules have to look at it.
+    pub fn awake(&self, x: i32, y: i32) -> bool {
+        self.activity.is_awake(ChunkPos::containing(CellPos::new(x, y)))
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<Particle> {
+        self.in_bounds(x, y).then(|| self.cells[self.index(x, y)])
+    }
+
+    // Whether the particle at (x, y) was painted by a player rather than being part of the world.
+    pub fn is_placed(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.placed[self.index(x, y)]
+    }
+
+    pub fn temperature(&self, x: i32, y: i32) -> Option<f32> {
+        self.in_bounds(x, y).then(|| self.temperature[self.index(x, y)])
+    }
+
+    pub fn temperatures(&self) -> &[f32] {
+        &self.temperature
+    }
+
+    // Raises (or, with a negative amount, lowers) the temperature at (x, y).
+    pub fn add_heat(&mut self, x: i32, y: i32, degrees: f32) {
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.temperature[i] += degrees;
+            self.touch(i);
+        }
+    }
+
+    // How many ticks each particle has existed as its current material, saturating; air doesn't
+    // age. Only the display reads it, to weather materials.
+    pub fn ages(&self) -> &[u16] {
+        &self.age
+    }
+
+    // Throws a particle that can fly at (x, y), dust, the other powders, water or oil, through
+    // the air with `velocity` in cells per tick. Anything else stays put.
+    pub fn launch(&mut self, x: i32, y: i32, velocity: IVec2) {
+        let Some(particle) = self.get(x, y) else { return };
+        let i = self.index(x, y);
+        if particle == Particle::Dust {
+            self.data[i] = encode_velocity(velocity);
+        } else if flies(particle) {
+            let v = velocity.clamp(IVec2::splat(-MAX_SQUIRT), IVec2::splat(MAX_SQUIRT));
+            self.velocity[i] = v.as_i8vec2();
+        }
+        self.touch(i);
+    }
+
+    // The velocity of the particle at (x, y) in cells per tick, for those that can be thrown
+    // through the air (see `launch`). Settled ones have none.
+    pub fn velocity(&self, x: i32, y: i32) -> Option<IVec2> {
+        match self.get(x, y)? {
+            Particle::Dust => Some(decode_velocity(self.data[self.index(x, y)])),
+            particle if flies(particle) => Some(self.velocity[self.index(x, y)].as_ivec2()),
+            _ => None,
+        }
+    }
+
+    // How many cells of water or oil (x, y) lies under, counting down from the highest open
+    // surface of the body of it that it belongs to; more than its own depth where a taller column
+    // elsewhere in the body pushes on it. Bodies that have settled keep what they last had.
+    pub fn pressure(&self, x: i32, y: i32) -> Option<u16> {
+        match self.get(x, y)? {
+            Particle::Water | Particle::Oil => Some(self.pressure[self.index(x, y)]),
+            _ => None,
+        }
+    }
+
+    // How charged every cell is, from nothing (0) to a battery's full charge (255). Only
+    // conductors connected to a battery carry any.
+    pub fn charges(&self) -> &[u8] {
+        &self.charge
+    }
+
+    // How charged the conductor at (x, y) is (see `charges`); other cells don't carry charge.
+    pub fn charge(&self, x: i32, y: i32) -> Option<u8> {
+        let particle = self.get(x, y)?;
+        (particle.conductivity() > 0.0).then(|| self.charge[self.index(x, y)])
+    }
+
+    // The wall behind every cell, in grid order, air where there is none. Walls are drawn behind
+    // empty cells, and nothing in the world moves, burns or blasts them. In low-memory mode they
+    // are kept packed, and unpacked here.
+    pub fn backdrops(&self) -> Cow<'_, [Particle]> {
+        self.backdrop.cells()
+    }
+
+    // The walls as they are kept, to copy and compare without unpacking them.
+    pub fn backdrop_layer(&self) -> &PackedCells {
+        &self.backdrop
+    }
+
+    pub fn backdrop(&self, x: i32, y: i32) -> Option<Particle> {
+        self.in_bounds(x, y).then(|| self.backdrop.get(self.index(x, y)))
+    }
+
+    // Puts a wall of `particle` behind (x, y), or takes the wall there away with air. Returns
+    // whether anything changed.
+    pub fn set_backdrop(&mut self, x: i32, y: i32, particle: Particle) -> bool {
+        if !self.in_bounds(x, y) {
+            return false;
+        }
+        let i = self.index(x, y);
+        let changed = self.backdrop.get(i) != particle;
+        self.backdrop.set(i, particle);
+        changed
+    }
+
+    // Packs the walls two cells per byte while they hold at most 16 materials, or unpacks them.
+    // Walls are only drawn and saved, so they cost next to nothing to keep packed; the cells
+    // themselves are read and written by every rule every tick and stay one byte each.
+    pub fn set_low_memory(&mut self, enabled: bool) {
+        if enabled != self.low_memory {
+            self.low_memory = enabled;
+            self.backdrop = PackedCells::new(&self.backdrop.to_vec(), enabled);
+        }
+    }
+
+    pub fn low_memory(&self) -> bool {
+        self.low_memory
+    }
+
+    // How many bytes the channels kept per cell take, walls included: 17 a cell, or 16.5 with the
+    // walls packed, so low-memory mode saves about 3% of the grid itself. Zones, tags and the
+    // other lists kept per world rather than per cell aren't counted.
+    pub fn memory_bytes(&self) -> usize {
+        fn bytes<T>(channel: &[T]) -> usize {
+            std::mem::size_of_val(channel)
+        }
+        bytes(&self.cells)
+            + bytes(&self.placed)
+            + bytes(&self.temperature)
+            + bytes(&self.data)
+            + bytes(&self.age)
+            + bytes(&self.stain)
+            + bytes(&self.shade)
+            + bytes(&self.velocity)
+            + bytes(&self.pressure)
+            + bytes(&self.charge)
+            + self.backdrop.memory_bytes()
+    }
+
+    // How much soot or sediment covers each particle, from clean (0) to black (255). Like ages,
+    // stains only change how particles look.
+    pub fn stains(&self) -> &[u8] {
+        &self.stain
+    }
+
+    // The noise each particle was given when it was painted or otherwise appeared, so grains of
+    // the same material don't all look alike. Like stains, shades only change how particles look,
+    // and a particle keeps its shade as it moves and transmutes.
+    pub fn shades(&self) -> &[u8] {
+        &self.shade
+    }
+
+    pub fn data(&self, x: i32, y: i32) -> Option<u8> {
+        self.in_bounds(x, y).then(|| self.data[self.index(x, y)])
+    }
+
+    // The state byte of every cell, in grid order.
+    pub fn states(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Replaces the state byte at (x, y), keeping everything else about the cell.
+    pub fn set_data(&mut self, x: i32, y: i32, data: u8) {
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.data[i] = data;
+            self.touch(i);
+        }
+    }
+
+    // The signal the cell at (x, y) puts out: a turbine's output, zero for anything else.
+    pub fn signal(&self, x: i32, y: i32) -> u8 {
+        match self.get(x, y) {
+            Some(Particle::Turbine) => self.data[self.index(x, y)],
+            _ => 0,
+        }
+    }
+
+    // Writes `particle` at (x, y) as part of the world; returns false if the position lies
+    // outside the grid.
+    pub fn set(&mut self, x: i32, y: i32, particle: Particle) -> bool {
+        self.write(x, y, particle, 0, false)
+    }
+
+    // Like `set`, but marks the particle as placed by a player and gives it a state byte. Air is
+    // never marked.
+    pub fn place(&mut self, x: i32, y: i32, particle: Particle, data: u8) -> bool {
+        self.write(x, y, particle, data, particle != Particle::Air)
+    }
+
+    // Turns the particle at (x, y) into `particle`, which starts out new but keeps the
+    // temperature, whether a player placed it, its tag and its body, unless it has turned into air.
+    pub fn transmute(&mut self, x: i32, y: i32, particle: Particle) {
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.cells[i] = particle;
+            self.data[i] = particle.lifetime().map_or(0, |(ticks, _)| ticks);
+            self.age[i] = 0;
+            self.velocity[i] = I8Vec2::ZERO;
+            if particle == Particle::Air {
+                self.tags.set(i, None);
+                self.bodies.set(i, None);
+            }
+            self.touch(i);
+        }
+    }
+
+    pub fn tags(&self) -> &CellTags {
+        &self.tags
+    }
+
+    pub fn tags_mut(&mut self) -> &mut CellTags {
+        &mut self.tags
+    }
+
+    pub fn tag(&self, x: i32, y: i32) -> Option<usize> {
+        self.in_bounds(x, y).then(|| self.tags.get(self.index(x, y))).flatten()
+    }
+
+    // Tags the cell at (x, y), or with `None` untags it.
+    pub fn set_tag(&mut self, x: i32, y: i32, tag: Option<usize>) {
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.tags.set(i, tag);
+            self.touch(i);
+        }
+    }
+
+    pub fn bodies(&self) -> &CellBodies {
+        &self.bodies
+    }
+
+    pub fn bodies_mut(&mut self) -> &mut CellBodies {
+        &mut self.bodies
+    }
+
+    // The rigid body the cell at (x, y) belongs to, if any (see bodies.rs).
+    pub fn body(&self, x: i32, y: i32) -> Option<u32> {
+        self.in_bounds(x, y).then(|| self.bodies.owner(self.index(x, y))).flatten()
+    }
+
+    pub fn set_body(&mut self, x: i32, y: i32, body: Option<u32>) {
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.bodies.set(i, body);
+            self.touch(i);
+        }
+    }
+
+    pub fn zones(&self) -> &[ParamZone] {
+        &self.zones
+    }
+
+    // Adds a zone on top of the existing ones; it wins where they overlap.
+    pub fn add_zone(&mut self, zone: ParamZone) {
+        self.zones.push(zone);
+        self.activity.wake_all();
+    }
+
+    pub fn remove_zone(&mut self, index: usize) -> ParamZone {
+        self.activity.wake_all();
+        self.zones.remove(index)
+    }
+
+    pub fn material_overrides(&self) -> &[MaterialOverride] {
+        &self.materials
+    }
+
+    // Replaces every material's overrides. At most one per material counts: the first.
+    pub fn set_material_overrides(&mut self, materials: Vec<MaterialOverride>) {
+        self.materials = materials;
+        self.activity.wake_all();
+    }
+
+    pub fn loops(&self) -> &[LoopBand] {
+        &self.loops
+    }
+
+    pub fn add_loop(&mut self, band: LoopBand) {
+        self.loops.push(band);
+        self.activity.wake_all();
+    }
+
+    pub fn remove_loop(&mut self, index: usize) -> LoopBand {
+        self.activity.wake_all();
+        self.loops.remove(index)
+    }
+
+    pub fn hourglass(&self) -> Option<Hourglass> {
+        self.hourglass
+    }
+
+    pub fn set_hourglass(&mut self, hourglass: Option<Hourglass>) {
+        self.hourglass = hourglass;
+        self.recycled = [0; Particle::ALL.len()];
+    }
+
+    pub fn count_recycled(&mut self, particle: Particle) {
+        self.recycled[particle as usize] += 1;
+    }
+
+    // How many cells of each material went round since the last call.
+    pub fn take_recycled(&mut self) -> [u32; Particle::ALL.len()] {
+        std::mem::take(&mut self.recycled)
+    }
+
+    // Starts or stops noting where reactions fire. Off, the rules keep no notes at all.
+    pub fn set_reaction_log(&mut self, enabled: bool) {
+        if enabled != self.reactions.is_some() {
+            self.reactions = enabled.then(Vec::new);
+        }
+    }
+
+    // Where reactions fired since the last call, and which.
+    pub fn take_reactions(&mut self) -> Vec<(IVec2, Reaction)> {
+        self.reactions.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    // How many cells each reaction changed since the last call, by `Reaction` index. Counted
+    // whether or not the log is on; a runner nobody takes them from just tops out.
+    pub fn take_reacted(&mut self) -> [u32; Reaction::ALL.len()] {
+        std::mem::take(&mut self.reacted)
+    }
+
+    // The counts `take_reacted` would return, left in place.
+    pub fn reacted(&self) -> [u32; Reaction::ALL.len()] {
+        self.reacted
+    }
+
+    pub fn note_reaction(&mut self, cell: IVec2, reaction: Reaction) {
+        let reacted = &mut self.reacted[reaction as usize];
+        *reacted = reacted.saturating_add(1);
+        if let Some(reactions) = &mut self.reactions {
+            reactions.push((cell, reaction));
+        }
+    }
+
+    // Everything kept about the cell at (x, y).
+    pub fn cell(&self, x: i32, y: i32) -> Option<CellState> {
+        self.in_bounds(x, y).then(|| {
+            let i = self.index(x, y);
+            CellState {
+                particle: self.cells[i],
+                placed: self.placed[i],
+                temperature: self.temperature[i],
+                data: self.data[i],
+                age: self.age[i],
+                stain: self.stain[i],
+                shade: self.shade[i],
+                body: self.bodies.owner(i),
+            }
+        })
+    }
+
+    // Puts `cell` at (x, y) exactly as it is, unlike `place`, which paints a fresh particle.
+    // Returns whether (x, y) is inside the grid, as `set` does.
+    pub fn set_cell(&mut self, x: i32, y: i32, cell: CellState) -> bool {
+        if !self.in_bounds(x, y) {
+            return false;
+        }
+        let i = self.index(x, y);
+        if self.cells[i] == Particle::Magnet || cell.particle == Particle::Magnet {
+            self.magnet_field = None;
+        }
+        self.cells[i] = cell.particle;
+        self.placed[i] = cell.placed;
+        self.temperature[i] = cell.temperature;
+        self.data[i] = cell.data;
+        self.age[i] = cell.age;
+        self.stain[i] = cell.stain;
+        self.shade[i] = cell.shade;
+        self.bodies.set(i, cell.body);
+        self.velocity[i] = I8Vec2::ZERO;
+        self.touch(i);
+        true
+    }
+
+    // Exchanges two cells along with everything kept about them, tags and bodies too. Returns
+    // whether it did, which it doesn't unless both lie inside the grid.
+    pub fn swap_cells(&mut self, a: CellPos, b: CellPos) -> bool {
+        if !self.in_bounds(a.x, a.y) || !self.in_bounds(b.x, b.y) {
+            return false;
+        }
+        let (a, b) = (self.index(a.x, a.y), self.index(b.x, b.y));
+        self.swap(a, b);
+        true
+    }
+
+    // Fills every cell with air and removes all walls, zones, material overrides, loop bands, tags
+    // and bodies. Hourglass mode stays as it is.
+    pub fn clear(&mut self) {
+        self.cells.fill(Particle::Air);
+        self.placed.fill(false);
+        self.temperature.fill(AMBIENT_TEMPERATURE);
+        self.data.fill(0);
+        self.age.fill(0);
+        self.stain.fill(0);
+        self.velocity.fill(I8Vec2::ZERO);
+        self.pressure.fill(0);
+        self.charge.fill(0);
+        self.backdrop = PackedCells::filled(Particle::Air, self.cells.len(), self.low_memory);
+        self.zones.clear();
+        self.materials.clear();
+        self.loops.clear();
+        self.tags.clear();
+        self.bodies.clear();
+        self.magnet_field = None;
+        self.activity.wake_all();
+    }
+
+    // Puts the world kept in `saved` in place of this one: every cell with everything kept about
+    // it, the zones, material overrides, loop bands, tags and bodies. Hourglass mode, its counts
+    // and the reaction log and counts belong to the session rather than the world, so they stay
+    // as they are.
+    pub fn restore(&mut self, saved: &SimulationGrid) {
+        let (hourglass, recycled, reacted) = (self.hourglass, self.recycled, self.reacted);
+        let (reactions, low_memory) = (self.reactions.take(), self.low_memory);
+        *self = saved.clone();
+        self.hourglass = hourglass;
+        self.recycled = recycled;
+        self.reactions = reactions;
+        self.reacted = reacted;
+        self.set_low_memory(low_memory);
+        self.activity.wake_all();
+    }
+
+    // Counts cells of `particle` inside the inclusive rectangle `min..=max`, clipped to the grid.
+    pub fn count_in_rect(&self, particle: Particle, min: CellPos, max: CellPos) -> u32 {
+        let (min, max) = self.clip(min, max);
+        let mut count = 0;
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                count += (self.cells[self.index(x, y)] == particle) as u32;
+            }
+        }
+        count
+    }
+
+    // Writes `particle` as part of the world into the inclusive rectangle `min..=max`, clipped
+    // to the grid. Returns how many cells it wrote.
+    pub fn fill_rect(&mut self, min: CellPos, max: CellPos, particle: Particle) -> u32 {
+        let (min, max) = self.clip(min, max);
+        let mut written = 0;
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                written += self.set(x, y, particle) as u32;
+            }
+        }
+        written
+    }
+
+    // Adds `degrees` to every cell of the inclusive rectangle `min..=max`, clipped to the grid.
+    pub fn heat_rect(&mut self, min: CellPos, max: CellPos, degrees: f32) {
+        let (min, max) = self.clip(min, max);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.add_heat(x, y, degrees);
+            }
+        }
+    }
+
+    // Writes `particle` as part of the world into every cell within `radius` cells of `center`.
+    // Returns how many cells it wrote.
+    pub fn fill_circle(&mut self, center: CellPos, radius: i32, particle: Particle) -> u32 {
+        let reach = IVec2::splat(radius);
+        let (min, max) = self.clip(CellPos(center.0 - reach), CellPos(center.0 + reach));
+        let mut written = 0;
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if (IVec2::new(x, y) - center.0).length_squared() <= radius * radius {
+                    written += self.set(x, y, particle) as u32;
+                }
+            }
+        }
+        written
+    }
+
+    // Every cell inside the inclusive rectangle `min..=max`, clipped to the grid, with
+    // everything kept about it, row by row from the bottom.
+    pub fn query_region(
+        &self,
+        min: CellPos,
+        max: CellPos,
+    ) -> impl Iterator<Item = (CellPos, CellState)> + '_ {
+        let (min, max) = self.clip(min, max);
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| CellPos::new(x, y)))
+            .filter_map(|cell| Some((cell, self.cell(cell.x, cell.y)?)))
+    }
+
+    // `min..=max` cut down to the cells inside the grid; empty if none are.
+    fn clip(&self, min: CellPos, max: CellPos) -> (IVec2, IVec2) {
+        let last = IVec2::new(self.width as i32 - 1, self.height as i32 - 1);
+        (min.0.max(IVec2::ZERO), max.0.min(last))
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    // Notes that the cell at index `i` changed, keeping its chunk awake.
+    fn touch(&mut self, i: usize) {
+        let width = self.width as usize;
+        self.activity.touch(CellPos::new((i % width) as i32, (i / width) as i32));
+    }
+
+    fn write(&mut self, x: i32, y: i32, particle: Particle, data: u8, placed: bool) -> bool {
+        if !self.in_bounds(x, y) {
+            return false;
+        }
+        let i = self.index(x, y);
+        if self.cells[i] == Particle::Magnet || particle == Particle::Magnet {
+            self.magnet_field = None;
+        }
+        self.cells[i] = particle;
+        self.placed[i] = placed;
+        // Particles that don't last count their ticks down in the state byte instead.
+        self.data[i] = particle.lifetime().map_or(data, |(ticks, _)| ticks);
+        self.age[i] = 0;
+        self.stain[i] = 0;
+        self.velocity[i] = I8Vec2::ZERO;
+        self.pressure[i] = 0;
+        self.charge[i] = 0;
+        self.shade[i] = (hash(x, y, self.written) >> 56) as u8;
+        self.written += 1;
+        self.temperature[i] = particle.thermal().painted_at;
+        // A painted cell is a new one, and carries no tag and belongs to no body.
+        self.tags.set(i, None);
+        self.bodies.set(i, None);
+        self.touch(i);
+        true
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.cells.swap(a, b);
+        self.placed.swap(a, b);
+        self.temperature.swap(a, b);
+        self.data.swap(a, b);
+        self.age.swap(a, b);
+        self.stain.swap(a, b);
+        self.shade.swap(a, b);
+        self.velocity.swap(a, b);
+        self.pressure.swap(a, b);
+        self.charge.swap(a, b);
+        self.tags.swap(a, b);
+        self.bodies.swap(a, b);
+        self.touch(a);
+        self.touch(b);
+    }
+}
+
+// One cell of the grid with everything kept about it, as `SimulationGrid::cell` reads it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct CellState {
+    pub particle: Particle,
+    pub placed: bool,
+    pub temperature: f32,
+    pub data: u8,
+    pub age: u16,
+    pub stain: u8,
+    pub shade: u8,
+    // The rigid body it belongs to; see bodies.rs.
+    pub body: Option<u32>,
+}
+
+// The tunable constants of the rules. Every backend reads them, and the parameters panel edits
+// them live, and presets store them; fields missing from a preset keep their defaults.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct SimParams {
+    // How many cells a particle may fall per tick. The fractional part is the chance of falling
+    // one cell further, so 0.25 falls a cell every fourth tick on average; 0 switches gravity off.
+    pub gravity: f32,
+    // How many cells a liquid may spread sideways per tick.
+    pub dispersion: u32,
+    // How strongly small amounts of water, oil and lava hold together, from 0 (not at all) to 1:
+    // droplets bead up instead of spreading thin, and cling to ceilings and walls for a while
+    // before they drip. It is only for looks, so it is off unless set, usually for one liquid.
+    pub cohesion: f32,
+    // Chance per tick that water past its boiling point boils, and that sand past its melting
+    // point melts.
+    pub boil_chance: f32,
+    pub melt_chance: f32,
+    // Fraction of the gap to its neighbours' mean temperature that a cell closes per tick.
+    pub heat_diffusion: f32,
+    // Fraction of the gap to ambient that every cell loses per tick.
+    pub cooling_rate: f32,
+    // The temperature cells drift towards.
+    pub ambient_temperature: f32,
+    // How many cells per tick the wind carries airborne particles sideways; negative blows left.
+    pub wind: f32,
+    // While set, drives the whole world's temperature, zones included.
+    pub thermostat: Option<Thermostat>,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        Self {
+            gravity: 1.0,
+            dispersion: 1,
+            cohesion: 0.0,
+            boil_chance: 1.0,
+            melt_chance: 1.0,
+            heat_diffusion: 0.1,
+            cooling_rate: 0.02,
+            ambient_temperature: AMBIENT_TEMPERATURE,
+            wind: 0.0,
+            thermostat: None,
+        }
+    }
+}
+
+// A control for experiments that freezes or bakes the whole world: every cell closes `rate` of
+// the gap to `target` per tick, whatever it is made of, on top of cooling towards ambient. Small
+// rates take the world there over many seconds.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub struct Thermostat {
+    pub target: f32,
+    pub rate: f32,
+}
+
+impl Default for Thermostat {
+    fn default() -> Self {
+        Self {
+            target: AMBIENT_TEMPERATURE,
+            rate: 0.002,
+        }
+    }
+}
+
+// The parts of a tick that can be scheduled on their own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum Subsystem {
+    // Falling, sliding and flowing, with the turbines and loop bands it drives.
+    Movement,
+    // Diffusion between cells and drifting towards ambient.
+    Heat,
+    // Boiling, melting, dissolving, crystallizing and the materials' own behaviors, like
+    // radioactive decay.
+    Chemistry,
+    // Wear that only shows in how particles look: how long each has been around, which makes it
+    // weather, and soot and sediment stains.
+    Aging,
+}
+
+impl Subsystem {
+    pub const ALL: [Subsystem; 4] =
+        [Subsystem::Movement, Subsystem::Heat, Subsystem::Chemistry, Subsystem::Aging];
+}
+
+// The chemistry rules that turn one material into another, as the reaction log notes them. Boil,
+// Melt, Thaw, Set and Condense are the phase changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum Reaction {
+    Boil,
+    Melt,
+    Thaw,
+    Compact,
+    Dissolve,
+    Crystallize,
+    Burn,
+    Set,
+    Condense,
+    // A rule from a reactions file fired.
+    Rule,
+    // A material's own behavior turned it into something else, like radioactive decay.
+    Behavior,
+    Explode,
+    Electrolyze,
+}
+
+impl Reaction {
+    pub const ALL: [Reaction; 13] = [
+        Reaction::Boil,
+        Reaction::Melt,
+        Reaction::Thaw,
+        Reaction::Compact,
+        Reaction::Dissolve,
+        Reaction::Crystallize,
+        Reaction::Burn,
+        Reaction::Set,
+        Reaction::Condense,
+        Reaction::Rule,
+        Reaction::Behavior,
+        Reaction::Explode,
+        Reaction::Electrolyze,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Reaction::Boil => "Water boils into steam",
+            Reaction::Melt => "Sand melts into glass",
+            Reaction::Thaw => "Snow or ice melts",
+            Reaction::Compact => "Snow compacts into ice",
+            Reaction::Dissolve => "Salt dissolves",
+            Reaction::Crystallize => "Crystal grows",
+            Reaction::Burn => "Something catches fire",
+            Reaction::Set => "Lava sets into obsidian",
+            Reaction::Condense => "Steam condenses",
+            Reaction::Rule => "Reaction rule (data)",
+            Reaction::Behavior => "Material behavior (decay)",
+            Reaction::Explode => "An explosive goes off",
+            Reaction::Electrolyze => "Water splits into hydrogen",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default, Serialize, Deserialize))]
+pub struct Cadence {
+    pub enabled: bool,
+    // Runs on every this many ticks, catching up on the ones it skipped.
+    pub every: u32,
+}
+
+impl Default for Cadence {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            every: 1,
+        }
+    }
+}
+
+// How often each subsystem runs. Slower machines can run heat and chemistry less often: a
+// subsystem that skips ticks applies their worth of change at once, so heat still spreads and
+// water still boils at the same pace, only in coarser steps.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+pub struct TickSchedule {
+    pub movement: Cadence,
+    pub heat: Cadence,
+    pub chemistry: Cadence,
+    pub aging: Cadence,
+    // At most this many ticks run in one frame; a frame that falls further behind drops the rest,
+    // so a slow machine runs slower instead of stalling.
+    pub max_ticks_per_frame: u32,
+}
+
+impl Default for TickSchedule {
+    fn default() -> Self {
+        Self {
+            movement: default(),
+            heat: default(),
+            chemistry: default(),
+            aging: default(),
+            max_ticks_per_frame: 4,
+        }
+    }
+}
+
+impl TickSchedule {
+    pub fn cadence(&self, subsystem: Subsystem) -> &Cadence {
+        match subsystem {
+            Subsystem::Movement => &self.movement,
+            Subsystem::Heat => &self.heat,
+            Subsystem::Chemistry => &self.chemistry,
+            Subsystem::Aging => &self.aging,
+        }
+    }
+
+    pub fn cadence_mut(&mut self, subsystem: Subsystem) -> &mut Cadence {
+        match subsystem {
+            Subsystem::Movement => &mut self.movement,
+            Subsystem::Heat => &mut self.heat,
+            Subsystem::Chemistry => &mut self.chemistry,
+            Subsystem::Aging => &mut self.aging,
+        }
+    }
+
+    // How many ticks' worth `subsystem` should apply on `tick`; 0 means it sits this one out.
+    fn due(&self, subsystem: Subsystem, tick: u64) -> u32 {
+        let Cadence { enabled, every } = *self.cadence(subsystem);
+        let every = every.max(1);
+        if enabled && tick.is_multiple_of(every as u64) { every } else { 0 }
+    }
+}
+
+// The simulation's tick rate, the one knob for how fast the world runs, which an app embedding
+// the sandbox can set at any time: `pace_ticks` sets the `FixedUpdate` timestep from it (times the
+// speed control), so a 240 Hz display steps no faster than a 60 Hz one, and a slow frame runs
+// several ticks. 0 stops the world. It isn't one of the rules' `SimParams`, so replays leave it be.
+#[derive(Resource, Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+pub struct SimulationTickRate {
+    pub ticks_per_second: f32,
+}
+
+impl Default for SimulationTickRate {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: 60.0,
+        }
+    }
+}
+
+// Pausing, single-stepping and running faster or slower than the tick rate, for watching
+// particles tick by tick (see `control`). Unlike a tick rate of 0, pausing keeps the rate as it is
+// and still allows stepping.
+#[derive(Resource, Clone, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct SimulationControl {
+    pub paused: bool,
+    // Ticks to run while paused, one per fixed step.
+    pub pending_steps: u32,
+    // What the tick rate is multiplied by.
+    pub speed: f32,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            pending_steps: 0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl SimulationControl {
+    pub fn running(&self) -> bool {
+        !self.paused || self.pending_steps > 0
+    }
+}
+
+// Where the world steps. On the CPU every rule runs on the grid. On the GPU (see gpu_sim.rs) a
+// compute pass runs only movement: powders and liquids sinking through what is lighter and sliding
+// off what they can't, liquids spreading and gases rising. It doesn't simulate:
+// - heat: temperatures don't spread, and nothing boils, melts, freezes or ignites;
+// - chemistry: the built-in reactions, the reaction rules from data, fire, explosives, salt,
+//   crystals, and the materials' own behaviors (decay, scripts);
+// - electricity, magnets, turbines, ropes, loops and hourglass mode;
+// - zones, material overrides and frozen tags, which the pass can't see;
+// - particles' lifetimes, weathering and stains, and launched cells' flight;
+// - liquids finding their level under pressure, and chunks going to sleep: every cell is looked
+//   at every tick.
+// Rather than leave those out, the world stays on the CPU while it holds anything they apply to.
+// The display settings pick it, and the CPU also stays in charge wherever the GPU can't step.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+pub enum SimulationBackend {
+    #[default]
+    Cpu,
+    Gpu,
+}
+
+impl SimulationBackend {
+    pub const ALL: [SimulationBackend; 2] = [SimulationBackend::Cpu, SimulationBackend::Gpu];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SimulationBackend::Cpu => "CPU (all rules)",
+            SimulationBackend::Gpu => "GPU (movement only)",
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct SimulationStats {
+    pub tick: u64,
+    // How many ticks ran in the last frame, and how long they took on the CPU.
+    pub ticks_last_frame: u32,
+    pub step_time: Duration,
+    // How many chunks movement still looks at (see `ChunkActivity`), and how many there are.
+    pub awake_chunks: u32,
+    pub chunks: u32,
+    counts: [u32; Particle::ALL.len()],
+}
+
+impl SimulationStats {
+    pub fn count(&self, particle: Particle) -> u32 {
+        self.counts[particle as usize]
+    }
+}
+
+// --- SYSTEMS ---
+
+// Keeps the fixed timestep at `ticks_per_second`, times the speed, and starts counting the frame's
+// ticks. A rate of 0 leaves the timestep be; the world doesn't step then.
+fn pace_ticks(
+    tick_rate: Res<SimulationTickRate>,
+    control: Res<SimulationControl>,
+    mut fixed: ResMut<Time<Fixed>>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    let rate = tick_rate.ticks_per_second * control.speed;
+    if rate > 0.0 && (fixed.timestep().as_secs_f32() * rate - 1.0).abs() > 1e-4 {
+        fixed.set_timestep_hz(rate as f64);
+    }
+    stats.ticks_last_frame = 0;
+    stats.step_time = Duration::ZERO;
+}
+
+// The rules a tick runs besides the built-in ones: those defined in data and the materials' own.
+#[derive(SystemParam)]
+struct ExtraRules<'w> {
+    reactions: Res<'w, ReactionTable>,
+    behaviors: Res<'w, MaterialBehaviors>,
+}
+
+// The schedule ticks run on: the one set, made coarser while load is being shed.
+#[derive(SystemParam)]
+struct TickPlan<'w> {
+    schedule: Res<'w, TickSchedule>,
+    degradation: Res<'w, Degradation>,
+}
+
+impl TickPlan<'_> {
+    fn schedule(&self) -> TickSchedule {
+        self.degradation.schedule(&self.schedule)
+    }
+}
+
+// One tick, run as often as the fixed timestep asks for: several times in a frame when frames are
+// slow, or in only some frames when they are fast. Ticks beyond `max_ticks_per_frame` in one frame
+// are dropped, so a slow machine runs slower instead of stalling. While paused, only the steps
+// asked for run. Shedding load may run a coarser schedule than the one set.
+fn step_simulation(
+    params: Res<SimParams>,
+    tick_rate: Res<SimulationTickRate>,
+    plan: TickPlan,
+    rules: ExtraRules,
+    mut control: ResMut<SimulationControl>,
+    mut grid: ResMut<SimulationGrid>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    let schedule = plan.schedule();
+    let most = schedule.max_ticks_per_frame.max(1);
+    if tick_rate.ticks_per_second <= 0.0 || stats.ticks_last_frame >= most || !control.running() {
+        return;
+    }
+    if control.paused {
+        control.pending_steps -= 1;
+    }
+    let start = Instant::now();
+    step(&mut grid, stats.tick, &params, &schedule, &rules.reactions, &rules.behaviors);
+    stats.tick += 1;
+    stats.ticks_last_frame += 1;
+    stats.step_time += start.elapsed();
+}
+
+// Recounts materials and awake chunks whenever the grid changed, whether by ticking or by
+// painting: once a frame however many ticks it ran, in one pass over the cells.
+fn update_stats(grid: Res<SimulationGrid>, mut stats: ResMut<SimulationStats>) {
+    if !grid.is_changed() {
+        return;
+    }
+    stats.counts = [0; Particle::ALL.len()];
+    for cell in grid.cells() {
+        stats.counts[*cell as usize] += 1;
+    }
+    stats.awake_chunks = grid.activity.awake().count() as u32;
+    stats.chunks = grid.activity.chunk_count() as u32;
+}
+
+// Tells the rest of the game how many cells each reaction changed in the frame's ticks, one
+// `SimEvent::Reacted` per reaction that fired. Taking the counts isn't an edit to the world.
+fn report_reactions(mut grid: ResMut<SimulationGrid>, mut sim_events: EventWriter<SimEvent>) {
+    let reacted = grid.bypass_change_detection().take_reacted();
+    for (reaction, cells) in Reaction::ALL.into_iter().zip(reacted) {
+        if cells > 0 {
+            sim_events.write(SimEvent::Reacted { reaction, cells });
+        }
+    }
+}
+
+// Keeps the walls packed in low-memory mode, whatever grid was loaded in. Packing isn't an edit to
+// the world.
+fn pack_walls(mut grid: ResMut<SimulationGrid>) {
+    if !grid.low_memory() {
+        let unpacked = grid.memory_bytes();
+        grid.bypass_change_detection().set_low_memory(true);
+        info!(
+            "Low-memory mode: the grid takes {} KiB instead of {} KiB",
+            grid.memory_bytes() / 1024,
+            unpacked / 1024
+        );
+    }
+}
+
+// --- RULES ---
+
+// Advances the world by one tick. Rows are processed bottom-up so a falling particle moves at
+// most once per tick, and the horizontal scan direction alternates every tick so liquids don't
+// drift towards one side. Zones override `params` inside their rectangles and material overrides
+// for their material, `schedule` decides which subsystems run, `reactions` adds the rules defined
+// in data and `behaviors` the materials' own rules.
+pub fn step(
+    grid: &mut SimulationGrid,
+    tick: u64,
+    params: &SimParams,
+    schedule: &TickSchedule,
+    reactions: &ReactionTable,
+    behaviors: &MaterialBehaviors,
+) {
+    let local = LocalParams::new(params, &grid.zones, &grid.materials);
+    // Every roll goes by the tick, so the seed moves the tick the rules see by whole multiples of
+    // 2^32: the dice fall differently while the scan direction alternates as before. The schedule
+    // goes by the real tick.
+    let seed = grid.seed ^ (grid.seed >> 32);
+    let (real, tick) = (tick, tick.wrapping_add(seed << 32));
+    if schedule.due(Subsystem::Movement, real) > 0 {
+        move_particles(grid, tick, &local);
+    }
+    let heat_ticks = schedule.due(Subsystem::Heat, real);
+    if heat_ticks > 0 {
+        exchange_heat(grid, &local, heat_ticks);
+        if let Some(thermostat) = params.thermostat {
+            drive_temperatures(grid, thermostat, heat_ticks);
+        }
+    }
+    let chemistry_ticks = schedule.due(Subsystem::Chemistry, real);
+    if chemistry_ticks > 0 {
+        conduct(grid, tick, chemistry_ticks);
+        react(grid, tick, &local, chemistry_ticks);
+        detonate(grid, tick);
+        reactions.run(grid, tick, chemistry_ticks);
+        diffuse_salt(grid, chemistry_ticks);
+        crystallize(grid, tick, chemistry_ticks);
+        behaviors.run(grid, tick, chemistry_ticks);
+    }
+    let aging_ticks = schedule.due(Subsystem::Aging, real);
+    if aging_ticks > 0 {
+        age_particles(grid, aging_ticks);
+        stain(grid, aging_ticks);
+    }
+}
+
+// Counts down the ticks every particle that doesn't last has left, and moves every powder, liquid
+// and gas cell of the chunks still awake once, lets water and oil under pressure find their level,
+// then spins the turbines they flowed through and carries particles around loop bands and, in
+// hourglass mode, from the bottom row to the top.
+fn move_particles(grid: &mut SimulationGrid, tick: u64, local: &LocalParams) {
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    let mut moved = vec![false; grid.cells.len()];
+    let field = grid.magnet_field.take().unwrap_or_else(|| magnet_field(grid));
+    // How many liquid cells flowed through each turbine cell this tick.
+    let mut flow = vec![0u8; grid.cells.len()];
+    grid.activity.settle();
+    tie_ropes(grid);
+    // Frozen tags hold their cells still; most worlds have none to look up.
+    let freezes = grid.tags.freezes();
+
+    for y in 0..height {
+        for i in 0..width {
+            let x = if tick.is_multiple_of(2) { i } else { width - 1 - i };
+            let index = grid.index(x, y);
+            if moved[index] || (freezes && grid.tags.is_frozen(index)) {
+                continue;
+            }
+            // Even in chunks that have gone to sleep, so nothing lasts forever there.
+            if let Some((_, becomes)) = grid.cells[index].lifetime() {
+                grid.data[index] = grid.data[index].saturating_sub(1);
+                if grid.data[index] == 0 {
+                    grid.transmute(x, y, becomes);
+                    continue;
+                }
+            }
+            if !grid.awake(x, y) {
+                continue;
+            }
+
+            let particle = grid.cells[index];
+            let target = match particle {
+                Particle::Sand
+                | Particle::Snow
+                | Particle::Salt
+                | Particle::Radium
+                | Particle::Lead
+                | Particle::Gunpowder => fly(grid, x, y).or_else(|| {
+                    grain_target(grid, x, y, tick, local.at(x, y, particle).gravity)
+                }),
+                Particle::Dust => drift_dust(grid, x, y, tick, local.at(x, y, particle)),
+                Particle::Foam => rise_foam(grid, x, y),
+                Particle::IronPowder => fly(grid, x, y).or_else(|| {
+                    let pull = field.get(index).copied().unwrap_or(Vec2::ZERO);
+                    iron_target(grid, x, y, tick, local.at(x, y, particle), pull)
+                }),
+                Particle::Water | Particle::Oil => fly(grid, x, y)
+                    .or_else(|| liquid_target(grid, x, y, tick, local.at(x, y, particle))),
+                Particle::Goo => goo_target(grid, x, y, tick, local.at(x, y, particle)),
+                Particle::Rope => rope_target(grid, x, y, tick, local.at(x, y, particle)),
+                Particle::Lava => lava_target(grid, x, y, tick, local.at(x, y, particle)),
+                Particle::Steam | Particle::Hydrogen => steam_target(grid, x, y, tick),
+                Particle::Fire => fire_target(grid, x, y, tick),
+                Particle::Smoke => {
+                    gas_target(grid, x, y, tick, SMOKE_BUOYANCY, SMOKE_DISPERSION)
+                }
+                Particle::Air
+                | Particle::Bedrock
+                | Particle::Laser
+                | Particle::Mirror
+                | Particle::Glass
+                | Particle::Turbine
+                | Particle::Ice
+                | Particle::Crystal
+                | Particle::Magnet
+                | Particle::Uranium
+                | Particle::Tnt
+                | Particle::Battery => None,
+            };
+
+            if let Some((tx, ty)) = target {
+                let target_index = grid.index(tx, ty);
+                if freezes && grid.tags.is_frozen(target_index) {
+                    continue;
+                }
+                // Credit every turbine between a liquid and where it ended up, on the line it
+                // moved along.
+                if grid.cells[index] == Particle::Water {
+                    let (start, delta) = (IVec2::new(x, y), IVec2::new(tx - x, ty - y));
+                    let steps = delta.abs().max_element();
+                    for step in 1..steps {
+                        let c = start + delta * step / steps;
+                        let i = grid.index(c.x, c.y);
+                        if grid.cells[i] == Particle::Turbine {
+                            flow[i] = flow[i].saturating_add(1);
+                        }
+                    }
+                }
+
+                grid.swap(index, target_index);
+                moved[target_index] = true;
+                if grid.cells[target_index] == Particle::Rope {
+                    move_rope_links(grid, (x, y), (tx, ty));
+                }
+
+                // Water landing on water now and then churns it into foam.
+                let landed = grid.cells[target_index] == Particle::Water
+                    && ty < y
+                    && grid.get(tx, ty - 1) == Some(Particle::Water);
+                if landed && roll(tx, ty, !tick) < FOAM_CHANCE {
+                    grid.transmute(tx, ty - 1, Particle::Foam);
+                    moved[grid.index(tx, ty - 1)] = true;
+                }
+            }
+        }
+    }
+
+    grid.magnet_field = Some(field);
+    level_liquids(grid, tick, local);
+    wrap_loop_bands(grid);
+    recycle_bottom_row(grid);
+    spin_turbines(grid, &flow);
+}
+
+// Heat spreads between neighbouring cells and every cell drifts back towards ambient temperature,
+// each as quickly as its material conducts, by as much as `ticks` ticks would have moved them. Only
+// in the chunks that are awake: a cell whose temperature still moves keeps its chunk and the ones
+// around it awake, so heat wakes the chunks it spreads into.
+fn exchange_heat(grid: &mut SimulationGrid, local: &LocalParams, ticks: u32) {
+    let (width, height) = (grid.width as i32, grid.height as i32);
+    if local.all().any(|params| params.heat_diffusion > 0.0) {
+        let before = grid.temperature.clone();
+        for y in 0..height {
+            for x in 0..width {
+                if !grid.awake(x, y) {
+                    continue;
+                }
+                let i = grid.index(x, y);
+                // Cells at the edge of the grid exchange nothing with the outside.
+                let at = |nx, ny| {
+                    if grid.in_bounds(nx, ny) { before[grid.index(nx, ny)] } else { before[i] }
+                };
+                let mean = (at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1)) / 4.0;
+                let conductivity = grid.cells[i].thermal().conductivity;
+                let diffusion = local.at(x, y, grid.cells[i]).heat_diffusion * conductivity;
+                let rate = compound(diffusion, ticks);
+                heat(grid, i, (mean - before[i]) * rate);
+            }
+        }
+    }
+
+    for i in 0..grid.cells.len() {
+        let (x, y) = ((i % width as usize) as i32, (i / width as usize) as i32);
+        if !grid.awake(x, y) {
+            continue;
+        }
+        let params = local.at(x, y, grid.cells[i]);
+        let cooling = params.cooling_rate * grid.cells[i].thermal().conductivity;
+        let drift = params.ambient_temperature - grid.temperature[i];
+        heat(grid, i, drift * compound(cooling, ticks));
+    }
+}
+
+// Pulls every cell's temperature towards the thermostat's target, `ticks` ticks' worth. Asleep
+// chunks too, since the thermostat is what wakes them.
+fn drive_temperatures(grid: &mut SimulationGrid, thermostat: Thermostat, ticks: u32) {
+    let rate = compound(thermostat.rate, ticks);
+    for i in 0..grid.temperature.len() {
+        heat(grid, i, (thermostat.target - grid.temperature[i]) * rate);
+    }
+}
+
+// Moves the temperature of cell `i` by `degrees`, touching its chunk if that stirs it.
+fn heat(grid: &mut SimulationGrid, i: usize, degrees: f32) {
+    grid.temperature[i] += degrees;
+    if degrees.abs() > STIRRING_HEAT {
+        grid.touch(i);
+    }
+}
+
+// Water that gets hot enough may boil into steam, sand may melt into glass and snow and ice into
+// water, cooled lava may set into obsidian and cooled steam condense, buried snow may compact into
+// ice, and what burns may catch fire from fire or heat while fire puffs out smoke, with the chances
+// of `ticks` ticks. Cells in chunks that are asleep don't change.
+fn react(grid: &mut SimulationGrid, tick: u64, local: &LocalParams, ticks: u32) {
+    let width = grid.width as usize;
+    let load = overburden(grid);
+    for (i, &load) in load.iter().enumerate() {
+        let (x, y) = ((i % width) as i32, (i / width) as i32);
+        if !grid.awake(x, y) {
+            continue;
+        }
+        let params = local.at(x, y, grid.cells[i]);
+        let hot = grid.temperature[i];
+        let happens = |per_tick| roll(x, y, tick) < compound(per_tick, ticks);
+        let before = grid.cells[i];
+        match grid.cells[i] {
+            Particle::Water if hot >= WATER_BOILS_AT && happens(params.boil_chance) => {
+                // Boiling off saturated water leaves its salt behind.
+                let salty = grid.data[i] >= SALT_SATURATION;
+                grid.cells[i] = if salty { Particle::Salt } else { Particle::Steam };
+                grid.placed[i] = false;
+                grid.data[i] = 0;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Boil);
+            }
+            Particle::Sand if hot >= SAND_MELTS_AT && happens(params.melt_chance) => {
+                grid.cells[i] = Particle::Glass;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Melt);
+            }
+            Particle::Snow | Particle::Ice
+                if hot >= melts_at(grid.cells[i]) && happens(params.melt_chance) =>
+            {
+                grid.cells[i] = Particle::Water;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Thaw);
+            }
+            Particle::Snow if load >= SNOW_COMPACTS_UNDER && happens(SNOW_COMPACT_CHANCE) => {
+                grid.cells[i] = Particle::Ice;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Compact);
+            }
+            Particle::Lava if hot < LAVA_SETS_AT => {
+                grid.cells[i] = Particle::Glass;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Set);
+            }
+            Particle::Steam if hot < STEAM_CONDENSES_AT && happens(STEAM_CONDENSE_CHANCE) => {
+                grid.cells[i] = Particle::Water;
+                grid.age[i] = 0;
+                grid.note_reaction(IVec2::new(x, y), Reaction::Condense);
+            }
+            Particle::Fire => {
+                // Flames stay as hot as they burn, however much heat they give off.
+                grid.temperature[i] = hot.max(Particle::Fire.thermal().painted_at);
+                if grid.get(x, y + 1) == Some(Particle::Air) && happens(FIRE_SMOKE_CHANCE) {
+                    grid.transmute(x, y + 1, Particle::Smoke);
+                }
+            }
+            particle
+                if particle.flammability() > 0.0
+                    && (hot >= IGNITES_AT || touches(grid, x, y, Particle::Fire))
+                    && happens(particle.flammability()) =>
+            {
+                // Fire burns at least as hot as it is painted, and nobody placed the flames.
+                grid.transmute(x, y, Particle::Fire);
+                grid.placed[i] = false;
+                grid.temperature[i] = hot.max(Particle::Fire.thermal().painted_at);
+                grid.note_reaction(IVec2::new(x, y), Reaction::Burn);
+            }
+            _ => {}
+        }
+        if grid.cells[i] != before {
+            grid.touch(i);
+        }
+    }
+}
+
+// Current flows out of every battery through the conductors connected to it: every one is charged
+// as much as the best path from a battery leaves it, worked out strongest first. Charged cells heat
+// up, set fire to what burns around them and split water at the electrodes, for `ticks` ticks.
+fn conduct(grid: &mut SimulationGrid, tick: u64, ticks: u32) {
+    grid.charge.fill(0);
+    let width = grid.width as usize;
+    // Cells waiting to pass on their charge, by how much they hold.
+    let mut waiting: Vec<Vec<usize>> = Vec::new();
+    for i in 0..grid.cells.len() {
+        if grid.cells[i] == Particle::Battery {
+            if waiting.is_empty() {
+                waiting.resize(BATTERY_CHARGE as usize + 1, Vec::new());
+            }
+            grid.charge[i] = BATTERY_CHARGE;
+            waiting[BATTERY_CHARGE as usize].push(i);
+        }
+    }
+    let mut charged = Vec::new();
+    for level in (1..waiting.len()).rev() {
+        while let Some(i) = waiting[level].pop() {
+            // Already reached with more by a better path.
+            if grid.charge[i] as usize != level {
+                continue;
+            }
+            charged.push(i);
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let Some(conductivity) = grid.get(nx, ny).map(|n| n.conductivity()) else {
+                    continue;
+                };
+                let n = grid.index(nx, ny);
+                if conductivity == 0.0 {
+                    continue;
+                }
+                let reaches = (level as f32 - CHARGE_DROP / conductivity).max(0.0) as u8;
+                if reaches > grid.charge[n] {
+                    grid.charge[n] = reaches;
+                    waiting[reaches as usize].push(n);
+                }
+            }
+        }
+    }
+
+    for i in charged {
+        let (x, y) = ((i % width) as i32, (i / width) as i32);
+        let (particle, level) = (grid.cells[i], grid.charge[i]);
+        let strength = level as f32 / BATTERY_CHARGE as f32;
+        let current = CURRENT_HEAT * strength * (1.0 - particle.conductivity()) * ticks as f32;
+        heat(grid, i, current);
+        let neighbours = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        for (nx, ny) in neighbours {
+            let Some(neighbour) = grid.get(nx, ny) else { continue };
+            let n = grid.index(nx, ny);
+            let burns = neighbour.flammability() > 0.0 || neighbour.blast_yield() > 0.0;
+            if level >= SPARK_CHARGE && burns {
+                grid.temperature[n] = grid.temperature[n].max(IGNITES_AT);
+                grid.touch(n);
+            }
+        }
+        let electrode = neighbours.into_iter().any(|(nx, ny)| {
+            let other = grid.get(nx, ny);
+            other.is_some_and(|p| p != Particle::Water) && grid.charge[grid.index(nx, ny)] > 0
+        });
+        let chance = ELECTROLYSIS_CHANCE * strength * ticks as f32;
+        if particle == Particle::Water && electrode && roll(x, y, tick) < chance {
+            grid.transmute(x, y, Particle::Hydrogen);
+            grid.note_reaction(IVec2::new(x, y), Reaction::Electrolyze);
+        }
+    }
+}
+
+// Every explosive cell that is lit, by touching fire or being as hot as flammable cells ignite
+// at, goes off together with every explosive cell connected to it: the whole charge blows up at
+// once around its middle, with a crater whose radius grows with the square root of the charge's
+// yield. Explosives the blast heats enough go off on a later tick, so charges set each other off
+// in a chain.
+fn detonate(grid: &mut SimulationGrid, tick: u64) {
+    let width = grid.width as usize;
+    let mut visited: Option<Vec<bool>> = None;
+    let (mut charge, mut blasts) = (Vec::new(), Vec::new());
+    for start in 0..grid.cells.len() {
+        if grid.cells[start].blast_yield() == 0.0 {
+            continue;
+        }
+        let (x, y) = ((start % width) as i32, (start / width) as i32);
+        if grid.temperature[start] < IGNITES_AT && !touches(grid, x, y, Particle::Fire) {
+            continue;
+        }
+        let visited = visited.get_or_insert_with(|| vec![false; grid.cells.len()]);
+        if visited[start] {
+            continue;
+        }
+        charge.clear();
+        charge.push(start);
+        visited[start] = true;
+        let mut next = 0;
+        while let Some(&i) = charge.get(next) {
+            next += 1;
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let explosive = grid.get(nx, ny).is_some_and(|n| n.blast_yield() > 0.0);
+                if explosive && !visited[grid.index(nx, ny)] {
+                    visited[grid.index(nx, ny)] = true;
+                    charge.push(grid.index(nx, ny));
+                }
+            }
+        }
+        let (mut power, mut middle) = (0.0, Vec2::ZERO);
+        for &i in &charge {
+            power += grid.cells[i].blast_yield();
+            middle += CellPos::new((i % width) as i32, (i / width) as i32).center();
+            grid.transmute((i % width) as i32, (i / width) as i32, Particle::Air);
+        }
+        let radius = power.sqrt().clamp(1.0, MAX_BLAST_RADIUS);
+        blasts.push((middle / charge.len() as f32, radius));
+    }
+    for (center, radius) in blasts {
+        explode(grid, center, radius, tick);
+    }
+}
+
+// Blows a crater of `radius` cells around `center`: everything in it but bedrock turns into fire,
+// smoke or nothing, and water into steam, all of it hot. Around it, loose particles are flung
+// outwards and everything is heated, less the further out it is.
+fn explode(grid: &mut SimulationGrid, center: Vec2, radius: f32, tick: u64) {
+    let reach = radius * BLAST_REACH;
+    let min = (center - Vec2::splat(reach)).floor().as_ivec2();
+    let max = (center + Vec2::splat(reach)).ceil().as_ivec2();
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let Some(particle) = grid.get(x, y) else { continue };
+            let cell = CellPos::new(x, y).center();
+            let distance = cell.distance(center);
+            if distance > reach || particle == Particle::Bedrock {
+                continue;
+            }
+            let i = grid.index(x, y);
+            if distance <= radius {
+                let chance = roll(x, y, tick);
+                let becomes = match particle {
+                    Particle::Water => Particle::Steam,
+                    _ if chance < BLAST_FIRE => Particle::Fire,
+                    _ if chance < BLAST_FIRE + BLAST_SMOKE => Particle::Smoke,
+                    _ => Particle::Air,
+                };
+                grid.transmute(x, y, becomes);
+                grid.placed[i] = false;
+                grid.temperature[i] = grid.temperature[i].max(BLAST_HEAT);
+            } else {
+                let falloff = 1.0 - (distance - radius) / (reach - radius);
+                grid.temperature[i] += BLAST_HEAT * falloff;
+                let away = (cell - center).normalize_or(Vec2::Y);
+                grid.launch(x, y, (away * BLAST_SPEED * falloff).round().as_ivec2());
+            }
+        }
+    }
+    grid.note_reaction(center.floor().as_ivec2(), Reaction::Explode);
+}
+
+// Dissolved salt spreads between neighbouring water cells, by as much as `ticks` ticks would
+// have spread it.
+fn diffuse_salt(grid: &mut SimulationGrid, ticks: u32) {
+    let rate = compound(SALT_DIFFUSION, ticks);
+    let before = grid.data.clone();
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let i = grid.index(x, y);
+            if grid.cells[i] != Particle::Water {
+                continue;
+            }
+            // Only water holds salt; everything else neither gives nor takes any.
+            let neighbours = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                .map(|(nx, ny)| match grid.get(nx, ny) {
+                    Some(Particle::Water) => before[grid.index(nx, ny)] as f32,
+                    _ => before[i] as f32,
+                });
+            let mean = neighbours.iter().sum::<f32>() / 4.0;
+            let salt = before[i] as f32 + (mean - before[i] as f32) * rate;
+            grid.data[i] = salt.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// Salt touching unsaturated water dissolves into a cell of salt water, and saturated water beside
+// the tip of a crystal turns into crystal, drawing the salt it needs from the water around it.
+fn crystallize(grid: &mut SimulationGrid, tick: u64, ticks: u32) {
+    let (dissolves, grows) =
+        (compound(SALT_DISSOLVE_CHANCE, ticks), compound(CRYSTAL_GROWTH_CHANCE, ticks));
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let i = grid.index(x, y);
+            if !matches!(grid.cells[i], Particle::Salt | Particle::Water) {
+                continue;
+            }
+            // The water cells beside this one.
+            let wet = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].map(|(nx, ny)| {
+                (grid.get(nx, ny) == Some(Particle::Water)).then(|| grid.index(nx, ny))
+            });
+            let unsaturated = wet.iter().flatten().any(|&n| grid.data[n] < SALT_SATURATION);
+            match grid.cells[i] {
+                Particle::Salt if unsaturated && roll(x, y, !tick) < dissolves => {
+                    grid.cells[i] = Particle::Water;
+                    grid.data[i] = SALT_PER_GRAIN;
+                    grid.age[i] = 0;
+                    grid.touch(i);
+                    grid.note_reaction(IVec2::new(x, y), Reaction::Dissolve);
+                }
+                Particle::Water if grid.data[i] >= SALT_SATURATION && roll(x, y, !tick) < grows => {
+                    let crystals = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+                        .filter(|&(nx, ny)| grid.get(nx, ny) == Some(Particle::Crystal))
+                        .count();
+                    if !(1..=2).contains(&crystals) {
+                        continue;
+                    }
+                    grid.cells[i] = Particle::Crystal;
+                    grid.data[i] = 0;
+                    grid.age[i] = 0;
+                    grid.touch(i);
+                    grid.note_reaction(IVec2::new(x, y), Reaction::Crystallize);
+                    for n in wet.into_iter().flatten() {
+                        grid.data[n] = grid.data[n].saturating_sub(SALT_PER_CRYSTAL);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Every particle but air grows `ticks` ticks older.
+fn age_particles(grid: &mut SimulationGrid, ticks: u32) {
+    let ticks = ticks.min(u16::MAX as u32) as u16;
+    let cells = grid.cells.iter().zip(grid.age.iter_mut());
+    for (_, age) in cells.filter(|(p, _)| **p != Particle::Air) {
+        *age = age.saturating_add(ticks);
+    }
+}
+
+// The ways the built-in rules turn `particle` into something else, in words, for the material
+// reference. Kept beside `react` and read from the same constants, so the two agree.
+pub fn builtin_changes(particle: Particle) -> Vec<String> {
+    let mut changes = Vec::new();
+    match particle {
+        Particle::Water => {
+            changes.push(format!(
+                "Boils into steam from {} C, or into salt once saturated",
+                WATER_BOILS_AT
+            ));
+            changes.push("Takes in salt it touches, and grows crystals once saturated".to_string());
+        }
+        Particle::Sand => changes.push(format!("Melts into glass from {} C", SAND_MELTS_AT)),
+        Particle::Snow => {
+            changes.push(format!("Melts into water from {} C", SNOW_MELTS_AT));
+            changes.push(format!("Compacts into ice under {} cells", SNOW_COMPACTS_UNDER));
+        }
+        Particle::Ice => changes.push(format!("Melts into water from {} C", ICE_MELTS_AT)),
+        Particle::Lava => {
+            changes.push(format!("Sets into obsidian glass below {} C", LAVA_SETS_AT))
+        }
+        Particle::Steam => {
+            changes.push(format!("Condenses into water below {} C", STEAM_CONDENSES_AT))
+        }
+        Particle::Salt => changes.push("Dissolves into unsaturated water it touches".to_string()),
+        Particle::Crystal => changes.push("Grows into saturated water at its tips".to_string()),
+        Particle::Fire => changes.push("Puffs smoke into the air above it".to_string()),
+        _ => {}
+    }
+    if particle.flammability() > 0.0 {
+        changes.push(format!(
+            "Catches fire touching fire or from {} C ({:.0}% a tick)",
+            IGNITES_AT,
+            particle.flammability() * 100.0
+        ));
+    }
+    if let Some((ticks, product)) = particle.lifetime() {
+        changes.push(format!("Turns into {:?} after {} ticks", product, ticks));
+    }
+    if let Some(decay) = particle.decay() {
+        changes.push(format!(
+            "Decays into {:?} (half-life {} ticks), heating what its radiation reaches",
+            decay.product, decay.half_life
+        ));
+    }
+    changes
+}
+
+fn melts_at(particle: Particle) -> f32 {
+    if particle == Particle::Ice { ICE_MELTS_AT } else { SNOW_MELTS_AT }
+}
+
+// How many cells rest on each cell: the height of the unbroken stack of non-air cells above it.
+fn overburden(grid: &SimulationGrid) -> Vec<u32> {
+    let mut load = vec![0; grid.cells.len()];
+    for x in 0..grid.width as i32 {
+        let mut above = 0;
+        for y in (0..grid.height as i32).rev() {
+            let i = grid.index(x, y);
+            load[i] = above;
+            above = if grid.cells[i] == Particle::Air { 0 } else { above + 1 };
+        }
+    }
+    load
+}
+
+// Hot cells soot up what is around them, water picks up dirt from dust, and water and the solids
+// and powders it touches even out their stains: dirty water leaves sediment behind and clean water
+// washes it off, carrying it along as it flows. All by as much as `ticks` ticks would have.
+fn stain(grid: &mut SimulationGrid, ticks: u32) {
+    let ticks = ticks.min(255) as u8;
+    let (soot, dirt) = (SOOT_PER_TICK.saturating_mul(ticks), DIRT_PER_TICK.saturating_mul(ticks));
+    let exchange = compound(STAIN_EXCHANGE, ticks as u32);
+    let before = grid.stain.clone();
+    let stainable = |p: Particle| matches!(p.class(), MaterialClass::Solid | MaterialClass::Powder);
+    for y in 0..grid.height as i32 {
+        for x in 0..grid.width as i32 {
+            let i = grid.index(x, y);
+            let cell = grid.cells[i];
+            let hot = grid.temperature[i] >= SOOT_TEMPERATURE;
+            if !hot && cell != Particle::Water {
+                continue;
+            }
+            for (nx, ny) in [(x, y), (x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                let Some(neighbour) = grid.get(nx, ny) else { continue };
+                let n = grid.index(nx, ny);
+                if hot && stainable(neighbour) {
+                    grid.stain[n] = grid.stain[n].saturating_add(soot);
+                }
+                if cell != Particle::Water || n == i {
+                    continue;
+                }
+                if neighbour == Particle::Dust {
+                    grid.stain[i] = grid.stain[i].saturating_add(dirt);
+                } else if stainable(neighbour) {
+                    let gap = before[i] as f32 - before[n] as f32;
+                    let moved = (gap * exchange / 2.0).round() as i32;
+                    grid.stain[i] = (grid.stain[i] as i32 - moved).clamp(0, 255) as u8;
+                    grid.stain[n] = (grid.stain[n] as i32 + moved).clamp(0, 255) as u8;
+                }
+            }
+        }
+    }
+}
+
+// A per-tick fraction or chance applied over `ticks` ticks at once.
+pub fn compound(per_tick: f32, ticks: u32) -> f32 {
+    1.0 - (1.0 - per_tick.clamp(0.0, 1.0)).powi(ticks as i32)
+}
+
+// Every turbine's signal decays a little and grows with the liquid that flowed through it.
+fn spin_turbines(grid: &mut SimulationGrid, flow: &[u8]) {
+    let turbines = grid.cells.iter().zip(grid.data.iter_mut()).zip(flow);
+    for ((_, signal), flow) in turbines.filter(|((p, _), _)| **p == Particle::Turbine) {
+        *signal = (*signal - signal.div_ceil(TURBINE_DECAY))
+            .saturating_add(flow.saturating_mul(TURBINE_GAIN));
+    }
+}
+
+// Powders fall straight down, else slide diagonally, sinking through water as they go. Snow and
+// dust float on water instead, and snow slides less often.
+fn powder_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    gravity: f32,
+) -> Option<(i32, i32)> {
+    let particle = grid.cells[grid.index(x, y)];
+    let snow = particle == Particle::Snow;
+    let sinks_into = |other| displaces(particle, other);
+    let free = |tx, ty| grid.get(tx, ty).is_some_and(sinks_into).then_some((tx, ty));
+    let fall = fall_reach(gravity, x, y, tick);
+    if fall == 0 {
+        return None;
+    }
+    let dir = side(x, y, tick);
+    // Rolled apart from the fall, which uses this cell's roll for the tick already.
+    let slides = !snow || roll(x, y, !tick) < SNOW_SLIDE_CHANCE;
+
+    travel(fall, (x, y), |cx, cy| free(cx, cy - 1))
+        .or_else(|| slides.then(|| free(x + dir, y - 1)).flatten())
+        .or_else(|| slides.then(|| free(x - dir, y - 1)).flatten())
+}
+
+// Grains fall and slide like any powder, and keep going the way they last slid over slippery
+// ground: a grain that can't fall or slide skates on sideways with the chance its surface's
+// friction leaves, and stops at the first roll that fails or against anything in its way. The way
+// it is going is kept in its state byte, 1 for right and 2 for left.
+fn grain_target(
+    grid: &mut SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    gravity: f32,
+) -> Option<(i32, i32)> {
+    let index = grid.index(x, y);
+    if let Some((tx, ty)) = powder_target(grid, x, y, tick, gravity) {
+        if tx != x {
+            grid.data[index] = if tx > x { 1 } else { 2 };
+        }
+        return Some((tx, ty));
+    }
+    let heading = match grid.data[index] {
+        1 => 1,
+        2 => -1,
+        _ => return None,
+    };
+    let below = grid.get(x, y - 1).unwrap_or(Particle::Bedrock);
+    let wet = touches(grid, x, y - 1, Particle::Water);
+    let friction = below.friction() * if wet { WET_FRICTION } else { 1.0 };
+    let (grain, target) = (grid.cells[index], (x + heading, y));
+    let open = grid.get(target.0, target.1).is_some_and(|other| displaces(grain, other));
+    // Rolled apart from the fall and the slide, which use this cell's rolls for the tick already.
+    if open && roll(x, y, tick.rotate_left(16)) >= friction {
+        return Some(target);
+    }
+    grid.data[index] = 0;
+    None
+}
+
+// Settled dust behaves like a light powder, and the wind may lift it when it lies in the open.
+// Airborne dust flies with its velocity plus the wind through air, loses speed to drag and
+// settles once it runs out of speed or into anything.
+fn drift_dust(
+    grid: &mut SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+) -> Option<(i32, i32)> {
+    let index = grid.index(x, y);
+    let mut velocity = decode_velocity(grid.data[index]);
+    // Rolled apart from each other and from the fall, which uses this cell's roll for the tick.
+    let (lift_roll, drag_roll) = (roll(x, y, !tick), roll(x, y, tick.rotate_left(32)));
+    if velocity == IVec2::ZERO {
+        let open = grid.get(x, y + 1) == Some(Particle::Air);
+        if !open || lift_roll >= params.wind.abs() * DUST_LIFT_CHANCE {
+            return powder_target(grid, x, y, tick, params.gravity * DUST_FALL);
+        }
+        velocity = IVec2::new(params.wind.signum() as i32, 1);
+    }
+
+    if drag_roll < DUST_DRAG {
+        velocity -= velocity.signum();
+    }
+    let gust = fall_reach(params.wind.abs(), x, y, tick) as i32 * params.wind.signum() as i32;
+    let start = IVec2::new(x, y);
+    let (mut at, mut remaining) = (start, velocity + IVec2::new(gust, 0));
+    while remaining != IVec2::ZERO {
+        let step = remaining.signum();
+        if grid.get(at.x + step.x, at.y + step.y) != Some(Particle::Air) {
+            velocity = IVec2::ZERO;
+            break;
+        }
+        at += step;
+        remaining -= step;
+    }
+    grid.data[index] = encode_velocity(velocity);
+    (at != start).then_some((at.x, at.y))
+}
+
+// The pull of every magnet on every cell, or nothing if there are no magnets. Each magnet cell adds
+// the same precomputed patch of pulls around itself, so this costs magnets times the patch size,
+// not magnets times iron, and only runs again after a magnet is placed or removed.
+fn magnet_field(grid: &SimulationGrid) -> Vec<Vec2> {
+    let magnets = grid.cells.iter().filter(|&&p| p == Particle::Magnet).count();
+    if magnets == 0 {
+        return Vec::new();
+    }
+    let mut patch = Vec::new();
+    for dy in -MAGNET_REACH..=MAGNET_REACH {
+        for dx in -MAGNET_REACH..=MAGNET_REACH {
+            let offset = IVec2::new(dx, dy).as_vec2();
+            let distance = offset.length();
+            if distance > 0.0 && distance <= MAGNET_REACH as f32 {
+                // Points from the cell at the offset back towards the magnet.
+                patch.push((IVec2::new(dx, dy), -offset / distance * MAGNET_STRENGTH / distance));
+            }
+        }
+    }
+
+    let mut field = vec![Vec2::ZERO; grid.cells.len()];
+    let width = grid.width as usize;
+    for (i, _) in grid.cells.iter().enumerate().filter(|(_, p)| **p == Particle::Magnet) {
+        let magnet = IVec2::new((i % width) as i32, (i / width) as i32);
+        for &(offset, pull) in &patch {
+            let cell = magnet + offset;
+            if grid.in_bounds(cell.x, cell.y) {
+                field[grid.index(cell.x, cell.y)] += pull;
+            }
+        }
+    }
+    field
+}
+
+// Iron powder steps towards the magnets pulling on it, with a chance that grows with the pull, and
+// falls like sand otherwise. Where the pull is strong enough it clings in place even when its way
+// is blocked, so iron piles up around magnets in clumps and whiskers.
+fn iron_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+    pull: Vec2,
+) -> Option<(i32, i32)> {
+    let strength = pull.length();
+    if roll(x, y, !tick) >= strength / MAGNET_HOLD {
+        return powder_target(grid, x, y, tick, params.gravity);
+    }
+    let step = (pull / strength).round().as_ivec2();
+    let (tx, ty) = (x + step.x, y + step.y);
+    matches!(grid.get(tx, ty), Some(Particle::Air | Particle::Water)).then_some((tx, ty))
+}
+
+// Foam bubbles up through water one cell per tick until its lifetime runs out.
+fn rise_foam(grid: &SimulationGrid, x: i32, y: i32) -> Option<(i32, i32)> {
+    (grid.get(x, y + 1) == Some(Particle::Water)).then_some((x, y + 1))
+}
+
+// Airborne velocities are packed into a state byte as two signed nibbles, x in the low one; each
+// axis holds -8..=7 cells per tick, and zero means the particle has settled.
+fn encode_velocity(velocity: IVec2) -> u8 {
+    let v = velocity.clamp(IVec2::splat(-8), IVec2::splat(7));
+    (v.x as u8 & 0x0F) | (v.y as u8) << 4
+}
+
+fn decode_velocity(data: u8) -> IVec2 {
+    IVec2::new(((data << 4) as i8 >> 4) as i32, (data as i8 >> 4) as i32)
+}
+
+// A particle at (x, y) that is flying, water squirted out under pressure or anything loose flung
+// by a blast, moves along its velocity a cell at a time until something stops it, and comes down
+// a cell per tick faster every tick while losing a cell per tick of its sideways speed. It lands,
+// and falls or flows like the rest of its kind again, once it runs into something or is only
+// falling.
+fn fly(grid: &mut SimulationGrid, x: i32, y: i32) -> Option<(i32, i32)> {
+    let index = grid.index(x, y);
+    let velocity = grid.velocity[index].as_ivec2();
+    if velocity == IVec2::ZERO {
+        return None;
+    }
+    let (particle, start) = (grid.cells[index], IVec2::new(x, y));
+    let steps = velocity.abs().max_element();
+    let mut at = start;
+    for step in 1..=steps {
+        let next = start + velocity * step / steps;
+        if grid.get(next.x, next.y).is_none_or(|other| !displaces(particle, other)) {
+            break;
+        }
+        at = next;
+    }
+    let slowed = IVec2::new(velocity.x - velocity.x.signum(), velocity.y - 1);
+    grid.velocity[index] = if at != start + velocity || (slowed.x == 0 && slowed.y <= 0) {
+        I8Vec2::ZERO
+    } else {
+        slowed.max(IVec2::splat(-MAX_SQUIRT)).as_i8vec2()
+    };
+    (at != start).then_some((at.x, at.y))
+}
+
+// Water and oil are all but incompressible, so a body of either that is still awake moves
+// liquid from its highest open surface to its lowest openings, up to MAX_LEVEL_MOVES cells a
+// tick, wherever that is at least two cells lower: levels even out across the body, rise up the
+// far side of U-bends and drain out of holes instead of standing in staircases. Every cell of
+// the body notes the pressure it is under on the way, and liquid pushed out sideways through a
+// hole under enough head squirts out of it. Droplets of a cohesive liquid keep their shape.
+fn level_liquids(grid: &mut SimulationGrid, tick: u64, local: &LocalParams) {
+    let width = grid.width as usize;
+    let freezes = grid.tags.freezes();
+    // Ties go to the left on even ticks and to the right on odd ones, like the scan.
+    let order = |i: usize| if tick.is_multiple_of(2) { i % width } else { width - i % width };
+    let open = |grid: &SimulationGrid, x: i32, y: i32| {
+        grid.get(x, y).is_some_and(|other| other.class() == MaterialClass::Gas)
+    };
+    let mut visited = vec![false; grid.cells.len()];
+    let (mut body, mut surface, mut openings) = (Vec::new(), Vec::new(), Vec::new());
+    for start in 0..grid.cells.len() {
+        let liquid = grid.cells[start];
+        if visited[start] || !matches!(liquid, Particle::Water | Particle::Oil) {
+            continue;
+        }
+        if !grid.awake((start % width) as i32, (start / width) as i32) {
+            continue;
+        }
+        body.clear();
+        body.push(start);
+        visited[start] = true;
+        let mut next = 0;
+        while let Some(&i) = body.get(next) {
+            next += 1;
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if grid.get(nx, ny) == Some(liquid) && !visited[grid.index(nx, ny)] {
+                    visited[grid.index(nx, ny)] = true;
+                    body.push(grid.index(nx, ny));
+                }
+            }
+        }
+
+        surface.clear();
+        surface.extend(body.iter().copied().filter(|&i| {
+            open(grid, (i % width) as i32, (i / width) as i32 + 1)
+        }));
+        // A sealed body is only under its own weight.
+        let highest = |cells: &[usize]| cells.iter().map(|&i| (i / width) as i32).max();
+        let top = highest(&surface).or(highest(&body)).unwrap_or(0);
+        for &i in &body {
+            grid.pressure[i] = (top - (i / width) as i32).max(0) as u16;
+        }
+        let (x, y) = ((start % width) as i32, (start / width) as i32);
+        let cohesive = local.at(x, y, liquid).cohesion > 0.0;
+        if cohesive && body.len() < DROPLET_SIZE as usize {
+            continue;
+        }
+
+        openings.clear();
+        for &i in &body {
+            let (x, y) = ((i % width) as i32, (i / width) as i32);
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                if y + dy <= top - 2 && open(grid, x + dx, y + dy) {
+                    openings.push((grid.index(x + dx, y + dy), dx));
+                }
+            }
+        }
+        surface.sort_unstable_by_key(|&i| (std::cmp::Reverse(i / width), order(i)));
+        openings.sort_unstable_by_key(|&(i, _)| (i / width, order(i), i));
+        openings.dedup_by_key(|&mut (i, _)| i);
+        for (&from, &(to, dx)) in surface.iter().zip(&openings).take(MAX_LEVEL_MOVES) {
+            let head = (from / width) as i32 - (to / width) as i32;
+            let frozen = freezes && (grid.tags.is_frozen(from) || grid.tags.is_frozen(to));
+            if head < 2 || frozen {
+                break;
+            }
+            grid.swap(from, to);
+            let speed = (head / SQUIRT_HEAD).min(MAX_SQUIRT);
+            if dx != 0 && speed > 0 {
+                grid.velocity[to] = I8Vec2::new((dx * speed) as i8, 0);
+            }
+        }
+    }
+}
+
+// Water falls, else slides diagonally, else spreads sideways, passing straight through turbines
+// and sinking through lighter liquids. With a cohesion, a droplet's cells hold back from sliding
+// and spreading to where they would touch less of it, like goo's, so it stays a bead; they still
+// fall freely, but stick a while to a solid above or beside them first.
+fn liquid_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+) -> Option<(i32, i32)> {
+    let dir = side(x, y, tick);
+    let fall = fall_reach(params.gravity, x, y, tick);
+    let falls = fall > 0;
+    let liquid = grid.cells[grid.index(x, y)];
+    let flow = |cx, cy, dx, dy| flow_target(grid, liquid, cx, cy, dx, dy);
+
+    let cohesion = params.cohesion.clamp(0.0, 1.0);
+    let nearby = if cohesion > 0.0 { droplet_size(grid, x, y, liquid) } else { DROPLET_SIZE };
+    let droplet = nearby < DROPLET_SIZE;
+    let breakaway = roll(x, y, !tick);
+    if droplet && clings(grid, x, y, nearby, cohesion, breakaway) {
+        return None;
+    }
+    let here = if droplet { neighbours(grid, x, y, liquid, (x, y)) } else { 0 };
+    let spreads = |(tx, ty): &(i32, i32)| {
+        let lost = here.saturating_sub(neighbours(grid, *tx, *ty, liquid, (x, y)));
+        !droplet || breakaway < (1.0 - cohesion).powi(lost as i32)
+    };
+    let slide = |dx| falls.then(|| flow(x, y, dx, -1)).flatten().filter(spreads);
+    let spread = |dx| travel(params.dispersion, (x, y), |cx, cy| flow(cx, cy, dx, 0));
+
+    travel(fall, (x, y), |cx, cy| flow(cx, cy, 0, -1))
+        .or_else(|| slide(dir))
+        .or_else(|| slide(-dir))
+        .or_else(|| spread(dir).filter(spreads))
+        .or_else(|| spread(-dir).filter(spreads))
+}
+
+// How many other cells of `liquid` are within two cells of (x, y), up to DROPLET_SIZE.
+fn droplet_size(grid: &SimulationGrid, x: i32, y: i32, liquid: Particle) -> u32 {
+    let mut count = 0;
+    for dy in -2..=2 {
+        for dx in -2..=2 {
+            count += ((dx, dy) != (0, 0) && grid.get(x + dx, y + dy) == Some(liquid)) as u32;
+        }
+    }
+    count.min(DROPLET_SIZE)
+}
+
+// Whether a droplet's cell at (x, y), with `nearby` cells of the droplet around it, stays stuck
+// to a solid above or beside it this tick. A lone cell clings with nearly all of `cohesion` as its
+// chance, and the more of the droplet gathers around it the less it does, so drops grow on a
+// ceiling until they are heavy enough to drip.
+fn clings(grid: &SimulationGrid, x: i32, y: i32, nearby: u32, cohesion: f32, roll: f32) -> bool {
+    let stuck = [(x - 1, y), (x + 1, y), (x, y + 1)]
+        .into_iter()
+        .any(|(nx, ny)| grid.get(nx, ny).is_some_and(|p| p.class() == MaterialClass::Solid));
+    stuck && roll < cohesion * (1.0 - (nearby + 1) as f32 / DROPLET_SIZE as f32)
+}
+
+// Lava flows like water, only on a share of ticks, so it creeps along and piles up a little.
+fn lava_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+) -> Option<(i32, i32)> {
+    if roll(x, y, !tick) >= LAVA_FLOW_CHANCE {
+        return None;
+    }
+    liquid_target(grid, x, y, tick, params)
+}
+
+// Steam and hydrogen rise, else drift diagonally upwards, else wander sideways, bubbling up
+// through water as well as air.
+fn steam_target(grid: &SimulationGrid, x: i32, y: i32, tick: u64) -> Option<(i32, i32)> {
+    let dir = side(x, y, tick);
+    let open = |(dx, dy): (i32, i32)| {
+        let (tx, ty) = (x + dx, y + dy);
+        matches!(grid.get(tx, ty), Some(Particle::Air | Particle::Water)).then_some((tx, ty))
+    };
+    [(0, 1), (dir, 1), (-dir, 1), (dir, 0)].into_iter().find_map(open)
+}
+
+// Fire clings to what it is burning, and otherwise flickers upwards like a gas.
+fn fire_target(grid: &SimulationGrid, x: i32, y: i32, tick: u64) -> Option<(i32, i32)> {
+    let fuelled = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+        .into_iter()
+        .any(|(nx, ny)| grid.get(nx, ny).is_some_and(|p| p.flammability() > 0.0));
+    if fuelled {
+        return None;
+    }
+    gas_target(grid, x, y, tick, FIRE_BUOYANCY, FIRE_DISPERSION)
+}
+
+// Fire and smoke rise through air on the share of ticks their `buoyancy` gives, straight up or else
+// diagonally, and otherwise wander up to `dispersion` cells sideways, so they billow out under
+// ceilings.
+fn gas_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    buoyancy: f32,
+    dispersion: u32,
+) -> Option<(i32, i32)> {
+    let dir = side(x, y, tick);
+    let open = |(tx, ty): (i32, i32)| (grid.get(tx, ty) == Some(Particle::Air)).then_some((tx, ty));
+    let rises = roll(x, y, !tick) < buoyancy;
+    rises
+        .then(|| [(x, y + 1), (x + dir, y + 1), (x - dir, y + 1)].into_iter().find_map(open))
+        .flatten()
+        .or_else(|| travel(dispersion, (x, y), |cx, cy| open((cx + dir, cy))))
+}
+
+// Goo flows like a slow liquid, a cell at a time, but weighs every move by how many goo cells it
+// would touch afterwards (of its eight neighbours, not counting the spot it leaves). Moves that
+// keep or gain neighbours are free, so cells on a blob's surface crawl around it and blobs round
+// out and join up when they meet; moves that lose neighbours are held back by cohesion, so a blob
+// sags slowly, and a drip hanging off it stretches until it breaks away.
+fn goo_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+) -> Option<(i32, i32)> {
+    let dir = side(x, y, tick);
+    let falls = fall_reach(params.gravity, x, y, tick) > 0;
+    let here = neighbours(grid, x, y, Particle::Goo, (x, y));
+    let breakaway = roll(x, y, !tick);
+    let free = |(dx, dy): (i32, i32)| {
+        let (tx, ty) = (x + dx, y + dy);
+        if !grid.get(tx, ty).is_some_and(|other| displaces(Particle::Goo, other)) {
+            return None;
+        }
+        let lost = here.saturating_sub(neighbours(grid, tx, ty, Particle::Goo, (x, y)));
+        (breakaway < (1.0 - GOO_COHESION).powi(lost as i32)).then_some((tx, ty))
+    };
+    let falling = [(0, -1), (dir, -1), (-dir, -1)];
+    falls
+        .then(|| falling.into_iter().find_map(free))
+        .flatten()
+        .or_else(|| [(dir, 0), (-dir, 0)].into_iter().find_map(free))
+}
+
+// How many of the eight cells around (x, y) hold `particle`, leaving out `except`.
+fn neighbours(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    particle: Particle,
+    except: (i32, i32),
+) -> u32 {
+    let mut count = 0;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let (nx, ny) = (x + dx, y + dy);
+            let neighbour = (dx, dy) != (0, 0) && (nx, ny) != except;
+            count += (neighbour && grid.get(nx, ny) == Some(particle)) as u32;
+        }
+    }
+    count
+}
+
+// Keeps rope links true before anything moves. A link only holds while the cell it points to is
+// rope that links back, so a cell cut out or burnt frees the cells on both sides of it. A rope cell
+// left with no links at all ties itself to the rope cells around it that have a link to spare,
+// which strings freshly painted rope into chains and lets a loose piece catch on rope it lands on.
+fn tie_ropes(grid: &mut SimulationGrid) {
+    let width = grid.width as usize;
+    for i in 0..grid.cells.len() {
+        if grid.cells[i] != Particle::Rope {
+            continue;
+        }
+        let (x, y) = ((i % width) as i32, (i / width) as i32);
+        let held = rope_links(grid.data[i])
+            .filter(|&(dx, dy)| links_to(grid, (x + dx, y + dy), (-dx, -dy)))
+            .fold(0, |links, offset| links | link_bit(offset));
+        grid.data[i] = held;
+        if held != 0 {
+            continue;
+        }
+        for (dx, dy) in ROPE_LINKS {
+            let (nx, ny) = (x + dx, y + dy);
+            let spare = grid.data[i].count_ones() < MAX_ROPE_LINKS;
+            if !spare || grid.get(nx, ny) != Some(Particle::Rope) {
+                continue;
+            }
+            let neighbour = grid.index(nx, ny);
+            if grid.data[neighbour].count_ones() < MAX_ROPE_LINKS {
+                grid.data[i] |= link_bit((dx, dy));
+                grid.data[neighbour] |= link_bit((-dx, -dy));
+            }
+        }
+    }
+}
+
+// Rope falls a cell at a time like a grain, but held by its links: it only moves where it stays
+// next to every rope cell it is linked to, so rope tied at one end hangs from it and rope tied at
+// both sags between them. A cell that can't drop sways sideways to get under the cell it hangs
+// from, which swings slanted rope down until it hangs straight. Rope touching a solid is tied to
+// it and doesn't move at all.
+fn rope_target(
+    grid: &SimulationGrid,
+    x: i32,
+    y: i32,
+    tick: u64,
+    params: &SimParams,
+) -> Option<(i32, i32)> {
+    let solid = |dx, dy| grid.get(x + dx, y + dy).map(|p| p.class()) == Some(MaterialClass::Solid);
+    let tied = (-1..=1).any(|dy| (-1..=1).any(|dx| solid(dx, dy)));
+    if tied || fall_reach(params.gravity, x, y, tick) == 0 {
+        return None;
+    }
+    let links: Vec<(i32, i32)> =
+        rope_links(grid.data[grid.index(x, y)]).map(|(dx, dy)| (x + dx, y + dy)).collect();
+    let free = |(dx, dy): (i32, i32)| {
+        let (tx, ty) = (x + dx, y + dy);
+        let open = matches!(grid.get(tx, ty), Some(Particle::Air | Particle::Water));
+        let linked = links.iter().all(|&(lx, ly)| (lx - tx).abs() <= 1 && (ly - ty).abs() <= 1);
+        (open && linked).then_some((tx, ty))
+    };
+    let hangs_from = links.iter().copied().filter(|&(_, ly)| ly > y).max_by_key(|&(_, ly)| ly);
+    let sways = |&(dx, _): &(i32, i32)| {
+        hangs_from.is_some_and(|(hx, _)| (x + dx - hx).abs() < (x - hx).abs())
+    };
+    let dir = side(x, y, tick);
+    [(0, -1), (dir, -1), (-dir, -1)]
+        .into_iter()
+        .find_map(free)
+        .or_else(|| [(dir, 0), (-dir, 0)].into_iter().filter(sways).find_map(free))
+}
+
+// Points the links of a rope cell that moved from `from` to `to`, and the links back to it, at
+// where it is now.
+fn move_rope_links(grid: &mut SimulationGrid, from: (i32, i32), to: (i32, i32)) {
+    let moved = grid.index(to.0, to.1);
+    let mut links = 0;
+    for (dx, dy) in rope_links(grid.data[moved]) {
+        let (nx, ny) = (from.0 + dx, from.1 + dy);
+        if !grid.in_bounds(nx, ny) {
+            continue;
+        }
+        let neighbour = grid.index(nx, ny);
+        grid.data[neighbour] =
+            grid.data[neighbour] & !link_bit((-dx, -dy)) | link_bit((to.0 - nx, to.1 - ny));
+        links |= link_bit((nx - to.0, ny - to.1));
+    }
+    grid.data[moved] = links;
+}
+
+// The offsets to the cells a rope cell's state byte links it to.
+fn rope_links(links: u8) -> impl Iterator<Item = (i32, i32)> {
+    ROPE_LINKS.into_iter().enumerate().filter(move |(d, _)| links & (1 << d) != 0).map(|(_, o)| o)
+}
+
+// The bit of a rope cell's state byte that links it to the neighbour `offset` away; none for
+// cells that aren't neighbours.
+fn link_bit(offset: (i32, i32)) -> u8 {
+    ROPE_LINKS.iter().position(|&o| o == offset).map_or(0, |d| 1 << d)
+}
+
+// Whether the cell at `cell` is rope linked to its neighbour `offset` away.
+fn links_to(grid: &SimulationGrid, (x, y): (i32, i32), offset: (i32, i32)) -> bool {
+    grid.get(x, y) == Some(Particle::Rope) && grid.data[grid.index(x, y)] & link_bit(offset) != 0
+}
+
+// How many cells the particle at (x, y) may fall this tick under `gravity`.
+fn fall_reach(gravity: f32, x: i32, y: i32, tick: u64) -> u32 {
+    let gravity = gravity.max(0.0);
+    gravity as u32 + (roll(x, y, tick) < gravity.fract()) as u32
+}
+
+// Takes up to `reach` moves from `start`, each one found by `next` from where the previous one
+// ended, and returns where the last one ended.
+fn travel(
+    reach: u32,
+    start: (i32, i32),
+    next: impl Fn(i32, i32) -> Option<(i32, i32)>,
+) -> Option<(i32, i32)> {
+    let mut end = None;
+    let mut at = start;
+    for _ in 0..reach {
+        let Some(to) = next(at.0, at.1) else { break };
+        end = Some(to);
+        at = to;
+    }
+    end
+}
+
+// The cell `liquid` at (x, y) takes the place of by moving in direction (dx, dy): the neighbour
+// itself, or the first cell past a run of turbines, if it gives way.
+fn flow_target(
+    grid: &SimulationGrid,
+    liquid: Particle,
+    x: i32,
+    y: i32,
+    dx: i32,
+    dy: i32,
+) -> Option<(i32, i32)> {
+    let (mut tx, mut ty) = (x + dx, y + dy);
+    while grid.get(tx, ty) == Some(Particle::Turbine) {
+        (tx, ty) = (tx + dx, ty + dy);
+    }
+    let other = grid.get(tx, ty)?;
+    displaces(liquid, other).then_some((tx, ty))
+}
+
+// Whether a powder or liquid moving into a cell of `other` swaps places with it: gases always make
+// way, and liquids do for anything denser, so it sinks through them and spreads out beneath them.
+fn displaces(particle: Particle, other: Particle) -> bool {
+    match other.class() {
+        MaterialClass::Gas => true,
+        MaterialClass::Liquid => other.density() < particle.density(),
+        MaterialClass::Solid | MaterialClass::Powder => false,
+    }
+}
+
+// Whether `particle` can be thrown through the air with a velocity kept alongside it: water, oil
+// and the powders that fall like grains. Dust keeps its velocity in its state byte instead.
+fn flies(particle: Particle) -> bool {
+    matches!(
+        particle,
+        Particle::Water
+            | Particle::Oil
+            | Particle::Sand
+            | Particle::Snow
+            | Particle::Salt
+            | Particle::Radium
+            | Particle::Lead
+            | Particle::IronPowder
+            | Particle::Gunpowder
+    )
+}
+
+// Whether any of the four neighbours of (x, y) is `particle`.
+fn touches(grid: &SimulationGrid, x: i32, y: i32, particle: Particle) -> bool {
+    [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+        .into_iter()
+        .any(|(nx, ny)| grid.get(nx, ny) == Some(particle))
+}
+
+// Picks which side (-1 or 1) a particle tries first, varying per cell and per tick.
+fn side(x: i32, y: i32, tick: u64) -> i32 {
+    if hash(x, y, tick) & 1 == 0 { 1 } else { -1 }
+}
+
+// A number in 0..1 for chance rolls, varying per cell and per tick.
+pub fn roll(x: i32, y: i32, tick: u64) -> f32 {
+    (hash(x, y, tick) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn hash(x: i32, y: i32, tick: u64) -> u64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ tick.wrapping_mul(0x1656_67B1_9E37_79F9);
+    h ^= h >> 29;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunks::SETTLE_TICKS;
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn cells_outside_the_grid_are_skipped_not_panicked_on() {
+        let mut grid = SimulationGrid::new(4, 4);
+        grid.set(0, 0, Particle::Sand);
+        assert!(!grid.swap_cells(CellPos::new(0, 0), CellPos::new(4, 0)));
+        assert!(!grid.swap_cells(CellPos::new(-1, 0), CellPos::new(0, 0)));
+        assert_eq!(grid.get(0, 0), Some(Particle::Sand));
+        assert!(grid.swap_cells(CellPos::new(0, 0), CellPos::new(3, 3)));
+        assert_eq!(grid.get(3, 3), Some(Particle::Sand));
+
+        let cell = grid.cell(3, 3).unwrap();
+        assert!(!grid.set_cell(9, 9, cell));
+        // Rectangles hanging over the edge are clipped to it.
+        let written = grid.fill_rect(CellPos::new(-2, -2), CellPos::new(1, 1), Particle::Water);
+        assert_eq!(written, 4);
+        let everywhere = (CellPos::new(-9, -9), CellPos::new(9, 9));
+        assert_eq!(grid.count_in_rect(Particle::Water, everywhere.0, everywhere.1), 4);
+    }
+
+    #[test]
+    fn chunks_wake_where_cells_are_written_and_asleep_ones_are_left_alone() {
+        let mut grid = SimulationGrid::new(128, 128);
+        for _ in 0..SETTLE_TICKS {
+            grid.activity.settle();
+        }
+        assert_eq!(grid.activity().awake().count(), 0);
+
+        grid.set(100, 100, Particle::Sand);
+        grid.activity.settle();
+        assert!(grid.awake(100, 100) && grid.awake(70, 70));
+        assert!(!grid.awake(10, 10));
+
+        // Heat left in an asleep chunk stays put until something wakes it.
+        let i = grid.index(10, 10);
+        grid.temperature[i] = 500.0;
+        let params = SimParams::default();
+        exchange_heat(&mut grid, &LocalParams::new(&params, &[], &[]), 1);
+        assert_eq!(grid.temperature[i], 500.0);
+    }
+
+    #[test]
+    fn low_memory_mode_saves_half_a_byte_a_cell() {
+        let mut grid = SimulationGrid::new(256, 256);
+        let cells = 256 * 256;
+        let unpacked = grid.memory_bytes();
+        assert_eq!(unpacked, cells * 17);
+        grid.set_low_memory(true);
+        // Half of every wall's byte, less the one-material palette.
+        let saved = unpacked - grid.memory_bytes();
+        assert_eq!(saved, cells / 2 - 1);
+    }
+
+    #[test]
+    fn the_tick_rate_sets_how_many_ticks_run_a_second() {
+        let mut world = World::new();
+        world.init_resource::<SimulationTickRate>();
+        world.init_resource::<SimulationControl>();
+        world.init_resource::<SimulationStats>();
+        world.insert_resource(Time::<Fixed>::default());
+        let ticks_per_second = |world: &mut World| {
+            world.run_system_once(pace_ticks).unwrap();
+            1.0 / world.resource::<Time<Fixed>>().timestep().as_secs_f64()
+        };
+        assert!((ticks_per_second(&mut world) - 60.0).abs() < 1e-3);
+
+        world.resource_mut::<SimulationTickRate>().ticks_per_second = 120.0;
+        assert!((ticks_per_second(&mut world) - 120.0).abs() < 1e-3);
+        // The speed control multiplies it, and a rate of 0 stops the world without touching it.
+        world.resource_mut::<SimulationControl>().speed = 0.5;
+        assert!((ticks_per_second(&mut world) - 60.0).abs() < 1e-3);
+        world.resource_mut::<SimulationTickRate>().ticks_per_second = 0.0;
+        assert!((ticks_per_second(&mut world) - 60.0).abs() < 1e-3);
+    }
+}
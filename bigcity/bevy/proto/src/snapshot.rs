@@ -0,0 +1,118 @@
+// --- IMPORTS ---
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::bodies::CellBodies;
+use crate::sim::SimulationGrid;
+use crate::loops::LoopBand;
+use crate::tags::CellTags;
+use crate::zones::{MaterialOverride, ParamZone};
+
+// --- SNAPSHOT ---
+
+// A compact, serializable copy of the grid: cells run-length encoded in grid order (row-major,
+// bottom row first), and so are their state bytes (mirror tilts and the like) and the walls behind
+// them unless there are none. Mostly-empty worlds stay small enough to embed in RON assets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldSnapshot {
+    pub width: u32,
+    pub height: u32,
+    pub runs: Vec<(Particle, u32)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub states: Vec<(u8, u32)>,
+    #[serde(default)]
+    pub zones: Vec<ParamZone>,
+    #[serde(default)]
+    pub loops: Vec<LoopBand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub materials: Vec<MaterialOverride>,
+    #[serde(default, skip_serializing_if = "CellTags::is_empty")]
+    pub tags: CellTags,
+    #[serde(default, skip_serializing_if = "CellBodies::is_empty")]
+    pub bodies: CellBodies,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backdrop: Vec<(Particle, u32)>,
+}
+
+impl WorldSnapshot {
+    pub fn from_grid(grid: &SimulationGrid) -> Self {
+        let mut states = run_lengths(grid.states());
+        if states.iter().all(|&(state, _)| state == 0) {
+            states.clear();
+        }
+        Self {
+            width: grid.width(),
+            height: grid.height(),
+            runs: run_lengths(grid.cells()),
+            states,
+            zones: grid.zones().to_vec(),
+            loops: grid.loops().to_vec(),
+            materials: grid.material_overrides().to_vec(),
+            tags: grid.tags().clone(),
+            bodies: grid.bodies().clone(),
+            backdrop: backdrop_runs(grid),
+        }
+    }
+
+    // Writes the snapshot into `grid`, anchored at the bottom left. Cells outside either one are
+    // dropped or left as air, so snapshots of a different size still load.
+    pub fn apply_to(&self, grid: &mut SimulationGrid) {
+        grid.clear();
+        let size = (self.width * self.height) as usize;
+        for (i, particle) in expand(&self.runs).take(size).enumerate() {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            grid.set(x as i32, y as i32, particle);
+        }
+        for (i, state) in expand(&self.states).take(size).enumerate() {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            grid.set_data(x as i32, y as i32, state);
+        }
+        for zone in &self.zones {
+            grid.add_zone(zone.clone());
+        }
+        for band in &self.loops {
+            grid.add_loop(band.clone());
+        }
+        grid.set_material_overrides(self.materials.clone());
+        grid.tags_mut().tags = self.tags.tags.clone();
+        for (i, tag) in self.tags.marks().filter(|&(i, _)| i < size) {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            grid.set_tag(x as i32, y as i32, Some(tag));
+        }
+        grid.bodies_mut().bodies = self.bodies.bodies.clone();
+        for (i, body) in self.bodies.marks().filter(|&(i, _)| i < size) {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            grid.set_body(x as i32, y as i32, Some(body));
+        }
+        for (i, wall) in expand(&self.backdrop).take(size).enumerate() {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            grid.set_backdrop(x as i32, y as i32, wall);
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// The walls behind `grid`'s cells, run-length encoded, or nothing if there are none.
+fn backdrop_runs(grid: &SimulationGrid) -> Vec<(Particle, u32)> {
+    let walls = grid.backdrops();
+    match walls.iter().all(|&wall| wall == Particle::Air) {
+        true => Vec::new(),
+        false => run_lengths(&walls),
+    }
+}
+
+fn run_lengths<T: Copy + PartialEq>(values: &[T]) -> Vec<(T, u32)> {
+    let mut runs: Vec<(T, u32)> = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some((run, count)) if *run == value => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    runs
+}
+
+fn expand<T: Copy>(runs: &[(T, u32)]) -> impl Iterator<Item = T> + '_ {
+    runs.iter().flat_map(|&(value, count)| std::iter::repeat_n(value, count as usize))
+}
@@ -0,0 +1,824 @@
+// --- IMPORTS ---
+use std::collections::HashMap;
+use std::io::{BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{
+    Receiver, Sender, SyncSender, TryRecvError, TrySendError, channel, sync_channel,
+};
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::coords::{CellPos, ChunkPos};
+use crate::levels::PaintRules;
+use crate::packed::PackedCells;
+use crate::player::PlayerInputSet;
+use crate::sim::{LowMemory, SimulationGrid, SimulationSet, ViewOnly};
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands, apply_world_commands};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+// Every message starts with these bytes, so a client can tell it reached a spectator host.
+const MAGIC: [u8; 4] = *b"JSP1";
+// ..and every brush stroke a player sends a server starts with these...
+const STROKE_MAGIC: [u8; 4] = *b"JPL1";
+// ..and the server's answer, saying how many of them the world it sent before has taken in.
+const APPLIED_MAGIC: [u8; 4] = *b"JSA1";
+// The most cells one stroke may carry; a server drops anyone sending more.
+const MAX_STROKE_CELLS: u32 = 1 << 16;
+// Spectators see the world this many times per second, however fast it runs.
+const SEND_INTERVAL_SECS: f32 = 0.1;
+// Messages queued for a spectator that can't keep up; past this it is dropped.
+const CLIENT_BACKLOG: usize = 32;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// A predicted cell the server hasn't answered for in this long goes back to the server's world.
+const PREDICTION_TIMEOUT_SECS: f32 = 2.0;
+// How long a cell the server painted differently from the prediction stays outlined.
+const CORRECTION_FLASH_SECS: f32 = 0.3;
+const CORRECTION_COLOR: Color = Color::srgb(1.0, 0.4, 0.3);
+const LABEL_COLOR: Color = Color::srgb(0.6, 0.8, 1.0);
+
+// --- PLUGIN ---
+
+// Streaming the world to others. `--host-spectators <port>` streams it to any number of
+// view-only clients; `--spectate <host:port>` starts as one of them. The host only sends the
+// chunks that changed since its last broadcast, run-length encoded, at a fixed low rate.
+// `--join <host:port>` plays on a dedicated server (see server.rs) instead: the world is the
+// server's as it streams in, as for a spectator, and this player's brush strokes are sent to the
+// server to be painted there. So painting doesn't lag a round trip behind the brush, they are
+// also painted here straight away as a prediction, which holds until the server says it has taken
+// the stroke in; then the cells become whatever the server made of them, outlined for a moment
+// where that differs.
+pub struct SpectatorPlugin;
+
+impl Plugin for SpectatorPlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1);
+        let mut role = None;
+        while let Some(arg) = args.next() {
+            match (arg.as_str(), args.next()) {
+                ("--host-spectators", Some(port)) => role = Some(Role::Host(port)),
+                ("--spectate", Some(address)) => role = Some(Role::Client(address)),
+                ("--join", Some(address)) => role = Some(Role::Player(address)),
+                _ => {}
+            }
+        }
+
+        match role {
+            Some(Role::Host(port)) => match SpectatorHost::bind(&port) {
+                Ok(host) => {
+                    info!("Hosting spectators on port {}", port);
+                    app.insert_resource(host)
+                        .add_systems(Startup, spawn_spectator_label)
+                        .add_systems(
+                            Update,
+                            (accept_spectators, broadcast_world, update_host_label)
+                                .chain()
+                                .after(SimulationSet),
+                        );
+                }
+                Err(err) => error!("Could not host spectators on port {}: {}", port, err),
+            },
+            Some(Role::Client(address)) => {
+                let client = SpectatorClient::connect(address, false);
+                // The world is the host's; nothing here steps or edits it.
+                app.insert_resource(client)
+                    .insert_resource(ViewOnly)
+                    .configure_sets(Update, PlayerInputSet.run_if(not(resource_exists::<ViewOnly>)))
+                    .add_systems(Startup, (spawn_spectator_label, protect_mirrored_world))
+                    .add_systems(
+                        Update,
+                        (receive_world, update_client_label).chain().before(SimulationSet),
+                    );
+            }
+            Some(Role::Player(address)) => {
+                let client = SpectatorClient::connect(address, true);
+                // The world steps on the server; the brush's strokes go there too.
+                app.insert_resource(client)
+                    .insert_resource(ViewOnly)
+                    .init_resource::<Prediction>()
+                    .add_systems(Startup, spawn_spectator_label)
+                    .add_systems(
+                        Update,
+                        (
+                            send_strokes.in_set(WorldCommandSet).before(apply_world_commands),
+                            (receive_world, update_client_label, draw_corrections)
+                                .chain()
+                                .before(SimulationSet),
+                        ),
+                    );
+            }
+            None => {}
+        }
+    }
+}
+
+enum Role {
+    Host(String),
+    Client(String),
+    Player(String),
+}
+
+// --- WIRE FORMAT ---
+
+// What a host sends: the world's size and the chunks that changed, or, to a player, that every
+// one of their strokes up to this one is in the world as of the update before.
+enum HostMessage {
+    World(UVec2, Vec<ChunkUpdate>),
+    Applied(u32),
+}
+
+// One chunk's cells, bottom row first, as (particle, count) runs. Chunks on the right and top
+// edges may be smaller than the rest.
+struct ChunkUpdate {
+    chunk: (u16, u16),
+    runs: Vec<(u8, u16)>,
+}
+
+// A message is MAGIC, then its length, the world size and the chunks it carries, all as little
+// endian integers: u32 length, u32 width, u32 height, u32 chunk count, then per chunk u16 x,
+// u16 y, u32 run count and the runs as u8 particle id, u16 count.
+fn encode(width: u32, height: u32, chunks: &[ChunkUpdate]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(width.to_le_bytes());
+    body.extend(height.to_le_bytes());
+    body.extend((chunks.len() as u32).to_le_bytes());
+    for update in chunks {
+        body.extend(update.chunk.0.to_le_bytes());
+        body.extend(update.chunk.1.to_le_bytes());
+        body.extend((update.runs.len() as u32).to_le_bytes());
+        for &(particle, count) in &update.runs {
+            body.push(particle);
+            body.extend(count.to_le_bytes());
+        }
+    }
+    let mut message = MAGIC.to_vec();
+    message.extend((body.len() as u32).to_le_bytes());
+    message.extend(body);
+    message
+}
+
+// A stroke's answer is APPLIED_MAGIC, then the stroke's number as a little endian u32.
+fn encode_applied(stroke: u32) -> Vec<u8> {
+    let mut message = APPLIED_MAGIC.to_vec();
+    message.extend(stroke.to_le_bytes());
+    message
+}
+
+fn decode(stream: &mut impl Read) -> std::io::Result<HostMessage> {
+    let bad = |what: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, what.to_string());
+    let mut magic = [0; 4];
+    stream.read_exact(&mut magic)?;
+    if magic == APPLIED_MAGIC {
+        return Ok(HostMessage::Applied(read_u32(stream)?));
+    }
+    if magic != MAGIC {
+        return Err(bad("not a spectator stream"));
+    }
+    let mut body = vec![0; read_u32(stream)? as usize];
+    stream.read_exact(&mut body)?;
+
+    let mut body = body.as_slice();
+    let size = UVec2::new(read_u32(&mut body)?, read_u32(&mut body)?);
+    let mut chunks = Vec::new();
+    for _ in 0..read_u32(&mut body)? {
+        let chunk = (read_u16(&mut body)?, read_u16(&mut body)?);
+        let mut runs = Vec::new();
+        for _ in 0..read_u32(&mut body)? {
+            let mut particle = [0];
+            body.read_exact(&mut particle)?;
+            runs.push((particle[0], read_u16(&mut body)?));
+        }
+        chunks.push(ChunkUpdate { chunk, runs });
+    }
+    Ok(HostMessage::World(size, chunks))
+}
+
+fn read_u32(stream: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u16(stream: &mut impl Read) -> std::io::Result<u16> {
+    let mut bytes = [0; 2];
+    stream.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+// Cells a player painted, sent to a server: `particle` with state byte `data` into `cells`.
+// Each player numbers their strokes from 1, so the server can tell them how far it got.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteStroke {
+    pub number: u32,
+    pub particle: Particle,
+    pub data: u8,
+    pub cells: Vec<(u16, u16)>,
+}
+
+// A stroke is STROKE_MAGIC, then u32 number, u8 particle id, u8 state byte, u32 cell count and
+// the cells as u16 x, u16 y, little endian.
+fn encode_stroke(stroke: &RemoteStroke) -> Vec<u8> {
+    let mut message = STROKE_MAGIC.to_vec();
+    message.extend(stroke.number.to_le_bytes());
+    message.push(stroke.particle.id());
+    message.push(stroke.data);
+    message.extend((stroke.cells.len() as u32).to_le_bytes());
+    for &(x, y) in &stroke.cells {
+        message.extend(x.to_le_bytes());
+        message.extend(y.to_le_bytes());
+    }
+    message
+}
+
+fn decode_stroke(stream: &mut impl Read) -> std::io::Result<RemoteStroke> {
+    let bad = |what: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, what.to_string());
+    let mut magic = [0; 4];
+    stream.read_exact(&mut magic)?;
+    if magic != STROKE_MAGIC {
+        return Err(bad("not a player's stroke"));
+    }
+    let number = read_u32(stream)?;
+    let mut header = [0; 2];
+    stream.read_exact(&mut header)?;
+    let particle = *Particle::ALL.get(header[0] as usize).ok_or_else(|| bad("no such material"))?;
+    let count = read_u32(stream)?;
+    if count > MAX_STROKE_CELLS {
+        return Err(bad("too many cells in one stroke"));
+    }
+    let cells = (0..count)
+        .map(|_| Ok((read_u16(stream)?, read_u16(stream)?)))
+        .collect::<std::io::Result<_>>()?;
+    Ok(RemoteStroke {
+        number,
+        particle,
+        data: header[1],
+        cells,
+    })
+}
+
+// --- RESOURCES ---
+
+// One connection to a host: what it is sent goes through its writer thread, and the stream is
+// kept to hang up on it. A player's last stroke taken in, and the last they were told about.
+struct Spectator {
+    id: u32,
+    address: SocketAddr,
+    sender: SyncSender<Arc<Vec<u8>>>,
+    stream: TcpStream,
+    applied: u32,
+    answered: u32,
+}
+
+// The strokes the reader threads hand over, with the id of who sent them: each thread sends on a
+// clone of `sender`, and the host takes them off `receiver`.
+struct StrokeInbox {
+    sender: Sender<(u32, RemoteStroke)>,
+    receiver: Mutex<Receiver<(u32, RemoteStroke)>>,
+}
+
+// The listening socket, every connected spectator and the world as last broadcast, which new
+// spectators receive whole before they get the changes on top of it. A host that takes strokes,
+// as a server does, also reads what every connection sends, on a thread each, and hands the
+// strokes over with the id of who sent them.
+#[derive(Resource)]
+pub struct SpectatorHost {
+    listener: TcpListener,
+    clients: Vec<Spectator>,
+    sent: Option<PackedCells>,
+    since_send: f32,
+    next_id: u32,
+    strokes: Option<StrokeInbox>,
+}
+
+impl SpectatorHost {
+    pub fn bind(port: &str) -> std::io::Result<Self> {
+        let port: u16 = port.parse().map_err(std::io::Error::other)?;
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            clients: Vec::new(),
+            sent: None,
+            since_send: SEND_INTERVAL_SECS,
+            next_id: 1,
+            strokes: None,
+        })
+    }
+
+    // Reads brush strokes from everyone who connects from now on.
+    pub fn taking_strokes(mut self) -> Self {
+        let (sender, receiver) = channel();
+        let receiver = Mutex::new(receiver);
+        self.strokes = Some(StrokeInbox { sender, receiver });
+        self
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |address| address.port())
+    }
+
+    // Who is connected, by id.
+    pub fn clients(&self) -> impl Iterator<Item = (u32, SocketAddr)> + '_ {
+        self.clients.iter().map(|client| (client.id, client.address))
+    }
+
+    // Takes everyone waiting to connect, starting them off with the world as everyone else last
+    // saw it, and returns who joined.
+    pub fn accept(&mut self, grid: &SimulationGrid, low_memory: bool) -> Vec<(u32, SocketAddr)> {
+        let mut joined = Vec::new();
+        loop {
+            let (stream, address) = match self.listener.accept() {
+                Ok(connection) => connection,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return joined,
+                Err(err) => {
+                    warn!("Could not accept a spectator: {}", err);
+                    return joined;
+                }
+            };
+            let (Ok(()), Ok(writer)) = (stream.set_nonblocking(false), stream.try_clone()) else {
+                continue;
+            };
+            let id = self.next_id;
+            self.next_id += 1;
+
+            // A writer thread per spectator, so a slow network never stalls the frame.
+            let (sender, messages) = sync_channel::<Arc<Vec<u8>>>(CLIENT_BACKLOG);
+            std::thread::spawn(move || {
+                let mut writer = writer;
+                for message in messages {
+                    if writer.write_all(&message).is_err() {
+                        return;
+                    }
+                }
+            });
+            if let Some(inbox) = &self.strokes {
+                let (strokes, reader) = (inbox.sender.clone(), stream.try_clone());
+                std::thread::spawn(move || {
+                    let Ok(reader) = reader else { return };
+                    let mut reader = BufReader::new(reader);
+                    // A stroke that doesn't decode hangs up on whoever sent it.
+                    while let Ok(stroke) = decode_stroke(&mut reader) {
+                        if strokes.send((id, stroke)).is_err() {
+                            return;
+                        }
+                    }
+                    let _ = reader.get_ref().shutdown(Shutdown::Both);
+                });
+            }
+
+            let sent = self.sent(grid, low_memory).to_vec();
+            let all = changed_chunks(&sent, None, grid.width(), grid.height());
+            let keyframe = encode(grid.width(), grid.height(), &all);
+            if sender.try_send(Arc::new(keyframe)).is_ok() {
+                self.clients.push(Spectator {
+                    id,
+                    address,
+                    sender,
+                    stream,
+                    applied: 0,
+                    answered: 0,
+                });
+                joined.push((id, address));
+            }
+        }
+    }
+
+    // Sends everyone the chunks that changed since the last broadcast, at most every
+    // SEND_INTERVAL_SECS seconds, and tells each player how many of their strokes that world has
+    // taken in; `delta` is the time since the last call.
+    pub fn send_changes(&mut self, delta: f32, grid: &SimulationGrid, low_memory: bool) {
+        self.since_send += delta;
+        if self.since_send < SEND_INTERVAL_SECS || self.clients.is_empty() {
+            return;
+        }
+        self.since_send = 0.0;
+
+        let (width, height) = (grid.width(), grid.height());
+        let sent = self.sent(grid, low_memory);
+        let changed = changed_chunks(grid.cells(), Some(sent), width, height);
+        if !changed.is_empty() {
+            sent.copy_from(0, grid.cells());
+            self.broadcast(encode(width, height, &changed));
+        }
+        // After the world, so a player only drops a prediction once they can see what replaced it.
+        for client in &mut self.clients {
+            if client.applied > client.answered
+                && client.sender.try_send(Arc::new(encode_applied(client.applied))).is_ok()
+            {
+                client.answered = client.applied;
+            }
+        }
+    }
+
+    // The strokes that came in since the last call, oldest first, with who sent them. Taking them
+    // counts as putting them in the world: the next `send_changes` tells their players so.
+    pub fn strokes(&mut self) -> Vec<(u32, RemoteStroke)> {
+        let Some(inbox) = &mut self.strokes else { return Vec::new() };
+        let strokes: Vec<_> =
+            inbox.receiver.get_mut().map_or(Vec::new(), |receiver| receiver.try_iter().collect());
+        for (id, stroke) in &strokes {
+            if let Some(client) = self.clients.iter_mut().find(|client| client.id == *id) {
+                client.applied = client.applied.max(stroke.number);
+            }
+        }
+        strokes
+    }
+
+    // Hangs up on `id`. Returns whether they were connected.
+    pub fn kick(&mut self, id: u32) -> bool {
+        let Some(index) = self.clients.iter().position(|client| client.id == id) else {
+            return false;
+        };
+        let client = self.clients.remove(index);
+        let _ = client.stream.shutdown(Shutdown::Both);
+        true
+    }
+
+    // The world as last broadcast, all air before the first broadcast or after the world was
+    // resized.
+    fn sent(&mut self, grid: &SimulationGrid, low_memory: bool) -> &mut PackedCells {
+        let count = grid.cells().len();
+        if self.sent.as_ref().is_some_and(|sent| sent.cell_count() != count) {
+            self.sent = None;
+        }
+        self.sent.get_or_insert_with(|| PackedCells::new(&vec![Particle::Air; count], low_memory))
+    }
+
+    // Queues `message` for every spectator, dropping those that left or fell too far behind.
+    fn broadcast(&mut self, message: Vec<u8>) {
+        let message = Arc::new(message);
+        self.clients.retain(|client| match client.sender.try_send(message.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                info!("Dropped spectator {} for falling behind", client.address);
+                let _ = client.stream.shutdown(Shutdown::Both);
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                info!("Spectator {} left", client.address);
+                false
+            }
+        });
+    }
+}
+
+// The connection to the host, read on its own thread. Updates arrive over the channel; a closed
+// channel means the host went away. A player joined to a server also has a channel the other way,
+// written out on a thread of its own, for their strokes.
+#[derive(Resource)]
+struct SpectatorClient {
+    address: String,
+    updates: Mutex<Receiver<HostMessage>>,
+    strokes: Option<SyncSender<Vec<u8>>>,
+    connected: bool,
+    chunks_received: u64,
+}
+
+impl SpectatorClient {
+    fn connect(address: String, playing: bool) -> Self {
+        let (sender, updates) = sync_channel(CLIENT_BACKLOG);
+        let (strokes, outgoing) = sync_channel::<Vec<u8>>(CLIENT_BACKLOG);
+        let target = address.clone();
+        std::thread::spawn(move || {
+            let stream = target
+                .to_socket_addrs()
+                .and_then(|mut addresses| {
+                    let address = addresses.next().ok_or(std::io::ErrorKind::NotFound)?;
+                    TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+                });
+            let Ok(stream) = stream.inspect_err(|err| warn!("Could not reach {}: {}", target, err))
+            else {
+                return;
+            };
+            if let Ok(mut writer) = stream.try_clone() {
+                std::thread::spawn(move || {
+                    for message in outgoing {
+                        if writer.write_all(&message).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+            let mut stream = BufReader::new(stream);
+            loop {
+                let update = match decode(&mut stream) {
+                    Ok(update) => update,
+                    Err(err) => {
+                        warn!("Spectator stream from {} ended: {}", target, err);
+                        return;
+                    }
+                };
+                // The app closed its end.
+                if sender.send(update).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            address,
+            updates: Mutex::new(updates),
+            strokes: playing.then_some(strokes),
+            connected: true,
+            chunks_received: 0,
+        }
+    }
+}
+
+// A joined player's strokes painted ahead of the server. `world` is the server's world as it has
+// streamed in, cell for cell with the grid; the grid is that with the predicted cells on top. Each
+// predicted cell holds the number of the stroke that painted it and when, and goes back to the
+// server's cell once that stroke is answered, or given up on. Those that come back different
+// from the prediction are kept for a moment to outline.
+#[derive(Resource, Default)]
+struct Prediction {
+    strokes_sent: u32,
+    world: Vec<Particle>,
+    cells: HashMap<usize, (u32, f32)>,
+    corrected: Vec<(CellPos, f32)>,
+}
+
+impl Prediction {
+    // Puts the predicted cells that `settled` picks back to the server's world.
+    fn settle(
+        &mut self,
+        grid: &mut SimulationGrid,
+        now: f32,
+        mut settled: impl FnMut(u32, f32) -> bool,
+    ) {
+        let width = grid.width() as usize;
+        let Self { world, cells, corrected, .. } = self;
+        cells.retain(|&i, &mut (stroke, painted)| {
+            if !settled(stroke, painted) {
+                return true;
+            }
+            let cell = CellPos(IVec2::new((i % width) as i32, (i / width) as i32));
+            if grid.get(cell.x, cell.y) != Some(world[i]) {
+                grid.set(cell.x, cell.y, world[i]);
+                corrected.push((cell, now));
+            }
+            false
+        });
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct SpectatorLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_spectator_label(mut commands: Commands) {
+    commands.spawn((
+        SpectatorLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(24.0),
+            left: Val::Px(5.0),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(LABEL_COLOR),
+    ));
+}
+
+fn protect_mirrored_world(mut rules: ResMut<PaintRules>) {
+    rules.protect_world = true;
+}
+
+fn accept_spectators(
+    mut host: ResMut<SpectatorHost>,
+    grid: Res<SimulationGrid>,
+    low_memory: Option<Res<LowMemory>>,
+) {
+    for (_, address) in host.accept(&grid, low_memory.is_some()) {
+        info!("Spectator {} joined", address);
+    }
+}
+
+fn broadcast_world(
+    time: Res<Time>,
+    grid: Res<SimulationGrid>,
+    low_memory: Option<Res<LowMemory>>,
+    mut host: ResMut<SpectatorHost>,
+) {
+    host.send_changes(time.delta_secs(), &grid, low_memory.is_some());
+}
+
+fn update_host_label(
+    host: Res<SpectatorHost>,
+    mut q_label: Query<&mut Text, With<SpectatorLabel>>,
+) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let watching = host.clients.len();
+    let text = format!("Hosting spectators on port {}: {} watching", host.port(), watching);
+    if label.0 != text {
+        label.0 = text;
+    }
+}
+
+// Applies whatever arrived from the host since the last frame. Spectators only see particles;
+// temperatures and state bytes stay local. A host world of another size is clipped to ours. A
+// player's predicted cells stay as predicted until their stroke is answered.
+fn receive_world(
+    time: Res<Time>,
+    mut client: ResMut<SpectatorClient>,
+    mut grid: ResMut<SimulationGrid>,
+    mut prediction: Option<ResMut<Prediction>>,
+) {
+    let now = time.elapsed_secs();
+    if let Some(prediction) = prediction.as_deref_mut() {
+        if prediction.world.len() != grid.cells().len() {
+            prediction.world = grid.cells().to_vec();
+        }
+        prediction.settle(&mut grid, now, |_, painted| now - painted > PREDICTION_TIMEOUT_SECS);
+    }
+    loop {
+        let updates = client.updates.get_mut();
+        let received = updates.map_or(Err(TryRecvError::Disconnected), |u| u.try_recv());
+        let (size, chunks) = match received {
+            Ok(HostMessage::World(size, chunks)) => (size, chunks),
+            Ok(HostMessage::Applied(stroke)) => {
+                if let Some(prediction) = prediction.as_deref_mut() {
+                    prediction.settle(&mut grid, now, |predicted, _| predicted <= stroke);
+                }
+                continue;
+            }
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                client.connected = false;
+                return;
+            }
+        };
+        client.chunks_received += chunks.len() as u64;
+        for update in chunks {
+            let chunk = ChunkPos(IVec2::new(update.chunk.0 as i32, update.chunk.1 as i32));
+            let origin = chunk.origin().as_uvec2();
+            let chunk_width = chunk.size_within(size.x, size.y).x;
+            let cells = update
+                .runs
+                .iter()
+                .flat_map(|&(id, count)| std::iter::repeat_n(id, count as usize));
+            for (i, id) in cells.enumerate() {
+                let (x, y) = (origin.x + i as u32 % chunk_width, origin.y + i as u32 / chunk_width);
+                let particle = Particle::ALL.get(id as usize).copied().unwrap_or_default();
+                if !grid.in_bounds(x as i32, y as i32) {
+                    continue;
+                }
+                if let Some(prediction) = prediction.as_deref_mut() {
+                    let index = (y * grid.width() + x) as usize;
+                    prediction.world[index] = particle;
+                    if prediction.cells.contains_key(&index) {
+                        continue;
+                    }
+                }
+                if grid.get(x as i32, y as i32) != Some(particle) {
+                    grid.set(x as i32, y as i32, particle);
+                }
+            }
+        }
+    }
+}
+
+// Sends the brush's strokes to the server, painting them here as a prediction until the server
+// answers. Nothing else changes a world that is the server's. A stroke that can't be sent isn't
+// predicted either.
+fn send_strokes(
+    time: Res<Time>,
+    mut commands: ResMut<WorldCommands>,
+    mut client: ResMut<SpectatorClient>,
+    mut prediction: ResMut<Prediction>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    let queued = commands.take();
+    let Some(strokes) = &client.strokes else { return };
+    let mut lost = false;
+    for command in queued {
+        let (WorldCommand::Paint {
+            particle,
+            data,
+            cells,
+            ..
+        }
+        | WorldCommand::Place {
+            particle,
+            data,
+            cells,
+        }) = command
+        else {
+            continue;
+        };
+        let cells: Vec<_> = cells
+            .iter()
+            .filter_map(|cell| Some((u16::try_from(cell.x).ok()?, u16::try_from(cell.y).ok()?)))
+            .collect();
+        let number = prediction.strokes_sent + 1;
+        let stroke = encode_stroke(&RemoteStroke {
+            number,
+            particle,
+            data,
+            cells: cells.clone(),
+        });
+        // A full backlog drops the stroke; a closed one means the server went away.
+        match strokes.try_send(stroke) {
+            Ok(()) => prediction.strokes_sent = number,
+            Err(err) => {
+                lost |= matches!(err, TrySendError::Disconnected(_));
+                continue;
+            }
+        }
+        let now = time.elapsed_secs();
+        for (x, y) in cells {
+            let (x, y) = (x as i32, y as i32);
+            if grid.in_bounds(x, y) {
+                let index = (y as u32 * grid.width() + x as u32) as usize;
+                grid.place(x, y, particle, data);
+                prediction.cells.insert(index, (number, now));
+            }
+        }
+    }
+    if lost {
+        client.connected = false;
+    }
+}
+
+// Outlines the cells the server painted differently from the prediction, fading out.
+fn draw_corrections(
+    time: Res<Time>,
+    mut prediction: ResMut<Prediction>,
+    view: WorldView,
+    mut gizmos: Gizmos,
+) {
+    let now = time.elapsed_secs();
+    prediction.corrected.retain(|&(_, at)| now - at < CORRECTION_FLASH_SECS);
+    for &(cell, at) in &prediction.corrected {
+        let rect = view.cells_to_world(cell, cell);
+        let fade = 1.0 - (now - at) / CORRECTION_FLASH_SECS;
+        let color = CORRECTION_COLOR.with_alpha(fade);
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), color);
+    }
+}
+
+fn update_client_label(
+    client: Res<SpectatorClient>,
+    mut q_label: Query<&mut Text, With<SpectatorLabel>>,
+) {
+    if !client.is_changed() {
+        return;
+    }
+    let Ok(mut label) = q_label.single_mut() else { return };
+    label.0 = if client.connected {
+        let (address, chunks) = (&client.address, client.chunks_received);
+        match client.strokes {
+            Some(_) => format!("Playing on {} ({} chunks received)", address, chunks),
+            None => format!("Spectating {} (view only, {} chunks received)", address, chunks),
+        }
+    } else {
+        format!("Lost the connection to {}", client.address)
+    };
+}
+
+// --- HELPERS ---
+
+// Every chunk of `cells` that differs from `previous`, or all chunks when there is nothing to
+// compare against.
+fn changed_chunks(
+    cells: &[Particle],
+    previous: Option<&PackedCells>,
+    width: u32,
+    height: u32,
+) -> Vec<ChunkUpdate> {
+    let mut updates = Vec::new();
+    for chunk in ChunkPos::covering(width, height) {
+        let (x0, y0) = (chunk.origin().x as u32, chunk.origin().y as u32);
+        let size = chunk.size_within(width, height);
+        let rows = (y0..y0 + size.y)
+            .map(|y| (y * width) as usize)
+            .map(|row| row + x0 as usize..row + (x0 + size.x) as usize);
+        let changed = previous.is_none_or(|previous| {
+            rows.clone().any(|span| !previous.matches(span.start, &cells[span]))
+        });
+        if !changed {
+            continue;
+        }
+
+        let mut runs: Vec<(u8, u16)> = Vec::new();
+        for &cell in rows.flat_map(|span| &cells[span]) {
+            match runs.last_mut() {
+                Some((id, count)) if *id == cell as u8 => *count += 1,
+                _ => runs.push((cell as u8, 1)),
+            }
+        }
+        updates.push(ChunkUpdate {
+            chunk: (chunk.0.x as u16, chunk.0.y as u16),
+            runs,
+        });
+    }
+    updates
+}
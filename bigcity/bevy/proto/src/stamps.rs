@@ -0,0 +1,484 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::ecs::system::SystemParam;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::coords::CellPos;
+use crate::inventory::Inventory;
+use crate::levels::PaintRules;
+use crate::locks::PaintLocks;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::{file_stem, load_user_ron, user_data_dir};
+use crate::player::PlayerInputSet;
+use crate::sim::SimulationGrid;
+use crate::snapshot::WorldSnapshot;
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::world_file::{WorldFileError, WorldSerializer};
+use crate::{Particle, WorldView};
+
+// --- CONSTANTS ---
+// Stamps are saved to this folder in the user data directory, each with a PNG thumbnail, in the
+// format worlds are saved in (see `world_file`). Older stamps, and shared ones, are one RON file.
+const STAMP_FOLDER: &str = "stamps";
+const CELLS_EXTENSION: &str = "cells.png";
+const SIDECAR_EXTENSION: &str = "cells.ron";
+const STAMP_EXTENSION: &str = "stamp.ron";
+// Thumbnails are at most this many pixels on their longer side.
+const THUMBNAIL_SIZE: u32 = 64;
+// How long the stamp panel stays up after capturing or picking a stamp.
+const PANEL_SECS: f32 = 3.0;
+
+// --- PLUGIN ---
+
+// The stamp library: F10 captures the screen into a stamp, Shift+V picks the next one and holds
+// it at the cursor, where its ghost shows what it would cover (see `selection`), and V pastes what
+// is held, or holds the selected stamp when nothing is. Ctrl+R turns and Ctrl+Shift+R flips the
+// held stamp while the selection tool has no copy of its own, and Esc lets go of it.
+pub struct StampsPlugin;
+
+impl Plugin for StampsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(StampLibrary {
+            stamps: load_user_stamps(),
+            selected: 0,
+            held: None,
+        })
+        .add_systems(Startup, spawn_stamp_panel)
+        .add_systems(
+            Update,
+            (capture_stamp, pick_stamp, paste_stamp, update_stamp_panel)
+                .chain()
+                .after(PlayerInputSet)
+                .before(WorldCommandSet),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// A reusable piece of a world: its cells and their state bytes, so mirrors keep their tilt.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub struct Stamp {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    // Run-length encoded row by row, bottom row first, like world snapshots.
+    pub runs: Vec<(Particle, u8, u32)>,
+}
+
+impl Stamp {
+    // Copies the occupied part of the inclusive rectangle `min..=max`, trimmed to the smallest
+    // box around its non-air cells. Returns None if there is nothing but air.
+    pub fn capture(grid: &SimulationGrid, min: IVec2, max: IVec2, name: String) -> Option<Self> {
+        let occupied = |x, y| grid.get(x, y).is_some_and(|p| p != Particle::Air);
+        let (mut low, mut high) = (max, min);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if occupied(x, y) {
+                    low = low.min(IVec2::new(x, y));
+                    high = high.max(IVec2::new(x, y));
+                }
+            }
+        }
+        if low.cmpgt(high).any() {
+            return None;
+        }
+
+        let cells = (low.y..=high.y).flat_map(|y| {
+            (low.x..=high.x)
+                .map(move |x| (grid.get(x, y).unwrap_or_default(), grid.data(x, y).unwrap_or(0)))
+        });
+        let size = (high - low + IVec2::ONE).as_uvec2();
+        Some(Self::from_cells(name, size.x, size.y, cells))
+    }
+
+    // A stamp of a whole saved world, trimmed like a capture. Returns None if it is all air.
+    pub fn from_snapshot(snapshot: &WorldSnapshot, name: String) -> Option<Self> {
+        let mut grid = SimulationGrid::new(snapshot.width, snapshot.height);
+        snapshot.apply_to(&mut grid);
+        let max = IVec2::new(snapshot.width as i32 - 1, snapshot.height as i32 - 1);
+        Self::capture(&grid, IVec2::ZERO, max, name)
+    }
+
+    // A world just big enough for the stamp, holding it, to save like any other world.
+    pub fn to_grid(&self) -> SimulationGrid {
+        let mut grid = SimulationGrid::new(self.width.max(1), self.height.max(1));
+        for (offset, particle, data) in self.cells() {
+            grid.set(offset.x, offset.y, particle);
+            grid.set_data(offset.x, offset.y, data);
+        }
+        grid
+    }
+
+    // The stamp turned a quarter turn clockwise.
+    pub fn rotated(&self) -> Self {
+        let width = self.width as i32;
+        self.remapped(self.height, self.width, |offset| IVec2::new(offset.y, width - 1 - offset.x))
+    }
+
+    // The stamp flipped left to right. State bytes stay as they were, so mirrors keep their tilt.
+    pub fn mirrored(&self) -> Self {
+        let width = self.width as i32;
+        self.remapped(self.width, self.height, |offset| IVec2::new(width - 1 - offset.x, offset.y))
+    }
+
+    // The stamp with every cell moved by `to` into a box `width` by `height`.
+    fn remapped(&self, width: u32, height: u32, to: impl Fn(IVec2) -> IVec2) -> Self {
+        let mut cells = vec![(Particle::Air, 0); (width * height) as usize];
+        for (offset, particle, data) in self.cells() {
+            let offset = to(offset);
+            cells[(offset.y * width as i32 + offset.x) as usize] = (particle, data);
+        }
+        Self::from_cells(self.name.clone(), width, height, cells)
+    }
+
+    // Run-length encodes `cells`, row by row from the bottom.
+    fn from_cells(
+        name: String,
+        width: u32,
+        height: u32,
+        cells: impl IntoIterator<Item = (Particle, u8)>,
+    ) -> Self {
+        let mut runs: Vec<(Particle, u8, u32)> = Vec::new();
+        for cell in cells {
+            match runs.last_mut() {
+                Some((particle, data, count)) if (*particle, *data) == cell => *count += 1,
+                _ => runs.push((cell.0, cell.1, 1)),
+            }
+        }
+        Self {
+            name,
+            width,
+            height,
+            runs,
+        }
+    }
+
+    // Every cell with its offset from the stamp's bottom left corner.
+    pub fn cells(&self) -> impl Iterator<Item = (IVec2, Particle, u8)> + '_ {
+        let width = self.width.max(1);
+        self.runs
+            .iter()
+            .flat_map(|&(particle, data, n)| std::iter::repeat_n((particle, data), n as usize))
+            .take((self.width * self.height) as usize)
+            .enumerate()
+            .map(move |(i, (particle, data))| {
+                let offset = IVec2::new((i as u32 % width) as i32, (i as u32 / width) as i32);
+                (offset, particle, data)
+            })
+    }
+
+    // Places the stamp's non-air cells with its bottom left corner at `origin`, as the player's
+    // own. Air in the stamp and locked cells leave the world untouched. Returns how many cells
+    // changed.
+    pub fn paste(&self, grid: &mut SimulationGrid, origin: CellPos, locks: &PaintLocks) -> u32 {
+        let mut changed = 0;
+        for (offset, particle, data) in self.cells().filter(|(_, p, _)| *p != Particle::Air) {
+            let cell = origin + offset;
+            let Some(old) = grid.get(cell.x, cell.y) else { continue };
+            if locks.is_locked(cell, old) {
+                continue;
+            }
+            changed += grid.place(cell.x, cell.y, particle, data) as u32;
+        }
+        changed
+    }
+
+    // A small picture of the stamp, top row first, with air left transparent.
+    pub fn thumbnail(&self) -> image::RgbaImage {
+        let factor = self.width.max(self.height).div_ceil(THUMBNAIL_SIZE).max(1);
+        let (width, height) = (self.width.div_ceil(factor), self.height.div_ceil(factor));
+        let mut pixels = image::RgbaImage::new(width.max(1), height.max(1));
+        for (offset, particle, _) in self.cells() {
+            let offset = offset.as_uvec2();
+            if particle == Particle::Air || offset.x % factor != 0 || offset.y % factor != 0 {
+                continue;
+            }
+            let (x, y) = (offset.x / factor, height - 1 - offset.y / factor);
+            pixels.put_pixel(x, y, image::Rgba(particle.color().to_srgba().to_u8_array()));
+        }
+        pixels
+    }
+}
+
+// --- RESOURCES ---
+
+// The player's stamps, in file order, the one picked, and a copy of it held at the cursor, turned
+// and flipped as the player likes, for V to paste.
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct StampLibrary {
+    pub stamps: Vec<Stamp>,
+    selected: usize,
+    held: Option<Stamp>,
+}
+
+impl StampLibrary {
+    // Adds a stamp at the end and selects it.
+    pub fn add(&mut self, stamp: Stamp) {
+        self.stamps.push(stamp);
+        self.selected = self.stamps.len() - 1;
+    }
+
+    // The stamp riding on the cursor, if one is held.
+    pub fn held(&self) -> Option<&Stamp> {
+        self.held.as_ref()
+    }
+
+    pub fn held_mut(&mut self) -> Option<&mut Stamp> {
+        self.held.as_mut()
+    }
+}
+
+// Where stamps are pasted: the world, by way of its command queue.
+#[derive(SystemParam)]
+pub struct PasteTarget<'w> {
+    commands: ResMut<'w, WorldCommands>,
+    rules: Res<'w, PaintRules>,
+    inventory: Res<'w, Inventory>,
+}
+
+impl PasteTarget<'_> {
+    // Pasting would hand out free material, so it's off in levels and challenge mode.
+    pub fn allowed(&self) -> bool {
+        !self.rules.protect_world && !self.inventory.is_enabled()
+    }
+
+    // Pastes `stamp` centered on `cell` as one edit to undo, before the next tick.
+    pub fn paste(&mut self, stamp: &Stamp, cell: CellPos) {
+        self.commands.push(WorldCommand::Paste {
+            stamp: stamp.clone(),
+            at: cell,
+        });
+    }
+}
+
+// --- COMPONENTS ---
+
+// Shows the selected stamp's thumbnail and name for a moment after it changes.
+#[derive(Component, Default)]
+struct StampPanel {
+    remaining: f32,
+}
+
+// --- SYSTEMS ---
+
+fn spawn_stamp_panel(mut commands: Commands) {
+    commands.spawn((
+        StampPanel::default(),
+        Name::new("stamp_panel"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(35.0),
+            left: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(4.0),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+    ));
+}
+
+// F10 copies everything on screen into a new stamp, named after what it is mostly made of, and
+// saves it with a thumbnail.
+fn capture_stamp(
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Res<SimulationGrid>,
+    mut library: ResMut<StampLibrary>,
+) {
+    if !keys.just_pressed(KeyCode::F10) {
+        return;
+    }
+    let max = IVec2::new(grid.width() as i32 - 1, grid.height() as i32 - 1);
+    let name = stamp_name(grid.cells().iter().copied(), library.stamps.len() + 1);
+    let Some(stamp) = Stamp::capture(&grid, IVec2::ZERO, max, name) else {
+        warn!("Nothing to capture into a stamp");
+        return;
+    };
+
+    save_stamp(&stamp);
+    info!("Captured stamp \"{}\" ({}x{})", stamp.name, stamp.width, stamp.height);
+    library.add(stamp);
+}
+
+// Shift+V steps through the library, holding each stamp it picks. Ctrl+V pastes the selection
+// tool's copy instead. Esc lets go of the held stamp.
+fn pick_stamp(keys: Res<ButtonInput<KeyCode>>, mut library: ResMut<StampLibrary>) {
+    if keys.just_pressed(KeyCode::Escape) && library.held.is_some() {
+        library.held = None;
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !shift || ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyV) || library.stamps.is_empty()
+    {
+        return;
+    }
+    library.selected = (library.selected + 1) % library.stamps.len();
+    library.held = library.stamps.get(library.selected).cloned();
+}
+
+// V pastes the held stamp centered on the cursor, and keeps holding it. With nothing held, it
+// holds the selected stamp first, so its ghost can be checked before anything is pasted.
+fn paste_stamp(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut library: ResMut<StampLibrary>,
+    view: WorldView,
+    mut target: PasteTarget,
+) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift || ctrl_held(&keys) || !keys.just_pressed(KeyCode::KeyV) || !target.allowed() {
+        return;
+    }
+    let Some(stamp) = &library.held else {
+        library.held = library.stamps.get(library.selected).cloned();
+        return;
+    };
+    let Some(cell) = view.cursor_cell() else { return };
+
+    target.paste(stamp, cell);
+}
+
+fn update_stamp_panel(
+    mut commands: Commands,
+    time: Res<Time>,
+    library: Res<StampLibrary>,
+    mut images: ResMut<Assets<Image>>,
+    mut q_panel: Query<(Entity, &mut StampPanel, &mut Node)>,
+) {
+    let Ok((entity, mut panel, mut node)) = q_panel.single_mut() else { return };
+
+    let selected = library.stamps.get(library.selected);
+    if let Some(stamp) = selected.filter(|_| library.is_changed() && !library.is_added()) {
+        let thumbnail = thumbnail_image(stamp);
+        let size = thumbnail.size_f32() * 2.0;
+        let thumbnail = images.add(thumbnail);
+        commands.entity(entity).despawn_related::<Children>().with_children(|panel| {
+            panel.spawn((
+                ImageNode::new(thumbnail),
+                Node {
+                    width: Val::Px(size.x),
+                    height: Val::Px(size.y),
+                    ..default()
+                },
+            ));
+            panel.spawn((
+                Text::new(format!(
+                    "{} ({}/{})\nV pastes, Shift+V picks the next, Esc lets go",
+                    stamp.name,
+                    library.selected + 1,
+                    library.stamps.len()
+                )),
+                TextLayout::new_with_justify(JustifyText::Center),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+            ));
+        });
+        node.display = Display::Flex;
+        panel.remaining = PANEL_SECS;
+    }
+
+    if panel.remaining > 0.0 {
+        panel.remaining -= time.delta_secs();
+        if panel.remaining <= 0.0 {
+            node.display = Display::None;
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// Saves a stamp into the user's stamp folder as a world, named after it, with its thumbnail, where
+// the library finds it on the next launch. A stamp of the same name is written over.
+pub fn save_stamp(stamp: &Stamp) {
+    let Some(dir) = user_data_dir().map(|dir| dir.join(STAMP_FOLDER)) else { return };
+    let stem = file_stem(&stamp.name);
+    let path = |extension| dir.join(format!("{}.{}", stem, extension));
+    let result = std::fs::create_dir_all(&dir).map_err(WorldFileError::from).and_then(|_| {
+        let (cells, sidecar) = (path(CELLS_EXTENSION), path(SIDECAR_EXTENSION));
+        WorldSerializer::save_named(&stamp.to_grid(), Some(&stamp.name), &cells, &sidecar)
+    });
+    if let Err(err) = result {
+        warn!("Could not save stamp \"{}\": {}", stamp.name, err);
+        return;
+    }
+    let thumbnail = path("png");
+    if let Err(err) = stamp.thumbnail().save(&thumbnail) {
+        warn!("Could not save stamp thumbnail {:?}: {}", thumbnail, err);
+    }
+}
+
+// Every stamp in the user's stamp folder, saved as a world or, from older builds and downloads, as
+// one RON file, in file order.
+fn load_user_stamps() -> Vec<Stamp> {
+    let Some(dir) = user_data_dir().map(|dir| dir.join(STAMP_FOLDER)) else { return Vec::new() };
+    let Ok(entries) = std::fs::read_dir(&dir) else { return Vec::new() };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|file| {
+            file.ends_with(&format!(".{}", SIDECAR_EXTENSION))
+                || file.ends_with(&format!(".{}", STAMP_EXTENSION))
+        })
+        .collect();
+    files.sort();
+    files
+        .iter()
+        .filter_map(|file| {
+            let Some(stem) = file.strip_suffix(&format!(".{}", SIDECAR_EXTENSION)) else {
+                return load_user_ron(&format!("{}/{}", STAMP_FOLDER, file));
+            };
+            let cells = dir.join(format!("{}.{}", stem, CELLS_EXTENSION));
+            let world = WorldSerializer::load(&cells, &dir.join(file))
+                .inspect_err(|err| warn!("Ignoring unreadable stamp {:?}: {}", file, err))
+                .ok()?;
+            Stamp::from_snapshot(&world.snapshot, world.name.unwrap_or_else(|| stem.to_string()))
+        })
+        .collect()
+}
+
+// Names a new stamp after the one or two materials most of `cells` are, e.g. "Sand and water 3".
+pub fn stamp_name(cells: impl IntoIterator<Item = Particle>, number: usize) -> String {
+    let mut totals = [0u32; Particle::ALL.len()];
+    for cell in cells {
+        totals[cell as usize] += 1;
+    }
+    let mut counts: Vec<(u32, Particle)> = Particle::ALL
+        .into_iter()
+        .filter(|&p| p != Particle::Air && totals[p as usize] > 0)
+        .map(|p| (totals[p as usize], p))
+        .collect();
+    counts.sort_by_key(|&(count, _)| std::cmp::Reverse(count));
+    let materials = match counts.as_slice() {
+        [(_, first), (_, second), ..] => {
+            format!("{:?} and {}", first, format!("{:?}", second).to_lowercase())
+        }
+        [(_, only)] => format!("{:?}", only),
+        [] => "Empty".to_string(),
+    };
+    format!("{} {}", materials, number)
+}
+
+fn thumbnail_image(stamp: &Stamp) -> Image {
+    let pixels = stamp.thumbnail();
+    let mut image = Image::new(
+        Extent3d {
+            width: pixels.width(),
+            height: pixels.height(),
+            ..default()
+        },
+        TextureDimension::D2,
+        pixels.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
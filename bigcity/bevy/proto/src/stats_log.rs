@@ -0,0 +1,391 @@
+// --- IMPORTS ---
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use crate::Particle;
+use crate::events::SimEvent;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::{Reaction, SimulationGrid, SimulationSet, SimulationStats};
+
+// --- CONSTANTS ---
+const EXPORT_FOLDER: &str = "stats";
+// Parquet rows are written out in row groups of this many, so a long log doesn't pile up in
+// memory and a crash loses at most one group.
+#[cfg(feature = "parquet")]
+const ROW_GROUP_ROWS: usize = 4096;
+
+// --- PLUGIN ---
+pub struct StatsLogPlugin;
+
+impl Plugin for StatsLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<StatsLogSettings>()
+            .init_resource::<StatsLog>()
+            .add_systems(FixedUpdate, record_stats.after(SimulationSet))
+            .add_systems(Update, (toggle_stats_log, tally_events).chain().after(SimulationSet))
+            // Last, so an exit requested anywhere this frame still gets the file finished.
+            .add_systems(Last, close_stats_log_on_exit);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum StatsFormat {
+    // Written row by row as the simulation runs.
+    Csv,
+    // Written a row group of ROW_GROUP_ROWS rows at a time, and finished when logging stops. Needs
+    // the `parquet` feature.
+    Parquet,
+}
+
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct StatsLogSettings {
+    pub format: StatsFormat,
+    // A row is recorded every this many ticks.
+    pub every_ticks: u64,
+}
+
+impl Default for StatsLogSettings {
+    fn default() -> Self {
+        Self {
+            format: StatsFormat::Csv,
+            every_ticks: 1,
+        }
+    }
+}
+
+// One row of the log: the world's material counts and temperatures after tick `tick`, and what
+// happened since the previous row: the gameplay events seen and the cells each reaction changed.
+#[derive(Clone, Debug, Default)]
+pub struct StatsRow {
+    pub tick: u64,
+    pub counts: [u32; Particle::ALL.len()],
+    pub painted_cells: u32,
+    pub selections: u32,
+    pub brush_resizes: u32,
+    // By `Reaction` index, as `SimEvent::Reacted` reports them once a frame.
+    pub reacted: [u32; Reaction::ALL.len()],
+    // Over every cell, in degrees Celsius.
+    pub mean_temperature: f32,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    // Every material's mean temperature; NaN for materials the world holds none of.
+    pub temperatures: [f32; Particle::ALL.len()],
+}
+
+impl StatsRow {
+    // The integer columns, then the temperatures.
+    fn columns() -> Vec<String> {
+        let name = |particle: &Particle| format!("{:?}", particle).to_lowercase();
+        let mut columns = vec!["tick".to_string()];
+        columns.extend(Particle::ALL.iter().map(name));
+        columns.extend(["painted_cells", "selections", "brush_resizes"].map(String::from));
+        columns.extend(Reaction::ALL.iter().map(|r| format!("{:?}_cells", r).to_lowercase()));
+        let temperatures = ["mean_temperature", "min_temperature", "max_temperature"];
+        columns.extend(temperatures.map(String::from));
+        columns.extend(Particle::ALL.iter().map(|p| format!("{}_temperature", name(p))));
+        columns
+    }
+
+    fn values(&self) -> Vec<u64> {
+        let mut values = vec![self.tick];
+        values.extend(self.counts.iter().map(|&c| c as u64));
+        values.extend([self.painted_cells, self.selections, self.brush_resizes].map(u64::from));
+        values.extend(self.reacted.iter().map(|&c| c as u64));
+        values
+    }
+
+    fn temperature_values(&self) -> Vec<f64> {
+        let mut values = vec![self.mean_temperature, self.min_temperature, self.max_temperature];
+        values.extend(self.temperatures);
+        values.into_iter().map(f64::from).collect()
+    }
+
+    // Takes the counts and temperatures from `grid`, in one pass over its cells.
+    fn sample(&mut self, grid: &SimulationGrid) {
+        let mut sums = [0.0f64; Particle::ALL.len()];
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        self.counts = [0; Particle::ALL.len()];
+        for (&cell, &temperature) in grid.cells().iter().zip(grid.temperatures()) {
+            self.counts[cell as usize] += 1;
+            sums[cell as usize] += temperature as f64;
+            min = min.min(temperature);
+            max = max.max(temperature);
+        }
+        let cells = grid.cells().len().max(1) as f64;
+        self.mean_temperature = (sums.iter().sum::<f64>() / cells) as f32;
+        (self.min_temperature, self.max_temperature) = (min, max);
+        for (i, mean) in self.temperatures.iter_mut().enumerate() {
+            *mean = if self.counts[i] == 0 {
+                f32::NAN
+            } else {
+                (sums[i] / self.counts[i] as f64) as f32
+            };
+        }
+    }
+}
+
+enum StatsSink {
+    Csv(BufWriter<File>),
+    #[cfg(feature = "parquet")]
+    Parquet(ParquetSink),
+}
+
+#[derive(Resource, Default)]
+pub struct StatsLog {
+    sink: Option<(PathBuf, StatsSink)>,
+    // Events and reactions are tallied between rows, so nothing is lost when `every_ticks` > 1.
+    pending: StatsRow,
+    // The grid's reaction counts as of the last tick, which `report_reactions` takes once a frame.
+    reacted_seen: [u32; Reaction::ALL.len()],
+}
+
+impl StatsLog {
+    pub fn is_recording(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn start(&mut self, format: StatsFormat) -> std::io::Result<PathBuf> {
+        self.stop();
+        let dir = user_data_dir()
+            .ok_or_else(|| std::io::Error::other("no user data directory"))?
+            .join(EXPORT_FOLDER);
+        std::fs::create_dir_all(&dir)?;
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+        let extension = match format {
+            StatsFormat::Csv => "csv",
+            StatsFormat::Parquet => "parquet",
+        };
+        let path = dir.join(format!("stats_{}.{}", stamp, extension));
+
+        let sink = match format {
+            StatsFormat::Csv => {
+                let mut file = BufWriter::new(File::create(&path)?);
+                writeln!(file, "{}", StatsRow::columns().join(","))?;
+                StatsSink::Csv(file)
+            }
+            #[cfg(feature = "parquet")]
+            StatsFormat::Parquet => StatsSink::Parquet(ParquetSink::create(&path)?),
+            #[cfg(not(feature = "parquet"))]
+            StatsFormat::Parquet => {
+                return Err(std::io::Error::other("built without the `parquet` feature"));
+            }
+        };
+        self.sink = Some((path.clone(), sink));
+        self.pending = StatsRow::default();
+        Ok(path)
+    }
+
+    // Finishes the current file, if any.
+    pub fn stop(&mut self) {
+        let Some((path, sink)) = self.sink.take() else { return };
+        let result = match sink {
+            StatsSink::Csv(mut file) => file.flush(),
+            #[cfg(feature = "parquet")]
+            StatsSink::Parquet(parquet) => parquet.finish(),
+        };
+        match result {
+            Ok(()) => info!("Per-tick statistics written to {:?}", path),
+            Err(err) => warn!("Could not finish {:?}: {}", path, err),
+        }
+    }
+
+    fn push(&mut self, row: StatsRow) {
+        let Some((path, sink)) = &mut self.sink else { return };
+        let result = match sink {
+            StatsSink::Csv(file) => {
+                let mut values: Vec<String> = row.values().iter().map(u64::to_string).collect();
+                values.extend(row.temperature_values().iter().map(f64::to_string));
+                writeln!(file, "{}", values.join(","))
+            }
+            #[cfg(feature = "parquet")]
+            StatsSink::Parquet(parquet) => parquet.push(row),
+        };
+        if let Err(err) = result {
+            warn!("Could not write to {:?}, stopping the log: {}", path, err);
+            self.sink = None;
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_stats_log(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<StatsLogSettings>,
+    mut log: ResMut<StatsLog>,
+) {
+    // Ctrl+F9 is a quick-load.
+    if ctrl_held(&keys) || !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+    if log.is_recording() {
+        log.stop();
+        return;
+    }
+
+    // Shift picks Parquet for this and later sessions, plain F9 goes back to CSV.
+    settings.format = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        StatsFormat::Parquet
+    } else {
+        StatsFormat::Csv
+    };
+    match log.start(settings.format) {
+        Ok(path) => info!("Logging per-tick statistics to {:?}", path),
+        Err(err) => warn!("Could not start the {:?} statistics log: {}", settings.format, err),
+    }
+}
+
+// Tallies the frame's gameplay events into the next row.
+fn tally_events(mut events: EventReader<SimEvent>, mut log: ResMut<StatsLog>) {
+    if !log.is_recording() {
+        events.clear();
+        return;
+    }
+    for event in events.read() {
+        match event {
+            SimEvent::Painted { cells, .. } => log.pending.painted_cells += cells,
+            SimEvent::ParticleSelected { .. } => log.pending.selections += 1,
+            SimEvent::BrushResized { .. } => log.pending.brush_resizes += 1,
+            // Counted tick by tick in `record_stats` instead.
+            SimEvent::WorldCreated | SimEvent::Reacted { .. } => {}
+        }
+    }
+}
+
+// Runs after every tick: tallies the cells the tick's reactions changed, and every `every_ticks`
+// ticks samples the world into a row.
+fn record_stats(
+    settings: Res<StatsLogSettings>,
+    stats: Res<SimulationStats>,
+    grid: Res<SimulationGrid>,
+    mut log: ResMut<StatsLog>,
+    mut last_row_tick: Local<Option<u64>>,
+) {
+    // The grid's counts only grow between the frames `report_reactions` takes them in, so what is
+    // below the last count seen was taken meanwhile and the whole count is new.
+    let reacted = grid.reacted();
+    for (i, &count) in reacted.iter().enumerate() {
+        let seen = log.reacted_seen[i];
+        log.pending.reacted[i] += if count >= seen { count - seen } else { count };
+    }
+    log.reacted_seen = reacted;
+    if !log.is_recording() {
+        log.pending = StatsRow::default();
+        return;
+    }
+
+    let every = settings.every_ticks.max(1);
+    if *last_row_tick == Some(stats.tick) || !stats.tick.is_multiple_of(every) {
+        return;
+    }
+    *last_row_tick = Some(stats.tick);
+    let mut row = std::mem::take(&mut log.pending);
+    row.tick = stats.tick;
+    row.sample(&grid);
+    log.push(row);
+}
+
+fn close_stats_log_on_exit(mut exits: EventReader<AppExit>, mut log: ResMut<StatsLog>) {
+    if exits.read().next().is_some() {
+        log.stop();
+    }
+}
+
+// --- PARQUET ---
+
+// A Parquet file being written: the rows since the last row group, written out as the next one
+// once there are ROW_GROUP_ROWS of them. The counts are required INT64 columns and the
+// temperatures required DOUBLEs, which pandas and Polars read without any schema hints.
+#[cfg(feature = "parquet")]
+struct ParquetSink {
+    writer: parquet::file::writer::SerializedFileWriter<File>,
+    rows: Vec<StatsRow>,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetSink {
+    fn create(path: &std::path::Path) -> std::io::Result<Self> {
+        use std::sync::Arc;
+
+        use parquet::file::properties::WriterProperties;
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let columns = StatsRow::columns();
+        let integers = StatsRow::default().values().len();
+        let fields: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let kind = if i < integers { "INT64" } else { "DOUBLE" };
+                format!("REQUIRED {} {};", kind, c)
+            })
+            .collect();
+        let message = format!("message stats {{ {} }}", fields.join(" "));
+        let create = || -> parquet::errors::Result<Self> {
+            let schema = Arc::new(parse_message_type(&message)?);
+            let properties = Arc::new(WriterProperties::builder().build());
+            Ok(Self {
+                writer: SerializedFileWriter::new(File::create(path)?, schema, properties)?,
+                rows: Vec::with_capacity(ROW_GROUP_ROWS),
+            })
+        };
+        create().map_err(std::io::Error::other)
+    }
+
+    fn push(&mut self, row: StatsRow) -> std::io::Result<()> {
+        self.rows.push(row);
+        if self.rows.len() < ROW_GROUP_ROWS {
+            return Ok(());
+        }
+        self.write_row_group()
+    }
+
+    // Writes the rows still waiting and the file's footer.
+    fn finish(mut self) -> std::io::Result<()> {
+        self.write_row_group()?;
+        self.writer.close().map(|_| ()).map_err(std::io::Error::other)
+    }
+
+    fn write_row_group(&mut self) -> std::io::Result<()> {
+        use parquet::data_type::{DoubleType, Int64Type};
+
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let integers: Vec<Vec<u64>> = self.rows.iter().map(StatsRow::values).collect();
+        let temperatures: Vec<Vec<f64>> =
+            self.rows.iter().map(StatsRow::temperature_values).collect();
+        let mut write = || -> parquet::errors::Result<()> {
+            let mut row_group = self.writer.next_row_group()?;
+            let mut index = 0usize;
+            while let Some(mut column) = row_group.next_column()? {
+                if let Some(at) = index.checked_sub(integers[0].len()) {
+                    let values: Vec<f64> = temperatures.iter().map(|row| row[at]).collect();
+                    column.typed::<DoubleType>().write_batch(&values, None, None)?;
+                } else {
+                    let values: Vec<i64> = integers.iter().map(|row| row[index] as i64).collect();
+                    column.typed::<Int64Type>().write_batch(&values, None, None)?;
+                }
+                column.close()?;
+                index += 1;
+            }
+            row_group.close()?;
+            Ok(())
+        };
+        write().map_err(std::io::Error::other)?;
+        self.rows.clear();
+        Ok(())
+    }
+}
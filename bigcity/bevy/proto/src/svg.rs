@@ -0,0 +1,147 @@
+// --- IMPORTS ---
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::collision::{simplify, trace};
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::{SimulationGrid, SimulationStats};
+use crate::{MaterialClass, Particle};
+
+// --- CONSTANTS ---
+const EXPORT_FOLDER: &str = "outlines";
+// How far, in cells, the exported outlines may stray from the cells' edges.
+const TOLERANCE: f32 = 0.5;
+
+// --- PLUGIN ---
+
+// Alt+F5 saves the outlines of the world's solids as an SVG, for vector art or for a laser cutter
+// or plotter to follow. Each solid material is traced on its own with the same marching squares
+// and Douglas-Peucker simplification as the collision outlines (see collision.rs), and becomes one
+// path filled with its color, holes cut out, in a picture one unit per cell.
+pub struct SvgExportPlugin;
+
+impl Plugin for SvgExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, export_outlines);
+    }
+}
+
+// --- SYSTEMS ---
+
+fn export_outlines(
+    keys: Res<ButtonInput<KeyCode>>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+) {
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !alt || ctrl_held(&keys) || !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let Some(dir) = user_data_dir().map(|dir| dir.join(EXPORT_FOLDER)) else { return };
+    let path = dir.join(format!("outlines_{}.svg", stats.tick));
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&path, outlines_svg(&grid, TOLERANCE)));
+    match result {
+        Ok(()) => info!("Saved the world's outlines to {:?}", path),
+        Err(err) => warn!("Could not save the world's outlines to {:?}: {}", path, err),
+    }
+}
+
+// --- HELPERS ---
+
+// The world's solids as an SVG document: a path per solid material in the world, named after it,
+// of every outline around its cells simplified to within `tolerance` cells. The world's bottom row
+// is the picture's bottom, and the outlines are filled even-odd, so the loops around air inside a
+// material are holes.
+pub fn outlines_svg(grid: &SimulationGrid, tolerance: f32) -> String {
+    let (width, height) = (grid.width() as i32, grid.height() as i32);
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\">\n",
+        width, height
+    );
+    for particle in Particle::ALL {
+        if particle.class() != MaterialClass::Solid {
+            continue;
+        }
+        // From one square outside the world, so outlines close along its edges.
+        let top_right = IVec2::new(width - 1, height - 1);
+        let outlines = trace(IVec2::NEG_ONE, top_right, |x, y| grid.get(x, y) == Some(particle));
+        let mut path = String::new();
+        for outline in outlines {
+            let outline = simplify(outline, tolerance);
+            if outline.points.len() < 3 {
+                continue;
+            }
+            for (i, point) in outline.points.iter().enumerate() {
+                let command = if i == 0 { 'M' } else { 'L' };
+                let _ = write!(path, "{}{} {} ", command, point.x, height as f32 - point.y);
+            }
+            path.push_str("Z ");
+        }
+        if path.is_empty() {
+            continue;
+        }
+        let [r, g, b] = particle.color().to_srgba().to_u8_array_no_alpha();
+        let _ = writeln!(
+            svg,
+            "  <path id=\"{:?}\" fill=\"#{:02x}{:02x}{:02x}\" fill-rule=\"evenodd\" d=\"{}\"/>",
+            particle,
+            r,
+            g,
+            b,
+            path.trim_end()
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The points of the path with `id`, in the picture's coordinates, and how many loops it has.
+    fn points(svg: &str, id: &str) -> (Vec<Vec2>, usize) {
+        let start = svg.find(&format!("id=\"{}\"", id)).unwrap();
+        let line = svg[start..].lines().next().unwrap();
+        let d = line.split(" d=\"").nth(1).unwrap().trim_end_matches("\"/>");
+        let numbers: Vec<f32> = d
+            .split(|c: char| c.is_whitespace() || "MLZ".contains(c))
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse().unwrap())
+            .collect();
+        let points = numbers.chunks(2).map(|xy| Vec2::new(xy[0], xy[1])).collect();
+        (points, d.matches('M').count())
+    }
+
+    #[test]
+    fn traces_a_path_per_solid_material() {
+        let mut grid = SimulationGrid::new(12, 12);
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            grid.set(x, y, Particle::Bedrock);
+        }
+        // A ring of glass around a hole.
+        for x in 4..9 {
+            for y in 4..9 {
+                if !(5..8).contains(&x) || !(5..8).contains(&y) {
+                    grid.set(x, y, Particle::Glass);
+                }
+            }
+        }
+        grid.set(0, 11, Particle::Water);
+
+        let svg = outlines_svg(&grid, TOLERANCE);
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(!svg.contains("Water"));
+        // The block covers cells (1, 1) to (3, 3), which are 9 to 11 from the top.
+        let (bedrock, loops) = points(&svg, "Bedrock");
+        assert_eq!(loops, 1);
+        let (min, max) = (Vec2::new(1.0, 9.0), Vec2::new(3.0, 11.0));
+        assert!(bedrock.iter().all(|p| p.cmpge(min).all() && p.cmple(max).all()), "{:?}", bedrock);
+        let (glass, loops) = points(&svg, "Glass");
+        assert_eq!(loops, 2, "{:?}", glass);
+    }
+}
@@ -0,0 +1,460 @@
+// --- IMPORTS ---
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::access::SimulationAccess;
+use crate::coords::CellPos;
+use crate::locks::PaintLocks;
+use crate::pan_zoom::ctrl_held;
+use crate::regions::Connectivity;
+use crate::sim::{SimulationGrid, SimulationSet};
+use crate::undo::PaintEdit;
+use crate::world_commands::{AppliedCommand, WorldCommand, WorldCommandSet, WorldCommands};
+use crate::WorldView;
+
+// --- CONSTANTS ---
+const LABEL_COLOR: Color = Color::srgb(0.75, 0.9, 1.0);
+
+// --- PLUGIN ---
+
+// Tags name groups of cells, like "dam wall", so they can be dealt with all at once. Ctrl+T
+// opens the tags window to add tags and pick the one to tag with; while it is open, Alt+T tags
+// the region outlined under the cursor (Alt+Shift+T: the whole material class). A tag's cells
+// can then all be deleted, turned into another material, frozen in place or counted on screen.
+// Tags are kept per cell beside the grid (see `CellTags`), go along with the cells as they move
+// and are saved with the world. Every change to them is a `WorldCommand::Tags`, so it goes in
+// order with everything else's, deleting and converting keep off locked cells and can be undone,
+// and a level's world can't be changed by tag.
+pub struct TagsPlugin;
+
+impl Plugin for TagsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TagTool>()
+            .add_systems(Startup, spawn_tag_label)
+            .add_systems(Update, (toggle_tags, tag_regions).chain().before(WorldCommandSet))
+            .add_systems(Update, follow_tag_edits.after(WorldCommandSet))
+            .add_systems(Update, update_tag_label.after(SimulationSet))
+            .add_systems(EguiContextPass, draw_tags);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    // Frozen cells hold still: they don't move, and nothing moves into them. They still react,
+    // burn and melt.
+    #[serde(default)]
+    pub frozen: bool,
+    // Counted on screen.
+    #[serde(default)]
+    pub in_hud: bool,
+}
+
+// The world's tags and which cell carries which, as a sparse map from cell index (in grid order)
+// to tag, since most cells carry none. A cell has at most one tag. Cells keep their tag as they
+// move and transmute, and lose it when painted over or burnt away to air.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CellTags {
+    pub tags: Vec<Tag>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    cells: BTreeMap<u32, u16>,
+}
+
+impl CellTags {
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn add(&mut self, name: String) -> usize {
+        self.tags.push(Tag {
+            name,
+            frozen: false,
+            in_hud: false,
+        });
+        self.tags.len() - 1
+    }
+
+    // Removes the tag, and with it the tag from its cells.
+    pub fn remove(&mut self, tag: usize) -> Tag {
+        let removed = self.tags.remove(tag);
+        let tag = tag as u16;
+        self.cells.retain(|_, cell| *cell != tag);
+        for cell in self.cells.values_mut() {
+            if *cell > tag {
+                *cell -= 1;
+            }
+        }
+        removed
+    }
+
+    // The tag of the cell at `index`, in grid order.
+    pub fn get(&self, index: usize) -> Option<usize> {
+        self.cells.get(&(index as u32)).map(|&tag| tag as usize)
+    }
+
+    // Tags the cell at `index`, or with `None` untags it. Tags that don't exist are ignored.
+    pub fn set(&mut self, index: usize, tag: Option<usize>) {
+        match tag {
+            Some(tag) if tag < self.tags.len() => {
+                self.cells.insert(index as u32, tag as u16);
+            }
+            Some(_) => {}
+            None => {
+                self.cells.remove(&(index as u32));
+            }
+        }
+    }
+
+    // Every tagged cell's index and tag, in grid order.
+    pub fn marks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.cells.iter().map(|(&index, &tag)| (index as usize, tag as usize))
+    }
+
+    pub fn cells_of(&self, tag: usize) -> impl Iterator<Item = usize> + '_ {
+        self.marks().filter(move |&(_, of)| of == tag).map(|(index, _)| index)
+    }
+
+    pub fn count(&self, tag: usize) -> usize {
+        self.cells_of(tag).count()
+    }
+
+    // Whether any tag is frozen, so the simulation only looks cells up when one is.
+    pub fn freezes(&self) -> bool {
+        self.tags.iter().any(|tag| tag.frozen)
+    }
+
+    pub fn is_frozen(&self, index: usize) -> bool {
+        self.get(index).is_some_and(|tag| self.tags[tag].frozen)
+    }
+
+    // Exchanges the tags of two cells, as the grid swaps them.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if self.cells.is_empty() {
+            return;
+        }
+        let (tag_a, tag_b) = (self.get(a), self.get(b));
+        if tag_a != tag_b {
+            self.set(a, tag_b);
+            self.set(b, tag_a);
+        }
+    }
+
+    // Removes every tag.
+    pub fn clear(&mut self) {
+        self.tags.clear();
+        self.cells.clear();
+    }
+}
+
+// What the tags window and Alt+T ask of the world's tags, by way of `WorldCommand::Tags`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Serialize, Deserialize))]
+pub enum TagOperation {
+    Add(String),
+    Tag(usize, Vec<CellPos>),
+    // Turns the tag's cells into air.
+    Delete(usize),
+    Convert(usize, Particle),
+    Untag(usize),
+    Remove(usize),
+    Freeze(usize, bool),
+    ShowInHud(usize, bool),
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct TagTool {
+    open: bool,
+    // The name typed for a new tag.
+    name: String,
+    // The tag Alt+T tags with.
+    active: Option<usize>,
+    // What Convert turns a tag's cells into.
+    convert_to: Particle,
+}
+
+impl Default for TagTool {
+    fn default() -> Self {
+        Self {
+            open: false,
+            name: String::new(),
+            active: None,
+            convert_to: Particle::Bedrock,
+        }
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct TagLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_tag_label(mut commands: Commands) {
+    commands.spawn((
+        TagLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(64.0),
+            left: Val::Px(5.0),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(LABEL_COLOR),
+    ));
+}
+
+fn toggle_tags(keys: Res<ButtonInput<KeyCode>>, mut tool: ResMut<TagTool>) {
+    if ctrl_held(&keys) && keys.just_pressed(KeyCode::KeyT) {
+        tool.open = !tool.open;
+    }
+}
+
+// Alt+T tags the region outlined under the cursor with the active tag, Alt+Shift+T the region of
+// its whole material class.
+fn tag_regions(
+    keys: Res<ButtonInput<KeyCode>>,
+    tool: Res<TagTool>,
+    view: WorldView,
+    mut access: SimulationAccess,
+    mut commands: ResMut<WorldCommands>,
+) {
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !tool.open || !alt || !keys.just_pressed(KeyCode::KeyT) {
+        return;
+    }
+    let Some(tag) = tool.active else {
+        info!("Pick a tag in the tags window to tag with");
+        return;
+    };
+    let Some(cell) = view.cursor_cell() else { return };
+    let region = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        access.connected_region_by(cell, Connectivity::Class)
+    } else {
+        access.connected_region(cell)
+    };
+    if let Some(region) = region {
+        commands.push(WorldCommand::Tags(TagOperation::Tag(tag, region.cells)));
+    }
+}
+
+// Keeps the tag picked in the tags window the same one as tags are added and removed: a new tag
+// is picked to tag with straight away.
+fn follow_tag_edits(
+    mut applied: EventReader<AppliedCommand>,
+    grid: Res<SimulationGrid>,
+    mut tool: ResMut<TagTool>,
+) {
+    for AppliedCommand { command, .. } in applied.read() {
+        match command {
+            WorldCommand::Tags(TagOperation::Add(name)) => {
+                tool.active = grid.tags().tags.iter().rposition(|tag| tag.name == *name);
+            }
+            &WorldCommand::Tags(TagOperation::Remove(tag)) => {
+                tool.active = match tool.active {
+                    Some(active) if active == tag => None,
+                    Some(active) if active > tag => Some(active - 1),
+                    active => active,
+                };
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw_tags(
+    mut contexts: EguiContexts,
+    mut tool: ResMut<TagTool>,
+    grid: Res<SimulationGrid>,
+    mut commands: ResMut<WorldCommands>,
+) {
+    if !tool.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let TagTool {
+        open,
+        name,
+        active,
+        convert_to,
+    } = &mut *tool;
+    let tags = grid.tags();
+    egui::Window::new("Tags").open(open).resizable(false).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(name);
+            let named = !name.trim().is_empty();
+            if ui.add_enabled(named, egui::Button::new("Add")).clicked() {
+                commands.push(WorldCommand::Tags(TagOperation::Add(name.trim().to_string())));
+                name.clear();
+            }
+        });
+        if tags.is_empty() {
+            ui.weak("No tags yet. Add one, then Alt+T tags the region under the cursor.");
+            return;
+        }
+        egui::Grid::new("tags").num_columns(4).show(ui, |ui| {
+            for (i, tag) in tags.tags.iter().enumerate() {
+                let label = format!("{} ({} cells)", tag.name, tags.count(i));
+                ui.radio_value(active, Some(i), label);
+                let mut frozen = tag.frozen;
+                if ui.checkbox(&mut frozen, "Frozen").changed() {
+                    commands.push(WorldCommand::Tags(TagOperation::Freeze(i, frozen)));
+                }
+                let mut in_hud = tag.in_hud;
+                if ui.checkbox(&mut in_hud, "In HUD").changed() {
+                    commands.push(WorldCommand::Tags(TagOperation::ShowInHud(i, in_hud)));
+                }
+                if ui.button("Remove").clicked() {
+                    commands.push(WorldCommand::Tags(TagOperation::Remove(i)));
+                }
+                ui.end_row();
+            }
+        });
+
+        let Some(tag) = active.filter(|&tag| tag < tags.tags.len()) else {
+            ui.weak("Pick a tag to tag with and act on.");
+            return;
+        };
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui.button("Delete all").clicked() {
+                commands.push(WorldCommand::Tags(TagOperation::Delete(tag)));
+            }
+            if ui.button("Untag").clicked() {
+                commands.push(WorldCommand::Tags(TagOperation::Untag(tag)));
+            }
+        });
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("tag_convert")
+                .selected_text(format!("{:?}", convert_to))
+                .show_ui(ui, |ui| {
+                    for particle in Particle::ALL {
+                        ui.selectable_value(convert_to, particle, format!("{:?}", particle));
+                    }
+                });
+            if ui.button("Convert").clicked() {
+                commands.push(WorldCommand::Tags(TagOperation::Convert(tag, *convert_to)));
+            }
+        });
+        ui.weak("Alt+T tags the region under the cursor (Alt+Shift+T: its material class).");
+    });
+}
+
+// Counts the cells of every tag shown in the HUD.
+fn update_tag_label(grid: Res<SimulationGrid>, mut q_label: Query<&mut Text, With<TagLabel>>) {
+    let Ok(mut label) = q_label.single_mut() else { return };
+    let tags = grid.tags();
+    let lines: Vec<String> = (0..tags.tags.len())
+        .filter(|&tag| tags.tags[tag].in_hud)
+        .map(|tag| format!("{}: {} cells", tags.tags[tag].name, tags.count(tag)))
+        .collect();
+    let text = lines.join("\n");
+    if label.0 != text {
+        label.0 = text;
+    }
+}
+
+// --- HELPERS ---
+
+// Does `operation` to the world's tags and, deleting or converting, to the tagged cells, apart
+// from locked ones. Returns what that did to the cells, to undo. A level's world is part of the
+// puzzle, so with `protect_world` nothing that changes it happens: no deleting, converting or
+// freezing.
+pub fn edit_tags(
+    grid: &mut SimulationGrid,
+    operation: &TagOperation,
+    protect_world: bool,
+    locks: &PaintLocks,
+) -> PaintEdit {
+    let mut edit = PaintEdit::default();
+    let tag = match operation {
+        TagOperation::Add(name) => {
+            grid.tags_mut().add(name.clone());
+            return edit;
+        }
+        TagOperation::Tag(tag, _)
+        | TagOperation::Delete(tag)
+        | TagOperation::Convert(tag, _)
+        | TagOperation::Untag(tag)
+        | TagOperation::Remove(tag)
+        | TagOperation::Freeze(tag, _)
+        | TagOperation::ShowInHud(tag, _) => *tag,
+    };
+    if tag >= grid.tags().tags.len() {
+        return edit;
+    }
+    let changes_world = matches!(
+        operation,
+        TagOperation::Delete(_) | TagOperation::Convert(..) | TagOperation::Freeze(_, true)
+    );
+    if changes_world && protect_world {
+        info!("A level's cells can't be changed by tag");
+        return edit;
+    }
+    let width = grid.width() as usize;
+    let cells: Vec<CellPos> = grid
+        .tags()
+        .cells_of(tag)
+        .map(|index| CellPos::new((index % width) as i32, (index / width) as i32))
+        .collect();
+    let name = grid.tags().tags[tag].name.clone();
+    // Turns the tagged cells that aren't locked into `particle`, noting them for undoing.
+    let mut change = |grid: &mut SimulationGrid, particle: Particle, transmute: bool| {
+        let mut changed = 0;
+        for &cell in &cells {
+            let Some(old) = grid.get(cell.x, cell.y) else { continue };
+            if locks.is_locked(cell, old) {
+                continue;
+            }
+            let before = grid.cell(cell.x, cell.y);
+            if transmute {
+                grid.transmute(cell.x, cell.y, particle);
+            } else {
+                grid.set(cell.x, cell.y, particle);
+            }
+            edit.note(grid, cell, before);
+            changed += 1;
+        }
+        changed
+    };
+    match operation {
+        TagOperation::Add(_) => {}
+        TagOperation::Tag(_, region) => {
+            for cell in region {
+                grid.set_tag(cell.x, cell.y, Some(tag));
+            }
+            info!("Tagged {} cells '{}'", region.len(), name);
+        }
+        TagOperation::Delete(_) => {
+            let deleted = change(grid, Particle::Air, false);
+            info!("Deleted the {} cells tagged '{}'", deleted, name);
+        }
+        TagOperation::Convert(_, particle) => {
+            let converted = change(grid, *particle, true);
+            info!("Turned the {} cells tagged '{}' into {:?}", converted, name, particle);
+        }
+        TagOperation::Untag(_) => {
+            for cell in &cells {
+                grid.set_tag(cell.x, cell.y, None);
+            }
+        }
+        TagOperation::Remove(_) => {
+            grid.tags_mut().remove(tag);
+        }
+        TagOperation::Freeze(_, frozen) => grid.tags_mut().tags[tag].frozen = *frozen,
+        TagOperation::ShowInHud(_, shown) => grid.tags_mut().tags[tag].in_hud = *shown,
+    }
+    edit
+}
@@ -0,0 +1,364 @@
+// --- IMPORTS ---
+use std::path::PathBuf;
+
+use crate::Particle;
+use crate::behavior::MaterialBehaviors;
+use crate::quality::Quality;
+use crate::reaction_rules::{ReactionRule, ReactionRules, ReactionTable};
+use crate::sim::{SimParams, SimulationGrid, TickSchedule};
+use crate::snapshot::WorldSnapshot;
+
+// --- CONSTANTS ---
+// A world counts as settled once no cell has changed for this many ticks in a row.
+const QUIET_TICKS: u64 = 10;
+
+// The characters `parse_grid` and `to_ascii` draw materials with. Materials not here are drawn
+// as `?` and can be parsed with a legend of one's own.
+pub const LEGEND: &[(char, Particle)] = &[
+    ('.', Particle::Air),
+    ('#', Particle::Bedrock),
+    ('s', Particle::Sand),
+    ('~', Particle::Water),
+    ('g', Particle::Glass),
+    ('*', Particle::Snow),
+    ('i', Particle::Ice),
+    (':', Particle::Dust),
+    ('+', Particle::Salt),
+    ('G', Particle::Goo),
+    ('|', Particle::Rope),
+    ('l', Particle::Lava),
+    ('^', Particle::Steam),
+    ('f', Particle::Fire),
+    ('%', Particle::Smoke),
+    ('o', Particle::Oil),
+    ('p', Particle::Gunpowder),
+    ('t', Particle::Tnt),
+];
+
+// --- WORLDS ---
+
+// Sim-level tests for apps embedding the sandbox, built with `--features test-utils` (as a
+// dev-dependency) and run without an app: worlds are built here, stepped by `Stepper` under the
+// bundled reaction rules exactly as the game steps them, and checked with `count`,
+// `assert_settles_within!` and `assert_conserved!`.
+//
+//     let mut world = WorldBuilder::from_ascii("
+//         #..s..#
+//         #.....#
+//         #######
+//     ").stepper();
+//     let before = world.grid().clone();
+//     assert_settles_within!(world, 100);
+//     assert_conserved!(before, *world.grid(), Particle::Sand);
+//
+// Every run starts from the same seed, so a test that passes once passes every time.
+#[derive(Clone)]
+pub struct WorldBuilder {
+    grid: SimulationGrid,
+}
+
+impl WorldBuilder {
+    // An empty world, all air.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            grid: SimulationGrid::new(width, height),
+        }
+    }
+
+    // The world `parse_grid` reads from `picture`.
+    pub fn from_ascii(picture: &str) -> Self {
+        Self {
+            grid: parse_grid(picture),
+        }
+    }
+
+    // A bedrock floor and walls along the world's bottom, left and right edges.
+    pub fn boxed(mut self) -> Self {
+        let (width, height) = (self.grid.width() as i32, self.grid.height() as i32);
+        for x in 0..width {
+            self.grid.set(x, 0, Particle::Bedrock);
+        }
+        for y in 0..height {
+            self.grid.set(0, y, Particle::Bedrock);
+            self.grid.set(width - 1, y, Particle::Bedrock);
+        }
+        self
+    }
+
+    // A block of `particle` from `min` up to `max`, exclusive.
+    pub fn fill(mut self, particle: Particle, min: (i32, i32), max: (i32, i32)) -> Self {
+        for y in min.1..max.1 {
+            for x in min.0..max.0 {
+                self.grid.set(x, y, particle);
+            }
+        }
+        self
+    }
+
+    pub fn cell(mut self, x: i32, y: i32, particle: Particle) -> Self {
+        self.grid.set(x, y, particle);
+        self
+    }
+
+    // The seed the world's dice are rolled with; 0 unless set.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.grid.set_seed(seed);
+        self
+    }
+
+    pub fn build(self) -> SimulationGrid {
+        self.grid
+    }
+
+    pub fn snapshot(self) -> WorldSnapshot {
+        WorldSnapshot::from_grid(&self.grid)
+    }
+
+    // The world ready to step under the bundled rules.
+    pub fn stepper(self) -> Stepper {
+        Stepper::new(self.grid)
+    }
+}
+
+// Reads a picture of a world drawn with LEGEND, top row first, one character per cell. Blank
+// lines and the whitespace around each row are skipped, so pictures can be indented in raw
+// strings. Panics on rows of different lengths and characters not in the legend.
+pub fn parse_grid(picture: &str) -> SimulationGrid {
+    parse_grid_with(picture, LEGEND)
+}
+
+pub fn parse_grid_with(picture: &str, legend: &[(char, Particle)]) -> SimulationGrid {
+    let rows: Vec<&str> = picture.lines().map(str::trim).filter(|row| !row.is_empty()).collect();
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    assert!(
+        rows.iter().all(|row| row.chars().count() == width),
+        "every row of the picture should be {} cells wide:\n{}",
+        width,
+        rows.join("\n")
+    );
+    let mut grid = SimulationGrid::new(width as u32, rows.len() as u32);
+    for (y, row) in rows.iter().rev().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let Some(&(_, particle)) = legend.iter().find(|(drawn, _)| *drawn == c) else {
+                panic!("{:?} at ({}, {}) isn't in the legend", c, x, y);
+            };
+            grid.set(x as i32, y as i32, particle);
+        }
+    }
+    grid
+}
+
+// The world drawn with LEGEND, top row first, as `parse_grid` reads it.
+pub fn to_ascii(grid: &SimulationGrid) -> String {
+    let mut picture = String::new();
+    for y in (0..grid.height() as i32).rev() {
+        for x in 0..grid.width() as i32 {
+            let particle = grid.get(x, y).unwrap_or_default();
+            let drawn = LEGEND.iter().find(|(_, p)| *p == particle);
+            picture.push(drawn.map_or('?', |&(c, _)| c));
+        }
+        picture.push('\n');
+    }
+    picture
+}
+
+// How many cells hold `particle`.
+pub fn count(grid: &SimulationGrid, particle: Particle) -> usize {
+    grid.cells().iter().filter(|&&cell| cell == particle).count()
+}
+
+// How many cells hold anything but air.
+pub fn occupied(grid: &SimulationGrid) -> usize {
+    grid.cells().iter().filter(|&&cell| cell != Particle::Air).count()
+}
+
+// The rules the game ships with, compiled into the build so tests don't depend on where they run.
+pub fn bundled_rules() -> ReactionTable {
+    ReactionTable::compile(&bundled_rule_list())
+}
+
+// The same rules as written, before compiling, for what keeps them that way (replays).
+pub fn bundled_rule_list() -> Vec<ReactionRule> {
+    let text = include_str!("../assets/reactions/builtin.reactions.ron");
+    let rules: ReactionRules = ron::from_str(text).expect("the bundled reaction rules parse");
+    rules.rules
+}
+
+// A scratch folder of its own for a test that reads and writes files, removed when it is dropped.
+// `test` names it, so give every test a different one.
+pub struct Scratch(PathBuf);
+
+impl Scratch {
+    pub fn new(test: &str) -> Self {
+        let name = format!("falling-sand-{}-{}", test, std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&dir).expect("the scratch folder can be made");
+        Self(dir)
+    }
+
+    // Where `file` goes in the folder.
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// --- STEPPING ---
+
+// A world stepped tick by tick at full quality, the way `--headless` runs it: the default
+// parameters unless given others, and the bundled rules unless given others, without mods.
+pub struct Stepper {
+    grid: SimulationGrid,
+    tick: u64,
+    params: SimParams,
+    schedule: TickSchedule,
+    reactions: ReactionTable,
+    behaviors: MaterialBehaviors,
+}
+
+impl Stepper {
+    pub fn new(grid: SimulationGrid) -> Self {
+        Self {
+            grid,
+            tick: 0,
+            params: SimParams::default(),
+            schedule: Quality::Ultra.schedule(),
+            reactions: bundled_rules(),
+            behaviors: MaterialBehaviors::default(),
+        }
+    }
+
+    pub fn with_params(mut self, params: SimParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn with_reactions(mut self, reactions: ReactionTable) -> Self {
+        self.reactions = reactions;
+        self
+    }
+
+    pub fn step(&mut self) {
+        let Self { grid, tick, params, schedule, reactions, behaviors } = self;
+        crate::sim::step(grid, *tick, params, schedule, reactions, behaviors);
+        *tick += 1;
+    }
+
+    pub fn run(&mut self, ticks: u64) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    // Steps until no cell has changed for QUIET_TICKS ticks in a row, or `max_ticks` have gone by.
+    // Returns how many ticks it took to come to rest, if it did.
+    pub fn settle(&mut self, max_ticks: u64) -> Option<u64> {
+        let mut last = self.grid.cells().to_vec();
+        let mut quiet = 0;
+        for ticks in 1..=max_ticks {
+            self.step();
+            if self.grid.cells() == last.as_slice() {
+                quiet += 1;
+                if quiet == QUIET_TICKS {
+                    return Some(ticks - QUIET_TICKS);
+                }
+            } else {
+                quiet = 0;
+                last.copy_from_slice(self.grid.cells());
+            }
+        }
+        None
+    }
+
+    // How many ticks have been stepped.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn grid(&self) -> &SimulationGrid {
+        &self.grid
+    }
+
+    pub fn grid_mut(&mut self) -> &mut SimulationGrid {
+        &mut self.grid
+    }
+
+    pub fn into_grid(self) -> SimulationGrid {
+        self.grid
+    }
+}
+
+// --- ASSERTIONS ---
+
+// Fails unless the `Stepper` comes to rest within `ticks` ticks, showing the world as it was left.
+#[macro_export]
+macro_rules! assert_settles_within {
+    ($stepper:expr, $ticks:expr $(,)?) => {{
+        let stepper: &mut $crate::test_utils::Stepper = &mut $stepper;
+        let ticks: u64 = $ticks;
+        if stepper.settle(ticks).is_none() {
+            panic!(
+                "the world was still moving after {} ticks:\n{}",
+                ticks,
+                $crate::test_utils::to_ascii(stepper.grid())
+            );
+        }
+    }};
+}
+
+// Fails unless two worlds hold as many cells of each material given, or, with none given, as
+// many cells that aren't air.
+#[macro_export]
+macro_rules! assert_conserved {
+    ($before:expr, $after:expr $(,)?) => {{
+        let before: &$crate::SimulationGrid = &$before;
+        let after: &$crate::SimulationGrid = &$after;
+        let (before, after) =
+            ($crate::test_utils::occupied(before), $crate::test_utils::occupied(after));
+        assert_eq!(before, after, "cells that aren't air: {} before, {} after", before, after);
+    }};
+    ($before:expr, $after:expr, $($particle:expr),+ $(,)?) => {{
+        let before: &$crate::SimulationGrid = &$before;
+        let after: &$crate::SimulationGrid = &$after;
+        $(
+            let particle: $crate::Particle = $particle;
+            let (was, is) = (
+                $crate::test_utils::count(before, particle),
+                $crate::test_utils::count(after, particle),
+            );
+            assert_eq!(was, is, "{:?} cells: {} before, {} after", particle, was, is);
+        )+
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sand_settles_and_is_conserved() {
+        let picture = "
+            #......#
+            #.sss..#
+            #.sss..#
+            #......#
+            #......#
+            ########
+        ";
+        let mut world = WorldBuilder::from_ascii(picture).stepper();
+        let drawn: String = picture.split_whitespace().collect();
+        assert_eq!(to_ascii(world.grid()).replace('\n', ""), drawn);
+        let before = world.grid().clone();
+        assert_settles_within!(world, 200);
+        assert_conserved!(before, *world.grid(), Particle::Sand, Particle::Bedrock);
+        assert_conserved!(before, *world.grid());
+        // It has all come down to the floor.
+        assert!((0..8).all(|x| world.grid().get(x, 4) != Some(Particle::Sand)));
+        assert_eq!(count(world.grid(), Particle::Sand), 6);
+    }
+}
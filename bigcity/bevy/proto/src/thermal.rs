@@ -0,0 +1,207 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimulationGrid, SimulationSet};
+
+// --- CONSTANTS ---
+// The range the thermal view uses while auto-scaling is off, in degrees Celsius.
+const FIXED_RANGE: (f32, f32) = (0.0, 500.0);
+// Auto-scaling never spreads the colormap over less than this many degrees, so a world at room
+// temperature doesn't flicker through the whole palette.
+const MIN_AUTO_SPAN: f32 = 10.0;
+const LEGEND_STEPS: u32 = 256;
+
+// Polynomial fit of matplotlib's inferno colormap, in sRGB, lowest order first. The shader uses
+// the same coefficients.
+const INFERNO: [[f32; 3]; 7] = [
+    [0.000219, 0.001651, -0.0194809],
+    [0.106513, 0.563956, 3.93271],
+    [11.6025, -3.97285, -15.9424],
+    [-41.704, 17.4364, 44.3541],
+    [77.1629, -33.4024, -81.8073],
+    [-71.3194, 32.6261, 73.2095],
+    [25.1311, -12.2427, -23.0703],
+];
+
+// --- PLUGIN ---
+pub struct ThermalPlugin;
+
+impl Plugin for ThermalPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThermalView>()
+            .add_systems(Startup, spawn_legend)
+            .add_systems(
+                Update,
+                (toggle_thermal_view, update_legend.after(SimulationSet)).chain(),
+            );
+    }
+}
+
+// --- RESOURCES ---
+
+// Whether the world is drawn by temperature instead of by material (H), and how temperatures map
+// onto the colormap (Shift+H switches between the two).
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ThermalView {
+    pub enabled: bool,
+    pub range: ThermalRange,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+pub enum ThermalRange {
+    // Stretch the colormap between the coldest and hottest cell in the world.
+    #[default]
+    Auto,
+    Fixed { min: f32, max: f32 },
+}
+
+impl ThermalView {
+    // The temperatures drawn as the bottom and top of the colormap, or None while the view is off.
+    pub fn scale(&self, grid: &SimulationGrid) -> Option<(f32, f32)> {
+        if !self.enabled {
+            return None;
+        }
+        let (min, max) = match self.range {
+            ThermalRange::Fixed { min, max } => (min, max),
+            ThermalRange::Auto => grid
+                .temperatures()
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &t| (lo.min(t), hi.max(t))),
+        };
+        let max = max.max(min + MIN_AUTO_SPAN);
+        Some((min, max))
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct ThermalLegend;
+
+#[derive(Component)]
+enum LegendLabel {
+    Max,
+    Min,
+}
+
+// --- SYSTEMS ---
+
+fn spawn_legend(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    // One pixel per step, hottest at the top.
+    let data = (0..LEGEND_STEPS)
+        .rev()
+        .flat_map(|i| {
+            inferno(i as f32 / (LEGEND_STEPS - 1) as f32).to_srgba().to_u8_array()
+        })
+        .collect();
+    let gradient = images.add(Image::new(
+        Extent3d {
+            width: 1,
+            height: LEGEND_STEPS,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    ));
+
+    let label = |which| {
+        (
+            which,
+            Text::default(),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )
+    };
+    commands
+        .spawn((
+            ThermalLegend,
+            Name::new("thermal_legend"),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(30.0),
+                right: Val::Px(5.0),
+                padding: UiRect::all(Val::Px(6.0)),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                display: Display::None,
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        ))
+        .with_children(|legend| {
+            legend.spawn(label(LegendLabel::Max));
+            legend.spawn((
+                ImageNode::new(gradient),
+                Node {
+                    width: Val::Px(16.0),
+                    height: Val::Px(200.0),
+                    ..default()
+                },
+            ));
+            legend.spawn(label(LegendLabel::Min));
+        });
+}
+
+fn toggle_thermal_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<ThermalView>) {
+    // Ctrl+H opens the material reference.
+    if !keys.just_pressed(KeyCode::KeyH) || ctrl_held(&keys) {
+        return;
+    }
+    if !keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        view.enabled = !view.enabled;
+        return;
+    }
+    view.range = match view.range {
+        ThermalRange::Auto => ThermalRange::Fixed {
+            min: FIXED_RANGE.0,
+            max: FIXED_RANGE.1,
+        },
+        ThermalRange::Fixed { .. } => ThermalRange::Auto,
+    };
+    info!("Thermal view range: {:?}", view.range);
+}
+
+fn update_legend(
+    view: Res<ThermalView>,
+    grid: Res<SimulationGrid>,
+    mut q_legend: Query<&mut Node, With<ThermalLegend>>,
+    mut q_labels: Query<(&LegendLabel, &mut Text)>,
+) {
+    let Ok(mut node) = q_legend.single_mut() else { return };
+    let Some((min, max)) = view.scale(&grid) else {
+        node.display = Display::None;
+        return;
+    };
+    node.display = Display::Flex;
+
+    let mode = match view.range {
+        ThermalRange::Auto => "auto",
+        ThermalRange::Fixed { .. } => "fixed",
+    };
+    for (which, mut text) in &mut q_labels {
+        text.0 = match which {
+            LegendLabel::Max => format!("{:.0} C", max),
+            LegendLabel::Min => format!("{:.0} C\n({})", min, mode),
+        };
+    }
+}
+
+// --- HELPERS ---
+
+// Samples the inferno colormap at `t` in 0..=1.
+pub fn inferno(t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |c: usize| INFERNO.iter().rev().fold(0.0, |acc, k| acc * t + k[c]).clamp(0.0, 1.0);
+    Color::srgb(channel(0), channel(1), channel(2))
+}
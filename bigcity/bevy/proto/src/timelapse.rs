@@ -0,0 +1,344 @@
+// --- IMPORTS ---
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::Particle;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::user_data_dir;
+use crate::sim::{SimulationGrid, SimulationSet, SimulationStats};
+
+// --- CONSTANTS ---
+const EXPORT_FOLDER: &str = "timelapse";
+const PREVIEW_SIZE: f32 = 256.0;
+const THUMBNAIL_SIZE: f32 = 48.0;
+// How many thumbnails the filmstrip shows around the selected frame.
+const VISIBLE_THUMBNAILS: usize = 9;
+const SELECTED_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+
+// --- PLUGIN ---
+pub struct TimelapsePlugin;
+
+impl Plugin for TimelapsePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimelapseSettings>()
+            .init_resource::<Timelapse>()
+            .add_systems(Startup, spawn_filmstrip)
+            .add_systems(
+                Update,
+                (
+                    toggle_recording,
+                    capture_keyframe,
+                    toggle_filmstrip,
+                    scrub_filmstrip,
+                    export_sprite_sheet,
+                    update_filmstrip,
+                )
+                    .chain()
+                    .after(SimulationSet),
+            );
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct TimelapseSettings {
+    // A keyframe is captured every this many simulation ticks while recording.
+    pub interval_ticks: u64,
+    // Each keyframe pixel summarizes a square of this many cells per side.
+    pub downsample: u32,
+    // The oldest keyframes are dropped once the strip holds this many.
+    pub max_frames: usize,
+}
+
+impl Default for TimelapseSettings {
+    fn default() -> Self {
+        Self {
+            interval_ticks: 120,
+            downsample: 4,
+            max_frames: 240,
+        }
+    }
+}
+
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct Keyframe {
+    pub tick: u64,
+    // Kept on the CPU as well, so the strip can be exported.
+    pub image: Handle<Image>,
+}
+
+// The in-memory filmstrip of downsampled keyframes and the viewer's state.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct Timelapse {
+    pub recording: bool,
+    pub frames: Vec<Keyframe>,
+    viewing: bool,
+    selected: usize,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct Filmstrip;
+
+// --- SYSTEMS ---
+
+fn spawn_filmstrip(mut commands: Commands) {
+    commands.spawn((
+        Filmstrip,
+        Name::new("filmstrip"),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(5.0),
+            width: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(6.0),
+            display: Display::None,
+            ..default()
+        },
+    ));
+}
+
+fn toggle_recording(keys: Res<ButtonInput<KeyCode>>, mut timelapse: ResMut<Timelapse>) {
+    // Ctrl+T and Alt+T are the tags'.
+    let alt = keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    if !keys.just_pressed(KeyCode::KeyT) || ctrl_held(&keys) || alt {
+        return;
+    }
+    timelapse.recording = !timelapse.recording;
+    info!(
+        "Time-lapse recording {}",
+        if timelapse.recording { "started" } else { "stopped" }
+    );
+}
+
+fn capture_keyframe(
+    settings: Res<TimelapseSettings>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    mut timelapse: ResMut<Timelapse>,
+    mut images: ResMut<Assets<Image>>,
+    mut last_tick: Local<u64>,
+) {
+    // The simulation advances zero or several ticks per frame, so look for a new multiple of the
+    // interval since the last keyframe rather than an exact one.
+    let every = settings.interval_ticks.max(1);
+    if !timelapse.recording || stats.tick / every == *last_tick / every {
+        return;
+    }
+    *last_tick = stats.tick;
+
+    let image = images.add(downsample(&grid, settings.downsample.max(1)));
+    timelapse.frames.push(Keyframe {
+        tick: stats.tick,
+        image,
+    });
+
+    let excess = timelapse.frames.len().saturating_sub(settings.max_frames.max(1));
+    timelapse.frames.drain(..excess);
+    // Follow the newest frame unless the viewer is scrubbing through older ones.
+    if !timelapse.viewing {
+        timelapse.selected = timelapse.frames.len() - 1;
+    }
+    timelapse.selected = timelapse.selected.saturating_sub(excess);
+}
+
+fn toggle_filmstrip(keys: Res<ButtonInput<KeyCode>>, mut timelapse: ResMut<Timelapse>) {
+    if keys.just_pressed(KeyCode::F6) {
+        timelapse.viewing = !timelapse.viewing;
+    }
+}
+
+fn scrub_filmstrip(keys: Res<ButtonInput<KeyCode>>, mut timelapse: ResMut<Timelapse>) {
+    if !timelapse.viewing || timelapse.frames.is_empty() {
+        return;
+    }
+    let step = if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) { 10 } else { 1 };
+    let last = timelapse.frames.len() - 1;
+    let selected = timelapse.selected;
+
+    let target = if keys.just_pressed(KeyCode::ArrowLeft) {
+        selected.saturating_sub(step)
+    } else if keys.just_pressed(KeyCode::ArrowRight) {
+        (selected + step).min(last)
+    } else if keys.just_pressed(KeyCode::Home) {
+        0
+    } else if keys.just_pressed(KeyCode::End) {
+        last
+    } else {
+        return;
+    };
+    timelapse.selected = target;
+}
+
+fn export_sprite_sheet(
+    keys: Res<ButtonInput<KeyCode>>,
+    timelapse: Res<Timelapse>,
+    images: Res<Assets<Image>>,
+) {
+    // Ctrl+F7 exports the world as a scene, in builds that can.
+    if ctrl_held(&keys) || !keys.just_pressed(KeyCode::F7) {
+        return;
+    }
+    let (Some(first), Some(last)) = (timelapse.frames.first(), timelapse.frames.last()) else {
+        warn!("No time-lapse frames to export");
+        return;
+    };
+    let Some(dir) = user_data_dir().map(|dir| dir.join(EXPORT_FOLDER)) else { return };
+    let path = dir.join(format!("sheet_{}-{}.png", first.tick, last.tick));
+
+    let frames: Vec<&Image> = timelapse.frames.iter().filter_map(|f| images.get(&f.image)).collect();
+    let sheet = sprite_sheet(&frames);
+    let result = std::fs::create_dir_all(&dir)
+        .map_err(image::ImageError::IoError)
+        .and_then(|_| sheet.save(&path));
+    match result {
+        Ok(()) => info!("Exported {} time-lapse frames to {:?}", frames.len(), path),
+        Err(err) => warn!("Could not export time-lapse to {:?}: {}", path, err),
+    }
+}
+
+fn update_filmstrip(
+    mut commands: Commands,
+    timelapse: Res<Timelapse>,
+    mut q_filmstrip: Query<(Entity, &mut Node), With<Filmstrip>>,
+) {
+    if !timelapse.is_changed() {
+        return;
+    }
+    let Ok((entity, mut node)) = q_filmstrip.single_mut() else { return };
+    commands.entity(entity).despawn_related::<Children>();
+
+    if !timelapse.viewing {
+        node.display = Display::None;
+        return;
+    }
+    node.display = Display::Flex;
+
+    let Some(selected) = timelapse.frames.get(timelapse.selected) else {
+        commands.entity(entity).with_child(Text::new(if timelapse.recording {
+            "Waiting for the first keyframe..."
+        } else {
+            "No keyframes yet. Press T to start recording."
+        }));
+        return;
+    };
+
+    let start = timelapse
+        .selected
+        .saturating_sub(VISIBLE_THUMBNAILS / 2)
+        .min(timelapse.frames.len().saturating_sub(VISIBLE_THUMBNAILS));
+    let visible = timelapse.frames.iter().enumerate().skip(start).take(VISIBLE_THUMBNAILS);
+
+    commands.entity(entity).with_children(|strip| {
+        strip.spawn((
+            ImageNode::new(selected.image.clone()),
+            Node {
+                width: Val::Px(PREVIEW_SIZE),
+                height: Val::Px(PREVIEW_SIZE),
+                ..default()
+            },
+        ));
+        strip.spawn((
+            Text::new(format!(
+                "Frame {}/{} - tick {}",
+                timelapse.selected + 1,
+                timelapse.frames.len(),
+                selected.tick
+            )),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+        ));
+        strip
+            .spawn(Node {
+                column_gap: Val::Px(4.0),
+                ..default()
+            })
+            .with_children(|row| {
+                for (index, frame) in visible {
+                    let mut thumbnail = row.spawn((
+                        ImageNode::new(frame.image.clone()),
+                        Node {
+                            width: Val::Px(THUMBNAIL_SIZE),
+                            height: Val::Px(THUMBNAIL_SIZE),
+                            ..default()
+                        },
+                    ));
+                    if index == timelapse.selected {
+                        thumbnail.insert(Outline::new(Val::Px(2.0), Val::Px(1.0), SELECTED_COLOR));
+                    }
+                }
+            });
+    });
+}
+
+// --- HELPERS ---
+
+// Shrinks the grid by `factor`, coloring each block by its most common particle. Rows are
+// flipped so the image reads top-down like the screen.
+fn downsample(grid: &SimulationGrid, factor: u32) -> Image {
+    let (width, height) = (grid.width() / factor, grid.height() / factor);
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+    for row in (0..height).rev() {
+        for column in 0..width {
+            let mut counts = [0u32; Particle::ALL.len()];
+            for y in row * factor..(row + 1) * factor {
+                for x in column * factor..(column + 1) * factor {
+                    if let Some(particle) = grid.get(x as i32, y as i32) {
+                        counts[particle as usize] += 1;
+                    }
+                }
+            }
+            let dominant = Particle::ALL
+                .into_iter()
+                .max_by_key(|p| counts[*p as usize])
+                .unwrap_or_default();
+            data.extend_from_slice(&dominant.color().to_srgba().to_u8_array());
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width,
+            height,
+            ..default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+// Lays the frames out left to right, top to bottom, in a roughly square grid.
+fn sprite_sheet(frames: &[&Image]) -> image::RgbaImage {
+    let Some(first) = frames.first() else { return image::RgbaImage::new(0, 0) };
+    let (width, height) = (first.width(), first.height());
+    let columns = (frames.len() as f32).sqrt().ceil() as u32;
+    let rows = (frames.len() as u32).div_ceil(columns);
+
+    let mut sheet = image::RgbaImage::new(columns * width, rows * height);
+    for (i, frame) in frames.iter().enumerate() {
+        let Some(data) = frame.data.clone() else { continue };
+        let Some(tile) = image::RgbaImage::from_raw(width, height, data) else { continue };
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        image::imageops::replace(
+            &mut sheet,
+            &tile,
+            (column * width) as i64,
+            (row * height) as i64,
+        );
+    }
+    sheet
+}
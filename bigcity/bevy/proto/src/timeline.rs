@@ -0,0 +1,277 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::coords::CellPos;
+use crate::levels::{ActiveLevel, Level};
+use crate::meteors::spawn_meteor;
+use crate::ron_asset::RonAssetLoader;
+use crate::sim::{SimulationGrid, SimulationStats};
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+use crate::{Particle, WorldLayout};
+
+// --- PLUGIN ---
+
+// Timelines script a scenario by the tick: valves that open and close, walls that vanish, blasts
+// and meteors, each at an exact tick counted from when the timeline started. They run with the
+// simulation's fixed ticks rather than with frames, so a contraption built on one plays out the
+// same on every run and every machine. A level can carry one, which starts with the level, and
+// `--timeline <path>` plays a `.timeline.ron` file from the assets in free play. What they do to
+// the world goes in as `WorldCommand`s, ahead of the tick they are due at, like everything else's
+// changes.
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1);
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            if arg == "--timeline" {
+                path = args.next();
+            }
+        }
+
+        app.init_asset::<Timeline>()
+            .register_asset_loader(RonAssetLoader::<Timeline>::new(&["timeline.ron"]))
+            .insert_resource(TimelineArg(path))
+            .init_resource::<TimelinePlayback>()
+            .add_systems(Startup, load_timeline_arg)
+            // After the levels have set up a new world, before the next tick runs on it.
+            .add_systems(PostUpdate, start_timelines)
+            .add_systems(FixedUpdate, play_timeline.before(WorldCommandSet));
+    }
+}
+
+// --- ASSETS ---
+
+// Emitters that can be opened and closed, and what happens at which tick. Ticks count from the
+// tick the timeline starts at, and positions are in cells, with (0, 0) at the bottom left.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Timeline {
+    #[serde(default)]
+    pub emitters: Vec<Emitter>,
+    pub events: Vec<TimelineEvent>,
+}
+
+// A valve or spout: while open, it fills the air in its rectangle with `particle` every `every`
+// ticks, counted from the timeline's start.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Emitter {
+    pub name: String,
+    pub particle: Particle,
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+    #[serde(default = "every_tick")]
+    pub every: u64,
+    #[serde(default)]
+    pub open: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TimelineEvent {
+    pub tick: u64,
+    pub action: TimelineAction,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum TimelineAction {
+    // Opens or closes the emitter with this name.
+    Open(String),
+    Close(String),
+    // Fills a rectangle (inclusive) with a material; air removes what was there.
+    Fill {
+        particle: Particle,
+        min: (i32, i32),
+        max: (i32, i32),
+    },
+    Heat {
+        min: (i32, i32),
+        max: (i32, i32),
+        degrees: f32,
+    },
+    Explode {
+        center: (f32, f32),
+        radius: f32,
+    },
+    // A meteor's flight runs with frames, not ticks, so only its launch is exact.
+    Meteor {
+        from: (f32, f32),
+        velocity: (f32, f32),
+        payload: Particle,
+    },
+}
+
+// --- RESOURCES ---
+
+// The timeline asked for on the command line, if any.
+#[derive(Resource)]
+struct TimelineArg(Option<String>);
+
+#[derive(Resource)]
+struct TimelineFile(Handle<Timeline>);
+
+#[derive(Resource, Default)]
+struct TimelinePlayback {
+    playing: Option<Playing>,
+    // The level whose timeline plays, with the tick it started at, or None for the file's.
+    level: Option<(AssetId<Level>, u64)>,
+}
+
+struct Playing {
+    timeline: Timeline,
+    start: u64,
+    // The next event to fire, in tick order.
+    next: usize,
+    open: Vec<bool>,
+    // The last tick played, since a tick can be put off to a later fixed step.
+    played: Option<u64>,
+}
+
+impl Playing {
+    fn new(mut timeline: Timeline, start: u64) -> Self {
+        // A stable sort, so events at the same tick fire in the order they are written.
+        timeline.events.sort_by_key(|event| event.tick);
+        for event in &timeline.events {
+            if let TimelineAction::Open(name) | TimelineAction::Close(name) = &event.action
+                && !timeline.emitters.iter().any(|emitter| emitter.name == *name)
+            {
+                warn!("Timeline event at tick {} names no emitter '{}'", event.tick, name);
+            }
+        }
+        let open = timeline.emitters.iter().map(|emitter| emitter.open).collect();
+        Self {
+            timeline,
+            start,
+            next: 0,
+            open,
+            played: None,
+        }
+    }
+
+    fn set_open(&mut self, name: &str, open: bool) {
+        let emitters = self.timeline.emitters.iter();
+        for (_, state) in emitters.zip(&mut self.open).filter(|(emitter, _)| emitter.name == name) {
+            *state = open;
+        }
+    }
+}
+
+// --- SYSTEMS ---
+
+fn load_timeline_arg(
+    mut commands: Commands,
+    arg: Res<TimelineArg>,
+    asset_server: Res<AssetServer>,
+) {
+    if let Some(path) = &arg.0 {
+        commands.insert_resource(TimelineFile(asset_server.load(path.clone())));
+    }
+}
+
+// Plays the timeline of a level that just started, from the tick it started at, and stops it when
+// the level is left. In free play, the command line's timeline plays once, as soon as it has
+// loaded.
+fn start_timelines(
+    mut commands: Commands,
+    active: Res<ActiveLevel>,
+    levels: Res<Assets<Level>>,
+    file: Option<Res<TimelineFile>>,
+    timelines: Res<Assets<Timeline>>,
+    stats: Res<SimulationStats>,
+    mut playback: ResMut<TimelinePlayback>,
+) {
+    let running = active.running().map(|(handle, start)| (handle.id(), start));
+    if running != playback.level {
+        playback.level = running;
+        playback.playing = running
+            .and_then(|(id, start)| Some((levels.get(id)?.timeline.clone()?, start)))
+            .map(|(timeline, start)| Playing::new(timeline, start));
+        if playback.playing.is_some() {
+            info!("Playing the level's timeline");
+        }
+        return;
+    }
+    if running.is_none()
+        && let Some(file) = file
+        && let Some(timeline) = timelines.get(&file.0)
+    {
+        playback.playing = Some(Playing::new(timeline.clone(), stats.tick));
+        commands.remove_resource::<TimelineFile>();
+        info!("Playing the timeline from tick {}", stats.tick);
+    }
+}
+
+// Fires the events due at the tick about to run and lets the open emitters pour, once per tick.
+fn play_timeline(
+    mut commands: Commands,
+    layout: Res<WorldLayout>,
+    stats: Res<SimulationStats>,
+    mut playback: ResMut<TimelinePlayback>,
+    grid: Res<SimulationGrid>,
+    mut world: ResMut<WorldCommands>,
+) {
+    let Some(playing) = &mut playback.playing else { return };
+    if stats.tick < playing.start || playing.played == Some(stats.tick) {
+        return;
+    }
+    playing.played = Some(stats.tick);
+    let tick = stats.tick - playing.start;
+
+    while let Some(event) = playing.timeline.events.get(playing.next)
+        && event.tick <= tick
+    {
+        let action = event.action.clone();
+        playing.next += 1;
+        match action {
+            TimelineAction::Open(name) => playing.set_open(&name, true),
+            TimelineAction::Close(name) => playing.set_open(&name, false),
+            TimelineAction::Fill { particle, min, max } => {
+                let (min, max) = corners(min, max);
+                world.push(WorldCommand::FillRect { min, max, particle });
+            }
+            TimelineAction::Heat { min, max, degrees } => {
+                let (min, max) = corners(min, max);
+                world.push(WorldCommand::Heat { min, max, degrees });
+            }
+            TimelineAction::Explode { center, radius } => {
+                world.push(WorldCommand::Explode {
+                    center: Vec2::from(center),
+                    radius,
+                });
+            }
+            TimelineAction::Meteor { from, velocity, payload } => {
+                spawn_meteor(&mut commands, &layout, from.into(), velocity.into(), payload);
+            }
+        }
+    }
+
+    let emitters = playing.timeline.emitters.iter().zip(&playing.open);
+    for (emitter, _) in emitters.filter(|(_, open)| **open) {
+        if !tick.is_multiple_of(emitter.every.max(1)) {
+            continue;
+        }
+        let (min, max) = corners(emitter.min, emitter.max);
+        let cells: Vec<_> = (min.y..=max.y)
+            .flat_map(|y| (min.x..=max.x).map(move |x| CellPos::new(x, y)))
+            .filter(|cell| grid.get(cell.x, cell.y) == Some(Particle::Air))
+            .collect();
+        if !cells.is_empty() {
+            world.push(WorldCommand::Set {
+                particle: emitter.particle,
+                cells,
+            });
+        }
+    }
+}
+
+// --- HELPERS ---
+
+// The bottom-left and top-right cells of the rectangle with corners `a` and `b`.
+fn corners(a: (i32, i32), b: (i32, i32)) -> (CellPos, CellPos) {
+    let (a, b) = (IVec2::from(a), IVec2::from(b));
+    (CellPos(a.min(b)), CellPos(a.max(b)))
+}
+
+fn every_tick() -> u64 {
+    1
+}
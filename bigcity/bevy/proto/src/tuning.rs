@@ -0,0 +1,314 @@
+// --- IMPORTS ---
+use std::ops::RangeInclusive;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::presets::{ActivePreset, ApplyPreset, Presets, SavePreset};
+use crate::Particle;
+use crate::degradation::DegradationPolicy;
+use crate::quality::Quality;
+use crate::sim::{
+    SimParams, SimulationGrid, SimulationTickRate, Subsystem, Thermostat, TickSchedule,
+};
+use crate::zones::{MaterialOverride, ParamOverrides};
+
+// --- PLUGIN ---
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TuningPanel>()
+            .add_systems(Update, toggle_tuning_panel)
+            .add_systems(EguiContextPass, draw_tuning_panel);
+    }
+}
+
+// --- RESOURCES ---
+
+// Whether the live parameters panel (F4) is open, the name typed for saving a preset and the
+// material whose overrides are shown.
+#[derive(Resource)]
+struct TuningPanel {
+    open: bool,
+    preset_name: String,
+    material: Particle,
+}
+
+impl Default for TuningPanel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            preset_name: String::new(),
+            material: Particle::Water,
+        }
+    }
+}
+
+// --- SYSTEM PARAM ---
+
+// The preset picker: what there is, what is applied and where choices go.
+#[derive(SystemParam)]
+struct PresetMenu<'w> {
+    presets: Presets<'w>,
+    active: Res<'w, ActivePreset>,
+    apply: EventWriter<'w, ApplyPreset>,
+    save: EventWriter<'w, SavePreset>,
+}
+
+// The tick rate, the quality level and the tick schedule it sets, which can be tuned further by
+// hand, and when to shed load.
+#[derive(SystemParam)]
+struct Performance<'w> {
+    tick_rate: ResMut<'w, SimulationTickRate>,
+    quality: ResMut<'w, Quality>,
+    schedule: ResMut<'w, TickSchedule>,
+    shedding: ResMut<'w, DegradationPolicy>,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_tuning_panel(keys: Res<ButtonInput<KeyCode>>, mut panel: ResMut<TuningPanel>) {
+    if keys.just_pressed(KeyCode::F4) {
+        panel.open = !panel.open;
+    }
+}
+
+// Edits a copy of the parameters and only writes it back when something moved, so the rest of
+// the app can tell real changes apart from the panel merely being open.
+fn draw_tuning_panel(
+    mut contexts: EguiContexts,
+    mut panel: ResMut<TuningPanel>,
+    mut params: ResMut<SimParams>,
+    mut performance: Performance,
+    mut menu: PresetMenu,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    if !panel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let TuningPanel {
+        open,
+        preset_name,
+        material,
+    } = &mut *panel;
+    let mut edited = params.clone();
+    let mut tick_rate = *performance.tick_rate;
+    let mut materials = grid.material_overrides().to_vec();
+    let mut quality = *performance.quality;
+    let mut scheduled = performance.schedule.clone();
+    let mut shedding = performance.shedding.clone();
+    egui::Window::new("Simulation parameters")
+        .open(open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new("sim_params").num_columns(2).show(ui, |ui| {
+                ui.label("Gravity (cells / tick)");
+                ui.add(egui::Slider::new(&mut edited.gravity, 0.0..=8.0));
+                ui.end_row();
+
+                ui.label("Dispersion (cells / tick)");
+                ui.add(egui::Slider::new(&mut edited.dispersion, 0..=16));
+                ui.end_row();
+
+                ui.label("Liquid cohesion");
+                ui.add(egui::Slider::new(&mut edited.cohesion, 0.0..=1.0));
+                ui.end_row();
+
+                ui.label("Boil chance");
+                ui.add(egui::Slider::new(&mut edited.boil_chance, 0.0..=1.0));
+                ui.end_row();
+
+                ui.label("Melt chance");
+                ui.add(egui::Slider::new(&mut edited.melt_chance, 0.0..=1.0));
+                ui.end_row();
+
+                ui.label("Heat diffusion");
+                ui.add(egui::Slider::new(&mut edited.heat_diffusion, 0.0..=1.0));
+                ui.end_row();
+
+                ui.label("Cooling rate");
+                ui.add(egui::Slider::new(&mut edited.cooling_rate, 0.0..=0.5));
+                ui.end_row();
+
+                ui.label("Ambient temperature (C)");
+                ui.add(egui::Slider::new(&mut edited.ambient_temperature, -50.0..=200.0));
+                ui.end_row();
+
+                ui.label("Wind (cells / tick)");
+                ui.add(egui::Slider::new(&mut edited.wind, -4.0..=4.0));
+                ui.end_row();
+
+                ui.label("Tick rate (ticks / s)");
+                ui.add(egui::Slider::new(&mut tick_rate.ticks_per_second, 0.0..=240.0));
+                ui.end_row();
+
+                ui.label("Thermostat");
+                let mut thermostat = edited.thermostat.is_some();
+                if ui.checkbox(&mut thermostat, "Drive the whole world's temperature").changed() {
+                    edited.thermostat = thermostat.then(Thermostat::default);
+                }
+                ui.end_row();
+
+                if let Some(thermostat) = &mut edited.thermostat {
+                    ui.label("Thermostat target (C)");
+                    ui.add(egui::Slider::new(&mut thermostat.target, -100.0..=1500.0));
+                    ui.end_row();
+
+                    ui.label("Thermostat rate");
+                    let rate = egui::Slider::new(&mut thermostat.rate, 0.0001..=0.1);
+                    ui.add(rate.logarithmic(true));
+                    ui.end_row();
+                }
+            });
+            if ui.button("Reset to defaults").clicked() {
+                edited = SimParams::default();
+                tick_rate = SimulationTickRate::default();
+            }
+
+            ui.separator();
+            let current = match &menu.active.0 {
+                Some(preset) if preset.params == edited && preset.sets(tick_rate) => {
+                    preset.name.clone()
+                }
+                Some(preset) => format!("{} (modified)", preset.name),
+                None => "Custom".to_string(),
+            };
+            egui::ComboBox::from_label("Preset")
+                .selected_text(current)
+                .show_ui(ui, |ui| {
+                    for preset in menu.presets.all() {
+                        if ui.selectable_label(false, &preset.name).clicked() {
+                            menu.apply.write(ApplyPreset(preset.name.clone()));
+                        }
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(preset_name);
+                if ui.button("Save as preset").clicked() {
+                    menu.save.write(SavePreset(preset_name.clone()));
+                }
+            });
+
+            ui.separator();
+            // Rules one material follows in this world only; they are saved with the world, not
+            // with presets. Unticked parameters follow the global and zone values.
+            ui.collapsing("Material overrides (this world)", |ui| {
+                egui::ComboBox::from_label("Material")
+                    .selected_text(format!("{:?}", material))
+                    .show_ui(ui, |ui| {
+                        for particle in Particle::ALL.into_iter().skip(1) {
+                            ui.selectable_value(material, particle, format!("{:?}", particle));
+                        }
+                    });
+                let index = materials.iter().position(|m| m.material == *material);
+                let mut overrides = index.map_or_else(default, |i| materials[i].overrides.clone());
+                edit_overrides(ui, &mut overrides, &edited);
+                match (index, overrides == ParamOverrides::default()) {
+                    (Some(index), true) => {
+                        materials.remove(index);
+                    }
+                    (Some(index), false) => materials[index].overrides = overrides,
+                    (None, false) => materials.push(MaterialOverride {
+                        material: *material,
+                        overrides,
+                    }),
+                    (None, true) => {}
+                }
+                if !materials.is_empty() {
+                    let names: Vec<_> =
+                        materials.iter().map(|m| format!("{:?}", m.material)).collect();
+                    ui.label(format!("Overridden here: {}", names.join(", ")));
+                }
+            });
+
+            ui.separator();
+            // Cheaper, coarser ticks for slow machines.
+            ui.collapsing("Scheduling", |ui| {
+                let tuned = if scheduled != quality.schedule() { " (modified)" } else { "" };
+                egui::ComboBox::from_label("Quality")
+                    .selected_text(format!("{:?}{}", quality, tuned))
+                    .show_ui(ui, |ui| {
+                        for level in Quality::ALL {
+                            // Picking the current level again undoes the hand tuning.
+                            let label = ui.selectable_label(level == quality, format!("{:?}", level));
+                            if label.clicked() {
+                                quality = level;
+                                scheduled = level.schedule();
+                            }
+                        }
+                    });
+                egui::Grid::new("tick_schedule").num_columns(2).show(ui, |ui| {
+                    for subsystem in Subsystem::ALL {
+                        let cadence = scheduled.cadence_mut(subsystem);
+                        ui.checkbox(&mut cadence.enabled, format!("{:?}", subsystem));
+                        let every = egui::Slider::new(&mut cadence.every, 1..=8).prefix("every ");
+                        ui.add_enabled(cadence.enabled, every.suffix(" ticks"));
+                        ui.end_row();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut shedding.enabled, "Shed load over");
+                    let target = egui::Slider::new(&mut shedding.target_ms, 10.0..=50.0);
+                    ui.add_enabled(shedding.enabled, target.suffix(" ms / frame"));
+                });
+            });
+        });
+
+    if edited != *params {
+        *params = edited;
+    }
+    if tick_rate != *performance.tick_rate {
+        *performance.tick_rate = tick_rate;
+    }
+    if quality != *performance.quality {
+        *performance.quality = quality;
+    }
+    if scheduled != *performance.schedule {
+        *performance.schedule = scheduled;
+    }
+    if shedding != *performance.shedding {
+        *performance.shedding = shedding;
+    }
+    if materials != grid.material_overrides() {
+        grid.set_material_overrides(materials);
+    }
+}
+
+// --- HELPERS ---
+
+fn edit_overrides(ui: &mut egui::Ui, overrides: &mut ParamOverrides, global: &SimParams) {
+    let (o, g) = (overrides, global);
+    egui::Grid::new("material_overrides").num_columns(2).show(ui, |ui| {
+        override_row(ui, "Gravity", &mut o.gravity, g.gravity, 0.0..=8.0);
+        override_row(ui, "Dispersion", &mut o.dispersion, g.dispersion, 0..=16);
+        override_row(ui, "Cohesion", &mut o.cohesion, g.cohesion, 0.0..=1.0);
+        override_row(ui, "Boil chance", &mut o.boil_chance, g.boil_chance, 0.0..=1.0);
+        override_row(ui, "Melt chance", &mut o.melt_chance, g.melt_chance, 0.0..=1.0);
+        override_row(ui, "Heat diffusion", &mut o.heat_diffusion, g.heat_diffusion, 0.0..=1.0);
+        override_row(ui, "Cooling rate", &mut o.cooling_rate, g.cooling_rate, 0.0..=0.5);
+        let ambient = g.ambient_temperature;
+        override_row(ui, "Ambient (C)", &mut o.ambient_temperature, ambient, -50.0..=200.0);
+        override_row(ui, "Wind", &mut o.wind, g.wind, -4.0..=4.0);
+    });
+}
+
+// A parameter a material may override: ticked, it follows the slider; unticked, the slider shows
+// the global value it follows instead.
+fn override_row<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<T>,
+    global: T,
+    range: RangeInclusive<T>,
+) {
+    let mut overridden = value.is_some();
+    ui.checkbox(&mut overridden, label);
+    let mut shown = value.unwrap_or(global);
+    ui.add_enabled(overridden, egui::Slider::new(&mut shown, range));
+    *value = overridden.then_some(shown);
+    ui.end_row();
+}
@@ -0,0 +1,325 @@
+// --- IMPORTS ---
+use bevy::asset::LoadedFolder;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::ron_asset::RonAssetLoader;
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+pub const TUTORIAL_FOLDER: &str = "tutorials";
+const HIGHLIGHT_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+const FINISHED_MESSAGE_SECS: f32 = 3.0;
+
+// --- PLUGIN ---
+pub struct TutorialPlugin;
+
+impl Plugin for TutorialPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Tutorial>()
+            .register_asset_loader(RonAssetLoader::<Tutorial>::new(&["tutorial.ron"]))
+            .init_resource::<ActiveTutorial>()
+            .add_systems(Startup, (load_tutorials, spawn_tutorial_panel))
+            .add_systems(
+                Update,
+                (
+                    toggle_tutorial,
+                    validate_tutorial_step,
+                    update_tutorial_panel,
+                    draw_cell_highlight,
+                )
+                    .chain(),
+            );
+    }
+}
+
+// --- ASSETS ---
+
+// A guided sequence of steps loaded from `tutorials/*.tutorial.ron`. Every file in that folder is
+// picked up, so mods can ship their own tutorials next to the built-in ones.
+#[derive(Asset, TypePath, Deserialize, Debug, Clone)]
+pub struct Tutorial {
+    pub name: String,
+    pub steps: Vec<TutorialStep>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TutorialStep {
+    pub text: String,
+    #[serde(default)]
+    pub highlight: Option<Highlight>,
+    pub complete_when: StepGoal,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub enum Highlight {
+    // Outline the UI node carrying this `Name`.
+    Ui(String),
+    // Outline a rectangle of simulation cells, inclusive.
+    Cells { min: (u32, u32), max: (u32, u32) },
+}
+
+// What the player has to do for a step to count as done. Goals are checked against `SimEvent`s,
+// never against raw input, so they work the same for every player and input device.
+#[derive(Deserialize, Debug, Clone)]
+pub enum StepGoal {
+    Select(Particle),
+    // Paint at least `cells` cells in total, of `particle` if given.
+    Paint { particle: Option<Particle>, cells: u32 },
+    ResizeBrush,
+    // Nothing to do; the step advances by itself after this many seconds.
+    Wait(f32),
+}
+
+impl StepGoal {
+    // Returns how much progress `event` makes towards this goal.
+    fn progress(&self, event: &SimEvent) -> u32 {
+        match (self, event) {
+            (StepGoal::Select(want), SimEvent::ParticleSelected { particle, .. }) => {
+                (want == particle) as u32
+            }
+            (StepGoal::Paint { particle: want, .. }, SimEvent::Painted { particle, cells, .. })
+                if want.is_none_or(|want| want == *particle) =>
+            {
+                *cells
+            }
+            (StepGoal::ResizeBrush, SimEvent::BrushResized { .. }) => 1,
+            _ => 0,
+        }
+    }
+
+    fn target(&self) -> u32 {
+        match self {
+            StepGoal::Paint { cells, .. } => *cells,
+            _ => 1,
+        }
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct TutorialLibrary(Handle<LoadedFolder>);
+
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct ActiveTutorial {
+    tutorial: Option<Handle<Tutorial>>,
+    step: usize,
+    progress: u32,
+    step_time: f32,
+    // Counts down while the "tutorial complete" message is shown.
+    finished_timer: f32,
+    finished_name: String,
+}
+
+impl ActiveTutorial {
+    pub fn is_running(&self) -> bool {
+        self.tutorial.is_some()
+    }
+
+    pub fn start(&mut self, tutorial: Handle<Tutorial>) {
+        *self = Self {
+            tutorial: Some(tutorial),
+            ..default()
+        };
+    }
+
+    pub fn stop(&mut self) {
+        *self = Self::default();
+    }
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct TutorialPanel;
+
+// Marks a UI node whose outline was added by the tutorial, so it can be taken off again.
+#[derive(Component)]
+struct TutorialOutline;
+
+// --- SYSTEMS ---
+
+fn load_tutorials(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TutorialLibrary(asset_server.load_folder(TUTORIAL_FOLDER)));
+}
+
+fn spawn_tutorial_panel(mut commands: Commands) {
+    commands.spawn((
+        TutorialPanel,
+        Name::new("tutorial_panel"),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            right: Val::Px(5.0),
+            max_width: Val::Px(360.0),
+            padding: UiRect::all(Val::Px(8.0)),
+            display: Display::None,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+        Text::default(),
+        TextFont {
+            font_size: 18.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn toggle_tutorial(
+    keys: Res<ButtonInput<KeyCode>>,
+    library: Res<TutorialLibrary>,
+    folders: Res<Assets<LoadedFolder>>,
+    tutorials: Res<Assets<Tutorial>>,
+    mut active: ResMut<ActiveTutorial>,
+) {
+    if !keys.just_pressed(KeyCode::F1) {
+        return;
+    }
+
+    if active.is_running() {
+        active.stop();
+        return;
+    }
+
+    let Some(folder) = folders.get(&library.0) else {
+        warn!("Tutorials are still loading");
+        return;
+    };
+    // Built-in and modded tutorials are offered alphabetically.
+    let first = folder
+        .handles
+        .iter()
+        .filter_map(|h| h.clone().try_typed::<Tutorial>().ok())
+        .filter_map(|h| tutorials.get(&h).map(|t| (t.name.clone(), h)))
+        .min_by(|a, b| a.0.cmp(&b.0));
+
+    match first {
+        Some((name, handle)) => {
+            info!("Starting tutorial '{}'", name);
+            active.start(handle);
+        }
+        None => warn!("No tutorials found in '{}'", TUTORIAL_FOLDER),
+    }
+}
+
+fn validate_tutorial_step(
+    time: Res<Time>,
+    tutorials: Res<Assets<Tutorial>>,
+    mut events: EventReader<SimEvent>,
+    mut active: ResMut<ActiveTutorial>,
+) {
+    if active.finished_timer > 0.0 {
+        active.finished_timer = (active.finished_timer - time.delta_secs()).max(0.0);
+    }
+
+    let Some(tutorial) = active.tutorial.as_ref().and_then(|h| tutorials.get(h)) else {
+        events.clear();
+        return;
+    };
+    let Some(step) = tutorial.steps.get(active.step) else { return };
+
+    active.step_time += time.delta_secs();
+    let gained: u32 = events.read().map(|e| step.complete_when.progress(e)).sum();
+    active.progress = active.progress.saturating_add(gained);
+
+    let done = match step.complete_when {
+        StepGoal::Wait(secs) => active.step_time >= secs,
+        _ => active.progress >= step.complete_when.target(),
+    };
+    if !done {
+        return;
+    }
+
+    active.step += 1;
+    active.progress = 0;
+    active.step_time = 0.0;
+    if active.step >= tutorial.steps.len() {
+        let name = tutorial.name.clone();
+        info!("Tutorial '{}' complete", name);
+        active.stop();
+        active.finished_name = name;
+        active.finished_timer = FINISHED_MESSAGE_SECS;
+    }
+}
+
+fn update_tutorial_panel(
+    mut commands: Commands,
+    tutorials: Res<Assets<Tutorial>>,
+    active: Res<ActiveTutorial>,
+    mut q_panel: Query<(&mut Node, &mut Text), With<TutorialPanel>>,
+    q_named: Query<(Entity, &Name)>,
+    q_outlined: Query<Entity, With<TutorialOutline>>,
+    mut shown_step: Local<Option<(AssetId<Tutorial>, usize)>>,
+) {
+    if !active.is_changed() {
+        return;
+    }
+    let Ok((mut node, mut text)) = q_panel.single_mut() else { return };
+
+    let tutorial = active.tutorial.as_ref().and_then(|h| tutorials.get(h));
+    let step = tutorial.and_then(|t| t.steps.get(active.step));
+
+    // Outlines only move when the step does.
+    let current = active.tutorial.as_ref().map(|h| (h.id(), active.step));
+    if *shown_step != current {
+        *shown_step = current;
+        for entity in &q_outlined {
+            commands
+                .entity(entity)
+                .remove::<(Outline, TutorialOutline)>();
+        }
+        if let Some(Highlight::Ui(name)) = step.and_then(|s| s.highlight.as_ref()) {
+            for (entity, _) in q_named.iter().filter(|(_, n)| n.as_str() == name) {
+                commands.entity(entity).insert((
+                    TutorialOutline,
+                    Outline::new(Val::Px(2.0), Val::Px(2.0), HIGHLIGHT_COLOR),
+                ));
+            }
+        }
+    }
+
+    match (tutorial, step) {
+        (Some(tutorial), Some(step)) => {
+            node.display = Display::Flex;
+            let mut body = format!(
+                "{} ({}/{})\n{}",
+                tutorial.name,
+                active.step + 1,
+                tutorial.steps.len(),
+                step.text
+            );
+            if let StepGoal::Paint { cells, .. } = step.complete_when {
+                body.push_str(&format!("\n{}/{} cells", active.progress.min(cells), cells));
+            }
+            text.0 = body;
+        }
+        _ if active.finished_timer > 0.0 => {
+            node.display = Display::Flex;
+            text.0 = format!("{} complete!", active.finished_name);
+        }
+        _ => node.display = Display::None,
+    }
+}
+
+fn draw_cell_highlight(
+    layout: Res<WorldLayout>,
+    tutorials: Res<Assets<Tutorial>>,
+    active: Res<ActiveTutorial>,
+    mut gizmos: Gizmos,
+) {
+    let Some(tutorial) = active.tutorial.as_ref().and_then(|h| tutorials.get(h)) else { return };
+    let Some(Highlight::Cells { min, max }) =
+        tutorial.steps.get(active.step).and_then(|s| s.highlight.as_ref())
+    else {
+        return;
+    };
+
+    let min = CellPos::new(min.0 as i32, min.1 as i32);
+    let rect = layout.cells_to_world(min, CellPos::new(max.0 as i32, max.1 as i32));
+    gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), HIGHLIGHT_COLOR);
+}
@@ -0,0 +1,184 @@
+// --- IMPORTS ---
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::coords::CellPos;
+use crate::locks::PaintLocks;
+use crate::pan_zoom::ctrl_held;
+use crate::resolution::WorldShifted;
+use crate::sim::{CellState, SimulationGrid, ViewOnly};
+use crate::world_commands::{WorldCommand, WorldCommandSet, WorldCommands};
+
+// --- CONSTANTS ---
+// How many strokes and pastes can be undone.
+const MAX_EDITS: usize = 64;
+
+// --- PLUGIN ---
+
+// Undo and redo for painting: every brush stroke, from pressing the button to letting go, and
+// every stamp pasted is one edit, and Ctrl+Z takes the latest back while Ctrl+Y (or Ctrl+Shift+Z)
+// puts it back again. The world keeps running in between, so undoing only puts back the cells
+// that still hold what was painted there; sand that has since fallen away stays wherever it went,
+// and whatever the simulation has done to the rest of the world is left alone. Redoing works the
+// same way the other way round. The keys push `WorldCommand::Undo` and `Redo`, and the history is
+// kept as the commands that paint and paste are applied. With an inventory, undoing would hand
+// out free material, so it's off.
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PaintHistory>()
+            .add_systems(
+                Update,
+                undo_paint
+                    .before(WorldCommandSet)
+                    .run_if(not(resource_exists::<ViewOnly>)),
+            )
+            .add_systems(Update, follow_world_shifts.before(WorldCommandSet));
+    }
+}
+
+// --- TYPES ---
+
+// What one stroke or paste changed: every cell it changed, as it was before and as it was left.
+#[derive(Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Default))]
+pub struct PaintEdit {
+    cells: HashMap<IVec2, (CellState, CellState)>,
+}
+
+impl PaintEdit {
+    // Notes that the cell at `cell`, which held `before`, now holds whatever is in `grid` there,
+    // if that is something else.
+    pub fn note(&mut self, grid: &SimulationGrid, cell: CellPos, before: Option<CellState>) {
+        let (Some(before), Some(after)) = (before, grid.cell(cell.x, cell.y)) else { return };
+        if before == after {
+            return;
+        }
+        // A cell painted over twice in one stroke goes back to how it was before the first time.
+        self.cells.entry(*cell).or_insert((before, after)).1 = after;
+    }
+
+    // Moves every cell by `offset`.
+    fn shift(&mut self, offset: IVec2) {
+        let cells = std::mem::take(&mut self.cells);
+        self.cells = cells.into_iter().map(|(cell, states)| (cell + offset, states)).collect();
+    }
+
+    // Puts in `to` wherever the cell still holds the material of `from`, skipping locked cells;
+    // `pick` chooses which of each cell's two states is which. A rigid body's cell goes back to
+    // air: the body stamps its cells in wherever it has got to since, and if it has gone, so have
+    // they. Returns how many cells changed.
+    fn apply(
+        &self,
+        grid: &mut SimulationGrid,
+        locks: &PaintLocks,
+        pick: fn(&(CellState, CellState)) -> (CellState, CellState),
+    ) -> usize {
+        let mut changed = 0;
+        for (cell, states) in &self.cells {
+            let (from, to) = pick(states);
+            let Some(now) = grid.get(cell.x, cell.y) else { continue };
+            if now != from.particle || locks.is_locked(CellPos(*cell), now) {
+                continue;
+            }
+            if to.body.is_some() {
+                grid.set(cell.x, cell.y, Particle::Air);
+            } else {
+                grid.set_cell(cell.x, cell.y, to);
+            }
+            changed += 1;
+        }
+        changed
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct PaintHistory {
+    // Oldest first.
+    done: VecDeque<PaintEdit>,
+    undone: Vec<PaintEdit>,
+    // The stroke each player is painting, by player index.
+    strokes: HashMap<usize, PaintEdit>,
+}
+
+impl PaintHistory {
+    // The stroke `player` is painting, started if they just began.
+    pub fn stroke(&mut self, player: usize) -> &mut PaintEdit {
+        self.strokes.entry(player).or_default()
+    }
+
+    // Ends the stroke `player` was painting, if any, making it the latest edit.
+    pub fn finish(&mut self, player: usize) {
+        if let Some(stroke) = self.strokes.remove(&player) {
+            self.push(stroke);
+        }
+    }
+
+    // Makes `edit` the latest edit. Anything undone can't be redone after it.
+    pub fn push(&mut self, edit: PaintEdit) {
+        if edit.cells.is_empty() {
+            return;
+        }
+        if self.done.len() == MAX_EDITS {
+            self.done.pop_front();
+        }
+        self.done.push_back(edit);
+        self.undone.clear();
+    }
+
+    // Takes the latest edit back.
+    pub fn undo(&mut self, grid: &mut SimulationGrid, locks: &PaintLocks) {
+        let Some(edit) = self.done.pop_back() else {
+            info!("Nothing to undo");
+            return;
+        };
+        let changed = edit.apply(grid, locks, |&(before, after)| (after, before));
+        info!("Undid {} of {} cells (Ctrl+Y redoes)", changed, edit.cells.len());
+        self.undone.push(edit);
+    }
+
+    // Puts the latest edit taken back in again.
+    pub fn redo(&mut self, grid: &mut SimulationGrid, locks: &PaintLocks) {
+        let Some(edit) = self.undone.pop() else {
+            info!("Nothing to redo");
+            return;
+        };
+        let changed = edit.apply(grid, locks, |&(before, after)| (before, after));
+        info!("Redid {} of {} cells", changed, edit.cells.len());
+        self.done.push_back(edit);
+    }
+}
+
+// --- SYSTEMS ---
+
+fn undo_paint(keys: Res<ButtonInput<KeyCode>>, mut commands: ResMut<WorldCommands>) {
+    if !ctrl_held(&keys) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::KeyY) || (shift && keys.just_pressed(KeyCode::KeyZ)) {
+        commands.push(WorldCommand::Redo);
+    } else if keys.just_pressed(KeyCode::KeyZ) {
+        commands.push(WorldCommand::Undo);
+    }
+}
+
+// Keeps what can be undone on the same cells when the world grows under it.
+fn follow_world_shifts(mut shifts: EventReader<WorldShifted>, mut history: ResMut<PaintHistory>) {
+    for shift in shifts.read() {
+        let PaintHistory {
+            done,
+            undone,
+            strokes,
+        } = &mut *history;
+        for edit in done.iter_mut().chain(undone).chain(strokes.values_mut()) {
+            edit.shift(shift.offset);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// --- IMPORTS ---
+use std::collections::BTreeMap;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::events::SimEvent;
+use crate::explosions::Explosion;
+use crate::persist::{load_user_ron, save_user_ron};
+
+// --- CONSTANTS ---
+const STATS_FILE: &str = "lifetime_stats.ron";
+// How often the stats are written out while playing, in seconds, besides on exit.
+const SAVE_EVERY_SECS: f32 = 30.0;
+
+// --- PLUGIN ---
+
+// Lifetime statistics for this user, across every session: cells painted of each material,
+// explosions set off, worlds generated and time played (only while the window has focus). They
+// are kept in `lifetime_stats.ron` in the user data directory, saved every 30 seconds and on exit,
+// and shown on the stats screen (Shift+F3). Anything that rewards long-term play, like
+// achievements, reads them from `LifetimeStats`.
+pub struct UserStatsPlugin;
+
+impl Plugin for UserStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_user_ron::<LifetimeStats>(STATS_FILE).unwrap_or_default())
+            .init_resource::<StatsScreen>()
+            .add_systems(Update, (toggle_stats_screen, track_stats, save_stats).chain())
+            .add_systems(EguiContextPass, draw_stats_screen)
+            .add_systems(Last, save_stats_on_exit);
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default, Serialize, Deserialize))]
+#[serde(default)]
+pub struct LifetimeStats {
+    // Cells painted, by material name, so new materials don't disturb the old counts.
+    pub placed: BTreeMap<String, u64>,
+    pub explosions: u64,
+    pub worlds_created: u64,
+    pub playtime_secs: f64,
+}
+
+impl LifetimeStats {
+    pub fn placed_total(&self) -> u64 {
+        self.placed.values().sum()
+    }
+}
+
+#[derive(Resource, Default)]
+struct StatsScreen {
+    open: bool,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_stats_screen(keys: Res<ButtonInput<KeyCode>>, mut screen: ResMut<StatsScreen>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if shift && keys.just_pressed(KeyCode::F3) {
+        screen.open = !screen.open;
+    }
+}
+
+fn track_stats(
+    time: Res<Time<Real>>,
+    mut sim_events: EventReader<SimEvent>,
+    mut explosions: EventReader<Explosion>,
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    mut stats: ResMut<LifetimeStats>,
+) {
+    for event in sim_events.read() {
+        match event {
+            SimEvent::Painted {
+                particle, cells, ..
+            } if *particle != Particle::Air && *cells > 0 => {
+                *stats.placed.entry(format!("{:?}", particle)).or_default() += *cells as u64;
+            }
+            SimEvent::WorldCreated => stats.worlds_created += 1,
+            _ => {}
+        }
+    }
+    stats.explosions += explosions.read().count() as u64;
+    if q_window.single().is_ok_and(|window| window.focused) {
+        stats.playtime_secs += time.delta_secs_f64();
+    }
+}
+
+fn save_stats(time: Res<Time<Real>>, stats: Res<LifetimeStats>, mut since_save: Local<f32>) {
+    *since_save += time.delta_secs();
+    if *since_save >= SAVE_EVERY_SECS {
+        *since_save = 0.0;
+        save_user_ron(STATS_FILE, &*stats);
+    }
+}
+
+fn save_stats_on_exit(mut exits: EventReader<AppExit>, stats: Res<LifetimeStats>) {
+    if exits.read().next().is_some() {
+        save_user_ron(STATS_FILE, &*stats);
+    }
+}
+
+fn draw_stats_screen(
+    mut contexts: EguiContexts,
+    mut screen: ResMut<StatsScreen>,
+    stats: Res<LifetimeStats>,
+) {
+    if !screen.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+    let mut open = true;
+    egui::Window::new("Lifetime stats").open(&mut open).resizable(false).show(ctx, |ui| {
+        egui::Grid::new("lifetime_stats").num_columns(2).show(ui, |ui| {
+            let minutes = (stats.playtime_secs / 60.0) as u64;
+            ui.label("Time played");
+            ui.label(format!("{} h {:02} min", minutes / 60, minutes % 60));
+            ui.end_row();
+            ui.label("Worlds generated");
+            ui.label(stats.worlds_created.to_string());
+            ui.end_row();
+            ui.label("Explosions");
+            ui.label(stats.explosions.to_string());
+            ui.end_row();
+            ui.label("Cells painted");
+            ui.label(stats.placed_total().to_string());
+            ui.end_row();
+        });
+        if !stats.placed.is_empty() {
+            ui.separator();
+            let mut placed: Vec<_> = stats.placed.iter().collect();
+            placed.sort_by(|a, b| b.1.cmp(a.1));
+            egui::Grid::new("lifetime_placed").num_columns(2).show(ui, |ui| {
+                for (material, cells) in placed {
+                    ui.label(material);
+                    ui.label(cells.to_string());
+                    ui.end_row();
+                }
+            });
+        }
+    });
+    screen.open &= open;
+}
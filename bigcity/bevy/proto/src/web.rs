@@ -0,0 +1,159 @@
+// --- IMPORTS ---
+use std::path::{Path, PathBuf};
+
+use bevy::asset::io::{
+    AssetReader, AssetReaderError, AssetSource, AssetSourceId, ErasedAssetReader, PathStream,
+    Reader,
+};
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::stream;
+
+// --- CONSTANTS ---
+// The canvas the game draws into; the page embedding it provides one with this id.
+const CANVAS: &str = "#falling-sand";
+// Every bundled asset. The browser fetches assets one URL at a time and can't list the folders
+// they are in, so the level select, presets, palettes and rules would come up empty without this.
+// Keep it in step with the `assets` folder; a test checks that it is.
+const BUNDLED_ASSETS: [&str; 18] = [
+    "demos/attract.demo.ron",
+    "frames/frame.png",
+    "levels/01_fill_the_basin.level.ron",
+    "levels/02_sand_dam.level.ron",
+    "levels/03_beam_maze.level.ron",
+    "objectives/sandbox.objectives.ron",
+    "palettes/primaries.palette.ron",
+    "palettes/terrain.palette.ron",
+    "presets/fast_forward.preset.ron",
+    "presets/hothouse.preset.ron",
+    "presets/moon_gravity.preset.ron",
+    "presets/standard.preset.ron",
+    "presets/thick_liquids.preset.ron",
+    "reactions/builtin.reactions.ron",
+    "shaders/falling_sand.wgsl",
+    "shaders/gpu_sim.wgsl",
+    "timelines/sluice.timeline.ron",
+    "tutorials/basics.tutorial.ron",
+];
+
+// --- PLUGIN ---
+
+// The browser build, for `wasm32-unknown-unknown`: the game draws into the page's `falling-sand`
+// canvas with WebGL2, or WebGPU with `--features webgpu`, and fits the canvas to its parent
+// element. Assets are fetched from the `assets` folder next to the page as usual, with folders
+// listed from `BUNDLED_ASSETS`. It replaces the default asset source, so `default_plugins` adds
+// it ahead of `DefaultPlugins` in web builds; there is no user data directory in the browser, so
+// settings, saves and mods stay off there.
+pub struct WebPlugin;
+
+impl Plugin for WebPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_asset_source(
+            AssetSourceId::Default,
+            AssetSource::build().with_reader(|| {
+                Box::new(ListedReader {
+                    inner: AssetSource::get_default_reader("assets".to_string())(),
+                    files: BUNDLED_ASSETS.iter().map(PathBuf::from).collect(),
+                })
+            }),
+        );
+    }
+}
+
+// --- TYPES ---
+
+// Reads assets with the platform's reader, but lists folders from a fixed list of files.
+struct ListedReader {
+    inner: Box<dyn ErasedAssetReader>,
+    files: Vec<PathBuf>,
+}
+
+impl ListedReader {
+    // The files and folders right inside `folder`.
+    fn children(&self, folder: &Path) -> Vec<PathBuf> {
+        let mut children: Vec<PathBuf> = (self.files.iter())
+            .filter_map(|file| {
+                let rest = file.strip_prefix(folder).ok()?;
+                Some(folder.join(rest.components().next()?))
+            })
+            .collect();
+        children.dedup();
+        children
+    }
+}
+
+impl AssetReader for ListedReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<Box<dyn Reader + 'a>, AssetReaderError> {
+        self.inner.read(path).await
+    }
+
+    // Bundled assets carry no .meta files, so there is no point asking the server for them.
+    async fn read_meta<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<Box<dyn Reader + 'a>, AssetReaderError> {
+        Err(AssetReaderError::NotFound(path.into()))
+    }
+
+    async fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> Result<Box<PathStream>, AssetReaderError> {
+        let children = self.children(path);
+        if children.is_empty() {
+            return Err(AssetReaderError::NotFound(path.into()));
+        }
+        Ok(Box::new(stream::iter(children)))
+    }
+
+    async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+        Ok(self.files.iter().any(|file| file != path && file.starts_with(path)))
+    }
+}
+
+// --- HELPERS ---
+
+// Has `window` draw into the page's canvas, filling its parent, and keeps the browser's own
+// handling of keys and touches (scrolling, zooming the page) from getting in the way.
+pub fn embed_in_page(window: &mut Window) {
+    window.canvas = Some(CANVAS.to_string());
+    window.fit_canvas_to_parent = true;
+    window.prevent_default_event_handling = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files_in(folder: &Path, root: &Path, files: &mut Vec<String>) {
+        for entry in std::fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files_in(&path, root, files);
+            } else {
+                let relative = path.strip_prefix(root).unwrap();
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+
+    #[test]
+    fn every_bundled_asset_is_listed() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let mut files = Vec::new();
+        files_in(&root, &root, &mut files);
+        files.sort();
+        assert_eq!(files, BUNDLED_ASSETS);
+    }
+
+    #[test]
+    fn folders_list_what_is_right_inside_them() {
+        let reader = ListedReader {
+            inner: AssetSource::get_default_reader("assets".to_string())(),
+            files: ["a/b.ron", "a/c/d.ron", "a/c/e.ron", "f.ron"].map(PathBuf::from).to_vec(),
+        };
+        let children = |folder: &str| reader.children(Path::new(folder));
+        assert_eq!(children("a"), ["a/b.ron", "a/c"].map(PathBuf::from));
+        assert_eq!(children(""), ["a", "f.ron"].map(PathBuf::from));
+        assert!(children("g").is_empty());
+    }
+}
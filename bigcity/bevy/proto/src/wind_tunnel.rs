@@ -0,0 +1,444 @@
+// --- IMPORTS ---
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+
+use crate::pan_zoom::ctrl_held;
+use crate::sim::{SimParams, SimulationGrid, SimulationSet, SimulationStats, ViewOnly};
+use crate::{MaterialClass, WorldView};
+
+// --- CONSTANTS ---
+// The flow is worked out on blocks of FLOW_BLOCK x FLOW_BLOCK cells, and a block stands in its way
+// once at least half of it is solid, powder or liquid.
+const FLOW_BLOCK: i32 = 4;
+// Over-relaxed Gauss-Seidel sweeps a frame, carrying on from the last frame's field, so it settles
+// over a few frames after the obstacles change instead of in one long one.
+const SWEEPS_PER_FRAME: usize = 8;
+const RELAXATION: f32 = 1.8;
+// Wind speeds the tunnel can blow at, in cells per tick; negative blows right to left.
+const SPEEDS: RangeInclusive<f32> = -4.0..=4.0;
+// Streamlines start evenly spaced along the upwind edge and are followed a cell at a time.
+const STREAMLINES: usize = 24;
+const MAX_STREAMLINE_STEPS: usize = 2000;
+// A row of tracers is let in along the upwind edge this often, in seconds, up to MAX_TRACERS.
+const TRACER_INTERVAL: f32 = 0.25;
+const MAX_TRACERS: usize = 4000;
+const TRACER_COLOR: Color = Color::srgb(1.0, 0.95, 0.6);
+// Streamlines go from SLOW_COLOR where the air is still to white at the tunnel speed and on to
+// FAST_COLOR at twice it.
+const SLOW_COLOR: LinearRgba = LinearRgba::rgb(0.2, 0.4, 1.0);
+const FAST_COLOR: LinearRgba = LinearRgba::rgb(1.0, 0.2, 0.1);
+
+// --- PLUGIN ---
+
+// The wind tunnel (Ctrl+W): a steady wind blows across the whole world, and the way the air flows
+// around whatever is built in it shows as streamlines, colored by how fast it goes there, and as
+// tracers let in along the upwind edge that drift with it. The flow is an ideal one, worked out
+// over blocks of cells from where the solids, powders and liquids are, so it shows where air is
+// turned aside, squeezed through and sped up, but not wakes or eddies. While the tunnel runs it
+// sets the wind parameter to its speed, so dust is blown along as well, and it puts the old wind
+// back when it stops. Tracers only move while the world ticks.
+pub struct WindTunnelPlugin;
+
+impl Plugin for WindTunnelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WindTunnel>()
+            .add_systems(
+                Update,
+                (toggle_wind_tunnel, blow, solve_flow, drift_tracers, draw_flow)
+                    .chain()
+                    .after(SimulationSet)
+                    .run_if(not(resource_exists::<ViewOnly>)),
+            )
+            .add_systems(EguiContextPass, draw_wind_tunnel);
+    }
+}
+
+// --- TYPES ---
+
+// Air flowing through the world as if nothing inside it had any friction: a potential whose
+// slope is the air's velocity, for a wind of one cell a tick blowing in along the upwind edge. It
+// is fixed along the downwind edge and settles to the mean of its open neighbours everywhere else,
+// so air neither enters nor leaves through blocked blocks or the top and bottom, and whatever
+// blows in has to get out past the obstacles.
+#[derive(Default)]
+struct FlowField {
+    // Blocks across and up.
+    across: IVec2,
+    // Which way the wind blows: 1 to the right, -1 to the left.
+    dir: i32,
+    blocked: Vec<bool>,
+    potential: Vec<f32>,
+    // The velocity at the middle of every block, zero in blocked ones.
+    velocity: Vec<Vec2>,
+}
+
+impl FlowField {
+    fn new(width: u32, height: u32, dir: i32) -> Self {
+        let across = Self::blocks_across(width, height);
+        let count = (across.x * across.y) as usize;
+        let potential =
+            (0..count).map(|i| (dir * (i as i32 % across.x) * FLOW_BLOCK) as f32).collect();
+        Self {
+            across,
+            dir,
+            blocked: vec![false; count],
+            potential,
+            velocity: vec![Vec2::X * dir as f32; count],
+        }
+    }
+
+    // How many blocks cover a world `width` x `height` cells big.
+    fn blocks_across(width: u32, height: u32) -> IVec2 {
+        (IVec2::new(width as i32, height as i32) + IVec2::splat(FLOW_BLOCK - 1)) / FLOW_BLOCK
+    }
+
+    fn index(&self, block: IVec2) -> Option<usize> {
+        let inside = block.cmpge(IVec2::ZERO).all() && block.cmplt(self.across).all();
+        inside.then(|| (block.y * self.across.x + block.x) as usize)
+    }
+
+    fn open(&self, block: IVec2) -> Option<usize> {
+        self.index(block).filter(|&i| !self.blocked[i])
+    }
+
+    // Marks the blocks that at least half fill with anything but gas.
+    fn block(&mut self, grid: &SimulationGrid) {
+        let mut filled = vec![0; self.blocked.len()];
+        let width = grid.width() as usize;
+        for (i, particle) in grid.cells().iter().enumerate() {
+            if particle.class() != MaterialClass::Gas {
+                let cell = IVec2::new((i % width) as i32, (i / width) as i32);
+                filled[self.index(cell / FLOW_BLOCK).unwrap_or(0)] += 1;
+            }
+        }
+        let half = FLOW_BLOCK * FLOW_BLOCK / 2;
+        for (blocked, filled) in self.blocked.iter_mut().zip(filled) {
+            *blocked = filled >= half;
+        }
+    }
+
+    fn relax(&mut self, sweeps: usize) {
+        let last = self.across.x - 1;
+        let (inlet, outlet) = if self.dir < 0 { (last, 0) } else { (0, last) };
+        for _ in 0..sweeps {
+            for y in 0..self.across.y {
+                for x in 0..self.across.x {
+                    let block = IVec2::new(x, y);
+                    let Some(i) = self.open(block) else { continue };
+                    if x == outlet {
+                        continue;
+                    }
+                    // The wind comes in at one cell a tick along the upwind edge.
+                    if x == inlet {
+                        let step = (self.dir * FLOW_BLOCK) as f32;
+                        if let Some(next) = self.open(block + IVec2::X * self.dir) {
+                            self.potential[i] = self.potential[next] - step;
+                        }
+                        continue;
+                    }
+                    let sides = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+                    let (mut sum, mut open) = (0.0, 0);
+                    for n in sides.into_iter().filter_map(|side| self.open(block + side)) {
+                        (sum, open) = (sum + self.potential[n], open + 1);
+                    }
+                    if open > 0 {
+                        let mean = sum / open as f32;
+                        self.potential[i] += RELAXATION * (mean - self.potential[i]);
+                    }
+                }
+            }
+        }
+        for y in 0..self.across.y {
+            for x in 0..self.across.x {
+                let block = IVec2::new(x, y);
+                let i = self.index(block).unwrap_or(0);
+                self.velocity[i] = match self.open(block) {
+                    Some(_) => Vec2::new(self.slope(block, IVec2::X), self.slope(block, IVec2::Y)),
+                    None => Vec2::ZERO,
+                };
+            }
+        }
+    }
+
+    // How fast the potential rises through `block` along `axis`, per cell, from whichever
+    // neighbours along it are open.
+    fn slope(&self, block: IVec2, axis: IVec2) -> f32 {
+        let here = self.index(block).map_or(0.0, |i| self.potential[i]);
+        let at = |side: IVec2| self.open(block + side).map(|i| self.potential[i]);
+        let span = FLOW_BLOCK as f32;
+        match (at(-axis), at(axis)) {
+            (Some(before), Some(after)) => (after - before) / (2.0 * span),
+            (Some(before), None) => (here - before) / span,
+            (None, Some(after)) => (after - here) / span,
+            (None, None) => 0.0,
+        }
+    }
+
+    // The velocity at `point`, in cells, blended between the middles of the blocks around it, or
+    // of the nearest ones along the world's edges.
+    fn velocity_at(&self, point: Vec2) -> Vec2 {
+        let block = point / FLOW_BLOCK as f32;
+        if self.open(block.floor().as_ivec2()).is_none() {
+            return Vec2::ZERO;
+        }
+        let corner = (block - Vec2::splat(0.5)).floor();
+        let t = block - Vec2::splat(0.5) - corner;
+        let at = |dx: i32, dy: i32| {
+            let block = corner.as_ivec2() + IVec2::new(dx, dy);
+            let i = self.index(block.clamp(IVec2::ZERO, self.across - 1));
+            i.map_or(Vec2::ZERO, |i| self.velocity[i])
+        };
+        let bottom = at(0, 0).lerp(at(1, 0), t.x);
+        let top = at(0, 1).lerp(at(1, 1), t.x);
+        bottom.lerp(top, t.y)
+    }
+}
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+struct WindTunnel {
+    open: bool,
+    running: bool,
+    // Cells per tick; negative blows right to left.
+    speed: f32,
+    streamlines: bool,
+    tracers: bool,
+    // The wind the world had before the tunnel started blowing.
+    saved_wind: Option<f32>,
+    field: FlowField,
+    // Tracer positions, in cells.
+    drifting: Vec<Vec2>,
+    since_tracers: f32,
+}
+
+impl Default for WindTunnel {
+    fn default() -> Self {
+        Self {
+            open: false,
+            running: false,
+            speed: 1.0,
+            streamlines: true,
+            tracers: true,
+            saved_wind: None,
+            field: FlowField::default(),
+            drifting: Vec::new(),
+            since_tracers: 0.0,
+        }
+    }
+}
+
+impl WindTunnel {
+    // Where the wind comes in, as a column of cells, for a world `width` cells wide.
+    fn upwind_edge(&self, width: u32) -> f32 {
+        if self.speed < 0.0 { width as f32 - 0.5 } else { 0.5 }
+    }
+
+    // The rows streamlines and tracers start on, evenly spread up a world `height` cells high.
+    fn starting_rows(height: u32) -> impl Iterator<Item = f32> {
+        let spacing = height as f32 / STREAMLINES as f32;
+        (0..STREAMLINES).map(move |row| (row as f32 + 0.5) * spacing)
+    }
+
+    // The air's velocity at `point`, in cells per tick.
+    fn velocity_at(&self, point: Vec2) -> Vec2 {
+        self.field.velocity_at(point) * self.speed.abs()
+    }
+}
+
+// --- SYSTEMS ---
+
+fn toggle_wind_tunnel(keys: Res<ButtonInput<KeyCode>>, mut tunnel: ResMut<WindTunnel>) {
+    // W on its own is the emitter tool.
+    if keys.just_pressed(KeyCode::KeyW) && ctrl_held(&keys) {
+        tunnel.open = !tunnel.open;
+    }
+}
+
+// Holds the wind at the tunnel's speed while it runs, and puts the old wind back once it stops.
+fn blow(mut tunnel: ResMut<WindTunnel>, mut params: ResMut<SimParams>) {
+    if tunnel.running {
+        tunnel.saved_wind.get_or_insert(params.wind);
+        if params.wind != tunnel.speed {
+            params.wind = tunnel.speed;
+        }
+    } else if let Some(wind) = tunnel.saved_wind.take() {
+        params.wind = wind;
+        tunnel.drifting.clear();
+    }
+}
+
+fn solve_flow(grid: Res<SimulationGrid>, mut tunnel: ResMut<WindTunnel>) {
+    if !tunnel.running {
+        return;
+    }
+    let dir = if tunnel.speed < 0.0 { -1 } else { 1 };
+    let field = &mut tunnel.field;
+    let fresh =
+        field.across != FlowField::blocks_across(grid.width(), grid.height()) || field.dir != dir;
+    if fresh {
+        *field = FlowField::new(grid.width(), grid.height(), dir);
+    }
+    if fresh || grid.is_changed() {
+        field.block(&grid);
+    }
+    field.relax(SWEEPS_PER_FRAME);
+}
+
+// Lets in a row of tracers now and then, and moves every one with the air by as many ticks as the
+// world ran this frame. Tracers that leave the world or come to rest against something go.
+fn drift_tracers(
+    time: Res<Time>,
+    grid: Res<SimulationGrid>,
+    stats: Res<SimulationStats>,
+    mut tunnel: ResMut<WindTunnel>,
+) {
+    if !tunnel.running || !tunnel.tracers {
+        return;
+    }
+    tunnel.since_tracers += time.delta_secs();
+    if tunnel.since_tracers >= TRACER_INTERVAL && stats.ticks_last_frame > 0 {
+        tunnel.since_tracers = 0.0;
+        let x = tunnel.upwind_edge(grid.width());
+        let room = MAX_TRACERS.saturating_sub(tunnel.drifting.len());
+        let row = WindTunnel::starting_rows(grid.height()).map(|y| Vec2::new(x, y));
+        let row: Vec<Vec2> = row.take(room).collect();
+        tunnel.drifting.extend(row);
+    }
+
+    let size = Vec2::new(grid.width() as f32, grid.height() as f32);
+    let ticks = stats.ticks_last_frame as f32;
+    let WindTunnel {
+        field,
+        drifting,
+        speed,
+        ..
+    } = &mut *tunnel;
+    drifting.retain_mut(|point| {
+        let velocity = field.velocity_at(*point) * speed.abs();
+        *point += velocity * ticks;
+        let inside = point.cmpge(Vec2::ZERO).all() && point.cmplt(size).all();
+        inside && (ticks == 0.0 || velocity.length_squared() > 1e-4)
+    });
+}
+
+fn draw_flow(
+    grid: Res<SimulationGrid>,
+    tunnel: Res<WindTunnel>,
+    view: WorldView,
+    mut gizmos: Gizmos,
+) {
+    if !tunnel.running {
+        return;
+    }
+    if tunnel.streamlines {
+        let x = tunnel.upwind_edge(grid.width());
+        for y in WindTunnel::starting_rows(grid.height()) {
+            let points = streamline(&tunnel, Vec2::new(x, y), grid.width(), grid.height());
+            let colored = points.into_iter().map(|(point, speed)| {
+                (*view.cell_to_world(point), speed_color(speed / tunnel.speed.abs().max(1e-3)))
+            });
+            gizmos.linestrip_gradient_2d(colored);
+        }
+    }
+    let radius = view.cell_size().x * 0.4;
+    for &point in &tunnel.drifting {
+        gizmos.circle_2d(*view.cell_to_world(point), radius, TRACER_COLOR);
+    }
+}
+
+fn draw_wind_tunnel(mut contexts: EguiContexts, mut tunnel: ResMut<WindTunnel>) {
+    if !tunnel.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let mut open = true;
+    egui::Window::new("Wind tunnel")
+        .open(&mut open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut tunnel.running, "Blow");
+            ui.horizontal(|ui| {
+                ui.label("Wind");
+                let speed = egui::Slider::new(&mut tunnel.speed, SPEEDS);
+                ui.add(speed.suffix(" cells / tick"));
+            });
+            ui.checkbox(&mut tunnel.streamlines, "Streamlines");
+            ui.checkbox(&mut tunnel.tracers, "Tracers");
+            if !tunnel.tracers {
+                tunnel.drifting.clear();
+            }
+            ui.label("Build obstacles in the world to see the air flow around them.");
+        });
+    if !open {
+        tunnel.open = false;
+    }
+}
+
+// --- HELPERS ---
+
+// Follows the air from `start` a cell at a time until it leaves the world, comes to rest or goes
+// on too long, with how fast it was going at every point.
+fn streamline(tunnel: &WindTunnel, start: Vec2, width: u32, height: u32) -> Vec<(Vec2, f32)> {
+    let size = Vec2::new(width as f32, height as f32);
+    let mut point = start;
+    let mut points = Vec::new();
+    for _ in 0..MAX_STREAMLINE_STEPS {
+        let velocity = tunnel.velocity_at(point);
+        let speed = velocity.length();
+        points.push((point, speed));
+        if speed < 1e-3 || !(point.cmpge(Vec2::ZERO).all() && point.cmplt(size).all()) {
+            break;
+        }
+        point += velocity / speed;
+    }
+    points
+}
+
+// SLOW_COLOR -> white -> FAST_COLOR as `ratio` goes from 0 through 1 to 2.
+fn speed_color(ratio: f32) -> Color {
+    let white = LinearRgba::WHITE;
+    let color = if ratio < 1.0 {
+        SLOW_COLOR.mix(&white, ratio.max(0.0))
+    } else {
+        white.mix(&FAST_COLOR, (ratio - 1.0).min(1.0))
+    };
+    color.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Particle;
+    use crate::coords::CellPos;
+
+    fn settled(grid: &SimulationGrid) -> FlowField {
+        let mut field = FlowField::new(grid.width(), grid.height(), 1);
+        field.block(grid);
+        field.relax(2000);
+        field
+    }
+
+    #[test]
+    fn empty_tunnels_blow_evenly() {
+        let field = settled(&SimulationGrid::new(64, 32));
+        for point in [Vec2::new(10.0, 5.0), Vec2::new(40.0, 20.0)] {
+            assert!((field.velocity_at(point) - Vec2::X).length() < 0.01);
+        }
+    }
+
+    #[test]
+    fn air_goes_around_obstacles() {
+        let mut grid = SimulationGrid::new(64, 32);
+        grid.fill_rect(CellPos::new(28, 0), CellPos::new(35, 20), Particle::Bedrock);
+        let field = settled(&grid);
+        assert_eq!(field.velocity_at(Vec2::new(30.0, 10.0)), Vec2::ZERO);
+        // Squeezed over the top of the wall it speeds up, and it climbs on the way there.
+        let over = field.velocity_at(Vec2::new(32.0, 27.0));
+        assert!(over.x > 1.5, "only {} over the wall", over.x);
+        assert!(field.velocity_at(Vec2::new(22.0, 14.0)).y > 0.1);
+    }
+}
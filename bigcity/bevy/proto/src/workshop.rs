@@ -0,0 +1,424 @@
+// --- IMPORTS ---
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy::tasks::futures::check_ready;
+use bevy::tasks::{IoTaskPool, Task};
+use bevy_egui::{EguiContextPass, EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::persist::{file_stem, load_user_ron, save_user_ron, user_data_dir};
+use crate::sim::SimulationGrid;
+use crate::snapshot::WorldSnapshot;
+use crate::stamps::{Stamp, StampLibrary};
+
+// --- CONSTANTS ---
+const CONFIG_FILE: &str = "workshop.ron";
+
+// --- PLUGIN ---
+
+// A client for a simple HTTP gallery of shared stamps, worlds and palettes. The gallery speaks
+// RON over plain GET and POST requests:
+//
+//   GET  {endpoint}/items                  -> Vec<GalleryItem>
+//   GET  {endpoint}/items/{id}             -> the item's file
+//   GET  {endpoint}/items/{id}/thumbnail   -> a PNG, if the item has one
+//   POST {endpoint}/items                  <- Upload
+pub struct WorkshopPlugin;
+
+impl Plugin for WorkshopPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Workshop {
+            config: load_user_ron(CONFIG_FILE).unwrap_or_default(),
+            ..default()
+        })
+        .add_systems(Update, (toggle_workshop, finish_requests).chain())
+        .add_systems(EguiContextPass, draw_workshop);
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Stamp,
+    // A whole world, as a snapshot.
+    Scenario,
+    Palette,
+}
+
+impl ItemKind {
+    // Downloads go to this folder in the user data directory, with this extension.
+    fn folder(self) -> &'static str {
+        match self {
+            ItemKind::Stamp => "stamps",
+            ItemKind::Scenario => "mods/scenarios",
+            ItemKind::Palette => "mods/palettes",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ItemKind::Stamp => "stamp.ron",
+            ItemKind::Scenario => "world.ron",
+            ItemKind::Palette => "palette.ron",
+        }
+    }
+}
+
+// What the gallery lists about an item.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GalleryItem {
+    // Given by the gallery; empty in uploads.
+    #[serde(default)]
+    pub id: String,
+    pub kind: ItemKind,
+    pub name: String,
+    pub author: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+impl GalleryItem {
+    fn local_path(&self) -> Option<PathBuf> {
+        let file = format!("{}.{}", file_stem(&self.name), self.kind.extension());
+        Some(user_data_dir()?.join(self.kind.folder()).join(file))
+    }
+}
+
+// The body of an upload: the item's description, its file and an optional PNG thumbnail.
+#[derive(Serialize, Debug)]
+struct Upload {
+    meta: GalleryItem,
+    content: String,
+    thumbnail: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+struct WorkshopConfig {
+    endpoint: String,
+    author: String,
+}
+
+impl Default for WorkshopConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "http://localhost:8731".into(),
+            author: "anonymous".into(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum WorkshopError {
+    #[error("request failed: {0}")]
+    Http(#[from] ureq::Error),
+    #[error("unexpected reply: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("could not encode upload: {0}")]
+    Encode(#[from] ron::Error),
+    #[error("could not save download: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+enum Reply {
+    Listed(Vec<GalleryItem>),
+    Downloaded {
+        item: GalleryItem,
+        content: String,
+        thumbnail: Option<Vec<u8>>,
+    },
+    Uploaded(String),
+}
+
+// What the upload form sends.
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum UploadSource {
+    #[default]
+    World,
+    Stamp(usize),
+}
+
+// --- RESOURCES ---
+
+// The workshop window (Ctrl+F12): settings, the last gallery listing and the request in flight. Only
+// one request runs at a time, on the IO task pool.
+#[derive(Resource, Default)]
+struct Workshop {
+    open: bool,
+    config: WorkshopConfig,
+    items: Vec<GalleryItem>,
+    status: String,
+    request: Option<Task<Result<Reply, WorkshopError>>>,
+    source: UploadSource,
+    name: String,
+    description: String,
+}
+
+// --- SYSTEMS ---
+
+fn toggle_workshop(keys: Res<ButtonInput<KeyCode>>, mut workshop: ResMut<Workshop>) {
+    if ctrl_held(&keys) && keys.just_pressed(KeyCode::F12) {
+        workshop.open = !workshop.open;
+    }
+}
+
+// Picks up a finished request and files what it brought back.
+fn finish_requests(mut workshop: ResMut<Workshop>, mut library: ResMut<StampLibrary>) {
+    let Some(request) = workshop.request.as_mut() else { return };
+    let Some(result) = check_ready(request) else { return };
+    workshop.request = None;
+
+    workshop.status = match result.and_then(|reply| store(reply, &mut workshop, &mut library)) {
+        Ok(status) => status,
+        Err(err) => {
+            warn!("Workshop: {}", err);
+            format!("Error: {}", err)
+        }
+    };
+}
+
+fn draw_workshop(
+    mut contexts: EguiContexts,
+    mut workshop: ResMut<Workshop>,
+    library: Res<StampLibrary>,
+    rules: Res<PaintRules>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    if !workshop.open {
+        return;
+    }
+    let Some(ctx) = contexts.try_ctx_mut() else { return };
+
+    let mut open = true;
+    let busy = workshop.request.is_some();
+    egui::Window::new("Workshop").open(&mut open).show(ctx, |ui| {
+        egui::Grid::new("workshop_settings").num_columns(2).show(ui, |ui| {
+            ui.label("Gallery");
+            ui.text_edit_singleline(&mut workshop.config.endpoint);
+            ui.end_row();
+            ui.label("Author");
+            ui.text_edit_singleline(&mut workshop.config.author);
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save settings").clicked() {
+                save_user_ron(CONFIG_FILE, &workshop.config);
+            }
+            if ui.add_enabled(!busy, egui::Button::new("Refresh")).clicked() {
+                let endpoint = workshop.config.endpoint.clone();
+                start(&mut workshop, "Listing the gallery...", move || list(&endpoint));
+            }
+        });
+
+        ui.separator();
+        let mut download = None;
+        let mut open_world = None;
+        egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+            egui::Grid::new("workshop_items").num_columns(4).striped(true).show(ui, |ui| {
+                for item in &workshop.items {
+                    ui.label(format!("{:?}", item.kind));
+                    ui.label(&item.name).on_hover_text(&item.description);
+                    ui.label(&item.author);
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(!busy, egui::Button::new("Download")).clicked() {
+                            download = Some(item.clone());
+                        }
+                        let local = item.local_path().filter(|path| path.exists());
+                        if let (ItemKind::Scenario, Some(path)) = (item.kind, local) {
+                            let button = egui::Button::new("Open");
+                            if ui.add_enabled(!rules.protect_world, button).clicked() {
+                                open_world = Some(path);
+                            }
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+        if let Some(item) = download {
+            let endpoint = workshop.config.endpoint.clone();
+            let status = format!("Downloading \"{}\"...", item.name);
+            start(&mut workshop, &status, move || fetch(&endpoint, item));
+        }
+        if let Some(path) = open_world {
+            workshop.status = match open_snapshot(&path) {
+                Ok(snapshot) => {
+                    snapshot.apply_to(&mut grid);
+                    format!("Opened {:?}", path)
+                }
+                Err(err) => format!("Error: {}", err),
+            };
+        }
+
+        ui.separator();
+        let describe = |source: UploadSource| match source {
+            UploadSource::World => "Current world".to_string(),
+            UploadSource::Stamp(index) => {
+                library.stamps.get(index).map_or("-".to_string(), |s| format!("Stamp: {}", s.name))
+            }
+        };
+        egui::Grid::new("workshop_upload").num_columns(2).show(ui, |ui| {
+            ui.label("Share");
+            egui::ComboBox::from_id_salt("upload_source")
+                .selected_text(describe(workshop.source))
+                .show_ui(ui, |ui| {
+                    let sources = std::iter::once(UploadSource::World)
+                        .chain((0..library.stamps.len()).map(UploadSource::Stamp));
+                    for source in sources {
+                        ui.selectable_value(&mut workshop.source, source, describe(source));
+                    }
+                });
+            ui.end_row();
+            ui.label("Name");
+            ui.text_edit_singleline(&mut workshop.name);
+            ui.end_row();
+            ui.label("Description");
+            ui.text_edit_multiline(&mut workshop.description);
+            ui.end_row();
+        });
+        let ready = !busy && !workshop.name.trim().is_empty();
+        if ui.add_enabled(ready, egui::Button::new("Upload")).clicked() {
+            match prepare_upload(&workshop, &library, &grid) {
+                Ok(upload) => {
+                    let endpoint = workshop.config.endpoint.clone();
+                    let status = format!("Uploading \"{}\"...", upload.meta.name);
+                    start(&mut workshop, &status, move || send(&endpoint, upload));
+                }
+                Err(err) => workshop.status = format!("Error: {}", err),
+            }
+        }
+
+        if !workshop.status.is_empty() {
+            ui.separator();
+            ui.label(&workshop.status);
+        }
+    });
+    workshop.open &= open;
+}
+
+// --- HELPERS ---
+
+fn start(
+    workshop: &mut Workshop,
+    status: &str,
+    request: impl FnOnce() -> Result<Reply, WorkshopError> + Send + 'static,
+) {
+    workshop.status = status.to_string();
+    workshop.request = Some(IoTaskPool::get().spawn(async move { request() }));
+}
+
+fn list(endpoint: &str) -> Result<Reply, WorkshopError> {
+    let text = ureq::get(format!("{}/items", endpoint)).call()?.body_mut().read_to_string()?;
+    Ok(Reply::Listed(ron::from_str(&text)?))
+}
+
+fn fetch(endpoint: &str, item: GalleryItem) -> Result<Reply, WorkshopError> {
+    let url = format!("{}/items/{}", endpoint, item.id);
+    let content = ureq::get(&url).call()?.body_mut().read_to_string()?;
+    // Thumbnails are optional; a gallery without one for this item just answers 404.
+    let thumbnail = ureq::get(format!("{}/thumbnail", url))
+        .call()
+        .ok()
+        .and_then(|mut reply| reply.body_mut().read_to_vec().ok());
+    Ok(Reply::Downloaded {
+        item,
+        content,
+        thumbnail,
+    })
+}
+
+fn send(endpoint: &str, upload: Upload) -> Result<Reply, WorkshopError> {
+    let body = ron::to_string(&upload)?;
+    ureq::post(format!("{}/items", endpoint))
+        .header("Content-Type", "application/ron")
+        .send(body)?;
+    Ok(Reply::Uploaded(upload.meta.name))
+}
+
+fn prepare_upload(
+    workshop: &Workshop,
+    library: &StampLibrary,
+    grid: &SimulationGrid,
+) -> Result<Upload, WorkshopError> {
+    let (kind, content, picture) = match workshop.source {
+        UploadSource::World => {
+            let content = ron::to_string(&WorldSnapshot::from_grid(grid))?;
+            let max = IVec2::new(grid.width() as i32 - 1, grid.height() as i32 - 1);
+            let picture = Stamp::capture(grid, IVec2::ZERO, max, String::new());
+            (ItemKind::Scenario, content, picture)
+        }
+        UploadSource::Stamp(index) => {
+            let stamp = library.stamps.get(index).cloned();
+            let content = ron::to_string(&stamp)?;
+            (ItemKind::Stamp, content, stamp)
+        }
+    };
+    let thumbnail = picture.map(|stamp| {
+        let mut bytes = Vec::new();
+        let image = image::DynamicImage::ImageRgba8(stamp.thumbnail());
+        image.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png).map(|_| bytes)
+    });
+    Ok(Upload {
+        meta: GalleryItem {
+            id: String::new(),
+            kind,
+            name: workshop.name.trim().to_string(),
+            author: workshop.config.author.clone(),
+            description: workshop.description.clone(),
+        },
+        content,
+        thumbnail: thumbnail.and_then(Result::ok),
+    })
+}
+
+// Saves a download into its folder, next to its thumbnail, and adds stamps to the library right
+// away. Returns the status line to show.
+fn store(
+    reply: Reply,
+    workshop: &mut Workshop,
+    library: &mut StampLibrary,
+) -> Result<String, WorkshopError> {
+    let (item, content, thumbnail) = match reply {
+        Reply::Listed(items) => {
+            let status = format!("{} items in the gallery", items.len());
+            workshop.items = items;
+            return Ok(status);
+        }
+        Reply::Uploaded(name) => return Ok(format!("Uploaded \"{}\"", name)),
+        Reply::Downloaded {
+            item,
+            content,
+            thumbnail,
+        } => (item, content, thumbnail),
+    };
+
+    // Check it parses before it lands where the game will read it.
+    let stamp = match item.kind {
+        ItemKind::Stamp => Some(ron::from_str::<Stamp>(&content)?),
+        ItemKind::Scenario => ron::from_str::<WorldSnapshot>(&content).map(|_| None)?,
+        ItemKind::Palette => None,
+    };
+    let Some(path) = item.local_path() else {
+        return Err(std::io::Error::other("no user data directory").into());
+    };
+    std::fs::create_dir_all(path.parent().unwrap_or(&path))?;
+    std::fs::write(&path, content)?;
+    if let Some(bytes) = thumbnail {
+        std::fs::write(path.with_extension("").with_extension("png"), bytes)?;
+    }
+    if let Some(stamp) = stamp {
+        library.add(stamp);
+    }
+    info!("Workshop: downloaded \"{}\" to {:?}", item.name, path);
+    Ok(format!("Downloaded \"{}\"", item.name))
+}
+
+fn open_snapshot(path: &PathBuf) -> Result<WorldSnapshot, WorkshopError> {
+    Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+}
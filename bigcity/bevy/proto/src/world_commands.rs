@@ -0,0 +1,436 @@
+// --- IMPORTS ---
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::Particle;
+use crate::bodies::{BodyOperation, edit_bodies};
+use crate::coords::CellPos;
+use crate::events::SimEvent;
+use crate::explosions::{Explosion, blast};
+use crate::inventory::Inventory;
+use crate::levels::PaintRules;
+use crate::locks::PaintLocks;
+use crate::replay::ReplayRecording;
+use crate::sim::{CellState, SimulationGrid, SimulationStats};
+use crate::stamps::Stamp;
+use crate::tags::{TagOperation, edit_tags};
+use crate::undo::{PaintEdit, PaintHistory};
+
+// --- PLUGIN ---
+
+// Every change players and their tools make to the world goes through one ordered queue: brush
+// strokes, stamps and copies pasted, what emitters and drains put in and take out, the attract
+// demo's painting, replays played back, timelines, tag edits, and undo and redo are all
+// `WorldCommand`s pushed onto `WorldCommands`, and they go into the grid in the order they were
+// pushed, between ticks. That happens in `WorldCommandSet`, in `Update` after input and before the
+// frame's world is read, and in `FixedUpdate` before every tick, for commands pushed by tick
+// (replays, timelines). Applying a command is the one place the paint rules, paint locks and the
+// inventory are enforced, edits are kept for undoing and brush strokes are noted for a replay
+// recording, and every command applied is sent on as an `AppliedCommand` with the tick it went in
+// before, for anything mirroring the world elsewhere. What the simulation does by itself, and whole
+// worlds loaded or generated, aren't commands.
+pub struct WorldCommandsPlugin;
+
+impl Plugin for WorldCommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldCommands>()
+            .add_event::<AppliedCommand>()
+            .add_systems(Update, apply_world_commands.in_set(WorldCommandSet))
+            .add_systems(FixedUpdate, apply_world_commands.in_set(WorldCommandSet));
+    }
+}
+
+// Commands pushed onto the queue before this set go into the world in it. Where it sits in the
+// frame is set out in frame_order.rs.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WorldCommandSet;
+
+// --- TYPES ---
+
+// One change to the world, as asked for.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub enum WorldCommand {
+    // One pass of `player`'s brush: paints `particle` with state byte `data` into `cells`, at
+    // most `max_cells` of them, as far as the paint rules, locks and inventory allow. Passes are
+    // one edit to undo until the stroke ends.
+    Paint {
+        player: usize,
+        particle: Particle,
+        data: u8,
+        cells: Vec<CellPos>,
+        max_cells: u32,
+    },
+    // `player` let go of the button.
+    EndStroke {
+        player: usize,
+    },
+    // Places `particle` with state byte `data` into `cells` without any limits, for scripts and
+    // replays.
+    Place {
+        particle: Particle,
+        data: u8,
+        cells: Vec<CellPos>,
+    },
+    // Pastes `stamp` centered on `at`, apart from locked cells, as one edit to undo.
+    Paste {
+        stamp: Stamp,
+        at: CellPos,
+    },
+    // Sets `cells` to `particle` as part of the world rather than a player's, as emitters,
+    // drains (with air) and scripts do.
+    Set {
+        particle: Particle,
+        cells: Vec<CellPos>,
+    },
+    // Sets the inclusive rectangle `min..=max` to `particle`, as `Set` does.
+    FillRect {
+        min: CellPos,
+        max: CellPos,
+        particle: Particle,
+    },
+    // Sets every cell within `radius` cells of `center` to `particle`, as `Set` does.
+    FillCircle {
+        center: CellPos,
+        radius: i32,
+        particle: Particle,
+    },
+    // Puts walls of `particle` behind `cells` on the background layer, or takes them away with
+    // air. Walls are only drawn, so no paint rules, locks or inventory keep them in check, and
+    // they aren't undone.
+    Backdrop {
+        particle: Particle,
+        cells: Vec<CellPos>,
+    },
+    // Adds `degrees` to every cell of the inclusive rectangle `min..=max`, as part of the world.
+    Heat {
+        min: CellPos,
+        max: CellPos,
+        degrees: f32,
+    },
+    // Sets off a blast of `radius` cells at `center`, in cell units, as part of the world, so a
+    // level's own cells are blown away too.
+    Explode {
+        center: Vec2,
+        radius: f32,
+    },
+    // Does `TagOperation` to the world's tags and the cells they tag; see tags.rs. Deleting and
+    // converting keep off locked cells and are one edit to undo.
+    Tags(TagOperation),
+    // Does `BodyOperation` to the world's rigid bodies and their cells; see bodies.rs. Bodies move
+    // by these, a tick at a time, so they aren't undone.
+    Bodies(BodyOperation),
+    // Empties the whole world, walls, zones, loop bands, tags and bodies with it.
+    Clear,
+    Undo,
+    Redo,
+}
+
+// What one brush stamp may change: at most `max_cells` cells, and with an inventory, every placed
+// cell is taken from it (stopping once it runs dry) and every player-placed cell that gets
+// overwritten is refunded. `protect_world` limits the brush to air and player-placed cells, and
+// `locks` keeps it off locked cells.
+struct PaintAllowance<'a> {
+    max_cells: u32,
+    inventory: Option<&'a mut Inventory>,
+    protect_world: bool,
+    locks: Option<&'a PaintLocks>,
+}
+
+// --- EVENTS ---
+
+// A command that has gone into the world, before tick `tick` ran.
+#[derive(Event, Clone, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+pub struct AppliedCommand {
+    pub tick: u64,
+    pub command: WorldCommand,
+}
+
+// --- RESOURCES ---
+
+// The commands waiting for the next tick boundary, oldest first.
+#[derive(Resource, Default)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource, Default))]
+pub struct WorldCommands {
+    queue: Vec<WorldCommand>,
+}
+
+impl WorldCommands {
+    pub fn push(&mut self, command: WorldCommand) {
+        self.queue.push(command);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // Takes every command waiting, so none of them go into the world here.
+    pub fn take(&mut self) -> Vec<WorldCommand> {
+        std::mem::take(&mut self.queue)
+    }
+}
+
+// --- SYSTEM PARAM ---
+
+// Changing the world from gameplay code like the game's own tools do, by way of the command
+// queue, so the changes go in between ticks in order with everything else; and reading it. What
+// is read is the world as it was when the system ran, before this frame's commands.
+#[derive(SystemParam)]
+pub struct SimulationCommands<'w> {
+    commands: ResMut<'w, WorldCommands>,
+    grid: Res<'w, SimulationGrid>,
+}
+
+impl SimulationCommands<'_> {
+    pub fn set(&mut self, x: i32, y: i32, particle: Particle) {
+        self.commands.push(WorldCommand::Set {
+            particle,
+            cells: vec![CellPos::new(x, y)],
+        });
+    }
+
+    // Fills the inclusive rectangle `min..=max`.
+    pub fn fill_rect(&mut self, min: CellPos, max: CellPos, particle: Particle) {
+        self.commands.push(WorldCommand::FillRect { min, max, particle });
+    }
+
+    pub fn fill_circle(&mut self, center: CellPos, radius: i32, particle: Particle) {
+        self.commands.push(WorldCommand::FillCircle {
+            center,
+            radius,
+            particle,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.push(WorldCommand::Clear);
+    }
+
+    // Queues any other command, a brush stroke or a paste say.
+    pub fn push(&mut self, command: WorldCommand) {
+        self.commands.push(command);
+    }
+
+    // Every cell of the inclusive rectangle `min..=max` inside the world, as it is now.
+    pub fn query_region(
+        &self,
+        min: CellPos,
+        max: CellPos,
+    ) -> impl Iterator<Item = (CellPos, CellState)> + '_ {
+        self.grid.query_region(min, max)
+    }
+
+    pub fn grid(&self) -> &SimulationGrid {
+        &self.grid
+    }
+}
+
+// What commands are checked against, and where what they did is kept: the undo history and,
+// while recording a replay, the recording.
+#[derive(SystemParam)]
+pub struct CommandLimits<'w> {
+    rules: Res<'w, PaintRules>,
+    locks: Res<'w, PaintLocks>,
+    inventory: ResMut<'w, Inventory>,
+    history: ResMut<'w, PaintHistory>,
+    stats: Res<'w, SimulationStats>,
+    recording: Option<ResMut<'w, ReplayRecording>>,
+}
+
+// --- SYSTEMS ---
+
+pub fn apply_world_commands(
+    mut commands: ResMut<WorldCommands>,
+    mut grid: ResMut<SimulationGrid>,
+    mut limits: CommandLimits,
+    mut sim_events: EventWriter<SimEvent>,
+    mut applied: EventWriter<AppliedCommand>,
+) {
+    if commands.queue.is_empty() {
+        return;
+    }
+    let tick = limits.stats.tick;
+    for command in std::mem::take(&mut commands.queue) {
+        apply(&command, &mut grid, &mut limits, &mut sim_events);
+        applied.write(AppliedCommand { tick, command });
+    }
+}
+
+// --- HELPERS ---
+
+fn apply(
+    command: &WorldCommand,
+    grid: &mut SimulationGrid,
+    limits: &mut CommandLimits,
+    sim_events: &mut EventWriter<SimEvent>,
+) {
+    let tick = limits.stats.tick;
+    match command {
+        &WorldCommand::Paint {
+            player,
+            particle,
+            data,
+            ref cells,
+            max_cells,
+        } => {
+            // The palette shows how much is left.
+            if !limits.rules.is_allowed(particle) || limits.inventory.remaining(particle) == Some(0)
+            {
+                return;
+            }
+            let allowance = PaintAllowance {
+                max_cells,
+                inventory: Some(&mut limits.inventory),
+                protect_world: limits.rules.protect_world,
+                locks: Some(&limits.locks),
+            };
+            let before: Vec<_> = cells.iter().map(|cell| grid.cell(cell.x, cell.y)).collect();
+            let painted = paint_brush(grid, cells.iter().copied(), particle, data, allowance);
+            if let Some(recording) = &mut limits.recording {
+                recording.note_paint(tick, particle, data, &painted);
+            }
+            let stroke = limits.history.stroke(player);
+            for (&cell, before) in cells.iter().zip(before) {
+                stroke.note(grid, cell, before);
+            }
+            sim_events.write(SimEvent::Painted {
+                player,
+                particle,
+                cells: painted.len() as u32,
+            });
+        }
+        WorldCommand::EndStroke { player } => limits.history.finish(*player),
+        &WorldCommand::Place {
+            particle,
+            data,
+            ref cells,
+        } => {
+            let placed: Vec<_> = cells
+                .iter()
+                .copied()
+                .filter(|cell| grid.place(cell.x, cell.y, particle, data))
+                .collect();
+            if let Some(recording) = &mut limits.recording {
+                recording.note_paint(tick, particle, data, &placed);
+            }
+        }
+        WorldCommand::Paste { stamp, at } => {
+            // Pasting would hand out free material.
+            if limits.rules.protect_world || limits.inventory.is_enabled() {
+                return;
+            }
+            let origin = *at - IVec2::new(stamp.width as i32, stamp.height as i32) / 2;
+            // The whole rectangle the stamp covers, as it was, for undoing the paste.
+            let covered: Vec<_> = (0..stamp.height as i32)
+                .flat_map(|y| (0..stamp.width as i32).map(move |x| origin + IVec2::new(x, y)))
+                .map(|cell| (cell, grid.cell(cell.x, cell.y)))
+                .collect();
+            let changed = stamp.paste(grid, origin, &limits.locks);
+            let mut edit = PaintEdit::default();
+            for (cell, before) in covered {
+                edit.note(grid, cell, before);
+            }
+            limits.history.push(edit);
+            info!("Pasted \"{}\" ({} cells)", stamp.name, changed);
+        }
+        &WorldCommand::Set {
+            particle,
+            ref cells,
+        } => {
+            for cell in cells {
+                grid.set(cell.x, cell.y, particle);
+            }
+        }
+        &WorldCommand::FillRect { min, max, particle } => {
+            grid.fill_rect(min, max, particle);
+        }
+        &WorldCommand::FillCircle {
+            center,
+            radius,
+            particle,
+        } => {
+            grid.fill_circle(center, radius, particle);
+        }
+        &WorldCommand::Backdrop {
+            particle,
+            ref cells,
+        } => {
+            for cell in cells {
+                grid.set_backdrop(cell.x, cell.y, particle);
+            }
+        }
+        &WorldCommand::Heat { min, max, degrees } => grid.heat_rect(min, max, degrees),
+        &WorldCommand::Explode { center, radius } => {
+            blast(grid, &Explosion { center, radius }, false);
+        }
+        WorldCommand::Tags(operation) => {
+            let edit = edit_tags(grid, operation, limits.rules.protect_world, &limits.locks);
+            limits.history.push(edit);
+        }
+        WorldCommand::Bodies(operation) => {
+            edit_bodies(grid, operation, limits.rules.protect_world);
+        }
+        WorldCommand::Clear => grid.clear(),
+        WorldCommand::Undo | WorldCommand::Redo => {
+            // With an inventory, undoing would hand out free material.
+            if limits.inventory.is_enabled() {
+                info!("Undo is off while materials are limited");
+                return;
+            }
+            if matches!(command, WorldCommand::Undo) {
+                limits.history.undo(grid, &limits.locks);
+            } else {
+                limits.history.redo(grid, &limits.locks);
+            }
+        }
+    }
+}
+
+// Paints `particle`, with state byte `data`, into the given grid cells (usually a brush shape's,
+// see `BrushShape::cells`), within what `allowance` permits. Returns the cells that actually
+// changed, in the order they were painted.
+fn paint_brush(
+    grid: &mut SimulationGrid,
+    brush_cells: impl IntoIterator<Item = CellPos>,
+    particle: Particle,
+    data: u8,
+    allowance: PaintAllowance,
+) -> Vec<CellPos> {
+    let PaintAllowance {
+        max_cells,
+        mut inventory,
+        protect_world,
+        locks,
+    } = allowance;
+    let mut cells = Vec::new();
+    for CellPos(IVec2 { x, y }) in brush_cells {
+        if cells.len() as u32 >= max_cells {
+            return cells;
+        }
+        let unchanged = |old| old == particle && grid.data(x, y) == Some(data);
+        let Some(old) = grid.get(x, y).filter(|&old| !unchanged(old)) else {
+            continue;
+        };
+        let placed = grid.is_placed(x, y);
+        if protect_world && old != Particle::Air && !placed {
+            continue;
+        }
+        if locks.is_some_and(|locks| locks.is_locked(CellPos::new(x, y), old)) {
+            continue;
+        }
+
+        if let Some(inventory) = inventory.as_deref_mut() {
+            if particle != Particle::Air && !inventory.take(particle) {
+                return cells;
+            }
+            if placed {
+                inventory.refund(old);
+            }
+        }
+        if grid.place(x, y, particle, data) {
+            cells.push(CellPos::new(x, y));
+        }
+    }
+    cells
+}
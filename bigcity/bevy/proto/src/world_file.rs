@@ -0,0 +1,355 @@
+// --- IMPORTS ---
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Particle;
+use crate::bodies::CellBodies;
+use crate::loops::LoopBand;
+use crate::sim::SimulationGrid;
+use crate::snapshot::WorldSnapshot;
+use crate::tags::CellTags;
+use crate::zones::{MaterialOverride, ParamZone};
+
+// --- CONSTANTS ---
+// Bumped whenever the sidecar or the picture changes in a way older builds can't read.
+const FORMAT_VERSION: u32 = 1;
+
+// --- TYPES ---
+
+// Writes and reads a world as two files: a paletted PNG with one pixel per cell (top row first, in
+// the materials' colors, so it opens in any image viewer), and a RON sidecar with what the picture
+// can't hold. The sidecar names the material of every palette index, so a world keeps loading
+// when materials are added or reordered; cells of a material this build doesn't know any more
+// become air. State bytes, the walls behind the cells (numbered by the same palette), zones, loop
+// bands, material overrides, tags and rigid bodies go in the sidecar too.
+pub struct WorldSerializer;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Sidecar {
+    version: u32,
+    // What the world is called, for worlds that carry their own name, like stamps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    width: u32,
+    height: u32,
+    // The material each palette index of the picture stands for, by name.
+    palette: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    states: Vec<(u8, u32)>,
+    #[serde(default)]
+    zones: Vec<ParamZone>,
+    #[serde(default)]
+    loops: Vec<LoopBand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<MaterialOverride>,
+    #[serde(default, skip_serializing_if = "CellTags::is_empty")]
+    tags: CellTags,
+    #[serde(default, skip_serializing_if = "CellBodies::is_empty")]
+    bodies: CellBodies,
+    // The walls behind the cells, run-length encoded palette indices.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    backdrop: Vec<(u8, u32)>,
+}
+
+// A world read back, its name if it was saved with one, and the names of the materials in it
+// this build doesn't know.
+pub struct LoadedWorld {
+    pub snapshot: WorldSnapshot,
+    pub name: Option<String>,
+    pub unknown: Vec<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum WorldFileError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write the picture: {0}")]
+    Encode(#[from] png::EncodingError),
+    #[error("unreadable picture: {0}")]
+    Decode(#[from] png::DecodingError),
+    #[error("could not write the sidecar: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("unreadable sidecar: {0}")]
+    Sidecar(#[from] ron::error::SpannedError),
+    #[error("saved by a newer version (format {0})")]
+    Version(u32),
+    #[error("the picture doesn't match its sidecar")]
+    Mismatch,
+}
+
+impl WorldSerializer {
+    pub fn save(
+        grid: &SimulationGrid,
+        picture: &Path,
+        sidecar: &Path,
+    ) -> Result<(), WorldFileError> {
+        Self::save_named(grid, None, picture, sidecar)
+    }
+
+    // Saves the world with `name` in its sidecar.
+    pub fn save_named(
+        grid: &SimulationGrid,
+        name: Option<&str>,
+        picture: &Path,
+        sidecar: &Path,
+    ) -> Result<(), WorldFileError> {
+        let snapshot = WorldSnapshot::from_grid(grid);
+        let palette: Vec<String> = Particle::ALL.iter().map(|&p| material_name(p)).collect();
+        let colors: Vec<u8> = Particle::ALL
+            .iter()
+            .flat_map(|p| p.color().to_srgba().to_u8_array_no_alpha())
+            .collect();
+
+        let (width, height) = (grid.width(), grid.height());
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in (0..height as i32).rev() {
+            for x in 0..width as i32 {
+                pixels.push(grid.get(x, y).unwrap_or_default() as u8);
+            }
+        }
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(picture)?), width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(colors);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&pixels)?;
+        writer.finish()?;
+
+        let header = Sidecar {
+            version: FORMAT_VERSION,
+            name: name.map(String::from),
+            width,
+            height,
+            palette,
+            states: snapshot.states,
+            zones: snapshot.zones,
+            loops: snapshot.loops,
+            materials: snapshot.materials,
+            tags: snapshot.tags,
+            bodies: snapshot.bodies,
+            backdrop: numbered_runs(&snapshot.backdrop),
+        };
+        std::fs::write(sidecar, ron::ser::to_string_pretty(&header, default())?)?;
+        Ok(())
+    }
+
+    pub fn load(picture: &Path, sidecar: &Path) -> Result<LoadedWorld, WorldFileError> {
+        let header: Sidecar = ron::from_str(&std::fs::read_to_string(sidecar)?)?;
+        if header.version > FORMAT_VERSION {
+            return Err(WorldFileError::Version(header.version));
+        }
+        let mut decoder = png::Decoder::new(File::open(picture)?);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info()?;
+        let info = reader.info();
+        let indexed = info.color_type == png::ColorType::Indexed
+            && info.bit_depth == png::BitDepth::Eight;
+        if !indexed || (info.width, info.height) != (header.width, header.height) {
+            return Err(WorldFileError::Mismatch);
+        }
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels)?;
+
+        let materials: Vec<Option<Particle>> =
+            header.palette.iter().map(|name| parse_material(name)).collect();
+        let mut unknown = Vec::new();
+        let mut runs: Vec<(Particle, u32)> = Vec::new();
+        // The picture's rows run top-down, the grid's bottom-up.
+        let rows = pixels.chunks_exact(header.width as usize).take(header.height as usize);
+        for row in rows.rev() {
+            for &index in row {
+                let index = index as usize;
+                let particle = materials.get(index).copied().flatten().unwrap_or_else(|| {
+                    // A name only counts once, however many cells are lost with it.
+                    let name = header.palette.get(index).cloned().unwrap_or_default();
+                    if !unknown.contains(&name) {
+                        unknown.push(name);
+                    }
+                    Particle::Air
+                });
+                match runs.last_mut() {
+                    Some((run, count)) if *run == particle => *count += 1,
+                    _ => runs.push((particle, 1)),
+                }
+            }
+        }
+
+        let snapshot = WorldSnapshot {
+            width: header.width,
+            height: header.height,
+            runs,
+            states: header.states,
+            zones: header.zones,
+            loops: header.loops,
+            materials: header.materials,
+            tags: header.tags,
+            bodies: header.bodies,
+            backdrop: material_runs(&header.backdrop, &materials),
+        };
+        Ok(LoadedWorld {
+            snapshot,
+            name: header.name,
+            unknown,
+        })
+    }
+}
+
+// --- HELPERS ---
+
+// A material's name is its serde name, the same one RON snapshots use.
+pub fn material_name(particle: Particle) -> String {
+    ron::to_string(&particle).unwrap_or_default()
+}
+
+pub fn parse_material(name: &str) -> Option<Particle> {
+    ron::from_str(name).ok()
+}
+
+// Runs of materials as runs of their indices in this build's palette, which is `Particle::ALL`.
+pub fn numbered_runs(runs: &[(Particle, u32)]) -> Vec<(u8, u32)> {
+    runs.iter().map(|&(particle, count)| (particle as u8, count)).collect()
+}
+
+// Runs of palette indices back as runs of materials, air for any the palette doesn't know.
+pub fn material_runs(runs: &[(u8, u32)], palette: &[Option<Particle>]) -> Vec<(Particle, u32)> {
+    let material = |index: u8| palette.get(index as usize).copied().flatten().unwrap_or_default();
+    runs.iter().map(|&(index, count)| (material(index), count)).collect()
+}
+
+// --- TESTS ---
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_utils::Scratch;
+
+    // A world file's picture and sidecar in a scratch folder.
+    fn paths(scratch: &Scratch) -> (PathBuf, PathBuf) {
+        (scratch.path("world.png"), scratch.path("world.ron"))
+    }
+
+    fn read_sidecar(path: &Path) -> Sidecar {
+        ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn write_sidecar(path: &Path, header: &Sidecar) {
+        std::fs::write(path, ron::to_string(header).unwrap()).unwrap();
+    }
+
+    fn sample_grid() -> SimulationGrid {
+        let mut grid = SimulationGrid::new(12, 7);
+        for x in 0..12 {
+            grid.set(x, 0, Particle::Bedrock);
+        }
+        grid.set(3, 1, Particle::Sand);
+        grid.set(4, 1, Particle::Water);
+        grid.set(5, 6, Particle::Uranium);
+        grid.place(6, 2, Particle::Mirror, 3);
+        grid.set_data(4, 1, 200);
+        for x in 2..8 {
+            grid.set_backdrop(x, 3, Particle::Glass);
+        }
+        grid
+    }
+
+    #[test]
+    fn round_trip_keeps_cells_and_states() {
+        let scratch = Scratch::new("world-file-round-trip");
+        let (picture, sidecar) = paths(&scratch);
+        let grid = sample_grid();
+        WorldSerializer::save(&grid, &picture, &sidecar).unwrap();
+
+        let loaded = WorldSerializer::load(&picture, &sidecar).unwrap();
+        assert!(loaded.unknown.is_empty());
+        assert_eq!(loaded.snapshot, WorldSnapshot::from_grid(&grid));
+
+        let mut restored = SimulationGrid::new(12, 7);
+        loaded.snapshot.apply_to(&mut restored);
+        assert_eq!(restored.cells(), grid.cells());
+        assert_eq!(restored.states(), grid.states());
+        assert_eq!(restored.backdrops(), grid.backdrops());
+
+        // Packed walls read back the same.
+        let mut packed = SimulationGrid::new(12, 7);
+        packed.set_low_memory(true);
+        loaded.snapshot.apply_to(&mut packed);
+        assert_ne!(packed.backdrop_layer(), restored.backdrop_layer());
+        assert_eq!(packed.backdrops(), grid.backdrops());
+    }
+
+    #[test]
+    fn reordered_palette_keeps_materials() {
+        let scratch = Scratch::new("world-file-reordered");
+        let (picture, sidecar) = paths(&scratch);
+        let grid = sample_grid();
+        WorldSerializer::save(&grid, &picture, &sidecar).unwrap();
+
+        // Swap what two palette indices stand for, as a build with reordered materials would
+        // have written them, and the pixels with them.
+        let (sand, water) = (Particle::Sand as u8, Particle::Water as u8);
+        let mut header = read_sidecar(&sidecar);
+        header.palette.swap(sand as usize, water as usize);
+        write_sidecar(&sidecar, &header);
+        let mut decoder = png::Decoder::new(File::open(&picture).unwrap());
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info().unwrap();
+        let palette = reader.info().palette.clone().unwrap().into_owned();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        for pixel in &mut pixels {
+            *pixel = match *pixel {
+                p if p == sand => water,
+                p if p == water => sand,
+                p => p,
+            };
+        }
+        let mut encoder = png::Encoder::new(File::create(&picture).unwrap(), 12, 7);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_palette(palette);
+        encoder.write_header().unwrap().write_image_data(&pixels).unwrap();
+
+        let loaded = WorldSerializer::load(&picture, &sidecar).unwrap();
+        assert_eq!(loaded.snapshot, WorldSnapshot::from_grid(&grid));
+    }
+
+    #[test]
+    fn unknown_materials_become_air() {
+        let scratch = Scratch::new("world-file-unknown");
+        let (picture, sidecar) = paths(&scratch);
+        WorldSerializer::save(&sample_grid(), &picture, &sidecar).unwrap();
+
+        let mut header = read_sidecar(&sidecar);
+        header.palette[Particle::Uranium as usize] = "Unobtainium".to_string();
+        header.palette[Particle::Lead as usize] = "Mithril".to_string();
+        write_sidecar(&sidecar, &header);
+
+        let loaded = WorldSerializer::load(&picture, &sidecar).unwrap();
+        // Only the name of a material the world has cells of is reported.
+        assert_eq!(loaded.unknown, vec!["Unobtainium".to_string()]);
+        let mut restored = SimulationGrid::new(12, 7);
+        loaded.snapshot.apply_to(&mut restored);
+        assert_eq!(restored.get(5, 6), Some(Particle::Air));
+        assert_eq!(restored.get(3, 1), Some(Particle::Sand));
+    }
+
+    #[test]
+    fn newer_formats_are_refused() {
+        let scratch = Scratch::new("world-file-newer");
+        let (picture, sidecar) = paths(&scratch);
+        WorldSerializer::save(&sample_grid(), &picture, &sidecar).unwrap();
+
+        let mut header = read_sidecar(&sidecar);
+        header.version = FORMAT_VERSION + 1;
+        write_sidecar(&sidecar, &header);
+
+        let result = WorldSerializer::load(&picture, &sidecar);
+        assert!(matches!(result, Err(WorldFileError::Version(v)) if v == FORMAT_VERSION + 1));
+    }
+}
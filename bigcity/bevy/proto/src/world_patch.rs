@@ -0,0 +1,341 @@
+// --- IMPORTS ---
+use std::io::Read;
+use std::path::Path;
+
+use bevy::prelude::*;
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::decoding::errors::FrameDecoderError;
+use ruzstd::encoding::{CompressionLevel, compress_to_vec};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Particle;
+use crate::bodies::CellBodies;
+use crate::coords::ChunkPos;
+use crate::loops::LoopBand;
+use crate::sim::SimulationGrid;
+use crate::snapshot::WorldSnapshot;
+use crate::tags::CellTags;
+use crate::world_file::{
+    LoadedWorld, material_name, material_runs, numbered_runs, parse_material,
+};
+use crate::zones::{MaterialOverride, ParamZone};
+
+// --- CONSTANTS ---
+const MAGIC: &[u8; 8] = b"FSPATCH\n";
+// Bumped whenever patches change in a way older builds can't read.
+const FORMAT_VERSION: u32 = 1;
+// What a base cell whose material the patch's palette lacks stands as, so it never matches.
+const NOT_IN_PALETTE: u8 = u8::MAX;
+
+// --- TYPES ---
+
+// Writes and reads a world as a patch against the scenario it started from, for sharing changed
+// versions of the standard ones in a few hundred bytes. The world and the base are compared chunk
+// by chunk: only chunks whose cells or state bytes differ are kept, as the two XORed together,
+// and those bytes are compressed with zstd. The file is a short marker, a RON header with the
+// base's name and a hash of it, the palette the cells are numbered by (like world files', so
+// patches keep loading when materials are added or reordered), which chunks follow, and the
+// world's zones, loop bands, material overrides, tags, rigid bodies and walls whole, and then the
+// compressed chunks.
+// A patch only loads against the same base: one that has changed since is refused.
+pub struct WorldPatch;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct PatchHeader {
+    version: u32,
+    // The scenario the world started from, as `ScenarioBases` names it.
+    base: String,
+    // A hash of the base's cells and state bytes, numbered by `palette`.
+    base_hash: u64,
+    width: u32,
+    height: u32,
+    palette: Vec<String>,
+    // The chunks that differ, in the order their bytes follow: the cells', then the state bytes'.
+    chunks: Vec<(i32, i32)>,
+    #[serde(default)]
+    zones: Vec<ParamZone>,
+    #[serde(default)]
+    loops: Vec<LoopBand>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    materials: Vec<MaterialOverride>,
+    #[serde(default, skip_serializing_if = "CellTags::is_empty")]
+    tags: CellTags,
+    #[serde(default, skip_serializing_if = "CellBodies::is_empty")]
+    bodies: CellBodies,
+    // The walls behind the cells, run-length encoded palette indices.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    backdrop: Vec<(u8, u32)>,
+}
+
+#[derive(Error, Debug)]
+pub enum PatchError {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write the header: {0}")]
+    Ron(#[from] ron::Error),
+    #[error("unreadable header: {0}")]
+    Header(#[from] ron::error::SpannedError),
+    #[error("damaged: {0}")]
+    Compressed(#[from] FrameDecoderError),
+    #[error("not a world patch")]
+    NotAPatch,
+    #[error("saved by a newer version (format {0})")]
+    Version(u32),
+    #[error("the scenario it was saved against, {0}, isn't available")]
+    MissingBase(String),
+    #[error("the scenario it was saved against, {0}, has changed since")]
+    BaseChanged(String),
+    #[error("the world isn't the size of its scenario")]
+    Size,
+    #[error("damaged: its chunks don't match its header")]
+    Damaged,
+}
+
+impl WorldPatch {
+    // Saves `grid` as a patch against `base`, the scenario called `base_name`. Returns how many
+    // bytes the file took.
+    pub fn save(
+        grid: &SimulationGrid,
+        base_name: &str,
+        base: &SimulationGrid,
+        path: &Path,
+    ) -> Result<usize, PatchError> {
+        let (width, height) = (grid.width(), grid.height());
+        if (base.width(), base.height()) != (width, height) {
+            return Err(PatchError::Size);
+        }
+        // This build numbers materials by `Particle::ALL`, so the palette is that.
+        let palette: Vec<String> = Particle::ALL.iter().map(|&p| material_name(p)).collect();
+        let world = numbered(grid, &palette);
+        let base_cells = numbered(base, &palette);
+
+        let (mut chunks, mut bytes) = (Vec::new(), Vec::new());
+        for chunk in ChunkPos::covering(width, height) {
+            let xored: Vec<u8> = chunk_bytes(chunk, width, height, &world)
+                .zip(chunk_bytes(chunk, width, height, &base_cells))
+                .map(|(cell, base)| cell ^ base)
+                .collect();
+            if xored.iter().any(|&byte| byte != 0) {
+                chunks.push((chunk.0.x, chunk.0.y));
+                bytes.extend(xored);
+            }
+        }
+
+        let snapshot = WorldSnapshot::from_grid(grid);
+        let header = PatchHeader {
+            version: FORMAT_VERSION,
+            base: base_name.to_string(),
+            base_hash: hash(&base_cells),
+            width,
+            height,
+            palette,
+            chunks,
+            zones: snapshot.zones,
+            loops: snapshot.loops,
+            materials: snapshot.materials,
+            tags: snapshot.tags,
+            bodies: snapshot.bodies,
+            backdrop: numbered_runs(&snapshot.backdrop),
+        };
+        let header = ron::to_string(&header)?;
+        let mut file = MAGIC.to_vec();
+        file.extend((header.len() as u32).to_le_bytes());
+        file.extend(header.as_bytes());
+        file.extend(compress_to_vec(bytes.as_slice(), CompressionLevel::Fastest));
+        std::fs::write(path, &file)?;
+        Ok(file.len())
+    }
+
+    // The name of the scenario the patch at `path` was saved against, and its size, so the base
+    // can be set up before the patch is loaded onto it.
+    pub fn base_of(path: &Path) -> Result<(String, UVec2), PatchError> {
+        let (header, _) = read(path)?;
+        Ok((header.base, UVec2::new(header.width, header.height)))
+    }
+
+    // Reads the patch at `path` back onto `base`. Cells of a material this build doesn't know any
+    // more become air.
+    pub fn load(path: &Path, base: &SimulationGrid) -> Result<LoadedWorld, PatchError> {
+        let (header, compressed) = read(path)?;
+        let (width, height) = (header.width, header.height);
+        if (base.width(), base.height()) != (width, height) {
+            return Err(PatchError::Size);
+        }
+        let mut cells = numbered(base, &header.palette);
+        if hash(&cells) != header.base_hash {
+            return Err(PatchError::BaseChanged(header.base));
+        }
+        let mut bytes = Vec::new();
+        StreamingDecoder::new(compressed.as_slice())
+            .map_err(PatchError::Compressed)?
+            .read_to_end(&mut bytes)?;
+
+        let mut bytes = bytes.into_iter();
+        let area = (width * height) as usize;
+        for &(x, y) in &header.chunks {
+            let chunk = ChunkPos(IVec2::new(x, y));
+            let across = ChunkPos::across(width, height);
+            if x < 0 || y < 0 || x >= across.x || y >= across.y {
+                return Err(PatchError::Damaged);
+            }
+            for i in chunk_cells(chunk, width, height) {
+                cells[i] ^= bytes.next().ok_or(PatchError::Damaged)?;
+            }
+            for i in chunk_cells(chunk, width, height) {
+                cells[area + i] ^= bytes.next().ok_or(PatchError::Damaged)?;
+            }
+        }
+        if bytes.next().is_some() {
+            return Err(PatchError::Damaged);
+        }
+
+        let materials: Vec<Option<Particle>> =
+            header.palette.iter().map(|name| parse_material(name)).collect();
+        let mut unknown = Vec::new();
+        let mut grid = SimulationGrid::new(width, height);
+        for i in 0..area {
+            let (x, y) = ((i as u32 % width) as i32, (i as u32 / width) as i32);
+            let index = cells[i] as usize;
+            let particle = materials.get(index).copied().flatten().unwrap_or_else(|| {
+                let name = header.palette.get(index).cloned().unwrap_or_default();
+                if !unknown.contains(&name) {
+                    unknown.push(name);
+                }
+                Particle::Air
+            });
+            grid.set(x, y, particle);
+            grid.set_data(x, y, cells[area + i]);
+        }
+        let snapshot = WorldSnapshot {
+            zones: header.zones,
+            loops: header.loops,
+            materials: header.materials,
+            tags: header.tags,
+            bodies: header.bodies,
+            backdrop: material_runs(&header.backdrop, &materials),
+            ..WorldSnapshot::from_grid(&grid)
+        };
+        Ok(LoadedWorld {
+            snapshot,
+            name: None,
+            unknown,
+        })
+    }
+}
+
+// --- HELPERS ---
+
+fn read(path: &Path) -> Result<(PatchHeader, Vec<u8>), PatchError> {
+    let file = std::fs::read(path)?;
+    let rest = file.strip_prefix(MAGIC).ok_or(PatchError::NotAPatch)?;
+    let (length, rest) = rest.split_first_chunk::<4>().ok_or(PatchError::NotAPatch)?;
+    let length = u32::from_le_bytes(*length) as usize;
+    let (header, compressed) =
+        rest.split_at_checked(length).ok_or(PatchError::NotAPatch)?;
+    let header: PatchHeader = ron::from_str(&String::from_utf8_lossy(header))?;
+    if header.version > FORMAT_VERSION {
+        return Err(PatchError::Version(header.version));
+    }
+    Ok((header, compressed.to_vec()))
+}
+
+// Every cell of `grid` as its index in `palette`, followed by every cell's state byte.
+fn numbered(grid: &SimulationGrid, palette: &[String]) -> Vec<u8> {
+    let mut indices = [NOT_IN_PALETTE; Particle::ALL.len()];
+    for particle in Particle::ALL {
+        let name = material_name(particle);
+        if let Some(index) = palette.iter().position(|known| *known == name) {
+            indices[particle as usize] = index as u8;
+        }
+    }
+    let cells = grid.cells().iter().map(|&particle| indices[particle as usize]);
+    cells.chain(grid.states().iter().copied()).collect()
+}
+
+// The indices into a grid's cells of the cells of `chunk` inside it, row by row.
+fn chunk_cells(chunk: ChunkPos, width: u32, height: u32) -> impl Iterator<Item = usize> {
+    let (origin, size) = (chunk.origin(), chunk.size_within(width, height));
+    (0..size.y as i32).flat_map(move |y| {
+        let row = (origin.y + y) as usize * width as usize + origin.x as usize;
+        row..row + size.x as usize
+    })
+}
+
+// The bytes `numbered` gives for the cells of `chunk`: their cells', then their state bytes'.
+fn chunk_bytes<'a>(
+    chunk: ChunkPos,
+    width: u32,
+    height: u32,
+    numbered: &'a [u8],
+) -> impl Iterator<Item = u8> + 'a {
+    let area = (width * height) as usize;
+    let cells = chunk_cells(chunk, width, height).map(|i| numbered[i]);
+    cells.chain(chunk_cells(chunk, width, height).map(move |i| numbered[area + i]))
+}
+
+// FNV-1a, like replay checksums.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+// --- TESTS ---
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::Scratch;
+
+    // A scenario big enough to have a few chunks, with a floor and a pool.
+    fn scenario() -> SimulationGrid {
+        let mut grid = SimulationGrid::new(100, 70);
+        for x in 0..100 {
+            grid.set(x, 0, Particle::Bedrock);
+        }
+        for y in 1..10 {
+            for x in 20..60 {
+                grid.set(x, y, Particle::Water);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn patches_round_trip_against_their_base() {
+        let scratch = Scratch::new("world-patch-round-trip");
+        let path = scratch.path("world.patch");
+        let base = scenario();
+        let mut grid = base.clone();
+        grid.set(5, 40, Particle::Sand);
+        grid.place(90, 65, Particle::Mirror, 3);
+        grid.set(30, 5, Particle::Oil);
+
+        let size = WorldPatch::save(&grid, "level:pool", &base, &path).unwrap();
+        assert_eq!(WorldPatch::base_of(&path).unwrap().0, "level:pool");
+        let loaded = WorldPatch::load(&path, &base).unwrap();
+        assert!(loaded.unknown.is_empty());
+        assert_eq!(loaded.snapshot, WorldSnapshot::from_grid(&grid));
+        // Three changed chunks of the twelve, against 14000 bytes of cells and state bytes; most of
+        // what is left is the palette.
+        assert!(size < 600, "the patch took {} bytes", size);
+    }
+
+    #[test]
+    fn changed_bases_are_refused() {
+        let scratch = Scratch::new("world-patch-changed-base");
+        let path = scratch.path("world.patch");
+        let base = scenario();
+        let mut grid = base.clone();
+        grid.set(5, 40, Particle::Sand);
+        WorldPatch::save(&grid, "level:pool", &base, &path).unwrap();
+
+        let mut changed = base.clone();
+        changed.set(70, 1, Particle::Lead);
+        let refused = WorldPatch::load(&path, &changed);
+        assert!(matches!(refused, Err(PatchError::BaseChanged(_))));
+        let refused = WorldPatch::load(&path, &SimulationGrid::new(64, 64));
+        assert!(matches!(refused, Err(PatchError::Size)));
+    }
+}
@@ -0,0 +1,314 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use bevy::tasks::futures::check_ready;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+
+use crate::coords::{CellPos, ChunkPos};
+use crate::events::SimEvent;
+use crate::player::PlayerInputSet;
+use crate::sim::{SimulationGrid, SimulationSet, roll};
+use crate::{Particle, WorldLayout};
+
+// --- CONSTANTS ---
+// The flat world's bedrock floor, in rows.
+const FLOOR_DEPTH: i32 = 5;
+// Hills: the lowest ground, how much higher the noise can lift it and the width of the largest
+// hills, in cells.
+const HILL_BASE: f32 = 24.0;
+const HILL_HEIGHT: f32 = 110.0;
+const HILL_WAVELENGTH: f32 = 96.0;
+const HILL_OCTAVES: u64 = 3;
+// How deep the sand over the bedrock is, at least and at most.
+const SAND_DEPTH: (f32, f32) = (4.0, 14.0);
+// Hollows below this row fill with water.
+const WATER_LEVEL: i32 = 56;
+// Ground above this row is capped with this much snow.
+const SNOW_LINE: i32 = 112;
+const SNOW_DEPTH: i32 = 4;
+const BAR_WIDTH: f32 = 320.0;
+
+// --- PLUGIN ---
+
+// Builds the starting world on the async compute pool, one chunk per task, and writes chunks into
+// the grid as they finish while a loading screen shows how far along it is. The world doesn't step
+// and can't be painted until every chunk is in. `--generate <seed>` starts in procedural hills
+// (any seed; without one it's picked from the clock); otherwise the world is a bedrock floor.
+pub struct WorldgenPlugin;
+
+impl Plugin for WorldgenPlugin {
+    fn build(&self, app: &mut App) {
+        let mut args = std::env::args().skip(1).peekable();
+        let mut terrain = Terrain::Flat;
+        while let Some(arg) = args.next() {
+            if arg == "--generate" {
+                let seed = args.next_if(|seed| !seed.starts_with("--"));
+                terrain = Terrain::Hills(seed.map_or_else(clock_seed, |seed| seed_from(&seed)));
+            }
+        }
+        app.insert_resource(StartingTerrain(terrain))
+            .configure_sets(
+                Update,
+                (SimulationSet, PlayerInputSet).run_if(not(resource_exists::<WorldGeneration>)),
+            )
+            .configure_sets(
+                FixedUpdate,
+                SimulationSet.run_if(not(resource_exists::<WorldGeneration>)),
+            )
+            .add_systems(Startup, start_generation)
+            .add_systems(
+                Update,
+                (stream_chunks, update_loading_screen)
+                    .chain()
+                    .run_if(resource_exists::<WorldGeneration>)
+                    .before(SimulationSet),
+            );
+    }
+}
+
+// --- TYPES ---
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "reflect", derive(Reflect))]
+enum Terrain {
+    // A bedrock floor and nothing else.
+    Flat,
+    // Rolling bedrock hills under sand, with lakes in the hollows and snow on the peaks.
+    Hills(u64),
+}
+
+impl Terrain {
+    // What is at (x, y). Only depends on the position, so chunks can be made in any order.
+    fn particle(self, x: i32, y: i32) -> Particle {
+        let seed = match self {
+            Terrain::Flat if y < FLOOR_DEPTH => return Particle::Bedrock,
+            Terrain::Flat => return Particle::Air,
+            Terrain::Hills(seed) => seed,
+        };
+        let ground = ground_height(seed, x);
+        let (least, most) = SAND_DEPTH;
+        let rock = ground - least - (most - least) * value_noise(seed ^ 0x5A4D, x as f32 / 24.0);
+        let ground = ground as i32;
+        if y < FLOOR_DEPTH.max(rock as i32) {
+            Particle::Bedrock
+        } else if y < ground {
+            Particle::Sand
+        } else if y < WATER_LEVEL {
+            Particle::Water
+        } else if ground >= SNOW_LINE && y < ground + SNOW_DEPTH {
+            Particle::Snow
+        } else {
+            Particle::Air
+        }
+    }
+}
+
+// One finished chunk: its bottom-left cell and its cells, row by row from the bottom.
+struct GeneratedChunk {
+    origin: CellPos,
+    size: UVec2,
+    cells: Vec<Particle>,
+}
+
+// The loading screen, hidden once the world is in.
+#[derive(Component)]
+struct LoadingScreen;
+
+#[derive(Component)]
+struct LoadingBar;
+
+// --- RESOURCES ---
+
+#[derive(Resource)]
+#[cfg_attr(feature = "reflect", derive(Reflect), reflect(Resource))]
+pub struct StartingTerrain(Terrain);
+
+impl StartingTerrain {
+    // The seed the hills were generated from, if the world started in hills.
+    pub fn seed(&self) -> Option<u64> {
+        match self.0 {
+            Terrain::Flat => None,
+            Terrain::Hills(seed) => Some(seed),
+        }
+    }
+
+    // What the starting terrain has at `cell`, which can be outside the world it started in.
+    pub fn particle(&self, cell: IVec2) -> Particle {
+        self.0.particle(cell.x, cell.y)
+    }
+}
+
+// The chunks still being generated. Exists only while they are.
+#[derive(Resource)]
+pub struct WorldGeneration {
+    tasks: Vec<Task<GeneratedChunk>>,
+    total: usize,
+}
+
+// --- SYSTEMS ---
+
+fn start_generation(
+    mut commands: Commands,
+    layout: Res<WorldLayout>,
+    terrain: Res<StartingTerrain>,
+) {
+    let terrain = terrain.0;
+    let pool = AsyncComputeTaskPool::get();
+    let mut tasks = Vec::new();
+    for chunk in ChunkPos::covering(layout.width, layout.height) {
+        let (origin, size) = (chunk.origin(), chunk.size_within(layout.width, layout.height));
+        tasks.push(pool.spawn(async move { generate_chunk(terrain, origin, size) }));
+    }
+    info!("Generating a {:?} world in {} chunks", terrain, tasks.len());
+    commands.insert_resource(WorldGeneration {
+        total: tasks.len(),
+        tasks,
+    });
+    spawn_loading_screen(&mut commands);
+}
+
+// Writes every chunk that finished since last frame into the grid.
+fn stream_chunks(
+    mut commands: Commands,
+    mut generation: ResMut<WorldGeneration>,
+    mut grid: ResMut<SimulationGrid>,
+    mut sim_events: EventWriter<SimEvent>,
+) {
+    let mut finished = Vec::new();
+    generation.tasks.retain_mut(|task| match check_ready(task) {
+        Some(chunk) => {
+            finished.push(chunk);
+            false
+        }
+        None => true,
+    });
+    for chunk in finished {
+        let rows = chunk.cells.chunks(chunk.size.x as usize);
+        for (y, row) in (chunk.origin.y..).zip(rows) {
+            for (x, &particle) in (chunk.origin.x..).zip(row) {
+                grid.set(x, y, particle);
+            }
+        }
+    }
+    if generation.tasks.is_empty() {
+        commands.remove_resource::<WorldGeneration>();
+        sim_events.write(SimEvent::WorldCreated);
+        info!("World generated");
+    }
+}
+
+fn update_loading_screen(
+    mut commands: Commands,
+    generation: Res<WorldGeneration>,
+    q_screen: Query<Entity, With<LoadingScreen>>,
+    mut q_bar: Query<&mut Node, With<LoadingBar>>,
+) {
+    // The last chunk removes the generation; the screen goes with it.
+    if generation.tasks.is_empty() {
+        for screen in &q_screen {
+            commands.entity(screen).despawn();
+        }
+        return;
+    }
+    let done = 1.0 - generation.tasks.len() as f32 / generation.total.max(1) as f32;
+    for mut bar in &mut q_bar {
+        bar.width = Val::Px(BAR_WIDTH * done);
+    }
+}
+
+// --- HELPERS ---
+
+fn spawn_loading_screen(commands: &mut Commands) {
+    commands
+        .spawn((
+            LoadingScreen,
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.05, 0.05, 0.07)),
+            GlobalZIndex(10),
+        ))
+        .with_children(|screen| {
+            screen.spawn((
+                Text::new("Generating world..."),
+                TextFont {
+                    font_size: 24.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            screen
+                .spawn((
+                    Node {
+                        width: Val::Px(BAR_WIDTH),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.2, 0.2, 0.25)),
+                ))
+                .with_child((
+                    LoadingBar,
+                    Node {
+                        width: Val::Px(0.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgb(0.9, 0.8, 0.5)),
+                ));
+        });
+}
+
+fn generate_chunk(terrain: Terrain, origin: CellPos, size: UVec2) -> GeneratedChunk {
+    let mut cells = Vec::with_capacity((size.x * size.y) as usize);
+    for y in origin.y..origin.y + size.y as i32 {
+        for x in origin.x..origin.x + size.x as i32 {
+            cells.push(terrain.particle(x, y));
+        }
+    }
+    GeneratedChunk {
+        origin,
+        size,
+        cells,
+    }
+}
+
+// The ground's height in column `x`: a few octaves of value noise, each half as wide and half as
+// tall as the one before.
+fn ground_height(seed: u64, x: i32) -> f32 {
+    let mut height = 0.0;
+    let mut scale = 0.0;
+    for octave in 0..HILL_OCTAVES {
+        let weight = 0.5f32.powi(octave as i32);
+        let wavelength = HILL_WAVELENGTH * weight;
+        height += weight * value_noise(seed.wrapping_add(octave), x as f32 / wavelength);
+        scale += weight;
+    }
+    HILL_BASE + HILL_HEIGHT * height / scale
+}
+
+// Smoothly interpolated random values in 0..1 at whole `t`.
+fn value_noise(seed: u64, t: f32) -> f32 {
+    let i = t.floor();
+    let f = t - i;
+    let ease = f * f * (3.0 - 2.0 * f);
+    let (a, b) = (roll(i as i32, 0, seed), roll(i as i32 + 1, 0, seed));
+    a + (b - a) * ease
+}
+
+// A seed from its text: a number as is, any other text hashed.
+fn seed_from(text: &str) -> u64 {
+    text.parse().unwrap_or_else(|_| {
+        text.bytes().fold(0xCBF2_9CE4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x100_0000_01B3))
+    })
+}
+
+fn clock_seed() -> u64 {
+    let now = web_time::SystemTime::now().duration_since(web_time::UNIX_EPOCH);
+    now.map_or(0, |since| since.as_nanos() as u64)
+}
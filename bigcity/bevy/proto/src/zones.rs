@@ -0,0 +1,319 @@
+// --- IMPORTS ---
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::Particle;
+use crate::coords::CellPos;
+use crate::levels::PaintRules;
+use crate::pan_zoom::ctrl_held;
+use crate::player::PlayerInputSet;
+use crate::sim::{SimParams, SimulationGrid, SimulationSet};
+use crate::WorldView;
+
+// --- PLUGIN ---
+pub struct ZonesPlugin;
+
+impl Plugin for ZonesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ZoneTool>()
+            .add_systems(Startup, spawn_zone_label)
+            .add_systems(
+                Update,
+                (place_zones, draw_zones, update_zone_label)
+                    .chain()
+                    .after(PlayerInputSet)
+                    .before(SimulationSet),
+            );
+    }
+}
+
+// --- TYPES ---
+
+// A rectangle of the world where some parameters differ from the global ones. Zones are part of
+// the world: they are saved in snapshots and levels, and cleared with it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamZone {
+    pub name: String,
+    // Inclusive corners, in cells.
+    pub min: (i32, i32),
+    pub max: (i32, i32),
+    pub overrides: ParamOverrides,
+}
+
+impl ParamZone {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.min.0..=self.max.0).contains(&x) && (self.min.1..=self.max.1).contains(&y)
+    }
+}
+
+// Parameters one material follows everywhere, on top of the global and zone ones, so that one
+// world's water can be extra viscous (a low dispersion) without changing anything else. Like
+// zones they belong to the world and are saved and cleared with it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MaterialOverride {
+    pub material: Particle,
+    pub overrides: ParamOverrides,
+}
+
+// The parameters a zone replaces; `None` keeps the global value. The tick rate is always global.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct ParamOverrides {
+    pub gravity: Option<f32>,
+    pub dispersion: Option<u32>,
+    pub cohesion: Option<f32>,
+    pub boil_chance: Option<f32>,
+    pub melt_chance: Option<f32>,
+    pub heat_diffusion: Option<f32>,
+    pub cooling_rate: Option<f32>,
+    pub ambient_temperature: Option<f32>,
+    pub wind: Option<f32>,
+}
+
+impl ParamOverrides {
+    pub fn apply(&self, global: &SimParams) -> SimParams {
+        SimParams {
+            gravity: self.gravity.unwrap_or(global.gravity),
+            dispersion: self.dispersion.unwrap_or(global.dispersion),
+            cohesion: self.cohesion.unwrap_or(global.cohesion),
+            boil_chance: self.boil_chance.unwrap_or(global.boil_chance),
+            melt_chance: self.melt_chance.unwrap_or(global.melt_chance),
+            heat_diffusion: self.heat_diffusion.unwrap_or(global.heat_diffusion),
+            cooling_rate: self.cooling_rate.unwrap_or(global.cooling_rate),
+            ambient_temperature: self.ambient_temperature.unwrap_or(global.ambient_temperature),
+            wind: self.wind.unwrap_or(global.wind),
+            thermostat: global.thermostat,
+        }
+    }
+}
+
+// The parameters in effect at every cell for one tick. Where zones overlap, the one placed last
+// wins, and a material's overrides win over both.
+pub struct LocalParams<'a> {
+    global: &'a SimParams,
+    zones: Vec<(ParamZone, SimParams)>,
+    // For each overridden material, its parameters outside all zones and then inside each zone.
+    materials: Vec<(Particle, Vec<SimParams>)>,
+}
+
+impl<'a> LocalParams<'a> {
+    pub fn new(global: &'a SimParams, zones: &[ParamZone], materials: &[MaterialOverride]) -> Self {
+        let zones: Vec<_> =
+            zones.iter().map(|zone| (zone.clone(), zone.overrides.apply(global))).collect();
+        let materials = materials
+            .iter()
+            .map(|material| {
+                let under = std::iter::once(global).chain(zones.iter().map(|(_, params)| params));
+                let params = under.map(|params| material.overrides.apply(params)).collect();
+                (material.material, params)
+            })
+            .collect();
+        Self {
+            global,
+            zones,
+            materials,
+        }
+    }
+
+    // The global parameters, every zone's and every material's.
+    pub fn all(&self) -> impl Iterator<Item = &SimParams> {
+        std::iter::once(self.global)
+            .chain(self.zones.iter().map(|(_, params)| params))
+            .chain(self.materials.iter().flat_map(|(_, params)| params))
+    }
+
+    // The parameters `particle` follows at (x, y).
+    pub fn at(&self, x: i32, y: i32, particle: Particle) -> &SimParams {
+        let zone = self.zones.iter().rposition(|(zone, _)| zone.contains(x, y));
+        match self.materials.iter().find(|(material, _)| *material == particle) {
+            Some((_, params)) => &params[zone.map_or(0, |zone| zone + 1)],
+            None => zone.map_or(self.global, |zone| &self.zones[zone].1),
+        }
+    }
+}
+
+// The kinds of zone the zone tool places.
+struct ZoneKind {
+    name: &'static str,
+    color: Color,
+    overrides: fn() -> ParamOverrides,
+}
+
+const ZONE_KINDS: [ZoneKind; 5] = [
+    ZoneKind {
+        name: "Low gravity",
+        color: Color::srgb(0.6, 0.4, 1.0),
+        overrides: || ParamOverrides {
+            gravity: Some(0.2),
+            dispersion: Some(3),
+            ..default()
+        },
+    },
+    ZoneKind {
+        name: "Hot",
+        color: Color::srgb(1.0, 0.4, 0.1),
+        overrides: || ParamOverrides {
+            ambient_temperature: Some(150.0),
+            cooling_rate: Some(0.05),
+            ..default()
+        },
+    },
+    ZoneKind {
+        name: "Cold",
+        color: Color::srgb(0.4, 0.8, 1.0),
+        overrides: || ParamOverrides {
+            ambient_temperature: Some(-20.0),
+            cooling_rate: Some(0.05),
+            ..default()
+        },
+    },
+    ZoneKind {
+        name: "Windy",
+        color: Color::srgb(0.8, 0.85, 0.7),
+        overrides: || ParamOverrides {
+            wind: Some(2.0),
+            ..default()
+        },
+    },
+    ZoneKind {
+        name: "No reactions",
+        color: Color::srgb(0.7, 0.7, 0.7),
+        overrides: || ParamOverrides {
+            boil_chance: Some(0.0),
+            melt_chance: Some(0.0),
+            ..default()
+        },
+    },
+];
+
+// --- RESOURCES ---
+
+// Z marks a zone's first corner and then its opposite one, Shift+Z picks the kind and Delete
+// removes the zone under the cursor.
+#[derive(Resource, Default)]
+struct ZoneTool {
+    kind: usize,
+    corner: Option<CellPos>,
+    // Shown at the bottom of the screen while the tool is in use.
+    hint: String,
+}
+
+// --- COMPONENTS ---
+
+#[derive(Component)]
+struct ZoneLabel;
+
+// --- SYSTEMS ---
+
+fn spawn_zone_label(mut commands: Commands) {
+    commands.spawn((
+        ZoneLabel,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(24.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        Text::default(),
+        TextLayout::new_with_justify(JustifyText::Center),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    ));
+}
+
+fn place_zones(
+    keys: Res<ButtonInput<KeyCode>>,
+    rules: Res<PaintRules>,
+    view: WorldView,
+    mut tool: ResMut<ZoneTool>,
+    mut grid: ResMut<SimulationGrid>,
+) {
+    // Ctrl+Z and Ctrl+Shift+Z are undo and redo.
+    if ctrl_held(&keys) {
+        return;
+    }
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::KeyZ) && shift {
+        tool.kind = (tool.kind + 1) % ZONE_KINDS.len();
+        tool.hint = format!("Next zone: {} (Z to place)", ZONE_KINDS[tool.kind].name);
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        tool.corner = None;
+        tool.hint.clear();
+    }
+    let placing = keys.just_pressed(KeyCode::KeyZ);
+    let removing = keys.just_pressed(KeyCode::Delete);
+    if !placing && !removing {
+        return;
+    }
+    // A level's zones are part of the puzzle.
+    if rules.protect_world {
+        return;
+    }
+    let Some(cell) = view.cursor_cell() else { return };
+
+    if removing {
+        if let Some(index) = grid.zones().iter().rposition(|zone| zone.contains(cell.x, cell.y)) {
+            let zone = grid.remove_zone(index);
+            info!("Removed zone '{}'", zone.name);
+        }
+        return;
+    }
+    let kind = &ZONE_KINDS[tool.kind];
+    let Some(corner) = tool.corner.take() else {
+        tool.corner = Some(cell);
+        tool.hint = format!(
+            "Placing a '{}' zone: press Z at the opposite corner (Esc cancels)",
+            kind.name
+        );
+        return;
+    };
+    tool.hint.clear();
+    let (min, max) = (corner.min(*cell), corner.max(*cell));
+    grid.add_zone(ParamZone {
+        name: kind.name.to_string(),
+        min: min.into(),
+        max: max.into(),
+        overrides: (kind.overrides)(),
+    });
+    info!("Added a '{}' zone from {:?} to {:?}", kind.name, min, max);
+}
+
+fn draw_zones(
+    tool: Res<ZoneTool>,
+    grid: Res<SimulationGrid>,
+    view: WorldView,
+    mut gizmos: Gizmos,
+) {
+    let mut outline = |min: IVec2, max: IVec2, color: Color| {
+        let rect = view.cells_to_world(CellPos(min), CellPos(max));
+        gizmos.rect_2d(Isometry2d::from_translation(rect.center()), rect.size(), color);
+    };
+    for zone in grid.zones() {
+        let color = ZONE_KINDS
+            .iter()
+            .find(|kind| kind.name == zone.name)
+            .map_or(Color::WHITE, |kind| kind.color);
+        outline(zone.min.into(), zone.max.into(), color.with_alpha(0.6));
+    }
+
+    // The zone being placed, from its first corner to the cursor.
+    let Some(corner) = tool.corner else { return };
+    let Some(cell) = view.cursor_cell() else { return };
+    outline(corner.min(*cell), corner.max(*cell), ZONE_KINDS[tool.kind].color);
+}
+
+fn update_zone_label(tool: Res<ZoneTool>, mut q_label: Query<&mut Text, With<ZoneLabel>>) {
+    if !tool.is_changed() {
+        return;
+    }
+    let Ok(mut label) = q_label.single_mut() else { return };
+    if label.0 != tool.hint {
+        label.0.clone_from(&tool.hint);
+    }
+}